@@ -0,0 +1,41 @@
+//! Records the git commit the binary is built from as `OXBIO_GIT_HASH`,
+//! for the reproducibility manifest of analysis runs. Builds outside a git
+//! checkout (e.g. the Docker image) leave it unset.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Trimmed stdout of a successful `git` command
+fn git(args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|out| out.trim().to_string())
+}
+
+fn rerun_if_changed(path: &Path) {
+    // A missing path would rerun the script on every build
+    if path.exists() {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
+
+fn main() {
+    let Some(hash) = git(&["rev-parse", "--short=12", "HEAD"]) else {
+        return;
+    };
+    // Asked of git rather than assumed, since `.git` is a file in worktrees
+    // and submodules: HEAD lives in the per-worktree directory, branch refs
+    // in the common one, loose under refs/heads (watched as a directory, so
+    // a ref written after `git gc` counts) or in packed-refs
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]).map(PathBuf::from) {
+        let common_dir = git(&["rev-parse", "--git-common-dir"]).map_or_else(|| git_dir.clone(), PathBuf::from);
+        rerun_if_changed(&git_dir.join("HEAD"));
+        rerun_if_changed(&common_dir.join("refs").join("heads"));
+        rerun_if_changed(&common_dir.join("packed-refs"));
+    }
+    println!("cargo:rustc-env=OXBIO_GIT_HASH={}", hash);
+}
@@ -0,0 +1,35 @@
+-- Persistent background job queue
+--
+-- Workers claim rows with `FOR UPDATE SKIP LOCKED` so several processes can
+-- poll the same table without handing out a job twice. A claimed job stays
+-- invisible to other workers until `locked_until`; if the worker dies before
+-- completing it, the job becomes claimable again once that deadline passes.
+-- Jobs that exhaust their attempts are moved to the 'dead' status (dead letter).
+
+CREATE TABLE IF NOT EXISTS jobs (
+  id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+  job_type TEXT NOT NULL CHECK (job_type IN ('analysis', 'literature_batch', 'document_ingestion')),
+  payload JSONB NOT NULL DEFAULT '{}',
+  status TEXT NOT NULL DEFAULT 'queued' CHECK (status IN ('queued', 'running', 'completed', 'failed', 'dead')),
+  attempts INTEGER NOT NULL DEFAULT 0,
+  max_attempts INTEGER NOT NULL DEFAULT 3,
+  run_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  locked_by TEXT,
+  locked_until TIMESTAMP WITH TIME ZONE,
+  result JSONB,
+  last_error TEXT,
+  created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+  updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+  completed_at TIMESTAMP WITH TIME ZONE
+);
+
+-- Claim query scans queued jobs by run_at, and expired running jobs by locked_until
+CREATE INDEX IF NOT EXISTS idx_jobs_claimable ON jobs(status, run_at);
+CREATE INDEX IF NOT EXISTS idx_jobs_locked_until ON jobs(locked_until) WHERE status = 'running';
+CREATE INDEX IF NOT EXISTS idx_jobs_job_type ON jobs(job_type);
+CREATE INDEX IF NOT EXISTS idx_jobs_created_at ON jobs(created_at DESC);
+
+CREATE TRIGGER update_jobs_updated_at
+  BEFORE UPDATE ON jobs
+  FOR EACH ROW
+  EXECUTE FUNCTION update_updated_at_column();
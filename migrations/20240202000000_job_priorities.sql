@@ -0,0 +1,15 @@
+-- Per-job retry policy, priority lanes, and progress reporting for the job queue
+
+ALTER TABLE jobs
+  ADD COLUMN IF NOT EXISTS priority TEXT NOT NULL DEFAULT 'batch' CHECK (priority IN ('interactive', 'batch')),
+  ADD COLUMN IF NOT EXISTS backoff_base_secs INTEGER NOT NULL DEFAULT 30,
+  ADD COLUMN IF NOT EXISTS backoff_max_secs INTEGER NOT NULL DEFAULT 3600,
+  ADD COLUMN IF NOT EXISTS progress REAL NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS progress_message TEXT;
+
+-- Interactive jobs are claimed ahead of batch jobs
+DROP INDEX IF EXISTS idx_jobs_claimable;
+CREATE INDEX IF NOT EXISTS idx_jobs_claimable ON jobs(status, priority, run_at);
+
+-- Progress watchers poll for rows changed since their last check
+CREATE INDEX IF NOT EXISTS idx_jobs_updated_at ON jobs(updated_at);
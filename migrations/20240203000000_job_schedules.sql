@@ -0,0 +1,47 @@
+-- Recurring job schedules and literature alert bookkeeping
+
+ALTER TABLE jobs DROP CONSTRAINT IF EXISTS jobs_job_type_check;
+ALTER TABLE jobs ADD CONSTRAINT jobs_job_type_check
+  CHECK (job_type IN ('analysis', 'literature_batch', 'document_ingestion', 'literature_alert'));
+
+CREATE TABLE IF NOT EXISTS job_schedules (
+  id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE,
+  job_type TEXT NOT NULL,
+  payload JSONB NOT NULL DEFAULT '{}',
+  -- Schedule expression as entered, e.g. '@weekly' or 'every 3d'
+  schedule TEXT NOT NULL,
+  interval_secs BIGINT NOT NULL CHECK (interval_secs > 0),
+  priority TEXT NOT NULL DEFAULT 'batch' CHECK (priority IN ('interactive', 'batch')),
+  enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  next_run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  last_run_at TIMESTAMPTZ,
+  last_job_id UUID REFERENCES jobs(id) ON DELETE SET NULL,
+  created_at TIMESTAMPTZ DEFAULT NOW(),
+  updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_job_schedules_due ON job_schedules(enabled, next_run_at);
+
+CREATE TRIGGER update_job_schedules_updated_at
+  BEFORE UPDATE ON job_schedules
+  FOR EACH ROW
+  EXECUTE FUNCTION update_updated_at_column();
+
+-- Papers seen by a literature alert; new rows are surfaced to the user once
+CREATE TABLE IF NOT EXISTS literature_alert_papers (
+  id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+  schedule_id UUID REFERENCES job_schedules(id) ON DELETE CASCADE,
+  paper_key TEXT NOT NULL,
+  biomarker TEXT NOT NULL,
+  title TEXT NOT NULL,
+  year INTEGER,
+  doi TEXT,
+  url TEXT,
+  first_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  notified_at TIMESTAMPTZ,
+  UNIQUE (schedule_id, paper_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_literature_alert_papers_pending
+  ON literature_alert_papers(notified_at) WHERE notified_at IS NULL;
@@ -0,0 +1,16 @@
+-- Wake idle workers (in any process or container) when a job becomes claimable
+
+CREATE OR REPLACE FUNCTION notify_job_queued()
+RETURNS TRIGGER AS $$
+BEGIN
+  IF NEW.status = 'queued' THEN
+    PERFORM pg_notify('jobs_queued', NEW.priority);
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notify_jobs_queued
+  AFTER INSERT OR UPDATE OF status ON jobs
+  FOR EACH ROW
+  EXECUTE FUNCTION notify_job_queued();
@@ -0,0 +1,42 @@
+-- Usage metering and billing records
+--
+-- Every billable unit (LLM tokens, search queries, analysis CPU time, stored
+-- bytes) is appended to usage_events. billing_records roll events up per
+-- account and period and can be linked to the x402 payment that settled them.
+
+CREATE TABLE IF NOT EXISTS usage_events (
+  id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+  -- User id, x402 payer address, or 'local' for the single-user TUI
+  account TEXT NOT NULL DEFAULT 'local',
+  metric TEXT NOT NULL CHECK (metric IN ('llm_tokens', 'search_queries', 'analysis_cpu_ms', 'storage_bytes')),
+  quantity BIGINT NOT NULL CHECK (quantity >= 0),
+  cost_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+  -- e.g. {"model": "gpt-4o"} or {"dataset_id": "..."}
+  detail JSONB NOT NULL DEFAULT '{}',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_usage_events_account_time ON usage_events(account, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_usage_events_metric ON usage_events(metric);
+
+CREATE TABLE IF NOT EXISTS billing_records (
+  id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+  account TEXT NOT NULL,
+  period_start TIMESTAMPTZ NOT NULL,
+  period_end TIMESTAMPTZ NOT NULL,
+  metric TEXT NOT NULL,
+  quantity BIGINT NOT NULL,
+  amount_usd DOUBLE PRECISION NOT NULL,
+  status TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'paid', 'void')),
+  payment_id UUID REFERENCES x402_payments(id) ON DELETE SET NULL,
+  created_at TIMESTAMPTZ DEFAULT NOW(),
+  updated_at TIMESTAMPTZ DEFAULT NOW(),
+  UNIQUE (account, period_start, period_end, metric)
+);
+
+CREATE INDEX IF NOT EXISTS idx_billing_records_account ON billing_records(account, period_start DESC);
+
+CREATE TRIGGER update_billing_records_updated_at
+  BEFORE UPDATE ON billing_records
+  FOR EACH ROW
+  EXECUTE FUNCTION update_updated_at_column();
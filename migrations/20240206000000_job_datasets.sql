@@ -0,0 +1,12 @@
+-- Datasets of queued analyses, for workers on other machines
+--
+-- A dataset is stored once, keyed by the SHA-256 of its bytes, and analysis
+-- job payloads only carry the key. Rows no unfinished job refers to are
+-- pruned once they are an hour old; storing a dataset again renews it.
+
+CREATE TABLE IF NOT EXISTS job_datasets (
+  sha256 TEXT PRIMARY KEY,
+  filename TEXT NOT NULL,
+  content BYTEA NOT NULL,
+  stored_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,376 @@
+//! Deep Research Loop
+//!
+//! Runs planning → literature search → synthesis for several iterations.
+//! Each iteration hands the planner what has been learned so far (objective,
+//! key insights and the searches already run, as conversation state), runs
+//! the follow-up searches it proposes and merges their sources and insights.
+//! The loop stops after `depth` iterations, or earlier once the planner only
+//! proposes searches that were already run or an iteration turns up no new
+//! sources or insights. The reply agent then writes the final report from
+//! every search result.
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::agents::literature::{LiteratureAgent, LiteratureResult, SourceReference};
+use crate::agents::planning::{PlanningAgent, PlanningResult};
+use crate::agents::reply::{ReplyAgent, ReplyMode};
+use crate::models::{ConversationState, ConversationStateValues, PlanTask};
+use crate::session::LiveConfig;
+use crate::types::{AppError, AppResult};
+use crate::utils::cancel::CancellationToken;
+
+/// How far a deep research run goes
+#[derive(Debug, Clone, Copy)]
+pub struct DeepResearchOptions {
+    /// Maximum planning/search/synthesis iterations
+    pub depth: u32,
+    /// Searches run per iteration
+    pub max_tasks: usize,
+}
+
+impl Default for DeepResearchOptions {
+    fn default() -> Self {
+        Self {
+            depth: 3,
+            max_tasks: 3,
+        }
+    }
+}
+
+/// Progress of a run, reported as it happens
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ResearchEvent {
+    IterationStarted { iteration: u32, depth: u32 },
+    Planned { iteration: u32, objective: String, tasks: Vec<String> },
+    Searched { iteration: u32, task: String, sources: usize },
+    SearchFailed { iteration: u32, task: String, error: String },
+    Synthesized { iteration: u32, new_sources: usize, new_insights: usize, total_sources: usize },
+    Stopped { iteration: u32, reason: String },
+    /// The final report is being written
+    Writing,
+}
+
+impl ResearchEvent {
+    /// Iteration the event belongs to; the final report counts as the last
+    pub fn iteration(&self) -> Option<u32> {
+        match self {
+            Self::IterationStarted { iteration, .. }
+            | Self::Planned { iteration, .. }
+            | Self::Searched { iteration, .. }
+            | Self::SearchFailed { iteration, .. }
+            | Self::Synthesized { iteration, .. }
+            | Self::Stopped { iteration, .. } => Some(*iteration),
+            Self::Writing => None,
+        }
+    }
+}
+
+/// What one iteration searched and found
+#[derive(Debug, Clone, Serialize)]
+pub struct ResearchIteration {
+    pub iteration: u32,
+    /// Planner's objective for this iteration
+    pub objective: String,
+    /// Search objectives run
+    pub tasks: Vec<String>,
+    pub new_sources: usize,
+    /// Insights not seen in earlier iterations
+    pub key_insights: Vec<String>,
+}
+
+/// Result of a deep research run
+#[derive(Debug, Clone, Serialize)]
+pub struct DeepResearchReport {
+    pub question: String,
+    /// Objective of the last iteration
+    pub objective: String,
+    pub depth: u32,
+    pub iterations: Vec<ResearchIteration>,
+    /// Why the loop ended
+    pub stop_reason: String,
+    /// Synthesized report (Markdown)
+    pub report: String,
+    pub key_insights: Vec<String>,
+    /// Every distinct source cited by the searches
+    pub sources: Vec<SourceReference>,
+}
+
+/// Research `question` over up to `options.depth` iterations, calling
+/// `on_event` as the run progresses; fails with `AppError::Cancelled` once
+/// `cancel` fires. Settings saved while it runs apply from the next
+/// iteration.
+pub async fn run<F>(
+    question: &str,
+    options: DeepResearchOptions,
+    live_config: &LiveConfig,
+    cancel: &CancellationToken,
+    mut on_event: F,
+) -> AppResult<DeepResearchReport>
+where
+    F: FnMut(ResearchEvent) + Send,
+{
+    let depth = options.depth.max(1);
+    info!(question_len = question.len(), depth, "Starting deep research");
+
+    let mut state = initial_state(question);
+    let mut searched: HashSet<String> = HashSet::new();
+    let mut results: Vec<LiteratureResult> = Vec::new();
+    let mut sources: Vec<SourceReference> = Vec::new();
+    let mut insights: Vec<String> = Vec::new();
+    let mut iterations: Vec<ResearchIteration> = Vec::new();
+    let mut last_plan: Option<PlanningResult> = None;
+    let mut stop_reason = format!("Reached depth {}", depth);
+    let mut settings = live_config.subscribe();
+    let mut config = settings.borrow_and_update().clone();
+
+    for iteration in 1..=depth {
+        if settings.has_changed().unwrap_or(false) {
+            config = settings.borrow_and_update().clone();
+            info!(iteration, "Settings changed, applying them from this iteration");
+        }
+        let config = config.as_ref();
+        on_event(ResearchEvent::IterationStarted { iteration, depth });
+        let plan = PlanningAgent::generate_plan(question, Some(&state), config, cancel).await?;
+        let tasks = new_tasks(&plan, &mut searched, options.max_tasks);
+        on_event(ResearchEvent::Planned {
+            iteration,
+            objective: plan.current_objective.clone(),
+            tasks: tasks.iter().map(|t| t.objective.clone()).collect(),
+        });
+        if tasks.is_empty() {
+            stop_reason = "The planner proposed no new searches".to_string();
+            on_event(ResearchEvent::Stopped { iteration, reason: stop_reason.clone() });
+            break;
+        }
+
+        let mut summary = ResearchIteration {
+            iteration,
+            objective: plan.current_objective.clone(),
+            tasks: Vec::new(),
+            new_sources: 0,
+            key_insights: Vec::new(),
+        };
+        for mut task in tasks {
+            task.level = Some(iteration as i32);
+            summary.tasks.push(task.objective.clone());
+            match LiteratureAgent::execute_task(&task, config, cancel).await {
+                Ok(result) => {
+                    on_event(ResearchEvent::Searched {
+                        iteration,
+                        task: task.objective.clone(),
+                        sources: result.sources.len(),
+                    });
+                    for source in &result.sources {
+                        if !sources.iter().any(|known| same_source(known, source)) {
+                            sources.push(source.clone());
+                            summary.new_sources += 1;
+                        }
+                    }
+                    for insight in &result.key_insights {
+                        if !insights.contains(insight) {
+                            insights.push(insight.clone());
+                            summary.key_insights.push(insight.clone());
+                        }
+                    }
+                    task.output = Some(result.findings.clone());
+                    results.push(result);
+                }
+                Err(AppError::Cancelled) => return Err(AppError::Cancelled),
+                Err(e) => {
+                    warn!(error = %e, task = %task.objective, "Deep research search failed");
+                    on_event(ResearchEvent::SearchFailed {
+                        iteration,
+                        task: task.objective.clone(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+            state.values.plan.get_or_insert_with(Vec::new).push(task);
+        }
+
+        state.values.current_objective = Some(plan.current_objective.clone());
+        state.values.current_level = Some(iteration as i32);
+        state.values.key_insights = Some(insights.clone());
+        on_event(ResearchEvent::Synthesized {
+            iteration,
+            new_sources: summary.new_sources,
+            new_insights: summary.key_insights.len(),
+            total_sources: sources.len(),
+        });
+        let exhausted = summary.new_sources == 0 && summary.key_insights.is_empty();
+        iterations.push(summary);
+        last_plan = Some(plan);
+        if exhausted && iteration < depth {
+            stop_reason = "The last iteration found no new sources or insights".to_string();
+            on_event(ResearchEvent::Stopped { iteration, reason: stop_reason.clone() });
+            break;
+        }
+    }
+
+    on_event(ResearchEvent::Writing);
+    let report = cancel
+        .run_until_cancelled(ReplyAgent::generate_response(
+            question,
+            last_plan.as_ref(),
+            &results,
+            None,
+            ReplyMode::Report,
+            &live_config.current(),
+        ))
+        .await
+        .unwrap_or(Err(AppError::Cancelled))?;
+    info!(
+        iterations = iterations.len(),
+        sources = sources.len(),
+        insights = insights.len(),
+        "Deep research complete"
+    );
+
+    Ok(DeepResearchReport {
+        question: question.to_string(),
+        objective: last_plan.map(|p| p.current_objective).unwrap_or_else(|| question.to_string()),
+        depth,
+        iterations,
+        stop_reason,
+        report,
+        key_insights: insights,
+        sources,
+    })
+}
+
+fn initial_state(question: &str) -> ConversationState {
+    ConversationState {
+        id: None,
+        values: ConversationStateValues {
+            objective: question.to_string(),
+            conversation_title: None,
+            current_objective: None,
+            current_level: Some(0),
+            key_insights: None,
+            methodology: None,
+            current_hypothesis: None,
+            discoveries: None,
+            plan: None,
+            suggested_next_steps: None,
+            research_mode: Some("fully-autonomous".to_string()),
+            uploaded_datasets: None,
+            artifacts: None,
+            project_context: None,
+            reply_mode: None,
+        },
+    }
+}
+
+/// Literature tasks of `plan` not searched yet, at most `max`
+fn new_tasks(plan: &PlanningResult, searched: &mut HashSet<String>, max: usize) -> Vec<PlanTask> {
+    let mut tasks = Vec::new();
+    for task in plan.plan.iter().filter(|t| t.task_type == "LITERATURE") {
+        if tasks.len() >= max {
+            break;
+        }
+        let key = task.objective.trim().to_lowercase();
+        if !key.is_empty() && searched.insert(key) {
+            tasks.push(task.clone());
+        }
+    }
+    tasks
+}
+
+/// Same DOI, URL or (case-insensitive) title
+fn same_source(a: &SourceReference, b: &SourceReference) -> bool {
+    let same = |x: &Option<String>, y: &Option<String>| {
+        matches!((x, y), (Some(x), Some(y)) if x.eq_ignore_ascii_case(y))
+    };
+    same(&a.doi, &b.doi) || same(&a.url, &b.url) || a.title.trim().eq_ignore_ascii_case(b.title.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(title: &str, doi: Option<&str>) -> SourceReference {
+        SourceReference {
+            title: title.to_string(),
+            authors: None,
+            year: None,
+            doi: doi.map(str::to_string),
+            url: None,
+            summary: String::new(),
+            quality: None,
+        }
+    }
+
+    fn literature_task(objective: &str) -> PlanTask {
+        PlanTask {
+            id: None,
+            job_id: None,
+            objective: objective.to_string(),
+            datasets: vec![],
+            task_type: "LITERATURE".to_string(),
+            level: None,
+            start: None,
+            end: None,
+            output: None,
+            artifacts: None,
+        }
+    }
+
+    #[test]
+    fn test_new_tasks_skips_searched_objectives() {
+        let plan = PlanningResult {
+            current_objective: "Senolytics".to_string(),
+            plan: vec![
+                literature_task("Senolytic drugs in mice"),
+                literature_task("senolytic drugs in mice "),
+                literature_task("Senolytic clinical trials"),
+                literature_task("Dasatinib and quercetin"),
+            ],
+        };
+        let mut searched = HashSet::new();
+        let first: Vec<String> = new_tasks(&plan, &mut searched, 2).into_iter().map(|t| t.objective).collect();
+        assert_eq!(first, ["Senolytic drugs in mice", "Senolytic clinical trials"]);
+        let second: Vec<String> = new_tasks(&plan, &mut searched, 2).into_iter().map(|t| t.objective).collect();
+        assert_eq!(second, ["Dasatinib and quercetin"]);
+        assert!(new_tasks(&plan, &mut searched, 2).is_empty());
+
+        assert!(same_source(&source("A", Some("10.1/X")), &source("B", Some("10.1/x"))));
+        assert!(same_source(&source("Rapamycin and aging", None), &source("rapamycin and aging ", None)));
+        assert!(!same_source(&source("A", Some("10.1/x")), &source("B", Some("10.1/y"))));
+    }
+
+    #[tokio::test]
+    async fn test_run_offline_stops_when_nothing_new() {
+        let mut config = crate::config::Config::from_env().unwrap();
+        config.search.serpapi_key.clear();
+        config.search.pubmed_enabled = false;
+        config.search.semantic_scholar_enabled = false;
+        for key in [
+            &mut config.llm.openai_api_key,
+            &mut config.llm.anthropic_api_key,
+            &mut config.llm.google_api_key,
+            &mut config.llm.openrouter_api_key,
+            &mut config.llm.groq_api_key,
+        ] {
+            key.clear();
+        }
+
+        let mut events = Vec::new();
+        let options = DeepResearchOptions { depth: 3, max_tasks: 2 };
+        let cancel = CancellationToken::new();
+        let report = run("Effects of rapamycin on lifespan", options, &config.into(), &cancel, |e| events.push(e))
+            .await
+            .unwrap();
+
+        // The fallback planner repeats its only search, so the second iteration stops
+        assert_eq!(report.iterations.len(), 1);
+        assert_eq!(report.stop_reason, "The planner proposed no new searches");
+        assert!(!report.report.is_empty());
+        assert!(matches!(events.first(), Some(ResearchEvent::IterationStarted { iteration: 1, depth: 3 })));
+        assert!(matches!(events.last(), Some(ResearchEvent::Writing)));
+        assert!(events.iter().any(|e| matches!(e, ResearchEvent::Stopped { iteration: 2, .. })));
+    }
+}
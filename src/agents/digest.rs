@@ -0,0 +1,202 @@
+//! Literature context compression
+//!
+//! When the findings of every literature search, as formatted for the reply,
+//! would not fit the reply model's context window, they are summarised
+//! map-reduce style instead of being cut off at the end or rejected by the
+//! provider. Each search result is first summarised on its own, to its share
+//! of the window (map); if the summaries together still do not fit, they are
+//! merged into a single digest (reduce). Sources are kept as they are, so the
+//! reply can still cite them.
+
+use std::borrow::Cow;
+
+use futures::future::join_all;
+use tracing::{info, warn};
+
+use crate::agents::literature::{LiteratureAgent, LiteratureResult, SourceReference};
+use crate::config::ResolvedLlm;
+use crate::llm::capabilities::CHARS_PER_TOKEN;
+use crate::llm::provider::LLM;
+use crate::types::{LLMMessage, LLMRequest};
+
+/// Shortest summary asked for, in tokens
+const MIN_SUMMARY_TOKENS: u32 = 256;
+
+/// Rough characters per word, for the length given in the prompts
+const CHARS_PER_WORD: usize = 6;
+
+const SYSTEM_PROMPT: &str = "You condense scientific literature findings without losing the evidence they rest on.";
+
+/// `results`, summarised when their formatted findings are longer than
+/// `budget` characters
+pub async fn fit<'a>(
+    llm: &LLM,
+    route: &ResolvedLlm,
+    results: &'a [LiteratureResult],
+    budget: usize,
+) -> Cow<'a, [LiteratureResult]> {
+    let chars = LiteratureAgent::format_for_reply(results).len();
+    if results.is_empty() || chars <= budget {
+        return Cow::Borrowed(results);
+    }
+    info!(chars, budget, results = results.len(), "Literature context exceeds the reply window, summarising");
+
+    let share = budget / results.len();
+    let mapped: Vec<LiteratureResult> = join_all(results.iter().map(|result| summarize(llm, route, result, share))).await;
+    let chars = LiteratureAgent::format_for_reply(&mapped).len();
+    if chars <= budget {
+        return Cow::Owned(mapped);
+    }
+    info!(chars, budget, "Summaries exceed the reply window, merging them into one digest");
+    Cow::Owned(vec![merge(llm, route, &mapped, budget).await])
+}
+
+/// Characters of `result` as formatted for the reply, besides its findings
+fn overhead(result: &LiteratureResult) -> usize {
+    let bare = LiteratureResult {
+        findings: String::new(),
+        key_insights: Vec::new(),
+        ..result.clone()
+    };
+    LiteratureAgent::format_for_reply(std::slice::from_ref(&bare)).len()
+}
+
+/// `result` with its findings and key insights summarised into about
+/// `chars` characters of formatted text
+async fn summarize(llm: &LLM, route: &ResolvedLlm, result: &LiteratureResult, chars: usize) -> LiteratureResult {
+    let target = chars.saturating_sub(overhead(result));
+    let insights: String = result.key_insights.iter().map(|insight| format!("- {}\n", insight)).collect();
+    let prompt = format!(
+        "Summarise the findings of this literature search in at most {words} words, for a research assistant \
+         answering a question from them. Keep quantitative results, named genes, compounds, cohorts and models, and \
+         which study reports each; drop background and repetition. Reply with the summary only.\n\n\
+         RESEARCH OBJECTIVE: {objective}\n\nFINDINGS:\n{findings}\n\nKEY INSIGHTS:\n{insights}",
+        words = (target / CHARS_PER_WORD).max(1),
+        objective = result.objective,
+        findings = result.findings,
+        insights = insights,
+    );
+    let findings = complete(llm, route, &prompt, target)
+        .await
+        .unwrap_or_else(|| truncate(&result.findings, target));
+    LiteratureResult {
+        findings,
+        key_insights: Vec::new(),
+        ..result.clone()
+    }
+}
+
+/// One result merging the summaries of `results` into about `budget`
+/// characters, with all their sources
+async fn merge(llm: &LLM, route: &ResolvedLlm, results: &[LiteratureResult], budget: usize) -> LiteratureResult {
+    let mut sources: Vec<SourceReference> = Vec::new();
+    for source in results.iter().flat_map(|r| &r.sources) {
+        if !sources.iter().any(|s| s.title == source.title) {
+            sources.push(source.clone());
+        }
+    }
+    let mut digest = LiteratureResult {
+        task_id: "digest".to_string(),
+        objective: format!("Digest of {} literature searches", results.len()),
+        findings: String::new(),
+        sources,
+        key_insights: Vec::new(),
+        queries: Vec::new(),
+    };
+    let target = budget.saturating_sub(overhead(&digest));
+    let summaries: String = results
+        .iter()
+        .map(|r| format!("### {}\n{}\n\n", r.objective, r.findings))
+        .collect();
+    let prompt = format!(
+        "Merge these summaries of literature searches into one digest of at most {words} words, for a research \
+         assistant answering a question from them. Keep the strongest evidence for each objective, quantitative \
+         results and which study reports each; merge findings the searches share. Reply with the digest only.\n\n\
+         {summaries}",
+        words = (target / CHARS_PER_WORD).max(1),
+        summaries = summaries,
+    );
+    digest.findings = complete(llm, route, &prompt, target)
+        .await
+        .unwrap_or_else(|| truncate(&summaries, target));
+    digest
+}
+
+/// Completion of `prompt` limited to about `chars` characters; `None` when
+/// the call fails or returns nothing
+async fn complete(llm: &LLM, route: &ResolvedLlm, prompt: &str, chars: usize) -> Option<String> {
+    let capabilities = route.capabilities();
+    let max_tokens = ((chars / CHARS_PER_TOKEN) as u32).max(MIN_SUMMARY_TOKENS).min(capabilities.max_output);
+    let request = LLMRequest {
+        provider: route.provider.clone(),
+        model: route.model.clone(),
+        messages: vec![LLMMessage::user(capabilities.fit_prompt(prompt, max_tokens).into_owned())],
+        max_tokens: Some(max_tokens),
+        temperature: Some(route.temperature_or(0.2)),
+        system_instruction: Some(SYSTEM_PROMPT.to_string()),
+        system_blocks: Vec::new(),
+    };
+    match llm.create_chat_completion(&request).await {
+        Ok(response) if !response.content.trim().is_empty() => Some(truncate(response.content.trim(), chars)),
+        Ok(_) => None,
+        Err(e) => {
+            warn!(error = %e, "Summarising literature context failed, cutting it instead");
+            None
+        }
+    }
+}
+
+/// `text` cut to at most `chars` bytes on a character boundary
+fn truncate(text: &str, chars: usize) -> String {
+    let mut end = chars.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::provider::LLMProviderConfig;
+
+    #[tokio::test]
+    async fn test_map_then_reduce() {
+        let route = ResolvedLlm {
+            provider: "mock".to_string(),
+            model: "mock".to_string(),
+            api_key: String::new(),
+            temperature: None,
+            max_tokens: None,
+        };
+        let llm = LLM::new(LLMProviderConfig {
+            name: "mock".to_string(),
+            api_key: String::new(),
+            network: Default::default(),
+            openrouter: Default::default(),
+            rate_limit: Default::default(),
+        });
+        let result = |objective: &str| LiteratureResult {
+            task_id: objective.to_string(),
+            objective: objective.to_string(),
+            findings: "Expression of CDKN2A rises with age. ".repeat(200),
+            sources: Vec::new(),
+            key_insights: vec!["CDKN2A tracks age".to_string()],
+            queries: Vec::new(),
+        };
+        let results = [result("Senescence markers"), result("Epigenetic clocks")];
+
+        assert!(matches!(fit(&llm, &route, &results, 100_000).await, Cow::Borrowed(_)));
+
+        // The mock's short replies fit once each result is summarised
+        let mapped = fit(&llm, &route, &results, 2_000).await;
+        assert_eq!(mapped.len(), 2);
+        assert!(mapped.iter().all(|r| r.findings.starts_with("Mock response") && r.key_insights.is_empty()));
+        assert!(LiteratureAgent::format_for_reply(&mapped).len() <= 2_000);
+
+        // Too short for both summaries: one merged digest
+        let merged = fit(&llm, &route, &results, 100).await;
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].objective, "Digest of 2 literature searches");
+    }
+}
@@ -0,0 +1,114 @@
+//! Drafting Agent
+//!
+//! Revises the template manuscript drafts produced by the Draft 1-3 workflow
+//! stages. The template already holds the analysis results, plan and
+//! literature; the LLM only rewrites it into readable prose and works in
+//! researcher feedback. Without an LLM (or if the call fails) the template
+//! draft is returned unchanged, as it is when the revision misquotes a
+//! computed statistic.
+
+use crate::analysis::findings::Findings;
+use crate::config::LlmTask;
+use crate::llm::provider::{LLMProviderConfig, LLM};
+use crate::types::{LLMMessage, LLMRequest};
+use tracing::{info, warn};
+
+pub struct DraftingAgent;
+
+/// A revised draft and the model that wrote it
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub text: String,
+    /// "provider/model", or `None` when the template draft was kept
+    pub model: Option<String>,
+}
+
+impl Revision {
+    fn template(text: String) -> Self {
+        Self { text, model: None }
+    }
+}
+
+impl DraftingAgent {
+    /// Revise `draft` (version 1-3), incorporating `feedback`. Statistics the
+    /// revision quotes for the markers in `findings` must match them.
+    pub async fn revise(
+        version: usize,
+        draft: String,
+        feedback: &[String],
+        findings: Option<&Findings>,
+        config: &crate::config::Config,
+    ) -> Revision {
+        let llm_route = match config.llm_for(LlmTask::Drafting) {
+            Some(route) => route,
+            None => return Revision::template(draft),
+        };
+
+        let llm = LLM::new(LLMProviderConfig {
+            name: llm_route.provider.clone(),
+            api_key: llm_route.api_key.clone(),
+            network: config.network.clone(),
+            openrouter: config.llm.openrouter.clone(),
+            rate_limit: config.llm.rate_limit(&llm_route.provider),
+        });
+
+        // A cut-off draft would come back shortened, so keep the template
+        let max_tokens = llm_route.output_tokens(Some(4096));
+        let prompt = Self::create_prompt(version, &draft, feedback);
+        if !llm_route.capabilities().fits(&prompt, max_tokens) {
+            warn!(version, model = %llm_route.model, "Draft exceeds the model's context window, keeping template draft");
+            return Revision::template(draft);
+        }
+
+        let request = LLMRequest {
+            provider: llm_route.provider.clone(),
+            model: llm_route.model.clone(),
+            messages: vec![LLMMessage::user(&prompt)],
+            max_tokens: Some(max_tokens),
+            temperature: Some(llm_route.temperature_or(0.4)),
+            system_instruction: Some(
+                "You are a scientific writing assistant. Never invent results, \
+                 numbers or citations that are not in the draft."
+                    .to_string(),
+            ),
+            system_blocks: Vec::new(),
+        };
+
+        match llm.create_chat_completion(&request).await {
+            Ok(response) if !response.content.trim().is_empty() => {
+                let misquoted = findings.map(|f| f.misquoted(&response.content)).unwrap_or_default();
+                if !misquoted.is_empty() {
+                    warn!(version, misquoted = ?misquoted, "Revised draft misquotes computed statistics, keeping template draft");
+                    return Revision::template(draft);
+                }
+                info!(version, response_len = response.content.len(), "Draft revised by LLM");
+                Revision {
+                    text: response.content,
+                    model: Some(format!("{}/{}", llm_route.provider, llm_route.model)),
+                }
+            }
+            Ok(_) => Revision::template(draft),
+            Err(e) => {
+                warn!(error = %e, version, "Draft revision failed, keeping template draft");
+                Revision::template(draft)
+            }
+        }
+    }
+
+    fn create_prompt(version: usize, draft: &str, feedback: &[String]) -> String {
+        let feedback = if feedback.is_empty() {
+            "None yet.".to_string()
+        } else {
+            feedback
+                .iter()
+                .map(|f| format!("- {}", f))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        format!(
+            "Rewrite the following manuscript draft as Draft {version}. Keep its section \
+             structure and every reported statistic. Address the researcher feedback.\n\n\
+             Researcher feedback:\n{feedback}\n\nDraft:\n{draft}"
+        )
+    }
+}
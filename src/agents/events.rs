@@ -0,0 +1,118 @@
+//! Research pipeline progress events
+//!
+//! [`execute_research_pipeline`](super::execute_research_pipeline) reports
+//! what it is doing as typed [`PipelineEvent`]s. The TUI turns them into
+//! status-bar updates and chat messages, `POST /api/chat/stream` sends them
+//! as server-sent events named after the `event` tag.
+
+use serde::Serialize;
+
+use crate::types::ErrorCategory;
+
+/// Step of the research pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineStep {
+    Planning,
+    Literature,
+    Generating,
+}
+
+/// Progress of a pipeline run, reported as it happens
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum PipelineEvent {
+    StageStarted { stage: PipelineStep },
+    /// The planner settled on an objective
+    ObjectiveSet { objective: String },
+    /// The planner's task `index` (0-based), sent as soon as the streamed
+    /// plan contains it
+    TaskPlanned { index: usize, task_type: String, objective: String },
+    /// Task `index` (0-based) of `total` started
+    TaskStarted { index: usize, total: usize, task_type: String, objective: String },
+    /// Task `index` finished; `error` is set when it failed
+    TaskFinished { index: usize, objective: String, sources: usize, error: Option<String> },
+    /// Streamed text of the response
+    Token { text: String },
+    /// LLM usage of a stage, from the session ledger ([`crate::budget`])
+    Usage { stage: PipelineStep, tokens: u64, cost_usd: f64 },
+    /// A file was written
+    ArtifactCreated { name: String, path: String },
+    Completed { response: String },
+    /// `error` is the user-facing message; `retryable` when sending the
+    /// same message again may succeed
+    Failed { error: String, category: ErrorCategory, retryable: bool },
+}
+
+impl PipelineEvent {
+    /// SSE event name, the same as the `event` tag
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::StageStarted { .. } => "stage_started",
+            Self::ObjectiveSet { .. } => "objective_set",
+            Self::TaskPlanned { .. } => "task_planned",
+            Self::TaskStarted { .. } => "task_started",
+            Self::TaskFinished { .. } => "task_finished",
+            Self::Token { .. } => "token",
+            Self::Usage { .. } => "usage",
+            Self::ArtifactCreated { .. } => "artifact_created",
+            Self::Completed { .. } => "completed",
+            Self::Failed { .. } => "failed",
+        }
+    }
+
+    /// `ArtifactCreated` for the file at `path`, named after its file name
+    pub fn artifact(path: &std::path::Path) -> Self {
+        Self::ArtifactCreated {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            path: path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// Measures the LLM usage of one stage from the session ledger. Concurrent
+/// runs in the same process share the ledger, so figures are approximate.
+pub(crate) struct UsageMeter(crate::budget::UsageTotals);
+
+impl UsageMeter {
+    pub(crate) fn start() -> Self {
+        Self(crate::budget::snapshot().session)
+    }
+
+    /// Usage since `start`, or `None` when nothing was spent
+    pub(crate) fn finish(self, stage: PipelineStep) -> Option<PipelineEvent> {
+        let now = crate::budget::snapshot().session;
+        let tokens = now.tokens.saturating_sub(self.0.tokens);
+        (tokens > 0).then(|| PipelineEvent::Usage {
+            stage,
+            tokens,
+            cost_usd: (now.usd - self.0.usd).max(0.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_name_matches_tag() {
+        let events = [
+            PipelineEvent::StageStarted { stage: PipelineStep::Literature },
+            PipelineEvent::TaskPlanned { index: 0, task_type: "LITERATURE".into(), objective: "x".into() },
+            PipelineEvent::TaskFinished { index: 0, objective: "x".into(), sources: 2, error: None },
+            PipelineEvent::Usage { stage: PipelineStep::Planning, tokens: 10, cost_usd: 0.001 },
+            PipelineEvent::artifact(std::path::Path::new("artifacts/analysis/d1/supplementary.zip")),
+        ];
+        for event in &events {
+            let json = serde_json::to_value(event).unwrap();
+            assert_eq!(json["event"], event.name());
+        }
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["stage"], "literature");
+        assert!(matches!(&events[4], PipelineEvent::ArtifactCreated { name, .. } if name == "supplementary.zip"));
+    }
+}
@@ -4,17 +4,33 @@
 //! 
 //! ## Search Strategy (Cascade)
 //! 
-//! 1. **Google Scholar (Primary)** - Academic papers, peer-reviewed research
-//! 2. **Google Light (Secondary)** - General web search filtered for reliable sources
+//! The literature LLM first turns the task objective into two or three search
+//! queries (boolean operators, quoted phrases, synonyms); each query is run
+//! against the enabled engines and the results are merged, dropping
+//! duplicates. Without an LLM the objective itself is the only query.
+//! 
+//! 1. **Google Scholar (Primary)** - Academic papers, peer-reviewed research,
+//!    together with PubMed and Semantic Scholar when enabled
+//! 2. **Google Light (Secondary)** - General web search
 //! 3. **LLM Knowledge (Fallback)** - AI knowledge base when search APIs unavailable
+//!
+//! Results scoring below `search.min_source_score` are dropped, and the
+//! sources keep their quality score (see [`crate::search::quality`]).
+//! Once the SerpAPI quota is used up, cached results for the same query are
+//! used instead; past the LLM budget the agent returns offline placeholders.
 //! 
 //! This approach ensures comprehensive, evidence-based information retrieval
 //! prioritizing peer-reviewed academic sources.
 
 use crate::models::PlanTask;
-use crate::types::{LLMRequest, LLMMessage, AppResult};
+use crate::types::{LLMRequest, LLMMessage, AppResult, AppError};
+use crate::utils::cancel::CancellationToken;
+use crate::utils::partial_json::PartialJson;
+use crate::config::LlmTask;
 use crate::llm::provider::{LLMProviderConfig, LLM};
-use crate::search::SerpApiClient;
+use crate::search::aggregate::merge_results;
+use crate::search::serpapi::CombinedSearchResults;
+use crate::search::{quality, Engine, SearchAggregator};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use tracing::{info, warn, error};
@@ -27,8 +43,18 @@ pub struct LiteratureResult {
     pub findings: String,
     pub sources: Vec<SourceReference>,
     pub key_insights: Vec<String>,
+    /// Search queries run for the objective; empty when the findings come
+    /// from the LLM's own knowledge
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queries: Vec<String>,
 }
 
+/// Most search queries generated per task
+pub const MAX_QUERIES: usize = 3;
+
+/// Longest generated query kept, in characters
+const MAX_QUERY_CHARS: usize = 256;
+
 /// Reference to a source/paper
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SourceReference {
@@ -42,6 +68,10 @@ pub struct SourceReference {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
     pub summary: String,
+    /// Quality score of a search result (see [`crate::search::quality`]);
+    /// `None` for sources recalled by the LLM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<f64>,
 }
 
 /// Raw JSON response from LLM for literature search
@@ -63,6 +93,12 @@ struct SourceRaw {
     summary: String,
 }
 
+/// Raw JSON response from LLM for query generation
+#[derive(Debug, Deserialize)]
+struct QueriesLLMResponse {
+    queries: Vec<String>,
+}
+
 /// Internal result from search cascade
 struct SearchCascadeResult {
     findings: String,
@@ -77,16 +113,31 @@ impl LiteratureAgent {
     /// 1. Google Scholar (Primary) - Academic papers
     /// 2. Google Light (Secondary) - Reliable web sources
     /// 3. LLM Knowledge (Fallback) - AI knowledge base
+    ///
+    /// Fails with `AppError::Cancelled` once `cancel` fires.
     pub async fn execute_task(
         task: &PlanTask,
         config: &crate::config::Config,
+        cancel: &CancellationToken,
+    ) -> AppResult<LiteratureResult> {
+        cancel
+            .run_until_cancelled(Self::execute_task_inner(task, config))
+            .await
+            .unwrap_or(Err(AppError::Cancelled))
+    }
+
+    async fn execute_task_inner(
+        task: &PlanTask,
+        config: &crate::config::Config,
     ) -> AppResult<LiteratureResult> {
         let task_id = task.id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
         info!(task_id = %task_id, objective = %task.objective, "Starting literature search");
 
-        // Try SerpAPI search first (Scholar -> Light cascade)
-        if config.search.serpapi_available() {
-            match Self::execute_search_cascade(&task.objective, config).await {
+        // Try the search engines first (Scholar/PubMed/Semantic Scholar -> Light cascade)
+        let engines = Engine::enabled(&config.search);
+        if !engines.is_empty() {
+            let queries = Self::generate_queries(&task.objective, config).await;
+            match Self::execute_search_cascade(&queries, &engines, config).await {
                 Ok(result) => {
                     // Check if we got meaningful results
                     if !result.findings.is_empty() && result.findings.len() > 100 {
@@ -101,6 +152,7 @@ impl LiteratureAgent {
                             findings: result.findings,
                             sources: result.sources,
                             key_insights: result.key_insights,
+                            queries,
                         });
                     }
                     warn!("Search cascade returned insufficient results, falling back to LLM");
@@ -110,22 +162,169 @@ impl LiteratureAgent {
                 }
             }
         } else {
-            info!("SerpAPI not configured, using LLM knowledge directly");
+            info!("No search engines available, using LLM knowledge directly");
         }
 
         // Fallback to LLM knowledge
         Self::execute_llm_search(&task_id, task, config).await
     }
 
-    /// Execute search using SerpAPI cascade (Scholar first, then Light)
-    async fn execute_search_cascade(
+    /// Search queries for `objective` written by the literature LLM. Falls
+    /// back to the objective alone without an LLM or a usable answer.
+    async fn generate_queries(objective: &str, config: &crate::config::Config) -> Vec<String> {
+        let fallback = vec![objective.to_string()];
+        let Some(llm_route) = config.llm_for(LlmTask::Literature) else {
+            return fallback;
+        };
+
+        let max_tokens = llm_route.output_tokens(Some(400));
+        let prompt = Self::create_queries_prompt(objective);
+        let prompt = llm_route.capabilities().fit_prompt(&prompt, max_tokens);
+        let llm = LLM::new(LLMProviderConfig {
+            name: llm_route.provider.clone(),
+            api_key: llm_route.api_key.clone(),
+            network: config.network.clone(),
+            openrouter: config.llm.openrouter.clone(),
+            rate_limit: config.llm.rate_limit(&llm_route.provider),
+        });
+        let request = LLMRequest {
+            provider: llm_route.provider.clone(),
+            model: llm_route.model.clone(),
+            messages: vec![LLMMessage::user(prompt)],
+            max_tokens: Some(max_tokens),
+            temperature: Some(llm_route.temperature_or(0.2)),
+            system_instruction: Some(
+                "You are a biomedical librarian who writes precise literature search queries.".to_string(),
+            ),
+            system_blocks: Vec::new(),
+        };
+
+        match llm.create_chat_completion(&request).await {
+            Ok(response) => {
+                let queries = Self::parse_queries(&response.content);
+                if queries.is_empty() {
+                    warn!("No usable search queries in the LLM response, searching the objective");
+                    return fallback;
+                }
+                info!(queries = ?queries, "Generated search queries");
+                queries
+            }
+            Err(e) => {
+                warn!(error = %e, "Query generation failed, searching the objective");
+                fallback
+            }
+        }
+    }
+
+    /// Create the query generation prompt
+    fn create_queries_prompt(objective: &str) -> String {
+        format!(r#"Write search queries for a scientific literature search on the following research objective:
+
+RESEARCH OBJECTIVE:
+{objective}
+
+Write 2 or 3 queries for academic search engines (Google Scholar, PubMed, Semantic Scholar). Each query should:
+- Cover a different angle of the objective
+- Combine the key concepts with AND, and synonyms or alternative names with OR in parentheses
+- Put multi-word terms in double quotes
+- Stay under 20 words
+
+OUTPUT FORMAT (respond with ONLY valid JSON):
+{{
+  "queries": [
+    "("epigenetic clock" OR "DNA methylation age") AND (mortality OR lifespan)",
+    "..."
+  ]
+}}"#,
+            objective = objective
+        )
+    }
+
+    /// Queries from the LLM response: trimmed, without duplicates or overly
+    /// long ones, at most [`MAX_QUERIES`]. A truncated response keeps the
+    /// queries that were complete.
+    fn parse_queries(response: &str) -> Vec<String> {
+        let json = PartialJson::from(response);
+        let raw: Vec<String> = if json.is_complete() {
+            json.value()
+                .and_then(|value| serde_json::from_value::<QueriesLLMResponse>(value).ok())
+                .map(|parsed| parsed.queries)
+                .unwrap_or_default()
+        } else {
+            json.finished_items("queries")
+                .into_iter()
+                .filter_map(|q| q.as_str().map(str::to_string))
+                .collect()
+        };
+
+        let mut queries: Vec<String> = Vec::new();
+        for query in raw {
+            let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+            if query.is_empty()
+                || query.chars().count() > MAX_QUERY_CHARS
+                || queries.iter().any(|q| q.eq_ignore_ascii_case(&query))
+            {
+                continue;
+            }
+            queries.push(query);
+            if queries.len() == MAX_QUERIES {
+                break;
+            }
+        }
+        queries
+    }
+
+    /// Results for `query`: cached ones once the SerpAPI quota is spent,
+    /// otherwise a fresh search of `engines`
+    async fn fetch_results(
         query: &str,
+        engines: &[Engine],
         config: &crate::config::Config,
-    ) -> Result<SearchCascadeResult> {
-        let client = SerpApiClient::from_config(&config.search)
-            .ok_or_else(|| anyhow::anyhow!("SerpAPI not configured"))?;
+    ) -> Result<CombinedSearchResults> {
+        let cached = crate::budget::search_exceeded(&config.budget)
+            .and_then(|reason| crate::search::cache::load(query).map(|cached| (reason, cached)));
+        match cached {
+            // Quota spent: answer from earlier results where possible
+            Some((reason, mut cached)) => {
+                info!(reason = %reason, "SerpAPI quota exceeded, using cached results");
+                quality::retain(&mut cached, config.search.min_source_score);
+                Ok(cached)
+            }
+            None => {
+                let results = SearchAggregator::new(config).search(query, engines).await;
+                if results.scholar_results.is_empty() && results.light_results.is_empty() {
+                    anyhow::bail!("No search results ({})", results.errors.join("; "));
+                }
+                // Mock fixtures must not stand in for real results later
+                if !engines.contains(&Engine::Mock) {
+                    crate::search::cache::store(query, &results);
+                }
+                Ok(results)
+            }
+        }
+    }
 
-        let search_results = client.search_combined(query).await;
+    /// Execute search using the engine cascade (Scholar first, then Light)
+    /// for each of `queries`, merging the results
+    async fn execute_search_cascade(
+        queries: &[String],
+        engines: &[Engine],
+        config: &crate::config::Config,
+    ) -> Result<SearchCascadeResult> {
+        let mut search_results = CombinedSearchResults::default();
+        let mut failures = Vec::new();
+        for query in queries {
+            match Self::fetch_results(query, engines, config).await {
+                Ok(results) => merge_results(&mut search_results, results),
+                Err(e) => {
+                    warn!(query = %query, error = %e, "Search query failed");
+                    failures.push(e.to_string());
+                }
+            }
+        }
+        if search_results.scholar_results.is_empty() && search_results.light_results.is_empty() {
+            anyhow::bail!("{}", failures.join("; "));
+        }
 
         // Build findings and sources from search results
         let mut findings = String::new();
@@ -167,6 +366,7 @@ impl LiteratureAgent {
                     doi: result.doi.clone(),
                     url: result.link.clone(),
                     summary: result.snippet.clone(),
+                    quality: Some(quality::paper_score(result)),
                 });
 
                 // Extract key insights from highly-cited papers
@@ -209,6 +409,7 @@ impl LiteratureAgent {
                     doi: None,
                     url: Some(result.link.clone()),
                     summary: result.snippet.clone(),
+                    quality: Some(quality::page_score(result)),
                 });
             }
         }
@@ -236,8 +437,8 @@ impl LiteratureAgent {
         config: &crate::config::Config,
     ) -> AppResult<LiteratureResult> {
         // Get LLM provider configuration
-        let api_key = match config.llm.active_api_key() {
-            Some(key) => key,
+        let llm_route = match config.llm_for(LlmTask::Literature) {
+            Some(route) => route,
             None => {
                 warn!("No LLM API key configured, using placeholder response");
                 return Ok(Self::placeholder_result(task));
@@ -245,23 +446,29 @@ impl LiteratureAgent {
         };
 
         // Create the search prompt
+        let max_tokens = llm_route.output_tokens(None);
         let prompt = Self::create_search_prompt(&task.objective);
+        let prompt = llm_route.capabilities().fit_prompt(&prompt, max_tokens);
 
         // Create LLM request
         let llm = LLM::new(LLMProviderConfig {
-            name: config.llm.default_provider.clone(),
-            api_key,
+            name: llm_route.provider.clone(),
+            api_key: llm_route.api_key.clone(),
+            network: config.network.clone(),
+            openrouter: config.llm.openrouter.clone(),
+            rate_limit: config.llm.rate_limit(&llm_route.provider),
         });
 
         let request = LLMRequest {
-            provider: config.llm.default_provider.clone(),
-            model: config.llm.default_model.clone(),
-            messages: vec![LLMMessage::user(&prompt)],
-            max_tokens: Some(if config.llm.default_provider == "groq" { 4096 } else { 2048 }),
-            temperature: Some(0.3), // Lower temperature for more factual responses
+            provider: llm_route.provider.clone(),
+            model: llm_route.model.clone(),
+            messages: vec![LLMMessage::user(prompt)],
+            max_tokens: Some(max_tokens),
+            temperature: Some(llm_route.temperature_or(0.3)), // Lower temperature for more factual responses
             system_instruction: Some(
                 "You are a scientific literature research assistant with deep knowledge of biology, medicine, and life sciences. Provide accurate, evidence-based information with citations where possible.".to_string()
             ),
+            system_blocks: Vec::new(),
         };
 
         match llm.create_chat_completion(&request).await {
@@ -290,6 +497,7 @@ impl LiteratureAgent {
                             findings: sanitized_findings,
                             sources: vec![],
                             key_insights: vec![],
+                            queries: vec![],
                         })
                     }
                 }
@@ -320,6 +528,7 @@ impl LiteratureAgent {
                 "Configure SerpAPI for Google Scholar search (recommended)".to_string(),
                 "Or configure an LLM API key for AI-powered research".to_string(),
             ],
+            queries: vec![],
         }
     }
 
@@ -366,29 +575,38 @@ IMPORTANT:
         )
     }
 
-    /// Parse the LLM response into a LiteratureResult
+    /// Parse the LLM response into a LiteratureResult. A response cut off
+    /// before its end keeps the findings so far and the complete sources and
+    /// insights.
     fn parse_literature_response(response: &str, task: &PlanTask) -> Result<LiteratureResult> {
-        // Try to extract JSON from the response
-        let json_str = if response.contains("```json") {
-            response
-                .split("```json")
-                .nth(1)
-                .and_then(|s| s.split("```").next())
-                .unwrap_or(response)
-                .trim()
-        } else if response.contains("```") {
-            response
-                .split("```")
-                .nth(1)
-                .unwrap_or(response)
-                .trim()
+        let json = PartialJson::from(response);
+        let value = json.value().ok_or_else(|| anyhow::anyhow!("No literature JSON in the response"))?;
+
+        let parsed: LiteratureLLMResponse = if json.is_complete() {
+            serde_json::from_value(value).map_err(|e| anyhow::anyhow!("Failed to parse literature JSON: {}", e))?
         } else {
-            response.trim()
+            let findings = value
+                .get("findings")
+                .and_then(|v| v.as_str())
+                .filter(|f| !f.trim().is_empty())
+                .ok_or_else(|| anyhow::anyhow!("Truncated literature JSON has no findings"))?
+                .to_string();
+            warn!("Literature response was truncated, keeping what was complete");
+            LiteratureLLMResponse {
+                findings,
+                sources: json
+                    .finished_items("sources")
+                    .into_iter()
+                    .filter_map(|s| serde_json::from_value(s).ok())
+                    .collect(),
+                key_insights: json
+                    .finished_items("key_insights")
+                    .into_iter()
+                    .filter_map(|i| i.as_str().map(str::to_string))
+                    .collect(),
+            }
         };
 
-        let parsed: LiteratureLLMResponse = serde_json::from_str(json_str)
-            .map_err(|e| anyhow::anyhow!("Failed to parse literature JSON: {}", e))?;
-
         let sources = parsed
             .sources
             .into_iter()
@@ -399,6 +617,7 @@ IMPORTANT:
                 doi: s.doi,
                 url: s.url,
                 summary: s.summary,
+                quality: None,
             })
             .collect();
 
@@ -408,6 +627,7 @@ IMPORTANT:
             findings: parsed.findings,
             sources,
             key_insights: parsed.key_insights,
+            queries: vec![],
         })
     }
 
@@ -587,19 +807,87 @@ IMPORTANT:
                         _ => String::new(),
                     };
                     let year = source.year.map(|y| format!(" ({})", y)).unwrap_or_default();
-                    output.push_str(&format!("- {}{}{}\n", source.title, year, citation));
+                    let quality = source.quality.map(|q| format!(" [quality {:.2}]", q)).unwrap_or_default();
+                    output.push_str(&format!("- {}{}{}{}\n", source.title, year, citation, quality));
                 }
             }
         }
         
         output
     }
+
+    /// The best `limit` sources of `results`, highest quality score first,
+    /// one per line with the score in front
+    pub fn format_sources(results: &[LiteratureResult], limit: usize) -> String {
+        let mut sources: Vec<&SourceReference> = results.iter().flat_map(|r| &r.sources).collect();
+        sources.sort_by(|a, b| b.quality.unwrap_or(-1.0).total_cmp(&a.quality.unwrap_or(-1.0)));
+        sources.dedup_by(|a, b| a.title.eq_ignore_ascii_case(&b.title));
+        sources
+            .iter()
+            .take(limit)
+            .map(|source| {
+                let score = source.quality.map(|q| format!("{:.2}", q)).unwrap_or_else(|| "  - ".to_string());
+                let year = source.year.map(|y| format!(" ({})", y)).unwrap_or_default();
+                format!("[{}] {}{}", score, source.title, year)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_truncated_literature_response() {
+        let task = PlanTask {
+            id: Some("t1".to_string()),
+            job_id: None,
+            objective: "Rapamycin and lifespan".to_string(),
+            datasets: vec![],
+            task_type: "LITERATURE".to_string(),
+            level: Some(1),
+            start: None,
+            end: None,
+            output: None,
+            artifacts: None,
+        };
+        let response = r#"{"findings": "mTOR inhibition extends lifespan in mice.", "sources": [{"title": "Rapamycin fed late in life", "authors": "Harrison DE", "year": 2009, "doi": null, "url": null, "summary": "ITP study"}, {"title": "Second st"#;
+
+        let result = LiteratureAgent::parse_literature_response(response, &task).unwrap();
+        assert_eq!(result.findings, "mTOR inhibition extends lifespan in mice.");
+        assert_eq!(result.sources.len(), 1);
+        assert!(result.key_insights.is_empty());
+    }
+
+    #[test]
+    fn test_parse_queries() {
+        let response = r#"```json
+{"queries": ["(\"epigenetic clock\" OR \"DNA methylation age\") AND mortality", "  epigenetic   clock\nmortality ", "EPIGENETIC CLOCK MORTALITY", "", "rapamycin AND lifespan", "senolytics"]}
+```"#;
+        assert_eq!(
+            LiteratureAgent::parse_queries(response),
+            [
+                "(\"epigenetic clock\" OR \"DNA methylation age\") AND mortality",
+                "epigenetic clock mortality",
+                "rapamycin AND lifespan",
+            ]
+        );
+
+        // Cut off mid-query: the complete ones are kept
+        assert_eq!(
+            LiteratureAgent::parse_queries(r#"{"queries": ["senolytics AND aging", "(dasatinib OR quer"#),
+            ["senolytics AND aging"]
+        );
+        assert!(LiteratureAgent::parse_queries("I cannot help with that.").is_empty());
+
+        let prompt = LiteratureAgent::create_queries_prompt("Rapamycin and lifespan");
+        let mock = LiteratureAgent::parse_queries(&crate::llm::mock::MockLLMAdapter::reply(&prompt));
+        assert_eq!(mock.len(), 2);
+        assert!(mock[1].contains(" AND "));
+    }
+
     #[test]
     fn test_format_for_reply_empty() {
         let result = LiteratureAgent::format_for_reply(&[]);
@@ -619,8 +907,10 @@ mod tests {
                 doi: Some("10.1234/test".to_string()),
                 url: None,
                 summary: "Test summary".to_string(),
+                quality: Some(0.82),
             }],
             key_insights: vec!["Insight 1".to_string()],
+            queries: vec![],
         }];
 
         let formatted = LiteratureAgent::format_for_reply(&results);
@@ -628,5 +918,7 @@ mod tests {
         assert!(formatted.contains("Test findings"));
         assert!(formatted.contains("Test Study"));
         assert!(formatted.contains("10.1234/test"));
+        assert!(formatted.contains("(2023) [DOI: 10.1234/test] [quality 0.82]"));
+        assert_eq!(LiteratureAgent::format_sources(&results, 5), "[0.82] Test Study (2023)");
     }
 }
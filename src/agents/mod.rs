@@ -4,7 +4,13 @@
 //! 
 //! - **Planning Agent**: Analyzes user queries and creates research task plans
 //! - **Literature Agent**: Searches scientific literature and databases
-//! - **Reply Agent**: Synthesizes findings and generates user-facing responses
+//! - **Reply Agent**: Synthesizes findings and generates user-facing responses,
+//!   summarising them first when they would not fit the model (see [`digest`])
+//! - **Drafting Agent**: Revises manuscript drafts in the TUI workflow
+//! - **Deep Research**: Repeats planning and literature search over several
+//!   iterations before replying (see [`deep_research`])
+//!
+//! The pipeline reports its progress as [`PipelineEvent`]s (see [`events`]).
 //! 
 //! ## Pipeline Overview
 //! 
@@ -36,35 +42,85 @@
 pub mod planning;
 pub mod literature;
 pub mod reply;
+pub mod digest;
+pub mod drafting;
+pub mod deep_research;
+pub mod events;
 pub mod file_upload;
 pub mod analysis;
 pub mod hypothesis;
 pub mod reflection;
+pub mod plan_store;
 
 // Re-export main components
 pub use planning::{PlanningAgent, PlanningResult};
 pub use literature::{LiteratureAgent, LiteratureResult, SourceReference};
 pub use reply::{ReplyAgent, ReplyMode};
+pub use drafting::{DraftingAgent, Revision};
+pub use deep_research::{DeepResearchOptions, DeepResearchReport, ResearchEvent};
+pub use events::{PipelineEvent, PipelineStep};
+pub use plan_store::{PlanStore, SavedPlan, TaskStatus};
 pub use file_upload::*;
 
-use crate::models::PlanTask;
-use crate::types::AppResult;
+use crate::models::{AnalysisArtifact, ConversationState, ConversationStateValues};
+use crate::types::{AppError, AppResult};
+use crate::utils::cancel::CancellationToken;
+use events::UsageMeter;
 use tracing::info;
 
-/// Execute the full research pipeline for a user message
-pub async fn execute_research_pipeline(
+/// Execute the full research pipeline for a user message, calling
+/// `on_event` as it progresses. The last event is `Completed` with the
+/// response, or `Failed` when planning or the reply fails or `cancel` fires.
+pub async fn execute_research_pipeline<F>(
     user_message: &str,
     conversation_state: Option<&crate::models::ConversationState>,
     config: &crate::config::Config,
-) -> AppResult<String> {
+    cancel: &CancellationToken,
+    mut on_event: F,
+) -> AppResult<String>
+where
+    F: FnMut(PipelineEvent) + Send,
+{
+    let result = run_pipeline(user_message, conversation_state, config, cancel, &mut on_event).await;
+    on_event(match &result {
+        Ok(response) => PipelineEvent::Completed { response: response.clone() },
+        Err(e) => PipelineEvent::Failed {
+            error: e.user_message(),
+            category: e.category(),
+            retryable: e.is_retryable(),
+        },
+    });
+    result
+}
+
+async fn run_pipeline<F>(
+    user_message: &str,
+    conversation_state: Option<&crate::models::ConversationState>,
+    config: &crate::config::Config,
+    cancel: &CancellationToken,
+    on_event: &mut F,
+) -> AppResult<String>
+where
+    F: FnMut(PipelineEvent) + Send,
+{
     info!(message_len = user_message.len(), "Starting research pipeline");
     
     // Step 1: Planning - create research tasks
-    let planning_result = PlanningAgent::generate_plan(
+    on_event(PipelineEvent::StageStarted { stage: PipelineStep::Planning });
+    let usage = UsageMeter::start();
+    let planning_result = PlanningAgent::generate_plan_streaming(
         user_message,
         conversation_state,
         config,
+        cancel,
+        |index, task| on_event(PipelineEvent::TaskPlanned {
+            index,
+            task_type: task.task_type.clone(),
+            objective: task.objective.clone(),
+        }),
     ).await?;
+    on_event(PipelineEvent::ObjectiveSet { objective: planning_result.current_objective.clone() });
+    report_usage(on_event, usage, PipelineStep::Planning);
     
     info!(
         objective = %planning_result.current_objective,
@@ -72,39 +128,168 @@ pub async fn execute_research_pipeline(
         "Planning complete"
     );
     
-    // Step 2: Execute literature tasks in parallel
+    // Step 2: Execute literature tasks
     let literature_tasks: Vec<_> = planning_result
         .plan
         .iter()
         .filter(|t| t.task_type == "LITERATURE")
         .collect();
     
+    on_event(PipelineEvent::StageStarted { stage: PipelineStep::Literature });
+    let usage = UsageMeter::start();
     let mut literature_results = Vec::new();
-    for task in &literature_tasks {
-        match LiteratureAgent::execute_task(task, config).await {
-            Ok(result) => literature_results.push(result),
+    for (index, task) in literature_tasks.iter().enumerate() {
+        on_event(PipelineEvent::TaskStarted {
+            index,
+            total: literature_tasks.len(),
+            task_type: task.task_type.clone(),
+            objective: task.objective.clone(),
+        });
+        let (sources, error) = match LiteratureAgent::execute_task(task, config, cancel).await {
+            Ok(result) => {
+                let sources = result.sources.len();
+                literature_results.push(result);
+                (sources, None)
+            }
+            Err(AppError::Cancelled) => return Err(AppError::Cancelled),
             Err(e) => {
                 tracing::warn!(error = %e, task = ?task.objective, "Literature task failed");
+                (0, Some(e.to_string()))
             }
-        }
+        };
+        on_event(PipelineEvent::TaskFinished { index, objective: task.objective.clone(), sources, error });
     }
+    report_usage(on_event, usage, PipelineStep::Literature);
     
     info!(
         results_count = literature_results.len(),
         "Literature searches complete"
     );
     
-    // Step 3: Generate reply
-    let reply_mode = ReplyAgent::classify_mode(user_message);
-    let response = ReplyAgent::generate_response(
-        user_message,
-        Some(&planning_result),
-        &literature_results,
-        reply_mode,
-        config,
-    ).await?;
+    // Step 3: Generate reply, streamed as tokens
+    on_event(PipelineEvent::StageStarted { stage: PipelineStep::Generating });
+    let usage = UsageMeter::start();
+    let reply_mode = conversation_state
+        .and_then(|s| s.values.reply_mode)
+        .unwrap_or_else(|| ReplyAgent::classify_mode(user_message));
+    let response = cancel
+        .run_until_cancelled(ReplyAgent::generate_response_streaming(
+            user_message,
+            Some(&planning_result),
+            &literature_results,
+            conversation_state,
+            reply_mode,
+            config,
+            |chunk| on_event(PipelineEvent::Token { text: chunk.to_string() }),
+        ))
+        .await
+        .unwrap_or(Err(AppError::Cancelled))?;
+    report_usage(on_event, usage, PipelineStep::Generating);
     
     info!(response_len = response.len(), "Research pipeline complete");
     
     Ok(response)
 }
+
+/// Conversation state handing the project's brief and glossary (see
+/// [`Project::prompt_context`]), the reply mode the user picked and the
+/// artifacts of the user's data (e.g. the sequence summaries of
+/// [`DatasetRegistry::artifacts`]) to planning and the reply; `None` when
+/// there are none of them
+///
+/// [`Project::prompt_context`]: crate::project::Project::prompt_context
+/// [`DatasetRegistry::artifacts`]: crate::data_registry::DatasetRegistry::artifacts
+pub fn data_state(
+    message: &str,
+    project_context: Option<String>,
+    reply_mode: Option<ReplyMode>,
+    artifacts: Vec<AnalysisArtifact>,
+) -> Option<ConversationState> {
+    if project_context.is_none() && reply_mode.is_none() && artifacts.is_empty() {
+        return None;
+    }
+    Some(ConversationState {
+        id: None,
+        values: ConversationStateValues {
+            objective: message.to_string(),
+            conversation_title: None,
+            current_objective: None,
+            current_level: None,
+            key_insights: None,
+            methodology: None,
+            current_hypothesis: None,
+            discoveries: None,
+            plan: None,
+            suggested_next_steps: None,
+            research_mode: None,
+            uploaded_datasets: None,
+            artifacts: (!artifacts.is_empty()).then_some(artifacts),
+            project_context,
+            reply_mode,
+        },
+    })
+}
+
+fn report_usage<F: FnMut(PipelineEvent)>(on_event: &mut F, usage: UsageMeter, stage: PipelineStep) {
+    if let Some(event) = usage.finish(stage) {
+        on_event(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pipeline_events_offline() {
+        let mut config = crate::config::Config::from_env().unwrap();
+        config.search.serpapi_key.clear();
+        config.search.pubmed_enabled = false;
+        config.search.semantic_scholar_enabled = false;
+        for key in [
+            &mut config.llm.openai_api_key,
+            &mut config.llm.anthropic_api_key,
+            &mut config.llm.google_api_key,
+            &mut config.llm.openrouter_api_key,
+            &mut config.llm.groq_api_key,
+        ] {
+            key.clear();
+        }
+
+        let mut events = Vec::new();
+        let cancel = CancellationToken::new();
+        let response = execute_research_pipeline("Effects of rapamycin on lifespan", None, &config, &cancel, |e| events.push(e))
+            .await
+            .unwrap();
+
+        assert!(matches!(events.first(), Some(PipelineEvent::StageStarted { stage: PipelineStep::Planning })));
+        assert!(matches!(events.last(), Some(PipelineEvent::Completed { response: r }) if *r == response));
+        let stages: Vec<PipelineStep> = events
+            .iter()
+            .filter_map(|e| match e {
+                PipelineEvent::StageStarted { stage } => Some(*stage),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(stages, [PipelineStep::Planning, PipelineStep::Literature, PipelineStep::Generating]);
+        let planned = events.iter().filter(|e| matches!(e, PipelineEvent::TaskPlanned { .. })).count();
+        assert!(planned >= 1);
+        let started = events.iter().filter(|e| matches!(e, PipelineEvent::TaskStarted { .. })).count();
+        let finished = events.iter().filter(|e| matches!(e, PipelineEvent::TaskFinished { .. })).count();
+        assert_eq!(started, finished);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_stops_when_cancelled() {
+        let config = crate::config::Config::from_env().unwrap();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let mut events = Vec::new();
+        let result = execute_research_pipeline("Senolytics", None, &config, &cancel, |e| events.push(e)).await;
+
+        assert!(matches!(result, Err(AppError::Cancelled)));
+        assert!(matches!(events.last(), Some(PipelineEvent::Failed { error, retryable: false, .. }) if error == "Cancelled"));
+        assert!(!events.iter().any(|e| matches!(e, PipelineEvent::TaskStarted { .. })));
+    }
+}
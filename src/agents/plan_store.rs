@@ -0,0 +1,292 @@
+//! Saved plans
+//!
+//! The automated TUI workflow saves its research plan to `plans.json` in the
+//! local data directory, one per conversation (the dataset the workflow runs
+//! on), with the status of every task and the results of the literature
+//! tasks that are done. `/resume` picks an interrupted workflow up from its
+//! saved plan and runs only the tasks that are not done, instead of starting
+//! again from the upload.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{LiteratureResult, PlanningResult};
+use crate::data_registry::{DatasetRecord, ParseOptions};
+use crate::models::PlanTask;
+
+/// Plan file under `<data dir>/oxidized-bio/`
+pub const PLANS_FILE: &str = "plans.json";
+
+/// How far a plan task got
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    #[default]
+    Pending,
+    /// Started; a task still running when the workflow stopped is run again
+    Running,
+    Done,
+    Failed,
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Failed => "failed",
+        })
+    }
+}
+
+/// A plan task with its status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedTask {
+    pub task: PlanTask,
+    #[serde(default)]
+    pub status: TaskStatus,
+    /// Result of a literature task that is done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<LiteratureResult>,
+    /// Why the task failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The plan of one conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPlan {
+    /// Conversation the plan belongs to: the id of the workflow's dataset
+    pub conversation_id: String,
+    /// Dataset file, its description and how it is read, to load it again
+    /// after a restart
+    pub dataset_path: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub parsing: ParseOptions,
+    pub current_objective: String,
+    pub tasks: Vec<SavedTask>,
+    /// The plan passed its approval gate
+    #[serde(default)]
+    pub approved: bool,
+    /// The workflow ran to the end
+    #[serde(default)]
+    pub finished: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SavedPlan {
+    /// `plan` for the workflow on `record`, with every task pending
+    pub fn new(record: &DatasetRecord, plan: &PlanningResult) -> Self {
+        Self {
+            conversation_id: record.dataset.id.clone(),
+            dataset_path: record.local_path.clone(),
+            description: record.dataset.description.clone(),
+            parsing: record.parsing.clone(),
+            current_objective: plan.current_objective.clone(),
+            tasks: plan
+                .plan
+                .iter()
+                .map(|task| SavedTask {
+                    task: task.clone(),
+                    status: TaskStatus::Pending,
+                    result: None,
+                    error: None,
+                })
+                .collect(),
+            approved: false,
+            finished: false,
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn planning_result(&self) -> PlanningResult {
+        PlanningResult {
+            current_objective: self.current_objective.clone(),
+            plan: self.tasks.iter().map(|t| t.task.clone()).collect(),
+        }
+    }
+
+    /// Tasks that are not done
+    pub fn incomplete(&self) -> usize {
+        self.tasks.iter().filter(|t| t.status != TaskStatus::Done).count()
+    }
+
+    /// Results of the literature tasks that are done, in plan order
+    pub fn literature_results(&self) -> Vec<LiteratureResult> {
+        self.tasks.iter().filter_map(|t| t.result.clone()).collect()
+    }
+
+    /// Set the status of every `task_type` task that is not done
+    pub fn mark(&mut self, task_type: &str, status: TaskStatus, error: Option<&str>) {
+        for saved in self.tasks.iter_mut().filter(|t| t.task.task_type == task_type) {
+            if saved.status != TaskStatus::Done {
+                saved.status = status;
+                saved.error = error.map(str::to_string);
+            }
+        }
+    }
+
+    /// The objective and the numbered tasks with their status
+    pub fn summary(&self) -> String {
+        let tasks: Vec<String> = self
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let error = t.error.as_deref().map(|e| format!(" ({})", e)).unwrap_or_default();
+                format!("{}. [{}] [{}] {}{}", i + 1, t.task.task_type, t.status, t.task.objective, error)
+            })
+            .collect();
+        format!("{}\n{}", self.current_objective, tasks.join("\n"))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PlanStore {
+    plans: Vec<SavedPlan>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl PlanStore {
+    /// Plans from the local data directory; empty when there are none yet
+    pub fn load_default() -> Self {
+        match dirs::data_local_dir() {
+            Some(dir) => Self::load(&dir.join("oxidized-bio").join(PLANS_FILE)),
+            None => Self::default(),
+        }
+    }
+
+    /// Plans from `path`. A missing or unreadable file starts an empty store
+    /// that will be written to `path`.
+    pub fn load(path: &Path) -> Self {
+        let mut store = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(error = %e, path = %path.display(), "Ignoring unreadable saved plans");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        store.path = Some(path.to_path_buf());
+        store
+    }
+
+    pub fn plans(&self) -> &[SavedPlan] {
+        &self.plans
+    }
+
+    /// Plan of `conversation_id`
+    pub fn get(&self, conversation_id: &str) -> Option<&SavedPlan> {
+        self.plans.iter().find(|p| p.conversation_id == conversation_id)
+    }
+
+    /// Most recently updated plan whose workflow did not finish
+    pub fn latest_unfinished(&self) -> Option<&SavedPlan> {
+        self.plans.iter().filter(|p| !p.finished).max_by_key(|p| p.updated_at)
+    }
+
+    /// Save `plan`, replacing the conversation's previous one
+    pub fn put(&mut self, plan: &mut SavedPlan) {
+        plan.updated_at = Utc::now();
+        match self.plans.iter_mut().find(|p| p.conversation_id == plan.conversation_id) {
+            Some(saved) => *saved = plan.clone(),
+            None => self.plans.push(plan.clone()),
+        }
+        self.persist();
+    }
+
+    /// Write the plans to their file
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self.save() {
+            warn!(error = %e, "Failed to save plans");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(task_type: &str, objective: &str) -> PlanTask {
+        PlanTask {
+            id: None,
+            job_id: None,
+            objective: objective.to_string(),
+            datasets: Vec::new(),
+            task_type: task_type.to_string(),
+            level: None,
+            start: None,
+            end: None,
+            output: None,
+            artifacts: None,
+        }
+    }
+
+    #[test]
+    fn test_plans_persist_with_task_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PLANS_FILE);
+        let data = dir.path().join("data.csv");
+        std::fs::write(&data, "gene_id,age\nENSG1,30\n").unwrap();
+        let record = DatasetRecord::from_path(&data, Some("ages".to_string())).unwrap();
+        let result = PlanningResult {
+            current_objective: "Find aging biomarkers".to_string(),
+            plan: vec![
+                task("LITERATURE", "Known clocks"),
+                task("LITERATURE", "Senescence markers"),
+                task("ANALYSIS", "Correlate with age"),
+            ],
+        };
+
+        let mut store = PlanStore::load(&path);
+        let mut plan = SavedPlan::new(&record, &result);
+        plan.approved = true;
+        plan.tasks[0].status = TaskStatus::Done;
+        plan.tasks[0].result = Some(LiteratureResult {
+            task_id: "t1".to_string(),
+            objective: "Known clocks".to_string(),
+            findings: "Horvath".to_string(),
+            sources: Vec::new(),
+            key_insights: Vec::new(),
+            queries: Vec::new(),
+        });
+        plan.tasks[1].status = TaskStatus::Running;
+        store.put(&mut plan);
+        plan.mark("ANALYSIS", TaskStatus::Failed, Some("no age column"));
+        store.put(&mut plan);
+        assert_eq!(store.plans().len(), 1);
+
+        let reloaded = PlanStore::load(&path);
+        let saved = reloaded.latest_unfinished().unwrap();
+        assert_eq!(saved.conversation_id, record.dataset.id);
+        assert_eq!(saved.dataset_path, record.local_path);
+        assert!(saved.approved);
+        assert_eq!(saved.incomplete(), 2);
+        assert_eq!(saved.literature_results().len(), 1);
+        assert_eq!(saved.planning_result().plan.len(), 3);
+        assert!(saved.summary().contains("3. [ANALYSIS] [failed] Correlate with age (no age column)"));
+
+        let mut finished = saved.clone();
+        finished.finished = true;
+        let mut store = reloaded;
+        store.put(&mut finished);
+        assert!(PlanStore::load(&path).latest_unfinished().is_none());
+    }
+}
@@ -5,8 +5,12 @@
 
 use crate::models::{ConversationState, Message, PlanTask, DatasetRef};
 use crate::types::{LLMRequest, LLMMessage, AppResult, AppError};
+use crate::config::LlmTask;
 use crate::llm::provider::{LLMProviderConfig, LLM};
+use crate::utils::cancel::CancellationToken;
+use crate::utils::partial_json::PartialJson;
 use anyhow::Result;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use tracing::{info, warn, error};
 
@@ -44,20 +48,53 @@ struct DatasetRefRaw {
 pub struct PlanningAgent;
 
 impl PlanningAgent {
-    /// Generate a research plan based on user's message
+    /// Generate a research plan based on user's message; fails with
+    /// `AppError::Cancelled` once `cancel` fires
     pub async fn generate_plan(
         message: &str,
         conversation_state: Option<&ConversationState>,
         config: &crate::config::Config,
+        cancel: &CancellationToken,
     ) -> AppResult<PlanningResult> {
+        Self::generate_plan_streaming(message, conversation_state, config, cancel, |_, _| {}).await
+    }
+
+    /// Like [`generate_plan`](Self::generate_plan), streaming the response
+    /// and calling `on_task` with each task (and its index) as soon as it
+    /// is complete; the result holds the tasks reported, in order
+    pub async fn generate_plan_streaming<F>(
+        message: &str,
+        conversation_state: Option<&ConversationState>,
+        config: &crate::config::Config,
+        cancel: &CancellationToken,
+        on_task: F,
+    ) -> AppResult<PlanningResult>
+    where
+        F: FnMut(usize, &PlanTask) + Send,
+    {
+        cancel
+            .run_until_cancelled(Self::generate_plan_inner(message, conversation_state, config, on_task))
+            .await
+            .unwrap_or(Err(AppError::Cancelled))
+    }
+
+    async fn generate_plan_inner<F>(
+        message: &str,
+        conversation_state: Option<&ConversationState>,
+        config: &crate::config::Config,
+        mut on_task: F,
+    ) -> AppResult<PlanningResult>
+    where
+        F: FnMut(usize, &PlanTask) + Send,
+    {
         info!(message_len = message.len(), "Starting planning agent");
 
         // Get LLM provider configuration
-        let api_key = match config.llm.active_api_key() {
-            Some(key) => key,
+        let llm_route = match config.llm_for(LlmTask::Planning) {
+            Some(route) => route,
             None => {
                 warn!("No LLM API key configured, using simple planning fallback");
-                return Ok(Self::simple_plan(message));
+                return Ok(Self::finish(Self::simple_plan(message), 0, &mut on_task));
             }
         };
 
@@ -65,48 +102,98 @@ impl PlanningAgent {
         let context = Self::build_context(conversation_state);
 
         // Create the planning prompt
+        let max_tokens = llm_route.output_tokens(Some(1024));
         let prompt = Self::create_planning_prompt(message, &context);
+        let prompt = llm_route.capabilities().fit_prompt(&prompt, max_tokens);
 
         // Create LLM request
         let llm = LLM::new(LLMProviderConfig {
-            name: config.llm.default_provider.clone(),
-            api_key,
+            name: llm_route.provider.clone(),
+            api_key: llm_route.api_key.clone(),
+            network: config.network.clone(),
+            openrouter: config.llm.openrouter.clone(),
+            rate_limit: config.llm.rate_limit(&llm_route.provider),
         });
 
         let request = LLMRequest {
-            provider: config.llm.default_provider.clone(),
-            model: config.llm.default_model.clone(),
-            messages: vec![LLMMessage::user(&prompt)],
-            max_tokens: Some(1024),
-            temperature: Some(0.7),
+            provider: llm_route.provider.clone(),
+            model: llm_route.model.clone(),
+            messages: vec![LLMMessage::user(prompt)],
+            max_tokens: Some(max_tokens),
+            temperature: Some(llm_route.temperature_or(0.7)),
             system_instruction: None,
+            system_blocks: Vec::new(),
         };
 
-        match llm.create_chat_completion(&request).await {
-            Ok(response) => {
-                info!(response_len = response.content.len(), "Received planning response from LLM");
-                
-                // Parse the JSON response
-                match Self::parse_planning_response(&response.content) {
-                    Ok(result) => {
-                        info!(
-                            objective = %result.current_objective,
-                            task_count = result.plan.len(),
-                            "Planning completed successfully"
-                        );
-                        Ok(result)
-                    }
+        // Tasks already passed to `on_task`
+        let mut reported = 0;
+        let content = match Self::stream_plan(&llm, &request, &mut on_task, &mut reported).await {
+            Ok(content) => content,
+            Err(e) => {
+                warn!(error = %e, "Streaming not available, falling back to standard completion");
+                match llm.create_chat_completion(&request).await {
+                    Ok(response) => response.content,
                     Err(e) => {
-                        warn!(error = %e, "Failed to parse planning response, using fallback");
-                        Ok(Self::simple_plan(message))
+                        error!(error = %e, "LLM call failed, using fallback planning");
+                        return Ok(Self::finish(Self::simple_plan(message), reported, &mut on_task));
                     }
                 }
             }
+        };
+        info!(response_len = content.len(), "Received planning response from LLM");
+
+        // Parse the JSON response
+        let result = match Self::parse_planning_response(&content) {
+            Ok(result) => {
+                info!(
+                    objective = %result.current_objective,
+                    task_count = result.plan.len(),
+                    "Planning completed successfully"
+                );
+                result
+            }
             Err(e) => {
-                error!(error = %e, "LLM call failed, using fallback planning");
-                Ok(Self::simple_plan(message))
+                warn!(error = %e, "Failed to parse planning response, using fallback");
+                Self::simple_plan(message)
             }
+        };
+        Ok(Self::finish(result, reported, &mut on_task))
+    }
+
+    /// Stream the planning response, passing each task to `on_task` once it
+    /// is complete; returns the full text
+    async fn stream_plan<F>(llm: &LLM, request: &LLMRequest, on_task: &mut F, reported: &mut usize) -> AppResult<String>
+    where
+        F: FnMut(usize, &PlanTask) + Send,
+    {
+        let mut stream = llm.create_chat_completion_stream(request).await?;
+        let mut partial = PartialJson::new();
+        while let Some(chunk) = stream.next().await {
+            partial.push(&chunk?);
+            let finished = partial.finished_items("plan");
+            for raw in finished.into_iter().skip(*reported) {
+                let Ok(raw) = serde_json::from_value::<PlanTaskRaw>(raw) else {
+                    break;
+                };
+                on_task(*reported, &Self::plan_task(raw));
+                *reported += 1;
+            }
+        }
+        if partial.text().trim().is_empty() {
+            return Err(AppError::Internal("Streaming returned an empty response".to_string()));
         }
+        Ok(partial.text().to_string())
+    }
+
+    /// Pass the tasks not streamed yet to `on_task`
+    fn finish<F>(result: PlanningResult, reported: usize, on_task: &mut F) -> PlanningResult
+    where
+        F: FnMut(usize, &PlanTask),
+    {
+        for (index, task) in result.plan.iter().enumerate().skip(reported) {
+            on_task(index, task);
+        }
+        result
     }
 
     /// Simple fallback plan when LLM is not available
@@ -133,6 +220,10 @@ impl PlanningAgent {
         match conversation_state {
             Some(state) => {
                 let mut context = String::new();
+
+                if let Some(project) = &state.values.project_context {
+                    context.push_str(&format!("Project Context:\n{}\n\n", project));
+                }
                 
                 if let Some(obj) = &state.values.current_objective {
                     context.push_str(&format!("Current Objective: {}\n", obj));
@@ -147,6 +238,15 @@ impl PlanningAgent {
                     }
                 }
                 
+                if let Some(plan) = &state.values.plan {
+                    if !plan.is_empty() {
+                        context.push_str("\nSearches Already Run:\n");
+                        for task in plan {
+                            context.push_str(&format!("- {}\n", task.objective));
+                        }
+                    }
+                }
+                
                 if let Some(hypothesis) = &state.values.current_hypothesis {
                     context.push_str(&format!("\nCurrent Hypothesis: {}\n", hypothesis));
                 }
@@ -159,6 +259,15 @@ impl PlanningAgent {
                         }
                     }
                 }
+
+                if let Some(artifacts) = &state.values.artifacts {
+                    if !artifacts.is_empty() {
+                        context.push_str("\nData Artifacts:\n");
+                        for artifact in artifacts {
+                            context.push_str(&format!("- [{}] {}\n", artifact.artifact_type, artifact.description));
+                        }
+                    }
+                }
                 
                 if context.is_empty() {
                     "No existing research context.".to_string()
@@ -183,7 +292,7 @@ USER'S MESSAGE:
 AVAILABLE TASK TYPES:
 - LITERATURE: Search and gather scientific papers and knowledge. Use for finding research, papers, clinical data, mechanisms.
 
-Create a focused plan with 1-2 tasks maximum.
+Create a focused plan with 1-2 tasks maximum. Do not repeat searches already run; propose follow-up searches that fill the gaps they left.
 
 OUTPUT FORMAT (respond with ONLY valid JSON):
 {{
@@ -203,60 +312,63 @@ Respond with ONLY the JSON object, no additional text."#,
         )
     }
 
-    /// Parse the LLM response into a PlanningResult
+    /// Parse the LLM response into a PlanningResult. A response cut off
+    /// before its end keeps the tasks that were complete.
     fn parse_planning_response(response: &str) -> Result<PlanningResult> {
-        // Try to extract JSON from the response (handle markdown code blocks)
-        let json_str = if response.contains("```json") {
-            response
-                .split("```json")
-                .nth(1)
-                .and_then(|s| s.split("```").next())
-                .unwrap_or(response)
-                .trim()
-        } else if response.contains("```") {
-            response
-                .split("```")
-                .nth(1)
-                .unwrap_or(response)
-                .trim()
+        let json = PartialJson::from(response);
+        let value = json.value().ok_or_else(|| anyhow::anyhow!("No planning JSON in the response"))?;
+
+        let (current_objective, tasks) = if json.is_complete() {
+            let parsed: PlanningLLMResponse = serde_json::from_value(value)
+                .map_err(|e| anyhow::anyhow!("Failed to parse planning JSON: {}", e))?;
+            (parsed.current_objective, parsed.plan)
         } else {
-            response.trim()
+            let current_objective = value
+                .get("currentObjective")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Truncated planning JSON has no objective"))?
+                .to_string();
+            let tasks: Vec<PlanTaskRaw> = json
+                .finished_items("plan")
+                .into_iter()
+                .map_while(|task| serde_json::from_value(task).ok())
+                .collect();
+            if tasks.is_empty() {
+                anyhow::bail!("Truncated planning JSON has no complete task");
+            }
+            warn!(task_count = tasks.len(), "Planning response was truncated, keeping the complete tasks");
+            (current_objective, tasks)
         };
 
-        let parsed: PlanningLLMResponse = serde_json::from_str(json_str)
-            .map_err(|e| anyhow::anyhow!("Failed to parse planning JSON: {}", e))?;
-
-        let plan = parsed
-            .plan
-            .into_iter()
-            .map(|task| PlanTask {
-                id: Some(uuid::Uuid::new_v4().to_string()),
-                job_id: None,
-                objective: task.objective,
-                datasets: task
-                    .datasets
-                    .into_iter()
-                    .map(|d| DatasetRef {
-                        filename: d.filename,
-                        id: d.id,
-                        description: d.description,
-                        path: None,
-                    })
-                    .collect(),
-                task_type: task.task_type,
-                level: Some(1),
-                start: None,
-                end: None,
-                output: None,
-                artifacts: None,
-            })
-            .collect();
-
         Ok(PlanningResult {
-            current_objective: parsed.current_objective,
-            plan,
+            current_objective,
+            plan: tasks.into_iter().map(Self::plan_task).collect(),
         })
     }
+
+    fn plan_task(task: PlanTaskRaw) -> PlanTask {
+        PlanTask {
+            id: Some(uuid::Uuid::new_v4().to_string()),
+            job_id: None,
+            objective: task.objective,
+            datasets: task
+                .datasets
+                .into_iter()
+                .map(|d| DatasetRef {
+                    filename: d.filename,
+                    id: d.id,
+                    description: d.description,
+                    path: None,
+                })
+                .collect(),
+            task_type: task.task_type,
+            level: Some(1),
+            start: None,
+            end: None,
+            output: None,
+            artifacts: None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -273,6 +385,17 @@ mod tests {
         assert_eq!(result.plan[0].task_type, "LITERATURE");
     }
 
+    #[test]
+    fn test_parse_truncated_planning_response() {
+        let response = "```json\n{\"currentObjective\":\"Senolytics\",\"plan\":[{\"objective\":\"Dasatinib and quercetin trials\",\"type\":\"LITERATURE\"},{\"objective\":\"Fisetin in ag";
+
+        let result = PlanningAgent::parse_planning_response(response).unwrap();
+        assert_eq!(result.current_objective, "Senolytics");
+        assert_eq!(result.plan.len(), 1);
+        assert_eq!(result.plan[0].objective, "Dasatinib and quercetin trials");
+        assert!(PlanningAgent::parse_planning_response("{\"currentObjective\":\"Seno").is_err());
+    }
+
     #[test]
     fn test_simple_plan() {
         let result = PlanningAgent::simple_plan("What are the effects of metformin?");
@@ -3,17 +3,26 @@
 //! Synthesizes research findings and generates user-facing responses.
 //! This is the final step in the agent pipeline.
 
-use crate::models::PlanTask;
-use crate::types::{LLMRequest, LLMMessage, AppResult};
+use crate::models::{AnalysisArtifact, ConversationState};
+use crate::types::{LLMRequest, LLMMessage, SystemBlock, AppResult};
+use crate::config::LlmTask;
+use crate::llm::capabilities::CHARS_PER_TOKEN;
 use crate::llm::provider::{LLMProviderConfig, LLM};
+use crate::agents::digest;
 use crate::agents::literature::LiteratureResult;
 use crate::agents::planning::PlanningResult;
 use anyhow::Result;
 use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
 use tracing::{info, warn, error};
 
-/// Reply mode - determines output format
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Reply mode - determines output format. Guessed from the message by
+/// [`ReplyAgent::classify_mode`] unless the user picked one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ReplyMode {
     /// Direct answer to a question
     Answer,
@@ -23,22 +32,66 @@ pub enum ReplyMode {
     Chat,
 }
 
-pub struct ReplyAgent;
+impl ReplyMode {
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Answer => "answer",
+            Self::Report => "report",
+            Self::Chat => "chat",
+        }
+    }
 
-impl ReplyAgent {
-    fn max_tokens_for_provider(config: &crate::config::Config) -> u32 {
-        if config.llm.default_provider == "groq" {
-            4096
-        } else {
-            2048
+    /// The mode after `mode` when cycling through them, `None` (guessed
+    /// from each message) coming before answer
+    pub fn cycle(mode: Option<Self>) -> Option<Self> {
+        match mode {
+            None => Some(Self::Answer),
+            Some(Self::Answer) => Some(Self::Report),
+            Some(Self::Report) => Some(Self::Chat),
+            Some(Self::Chat) => None,
         }
     }
+}
 
+impl fmt::Display for ReplyMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for ReplyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "answer" => Ok(Self::Answer),
+            "report" => Ok(Self::Report),
+            "chat" => Ok(Self::Chat),
+            other => Err(format!("unknown reply mode '{}' (expected answer, report or chat)", other)),
+        }
+    }
+}
+
+const SYSTEM_PROMPT: &str = "You are a knowledgeable research assistant. Provide clear, accurate, and helpful responses based on the research context provided.";
+
+/// Heading of the findings block
+const FINDINGS_HEADING: &str = "RESEARCH FINDINGS:\n";
+
+/// Heading of the data artifacts after the findings
+const ARTIFACTS_HEADING: &str = "DATA ARTIFACTS:\n";
+
+/// Heading of the project brief and glossary ahead of the findings
+const PROJECT_HEADING: &str = "PROJECT CONTEXT:\n";
+
+pub struct ReplyAgent;
+
+impl ReplyAgent {
     /// Generate a response based on research findings
     pub async fn generate_response(
         user_message: &str,
         planning: Option<&PlanningResult>,
         literature_results: &[LiteratureResult],
+        state: Option<&ConversationState>,
         mode: ReplyMode,
         config: &crate::config::Config,
     ) -> AppResult<String> {
@@ -50,37 +103,23 @@ impl ReplyAgent {
         );
 
         // Get LLM provider configuration
-        let api_key = match config.llm.active_api_key() {
-            Some(key) => key,
+        let llm_route = match config.llm_for(LlmTask::Reply) {
+            Some(route) => route,
             None => {
                 warn!("No LLM API key configured, using simple response");
                 return Ok(Self::simple_response(user_message, literature_results));
             }
         };
 
-        // Build the prompt based on mode
-        let prompt = match mode {
-            ReplyMode::Answer => Self::create_answer_prompt(user_message, literature_results, planning),
-            ReplyMode::Report => Self::create_report_prompt(user_message, literature_results, planning),
-            ReplyMode::Chat => Self::create_chat_prompt(user_message, literature_results),
-        };
-
-        // Create LLM request
         let llm = LLM::new(LLMProviderConfig {
-            name: config.llm.default_provider.clone(),
-            api_key,
+            name: llm_route.provider.clone(),
+            api_key: llm_route.api_key.clone(),
+            network: config.network.clone(),
+            openrouter: config.llm.openrouter.clone(),
+            rate_limit: config.llm.rate_limit(&llm_route.provider),
         });
-
-        let request = LLMRequest {
-            provider: config.llm.default_provider.clone(),
-            model: config.llm.default_model.clone(),
-            messages: vec![LLMMessage::user(&prompt)],
-            max_tokens: Some(Self::max_tokens_for_provider(config)),
-            temperature: Some(0.7),
-            system_instruction: Some(
-                "You are a knowledgeable research assistant. Provide clear, accurate, and helpful responses based on the research context provided.".to_string()
-            ),
-        };
+        let context = Self::fit_literature(&llm, &llm_route, user_message, planning, literature_results, mode).await;
+        let request = Self::build_request(&llm_route, user_message, planning, &context, state, mode);
 
         match llm.create_chat_completion(&request).await {
             Ok(response) => {
@@ -94,11 +133,14 @@ impl ReplyAgent {
         }
     }
 
-    /// Generate a response with streaming support (chunks sent via callback)
+    /// Generate a response with streaming support (chunks sent via
+    /// callback). Run it under `CancellationToken::run_until_cancelled` to
+    /// stop it.
     pub async fn generate_response_streaming<F>(
         user_message: &str,
         planning: Option<&PlanningResult>,
         literature_results: &[LiteratureResult],
+        state: Option<&ConversationState>,
         mode: ReplyMode,
         config: &crate::config::Config,
         mut on_chunk: F,
@@ -113,35 +155,23 @@ impl ReplyAgent {
             "Generating reply (streaming)"
         );
 
-        let api_key = match config.llm.active_api_key() {
-            Some(key) => key,
+        let llm_route = match config.llm_for(LlmTask::Reply) {
+            Some(route) => route,
             None => {
                 warn!("No LLM API key configured, using simple response");
                 return Ok(Self::simple_response(user_message, literature_results));
             }
         };
 
-        let prompt = match mode {
-            ReplyMode::Answer => Self::create_answer_prompt(user_message, literature_results, planning),
-            ReplyMode::Report => Self::create_report_prompt(user_message, literature_results, planning),
-            ReplyMode::Chat => Self::create_chat_prompt(user_message, literature_results),
-        };
-
         let llm = LLM::new(LLMProviderConfig {
-            name: config.llm.default_provider.clone(),
-            api_key,
+            name: llm_route.provider.clone(),
+            api_key: llm_route.api_key.clone(),
+            network: config.network.clone(),
+            openrouter: config.llm.openrouter.clone(),
+            rate_limit: config.llm.rate_limit(&llm_route.provider),
         });
-
-        let request = LLMRequest {
-            provider: config.llm.default_provider.clone(),
-            model: config.llm.default_model.clone(),
-            messages: vec![LLMMessage::user(&prompt)],
-            max_tokens: Some(Self::max_tokens_for_provider(config)),
-            temperature: Some(0.7),
-            system_instruction: Some(
-                "You are a knowledgeable research assistant. Provide clear, accurate, and helpful responses based on the research context provided.".to_string()
-            ),
-        };
+        let context = Self::fit_literature(&llm, &llm_route, user_message, planning, literature_results, mode).await;
+        let request = Self::build_request(&llm_route, user_message, planning, &context, state, mode);
 
         match llm.create_chat_completion_stream(&request).await {
             Ok(mut stream) => {
@@ -156,25 +186,113 @@ impl ReplyAgent {
                         }
                         Err(e) => {
                             warn!(error = %e, "Streaming chunk failed, falling back");
-                            return Self::generate_response(user_message, planning, literature_results, mode, config).await;
+                            return Self::generate_response(user_message, planning, literature_results, state, mode, config).await;
                         }
                     }
                 }
 
                 if full.is_empty() {
                     warn!("Streaming returned empty response, falling back");
-                    return Self::generate_response(user_message, planning, literature_results, mode, config).await;
+                    return Self::generate_response(user_message, planning, literature_results, state, mode, config).await;
                 }
 
                 Ok(full)
             }
             Err(e) => {
                 warn!(error = %e, "Streaming not available, falling back to standard completion");
-                Self::generate_response(user_message, planning, literature_results, mode, config).await
+                Self::generate_response(user_message, planning, literature_results, state, mode, config).await
             }
         }
     }
 
+    /// The reply request. The project context, research findings and data
+    /// artifacts of `state` go in a cached system block ahead of the
+    /// per-message prompt, so iterating on the same findings reuses the
+    /// provider's prompt cache rather than paying for them again.
+    fn build_request(
+        llm_route: &crate::config::ResolvedLlm,
+        user_message: &str,
+        planning: Option<&PlanningResult>,
+        literature_results: &[LiteratureResult],
+        state: Option<&ConversationState>,
+        mode: ReplyMode,
+    ) -> LLMRequest {
+        let prompt = Self::create_prompt(user_message, planning, mode);
+        let max_tokens = llm_route.output_tokens(None);
+        let artifacts = state.and_then(|s| s.values.artifacts.as_deref()).unwrap_or_default();
+        let mut context = Self::research_context(literature_results, artifacts);
+        // First, so that cutting long findings leaves it in place
+        if let Some(project) = state.and_then(|s| s.values.project_context.as_deref()) {
+            context = format!("{}{}\n\n{}", PROJECT_HEADING, project, context);
+        }
+        // Only cuts findings that summarising left too long
+        let context = llm_route
+            .capabilities()
+            .fit_prompt(&context, Self::reserved_tokens(llm_route, &prompt));
+
+        LLMRequest {
+            provider: llm_route.provider.clone(),
+            model: llm_route.model.clone(),
+            messages: vec![LLMMessage::user(prompt)],
+            max_tokens: Some(max_tokens),
+            temperature: Some(llm_route.temperature_or(0.7)),
+            system_instruction: Some(SYSTEM_PROMPT.to_string()),
+            system_blocks: vec![SystemBlock::cached(context)],
+        }
+    }
+
+    fn create_prompt(user_message: &str, planning: Option<&PlanningResult>, mode: ReplyMode) -> String {
+        match mode {
+            ReplyMode::Answer => Self::create_answer_prompt(user_message, planning),
+            ReplyMode::Report => Self::create_report_prompt(user_message, planning),
+            ReplyMode::Chat => Self::create_chat_prompt(user_message),
+        }
+    }
+
+    /// Tokens of the window kept for the reply and the prompt around the
+    /// findings; the findings give way when the window is short
+    fn reserved_tokens(llm_route: &crate::config::ResolvedLlm, prompt: &str) -> u32 {
+        let chars = SYSTEM_PROMPT.len() + FINDINGS_HEADING.len() + prompt.len();
+        llm_route.output_tokens(None) + (chars / CHARS_PER_TOKEN) as u32
+    }
+
+    /// `literature_results`, summarised when they would not fit the reply
+    /// model's window next to the prompt (see [`digest`])
+    async fn fit_literature<'a>(
+        llm: &LLM,
+        llm_route: &crate::config::ResolvedLlm,
+        user_message: &str,
+        planning: Option<&PlanningResult>,
+        literature_results: &'a [LiteratureResult],
+        mode: ReplyMode,
+    ) -> Cow<'a, [LiteratureResult]> {
+        let prompt = Self::create_prompt(user_message, planning, mode);
+        let budget = llm_route.capabilities().prompt_chars(Self::reserved_tokens(llm_route, &prompt));
+        digest::fit(llm, llm_route, literature_results, budget).await
+    }
+
+    /// The literature findings the reply draws on, then the artifacts of
+    /// the user's data (e.g. `SEQUENCE_SUMMARY` read statistics)
+    fn research_context(literature_results: &[LiteratureResult], artifacts: &[AnalysisArtifact]) -> String {
+        let findings = if literature_results.is_empty() {
+            "No research context available.".to_string()
+        } else {
+            crate::agents::LiteratureAgent::format_for_reply(literature_results)
+        };
+        let mut context = format!("{}{}", FINDINGS_HEADING, findings);
+        if !artifacts.is_empty() {
+            context.push_str("\n\n");
+            context.push_str(ARTIFACTS_HEADING);
+            for artifact in artifacts {
+                context.push_str(&format!("- [{}] {}\n", artifact.artifact_type, artifact.description));
+                if let Some(content) = &artifact.content {
+                    context.push_str(&format!("  {}\n", content));
+                }
+            }
+        }
+        context
+    }
+
     /// Simple fallback response when LLM is not available
     fn simple_response(user_message: &str, literature_results: &[LiteratureResult]) -> String {
         if literature_results.is_empty() {
@@ -210,13 +328,7 @@ impl ReplyAgent {
     }
 
     /// Create prompt for answer mode (direct questions)
-    fn create_answer_prompt(
-        question: &str,
-        literature_results: &[LiteratureResult],
-        planning: Option<&PlanningResult>,
-    ) -> String {
-        let literature_context = crate::agents::LiteratureAgent::format_for_reply(literature_results);
-        
+    fn create_answer_prompt(question: &str, planning: Option<&PlanningResult>) -> String {
         let next_steps = planning
             .map(|p| {
                 if p.plan.is_empty() {
@@ -235,18 +347,16 @@ impl ReplyAgent {
 
 QUESTION: {question}
 
-RESEARCH FINDINGS:
-{literature_context}
-
 PLANNED NEXT STEPS:
 {next_steps}
 
 TASK:
-Generate a clear, helpful answer to the user's question based on the research findings.
+Generate a clear, helpful answer to the user's question based on the RESEARCH FINDINGS above.
 
 GUIDELINES:
 - Lead with a DIRECT ANSWER to their question
 - Support claims with evidence from the research findings
+- For questions about the user's own data, use the DATA ARTIFACTS above when present
 - Include inline citations where available using format (claim)[DOI/URL]
 - Be clear and accessible - avoid unnecessary jargon
 - If the question cannot be fully answered, acknowledge limitations
@@ -266,19 +376,12 @@ OUTPUT FORMAT:
 
 **Let me know if you'd like me to explore any aspect further!**"#,
             question = question,
-            literature_context = literature_context,
             next_steps = next_steps,
         )
     }
 
     /// Create prompt for report mode (research directives)
-    fn create_report_prompt(
-        directive: &str,
-        literature_results: &[LiteratureResult],
-        planning: Option<&PlanningResult>,
-    ) -> String {
-        let literature_context = crate::agents::LiteratureAgent::format_for_reply(literature_results);
-        
+    fn create_report_prompt(directive: &str, planning: Option<&PlanningResult>) -> String {
         let (current_objective, next_steps) = planning
             .map(|p| {
                 let obj = p.current_objective.clone();
@@ -301,16 +404,13 @@ USER'S REQUEST: {directive}
 
 CURRENT OBJECTIVE: {current_objective}
 
-RESEARCH FINDINGS:
-{literature_context}
-
 PLANNED NEXT STEPS:
 {next_steps}
 
 TASK:
 Generate a user-facing research report that:
 1. Summarizes what was done
-2. Presents key findings and discoveries
+2. Presents key findings and discoveries from the RESEARCH FINDINGS above, and any DATA ARTIFACTS
 3. Describes the current objective and next steps
 4. Asks for user feedback
 
@@ -347,42 +447,28 @@ Here's my plan for the next iteration:
 **Let me know if you'd like me to proceed with this plan, or if you want to adjust the direction!**"#,
             directive = directive,
             current_objective = current_objective,
-            literature_context = literature_context,
             next_steps = next_steps,
         )
     }
 
     /// Create prompt for chat mode (simple conversation)
-    fn create_chat_prompt(
-        message: &str,
-        literature_results: &[LiteratureResult],
-    ) -> String {
-        let context = if literature_results.is_empty() {
-            "No research context available.".to_string()
-        } else {
-            crate::agents::LiteratureAgent::format_for_reply(literature_results)
-        };
-
+    fn create_chat_prompt(message: &str) -> String {
         format!(r#"You are a knowledgeable research assistant having a conversation.
 
 USER'S MESSAGE: {message}
 
-AVAILABLE CONTEXT:
-{context}
-
 TASK:
 Provide a helpful, conversational response to the user's message.
 
 GUIDELINES:
 - Be direct and concise
-- Use evidence from the context if relevant
+- Use evidence from the RESEARCH FINDINGS above if relevant
 - Be helpful and friendly
 - If asked something outside your knowledge, be honest about limitations
 - Don't over-explain or be verbose
 
 Respond naturally and helpfully."#,
             message = message,
-            context = context,
         )
     }
 
@@ -442,6 +528,73 @@ mod tests {
         assert_eq!(ReplyAgent::classify_mode("Find papers on senolytics"), ReplyMode::Report);
     }
 
+    #[test]
+    fn test_reply_mode_cycle_and_parse() {
+        let mut mode = None;
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            mode = ReplyMode::cycle(mode);
+            seen.push(mode.map(|m| m.to_string()));
+        }
+        assert_eq!(seen, [Some("answer".to_string()), Some("report".to_string()), Some("chat".to_string()), None]);
+        assert_eq!(" Report ".parse::<ReplyMode>(), Ok(ReplyMode::Report));
+        assert!("essay".parse::<ReplyMode>().unwrap_err().contains("answer, report or chat"));
+    }
+
+    #[test]
+    fn test_findings_in_cached_system_block() {
+        let route = crate::config::ResolvedLlm {
+            provider: "anthropic".to_string(),
+            model: "claude-sonnet-4".to_string(),
+            api_key: String::new(),
+            temperature: None,
+            max_tokens: None,
+        };
+        let literature = [LiteratureResult {
+            task_id: "t1".to_string(),
+            objective: "Metformin and aging".to_string(),
+            findings: "Metformin lowers mTOR signalling.".to_string(),
+            sources: Vec::new(),
+            key_insights: Vec::new(),
+            queries: Vec::new(),
+        }];
+
+        let first = ReplyAgent::build_request(&route, "Does metformin slow aging?", None, &literature, None, ReplyMode::Answer);
+        let second = ReplyAgent::build_request(&route, "Research metformin dosing", None, &literature, None, ReplyMode::Report);
+        // The findings are a shared, cached prefix; only the prompt differs
+        assert_eq!(first.system_blocks.len(), 1);
+        assert!(first.system_blocks[0].cache);
+        assert!(first.system_blocks[0].text.contains("mTOR"));
+        assert_eq!(first.system_blocks[0].text, second.system_blocks[0].text);
+        assert!(!first.messages[0].content.as_text().unwrap().contains("mTOR"));
+        assert!(first.messages[0].content.as_text().unwrap().contains("Does metformin slow aging?"));
+
+        let state = crate::agents::data_state("Q", Some("Brief: T cell aging".to_string()), None, Vec::new());
+        let with_project =
+            ReplyAgent::build_request(&route, "Does metformin slow aging?", None, &literature, state.as_ref(), ReplyMode::Answer);
+        let text = &with_project.system_blocks[0].text;
+        assert!(text.starts_with("PROJECT CONTEXT:\nBrief: T cell aging\n\nRESEARCH FINDINGS:\n"), "{}", text);
+        assert!(!text.contains(ARTIFACTS_HEADING));
+    }
+
+    #[test]
+    fn test_artifacts_follow_findings() {
+        let artifact = AnalysisArtifact {
+            id: "d1-sequence-summary".to_string(),
+            description: "Sequence summary of reads.fastq: 2 reads, 8 bases".to_string(),
+            artifact_type: "SEQUENCE_SUMMARY".to_string(),
+            content: Some("{\"reads\":2}".to_string()),
+            name: "reads.fastq".to_string(),
+            path: None,
+        };
+        let context = ReplyAgent::research_context(&[], &[artifact]);
+        assert!(context.starts_with(FINDINGS_HEADING));
+        assert!(context.ends_with(
+            "DATA ARTIFACTS:\n- [SEQUENCE_SUMMARY] Sequence summary of reads.fastq: 2 reads, 8 bases\n  {\"reads\":2}\n"
+        ));
+        assert!(!ReplyAgent::research_context(&[], &[]).contains(ARTIFACTS_HEADING));
+    }
+
     #[test]
     fn test_simple_response() {
         let response = ReplyAgent::simple_response("test question", &[]);
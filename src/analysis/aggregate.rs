@@ -0,0 +1,583 @@
+//! Single-pass statistics
+//!
+//! [`GroupAggregator`] keeps, for every group and column, a running count,
+//! mean and variance (Welford) plus a t-digest quantile sketch, so box plots
+//! can be drawn for any column without keeping the raw values. Aggregators
+//! built over separate chunks of rows can be combined with `merge`.
+//!
+//! [`ColumnStats`] and [`PairStats`] do the same for whole columns and for
+//! marker-target pairs, so the row pass of an analysis holds a fixed amount
+//! per column however many rows there are. Medians and rank statistics come
+//! from a bounded [`Reservoir`] sample, which is the data itself while the
+//! column fits in it.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// t-digest compression: higher keeps more centroids and tighter quantiles
+const COMPRESSION: f64 = 100.0;
+
+/// Values buffered before they are merged into the centroids
+const BUFFER_SIZE: usize = 500;
+
+/// Seed of every [`Reservoir`]
+pub(super) const RESERVOIR_SEED: u64 = 42;
+
+#[derive(Debug, Clone, Copy)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// Merging t-digest (Dunning) with the k1 scale function
+#[derive(Debug, Clone)]
+pub struct TDigest {
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    count: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        Self {
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            count: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl TDigest {
+    pub fn push(&mut self, value: f64) {
+        self.add(Centroid { mean: value, weight: 1.0 });
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn merge(&mut self, other: &TDigest) {
+        for centroid in other.centroids.iter().chain(&other.buffer) {
+            self.add(*centroid);
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0.0
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// Estimated `q` quantile (0-1), `NaN` when empty
+    pub fn quantile(&self, q: f64) -> f64 {
+        if self.is_empty() {
+            return f64::NAN;
+        }
+        let centroids = if self.buffer.is_empty() {
+            Cow::Borrowed(&self.centroids)
+        } else {
+            Cow::Owned(compress(self.centroids.iter().chain(&self.buffer).copied().collect(), self.count))
+        };
+        let target = q.clamp(0.0, 1.0) * self.count;
+
+        // Interpolate between centroid centres, and towards min/max at the tails
+        let first = centroids[0];
+        if target <= first.weight / 2.0 {
+            return self.min + (first.mean - self.min) * target / (first.weight / 2.0);
+        }
+        let mut cumulative = 0.0;
+        for pair in centroids.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let left_centre = cumulative + left.weight / 2.0;
+            let right_centre = cumulative + left.weight + right.weight / 2.0;
+            if target <= right_centre {
+                let t = (target - left_centre) / (right_centre - left_centre);
+                return left.mean + (right.mean - left.mean) * t;
+            }
+            cumulative += left.weight;
+        }
+        let last = centroids[centroids.len() - 1];
+        let last_centre = self.count - last.weight / 2.0;
+        let t = ((target - last_centre) / (last.weight / 2.0)).min(1.0);
+        last.mean + (self.max - last.mean) * t
+    }
+
+    fn add(&mut self, centroid: Centroid) {
+        self.buffer.push(centroid);
+        self.count += centroid.weight;
+        if self.buffer.len() >= BUFFER_SIZE {
+            let mut all = std::mem::take(&mut self.centroids);
+            all.append(&mut self.buffer);
+            self.centroids = compress(all, self.count);
+        }
+    }
+}
+
+/// Sort `centroids` and merge neighbours while they span at most one unit
+/// of the k1 scale, which keeps the tails fine-grained
+fn compress(mut centroids: Vec<Centroid>, count: f64) -> Vec<Centroid> {
+    centroids.sort_by(|a, b| a.mean.partial_cmp(&b.mean).unwrap_or(std::cmp::Ordering::Equal));
+    let scale = |q: f64| COMPRESSION / (2.0 * PI) * (2.0 * q - 1.0).clamp(-1.0, 1.0).asin();
+
+    let mut merged: Vec<Centroid> = Vec::with_capacity(centroids.len());
+    let mut seen = 0.0;
+    let mut k_left = scale(0.0);
+    for centroid in centroids {
+        match merged.last_mut() {
+            Some(current) if scale((seen + current.weight + centroid.weight) / count) - k_left <= 1.0 => {
+                let weight = current.weight + centroid.weight;
+                current.mean += (centroid.mean - current.mean) * centroid.weight / weight;
+                current.weight = weight;
+            }
+            Some(current) => {
+                seen += current.weight;
+                k_left = scale(seen / count);
+                merged.push(centroid);
+            }
+            None => merged.push(centroid),
+        }
+    }
+    merged
+}
+
+/// Running statistics of one column within one group
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    m2: f64,
+    digest: TDigest,
+}
+
+impl Summary {
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.digest.push(value);
+    }
+
+    /// Combine with a summary of other rows (Chan et al.)
+    pub fn merge(&mut self, other: &Summary) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * (self.count * other.count) as f64 / count as f64;
+        self.count = count;
+        self.digest.merge(&other.digest);
+    }
+
+    /// Sample variance, 0 with fewer than two values
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    pub fn quantile(&self, q: f64) -> f64 {
+        self.digest.quantile(q)
+    }
+}
+
+/// Five-number summary of one group, for a box plot
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoxStats {
+    pub label: String,
+    /// Values in the group
+    pub n: usize,
+    pub min: f64,
+    pub q1: f64,
+    pub median: f64,
+    pub q3: f64,
+    pub max: f64,
+}
+
+/// Per-group [`Summary`] of each of `columns` columns
+#[derive(Debug, Clone)]
+pub struct GroupAggregator {
+    columns: usize,
+    groups: HashMap<String, Vec<Summary>>,
+}
+
+impl GroupAggregator {
+    pub fn new(columns: usize) -> Self {
+        Self {
+            columns,
+            groups: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, group: &str, column: usize, value: f64) {
+        // Look up before inserting so known groups don't allocate a key
+        if !self.groups.contains_key(group) {
+            self.groups.insert(group.to_string(), vec![Summary::default(); self.columns]);
+        }
+        if let Some(summaries) = self.groups.get_mut(group) {
+            summaries[column].push(value);
+        }
+    }
+
+    pub fn merge(&mut self, other: &GroupAggregator) {
+        for (group, theirs) in &other.groups {
+            let ours = self
+                .groups
+                .entry(group.clone())
+                .or_insert_with(|| vec![Summary::default(); self.columns]);
+            for (summary, other) in ours.iter_mut().zip(theirs) {
+                summary.merge(other);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Summaries of `column` in each group that has values for it
+    pub fn column(&self, column: usize) -> impl Iterator<Item = (&str, &Summary)> {
+        self.groups
+            .iter()
+            .map(move |(label, summaries)| (label.as_str(), &summaries[column]))
+            .filter(|(_, summary)| summary.count > 0)
+    }
+
+    /// Box plot data for `column`: the first `max_groups` groups by label
+    pub fn boxplot(&self, column: usize, max_groups: usize) -> Vec<BoxStats> {
+        let mut boxes: Vec<BoxStats> = self
+            .column(column)
+            .map(|(label, summary)| BoxStats {
+                label: label.to_string(),
+                n: summary.count,
+                min: summary.digest.min(),
+                q1: summary.quantile(0.25),
+                median: summary.quantile(0.5),
+                q3: summary.quantile(0.75),
+                max: summary.digest.max(),
+            })
+            .collect();
+        boxes.sort_by(|a, b| a.label.cmp(&b.label));
+        boxes.truncate(max_groups);
+        boxes
+    }
+}
+
+/// Uniform sample of at most `capacity` items of a stream (Vitter's
+/// Algorithm R), drawn with a fixed seed so reruns keep the same items. It
+/// holds every item while no more than `capacity` were pushed.
+#[derive(Debug, Clone)]
+pub struct Reservoir<T> {
+    items: Vec<T>,
+    capacity: usize,
+    seen: usize,
+    /// Created once the reservoir is full, so small streams carry no RNG
+    rng: Option<Box<StdRng>>,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: Vec::new(),
+            capacity: capacity.max(1),
+            seen: 0,
+            rng: None,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.seen += 1;
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+            return;
+        }
+        let rng = self.rng.get_or_insert_with(|| Box::new(StdRng::seed_from_u64(RESERVOIR_SEED)));
+        let slot = rng.gen_range(0..self.seen);
+        if slot < self.capacity {
+            self.items[slot] = item;
+        }
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Items pushed, kept or not
+    pub fn seen(&self) -> usize {
+        self.seen
+    }
+
+    /// Whether every pushed item is kept
+    pub fn is_complete(&self) -> bool {
+        self.seen <= self.capacity
+    }
+}
+
+/// Running count, mean and variance (Welford) and extremes of one column,
+/// with a [`Reservoir`] of its values for the median
+#[derive(Debug, Clone)]
+pub struct ColumnStats {
+    pub count: usize,
+    pub mean: f64,
+    m2: f64,
+    pub min: f64,
+    pub max: f64,
+    sample: Reservoir<f64>,
+}
+
+impl ColumnStats {
+    pub fn new(sample_size: usize) -> Self {
+        Self {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sample: Reservoir::new(sample_size),
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sample.push(value);
+    }
+
+    /// Sample variance, 0 with fewer than two values
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    /// Variance with the count as denominator
+    pub fn population_variance(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.m2 / self.count as f64
+        }
+    }
+
+    /// Median of the sample: exact unless the column outgrew it
+    pub fn median(&self) -> f64 {
+        let mut sorted = self.sample.items().to_vec();
+        if sorted.is_empty() {
+            return f64::NAN;
+        }
+        sorted.sort_by(f64::total_cmp);
+        let mid = sorted.len() / 2;
+        if sorted.len().is_multiple_of(2) {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        }
+    }
+
+    /// The sampled values, in no particular order
+    pub fn sample(&self) -> &[f64] {
+        self.sample.items()
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        !self.sample.is_complete()
+    }
+}
+
+/// Running co-moments of paired values, giving Pearson's r and the
+/// least-squares line without keeping the pairs, and optionally a
+/// [`Reservoir`] of the pairs for rank statistics
+#[derive(Debug, Clone)]
+pub struct PairStats {
+    pub count: usize,
+    mean_x: f64,
+    mean_y: f64,
+    m2_x: f64,
+    m2_y: f64,
+    c_xy: f64,
+    sample: Option<Reservoir<(f64, f64)>>,
+}
+
+impl PairStats {
+    /// Pairs are sampled only when `sample_size` is given
+    pub fn new(sample_size: Option<usize>) -> Self {
+        Self {
+            count: 0,
+            mean_x: 0.0,
+            mean_y: 0.0,
+            m2_x: 0.0,
+            m2_y: 0.0,
+            c_xy: 0.0,
+            sample: sample_size.map(Reservoir::new),
+        }
+    }
+
+    pub fn push(&mut self, x: f64, y: f64) {
+        self.count += 1;
+        let n = self.count as f64;
+        let dx = x - self.mean_x;
+        self.mean_x += dx / n;
+        let dy = y - self.mean_y;
+        self.mean_y += dy / n;
+        self.m2_x += dx * (x - self.mean_x);
+        self.m2_y += dy * (y - self.mean_y);
+        self.c_xy += dx * (y - self.mean_y);
+        if let Some(sample) = &mut self.sample {
+            sample.push((x, y));
+        }
+    }
+
+    /// Pearson's r, 0 when either side is constant
+    pub fn pearson(&self) -> f64 {
+        if self.count < 2 || self.m2_x <= 0.0 || self.m2_y <= 0.0 {
+            return 0.0;
+        }
+        self.c_xy / (self.m2_x.sqrt() * self.m2_y.sqrt())
+    }
+
+    /// Intercept, slope and R² of y on x; `None` when x is constant
+    pub fn line(&self) -> Option<(f64, f64, f64)> {
+        if self.count < 2 || self.m2_x <= 0.0 {
+            return None;
+        }
+        let slope = self.c_xy / self.m2_x;
+        let intercept = self.mean_y - slope * self.mean_x;
+        let r2 = if self.m2_y > 0.0 {
+            self.c_xy * self.c_xy / (self.m2_x * self.m2_y)
+        } else {
+            0.0
+        };
+        Some((intercept, slope, r2))
+    }
+
+    /// The sampled pairs as x and y series; `None` when not sampling
+    pub fn sample(&self) -> Option<(Vec<f64>, Vec<f64>)> {
+        self.sample.as_ref().map(|sample| sample.items().iter().copied().unzip())
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.sample.as_ref().is_some_and(|sample| !sample.is_complete())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles_track_exact_values() {
+        let mut summary = Summary::default();
+        // A permutation of 0..10000, so insertion order is not sorted
+        for i in 0..10_000u64 {
+            summary.push(((i * 7_919) % 10_000) as f64);
+        }
+        assert!((summary.mean - 4_999.5).abs() < 1e-9);
+        assert!((summary.quantile(0.5) - 4_999.5).abs() < 50.0);
+        assert!((summary.quantile(0.25) - 2_499.75).abs() < 50.0);
+        assert!((summary.quantile(0.99) - 9_899.0).abs() < 10.0);
+        assert_eq!(summary.quantile(0.0), 0.0);
+        assert_eq!(summary.quantile(1.0), 9_999.0);
+
+        let mut small = Summary::default();
+        for value in [4.0, 1.0, 3.0, 2.0] {
+            small.push(value);
+        }
+        assert_eq!(small.quantile(0.5), 2.5);
+    }
+
+    #[test]
+    fn test_merge_matches_single_pass() {
+        let values: Vec<f64> = (0..2_000).map(|i| ((i * 37) % 101) as f64 * 0.5).collect();
+        let mut whole = GroupAggregator::new(1);
+        let (mut left, mut right) = (GroupAggregator::new(1), GroupAggregator::new(1));
+        for (i, value) in values.iter().enumerate() {
+            whole.push("a", 0, *value);
+            let chunk = if i % 3 == 0 { &mut left } else { &mut right };
+            chunk.push("a", 0, *value);
+        }
+        right.push("b", 0, 1.0);
+        left.merge(&right);
+
+        let (merged, single) = (&left.groups["a"][0], &whole.groups["a"][0]);
+        assert_eq!(merged.count, single.count);
+        assert!((merged.mean - single.mean).abs() < 1e-9);
+        assert!((merged.variance() - single.variance()).abs() < 1e-6);
+        assert!((merged.quantile(0.5) - single.quantile(0.5)).abs() < 1.0);
+        assert_eq!(left.column(0).count(), 2);
+    }
+
+    #[test]
+    fn test_boxplot_sorted_and_limited() {
+        let mut groups = GroupAggregator::new(2);
+        for (group, value) in [("young", 1.0), ("old", 5.0), ("old", 7.0), ("mid", 3.0)] {
+            groups.push(group, 1, value);
+        }
+        let boxes = groups.boxplot(1, 2);
+        let labels: Vec<&str> = boxes.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, ["mid", "old"]);
+        assert_eq!((boxes[1].min, boxes[1].median, boxes[1].max), (5.0, 6.0, 7.0));
+        assert!(groups.boxplot(0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_streaming_column_and_pair_stats() {
+        let x: Vec<f64> = (0..1_000).map(|i| ((i * 7_919) % 1_000) as f64).collect();
+        let y: Vec<f64> = x.iter().enumerate().map(|(i, v)| 2.0 * v + 3.0 + (i % 7) as f64).collect();
+
+        let (mut exact, mut sampled) = (ColumnStats::new(x.len()), ColumnStats::new(100));
+        let mut pairs = PairStats::new(Some(100));
+        for (a, b) in x.iter().zip(&y) {
+            exact.push(*a);
+            sampled.push(*a);
+            pairs.push(*a, *b);
+        }
+        assert_eq!((exact.count, exact.min, exact.max, exact.median()), (1_000, 0.0, 999.0, 499.5));
+        assert!(!exact.is_sampled() && sampled.is_sampled());
+        assert_eq!(sampled.sample().len(), 100);
+        assert!((sampled.median() - 499.5).abs() < 150.0);
+        let mean = x.iter().sum::<f64>() / 1_000.0;
+        let variance = x.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / 999.0;
+        assert!((sampled.mean - mean).abs() < 1e-9 && (sampled.variance() - variance).abs() < 1e-6);
+
+        let r = super::super::correlation(&x, &y);
+        assert!((pairs.pearson() - r).abs() < 1e-12);
+        let (intercept, slope, r2) = pairs.line().unwrap();
+        assert!((slope - 2.0).abs() < 1e-2 && (intercept - 6.0).abs() < 0.1);
+        assert!((r2 - r * r).abs() < 1e-12);
+        let (xs, ys) = pairs.sample().unwrap();
+        assert_eq!((xs.len(), ys.len()), (100, 100));
+        assert!(PairStats::new(None).sample().is_none());
+
+        let mut reservoir = Reservoir::new(3);
+        for i in 0..3 {
+            reservoir.push(i);
+        }
+        assert_eq!((reservoir.items(), reservoir.is_complete()), (&[0, 1, 2][..], true));
+        reservoir.push(3);
+        assert_eq!((reservoir.items().len(), reservoir.seen(), reservoir.is_complete()), (3, 4, false));
+    }
+}
@@ -0,0 +1,258 @@
+//! One-way ANOVA across multi-level groups
+//!
+//! When the group column has more than two levels, each marker's group means
+//! are compared with a one-way ANOVA computed from the single-pass group
+//! summaries (F test, Benjamini–Hochberg across markers), which also backs
+//! the novelty scores with a p-value. The rank-based Kruskal-Wallis test
+//! needs the values themselves, so it is optional and reads the dataset
+//! again.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use super::aggregate::GroupAggregator;
+use super::sex::MISSING;
+use super::stats::{self, Thresholds};
+use crate::data_registry::DatasetRecord;
+use crate::models::{Anova, NoveltyScore};
+use crate::utils::cancel::{self, CancellationToken};
+use crate::utils::number;
+
+/// Fewest levels tested; two levels are covered by the group contrast
+pub const MIN_LEVELS: usize = 3;
+
+/// Markers named in the manuscript's results sentence
+const REPORTED: usize = 5;
+
+fn is_missing(label: &str) -> bool {
+    MISSING.contains(&label.trim().to_ascii_lowercase().as_str())
+}
+
+/// One-way ANOVA of every selected column but the target across the
+/// non-missing levels of the group column, for columns with at least
+/// [`MIN_LEVELS`] of them
+pub fn build(
+    headers: &[String],
+    selected_indices: &[usize],
+    target_index: Option<usize>,
+    groups: &GroupAggregator,
+) -> Vec<Anova> {
+    let mut tests = Vec::new();
+    for (pos, col_idx) in selected_indices.iter().enumerate() {
+        if Some(*col_idx) == target_index {
+            continue;
+        }
+        let summaries: Vec<_> = groups
+            .column(pos)
+            .filter(|(label, s)| !is_missing(label) && s.count > 0)
+            .map(|(_, s)| s)
+            .collect();
+        let k = summaries.len();
+        let n: usize = summaries.iter().map(|s| s.count).sum();
+        if k < MIN_LEVELS || n <= k {
+            continue;
+        }
+        let grand_mean = summaries.iter().map(|s| s.mean * s.count as f64).sum::<f64>() / n as f64;
+        let between: f64 = summaries.iter().map(|s| s.count as f64 * (s.mean - grand_mean).powi(2)).sum();
+        let within: f64 = summaries.iter().map(|s| s.variance() * (s.count - 1) as f64).sum();
+        let (df_between, df_within) = (k - 1, n - k);
+        let f = if within > 0.0 {
+            (between / df_between as f64) / (within / df_within as f64)
+        } else if between > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+        let total = between + within;
+        tests.push(Anova {
+            column: headers.get(*col_idx).cloned().unwrap_or_else(|| format!("column_{}", col_idx + 1)),
+            groups: k,
+            n,
+            f,
+            df_between,
+            df_within,
+            p_value: stats::f_p_value(f, df_between as f64, df_within as f64),
+            q_value: 1.0,
+            eta_squared: if total > 0.0 { between / total } else { 0.0 },
+            kruskal_h: None,
+            kruskal_p: None,
+            kruskal_q: None,
+        });
+    }
+    let p_values: Vec<f64> = tests.iter().map(|t| t.p_value).collect();
+    for (test, q) in tests.iter_mut().zip(stats::benjamini_hochberg(&p_values)) {
+        test.q_value = q;
+    }
+    tests
+}
+
+/// Add the Kruskal-Wallis test to each of `tests`, reading the values of
+/// their columns by group
+pub fn kruskal_wallis(
+    record: &DatasetRecord,
+    headers: &[String],
+    group_index: usize,
+    tests: &mut [Anova],
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let index: HashMap<&str, usize> = headers.iter().enumerate().map(|(i, h)| (h.as_str(), i)).collect();
+    let columns: Vec<Option<usize>> = tests.iter().map(|t| index.get(t.column.as_str()).copied()).collect();
+    let mut rdr = record.reader()?;
+    // Per test, the values of each level, levels in first-seen order
+    let mut levels: HashMap<String, usize> = HashMap::new();
+    let mut values: Vec<Vec<Vec<f64>>> = vec![Vec::new(); tests.len()];
+    for (i, row) in rdr.records().enumerate() {
+        if i % super::CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let row = row?;
+        // Same labels as the streaming group summaries
+        let Some(label) = row.get(group_index).filter(|l| !is_missing(l)) else { continue };
+        let next = levels.len();
+        let level = *levels.entry(label.to_string()).or_insert(next);
+        for (groups, col_idx) in values.iter_mut().zip(&columns) {
+            let Some(value) = col_idx.and_then(|idx| row.get(idx)).and_then(|v| record.number(v)) else {
+                continue;
+            };
+            if groups.len() <= level {
+                groups.resize(level + 1, Vec::new());
+            }
+            groups[level].push(value);
+        }
+    }
+    let results: Vec<(f64, f64)> = values.iter().map(|groups| stats::kruskal_wallis(groups)).collect();
+    let p_values: Vec<f64> = results.iter().map(|(_, p)| *p).collect();
+    for ((test, (h, p)), q) in tests.iter_mut().zip(results).zip(stats::benjamini_hochberg(&p_values)) {
+        test.kruskal_h = Some(h);
+        test.kruskal_p = Some(p);
+        test.kruskal_q = Some(q);
+    }
+    Ok(())
+}
+
+/// Attach each column's ANOVA p- and q-value to its novelty score
+pub fn back_novelty(novelty: &mut [NoveltyScore], tests: &[Anova]) {
+    for score in novelty {
+        let Some(test) = tests.iter().find(|t| t.column == score.column) else { continue };
+        score.p_value = Some(test.p_value);
+        score.q_value = Some(test.q_value);
+        score.rationale.push_str(&format!("; one-way ANOVA F = {:.2}, {}", test.f, number::p_quote("q", test.q_value)));
+    }
+}
+
+pub fn methods(tests: &[Anova], group: &str) -> String {
+    let levels = tests.iter().map(|t| t.groups).max().unwrap_or(0);
+    let kruskal = if tests.iter().any(|t| t.kruskal_h.is_some()) {
+        " and with the rank-based Kruskal-Wallis test (tie-corrected, chi-squared approximation)"
+    } else {
+        ""
+    };
+    format!(
+        "Differences in marker means across the {} levels of {} were tested with one-way ANOVA (F test){}, \
+         with Benjamini-Hochberg correction across markers.",
+        levels, group, kruskal
+    )
+}
+
+/// Results sentence naming the first markers whose means differ. The
+/// q-values are written q_F and q_H so they are not mistaken for the q-value
+/// of the marker's association with the target.
+pub fn results(tests: &[Anova], group: &str, thresholds: &Thresholds) -> String {
+    let mut differing: Vec<&Anova> = tests.iter().filter(|t| thresholds.significant(t.p_value, t.q_value)).collect();
+    differing.sort_by(|a, b| a.p_value.total_cmp(&b.p_value));
+    let kruskal = if tests.iter().any(|t| t.kruskal_h.is_some()) {
+        let ranked = tests
+            .iter()
+            .filter(|t| match (t.kruskal_p, t.kruskal_q) {
+                (Some(p), Some(q)) => thresholds.significant(p, q),
+                _ => false,
+            })
+            .count();
+        format!("; {} by the Kruskal-Wallis test", ranked)
+    } else {
+        String::new()
+    };
+    let listed = differing
+        .iter()
+        .take(REPORTED)
+        .map(|t| {
+            let h = t.kruskal_q.map(|q| format!(", {}", number::p_quote("q_H", q))).unwrap_or_default();
+            format!("{} (F = {:.2}, eta² = {:.2}, {}{})", t.column, t.f, t.eta_squared, number::p_quote("q_F", t.q_value), h)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "One-way ANOVA across {} levels: {} of {} markers differed at {}{}{}.",
+        group,
+        differing.len(),
+        tests.len(),
+        thresholds.criterion(),
+        kruskal,
+        if listed.is_empty() { String::new() } else { format!(": {}", listed) }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anova_and_kruskal_wallis() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        let mut csv = String::from("sample_id,tissue,gene_a,gene_b\n");
+        for (i, (tissue, a, b)) in [
+            ("liver", 1.0, 5.0),
+            ("liver", 2.0, 6.0),
+            ("liver", 3.0, 4.0),
+            ("brain", 4.0, 5.5),
+            ("brain", 5.0, 4.5),
+            ("brain", 6.0, 5.0),
+            ("heart", 7.0, 6.0),
+            ("heart", 8.0, 4.0),
+            ("heart", 9.0, 5.0),
+            ("NA", 50.0, 50.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            csv.push_str(&format!("S{},{},{},{}\n", i, tissue, a, b));
+        }
+        std::fs::write(&input, csv).unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+        let headers: Vec<String> = ["sample_id", "tissue", "gene_a", "gene_b"].map(String::from).to_vec();
+        let mut groups = GroupAggregator::new(2);
+        let rows = std::fs::read_to_string(&input).unwrap();
+        for line in rows.lines().skip(1) {
+            let fields: Vec<&str> = line.split(',').collect();
+            groups.push(fields[1], 0, fields[2].parse().unwrap());
+            groups.push(fields[1], 1, fields[3].parse().unwrap());
+        }
+
+        let mut tests = build(&headers, &[2, 3], None, &groups);
+        assert_eq!(tests.len(), 2);
+        let gene_a = &tests[0];
+        assert_eq!((gene_a.groups, gene_a.n, gene_a.df_between, gene_a.df_within), (3, 9, 2, 6));
+        assert!((gene_a.f - 27.0).abs() < 1e-9 && (gene_a.p_value - 0.001).abs() < 1e-9);
+        assert!((gene_a.eta_squared - 0.9).abs() < 1e-9);
+        assert!(tests[1].p_value > 0.5);
+
+        kruskal_wallis(&record, &headers, 1, &mut tests, &CancellationToken::new()).unwrap();
+        assert!((tests[0].kruskal_h.unwrap() - 7.2).abs() < 1e-9);
+        let text = results(&tests, "tissue", &Thresholds::default());
+        assert!(text.starts_with("One-way ANOVA across tissue levels: 1 of 2 markers differed at q < 0.05; 0 by"), "{}", text);
+        assert!(text.contains("gene_a (F = 27.00, eta² = 0.90, q_F = 0.002, q_H = 0.055)"), "{}", text);
+
+        let mut novelty = vec![NoveltyScore {
+            column: "gene_a".to_string(),
+            score: 0.5,
+            rationale: "Scaled deviation".to_string(),
+            p_value: None,
+            q_value: None,
+        }];
+        back_novelty(&mut novelty, &tests);
+        assert!((novelty[0].q_value.unwrap() - 0.002).abs() < 1e-9);
+        assert!(novelty[0].rationale.ends_with("; one-way ANOVA F = 27.00, q = 0.002"));
+    }
+}
@@ -0,0 +1,495 @@
+//! Batch-effect correction
+//!
+//! Samples processed in different batches (arrays hybridised on different
+//! days, GEO series merged into one dataset) differ systematically in ways
+//! that have nothing to do with the biology. With a batch column the marker
+//! values are adjusted before the analysis, after imputation:
+//!
+//! - **Mean-centering** moves every batch's mean of each marker onto the
+//!   marker's overall mean.
+//! - **ComBat** (Johnson, Li & Rabinovic, 2007) standardises each marker,
+//!   estimates each batch's shift and scale per marker, shrinks them towards
+//!   the batch's average over all markers by parametric empirical Bayes, and
+//!   removes them. Shrinkage keeps small batches from being over-corrected.
+//!
+//! Neither method knows about the target or groups: a group that lies
+//! entirely within one batch has its differences removed along with the
+//! batch's, which is warned about. The corrected dataset is written next to
+//! the results.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::data_registry::{ColumnKind, DatasetRecord};
+use crate::utils::cancel::{self, CancellationToken};
+
+/// Batch-corrected dataset
+pub const CORRECTED_FILE: &str = "batch_corrected.csv";
+
+/// Fewest samples with a value per batch for a marker to be corrected
+const MIN_BATCH: usize = 2;
+
+/// Empirical Bayes iterations stop once no estimate moves by more than this
+/// share, or after `MAX_ITERATIONS`
+const TOLERANCE: f64 = 1e-4;
+const MAX_ITERATIONS: usize = 100;
+
+/// Markers corrected between cancellation checks
+const CANCEL_CHECK_MARKERS: usize = 1000;
+
+/// How batch effects are removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchCorrection {
+    /// Move each batch's marker means onto the overall mean
+    Center,
+    /// Empirical Bayes adjustment of each batch's mean and variance
+    #[default]
+    Combat,
+}
+
+impl BatchCorrection {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Center => "center",
+            Self::Combat => "combat",
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Center => "mean-centering each batch on the overall mean of every marker",
+            Self::Combat => {
+                "ComBat (parametric empirical Bayes adjustment of the batch means and variances of every \
+                 standardised marker)"
+            }
+        }
+    }
+}
+
+impl fmt::Display for BatchCorrection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for BatchCorrection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "center" | "centre" | "mean" => Ok(Self::Center),
+            "combat" => Ok(Self::Combat),
+            other => Err(format!("unknown batch correction '{}' (expected center or combat)", other)),
+        }
+    }
+}
+
+/// A batch and its samples
+#[derive(Debug, Clone, Serialize)]
+pub struct Batch {
+    pub name: String,
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchSummary {
+    pub method: BatchCorrection,
+    pub column: String,
+    pub batches: Vec<Batch>,
+    /// Markers adjusted; markers without two values in every batch, or
+    /// without variance, are left as they were
+    pub markers: usize,
+    /// Groups whose samples all lie in one batch
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub confounded_groups: Vec<String>,
+    /// Corrected dataset
+    pub path: PathBuf,
+}
+
+impl BatchSummary {
+    /// One sentence for the manuscript's Methods section
+    pub fn methods(&self) -> String {
+        format!(
+            "Batch effects between the {} batches of {} ({} samples) were removed from {} markers by {}.",
+            self.batches.len(),
+            self.column,
+            self.batches.iter().map(|b| b.samples).sum::<usize>(),
+            self.markers,
+            self.method.describe()
+        )
+    }
+
+    /// Caveat for the Limitations section
+    pub fn limitations(&self) -> String {
+        let mut text = format!(
+            "Batch correction assumes the {} batches do not differ biologically, and removes any such difference",
+            self.column
+        );
+        if self.confounded_groups.is_empty() {
+            text.push('.');
+        } else {
+            text.push_str(&format!(
+                "; {} lie entirely within one batch, so their differences cannot be told apart from batch effects.",
+                self.confounded_groups.join(", ")
+            ));
+        }
+        text
+    }
+}
+
+/// Write `record` with the batch effects of `batch_column` removed from its
+/// numeric columns not named in `exclude` as `path`. Rows without a batch
+/// are left as they were. `group_column`, when given, is checked for groups
+/// confounded with batch.
+pub fn correct(
+    record: &DatasetRecord,
+    batch_column: &str,
+    group_column: Option<&str>,
+    method: BatchCorrection,
+    exclude: &[&str],
+    path: &Path,
+    cancel: &CancellationToken,
+) -> Result<(DatasetRecord, BatchSummary)> {
+    let Some(batch_index) = record.columns.iter().position(|c| c == batch_column) else {
+        bail!("Batch column '{}' not found in the dataset", batch_column);
+    };
+    let mut rdr = record.reader()?;
+    let rows: Vec<csv::StringRecord> = rdr.records().collect::<Result<_, _>>()?;
+
+    // Batch of each row, batches in order of first appearance
+    let mut batches: Vec<Batch> = Vec::new();
+    let batch_of: Vec<Option<usize>> = rows
+        .iter()
+        .map(|row| {
+            let name = row.get(batch_index).map(str::trim).filter(|v| !v.is_empty())?;
+            Some(match batches.iter().position(|b| b.name == name) {
+                Some(i) => {
+                    batches[i].samples += 1;
+                    i
+                }
+                None => {
+                    batches.push(Batch { name: name.to_string(), samples: 1 });
+                    batches.len() - 1
+                }
+            })
+        })
+        .collect();
+    if batches.len() < 2 {
+        bail!("Batch column '{}' has {} batch(es); correction needs at least two", batch_column, batches.len());
+    }
+    if let Some(small) = batches.iter().find(|b| b.samples < MIN_BATCH) {
+        bail!(
+            "Batch '{}' of {} has a single sample; batch correction needs at least {} samples per batch \
+             (merge it into another batch or leave its batch empty)",
+            small.name,
+            batch_column,
+            MIN_BATCH
+        );
+    }
+    let confounded_groups = group_column
+        .and_then(|column| record.columns.iter().position(|c| c == column))
+        .map(|group_index| confounded(&rows, group_index, &batch_of))
+        .unwrap_or_default();
+    if !confounded_groups.is_empty() {
+        warn!(groups = %confounded_groups.join(", "), batch = batch_column, "Groups lie within a single batch");
+    }
+
+    let columns: Vec<usize> = (0..record.columns.len())
+        .filter(|idx| *idx != batch_index)
+        .filter(|idx| record.column_kind(*idx).is_none_or(|kind| kind == ColumnKind::Numeric))
+        .filter(|idx| !exclude.contains(&record.columns[*idx].as_str()))
+        .collect();
+    // Correctable markers and their values, row by row
+    let mut markers: Vec<(usize, Marker)> = Vec::new();
+    for (n, idx) in columns.iter().enumerate() {
+        if n % CANCEL_CHECK_MARKERS == 0 {
+            cancel::check(cancel)?;
+        }
+        let values: Vec<Option<f64>> = rows.iter().map(|row| row.get(*idx).and_then(|v| record.number(v))).collect();
+        if let Some(marker) = Marker::new(values, &batch_of, batches.len()) {
+            markers.push((*idx, marker));
+        }
+    }
+    if markers.is_empty() {
+        bail!(
+            "No marker has at least {} values in every batch of '{}' and any variance to correct",
+            MIN_BATCH,
+            batch_column
+        );
+    }
+    match method {
+        BatchCorrection::Center => {
+            for (_, marker) in &mut markers {
+                marker.center(&batch_of);
+            }
+        }
+        BatchCorrection::Combat => combat(&mut markers, &batch_of, batches.len(), cancel)?,
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .from_path(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    wtr.write_record(&record.columns)?;
+    let mut positions = vec![None; record.columns.len()];
+    for (pos, (idx, _)) in markers.iter().enumerate() {
+        positions[*idx] = Some(pos);
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let out: Vec<String> = (0..record.columns.len())
+            .map(|idx| {
+                let corrected = positions[idx]
+                    .filter(|_| batch_of[i].is_some())
+                    .and_then(|pos: usize| markers[pos].1.values[i]);
+                match corrected {
+                    Some(value) => value.to_string(),
+                    None => row.get(idx).unwrap_or_default().to_string(),
+                }
+            })
+            .collect();
+        wtr.write_record(&out)?;
+    }
+    wtr.flush()?;
+
+    let summary = BatchSummary {
+        method,
+        column: batch_column.to_string(),
+        batches,
+        markers: markers.len(),
+        confounded_groups,
+        path: path.to_path_buf(),
+    };
+    let mut corrected = DatasetRecord::from_path_with(path, Some(record.dataset.description.clone()), record.parsing.derived())?;
+    corrected.dataset.id = record.dataset.id.clone();
+    corrected.species = record.species;
+    Ok((corrected, summary))
+}
+
+/// Groups of `group_index` whose samples with a batch all lie in one batch,
+/// when the batches hold more than one group between them
+fn confounded(rows: &[csv::StringRecord], group_index: usize, batch_of: &[Option<usize>]) -> Vec<String> {
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    for (row, batch) in rows.iter().zip(batch_of) {
+        let (Some(group), Some(batch)) = (row.get(group_index).map(str::trim).filter(|g| !g.is_empty()), batch) else {
+            continue;
+        };
+        let at = match groups.iter().position(|(name, _)| name == group) {
+            Some(at) => at,
+            None => {
+                groups.push((group.to_string(), Vec::new()));
+                groups.len() - 1
+            }
+        };
+        if !groups[at].1.contains(batch) {
+            groups[at].1.push(*batch);
+        }
+    }
+    if groups.len() < 2 {
+        return Vec::new();
+    }
+    groups
+        .into_iter()
+        .filter(|(_, batches)| batches.len() == 1)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// One marker's values and the moments the corrections start from
+struct Marker {
+    values: Vec<Option<f64>>,
+    /// Mean over the samples with a batch
+    mean: f64,
+    /// Pooled within-batch standard deviation
+    sd: f64,
+    batch_means: Vec<f64>,
+}
+
+impl Marker {
+    /// `None` when a batch has fewer than `MIN_BATCH` values or the values
+    /// do not vary within the batches
+    fn new(values: Vec<Option<f64>>, batch_of: &[Option<usize>], batches: usize) -> Option<Self> {
+        let mut sums = vec![0.0; batches];
+        let mut counts = vec![0usize; batches];
+        for (value, batch) in values.iter().zip(batch_of) {
+            if let (Some(value), Some(batch)) = (value, batch) {
+                sums[*batch] += value;
+                counts[*batch] += 1;
+            }
+        }
+        if counts.iter().any(|n| *n < MIN_BATCH) {
+            return None;
+        }
+        let n: usize = counts.iter().sum();
+        let mean = sums.iter().sum::<f64>() / n as f64;
+        let batch_means: Vec<f64> = sums.iter().zip(&counts).map(|(sum, n)| sum / *n as f64).collect();
+        let ss: f64 = values
+            .iter()
+            .zip(batch_of)
+            .filter_map(|(value, batch)| Some((value.as_ref()? - batch_means[(*batch)?]).powi(2)))
+            .sum();
+        let sd = (ss / n as f64).sqrt();
+        (sd > 0.0 && sd.is_finite()).then_some(Self { values, mean, sd, batch_means })
+    }
+
+    /// Batch means moved onto the overall mean
+    fn center(&mut self, batch_of: &[Option<usize>]) {
+        for (value, batch) in self.values.iter_mut().zip(batch_of) {
+            if let (Some(value), Some(batch)) = (value.as_mut(), batch) {
+                *value += self.mean - self.batch_means[*batch];
+            }
+        }
+    }
+
+    /// Standardised values of `batch`
+    fn standardised(&self, batch: usize, batch_of: &[Option<usize>]) -> Vec<f64> {
+        self.values
+            .iter()
+            .zip(batch_of)
+            .filter(|(_, b)| **b == Some(batch))
+            .filter_map(|(value, _)| value.map(|v| (v - self.mean) / self.sd))
+            .collect()
+    }
+}
+
+/// ComBat without covariates: per batch, the standardised markers' shifts
+/// are shrunk towards a normal prior and their variances towards an inverse
+/// gamma prior, both estimated over all markers, and then removed
+fn combat(
+    markers: &mut [(usize, Marker)],
+    batch_of: &[Option<usize>],
+    batches: usize,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    for batch in 0..batches {
+        cancel::check(cancel)?;
+        let z: Vec<Vec<f64>> = markers.iter().map(|(_, m)| m.standardised(batch, batch_of)).collect();
+        let gamma_hat: Vec<f64> = z.iter().map(|z| mean(z)).collect();
+        let delta_hat: Vec<f64> = z.iter().map(|z| variance(z)).collect();
+
+        // Priors: shifts ~ N(gamma_bar, tau2), variances ~ InvGamma(a, b)
+        let gamma_bar = mean(&gamma_hat);
+        let tau2 = variance(&gamma_hat);
+        let v = mean(&delta_hat);
+        let s2 = variance(&delta_hat);
+        let prior = (markers.len() > 1 && tau2.is_finite() && s2 > 0.0 && s2.is_finite())
+            .then(|| ((2.0 * s2 + v * v) / s2, (v * v * v + v * s2) / s2));
+
+        for (g, (_, marker)) in markers.iter_mut().enumerate() {
+            if g % CANCEL_CHECK_MARKERS == 0 {
+                cancel::check(cancel)?;
+            }
+            let (gamma, delta) = match prior {
+                Some((a, b)) => posterior(&z[g], gamma_bar, tau2, a, b, delta_hat[g]),
+                None => (gamma_hat[g], delta_hat[g]),
+            };
+            let delta = if delta > 0.0 && delta.is_finite() { delta.sqrt() } else { 1.0 };
+            let (mu, sd) = (marker.mean, marker.sd);
+            for (value, b) in marker.values.iter_mut().zip(batch_of) {
+                if let (Some(value), Some(b)) = (value.as_mut(), b) {
+                    if *b == batch {
+                        *value = sd * ((*value - mu) / sd - gamma) / delta + mu;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Empirical Bayes estimates of a batch's shift and variance of one marker
+/// from its standardised values `z`
+fn posterior(z: &[f64], gamma_bar: f64, tau2: f64, a: f64, b: f64, delta_hat: f64) -> (f64, f64) {
+    let n = z.len() as f64;
+    let gamma_hat = mean(z);
+    let mut gamma = gamma_hat;
+    let mut delta = delta_hat;
+    for _ in 0..MAX_ITERATIONS {
+        let next_gamma = (n * tau2 * gamma_hat + delta * gamma_bar) / (n * tau2 + delta);
+        let ss: f64 = z.iter().map(|v| (v - next_gamma).powi(2)).sum();
+        let next_delta = (b + ss / 2.0) / (n / 2.0 + a - 1.0);
+        let change = ((next_gamma - gamma).abs() / gamma.abs().max(f64::EPSILON))
+            .max((next_delta - delta).abs() / delta.abs().max(f64::EPSILON));
+        gamma = next_gamma;
+        delta = next_delta;
+        if change < TOLERANCE {
+            break;
+        }
+    }
+    (gamma, delta)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Sample variance; NaN below two values
+fn variance(values: &[f64]) -> f64 {
+    let m = mean(values);
+    values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (values.len() as f64 - 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch_means(path: &Path, column: &str) -> (f64, f64) {
+        let mut rdr = csv::Reader::from_path(path).unwrap();
+        let idx = rdr.headers().unwrap().iter().position(|c| c == column).unwrap();
+        let values: Vec<f64> = rdr.records().map(|r| r.unwrap()[idx].parse().unwrap()).collect();
+        (mean(&values[..3]), mean(&values[3..]))
+    }
+
+    #[test]
+    fn test_batch_correction() {
+        assert_eq!("centre".parse::<BatchCorrection>(), Ok(BatchCorrection::Center));
+        assert_eq!("ComBat".parse::<BatchCorrection>(), Ok(BatchCorrection::Combat));
+        assert!("limma".parse::<BatchCorrection>().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        // Batch B reads about 10 higher on every gene; the young samples are
+        // all in batch A, age is never corrected
+        let csv = "sample_id,age,batch,group,gene_a,gene_b,gene_c,flat\n\
+                   S1,30,A,young,1,5,2,4\nS2,35,A,old,2,6,4,4\nS3,60,A,young,3,8,3,4\n\
+                   S4,40,B,old,11,15,12,4\nS5,45,B,old,12,17,14,4\nS6,50,B,old,13,16,13,4\n";
+        std::fs::write(&input, csv).unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+        let path = dir.path().join(CORRECTED_FILE);
+        let token = CancellationToken::new();
+
+        let (corrected, summary) =
+            correct(&record, "batch", Some("group"), BatchCorrection::Center, &["age"], &path, &token).unwrap();
+        assert_eq!(summary.markers, 3);
+        assert_eq!(summary.batches.iter().map(|b| (b.name.as_str(), b.samples)).collect::<Vec<_>>(), [("A", 3), ("B", 3)]);
+        assert_eq!(summary.confounded_groups, ["young"]);
+        assert!(summary.methods().starts_with("Batch effects between the 2 batches of batch (6 samples) were removed from 3 markers"));
+        let written = std::fs::read_to_string(&corrected.local_path).unwrap();
+        assert!(written.lines().nth(1).unwrap().starts_with("S1,30,A,young,6,"));
+        assert!(written.lines().nth(1).unwrap().ends_with(",4"));
+        let (a, b) = batch_means(&path, "gene_a");
+        assert!((a - 7.0).abs() < 1e-9 && (b - 7.0).abs() < 1e-9, "{} {}", a, b);
+
+        correct(&record, "batch", None, BatchCorrection::Combat, &["age"], &path, &token).unwrap();
+        for gene in ["gene_a", "gene_b", "gene_c"] {
+            let (a, b) = batch_means(&path, gene);
+            assert!((a - b).abs() < 1.0, "{}: {} {}", gene, a, b);
+        }
+        let (_, b) = batch_means(&path, "age");
+        assert_eq!(b, 45.0);
+
+        assert!(correct(&record, "plate", None, BatchCorrection::Combat, &[], &path, &token).is_err());
+        std::fs::write(&input, "sample_id,batch,gene_a\nS1,A,1\nS2,A,2\nS3,B,3\n").unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+        let err = correct(&record, "batch", None, BatchCorrection::Center, &[], &path, &token).unwrap_err();
+        assert!(err.to_string().contains("single sample"), "{}", err);
+    }
+}
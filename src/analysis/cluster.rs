@@ -0,0 +1,177 @@
+//! Hierarchical clustering
+//!
+//! Agglomerative clustering with average linkage (UPGMA) on a distance
+//! matrix, used to order the heatmaps so that co-regulated markers, and
+//! samples with similar profiles, sit next to each other. The dendrogram is
+//! drawn along the heatmap from [`Dendrogram::lines`].
+
+use super::correlation;
+
+/// Leaves clustered at most; larger sets keep their order, as the
+/// linkage is cubic in the number of leaves
+pub const MAX_LEAVES: usize = 500;
+
+/// Two clusters joined at `height`. Leaves are nodes `0..n`, the cluster
+/// formed by merge `i` is node `n + i`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merge {
+    pub left: usize,
+    pub right: usize,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dendrogram {
+    pub leaves: usize,
+    /// In the order they were made, lowest first
+    pub merges: Vec<Merge>,
+    /// Leaves in plot order
+    pub order: Vec<usize>,
+}
+
+impl Dendrogram {
+    /// Height of the root
+    pub fn height(&self) -> f64 {
+        self.merges.iter().map(|m| m.height).fold(0.0, f64::max)
+    }
+
+    /// One bracket per merge as (position, height) points, down from the
+    /// left child, across and down to the right child. Leaf `p` of `order`
+    /// sits at `offset + p + 0.5`, the centre of its heatmap cell.
+    pub fn lines(&self, offset: f64) -> Vec<[(f64, f64); 4]> {
+        let mut position = vec![0.0; self.leaves + self.merges.len()];
+        let mut height = vec![0.0; self.leaves + self.merges.len()];
+        for (p, leaf) in self.order.iter().enumerate() {
+            position[*leaf] = offset + p as f64 + 0.5;
+        }
+        self.merges
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let node = self.leaves + i;
+                position[node] = (position[m.left] + position[m.right]) / 2.0;
+                height[node] = m.height;
+                [
+                    (position[m.left], height[m.left]),
+                    (position[m.left], m.height),
+                    (position[m.right], m.height),
+                    (position[m.right], height[m.right]),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Average-linkage clustering of a symmetric distance matrix. Of two merged
+/// clusters, the one holding the lower leaf index goes left, so ties keep
+/// the input order.
+pub fn average_linkage(distances: &[Vec<f64>]) -> Dendrogram {
+    let n = distances.len();
+    let mut d: Vec<Vec<f64>> = distances
+        .iter()
+        .map(|row| row.iter().map(|v| if v.is_finite() { *v } else { 0.0 }).collect())
+        .collect();
+    // Per slot: node, size and lowest leaf of the cluster it holds
+    let mut clusters: Vec<Option<(usize, usize, usize)>> = (0..n).map(|i| Some((i, 1, i))).collect();
+    let mut merges = Vec::with_capacity(n.saturating_sub(1));
+    for step in 0..n.saturating_sub(1) {
+        let mut best: Option<(usize, usize)> = None;
+        for i in 0..n {
+            if clusters[i].is_none() {
+                continue;
+            }
+            for j in i + 1..n {
+                if clusters[j].is_some() && best.is_none_or(|(a, b)| d[i][j] < d[a][b]) {
+                    best = Some((i, j));
+                }
+            }
+        }
+        let Some((i, j)) = best else { break };
+        let (Some(a), Some(b)) = (clusters[i], clusters[j]) else { break };
+        let (left, right) = if a.2 < b.2 { (a, b) } else { (b, a) };
+        merges.push(Merge { left: left.0, right: right.0, height: d[i][j] });
+        for k in 0..n {
+            if k == i || k == j || clusters[k].is_none() {
+                continue;
+            }
+            let joined = (d[i][k] * a.1 as f64 + d[j][k] * b.1 as f64) / (a.1 + b.1) as f64;
+            d[i][k] = joined;
+            d[k][i] = joined;
+        }
+        clusters[i] = Some((n + step, a.1 + b.1, a.2.min(b.2)));
+        clusters[j] = None;
+    }
+
+    let mut order = Vec::with_capacity(n);
+    let mut stack: Vec<usize> = match merges.len() {
+        0 => (0..n).rev().collect(),
+        k => vec![n + k - 1],
+    };
+    while let Some(node) = stack.pop() {
+        match node.checked_sub(n) {
+            Some(i) => {
+                stack.push(merges[i].right);
+                stack.push(merges[i].left);
+            }
+            None => order.push(node),
+        }
+    }
+    Dendrogram { leaves: n, merges, order }
+}
+
+/// 1 - Pearson's r between every pair of `rows`, over the positions both
+/// have a value at; pairs without a correlation are at distance 1
+pub fn correlation_distances(rows: &[Vec<Option<f64>>]) -> Vec<Vec<f64>> {
+    let n = rows.len();
+    let mut d = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let (x, y): (Vec<f64>, Vec<f64>) = rows[i]
+                .iter()
+                .zip(&rows[j])
+                .filter_map(|(a, b)| Some(((*a)?, (*b)?)))
+                .unzip();
+            let distance = 1.0 - correlation(&x, &y);
+            d[i][j] = distance;
+            d[j][i] = distance;
+        }
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average_linkage() {
+        // Leaves 0 and 2 are close, 1 and 3 are close, the pairs far apart
+        let d = vec![
+            vec![0.0, 8.0, 1.0, 9.0],
+            vec![8.0, 0.0, 7.0, 2.0],
+            vec![1.0, 7.0, 0.0, 6.0],
+            vec![9.0, 2.0, 6.0, 0.0],
+        ];
+        let tree = average_linkage(&d);
+        assert_eq!(tree.order, [0, 2, 1, 3]);
+        assert_eq!(tree.merges[0], Merge { left: 0, right: 2, height: 1.0 });
+        assert_eq!(tree.merges[1], Merge { left: 1, right: 3, height: 2.0 });
+        // Root: mean of 8, 9, 7 and 6
+        assert_eq!(tree.merges[2], Merge { left: 4, right: 5, height: 7.5 });
+        assert_eq!(tree.height(), 7.5);
+
+        let lines = tree.lines(0.0);
+        assert_eq!(lines[0], [(0.5, 0.0), (0.5, 1.0), (1.5, 1.0), (1.5, 0.0)]);
+        assert_eq!(lines[2], [(1.0, 1.0), (1.0, 7.5), (3.0, 7.5), (3.0, 2.0)]);
+
+        let rows = vec![
+            vec![Some(1.0), Some(2.0), Some(3.0)],
+            vec![Some(3.0), None, Some(1.0)],
+            vec![Some(2.0), Some(4.0), Some(6.5)],
+        ];
+        let d = correlation_distances(&rows);
+        assert!((d[0][1] - 2.0).abs() < 1e-12 && d[0][2] < 0.01);
+        assert_eq!(average_linkage(&d).order, [0, 2, 1]);
+        assert_eq!(average_linkage(&[vec![0.0]]).order, [0]);
+    }
+}
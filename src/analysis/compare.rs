@@ -0,0 +1,244 @@
+//! Comparison of two analysis runs
+//!
+//! `/compare` joins the biomarker tables of two runs (different cohorts, or
+//! the same data preprocessed differently) on the marker column, reports how
+//! each marker's rank moved and how well the correlations agree (Spearman of
+//! the r values over the shared markers), and draws r(A) against r(B).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::report::TableFormat;
+use super::stats::ranks;
+
+/// Biomarker table written next to every analysis run
+const BIOMARKER_TABLE: &str = "biomarker_candidates.csv";
+
+/// Per-marker comparison table
+pub const COMPARISON_FILE: &str = "comparison.csv";
+
+/// Scatter plot of the shared markers' correlations
+pub const SCATTER_FILE: &str = "comparison_scatter.png";
+
+/// The columns of a biomarker table the comparison needs
+#[derive(Debug, Clone, Deserialize)]
+pub struct RankedMarker {
+    pub column: String,
+    #[serde(default)]
+    pub symbol: String,
+    pub correlation: f64,
+    /// Complete (marker, target) pairs; 0 in tables written before it was
+    /// recorded
+    #[serde(default)]
+    pub n: usize,
+}
+
+/// One marker across both runs; ranks are 1-based
+#[derive(Debug, Clone, Serialize)]
+pub struct MarkerComparison {
+    pub column: String,
+    pub label: String,
+    pub rank_a: Option<usize>,
+    pub rank_b: Option<usize>,
+    pub r_a: Option<f64>,
+    pub r_b: Option<f64>,
+}
+
+impl MarkerComparison {
+    /// Places gained from A to B (positive when the marker moved up)
+    pub fn rank_change(&self) -> Option<i64> {
+        Some(self.rank_a? as i64 - self.rank_b? as i64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    /// Markers of A, then the markers only B has
+    pub markers: Vec<MarkerComparison>,
+    pub shared: usize,
+    /// Spearman correlation of the r values of the shared markers; `None`
+    /// with fewer than three of them
+    pub concordance: Option<f64>,
+}
+
+impl Comparison {
+    pub fn only_a(&self) -> usize {
+        self.markers.iter().filter(|m| m.rank_b.is_none()).count()
+    }
+
+    pub fn only_b(&self) -> usize {
+        self.markers.iter().filter(|m| m.rank_a.is_none()).count()
+    }
+
+    /// Shared markers by the size of their rank change, largest first
+    pub fn biggest_moves(&self, limit: usize) -> Vec<&MarkerComparison> {
+        let mut moves: Vec<&MarkerComparison> = self.markers.iter().filter(|m| m.rank_change().is_some()).collect();
+        moves.sort_by_key(|m| std::cmp::Reverse(m.rank_change().map(i64::abs)));
+        moves.truncate(limit);
+        moves
+    }
+}
+
+/// Directory of an analysis run: a path as given, else the run of that
+/// dataset ID under `artifacts/analysis`
+pub fn run_dir(id: &str) -> PathBuf {
+    let path = Path::new(id);
+    if path.is_dir() {
+        path.to_path_buf()
+    } else {
+        Path::new("artifacts").join("analysis").join(id)
+    }
+}
+
+/// Biomarker table of the run in `dir`, in rank order
+pub fn read_markers(dir: &Path) -> Result<Vec<RankedMarker>> {
+    let Some((path, format)) = [TableFormat::Csv, TableFormat::Tsv]
+        .into_iter()
+        .map(|format| (format.path(dir, BIOMARKER_TABLE), format))
+        .find(|(path, _)| path.is_file())
+    else {
+        bail!("No biomarker table in {} (run /analyze first)", dir.display());
+    };
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(format.delimiter())
+        .from_path(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    rdr.deserialize()
+        .collect::<Result<Vec<RankedMarker>, _>>()
+        .with_context(|| format!("Malformed biomarker table {}", path.display()))
+}
+
+/// Join the ranked markers of two runs
+pub fn compare(a: &[RankedMarker], b: &[RankedMarker]) -> Comparison {
+    let in_b: HashMap<&str, (usize, &RankedMarker)> =
+        b.iter().enumerate().map(|(i, m)| (m.column.as_str(), (i + 1, m))).collect();
+    let label = |m: &RankedMarker| if m.symbol.is_empty() { m.column.clone() } else { m.symbol.clone() };
+
+    let mut markers: Vec<MarkerComparison> = a
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let other = in_b.get(m.column.as_str());
+            MarkerComparison {
+                column: m.column.clone(),
+                label: label(m),
+                rank_a: Some(i + 1),
+                rank_b: other.map(|(rank, _)| *rank),
+                r_a: Some(m.correlation),
+                r_b: other.map(|(_, o)| o.correlation),
+            }
+        })
+        .collect();
+    let in_a: std::collections::HashSet<&str> = a.iter().map(|m| m.column.as_str()).collect();
+    markers.extend(b.iter().enumerate().filter(|(_, m)| !in_a.contains(m.column.as_str())).map(|(i, m)| {
+        MarkerComparison {
+            column: m.column.clone(),
+            label: label(m),
+            rank_a: None,
+            rank_b: Some(i + 1),
+            r_a: None,
+            r_b: Some(m.correlation),
+        }
+    }));
+
+    let (r_a, r_b): (Vec<f64>, Vec<f64>) = markers.iter().filter_map(|m| Some((m.r_a?, m.r_b?))).unzip();
+    let concordance = (r_a.len() >= 3).then(|| super::correlation(&ranks(&r_a), &ranks(&r_b)));
+    Comparison {
+        shared: r_a.len(),
+        markers,
+        concordance,
+    }
+}
+
+/// Write the comparison table and scatter plot to `output_dir`; returns the
+/// files written
+pub fn write_comparison(output_dir: &Path, comparison: &Comparison, label_a: &str, label_b: &str) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(output_dir).with_context(|| format!("Failed to create {}", output_dir.display()))?;
+    let table = output_dir.join(COMPARISON_FILE);
+    let mut wtr = csv::Writer::from_path(&table)?;
+    wtr.write_record(["column", "label", "rank_a", "rank_b", "rank_change", "r_a", "r_b"])?;
+    let cell = |v: Option<String>| v.unwrap_or_default();
+    for m in &comparison.markers {
+        wtr.write_record([
+            m.column.clone(),
+            m.label.clone(),
+            cell(m.rank_a.map(|r| r.to_string())),
+            cell(m.rank_b.map(|r| r.to_string())),
+            cell(m.rank_change().map(|c| c.to_string())),
+            cell(m.r_a.map(|r| r.to_string())),
+            cell(m.r_b.map(|r| r.to_string())),
+        ])?;
+    }
+    wtr.flush()?;
+
+    let mut files = vec![table];
+    if comparison.shared > 0 {
+        let plot = output_dir.join(SCATTER_FILE);
+        write_scatter(&plot, comparison, label_a, label_b)?;
+        files.push(plot);
+    }
+    Ok(files)
+}
+
+/// r in run A against r in run B for the shared markers, with the identity
+/// line for reference
+fn write_scatter(output_path: &Path, comparison: &Comparison, label_a: &str, label_b: &str) -> Result<()> {
+    let points: Vec<(f64, f64)> = comparison.markers.iter().filter_map(|m| Some((m.r_a?, m.r_b?))).collect();
+    let root = BitMapBackend::new(output_path, (700, 700)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let caption = match comparison.concordance {
+        Some(rho) => format!("Marker correlations (Spearman ρ = {:.2})", rho),
+        None => "Marker correlations".to_string(),
+    };
+    let mut chart = ChartBuilder::on(&root)
+        .margin(20)
+        .caption(caption, ("sans-serif", 22))
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(-1f64..1f64, -1f64..1f64)?;
+    chart
+        .configure_mesh()
+        .x_desc(format!("r ({})", label_a))
+        .y_desc(format!("r ({})", label_b))
+        .draw()?;
+    chart.draw_series(std::iter::once(PathElement::new(vec![(-1.0, -1.0), (1.0, 1.0)], BLACK.mix(0.3))))?;
+    chart.draw_series(points.iter().map(|p| Circle::new(*p, 4, BLUE.mix(0.7).filled())))?;
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(column: &str, correlation: f64) -> RankedMarker {
+        RankedMarker {
+            column: column.to_string(),
+            symbol: String::new(),
+            correlation,
+            n: 0,
+        }
+    }
+
+    #[test]
+    fn test_compare_ranks_and_concordance() {
+        let a = [marker("g1", 0.9), marker("g2", -0.8), marker("g3", 0.5), marker("g4", 0.2)];
+        let b = [marker("g3", 0.7), marker("g1", 0.95), marker("g2", -0.6), marker("g5", 0.1)];
+        let comparison = compare(&a, &b);
+
+        assert_eq!(comparison.shared, 3);
+        assert_eq!((comparison.only_a(), comparison.only_b()), (1, 1));
+        let g3 = comparison.markers.iter().find(|m| m.column == "g3").unwrap();
+        assert_eq!(g3.rank_change(), Some(2));
+        assert_eq!(comparison.biggest_moves(1)[0].column, "g3");
+        // Shared r values are in the same order in both runs
+        assert!((comparison.concordance.unwrap() - 1.0).abs() < 1e-12);
+
+        assert_eq!(ranks(&[3.0, 1.0, 3.0]), [2.5, 1.0, 2.5]);
+        assert!(compare(&a[..2], &b).concordance.is_none());
+    }
+}
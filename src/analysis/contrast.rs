@@ -0,0 +1,379 @@
+//! Two-group contrasts
+//!
+//! When the group column has exactly two levels, every analysed marker is
+//! compared between them with Welch's t test and the Mann-Whitney U test,
+//! each with Benjamini–Hochberg q-values across markers, and Cohen's d as
+//! the effect size. Differences run from the first level (by label) to the
+//! second, as in the sex interaction model.
+//!
+//! The volcano plot shows each marker's difference of means against its
+//! Welch p-value, and markers at least [`VOLCANO_DIFFERENCE`] apart that
+//! pass the thresholds are highlighted. The axis only claims a log2 fold
+//! change (and the highlight a two-fold change) when the run log2-
+//! transformed the data itself; see [`GroupContrast::log2_scale`].
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use serde::Serialize;
+
+use super::sex::MISSING;
+use super::stats::{self, Thresholds};
+use crate::data_registry::DatasetRecord;
+use crate::utils::cancel::{self, CancellationToken};
+use crate::utils::number;
+
+/// Markers named in the manuscript's results sentence
+const REPORTED: usize = 5;
+
+pub const VOLCANO_FILE: &str = "volcano.png";
+
+/// Smallest |difference of means| highlighted in the volcano plot: a
+/// two-fold change on log2-scale data, in the data's own units otherwise
+pub const VOLCANO_DIFFERENCE: f64 = 1.0;
+
+/// Highlighted markers labelled in the volcano plot, smallest p first
+const VOLCANO_LABELS: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupContrast {
+    pub column: String,
+    /// The two levels of the group column, sorted
+    pub groups: [String; 2],
+    /// Samples in each level
+    pub samples: [usize; 2],
+    /// Tested markers, smallest Welch p-value first
+    pub markers: Vec<MarkerContrast>,
+    /// Cut-offs a test has to pass
+    #[serde(skip)]
+    pub thresholds: Thresholds,
+    /// The run log2-transformed every marker and did not rescale them, so
+    /// differences of means are log2 fold changes
+    pub log2_scale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MarkerContrast {
+    pub column: String,
+    pub label: String,
+    /// Per level: values, mean and standard deviation
+    pub n: [usize; 2],
+    pub mean: [f64; 2],
+    pub std_dev: [f64; 2],
+    /// Second level's mean minus the first's
+    pub difference: f64,
+    /// `difference` over the pooled standard deviation
+    pub cohens_d: f64,
+    /// Welch's t, its degrees of freedom, p- and q-value
+    pub t: f64,
+    pub df: f64,
+    pub t_p: f64,
+    pub t_q: f64,
+    /// Mann-Whitney U of the second level against the first, p- and q-value
+    pub u: f64,
+    pub u_p: f64,
+    pub u_q: f64,
+}
+
+impl GroupContrast {
+    /// Markers whose means differ by Welch's t test
+    pub fn differing(&self) -> impl Iterator<Item = &MarkerContrast> {
+        self.markers.iter().filter(|m| self.thresholds.significant(m.t_p, m.t_q))
+    }
+
+    /// Markers highlighted in the volcano plot: differing by Welch's t test
+    /// by at least [`VOLCANO_DIFFERENCE`], smallest p-value first
+    pub fn volcano_hits(&self) -> impl Iterator<Item = &MarkerContrast> {
+        self.differing().filter(|m| m.difference.abs() >= VOLCANO_DIFFERENCE)
+    }
+
+    /// What `difference` measures: the log2 fold change on log2-scale data
+    pub fn effect(&self) -> &'static str {
+        if self.log2_scale {
+            "log2 fold change"
+        } else {
+            "difference of means"
+        }
+    }
+
+    /// Volcano x axis, second level relative to the first
+    pub fn effect_axis(&self) -> String {
+        let [a, b] = &self.groups;
+        match self.log2_scale {
+            true => format!("log2 fold change ({} / {})", b, a),
+            false => format!("difference of means ({} - {})", b, a),
+        }
+    }
+
+    /// Markers whose distributions differ by the Mann-Whitney U test
+    pub fn shifted(&self) -> impl Iterator<Item = &MarkerContrast> {
+        self.markers.iter().filter(|m| self.thresholds.significant(m.u_p, m.u_q))
+    }
+
+    pub fn methods(&self) -> String {
+        format!(
+            "Each marker was compared between the two levels of {column} ({a}, {b}) with Welch's t test and the \
+             two-sided Mann-Whitney U test (normal approximation with tie and continuity corrections), with \
+             Benjamini-Hochberg correction across markers for each test; effect sizes are Cohen's d of {b} \
+             relative to {a} with the pooled standard deviation.",
+            column = self.column,
+            a = self.groups[0],
+            b = self.groups[1],
+        )
+    }
+
+    /// Results sentence naming the first markers that differ by Welch's
+    /// test. Test-specific q-values are written q_t and q_U so they are not
+    /// mistaken for the q-value of the marker's association with the target.
+    pub fn results(&self) -> String {
+        let intro = format!(
+            "Between {} groups ({}: n = {}; {}: n = {}),",
+            self.column, self.groups[0], self.samples[0], self.groups[1], self.samples[1]
+        );
+        let differing: Vec<&MarkerContrast> = self.differing().collect();
+        let shifted = self.shifted().count();
+        if differing.is_empty() {
+            return format!(
+                "{} no marker's mean differed by Welch's t test (none of the {} tested markers reached {}); {} \
+                 differed by the Mann-Whitney U test.",
+                intro,
+                self.markers.len(),
+                self.thresholds.criterion(),
+                shifted
+            );
+        }
+        let listed = differing
+            .iter()
+            .take(REPORTED)
+            .map(|m| {
+                let (t, u) = (number::p_quote("q_t", m.t_q), number::p_quote("q_U", m.u_q));
+                format!("{} (d = {:.2}, {}, {})", m.label, m.cohens_d, t, u)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "{} {} of {} markers differed by Welch's t test and {} by the Mann-Whitney U test ({}): {}.",
+            intro,
+            differing.len(),
+            self.markers.len(),
+            shifted,
+            self.thresholds.criterion(),
+            listed
+        )
+    }
+}
+
+/// Welch, Mann-Whitney and Cohen's d contrasts of `markers` (column index
+/// and display label) between the levels of the group column, or `None`
+/// when it does not have exactly two levels. [`GroupContrast::log2_scale`]
+/// is left for the caller, which knows how the data were normalized.
+pub fn analyze(
+    record: &DatasetRecord,
+    headers: &[String],
+    group_index: usize,
+    markers: &[(usize, String)],
+    thresholds: Thresholds,
+    cancel: &CancellationToken,
+) -> Result<Option<GroupContrast>> {
+    let mut rdr = record.reader()?;
+    // Level (lower case) -> first spelling seen
+    let mut spellings: HashMap<String, String> = HashMap::new();
+    let mut rows: Vec<(String, Vec<Option<f64>>)> = Vec::new();
+    for (i, row) in rdr.records().enumerate() {
+        if i % super::CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let row = row?;
+        let Some(level) = row.get(group_index).map(str::trim) else { continue };
+        let key = level.to_ascii_lowercase();
+        if MISSING.contains(&key.as_str()) {
+            continue;
+        }
+        spellings.entry(key.clone()).or_insert_with(|| level.to_string());
+        if spellings.len() > 2 {
+            return Ok(None);
+        }
+        let value = |idx: usize| row.get(idx).and_then(|v| record.number(v));
+        rows.push((key, markers.iter().map(|(idx, _)| value(*idx)).collect()));
+    }
+
+    let mut keys: Vec<&String> = spellings.keys().collect();
+    keys.sort();
+    let [first, second] = keys[..] else {
+        return Ok(None);
+    };
+    let samples = [first, second].map(|key| rows.iter().filter(|(level, _)| level == key).count());
+
+    let mut tested: Vec<MarkerContrast> = markers
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, (idx, label))| {
+            let level = |key: &String| -> Vec<f64> {
+                rows.iter().filter(|(level, _)| level == key).filter_map(|(_, values)| values[pos]).collect()
+            };
+            let (a, b) = (level(first), level(second));
+            if a.len() < 2 || b.len() < 2 {
+                return None;
+            }
+            let ((mean_a, var_a), (mean_b, var_b)) = (mean_var(&a), mean_var(&b));
+            let (t, df, t_p) = stats::welch_t_test(mean_b, var_b, b.len(), mean_a, var_a, a.len());
+            let (u, u_p) = stats::mann_whitney_u(&b, &a);
+            Some(MarkerContrast {
+                column: headers.get(*idx).cloned().unwrap_or_else(|| format!("column_{}", idx + 1)),
+                label: label.clone(),
+                n: [a.len(), b.len()],
+                mean: [mean_a, mean_b],
+                std_dev: [var_a.sqrt(), var_b.sqrt()],
+                difference: mean_b - mean_a,
+                cohens_d: stats::cohens_d(mean_b, var_b, b.len(), mean_a, var_a, a.len()),
+                t,
+                df,
+                t_p,
+                t_q: 1.0,
+                u,
+                u_p,
+                u_q: 1.0,
+            })
+        })
+        .collect();
+    let t_p: Vec<f64> = tested.iter().map(|m| m.t_p).collect();
+    let u_p: Vec<f64> = tested.iter().map(|m| m.u_p).collect();
+    let adjusted = stats::benjamini_hochberg(&t_p).into_iter().zip(stats::benjamini_hochberg(&u_p));
+    for (marker, (t_q, u_q)) in tested.iter_mut().zip(adjusted) {
+        marker.t_q = t_q;
+        marker.u_q = u_q;
+    }
+    tested.sort_by(|a, b| a.t_p.total_cmp(&b.t_p));
+
+    Ok(Some(GroupContrast {
+        column: headers[group_index].clone(),
+        groups: [spellings[first].clone(), spellings[second].clone()],
+        samples,
+        markers: tested,
+        thresholds,
+        log2_scale: false,
+    }))
+}
+
+/// Volcano plot of `contrast`: difference of means (log2 fold change on
+/// log2-scale data) against -log10 Welch p, with dashed lines at
+/// ±[`VOLCANO_DIFFERENCE`] and at p = alpha. Highlighted markers are red
+/// when higher in the second level and blue when lower, and the first of
+/// them are labelled.
+pub fn write_volcano_plot(output_path: &Path, contrast: &GroupContrast) -> Result<()> {
+    let height = |m: &MarkerContrast| -m.t_p.max(f64::MIN_POSITIVE).log10();
+    let alpha_line = -contrast.thresholds.alpha.log10();
+    let x_max = contrast
+        .markers
+        .iter()
+        .map(|m| m.difference.abs())
+        .filter(|d| d.is_finite())
+        .fold(VOLCANO_DIFFERENCE, f64::max)
+        * 1.1;
+    let y_max = contrast.markers.iter().map(height).filter(|h| h.is_finite()).fold(alpha_line, f64::max) * 1.1;
+
+    let root = BitMapBackend::new(output_path, (800, 700)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .margin(20)
+        .caption(
+            format!("{}: {} vs {}", contrast.column, contrast.groups[1], contrast.groups[0]),
+            ("sans-serif", 24),
+        )
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(-x_max..x_max, 0f64..y_max)?;
+    chart
+        .configure_mesh()
+        .x_desc(contrast.effect_axis())
+        .y_desc("-log10 p (Welch)")
+        .draw()?;
+
+    let dashed = BLACK.mix(0.5);
+    for x in [-VOLCANO_DIFFERENCE, VOLCANO_DIFFERENCE] {
+        chart.draw_series(DashedLineSeries::new(vec![(x, 0.0), (x, y_max)], 6, 4, dashed.into()))?;
+    }
+    chart.draw_series(DashedLineSeries::new(vec![(-x_max, alpha_line), (x_max, alpha_line)], 6, 4, dashed.into()))?;
+
+    let hits: Vec<&MarkerContrast> = contrast.volcano_hits().collect();
+    let colour = |m: &MarkerContrast| match hits.iter().any(|h| h.column == m.column) {
+        true if m.difference > 0.0 => RED.mix(0.8),
+        true => BLUE.mix(0.8),
+        false => BLACK.mix(0.25),
+    };
+    chart.draw_series(
+        contrast
+            .markers
+            .iter()
+            .filter(|m| m.difference.is_finite() && height(m).is_finite())
+            .map(|m| Circle::new((m.difference, height(m)), 4, colour(m).filled())),
+    )?;
+    // Labels point towards the centre so they stay inside the plot
+    let label = ("sans-serif", 13).into_font().color(&BLACK);
+    chart.draw_series(hits.iter().take(VOLCANO_LABELS).map(|m| {
+        let side = if m.difference > 0.0 { HPos::Right } else { HPos::Left };
+        let style = label.clone().pos(Pos::new(side, VPos::Top));
+        Text::new(m.label.clone(), (m.difference, height(m)), style)
+    }))?;
+    root.present()?;
+    Ok(())
+}
+
+/// Mean and sample variance
+fn mean_var(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::legends::FigureLegend;
+
+    #[test]
+    fn test_contrast_two_levels_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        let csv = "sample_id,status,gene_a,gene_b\n\
+                   S1,old,1.0,2.0\nS2,old,2.0,NA\nS3,old,3.0,2.5\n\
+                   S4,young,4.0,2.2\nS5,Young,5.0,2.1\nS6,young,6.0,2.4\nS7,NA,9.0,9.0\n";
+        std::fs::write(&input, csv).unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+        let headers: Vec<String> = ["sample_id", "status", "gene_a", "gene_b"].map(String::from).to_vec();
+        let markers = [(2, "GENEA".to_string()), (3, "gene_b".to_string())];
+        let cancel = CancellationToken::new();
+
+        let contrast = analyze(&record, &headers, 1, &markers, Thresholds::default(), &cancel).unwrap().unwrap();
+        assert_eq!(contrast.groups, ["old", "young"]);
+        assert_eq!(contrast.samples, [3, 3]);
+        let gene_a = &contrast.markers[0];
+        assert_eq!((gene_a.label.as_str(), gene_a.n, gene_a.difference, gene_a.u), ("GENEA", [3, 3], 3.0, 9.0));
+        assert!((gene_a.t - 3.674_235).abs() < 1e-6 && (gene_a.cohens_d - 3.0).abs() < 1e-12);
+        assert_eq!(contrast.markers[1].n, [2, 3]);
+        assert!(contrast.results().starts_with("Between status groups (old: n = 3; young: n = 3), 1 of 2 markers"));
+
+        assert_eq!(contrast.volcano_hits().map(|m| m.label.as_str()).collect::<Vec<_>>(), ["GENEA"]);
+        assert_eq!(contrast.effect_axis(), "difference of means (young - old)");
+        let legend = FigureLegend::volcano("volcano.png", &contrast).body;
+        assert!(legend.contains("mark a difference of means of ±1") && !legend.contains("log2"));
+        let path = dir.path().join(VOLCANO_FILE);
+        write_volcano_plot(&path, &contrast).unwrap();
+        // PNG signature, then the IHDR width and height
+        let png = std::fs::read(&path).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!((&png[16..20], &png[20..24]), (&800u32.to_be_bytes()[..], &700u32.to_be_bytes()[..]));
+
+        let logged = GroupContrast { log2_scale: true, ..contrast.clone() };
+        assert_eq!(logged.effect_axis(), "log2 fold change (young / old)");
+        assert!(FigureLegend::volcano("volcano.png", &logged).body.contains("|log2 fold change| ≥ 1"));
+        write_volcano_plot(&path, &logged).unwrap();
+
+        std::fs::write(&input, format!("{}S8,middle,3.5,2.3\n", csv)).unwrap();
+        assert!(analyze(&record, &headers, 1, &markers, Thresholds::default(), &cancel).unwrap().is_none());
+    }
+}
@@ -0,0 +1,384 @@
+//! Expression heatmap
+//!
+//! The figure most expression papers show: the top markers (rows) across
+//! every sample (columns), each marker scaled to z-scores so that markers
+//! on different intensity scales share one colour range. Samples are
+//! ordered by group under a colour bar marking the group of each column.
+//! With a group column the markers are those whose group means differ most
+//! (the novelty score); otherwise the top-ranked biomarker candidates.
+//! Markers, and the samples within each group, are then ordered by
+//! hierarchical clustering, with their dendrograms drawn along the plot.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use plotters::prelude::*;
+
+use super::cluster::{self, Dendrogram};
+use crate::data_registry::DatasetRecord;
+use crate::models::{BiomarkerCandidate, NoveltyScore};
+use crate::utils::cancel::{self, CancellationToken};
+
+/// File name of the expression heatmap in the analysis directory
+pub const EXPRESSION_FILE: &str = "expression_heatmap.png";
+
+/// Markers shown in the expression heatmap
+pub const EXPRESSION_MARKERS: usize = 30;
+
+/// z-scores beyond this are drawn in the end colours of the scale
+pub const Z_LIMIT: f64 = 2.5;
+
+/// Groups named in the legend panel; the rest still get their colour
+const LEGEND_GROUPS: usize = 12;
+
+/// Label of samples with no group value
+const NO_GROUP: &str = "NA";
+
+/// Pixels of the sample dendrogram above the plot and the marker
+/// dendrogram left of it
+const TREE_SIZE: u32 = 80;
+
+/// Row-scaled values of the top markers, samples ordered by group
+#[derive(Debug, Clone)]
+pub struct ExpressionMatrix {
+    /// Marker columns in plot order
+    pub markers: Vec<String>,
+    /// Group of each sample in plot order, when grouped
+    pub groups: Option<Vec<String>>,
+    /// z-scores, one row per marker and one value per sample
+    pub values: Vec<Vec<Option<f64>>>,
+    /// Clustering the markers were ordered by
+    pub marker_tree: Option<Dendrogram>,
+    /// Per group of at least two samples, its first sample and the
+    /// clustering its samples were ordered by
+    pub sample_trees: Vec<(usize, Dendrogram)>,
+}
+
+impl ExpressionMatrix {
+    pub fn samples(&self) -> usize {
+        self.values.first().map_or(0, Vec::len)
+    }
+
+    /// Groups in plot order with their sample counts
+    pub fn group_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for group in self.groups.iter().flatten() {
+            match counts.last_mut() {
+                Some((last, n)) if last == group => *n += 1,
+                _ => counts.push((group.clone(), 1)),
+            }
+        }
+        counts
+    }
+
+    /// Reorder the markers, and the samples within each group, by
+    /// average-linkage clustering on correlation distance (1 - r). Groups
+    /// larger than [`cluster::MAX_LEAVES`] keep their file order.
+    fn cluster(&mut self) {
+        let markers = cluster::average_linkage(&cluster::correlation_distances(&self.values));
+        self.markers = markers.order.iter().map(|i| self.markers[*i].clone()).collect();
+        self.values = markers.order.iter().map(|i| self.values[*i].clone()).collect();
+        self.marker_tree = Some(markers);
+
+        let blocks: Vec<usize> = match self.groups {
+            Some(_) => self.group_counts().into_iter().map(|(_, n)| n).collect(),
+            None => vec![self.samples()],
+        };
+        let mut order = Vec::with_capacity(self.samples());
+        let mut start = 0;
+        for n in blocks {
+            if (2..=cluster::MAX_LEAVES).contains(&n) {
+                let profiles: Vec<Vec<Option<f64>>> =
+                    (start..start + n).map(|s| self.values.iter().map(|row| row[s]).collect()).collect();
+                let tree = cluster::average_linkage(&cluster::correlation_distances(&profiles));
+                order.extend(tree.order.iter().map(|i| start + i));
+                self.sample_trees.push((start, tree));
+            } else {
+                order.extend(start..start + n);
+            }
+            start += n;
+        }
+        for row in &mut self.values {
+            *row = order.iter().map(|s| row[*s]).collect();
+        }
+    }
+}
+
+/// Columns to show: the markers with the largest group differences when
+/// grouped, else the candidates in rank order. `target` is never shown.
+pub fn top_markers(
+    novelty: &[NoveltyScore],
+    candidates: &[BiomarkerCandidate],
+    grouped: bool,
+    target: Option<&str>,
+) -> Vec<String> {
+    let mut columns: Vec<(&str, f64)> = if grouped {
+        novelty.iter().filter(|n| n.score > 0.0).map(|n| (n.column.as_str(), n.score)).collect()
+    } else {
+        candidates.iter().map(|c| (c.column.as_str(), 0.0)).collect()
+    };
+    // Stable, so candidates keep their rank order
+    columns.sort_by(|a, b| b.1.total_cmp(&a.1));
+    columns
+        .into_iter()
+        .map(|(column, _)| column)
+        .filter(|column| Some(*column) != target)
+        .take(EXPRESSION_MARKERS)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Read `columns` for every sample of `record` and scale each to z-scores.
+/// None when fewer than two markers or two samples have values.
+pub fn build(
+    record: &DatasetRecord,
+    headers: &[String],
+    columns: &[String],
+    group_index: Option<usize>,
+    cancel: &CancellationToken,
+) -> Result<Option<ExpressionMatrix>> {
+    let index: HashMap<&str, usize> = headers.iter().enumerate().map(|(i, h)| (h.as_str(), i)).collect();
+    let markers: Vec<(&String, usize)> =
+        columns.iter().filter_map(|c| Some((c, *index.get(c.as_str())?))).collect();
+    if markers.len() < 2 {
+        return Ok(None);
+    }
+
+    let mut rdr = record.reader()?;
+    let mut samples: Vec<(String, Vec<Option<f64>>)> = Vec::new();
+    for (i, row) in rdr.records().enumerate() {
+        if i % super::CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let row = row?;
+        let values: Vec<Option<f64>> = markers
+            .iter()
+            .map(|(_, idx)| row.get(*idx).and_then(|v| record.number(v)))
+            .collect();
+        if values.iter().all(Option::is_none) {
+            continue;
+        }
+        let group = group_index
+            .and_then(|idx| row.get(idx))
+            .map(str::trim)
+            .filter(|g| !g.is_empty())
+            .unwrap_or(NO_GROUP);
+        samples.push((group.to_string(), values));
+    }
+    if samples.len() < 2 {
+        return Ok(None);
+    }
+    // Groups by label; `cluster` orders the samples within each group
+    if group_index.is_some() {
+        samples.sort_by(|a, b| (a.0 == NO_GROUP).cmp(&(b.0 == NO_GROUP)).then_with(|| a.0.cmp(&b.0)));
+    }
+
+    let values = (0..markers.len())
+        .map(|m| scale(samples.iter().map(|(_, values)| values[m]).collect()))
+        .collect();
+    let mut matrix = ExpressionMatrix {
+        markers: markers.into_iter().map(|(column, _)| column.clone()).collect(),
+        groups: group_index.map(|_| samples.into_iter().map(|(group, _)| group).collect()),
+        values,
+        marker_tree: None,
+        sample_trees: Vec::new(),
+    };
+    matrix.cluster();
+    Ok(Some(matrix))
+}
+
+/// z-scores of the present values; a constant marker scales to zero
+fn scale(values: Vec<Option<f64>>) -> Vec<Option<f64>> {
+    let present: Vec<f64> = values.iter().flatten().copied().collect();
+    let n = present.len() as f64;
+    let mean = present.iter().sum::<f64>() / n;
+    let sd = if present.len() > 1 {
+        (present.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+    } else {
+        0.0
+    };
+    values
+        .into_iter()
+        .map(|v| v.map(|v| if sd > 0.0 { (v - mean) / sd } else { 0.0 }))
+        .collect()
+}
+
+/// Blue (low) through white to red (high), saturating at [`Z_LIMIT`]
+fn z_colour(z: Option<f64>) -> RGBColor {
+    let Some(z) = z else {
+        return RGBColor(200, 200, 200);
+    };
+    let t = (z / Z_LIMIT).clamp(-1.0, 1.0);
+    let fade = |full: u8| (255.0 - (255.0 - full as f64) * t.abs()).round() as u8;
+    if t < 0.0 {
+        RGBColor(fade(33), fade(102), fade(172))
+    } else {
+        RGBColor(fade(178), fade(24), fade(43))
+    }
+}
+
+/// Draw `matrix` with one label per marker row, in the order of
+/// `matrix.markers`
+pub fn write_expression_heatmap(output_path: &Path, matrix: &ExpressionMatrix, labels: &[String]) -> Result<()> {
+    let markers = matrix.markers.len();
+    let samples = matrix.samples();
+    if markers == 0 || samples == 0 {
+        return Ok(());
+    }
+    let group_counts = matrix.group_counts();
+    let colour_of: HashMap<&str, PaletteColor<Palette99>> = group_counts
+        .iter()
+        .enumerate()
+        .map(|(i, (group, _))| (group.as_str(), Palette99::pick(i)))
+        .collect();
+    // Marker rows, a gap, then the group bar on top
+    let rows = if matrix.groups.is_some() { markers + 2 } else { markers };
+
+    let height = (160 + TREE_SIZE as usize + 22 * rows) as u32;
+    let root = BitMapBackend::new(output_path, (1100 + TREE_SIZE, height.max(400))).into_drawing_area();
+    root.fill(&WHITE)?;
+    let root = root.titled("Expression Heatmap", ("sans-serif", 24))?;
+    let (plot, side) = root.split_horizontally(900 + TREE_SIZE);
+    let (top, body) = plot.split_vertically(TREE_SIZE);
+    let (left, plot) = body.split_horizontally(TREE_SIZE);
+
+    // Marker labels on the right, so the marker dendrogram meets the rows
+    let mut chart = ChartBuilder::on(&plot)
+        .margin(20)
+        .margin_left(0)
+        .margin_top(0)
+        .x_label_area_size(30)
+        .right_y_label_area_size(140)
+        .build_cartesian_2d(0f64..samples as f64, (0..rows - 1).into_segmented())?;
+
+    // The top marker is drawn at the top, under the group bar
+    let cell = |sample: usize, y: usize| {
+        [(sample as f64, SegmentValue::Exact(y)), (sample as f64 + 1.0, SegmentValue::Exact(y + 1))]
+    };
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_labels(0)
+        .x_desc(format!("{} samples", samples))
+        .y_labels(rows)
+        .y_label_formatter(&|y| match y {
+            SegmentValue::CenterOf(y) => match markers.checked_sub(1 + y) {
+                Some(idx) => labels.get(idx).cloned().unwrap_or_default(),
+                None if *y == markers + 1 => "group".to_string(),
+                None => String::new(),
+            },
+            _ => String::new(),
+        })
+        .draw()?;
+
+    for (idx, values) in matrix.values.iter().enumerate() {
+        let y = markers - 1 - idx;
+        chart.draw_series(values.iter().enumerate().map(|(s, z)| Rectangle::new(cell(s, y), z_colour(*z).filled())))?;
+    }
+    if let Some(groups) = &matrix.groups {
+        chart.draw_series(groups.iter().enumerate().map(|(s, group)| {
+            Rectangle::new(cell(s, markers + 1), colour_of[group.as_str()].filled())
+        }))?;
+    }
+
+    // Dendrograms: samples above the top row, leaves down; markers left of
+    // the rows, leaves right. Margins match the heatmap's so leaves line up.
+    let sample_height = matrix.sample_trees.iter().map(|(_, tree)| tree.height()).fold(f64::EPSILON, f64::max);
+    let mut samples_tree = ChartBuilder::on(&top)
+        .margin(20)
+        .margin_left(TREE_SIZE)
+        .margin_top(10)
+        .margin_bottom(0)
+        .right_y_label_area_size(140)
+        .build_cartesian_2d(0f64..samples as f64, 0f64..sample_height)?;
+    for (start, tree) in &matrix.sample_trees {
+        let lines = tree.lines(*start as f64);
+        samples_tree.draw_series(lines.into_iter().map(|line| PathElement::new(line.to_vec(), BLACK)))?;
+    }
+    if let Some(tree) = &matrix.marker_tree {
+        let mut markers_tree = ChartBuilder::on(&left)
+            .margin(20)
+            .margin_top(0)
+            .margin_left(10)
+            .margin_right(0)
+            .x_label_area_size(30)
+            .build_cartesian_2d(-tree.height().max(f64::EPSILON)..0f64, 0f64..rows as f64)?;
+        // Row p of the plot order is drawn `p` rows below the top marker
+        let point = |(position, height): (f64, f64)| (-height, markers as f64 - position);
+        markers_tree.draw_series(
+            tree.lines(0.0).into_iter().map(|line| PathElement::new(line.map(point).to_vec(), BLACK)),
+        )?;
+    }
+
+    // Legend panel: the groups, then the colour scale
+    let text = ("sans-serif", 14).into_font();
+    let mut y = 60;
+    for (group, n) in group_counts.iter().take(LEGEND_GROUPS) {
+        side.draw(&Rectangle::new([(10, y), (24, y + 14)], colour_of[group.as_str()].filled()))?;
+        side.draw(&Text::new(format!("{} (n = {})", group, n), (30, y), text.clone()))?;
+        y += 20;
+    }
+    if group_counts.len() > LEGEND_GROUPS {
+        side.draw(&Text::new(format!("{} more groups", group_counts.len() - LEGEND_GROUPS), (10, y), text.clone()))?;
+        y += 20;
+    }
+    y += 20;
+    side.draw(&Text::new("z-score", (10, y), text.clone()))?;
+    y += 20;
+    let steps = 50;
+    for step in 0..steps {
+        let z = Z_LIMIT - 2.0 * Z_LIMIT * step as f64 / (steps - 1) as f64;
+        let top = y + 3 * step;
+        side.draw(&Rectangle::new([(10, top), (30, top + 3)], z_colour(Some(z)).filled()))?;
+    }
+    side.draw(&Text::new(format!("{}", Z_LIMIT), (36, y), text.clone()))?;
+    side.draw(&Text::new("0", (36, y + 3 * steps / 2 - 7), text.clone()))?;
+    side.draw(&Text::new(format!("-{}", Z_LIMIT), (36, y + 3 * steps - 14), text.clone()))?;
+    side.draw(&Rectangle::new([(10, y + 3 * steps + 14), (30, y + 3 * steps + 28)], z_colour(None).filled()))?;
+    side.draw(&Text::new("missing", (36, y + 3 * steps + 14), text))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn novelty(column: &str, score: f64) -> NoveltyScore {
+        NoveltyScore { column: column.to_string(), score, rationale: String::new(), p_value: None, q_value: None }
+    }
+
+    #[test]
+    fn test_expression_heatmap() {
+        let scores = [novelty("age", 0.9), novelty("gene_a", 0.2), novelty("gene_b", 0.6), novelty("gene_c", 0.0)];
+        assert_eq!(top_markers(&scores, &[], true, Some("age")), ["gene_b", "gene_a"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        let csv = "sample_id,group,gene_a,gene_b\n\
+                   S1,treated,1.0,5.0\nS2,control,2.0,5.0\nS3,treated,3.0,NA\nS4,,4.0,5.0\nS5,control,NA,NA\n";
+        std::fs::write(&input, csv).unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+        let headers: Vec<String> = ["sample_id", "group", "gene_a", "gene_b"].map(String::from).to_vec();
+        let columns = ["gene_b".to_string(), "gene_a".to_string()];
+        let matrix = build(&record, &headers, &columns, Some(1), &CancellationToken::new()).unwrap().unwrap();
+
+        // S5 has no values; the sample without a group goes last
+        assert_eq!(matrix.samples(), 4);
+        assert_eq!(matrix.group_counts(), [("control".to_string(), 1), ("treated".to_string(), 2), ("NA".to_string(), 1)]);
+        // gene_b is constant, gene_a runs 2, 1, 3, 4 in plot order
+        assert_eq!(matrix.values[0], [Some(0.0), Some(0.0), None, Some(0.0)]);
+        let gene_a: Vec<f64> = matrix.values[1].iter().flatten().copied().collect();
+        assert!(gene_a.iter().sum::<f64>().abs() < 1e-9);
+        assert!(gene_a[2] > gene_a[0] && gene_a[0] > gene_a[1]);
+        // Only the treated group has two samples to cluster
+        assert_eq!(matrix.marker_tree.as_ref().unwrap().order, [0, 1]);
+        assert_eq!(matrix.sample_trees.len(), 1);
+        assert_eq!(matrix.sample_trees[0].0, 1);
+
+        let path = dir.path().join(EXPRESSION_FILE);
+        write_expression_heatmap(&path, &matrix, &columns).unwrap();
+        assert!(path.exists());
+    }
+}
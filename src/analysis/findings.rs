@@ -0,0 +1,482 @@
+//! Structured findings of an analysis run
+//!
+//! [`Findings`] holds what a write-up quotes: how many markers were tested
+//! and passed the significance thresholds, the top candidates with their
+//! statistics (those passing listed first), the
+//! regression fit, the enriched GO terms and the figures, numbered as in the
+//! manuscript. The template manuscript's Results, `summary.json`, the API
+//! response and the TUI's findings view are all rendered from it, and
+//! [`Findings::misquoted`] checks that a revised draft still quotes the
+//! computed values.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::legends::Figure;
+use super::AnalysisArtifacts;
+use crate::utils::number::{self, NumberFormat};
+
+/// Top candidates carried in the findings
+pub const TOP_MARKERS: usize = 10;
+
+/// Enriched GO terms carried in the findings
+const TOP_TERMS: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Findings {
+    /// Markers with descriptive statistics
+    pub markers_tested: usize,
+    /// The thresholds as quoted, "q < 0.05"
+    pub criterion: String,
+    /// Candidates passing the thresholds
+    pub significant: usize,
+    pub top_markers: Vec<TopMarker>,
+    /// First regression model, if one was fit
+    pub regression: Option<RegressionFit>,
+    /// GO terms passing the p- and q-value cut-offs, most significant first
+    pub enriched_terms: Vec<EnrichedTerm>,
+    pub figures: Vec<FigureRef>,
+    /// Separators of the numbers in [`Findings::to_markdown`]
+    #[serde(skip)]
+    pub numbers: NumberFormat,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopMarker {
+    /// 1-based
+    pub rank: usize,
+    pub column: String,
+    /// Gene symbol (with the human ortholog for mouse and rat) or column
+    pub label: String,
+    pub n: usize,
+    pub r: f64,
+    pub p_value: f64,
+    pub q_value: f64,
+    /// Area under the ROC curve, when the target was binary or split
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auc: Option<f64>,
+    pub direction: String,
+    /// Passes the significance thresholds
+    pub significant: bool,
+    /// "previously reported" / "novel" once cross-referenced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evidence: Option<String>,
+    /// Publications found by cross-referencing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publications: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegressionFit {
+    pub target: String,
+    pub predictors: Vec<String>,
+    pub r2: f64,
+    pub n: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnrichedTerm {
+    pub term_id: String,
+    pub term_name: String,
+    pub genes: usize,
+    pub fold_enrichment: f64,
+    pub q_value: f64,
+}
+
+/// A figure of the run, numbered in manuscript order
+#[derive(Debug, Clone, Serialize)]
+pub struct FigureRef {
+    /// "Figure 1", ...
+    pub label: String,
+    pub caption: String,
+    pub path: String,
+}
+
+impl Findings {
+    pub fn from_analysis(analysis: &AnalysisArtifacts) -> Self {
+        let thresholds = &analysis.thresholds;
+        let top_markers = analysis
+            .biomarker_candidates
+            .iter()
+            .take(TOP_MARKERS)
+            .enumerate()
+            .map(|(i, b)| TopMarker {
+                rank: i + 1,
+                column: b.column.clone(),
+                label: b.display_label(),
+                n: b.n,
+                r: b.correlation,
+                p_value: b.p_value,
+                q_value: b.q_value,
+                auc: b.auc,
+                direction: b.direction.clone(),
+                significant: thresholds.passes(b.p_value, b.q_value, b.correlation),
+                evidence: b.prior_evidence.as_ref().map(|e| e.status.describe().to_string()),
+                publications: b.prior_evidence.as_ref().map(|e| e.publications),
+            })
+            .collect();
+        let mut enriched: Vec<_> = analysis
+            .enrichment
+            .iter()
+            .filter(|e| thresholds.significant(e.p_value, e.q_value))
+            .collect();
+        enriched.sort_by(|a, b| a.q_value.total_cmp(&b.q_value));
+        let figures = [
+            (Figure::Heatmap, &analysis.heatmap_path, "Heatmap of the analyzed markers"),
+            (Figure::Expression, &analysis.expression_heatmap_path, "Expression of the top markers across samples"),
+            (Figure::Boxplot, &analysis.boxplot_path, "Marker distribution by group"),
+            (Figure::Volcano, &analysis.volcano_path, "Markers differing between the two groups"),
+            (Figure::Roc, &analysis.roc_path, "ROC curves of the best-discriminating candidates"),
+            (Figure::Survival, &analysis.survival_path, "Kaplan-Meier survival curves"),
+            (Figure::Enrichment, &analysis.enrichment_plot_path, "GO terms enriched among the significant biomarkers"),
+            (Figure::Scree, &analysis.pca_scree_path, "Variance explained by the principal components"),
+            (Figure::Scores, &analysis.pca_scores_path, "Samples on the first two principal components"),
+        ]
+        .into_iter()
+        .filter_map(|(kind, path, caption)| Some((kind, path.as_ref()?, caption)))
+        .enumerate()
+        .map(|(i, (kind, path, caption))| FigureRef {
+            label: format!("Figure {}", i + 1),
+            caption: analysis
+                .figure_legends
+                .iter()
+                .find(|legend| legend.figure == kind)
+                .map_or_else(|| caption.to_string(), |legend| legend.title.clone()),
+            path: path.clone(),
+        })
+        .collect();
+
+        Self {
+            markers_tested: analysis.descriptive_stats.len(),
+            criterion: thresholds.marker_criterion(),
+            significant: analysis
+                .biomarker_candidates
+                .iter()
+                .filter(|b| thresholds.passes(b.p_value, b.q_value, b.correlation))
+                .count(),
+            top_markers,
+            regression: analysis.regressions.first().map(|r| RegressionFit {
+                target: r.target.clone(),
+                predictors: r.predictors.clone(),
+                r2: r.r2,
+                n: r.n,
+            }),
+            enriched_terms: enriched
+                .into_iter()
+                .take(TOP_TERMS)
+                .map(|e| EnrichedTerm {
+                    term_id: e.term_id.clone(),
+                    term_name: e.term_name.clone(),
+                    genes: e.genes.len(),
+                    fold_enrichment: e.fold_enrichment,
+                    q_value: e.q_value,
+                })
+                .collect(),
+            figures,
+            numbers: analysis.numbers,
+        }
+    }
+
+    /// Results paragraph of the template manuscript
+    pub fn results_text(&self) -> String {
+        let mut text = format!(
+            "Of {} markers tested, {} candidate(s) reached {}.",
+            self.markers_tested, self.significant, self.criterion
+        );
+        if let Some(fit) = &self.regression {
+            text.push_str(&format!(
+                " The regression of {} on {} explained R² = {:.3} of its variance (n = {}).",
+                fit.target,
+                fit.predictors.join(", "),
+                fit.r2,
+                fit.n
+            ));
+        }
+        if self.top_markers.is_empty() {
+            text.push_str(" No biomarker candidates were identified.");
+        } else {
+            let (passing, below): (Vec<&TopMarker>, Vec<&TopMarker>) =
+                self.top_markers.iter().partition(|m| m.significant);
+            let quote = |markers: Vec<&TopMarker>| markers.into_iter().map(TopMarker::quote).collect::<Vec<_>>().join(", ");
+            match (passing.is_empty(), below.is_empty()) {
+                (false, true) => text.push_str(&format!(" Top biomarker candidates: {}.", quote(passing))),
+                (true, _) => text.push_str(&format!(
+                    " Top biomarker candidates, none passing the thresholds: {}.",
+                    quote(below)
+                )),
+                (false, false) => text.push_str(&format!(
+                    " Top biomarker candidates: {}; below the thresholds: {}.",
+                    quote(passing),
+                    quote(below)
+                )),
+            }
+        }
+        if !self.enriched_terms.is_empty() {
+            let terms: Vec<String> = self
+                .enriched_terms
+                .iter()
+                .map(|t| format!("{} ({:.1}-fold, {})", t.term_name, t.fold_enrichment, number::p_quote("q", t.q_value)))
+                .collect();
+            text.push_str(&format!(" Enriched GO terms: {}.", terms.join(", ")));
+        }
+        if !self.figures.is_empty() {
+            let figures: Vec<String> = self
+                .figures
+                .iter()
+                .map(|f| format!("{} ({})", f.label, f.caption.to_lowercase()))
+                .collect();
+            text.push_str(&format!(" See {}.", figures.join("; ")));
+        }
+        text
+    }
+
+    /// Markdown view for the TUI: counts, the top marker table and figures
+    pub fn to_markdown(&self) -> String {
+        let numbers = self.numbers;
+        let mut text = format!(
+            "Markers tested: {} | {}: {}",
+            numbers.integer(self.markers_tested),
+            self.criterion,
+            numbers.integer(self.significant)
+        );
+        if let Some(fit) = &self.regression {
+            text.push_str(&format!(" | R² ({}): {}", fit.target, numbers.fixed(fit.r2, 3)));
+        }
+        if !self.top_markers.is_empty() {
+            let roc = self.top_markers.iter().any(|m| m.auc.is_some());
+            if roc {
+                text.push_str(
+                    "\n\n| # | Marker | n | r | q | AUC | Direction | Literature |\n|---|---|---|---|---|---|---|---|",
+                );
+            } else {
+                text.push_str("\n\n| # | Marker | n | r | q | Direction | Literature |\n|---|---|---|---|---|---|---|");
+            }
+            for m in &self.top_markers {
+                let literature = match (&m.evidence, m.publications) {
+                    (Some(evidence), Some(publications)) => format!("{} ({})", evidence, publications),
+                    _ => "-".to_string(),
+                };
+                let auc = match (roc, m.auc) {
+                    (false, _) => String::new(),
+                    (true, Some(auc)) => format!(" {} |", numbers.fixed(auc, 3)),
+                    (true, None) => " - |".to_string(),
+                };
+                text.push_str(&format!(
+                    "\n| {}{} | {} | {} | {} | {} |{} {} | {} |",
+                    m.rank,
+                    if m.significant { "*" } else { "" },
+                    m.label,
+                    numbers.integer(m.n),
+                    numbers.fixed(m.r, 3),
+                    numbers.p_value(m.q_value),
+                    auc,
+                    m.direction,
+                    literature
+                ));
+            }
+        }
+        for term in &self.enriched_terms {
+            text.push_str(&format!(
+                "\nGO {} {}: {} genes, {}-fold, {}",
+                term.term_id,
+                term.term_name,
+                numbers.integer(term.genes),
+                numbers.fixed(term.fold_enrichment, 1),
+                numbers.p_quote("q", term.q_value)
+            ));
+        }
+        for figure in &self.figures {
+            let name = Path::new(&figure.path).file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+            text.push_str(&format!("\n{}: {} ({})", figure.label, figure.caption, name));
+        }
+        text
+    }
+
+    /// Quotes in `text` of a top marker's r or q that differ from the
+    /// computed value, described for a log message; empty when every quoted
+    /// number matches (markers the text does not quote are fine)
+    pub fn misquoted(&self, text: &str) -> Vec<String> {
+        let labels: Vec<&str> = self.top_markers.iter().map(|m| m.label.as_str()).collect();
+        let mut problems = Vec::new();
+        for marker in &self.top_markers {
+            for start in mentions(text, &marker.label) {
+                let window = quote_window(&text[start + marker.label.len()..], &labels);
+                for (name, computed, shown) in [
+                    ('r', marker.r, format!("{:.3}", marker.r)),
+                    ('q', marker.q_value, number::p_value(marker.q_value)),
+                ] {
+                    if let Some(quote) = quoted_value(window, name).filter(|quote| !quote.agrees(computed)) {
+                        problems.push(format!("{}: {} quoted, {} computed", marker.label, quote.text, shown));
+                    }
+                }
+            }
+        }
+        problems
+    }
+}
+
+impl TopMarker {
+    /// "TP53 (r = 0.812, q < 0.001, AUC = 0.904, up, novel)" as quoted in
+    /// the manuscript
+    fn quote(&self) -> String {
+        let auc = self.auc.map(|auc| format!(", AUC = {:.3}", auc)).unwrap_or_default();
+        let evidence = self.evidence.as_ref().map(|e| format!(", {}", e)).unwrap_or_default();
+        format!(
+            "{} (r = {:.3}, {}{}, {}{})",
+            self.label,
+            self.r,
+            number::p_quote("q", self.q_value),
+            auc,
+            self.direction,
+            evidence
+        )
+    }
+}
+
+impl AnalysisArtifacts {
+    /// Findings of this run, from its current tables (so cross-referencing
+    /// done after the analysis is included)
+    pub fn findings(&self) -> Findings {
+        Findings::from_analysis(self)
+    }
+}
+
+/// A statistic quoted in a draft
+struct Quote {
+    /// "r = 0.81", "q < 0.05"
+    text: String,
+    /// Quoted as an upper bound
+    bound: bool,
+    value: f64,
+    /// Digits after the decimal point, `None` in scientific notation
+    decimals: Option<usize>,
+}
+
+impl Quote {
+    /// Whether `computed` rounds to the quoted value (or is below a quoted
+    /// bound)
+    fn agrees(&self, computed: f64) -> bool {
+        if self.bound {
+            return computed < self.value;
+        }
+        match self.decimals {
+            Some(decimals) => (computed - self.value).abs() <= 0.5 * 10f64.powi(-(decimals as i32)) + 1e-9,
+            // Two significant digits, as in older drafts
+            None => (computed - self.value).abs() <= 0.051 * computed.abs().max(self.value.abs()),
+        }
+    }
+}
+
+/// Byte offsets where `label` occurs as a whole word
+fn mentions<'a>(text: &'a str, label: &'a str) -> impl Iterator<Item = usize> + 'a {
+    text.match_indices(label).map(|(i, _)| i).filter(move |&i| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + label.len()..].chars().next();
+        !before.is_some_and(|c| c.is_alphanumeric()) && !after.is_some_and(|c| c.is_alphanumeric())
+    })
+}
+
+/// The text following a marker's name that can hold its statistics: up to
+/// the closing parenthesis, line end or the next marker's name
+fn quote_window<'a>(rest: &'a str, labels: &[&str]) -> &'a str {
+    let mut end = rest.find([')', '\n']).unwrap_or(rest.len()).min(80);
+    while !rest.is_char_boundary(end) {
+        end -= 1;
+    }
+    let window = &rest[..end];
+    let next_label = labels
+        .iter()
+        .filter_map(|label| mentions(window, label).next())
+        .min()
+        .unwrap_or(window.len());
+    &window[..next_label]
+}
+
+/// The statistic quoted as `r = ...` / `q=...` / `q < ...` in `window`
+fn quoted_value(window: &str, name: char) -> Option<Quote> {
+    for (i, c) in window.char_indices() {
+        let standalone = !window[..i].chars().next_back().is_some_and(|p| p.is_alphanumeric());
+        if c != name || !standalone {
+            continue;
+        }
+        let rest = window[i + 1..].trim_start();
+        let Some(relation) = rest.chars().next().filter(|c| matches!(c, '=' | '<' | ':')) else {
+            continue;
+        };
+        let number: String = rest[1..]
+            .trim_start()
+            .replace('−', "-")
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | 'e' | 'E' | '+'))
+            .collect();
+        let number = number.trim_end_matches(['.', 'e', 'E', '-', '+']);
+        if let Ok(value) = number.parse() {
+            let scientific = number.contains(['e', 'E']);
+            return Some(Quote {
+                text: format!("{} {} {}", name, if relation == '<' { '<' } else { '=' }, number),
+                bound: relation == '<',
+                value,
+                decimals: (!scientific).then(|| number.split_once('.').map_or(0, |(_, d)| d.len())),
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn findings() -> Findings {
+        let marker = |rank: usize, label: &str, r: f64, q_value: f64, significant: bool| TopMarker {
+            rank,
+            column: label.to_string(),
+            label: label.to_string(),
+            n: 80,
+            r,
+            p_value: q_value / 2.0,
+            q_value,
+            auc: None,
+            direction: "up".to_string(),
+            significant,
+            evidence: None,
+            publications: None,
+        };
+        Findings {
+            markers_tested: 50,
+            criterion: "q < 0.05 and |r| ≥ 0.5".to_string(),
+            significant: 1,
+            top_markers: vec![
+                marker(1, "CD4", 0.812, 0.00002, true),
+                marker(2, "CD44", -0.431, 0.012, false),
+            ],
+            regression: None,
+            enriched_terms: Vec::new(),
+            figures: vec![FigureRef {
+                label: "Figure 1".to_string(),
+                caption: "Heatmap of the analyzed markers".to_string(),
+                path: "out/heatmap.png".to_string(),
+            }],
+            numbers: NumberFormat::Plain,
+        }
+    }
+
+    #[test]
+    fn test_results_text_quotes_match() {
+        let findings = findings();
+        let results = findings.results_text();
+        assert!(results.contains("CD4 (r = 0.812, q < 0.001, up)"), "{}", results);
+        assert!(results.contains("1 candidate(s) reached q < 0.05 and |r| ≥ 0.5"));
+        assert!(results.contains("; below the thresholds: CD44 (r = -0.431"), "{}", results);
+        assert!(findings.to_markdown().contains("| 1* | CD4 |"));
+        assert!(results.contains("Figure 1 (heatmap of the analyzed markers)"));
+        assert!(findings.misquoted(&results).is_empty());
+
+        // Rounded quotes and bounds are fine
+        assert!(findings.misquoted("CD4 rises with age (r = 0.81), CD44 falls (r=−0.431, q < 0.05)").is_empty());
+        assert_eq!(findings.misquoted("CD44 and CD4 (r = 0.9)"), ["CD4: r = 0.9 quoted, 0.812 computed"]);
+        assert_eq!(findings.misquoted("CD44 (q = 0.12)"), ["CD44: q = 0.12 quoted, 0.012 computed"]);
+        assert_eq!(findings.misquoted("CD4 (q = 3.1e-5)"), ["CD4: q = 3.1e-5 quoted, <0.001 computed"]);
+    }
+}
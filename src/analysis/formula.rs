@@ -0,0 +1,504 @@
+//! Model formulas and covariate adjustment
+//!
+//! An R-style formula such as `age ~ marker + sex + batch + bmi` names the
+//! target and the covariates every marker is adjusted for. `marker` stands
+//! for each tested marker in turn. Numeric covariates enter the design
+//! matrix as they are; categorical ones (any covariate with a non-numeric
+//! value) as 0/1 indicators of each level against the first level in sort
+//! order. Candidates are then ranked by the partial correlation of marker
+//! and target given the covariates, `t / sqrt(t^2 + df)` from the t test on
+//! the marker coefficient.
+//!
+//! [`PRESETS`] name common adjustment sets, so `formula=demographics` reads
+//! as `<target> ~ marker + sex + bmi`.
+//!
+//! With a subject column the models become mixed models with a random
+//! intercept per subject (see [`mixed`]), so repeated samples of one subject
+//! are not counted as independent evidence.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use nalgebra::{DMatrix, DVector};
+use serde::Serialize;
+use tracing::warn;
+
+use super::{mixed, ols_fit, sex, stats};
+use crate::data_registry::{ColumnKind, DatasetRecord};
+use crate::models::{BiomarkerCandidate, RegressionResult};
+use crate::utils::cancel::{self, CancellationToken};
+
+/// Formula term standing for each tested marker
+pub const MARKER: &str = "marker";
+
+/// Named covariate sets; the target is taken from the analysis
+pub const PRESETS: [(&str, &[&str]); 4] = [
+    ("sex", &["sex"]),
+    ("batch", &["batch"]),
+    ("demographics", &["sex", "bmi"]),
+    ("full", &["sex", "bmi", "batch"]),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formula {
+    pub response: String,
+    /// Covariate columns, in formula order
+    pub covariates: Vec<String>,
+    /// Whether `marker` is a term; without it only the covariate model is
+    /// fitted and the candidates stay unadjusted
+    pub marker: bool,
+}
+
+impl Formula {
+    /// `spec` as a formula, or as the preset of that name with `target` as
+    /// the response
+    pub fn resolve(spec: &str, target: &str) -> Result<Self, String> {
+        if spec.contains('~') {
+            return spec.parse();
+        }
+        let name = spec.trim().to_ascii_lowercase();
+        let (_, covariates) = PRESETS.iter().find(|(preset, _)| *preset == name).ok_or_else(|| {
+            let names: Vec<&str> = PRESETS.iter().map(|(preset, _)| *preset).collect();
+            format!(
+                "unknown formula preset '{}' (expected 'target ~ marker + covariate' or one of {})",
+                spec.trim(),
+                names.join(", ")
+            )
+        })?;
+        Ok(Self {
+            response: target.to_string(),
+            covariates: covariates.iter().map(|c| c.to_string()).collect(),
+            marker: true,
+        })
+    }
+}
+
+impl FromStr for Formula {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((response, terms)) = s.split_once('~') else {
+            return Err(format!("formula '{}' has no '~' (expected 'target ~ marker + covariate')", s.trim()));
+        };
+        let response = response.trim();
+        if response.is_empty() || response.contains('+') {
+            return Err(format!("formula '{}' needs a single target column left of '~'", s.trim()));
+        }
+        let mut formula = Self {
+            response: response.to_string(),
+            covariates: Vec::new(),
+            marker: false,
+        };
+        for term in terms.split('+').map(str::trim) {
+            match term {
+                "1" => {}
+                "" => return Err(format!("formula '{}' has an empty term", s.trim())),
+                MARKER => formula.marker = true,
+                _ if term.contains([':', '*', '(', '^', '-', '~']) => {
+                    return Err(format!("formula term '{}' is not supported (only main effects are)", term));
+                }
+                _ if term == response => return Err(format!("target '{}' cannot also be a covariate", term)),
+                _ if formula.covariates.iter().any(|c| c == term) => {}
+                _ => formula.covariates.push(term.to_string()),
+            }
+        }
+        if !formula.marker && formula.covariates.is_empty() {
+            return Err(format!("formula '{}' has no terms", s.trim()));
+        }
+        Ok(formula)
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<&str> = self
+            .marker
+            .then_some(MARKER)
+            .into_iter()
+            .chain(self.covariates.iter().map(String::as_str))
+            .collect();
+        write!(f, "{} ~ {}", self.response, terms.join(" + "))
+    }
+}
+
+/// How the candidates were adjusted, for the report and the methods
+#[derive(Debug, Clone, Serialize)]
+pub struct Adjustment {
+    pub formula: String,
+    /// Design-matrix columns of the covariates, `column[level]` for the
+    /// indicators of a categorical covariate
+    pub terms: Vec<String>,
+    /// Samples with the target and every covariate
+    pub samples: usize,
+    /// Candidates whose adjusted model could not be fitted (too few samples
+    /// or a singular design) and were left out of the ranking
+    pub dropped: usize,
+    /// Random intercept per subject, when a subject column was given
+    pub repeated: Option<Repeated>,
+}
+
+/// The subjects of a mixed-model adjustment
+#[derive(Debug, Clone, Serialize)]
+pub struct Repeated {
+    pub column: String,
+    /// Subjects among the samples with the target and every covariate
+    pub subjects: usize,
+    /// Share of the target's residual variance between subjects in the
+    /// covariate model; `None` when it could not be fitted
+    pub icc: Option<f64>,
+}
+
+impl Adjustment {
+    /// Methods sentences replacing the unadjusted ranking
+    pub fn methods(&self, target: &str) -> String {
+        if let Some(repeated) = &self.repeated {
+            return format!(
+                "Biomarker candidates were ranked by their partial correlation with {target} in the linear mixed \
+                 model {formula}, fitted by REML for each marker on the {samples} samples from {subjects} subjects \
+                 with complete covariates (a random intercept per {column} for repeated samples; categorical \
+                 covariates coded as indicators against their first level); two-sided p-values from the Wald t test \
+                 on the marker coefficient with subjects minus fixed effects as degrees of freedom{icc}",
+                formula = self.formula,
+                samples = self.samples,
+                subjects = repeated.subjects,
+                column = repeated.column,
+                icc = match repeated.icc {
+                    Some(icc) => format!(". Differences between subjects accounted for {:.0}% of the residual variance of {target}", icc * 100.0),
+                    None => String::new(),
+                },
+            );
+        }
+        format!(
+            "Biomarker candidates were ranked by their partial correlation with {target} in the linear model \
+             {formula}, fitted for each marker on the {samples} samples with complete covariates (categorical \
+             covariates coded as indicators against their first level); two-sided p-values from the t test on \
+             the marker coefficient",
+            formula = self.formula,
+            samples = self.samples,
+        )
+    }
+}
+
+/// One covariate's design-matrix encoding
+enum Encoding {
+    Numeric,
+    /// Sorted levels; the first is the reference and gets no indicator
+    Levels(Vec<String>),
+}
+
+impl Encoding {
+    fn of(values: &[Option<&str>], kind: Option<ColumnKind>) -> Self {
+        let present = values.iter().flatten();
+        let numeric = kind != Some(ColumnKind::Categorical)
+            && present.clone().all(|v| v.parse::<f64>().is_ok_and(f64::is_finite));
+        if numeric {
+            return Encoding::Numeric;
+        }
+        let levels: BTreeSet<&str> = present.copied().collect();
+        Encoding::Levels(levels.into_iter().map(str::to_string).collect())
+    }
+
+    fn terms(&self, column: &str) -> Vec<String> {
+        match self {
+            Encoding::Numeric => vec![column.to_string()],
+            Encoding::Levels(levels) => levels.iter().skip(1).map(|l| format!("{}[{}]", column, l)).collect(),
+        }
+    }
+
+    fn push(&self, value: &str, row: &mut Vec<f64>) {
+        match self {
+            Encoding::Numeric => row.push(value.parse().unwrap_or(f64::NAN)),
+            Encoding::Levels(levels) => row.extend(levels.iter().skip(1).map(|l| f64::from(u8::from(l == value)))),
+        }
+    }
+}
+
+/// Fit `formula` on the dataset: the covariate model of the response, and,
+/// when the formula has a `marker` term, each candidate's adjusted
+/// association, which replaces its correlation, p- and q-value and reorders
+/// `candidates`. With a `subject` column every model gets a random
+/// intercept per subject and samples without a subject are left out.
+pub fn adjust(
+    record: &DatasetRecord,
+    headers: &[String],
+    formula: &Formula,
+    subject: Option<&str>,
+    candidates: &mut Vec<BiomarkerCandidate>,
+    cancel: &CancellationToken,
+) -> Result<(Adjustment, Option<RegressionResult>)> {
+    let index: HashMap<&str, usize> = headers.iter().enumerate().map(|(i, h)| (h.as_str(), i)).collect();
+    let missing: Vec<&str> = std::iter::once(formula.response.as_str())
+        .chain(formula.covariates.iter().map(String::as_str))
+        .chain(subject)
+        .filter(|c| !index.contains_key(c))
+        .collect();
+    if !missing.is_empty() {
+        bail!("Formula column(s) not found: {}", missing.join(", "));
+    }
+    let target_index = index[formula.response.as_str()];
+    let covariate_indices: Vec<usize> = formula.covariates.iter().map(|c| index[c.as_str()]).collect();
+    let marker_indices: Vec<Option<usize>> = candidates.iter().map(|c| index.get(c.column.as_str()).copied()).collect();
+    let subject_index = subject.map(|s| index[s]);
+
+    let mut rdr = record.reader()?;
+    let mut subject_ids: HashMap<String, usize> = HashMap::new();
+    // Subject of each row, when mixed
+    let mut subjects: Vec<usize> = Vec::new();
+    let mut targets: Vec<f64> = Vec::new();
+    let mut raw: Vec<Vec<Option<String>>> = Vec::new();
+    let mut markers: Vec<Vec<Option<f64>>> = Vec::new();
+    for (i, row) in rdr.records().enumerate() {
+        if i % super::CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let row = row?;
+        let value = |idx: usize| row.get(idx).and_then(|v| record.number(v));
+        let Some(target) = value(target_index) else { continue };
+        if let Some(idx) = subject_index {
+            let label = row.get(idx).map(str::trim).unwrap_or_default();
+            if label.is_empty() || record.parsing.is_missing(label) {
+                continue;
+            }
+            let next = subject_ids.len();
+            subjects.push(*subject_ids.entry(label.to_string()).or_insert(next));
+        }
+        targets.push(target);
+        raw.push(
+            covariate_indices
+                .iter()
+                .map(|idx| {
+                    let v = row.get(*idx).map(str::trim).unwrap_or_default();
+                    if sex::MISSING.contains(&v.to_ascii_lowercase().as_str()) || record.parsing.is_missing(v) {
+                        return None;
+                    }
+                    // Numbers as written with a decimal point, whatever the file's separator
+                    Some(record.number(v).map_or_else(|| v.to_string(), |n| n.to_string()))
+                })
+                .collect(),
+        );
+        markers.push(marker_indices.iter().map(|idx| idx.and_then(value)).collect());
+    }
+
+    let encodings: Vec<Encoding> = covariate_indices
+        .iter()
+        .enumerate()
+        .map(|(pos, idx)| {
+            let values: Vec<Option<&str>> = raw.iter().map(|r| r[pos].as_deref()).collect();
+            Encoding::of(&values, record.column_kind(*idx))
+        })
+        .collect();
+    let terms: Vec<String> = encodings
+        .iter()
+        .zip(&formula.covariates)
+        .flat_map(|(encoding, column)| encoding.terms(column))
+        .collect();
+    // Encoded covariates of each row, `None` when one is missing
+    let covariates: Vec<Option<Vec<f64>>> = raw
+        .iter()
+        .map(|values| {
+            let mut row = Vec::with_capacity(terms.len());
+            for (value, encoding) in values.iter().zip(&encodings) {
+                encoding.push(value.as_deref()?, &mut row);
+            }
+            Some(row)
+        })
+        .collect();
+    let complete: Vec<usize> = (0..targets.len()).filter(|&i| covariates[i].is_some()).collect();
+    let samples = complete.len();
+
+    // The covariate model; with subjects also fitted without covariates,
+    // for the share of variance between subjects
+    let data: Vec<f64> = complete
+        .iter()
+        .flat_map(|&i| std::iter::once(1.0).chain(covariates[i].iter().flatten().copied()))
+        .collect();
+    let x = DMatrix::from_row_slice(samples, terms.len() + 1, &data);
+    let y = DVector::from_iterator(samples, complete.iter().map(|&i| targets[i]));
+    let mut repeated = None;
+    let model = match subject {
+        Some(column) => {
+            let row_subjects: Vec<usize> = complete.iter().map(|&i| subjects[i]).collect();
+            let mixed = mixed::fit(&x, &y, &row_subjects);
+            let mut distinct = row_subjects.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            repeated = Some(Repeated {
+                column: column.to_string(),
+                subjects: distinct.len(),
+                icc: mixed.as_ref().map(mixed::MixedFit::icc),
+            });
+            mixed.filter(|_| !terms.is_empty()).map(|fit| RegressionResult {
+                target: formula.response.clone(),
+                predictors: terms.clone(),
+                intercept: fit.coefficients[0],
+                coefficients: fit.coefficients[1..].to_vec(),
+                r2: fit.r2,
+                n: samples,
+            })
+        }
+        None if terms.is_empty() => None,
+        None => ols_fit(&x, &y).map(|(intercept, coefficients, r2)| RegressionResult {
+            target: formula.response.clone(),
+            predictors: terms.clone(),
+            intercept,
+            coefficients,
+            r2,
+            n: samples,
+        }),
+    };
+
+    let mut adjustment = Adjustment {
+        formula: match subject {
+            Some(column) => format!("{} + (1 | {})", formula, column),
+            None => formula.to_string(),
+        },
+        terms,
+        samples,
+        dropped: 0,
+        repeated,
+    };
+    if !formula.marker {
+        return Ok((adjustment, model));
+    }
+
+    cancel::check(cancel)?;
+    let adjusted = match subject {
+        Some(column) => format!("{} with a random intercept per {}", covariate_label(&formula.covariates), column),
+        None => covariate_label(&formula.covariates),
+    };
+    let mut fitted = Vec::with_capacity(candidates.len());
+    for (pos, mut candidate) in candidates.drain(..).enumerate() {
+        let present: Vec<usize> = complete.iter().copied().filter(|&i| markers[i][pos].is_some()).collect();
+        let rows: Vec<(f64, &Vec<f64>, f64)> = present
+            .iter()
+            .filter_map(|&i| Some((markers[i][pos]?, covariates[i].as_ref()?, targets[i])))
+            .collect();
+        let result = match subject {
+            Some(_) => mixed_t(&rows, &present.iter().map(|&i| subjects[i]).collect::<Vec<_>>()),
+            None => marker_t(&rows),
+        };
+        let Some((t, df)) = result else {
+            adjustment.dropped += 1;
+            continue;
+        };
+        let r = t / (t * t + df).sqrt();
+        candidate.correlation = r;
+        candidate.score = r.abs();
+        candidate.n = rows.len();
+        candidate.p_value = stats::student_t_p_value(t, df);
+        candidate.direction = if r >= 0.0 { "positive" } else { "negative" }.to_string();
+        candidate.notes = format!(
+            "Partial correlation with {} adjusted for {} ({}). Higher absolute correlation suggests stronger biomarker signal.",
+            formula.response, adjusted, adjustment.formula
+        );
+        fitted.push(candidate);
+    }
+    if adjustment.dropped > 0 {
+        warn!(dropped = adjustment.dropped, formula = %adjustment.formula, "Adjusted model could not be fitted for some markers");
+    }
+    let p_values: Vec<f64> = fitted.iter().map(|c| c.p_value).collect();
+    for (candidate, q) in fitted.iter_mut().zip(stats::benjamini_hochberg(&p_values)) {
+        candidate.q_value = q;
+    }
+    fitted.sort_by(|a, b| b.score.total_cmp(&a.score));
+    *candidates = fitted;
+    Ok((adjustment, model))
+}
+
+fn covariate_label(covariates: &[String]) -> String {
+    match covariates {
+        [] => "no covariates".to_string(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+/// t statistic of the marker coefficient and the residual degrees of
+/// freedom in `y ~ 1 + marker + covariates` over (marker, covariates, y)
+/// rows; `None` when the design is singular or has fewer than two residual
+/// degrees of freedom
+fn marker_t(rows: &[(f64, &Vec<f64>, f64)]) -> Option<(f64, f64)> {
+    let (design, y) = marker_design(rows)?;
+    let (n, p) = design.shape();
+    let xtx_inv = (design.transpose() * &design).try_inverse()?;
+    let beta = &xtx_inv * design.transpose() * &y;
+    let residuals = &y - &design * &beta;
+    let df = (n - p) as f64;
+    let se = (residuals.norm_squared() / df * xtx_inv[(1, 1)]).sqrt();
+    if !se.is_finite() || se <= 0.0 {
+        return None;
+    }
+    Some((beta[1] / se, df))
+}
+
+/// [`marker_t`] in the mixed model with a random intercept per subject,
+/// `subjects` giving each row's; the degrees of freedom are subjects minus
+/// fixed effects
+fn mixed_t(rows: &[(f64, &Vec<f64>, f64)], subjects: &[usize]) -> Option<(f64, f64)> {
+    let (design, y) = marker_design(rows)?;
+    let fit = mixed::fit(&design, &y, subjects)?;
+    (fit.df >= 2.0).then(|| (fit.t(1), fit.df))
+}
+
+/// Design matrix `[1, marker, covariates]` and response of the rows;
+/// `None` with fewer than two residual degrees of freedom
+fn marker_design(rows: &[(f64, &Vec<f64>, f64)]) -> Option<(DMatrix<f64>, DVector<f64>)> {
+    let n = rows.len();
+    let p = 2 + rows.first()?.1.len();
+    if n < p + 2 {
+        return None;
+    }
+    let data: Vec<f64> = rows
+        .iter()
+        .flat_map(|(x, c, _)| [1.0, *x].into_iter().chain(c.iter().copied()))
+        .collect();
+    Some((DMatrix::from_row_slice(n, p, &data), DVector::from_iterator(n, rows.iter().map(|(_, _, y)| *y))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formula_parsing_and_adjustment() {
+        let formula: Formula = "age ~ marker + sex + batch + bmi".parse().unwrap();
+        assert_eq!(formula.response, "age");
+        assert_eq!(formula.covariates, ["sex", "batch", "bmi"]);
+        assert!(formula.marker);
+        assert_eq!(formula.to_string(), "age ~ marker + sex + batch + bmi");
+        assert_eq!(
+            Formula::resolve("Demographics", "age").unwrap().to_string(),
+            "age ~ marker + sex + bmi"
+        );
+        assert!("age ~ marker:sex".parse::<Formula>().is_err());
+        assert!("age ~ age + marker".parse::<Formula>().is_err());
+        assert!(Formula::resolve("everything", "age").is_err());
+
+        let levels = Encoding::of(&[Some("M"), Some("F"), None, Some("F")], None);
+        assert_eq!(levels.terms("sex"), ["sex[M]"]);
+        let mut row = Vec::new();
+        levels.push("M", &mut row);
+        Encoding::of(&[Some("2.5")], None).push("2.5", &mut row);
+        assert_eq!(row, [1.0, 2.5]);
+
+        // Marker confounded with sex: raw correlation is strong, the
+        // adjusted association is noise
+        let noise = |i: usize| ((i * 7919) % 13) as f64 / 13.0 - 0.5;
+        let other = |i: usize| ((i * 104_729) % 11) as f64 / 11.0 - 0.5;
+        let covariates: Vec<Vec<f64>> = (0..40).map(|i| vec![(i % 2) as f64]).collect();
+        let rows: Vec<(f64, &Vec<f64>, f64)> = (0..40)
+            .map(|i| {
+                let s = (i % 2) as f64;
+                (10.0 * s + other(i), &covariates[i], 30.0 + 20.0 * s + noise(i))
+            })
+            .collect();
+        let (t, df) = marker_t(&rows).unwrap();
+        assert_eq!(df, 37.0);
+        assert!(stats::student_t_p_value(t, df) > 0.05, "t {}", t);
+        // One sample per subject leaves the mixed model at the OLS fit
+        let (mixed, mixed_df) = mixed_t(&rows, &(0..40).collect::<Vec<_>>()).unwrap();
+        assert!((mixed - t).abs() < 1e-9 && mixed_df == df);
+    }
+}
@@ -0,0 +1,301 @@
+//! Gene set enrichment analysis (GSEA) of the ranked candidates
+//!
+//! Preranked GSEA (Subramanian et al., 2005): every tested marker is ranked
+//! by its signed association with the target, and each gene set of the
+//! user's GMT files is scored by the weighted Kolmogorov-Smirnov running
+//! sum, stepping up at the set's genes in proportion to their |r| and down
+//! at the others. The enrichment score (ES) is the sum's largest deviation
+//! from zero: positive when the set's genes gather among the markers rising
+//! with the target, negative when among the falling ones. Unlike the GO
+//! over-representation test it needs no significance cut-off.
+//!
+//! p-values come from gene-set permutation: the ES of random sets of the
+//! same size drawn from the ranked genes, counting the same-signed scores at
+//! least as extreme. The normalized score (NES) divides the ES by the mean
+//! of those same-signed scores so that sets of different sizes compare;
+//! q-values are Benjamini-Hochberg across sets. The leading edge is the
+//! set's genes ranked before the peak of the running sum (after it for a
+//! negative ES), the ones driving the score.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use super::stats::{self, Thresholds};
+use crate::annotation::GeneSets;
+use crate::models::BiomarkerCandidate;
+use crate::utils::number;
+
+/// GSEA table next to the other result tables
+pub const GSEA_FILE: &str = "gsea.csv";
+
+/// Random gene sets scored per set
+pub const PERMUTATIONS: usize = 1000;
+
+/// Seed of the permutations, so that reruns give the same p-values
+pub(super) const SEED: u64 = 42;
+
+/// Fewest and most ranked genes of a tested set; smaller than GSEA's
+/// defaults (15-500) because only the analysed markers are ranked
+const MIN_SET_SIZE: usize = 5;
+const MAX_SET_SIZE: usize = 500;
+
+/// Enrichment of one gene set
+#[derive(Debug, Clone, Serialize)]
+pub struct GseaResult {
+    pub term_id: String,
+    pub term_name: String,
+    /// GMT file the set came from
+    pub source: String,
+    /// Genes of the set among the ranked markers
+    pub size: usize,
+    pub es: f64,
+    pub nes: f64,
+    pub p_value: f64,
+    pub q_value: f64,
+    /// Genes of the set driving the score, in rank order
+    pub leading_edge: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Gsea {
+    /// Target the markers were ranked by their association with
+    pub target: String,
+    /// Distinct genes ranked
+    pub ranked: usize,
+    pub permutations: usize,
+    /// GMT files the sets were read from
+    pub sources: Vec<String>,
+    /// Sets outside the size limits, not tested
+    pub skipped: usize,
+    /// Tested sets, by p-value and then |NES|
+    pub results: Vec<GseaResult>,
+}
+
+impl Gsea {
+    /// Sets at `thresholds`, positively and negatively enriched
+    fn significant(&self, thresholds: &Thresholds) -> (usize, usize) {
+        let significant = self.results.iter().filter(|r| thresholds.significant(r.p_value, r.q_value));
+        significant.fold((0, 0), |(up, down), r| if r.es > 0.0 { (up + 1, down) } else { (up, down + 1) })
+    }
+
+    /// One sentence for the analysis summary
+    pub fn summary(&self, thresholds: &Thresholds) -> String {
+        let (up, down) = self.significant(thresholds);
+        format!(
+            "GSEA: {} of {} gene set(s) enriched at {} ({} among markers rising with {}, {} among falling ones).",
+            up + down,
+            self.results.len(),
+            thresholds.criterion(),
+            up,
+            self.target,
+            down
+        )
+    }
+
+    pub fn methods(&self) -> String {
+        format!(
+            "Gene set enrichment analysis (GSEA, preranked) scored the {} gene sets of {} with {} to {} genes among \
+             the {} markers ranked by their correlation with {}, using the weighted (p = 1) Kolmogorov-Smirnov \
+             running sum; p-values came from {} gene-set permutations, normalized enrichment scores divide by the \
+             mean same-signed permuted score, and q-values are Benjamini-Hochberg across sets.",
+            self.results.len(),
+            self.sources.join(", "),
+            MIN_SET_SIZE,
+            MAX_SET_SIZE,
+            self.ranked,
+            self.target,
+            self.permutations
+        )
+    }
+
+    pub fn results(&self, thresholds: &Thresholds) -> String {
+        let top: Vec<String> = self
+            .results
+            .iter()
+            .filter(|r| thresholds.significant(r.p_value, r.q_value))
+            .take(5)
+            .map(|r| format!("{} (NES = {:.2}, {})", r.term_name, r.nes, number::p_quote("q", r.q_value)))
+            .collect();
+        if top.is_empty() {
+            return format!("No gene set was enriched at {} by GSEA.", thresholds.criterion());
+        }
+        format!("GSEA found {} enriched among the ranked markers.", top.join(", "))
+    }
+}
+
+/// Read the gene sets of each GMT file, failing on unreadable or empty ones
+pub fn load(paths: &[PathBuf]) -> Result<Vec<(String, GeneSets)>> {
+    paths
+        .iter()
+        .map(|path| {
+            let gmt = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read gene sets {}", path.display()))?;
+            let sets = GeneSets::parse_gmt(&gmt);
+            if sets.is_empty() {
+                bail!("No gene sets in {} (expected GMT: id, name and genes, tab-separated)", path.display());
+            }
+            let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().to_string());
+            Ok((name, sets))
+        })
+        .collect()
+}
+
+/// Genes of `candidates` by descending correlation with the target, each
+/// gene once with its strongest marker; markers without a correlation are
+/// left out
+pub fn rank(candidates: &[BiomarkerCandidate]) -> Vec<(String, f64)> {
+    let mut strongest: HashMap<String, f64> = HashMap::new();
+    for candidate in candidates.iter().filter(|c| c.correlation.is_finite()) {
+        let gene = candidate.human_label().to_ascii_uppercase();
+        let metric = strongest.entry(gene).or_insert(candidate.correlation);
+        if candidate.correlation.abs() > metric.abs() {
+            *metric = candidate.correlation;
+        }
+    }
+    let mut ranked: Vec<(String, f64)> = strongest.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
+
+/// Running-sum enrichment score of the genes at `hits`, ascending positions
+/// in `weights` (|metric| of each ranked gene), and the position of its
+/// peak: the last gene counted for a positive score, the first one after it
+/// for a negative score
+fn enrichment_score(hits: &[usize], weights: &[f64]) -> (f64, usize) {
+    let total: f64 = hits.iter().map(|&i| weights[i]).sum();
+    // Sets of genes with no association at all step up equally
+    let weight = |i: usize| if total > 0.0 { weights[i] / total } else { 1.0 / hits.len() as f64 };
+    let miss = 1.0 / (weights.len() - hits.len()).max(1) as f64;
+    let (mut hit_sum, mut best, mut peak) = (0.0, 0.0f64, 0);
+    for (k, &i) in hits.iter().enumerate() {
+        // The sum is lowest just before a hit and highest just after it
+        let low = hit_sum - (i - k) as f64 * miss;
+        if low.abs() > best.abs() {
+            (best, peak) = (low, i);
+        }
+        hit_sum += weight(i);
+        let high = hit_sum - (i - k) as f64 * miss;
+        if high.abs() > best.abs() {
+            (best, peak) = (high, i);
+        }
+    }
+    (best, peak)
+}
+
+/// GSEA of `ranked` genes against every set of `gene_sets`; `None` when no
+/// set has enough ranked genes
+pub fn analyze(target: &str, ranked: &[(String, f64)], gene_sets: &[(String, GeneSets)]) -> Option<Gsea> {
+    let positions: HashMap<&str, usize> = ranked.iter().enumerate().map(|(i, (g, _))| (g.as_str(), i)).collect();
+    let weights: Vec<f64> = ranked.iter().map(|(_, metric)| metric.abs()).collect();
+    let mut skipped = 0;
+    let mut tested = Vec::new();
+    for (source, sets) in gene_sets {
+        for set in sets.sets() {
+            let mut hits: Vec<usize> = set.genes.iter().filter_map(|g| positions.get(g.as_str()).copied()).collect();
+            if hits.len() < MIN_SET_SIZE || hits.len() > MAX_SET_SIZE || hits.len() >= ranked.len() {
+                skipped += 1;
+                continue;
+            }
+            hits.sort_unstable();
+            tested.push((source, set, hits));
+        }
+    }
+    if tested.is_empty() {
+        return None;
+    }
+
+    let mut results: Vec<GseaResult> = tested
+        .par_iter()
+        .enumerate()
+        .map(|(index, (source, set, hits))| {
+            let (es, peak) = enrichment_score(hits, &weights);
+            // One stream per set keeps the p-values independent of threading
+            let mut rng = StdRng::seed_from_u64(SEED.wrapping_add(index as u64));
+            let null: Vec<f64> = (0..PERMUTATIONS)
+                .map(|_| {
+                    let mut random = rand::seq::index::sample(&mut rng, ranked.len(), hits.len()).into_vec();
+                    random.sort_unstable();
+                    enrichment_score(&random, &weights).0
+                })
+                .collect();
+            let same_sign: Vec<f64> = null.iter().copied().filter(|n| n.signum() == es.signum()).collect();
+            let extreme = same_sign.iter().filter(|n| n.abs() >= es.abs()).count();
+            let mean = same_sign.iter().map(|n| n.abs()).sum::<f64>() / same_sign.len().max(1) as f64;
+            let leading_edge = hits
+                .iter()
+                .filter(|&&i| if es >= 0.0 { i <= peak } else { i >= peak })
+                .map(|&i| ranked[i].0.clone())
+                .collect();
+            GseaResult {
+                term_id: set.id.clone(),
+                term_name: set.name.clone(),
+                source: source.to_string(),
+                size: hits.len(),
+                es,
+                nes: if mean > 0.0 { es / mean } else { 0.0 },
+                p_value: (extreme + 1) as f64 / (same_sign.len() + 1) as f64,
+                q_value: 1.0,
+                leading_edge,
+            }
+        })
+        .collect();
+    let q_values = stats::benjamini_hochberg(&results.iter().map(|r| r.p_value).collect::<Vec<_>>());
+    for (result, q) in results.iter_mut().zip(q_values) {
+        result.q_value = q;
+    }
+    results.sort_by(|a, b| a.p_value.total_cmp(&b.p_value).then_with(|| b.nes.abs().total_cmp(&a.nes.abs())));
+    Some(Gsea {
+        target: target.to_string(),
+        ranked: ranked.len(),
+        permutations: PERMUTATIONS,
+        sources: gene_sets.iter().map(|(source, _)| source.clone()).collect(),
+        skipped,
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enrichment_scores_and_permutation_p_values() {
+        // One set at the top, one at the bottom, one spread evenly
+        let ranked: Vec<(String, f64)> = (0..60).map(|i| (format!("G{}", i), 1.0 - i as f64 / 30.0)).collect();
+        let genes = |ids: &[usize]| ids.iter().map(|i| format!("G{}", i)).collect::<Vec<_>>().join("\t");
+        let gmt = format!(
+            "UP\tRising\t{}\nDOWN\tFalling\t{}\nFLAT\tSpread\t{}\nTINY\tToo small\tG0\tG1\nNONE\tUnranked\tA\tB\tC\tD\tE\n",
+            genes(&[0, 1, 2, 3, 4, 5]),
+            genes(&[54, 55, 56, 57, 58, 59]),
+            genes(&[0, 12, 24, 36, 48, 59]),
+        );
+        let sets = vec![("sets.gmt".to_string(), GeneSets::parse_gmt(&gmt))];
+        let gsea = analyze("age", &ranked, &sets).unwrap();
+        assert_eq!((gsea.ranked, gsea.skipped, gsea.results.len()), (60, 2, 3));
+
+        let result = |id: &str| gsea.results.iter().find(|r| r.term_id == id).unwrap();
+        let (up, down, flat) = (result("UP"), result("DOWN"), result("FLAT"));
+        // Every hit before any miss: the running sum reaches 1
+        assert!((up.es - 1.0).abs() < 1e-12, "{}", up.es);
+        assert!((down.es + 1.0).abs() < 1e-12, "{}", down.es);
+        assert!(flat.es.abs() < 0.5, "{}", flat.es);
+        assert!(up.nes > 1.0 && down.nes < -1.0);
+        assert!(up.p_value < 0.01 && down.p_value < 0.01, "{} {}", up.p_value, down.p_value);
+        assert!(flat.p_value > 0.1, "{}", flat.p_value);
+        assert_eq!(up.leading_edge, ["G0", "G1", "G2", "G3", "G4", "G5"]);
+        assert_eq!(down.leading_edge, ["G54", "G55", "G56", "G57", "G58", "G59"]);
+        assert_eq!(gsea.results.last().unwrap().term_id, "FLAT");
+
+        // Reruns are reproducible
+        let again = analyze("age", &ranked, &sets).unwrap();
+        let p_values = |g: &Gsea| g.results.iter().map(|r| r.p_value).collect::<Vec<_>>();
+        assert_eq!(p_values(&again), p_values(&gsea));
+        assert!(analyze("age", &ranked[..4], &sets).is_none());
+    }
+}
@@ -0,0 +1,348 @@
+//! Missing-value imputation
+//!
+//! Without imputation a missing or non-numeric cell leaves its sample out of
+//! that marker's statistics only, so every marker is analysed over a
+//! different subset of the samples, and samples with any gap drop out of the
+//! multi-marker models. Imputation fills the gaps in before the analysis,
+//! after technical replicates are collapsed: with the column's mean or
+//! median, or with the mean of the k nearest samples that have the value
+//! (KNN), nearness being the root mean squared difference of the z-scored
+//! markers both samples have. The target, group and covariate columns are
+//! never imputed. The imputed dataset is written next to the results and the
+//! number of values imputed per column is reported.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::data_registry::{ColumnKind, DatasetRecord};
+use crate::utils::cancel::{self, CancellationToken};
+
+/// Imputed dataset written when any value was imputed
+pub const IMPUTED_FILE: &str = "imputed_matrix.csv";
+
+/// Neighbours averaged by `knn` without a count
+pub const DEFAULT_NEIGHBOURS: usize = 5;
+
+/// Rows imputed between cancellation checks
+const CANCEL_CHECK_ROWS: usize = 100;
+
+/// How missing marker values are filled in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Imputation {
+    /// Leave the values out, marker by marker
+    #[default]
+    Off,
+    Mean,
+    Median,
+    /// Mean of the k nearest samples with the value
+    Knn(usize),
+}
+
+impl Imputation {
+    fn describe(self) -> String {
+        match self {
+            Self::Off => "not imputed".to_string(),
+            Self::Mean => "the column mean".to_string(),
+            Self::Median => "the column median".to_string(),
+            Self::Knn(k) => format!(
+                "the mean of the {} nearest samples with the value (root mean squared difference of the z-scored \
+                 markers both samples have)",
+                k
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Imputation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => f.write_str("off"),
+            Self::Mean => f.write_str("mean"),
+            Self::Median => f.write_str("median"),
+            Self::Knn(k) => write!(f, "knn:{}", k),
+        }
+    }
+}
+
+impl FromStr for Imputation {
+    type Err = String;
+
+    /// `off`, `mean`, `median`, `knn` or `knn:<k>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        match s.as_str() {
+            "off" | "none" => Ok(Self::Off),
+            "mean" => Ok(Self::Mean),
+            "median" => Ok(Self::Median),
+            "knn" => Ok(Self::Knn(DEFAULT_NEIGHBOURS)),
+            other => match other.strip_prefix("knn:").map(|k| k.trim().parse::<usize>()) {
+                Some(Ok(k)) if k > 0 => Ok(Self::Knn(k)),
+                _ => Err(format!("invalid imputation '{}' (expected off, mean, median, knn or knn:<k>)", s)),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for Imputation {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Imputation> for String {
+    fn from(imputation: Imputation) -> Self {
+        imputation.to_string()
+    }
+}
+
+/// Values imputed in one column
+#[derive(Debug, Clone, Serialize)]
+pub struct ImputedColumn {
+    pub column: String,
+    /// Values the column had
+    pub observed: usize,
+    pub imputed: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImputationSummary {
+    pub method: Imputation,
+    pub rows: usize,
+    /// Columns with imputed values, in dataset order
+    pub columns: Vec<ImputedColumn>,
+    /// Imputed dataset
+    pub path: PathBuf,
+}
+
+impl ImputationSummary {
+    /// Values imputed over every column
+    pub fn imputed(&self) -> usize {
+        self.columns.iter().map(|c| c.imputed).sum()
+    }
+
+    /// One sentence for the manuscript's Methods section
+    pub fn methods(&self) -> String {
+        format!(
+            "Missing marker values ({} in {} columns over {} samples) were imputed with {}; \
+             target, group and covariate columns were not imputed.",
+            self.imputed(),
+            self.columns.len(),
+            self.rows,
+            self.method.describe()
+        )
+    }
+}
+
+/// Write `record` with the missing values of its numeric columns not named
+/// in `exclude` imputed as `path`; `None` when `method` is `Off` or no value
+/// is missing. Columns without any value stay empty.
+pub fn impute(
+    record: &DatasetRecord,
+    method: Imputation,
+    exclude: &[&str],
+    path: &Path,
+    cancel: &CancellationToken,
+) -> Result<Option<(DatasetRecord, ImputationSummary)>> {
+    if method == Imputation::Off {
+        return Ok(None);
+    }
+    let columns: Vec<usize> = (0..record.columns.len())
+        .filter(|idx| record.column_kind(*idx).is_none_or(|kind| kind == ColumnKind::Numeric))
+        .filter(|idx| !exclude.contains(&record.columns[*idx].as_str()))
+        .collect();
+    let mut rdr = record.reader()?;
+    let rows: Vec<csv::StringRecord> = rdr.records().collect::<Result<_, _>>()?;
+    // values[column][row]
+    let values: Vec<Vec<Option<f64>>> = columns
+        .iter()
+        .map(|idx| rows.iter().map(|row| row.get(*idx).and_then(|v| record.number(v))).collect())
+        .collect();
+    let fillable = |col: &[Option<f64>]| col.iter().any(Option::is_some) && col.iter().any(Option::is_none);
+    if !values.iter().any(|col| fillable(col)) {
+        return Ok(None);
+    }
+
+    let filled = match method {
+        Imputation::Off => unreachable!(),
+        Imputation::Mean | Imputation::Median => fill_central(&values, method),
+        Imputation::Knn(k) => fill_knn(&values, k, cancel)?,
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .from_path(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    wtr.write_record(&record.columns)?;
+    let mut positions = vec![None; record.columns.len()];
+    for (pos, idx) in columns.iter().enumerate() {
+        positions[*idx] = Some(pos);
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let out: Vec<String> = (0..record.columns.len())
+            .map(|idx| {
+                let imputed = positions[idx]
+                    .filter(|pos: &usize| values[*pos][i].is_none())
+                    .and_then(|pos| filled[pos][i]);
+                match imputed {
+                    Some(value) => value.to_string(),
+                    None => row.get(idx).unwrap_or_default().to_string(),
+                }
+            })
+            .collect();
+        wtr.write_record(&out)?;
+    }
+    wtr.flush()?;
+
+    let summary = ImputationSummary {
+        method,
+        rows: rows.len(),
+        columns: columns
+            .iter()
+            .zip(&values)
+            .filter(|(_, col)| fillable(col))
+            .map(|(idx, col)| {
+                let observed = col.iter().filter(|v| v.is_some()).count();
+                ImputedColumn {
+                    column: record.columns[*idx].clone(),
+                    observed,
+                    imputed: col.len() - observed,
+                }
+            })
+            .collect(),
+        path: path.to_path_buf(),
+    };
+    let mut imputed = DatasetRecord::from_path_with(path, Some(record.dataset.description.clone()), record.parsing.derived())?;
+    imputed.dataset.id = record.dataset.id.clone();
+    imputed.species = record.species;
+    Ok(Some((imputed, summary)))
+}
+
+/// Each column's missing values replaced by its mean or median
+fn fill_central(values: &[Vec<Option<f64>>], method: Imputation) -> Vec<Vec<Option<f64>>> {
+    values
+        .iter()
+        .map(|col| {
+            let mut observed: Vec<f64> = col.iter().flatten().copied().collect();
+            let fill = (!observed.is_empty()).then(|| match method {
+                Imputation::Median => {
+                    observed.sort_by(f64::total_cmp);
+                    let mid = observed.len() / 2;
+                    if observed.len().is_multiple_of(2) {
+                        (observed[mid - 1] + observed[mid]) / 2.0
+                    } else {
+                        observed[mid]
+                    }
+                }
+                _ => observed.iter().sum::<f64>() / observed.len() as f64,
+            });
+            col.iter().map(|v| v.or(fill)).collect()
+        })
+        .collect()
+}
+
+/// Each missing value replaced by the mean of the `k` nearest rows that
+/// have it; rows sharing no observed column with any such row get the
+/// column mean
+fn fill_knn(values: &[Vec<Option<f64>>], k: usize, cancel: &CancellationToken) -> Result<Vec<Vec<Option<f64>>>> {
+    let rows = values.first().map_or(0, Vec::len);
+    let means = fill_central(values, Imputation::Mean);
+    // z-scores, so that no marker dominates the distance by its scale
+    let scaled: Vec<Vec<Option<f64>>> = values
+        .iter()
+        .map(|col| {
+            let observed: Vec<f64> = col.iter().flatten().copied().collect();
+            let n = observed.len().max(1) as f64;
+            let mean = observed.iter().sum::<f64>() / n;
+            let sd = (observed.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+            let sd = if sd > 0.0 { sd } else { 1.0 };
+            col.iter().map(|v| v.map(|v| (v - mean) / sd)).collect()
+        })
+        .collect();
+
+    let mut filled = values.to_vec();
+    for row in 0..rows {
+        if row % CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let missing: Vec<usize> = (0..values.len()).filter(|c| values[*c][row].is_none()).collect();
+        if missing.is_empty() {
+            continue;
+        }
+        let mut distances: Vec<(f64, usize)> = (0..rows)
+            .filter(|other| *other != row)
+            .filter_map(|other| {
+                let (sum, shared) = scaled
+                    .iter()
+                    .filter_map(|col| Some((col[row]? - col[other]?).powi(2)))
+                    .fold((0.0, 0usize), |(sum, n), d| (sum + d, n + 1));
+                (shared > 0).then(|| ((sum / shared as f64).sqrt(), other))
+            })
+            .collect();
+        distances.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for col in missing {
+            let neighbours: Vec<f64> = distances.iter().filter_map(|(_, other)| values[col][*other]).take(k).collect();
+            filled[col][row] = if neighbours.is_empty() {
+                means[col][row]
+            } else {
+                Some(neighbours.iter().sum::<f64>() / neighbours.len() as f64)
+            };
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_imputation() {
+        assert_eq!("knn".parse::<Imputation>(), Ok(Imputation::Knn(DEFAULT_NEIGHBOURS)));
+        assert_eq!("KNN:3".parse::<Imputation>(), Ok(Imputation::Knn(3)));
+        assert_eq!("none".parse::<Imputation>(), Ok(Imputation::Off));
+        assert!("knn:0".parse::<Imputation>().is_err());
+        assert!("zero".parse::<Imputation>().is_err());
+        assert_eq!(Imputation::Knn(3).to_string(), "knn:3");
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        // On gene_b S4 resembles S1 and S2, S5 resembles S3; age is never
+        // imputed
+        let csv = "sample_id,age,gene_a,gene_b,empty\n\
+                   S1,30,1.0,10.0,\nS2,35,3.0,11.0,\nS3,60,20.0,50.0,\nS4,,NA,10.5,\nS5,40,,49.0,\n";
+        std::fs::write(&input, csv).unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+        let path = dir.path().join(IMPUTED_FILE);
+        let token = CancellationToken::new();
+
+        let (imputed, summary) = impute(&record, Imputation::Median, &["age"], &path, &token).unwrap().unwrap();
+        assert_eq!(summary.columns.len(), 1);
+        assert_eq!((summary.columns[0].column.as_str(), summary.columns[0].observed, summary.columns[0].imputed), ("gene_a", 3, 2));
+        assert!(summary.methods().starts_with("Missing marker values (2 in 1 columns over 5 samples) were imputed with the column median"));
+        let written = std::fs::read_to_string(&imputed.local_path).unwrap();
+        assert_eq!(written.lines().nth(4), Some("S4,,3,10.5,"));
+        assert_eq!(imputed.dataset.id, record.dataset.id);
+
+        let (_, summary) = impute(&record, Imputation::Knn(2), &["age"], &path, &token).unwrap().unwrap();
+        assert_eq!(summary.imputed(), 2);
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().nth(4), Some("S4,,2,10.5,"));
+        assert_eq!(written.lines().nth(5), Some("S5,40,11.5,49.0,"));
+
+        assert!(impute(&record, Imputation::Off, &[], &path, &token).unwrap().is_none());
+        let complete = dir.path().join("complete.csv");
+        std::fs::write(&complete, "sample_id,gene_a\nS1,1\nS2,2\n").unwrap();
+        let record = DatasetRecord::from_path(&complete, None).unwrap();
+        assert!(impute(&record, Imputation::Mean, &[], &path, &token).unwrap().is_none());
+    }
+}
@@ -0,0 +1,261 @@
+//! K-means clustering of samples
+//!
+//! Samples are partitioned on the same scaled marker matrix as the PCA
+//! (z-scores, missing values at the marker mean) with Lloyd's algorithm,
+//! seeded deterministically by farthest-point (maximin) selection so that
+//! reruns assign the same clusters. The number of clusters is the k in
+//! 2..=[`MAX_K`] with the highest mean silhouette width. Each cluster is
+//! described by its size and the markers furthest from zero in its centroid.
+
+use nalgebra::{DMatrix, RowDVector};
+use serde::Serialize;
+
+use super::pca::Scaled;
+
+/// Largest number of clusters tried
+pub const MAX_K: usize = 8;
+
+/// Samples clustered at most; the silhouette needs all pairwise distances
+pub const MAX_SAMPLES: usize = 2000;
+
+/// Lloyd iterations before giving up on convergence
+const MAX_ITERATIONS: usize = 100;
+
+/// Markers named per cluster
+const CENTROID_MARKERS: usize = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KMeans {
+    pub k: usize,
+    /// Markers the samples were clustered on
+    pub markers: usize,
+    /// Mean silhouette width over the samples
+    pub silhouette: f64,
+    /// Largest cluster first
+    pub clusters: Vec<Cluster>,
+    /// One per clustered sample, in file order
+    pub assignments: Vec<Assignment>,
+    /// Group column, when grouped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Cluster {
+    /// 1-based
+    pub index: usize,
+    pub size: usize,
+    /// Mean silhouette width of its samples
+    pub silhouette: f64,
+    /// Markers with the largest absolute centroid z-score, and that z-score
+    pub centroid_markers: Vec<(String, f64)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Assignment {
+    pub sample: String,
+    pub cluster: usize,
+    /// Euclidean distance to the cluster centroid, in z-score units
+    pub distance: f64,
+    pub silhouette: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+impl KMeans {
+    /// Sentence for the analysis summary
+    pub fn summary(&self) -> String {
+        let sizes: Vec<String> = self.clusters.iter().map(|c| c.size.to_string()).collect();
+        let centroids: Vec<String> = self
+            .clusters
+            .iter()
+            .map(|c| {
+                let markers: Vec<String> =
+                    c.centroid_markers.iter().map(|(marker, z)| format!("{} ({:+.2})", marker, z)).collect();
+                format!("C{} {}", c.index, markers.join(", "))
+            })
+            .collect();
+        format!(
+            "K-means: {} clusters of {} samples (mean silhouette {:.2}); centroid markers: {}.",
+            self.k,
+            sizes.join(", "),
+            self.silhouette,
+            centroids.join("; ")
+        )
+    }
+}
+
+/// K-means clustering of the samples of `scaled`, with k chosen by mean
+/// silhouette width. `None` with fewer than four or more than
+/// [`MAX_SAMPLES`] samples.
+pub fn analyze(scaled: &Scaled) -> Option<KMeans> {
+    let x = &scaled.values;
+    let n = x.nrows();
+    if !(4..=MAX_SAMPLES).contains(&n) {
+        return None;
+    }
+    let distances = DMatrix::from_fn(n, n, |i, j| (x.row(i) - x.row(j)).norm());
+
+    let mut best: Option<(Vec<usize>, DMatrix<f64>, Vec<f64>)> = None;
+    for k in 2..=MAX_K.min(n - 1) {
+        let (labels, centroids) = lloyd(x, k);
+        let widths = silhouettes(&distances, &labels, k);
+        let mean = widths.iter().sum::<f64>() / n as f64;
+        if best.as_ref().is_none_or(|(_, _, w)| mean > w.iter().sum::<f64>() / n as f64) {
+            best = Some((labels, centroids, widths));
+        }
+    }
+    let (labels, centroids, widths) = best?;
+
+    // Number the clusters by size, largest first, ties by first sample
+    let k = centroids.nrows();
+    let size = |c: usize| labels.iter().filter(|l| **l == c).count();
+    let first = |c: usize| labels.iter().position(|l| *l == c).unwrap_or(n);
+    let mut order: Vec<usize> = (0..k).filter(|c| size(*c) > 0).collect();
+    order.sort_by_key(|c| (std::cmp::Reverse(size(*c)), first(*c)));
+    let mut number = vec![0; k];
+    for (i, c) in order.iter().enumerate() {
+        number[*c] = i + 1;
+    }
+
+    let clusters = order
+        .iter()
+        .map(|&c| {
+            let members: Vec<usize> = (0..n).filter(|i| labels[*i] == c).collect();
+            let mut markers: Vec<(String, f64)> =
+                scaled.markers.iter().enumerate().map(|(j, m)| (m.clone(), centroids[(c, j)])).collect();
+            markers.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+            markers.truncate(CENTROID_MARKERS);
+            Cluster {
+                index: number[c],
+                size: members.len(),
+                silhouette: members.iter().map(|i| widths[*i]).sum::<f64>() / members.len() as f64,
+                centroid_markers: markers,
+            }
+        })
+        .collect();
+    let assignments = (0..n)
+        .map(|i| Assignment {
+            sample: scaled.samples[i].clone(),
+            cluster: number[labels[i]],
+            distance: (x.row(i) - centroids.row(labels[i])).norm(),
+            silhouette: widths[i],
+            group: scaled.groups.as_ref().map(|(_, groups)| groups[i].clone()),
+        })
+        .collect();
+    Some(KMeans {
+        k: order.len(),
+        markers: scaled.markers.len(),
+        silhouette: widths.iter().sum::<f64>() / n as f64,
+        clusters,
+        assignments,
+        group: scaled.groups.as_ref().map(|(name, _)| name.clone()),
+    })
+}
+
+/// Cluster of each row of `x` and the `k` centroids, from maximin seeds
+fn lloyd(x: &DMatrix<f64>, k: usize) -> (Vec<usize>, DMatrix<f64>) {
+    let n = x.nrows();
+    let nearest = |row: &RowDVector<f64>, centroids: &DMatrix<f64>, count: usize| {
+        (0..count)
+            .map(|c| (c, (row - centroids.row(c)).norm()))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or((0, 0.0))
+    };
+
+    // First seed nearest the overall mean, then each the sample furthest
+    // from the seeds so far
+    let mean = x.row_mean();
+    let first = (0..n)
+        .map(|i| (i, (x.row(i) - &mean).norm()))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(i, _)| i);
+    let mut centroids = DMatrix::zeros(k, x.ncols());
+    centroids.set_row(0, &x.row(first));
+    for c in 1..k {
+        let far = (0..n)
+            .map(|i| (i, nearest(&x.row(i).into_owned(), &centroids, c).1))
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map_or(0, |(i, _)| i);
+        centroids.set_row(c, &x.row(far));
+    }
+
+    let mut labels = vec![usize::MAX; n];
+    for _ in 0..MAX_ITERATIONS {
+        let next: Vec<usize> = (0..n).map(|i| nearest(&x.row(i).into_owned(), &centroids, k).0).collect();
+        if next == labels {
+            break;
+        }
+        labels = next;
+        for c in 0..k {
+            let members: Vec<usize> = (0..n).filter(|i| labels[*i] == c).collect();
+            // An emptied cluster keeps its centroid
+            if members.is_empty() {
+                continue;
+            }
+            let sum = members.iter().fold(RowDVector::zeros(x.ncols()), |sum, i| sum + x.row(*i));
+            centroids.set_row(c, &(sum / members.len() as f64));
+        }
+    }
+    (labels, centroids)
+}
+
+/// Silhouette width of each sample; zero for samples alone in their cluster
+fn silhouettes(distances: &DMatrix<f64>, labels: &[usize], k: usize) -> Vec<f64> {
+    let n = labels.len();
+    (0..n)
+        .map(|i| {
+            let mut sums = vec![0.0; k];
+            let mut counts = vec![0usize; k];
+            for j in (0..n).filter(|j| *j != i) {
+                sums[labels[j]] += distances[(i, j)];
+                counts[labels[j]] += 1;
+            }
+            if counts[labels[i]] == 0 {
+                return 0.0;
+            }
+            let a = sums[labels[i]] / counts[labels[i]] as f64;
+            let b = (0..k)
+                .filter(|c| *c != labels[i] && counts[*c] > 0)
+                .map(|c| sums[c] / counts[c] as f64)
+                .fold(f64::INFINITY, f64::min);
+            if !b.is_finite() || a.max(b) == 0.0 {
+                0.0
+            } else {
+                (b - a) / a.max(b)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kmeans_finds_separated_clusters() {
+        // Three blobs in two markers: 5 samples near (0, 0), 4 near (6, 0)
+        // and 3 near (0, 6)
+        let points = [
+            (0.1, 0.0), (-0.2, 0.1), (0.0, -0.1), (0.2, 0.2), (-0.1, -0.2),
+            (6.0, 0.1), (6.2, -0.1), (5.9, 0.0), (6.1, 0.2),
+            (0.1, 6.0), (-0.1, 6.1), (0.0, 5.9),
+        ];
+        let scaled = Scaled {
+            markers: vec!["gene_a".to_string(), "gene_b".to_string()],
+            samples: (0..points.len()).map(|i| format!("S{}", i)).collect(),
+            groups: None,
+            values: DMatrix::from_fn(points.len(), 2, |i, j| if j == 0 { points[i].0 } else { points[i].1 }),
+        };
+        let kmeans = analyze(&scaled).unwrap();
+        assert_eq!(kmeans.k, 3);
+        let sizes: Vec<usize> = kmeans.clusters.iter().map(|c| c.size).collect();
+        assert_eq!(sizes, [5, 4, 3]);
+        let clusters: Vec<usize> = kmeans.assignments.iter().map(|a| a.cluster).collect();
+        assert_eq!(clusters, [1, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3]);
+        assert!(kmeans.silhouette > 0.9);
+        assert_eq!(kmeans.clusters[1].centroid_markers[0].0, "gene_a");
+        assert!(kmeans.summary().starts_with("K-means: 3 clusters of 5, 4, 3 samples (mean silhouette 0.9"));
+        assert!(kmeans.summary().contains("C2 gene_a (+6.05)"), "{}", kmeans.summary());
+    }
+}
@@ -0,0 +1,487 @@
+//! Figure legends
+//!
+//! Each plot written by [`run_analysis`](super::run_analysis) gets a legend
+//! built from the analysis metadata: what is shown, the sample sizes, the
+//! test and the significance threshold. With `MANUSCRIPT_POLISH_LEGENDS` set,
+//! [`polish`] has the drafting model smooth the wording; a rewrite that
+//! changes, drops or adds a number is discarded.
+
+use chrono::Utc;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use super::aggregate::BoxStats;
+use super::contrast::{GroupContrast, VOLCANO_DIFFERENCE};
+use super::expression::{ExpressionMatrix, Z_LIMIT};
+use super::manifest::Stage;
+use super::pca::Pca;
+use super::roc::RocAnalysis;
+use super::survival::Survival;
+use super::stats::{CorrelationMethod, Thresholds};
+use super::AnalysisArtifacts;
+use crate::config::{Config, LlmTask};
+use crate::llm::provider::{LLMProviderConfig, LLM};
+use crate::models::EnrichmentResult;
+use crate::search::bibtex;
+use crate::types::{LLMMessage, LLMRequest};
+use crate::utils::number;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Figure {
+    Heatmap,
+    Expression,
+    Boxplot,
+    Volcano,
+    Roc,
+    Survival,
+    Enrichment,
+    Scree,
+    Scores,
+}
+
+impl Figure {
+    /// `\label` of the figure in the LaTeX export
+    pub fn latex_label(&self) -> &'static str {
+        match self {
+            Figure::Heatmap => "fig:heatmap",
+            Figure::Expression => "fig:expression",
+            Figure::Boxplot => "fig:boxplot",
+            Figure::Volcano => "fig:volcano",
+            Figure::Roc => "fig:roc",
+            Figure::Survival => "fig:survival",
+            Figure::Enrichment => "fig:enrichment",
+            Figure::Scree => "fig:scree",
+            Figure::Scores => "fig:scores",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FigureLegend {
+    pub figure: Figure,
+    pub path: String,
+    /// Short title, the legend's first sentence
+    pub title: String,
+    /// Everything after the title
+    pub body: String,
+    /// "provider/model" when the wording was polished
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub polished_by: Option<String>,
+}
+
+impl FigureLegend {
+    fn new(figure: Figure, path: &str, title: String, body: String) -> Self {
+        Self {
+            figure,
+            path: path.to_string(),
+            title,
+            body,
+            polished_by: None,
+        }
+    }
+
+    /// Correlation heatmap of `markers` markers with `samples` values per
+    /// marker (smallest and largest), `starred` of them passing `thresholds`
+    pub fn heatmap(
+        path: &str,
+        markers: usize,
+        samples: (usize, usize),
+        method: CorrelationMethod,
+        starred: usize,
+        thresholds: &Thresholds,
+    ) -> Self {
+        let n = match samples {
+            (min, max) if min == max => min.to_string(),
+            (min, max) => format!("{} to {}", min, max),
+        };
+        Self::new(
+            Figure::Heatmap,
+            path,
+            "Correlation heatmap of the analyzed markers".to_string(),
+            format!(
+                "Pairwise {method} correlation coefficients between the first {markers} analyzed markers \
+                 (n = {n} samples per marker), ordered by average-linkage hierarchical clustering on 1 - r as \
+                 shown by the dendrogram. Colour runs from blue (r = -1) through green (r = 0) to red \
+                 (r = 1).{stars} Exploratory; no significance test was applied to the pairwise coefficients.",
+                method = method.name(),
+                stars = stars(starred, "whose association with the target passed", thresholds),
+            ),
+        )
+    }
+
+    /// Expression heatmap of `matrix`, whose markers were chosen `basis`
+    /// ("whose group means differ most ...") and whose samples are ordered by `group`
+    pub fn expression(
+        path: &str,
+        matrix: &ExpressionMatrix,
+        basis: &str,
+        group: Option<&str>,
+        starred: usize,
+        thresholds: &Thresholds,
+    ) -> Self {
+        let markers = matrix.markers.len();
+        let samples = matrix.samples();
+        let order = match group {
+            Some(group) => {
+                let counts = matrix
+                    .group_counts()
+                    .iter()
+                    .map(|(label, n)| format!("{}, n = {}", label, n))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                format!(", ordered by {group} as marked by the colour bar ({counts})")
+            }
+            None => String::new(),
+        };
+        let clustered = match group {
+            Some(_) => "Markers, and samples within each group, are",
+            None => "Markers and samples are",
+        };
+        Self::new(
+            Figure::Expression,
+            path,
+            "Expression of the top markers across samples".to_string(),
+            format!(
+                "The {markers} markers {basis} (rows) across {samples} samples (columns){order}. {clustered} \
+                 ordered by average-linkage hierarchical clustering on 1 - r, as shown by the dendrograms. Each \
+                 marker is scaled to z-scores across samples; colour runs from blue (z = -{Z_LIMIT} or below) through white \
+                 (z = 0) to red (z = {Z_LIMIT} or above), with missing values in grey.{stars} The colours are \
+                 descriptive.",
+                stars = stars(starred, "whose association with the target or a group comparison passed", thresholds),
+            ),
+        )
+    }
+
+    /// Box plot of `marker` by `group`; `total_groups` counts the groups
+    /// before the plot was cut to `boxes`
+    pub fn boxplot(path: &str, marker: &str, group: &str, boxes: &[BoxStats], total_groups: usize) -> Self {
+        let counts = boxes
+            .iter()
+            .map(|b| format!("{}, n = {}", b.label, b.n))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let shown = if boxes.len() < total_groups {
+            format!("the first {} of {} groups by label", boxes.len(), total_groups)
+        } else {
+            format!("{} groups", boxes.len())
+        };
+        Self::new(
+            Figure::Boxplot,
+            path,
+            format!("Distribution of {} by {}", marker, group),
+            format!(
+                "Box plots for {shown} ({counts}). Boxes span the interquartile range with the median marked; \
+                 whiskers extend to the minimum and maximum. Descriptive; no test of group differences was applied."
+            ),
+        )
+    }
+
+    /// Volcano plot of the two-group `contrast`
+    pub fn volcano(path: &str, contrast: &GroupContrast) -> Self {
+        let [a, b] = &contrast.groups;
+        let hits: Vec<_> = contrast.volcano_hits().collect();
+        let up = hits.iter().filter(|m| m.difference > 0.0).count();
+        Self::new(
+            Figure::Volcano,
+            path,
+            format!("Markers differing between {} and {}", b, a),
+            format!(
+                "Difference of means of {b} ({nb} samples) relative to {a} ({na} samples){scale}, against -log10 \
+                 of the Welch t test p-value for {markers} markers. Dashed lines mark a {effect} of ±{fc} and \
+                 p = {alpha}; the {up} marker(s) higher and {down} lower in {b} at {criterion} and |{effect}| ≥ \
+                 {fc} are shown in red and blue.",
+                scale = if contrast.log2_scale { ", the log2 fold change on the log2-transformed data" } else { "" },
+                effect = contrast.effect(),
+                nb = contrast.samples[1],
+                na = contrast.samples[0],
+                markers = contrast.markers.len(),
+                fc = VOLCANO_DIFFERENCE,
+                alpha = contrast.thresholds.alpha,
+                down = hits.len() - up,
+                criterion = contrast.thresholds.criterion(),
+            ),
+        )
+    }
+
+    /// ROC curves of the best-discriminating candidates of `roc`
+    pub fn roc(path: &str, roc: &RocAnalysis) -> Self {
+        let aucs: Vec<String> = roc
+            .curves
+            .iter()
+            .map(|c| format!("{} {:.3}", c.label, c.discrimination()))
+            .collect();
+        Self::new(
+            Figure::Roc,
+            path,
+            format!("ROC curves of the candidates separating {}", roc.classes()),
+            format!(
+                "True against false positive rate of the {shown} of {markers} candidates with the largest area under                  the ROC curve (AUC: {aucs}), classifying {positives} samples with {classes} as positive and                  {negatives} as negative. Markers lower in the positive class are drawn reversed. The dashed                  diagonal is chance (AUC 0.5).",
+                shown = roc.curves.len(),
+                markers = roc.markers,
+                aucs = aucs.join(", "),
+                positives = roc.split.positives,
+                classes = roc.classes(),
+                negatives = roc.split.negatives,
+            ),
+        )
+    }
+
+    /// Kaplan-Meier curves of `survival`
+    pub fn survival(path: &str, survival: &Survival) -> Self {
+        let title = match &survival.strata {
+            Some(_) => {
+                let labels: Vec<&str> = survival.curves.iter().map(|c| c.label.as_str()).collect();
+                format!("Kaplan-Meier survival of {}", labels.join(" vs "))
+            }
+            None => "Kaplan-Meier survival of all samples".to_string(),
+        };
+        let log_rank = match &survival.log_rank {
+            Some(test) => format!(" Log-rank {} ({} df).", number::p_quote("p", test.p_value), test.df),
+            None => String::new(),
+        };
+        Self::new(
+            Figure::Survival,
+            path,
+            title,
+            format!(
+                "Estimated probability of no event ({event}) against {time}, over {samples} samples with \
+                 {events} events. Crosses mark censored samples.{log_rank}",
+                event = survival.event,
+                time = survival.time,
+                samples = survival.samples,
+                events = survival.events,
+            ),
+        )
+    }
+
+    /// GO enrichment bar chart of the first `shown` of `enrichment`
+    /// (sorted by p-value) for markers correlated with `target`
+    pub fn enrichment(
+        path: &str,
+        enrichment: &[EnrichmentResult],
+        shown: usize,
+        target: &str,
+        thresholds: &Thresholds,
+    ) -> Self {
+        let shown = shown.min(enrichment.len());
+        let significant = enrichment
+            .iter()
+            .take(shown)
+            .filter(|e| thresholds.significant(e.p_value, e.q_value))
+            .count();
+        let (markers, terms) = (thresholds.marker_criterion(), thresholds.criterion());
+        let (hits, universe) = enrichment.first().map_or((0, 0), |e| (e.hits, e.universe));
+        Self::new(
+            Figure::Enrichment,
+            path,
+            "GO terms enriched among the significant biomarkers".to_string(),
+            format!(
+                "The {shown} GO terms with the smallest p-values for over-representation among the {hits} \
+                 annotated biomarkers correlated with {target} at {markers}, against a universe of \
+                 {universe} annotated markers. Bars show -log10(p) of the one-sided hypergeometric test; solid \
+                 bars mark the {significant} term(s) with Benjamini-Hochberg {terms}."
+            ),
+        )
+    }
+
+    /// Scree plot of the components of `pca`
+    pub fn scree(path: &str, pca: &Pca) -> Self {
+        let share = |i: usize| pca.components.get(i).map_or(0.0, |c| c.ratio * 100.0);
+        Self::new(
+            Figure::Scree,
+            path,
+            "Variance explained by the principal components".to_string(),
+            format!(
+                "Share of the total variance explained by each of the first {} principal components (bars) and \
+                 their cumulative share (line), from {} markers scaled to unit variance across {} samples. The \
+                 first two components explained {:.1}% and {:.1}%.",
+                pca.components.len(),
+                pca.markers.len(),
+                pca.samples,
+                share(0),
+                share(1)
+            ),
+        )
+    }
+
+    /// PC1/PC2 score plot of `pca`, coloured by `group` when grouped
+    pub fn scores(path: &str, pca: &Pca, group: Option<&str>) -> Self {
+        let share = |i: usize| pca.components.get(i).map_or(0.0, |c| c.ratio * 100.0);
+        let colour = match group.filter(|_| pca.groups.is_some()) {
+            Some(group) => {
+                let counts = pca
+                    .group_counts()
+                    .iter()
+                    .map(|(label, n)| format!("{}, n = {}", label, n))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                format!(", coloured by {} ({})", group, counts)
+            }
+            None => String::new(),
+        };
+        Self::new(
+            Figure::Scores,
+            path,
+            "Samples on the first two principal components".to_string(),
+            format!(
+                "Scores of {} samples on PC1 ({:.1}% of the variance) and PC2 ({:.1}%), from {} markers scaled to \
+                 unit variance{}. Descriptive; no test of separation was applied.",
+                pca.samples,
+                share(0),
+                share(1),
+                pca.markers.len(),
+                colour
+            ),
+        )
+    }
+
+    /// Title and body as one legend
+    pub fn text(&self) -> String {
+        format!("{}. {}", self.title, self.body)
+    }
+
+    /// The legend as LaTeX caption text
+    pub fn to_latex(&self) -> String {
+        latex_escape(&self.text())
+    }
+
+    /// `text` as this legend's new wording, when it quotes exactly the same
+    /// numbers
+    fn reworded(&self, text: &str) -> Option<(String, String)> {
+        let text = text.trim().trim_matches('"').trim();
+        if text.is_empty() {
+            return None;
+        }
+        let mut expected = numbers(&self.text());
+        let mut found = numbers(text);
+        expected.sort();
+        found.sort();
+        if expected != found {
+            return None;
+        }
+        let (title, body) = text.split_once(". ")?;
+        Some((title.trim().to_string(), body.trim().to_string()))
+    }
+}
+
+/// Numbers quoted in `text`, as written
+fn numbers(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|token| token.trim_matches('.'))
+        .filter(|token| token.starts_with(|c: char| c.is_ascii_digit()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Legend sentence on the `starred` markers marked with an asterisk
+fn stars(starred: usize, which: &str, thresholds: &Thresholds) -> String {
+    if starred == 0 {
+        return String::new();
+    }
+    format!(" Asterisks mark the {} marker(s) {} {}.", starred, which, thresholds.marker_criterion())
+}
+
+/// Plain legend text as LaTeX, with the comparisons and log in math mode
+pub(crate) fn latex_escape(text: &str) -> String {
+    bibtex::escape(text)
+        .replace("-log10(p)", "$-\\log_{10} p$")
+        .replace(" < ", " $<$ ")
+        .replace("|r| ≥ ", "$|r| \\geq$ ")
+}
+
+/// Reword the legends of `analysis` with the drafting model when
+/// `MANUSCRIPT_POLISH_LEGENDS` is set. Legends the model gets wrong, or
+/// cannot be asked about, keep their template wording.
+pub async fn polish(config: &Config, analysis: &mut AnalysisArtifacts) {
+    if !config.manuscript.polish_legends || analysis.figure_legends.is_empty() {
+        return;
+    }
+    let Some(route) = config.llm_for(LlmTask::Drafting) else {
+        return;
+    };
+    let llm = LLM::new(LLMProviderConfig {
+        name: route.provider.clone(),
+        api_key: route.api_key.clone(),
+        network: config.network.clone(),
+        openrouter: config.llm.openrouter.clone(),
+        rate_limit: config.llm.rate_limit(&route.provider),
+    });
+    let model = format!("{}/{}", route.provider, route.model);
+    let started = Utc::now();
+
+    for legend in &mut analysis.figure_legends {
+        let request = LLMRequest {
+            provider: route.provider.clone(),
+            model: route.model.clone(),
+            messages: vec![LLMMessage::user(format!(
+                "Rewrite this figure legend for a journal submission. Start with a short title sentence, keep \
+                 every number, test and threshold exactly as written, and reply with the legend only.\n\n{}",
+                legend.text()
+            ))],
+            max_tokens: Some(route.output_tokens(Some(400))),
+            temperature: Some(route.temperature_or(0.3)),
+            system_instruction: Some(
+                "You are a scientific writing assistant. Never invent results or numbers.".to_string(),
+            ),
+            system_blocks: Vec::new(),
+        };
+        match llm.create_chat_completion(&request).await {
+            Ok(response) => match legend.reworded(&response.content) {
+                Some((title, body)) => {
+                    legend.title = title;
+                    legend.body = body;
+                    legend.polished_by = Some(model.clone());
+                }
+                None => warn!(figure = ?legend.figure, "Reworded legend changed its numbers, keeping template"),
+            },
+            Err(e) => {
+                warn!(error = %e, "Legend polishing failed, keeping template legends");
+                break;
+            }
+        }
+    }
+    let polished = analysis.figure_legends.iter().filter(|l| l.polished_by.is_some()).count();
+    info!(model = %model, polished, "Figure legends polished");
+    if let Some(manifest) = &mut analysis.manifest {
+        manifest.stage(Stage::finished("legends", Some(&route), started));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legends_and_rewording() {
+        let boxes = vec![
+            BoxStats { label: "old".to_string(), n: 12, min: 0.0, q1: 1.0, median: 2.0, q3: 3.0, max: 4.0 },
+            BoxStats { label: "young".to_string(), n: 15, min: 0.0, q1: 1.0, median: 2.0, q3: 3.0, max: 4.0 },
+        ];
+        let legend = FigureLegend::boxplot("out/boxplot.png", "TP53", "age_group", &boxes, 3);
+        assert_eq!(legend.title, "Distribution of TP53 by age_group");
+        assert!(legend.body.starts_with("Box plots for the first 2 of 3 groups by label (old, n = 12; young, n = 15)."));
+
+        let strict = Thresholds { min_effect: 0.3, ..Default::default() };
+        let heatmap = FigureLegend::heatmap("out/heatmap.png", 20, (38, 40), CorrelationMethod::Spearman, 4, &strict);
+        assert!(heatmap.body.starts_with("Pairwise Spearman correlation"));
+        assert!(heatmap.body.contains("Asterisks mark the 4 marker(s) whose association with the target passed q < 0.05 and |r| ≥ 0.3."), "{}", heatmap.body);
+        assert!(heatmap.to_latex().contains("q $<$ 0.05 and $|r| \\geq$ 0.3"));
+        assert!(heatmap.body.contains("(n = 38 to 40 samples per marker)"));
+        assert_eq!(
+            FigureLegend::enrichment("out/go.png", &[], 10, "age", &Thresholds::default()).to_latex(),
+            "GO terms enriched among the significant \
+             biomarkers. The 0 GO terms with the smallest p-values for over-representation among the 0 annotated \
+             biomarkers correlated with age at q $<$ 0.05, against a universe of 0 annotated markers. Bars show \
+             $-\\log_{10} p$ of the one-sided hypergeometric test; solid bars mark the 0 term(s) with \
+             Benjamini-Hochberg q $<$ 0.05."
+        );
+
+        let reworded = legend.reworded(
+            "TP53 by age group. Expression in 2 of 3 groups (old, n = 12; young, n = 15), shown as medians \
+             with interquartile boxes and min-max whiskers; descriptive only.",
+        );
+        assert_eq!(reworded.unwrap().0, "TP53 by age group");
+        assert!(legend.reworded("TP53 by age group. Old (n = 13) and young (n = 15) samples.").is_none());
+    }
+}
@@ -0,0 +1,223 @@
+//! Literature cross-referencing of biomarker candidates
+//!
+//! After an analysis, the gene of each top-ranked candidate is searched as
+//! `"<gene> aging"` (`CROSS_REFERENCE_QUERY`) and flagged as previously
+//! reported when at least `CROSS_REFERENCE_MIN_REPORTS` publications match,
+//! or as novel otherwise. PubMed is preferred since it reports the total
+//! number of matches; without it the enabled Scholar engines are searched and
+//! the count is capped at their result limit. Mouse and rat genes are
+//! searched by their human ortholog. Candidates that are not genes or whose
+//! search fails are left unflagged.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use chrono::Utc;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use super::manifest::Stage;
+use super::AnalysisArtifacts;
+use crate::config::Config;
+use crate::models::{BiomarkerCandidate, EvidenceStatus, PriorEvidence};
+use crate::search::pubmed::PubMedClient;
+use crate::search::{Engine, SearchAggregator};
+
+/// Outcome of cross-referencing one analysis
+#[derive(Debug, Clone, Serialize)]
+pub struct CrossReferenceSummary {
+    /// Engine the counts came from
+    pub engine: String,
+    /// Query template, `{gene}` standing for the symbol
+    pub query: String,
+    pub min_reports: usize,
+    /// Candidates searched
+    pub searched: usize,
+    pub reported: usize,
+    pub novel: usize,
+    /// Searches that failed
+    pub failed: usize,
+}
+
+impl CrossReferenceSummary {
+    /// One sentence for the manuscript's Methods section
+    pub fn methods(&self) -> String {
+        format!(
+            "The top {} candidate genes were searched in {} (\"{}\") and flagged as previously reported when at \
+least {} publication(s) matched, and as novel otherwise.",
+            self.searched,
+            engine_name(&self.engine),
+            self.query.replace("{gene}", "<gene>"),
+            self.min_reports
+        )
+    }
+}
+
+/// Search the genes of the `top_n` best candidates and record their prior
+/// evidence in `analysis`. Does nothing when `top_n` is 0 or no literature
+/// engine is enabled.
+pub async fn cross_reference(config: &Config, analysis: &mut AnalysisArtifacts, top_n: usize) {
+    if top_n == 0 {
+        return;
+    }
+    let settings = &config.cross_reference;
+    let enabled = Engine::enabled(&config.search);
+    // Google Light returns web pages, not publications
+    let engine = [Engine::PubMed, Engine::Scholar, Engine::SemanticScholar, Engine::Mock]
+        .into_iter()
+        .find(|engine| enabled.contains(engine));
+    let Some(engine) = engine else {
+        warn!("Skipping literature cross-referencing: no literature search engine is enabled");
+        return;
+    };
+
+    let started = Utc::now();
+    let mut summary = CrossReferenceSummary {
+        engine: engine.as_str().to_string(),
+        query: settings.query.clone(),
+        min_reports: settings.min_reports,
+        searched: 0,
+        reported: 0,
+        novel: 0,
+        failed: 0,
+    };
+    // PubMed allows 3 requests per second without a key, 10 with one
+    let pause = match engine {
+        Engine::PubMed if config.search.ncbi_api_key.is_empty() => Duration::from_millis(350),
+        Engine::PubMed => Duration::from_millis(110),
+        _ => Duration::ZERO,
+    };
+    let mut counts: HashMap<String, Option<usize>> = HashMap::new();
+    for candidate in analysis.biomarker_candidates.iter_mut().take(top_n) {
+        let Some(gene) = searchable_gene(candidate) else {
+            continue;
+        };
+        let query = settings.query.replace("{gene}", &gene);
+        summary.searched += 1;
+        if !counts.contains_key(&gene) {
+            if !counts.is_empty() {
+                tokio::time::sleep(pause).await;
+            }
+            let count = match publications(config, engine, &query).await {
+                Ok(count) => Some(count),
+                Err(e) => {
+                    warn!(gene = %gene, error = %e, "Literature cross-reference failed");
+                    None
+                }
+            };
+            counts.insert(gene.clone(), count);
+        }
+        let Some(publications) = counts[&gene] else {
+            summary.failed += 1;
+            continue;
+        };
+        let status = if publications >= settings.min_reports {
+            summary.reported += 1;
+            EvidenceStatus::PreviouslyReported
+        } else {
+            summary.novel += 1;
+            EvidenceStatus::Novel
+        };
+        candidate.prior_evidence = Some(PriorEvidence {
+            query,
+            publications,
+            status,
+        });
+    }
+    if summary.searched == 0 {
+        return;
+    }
+
+    info!(
+        engine = %summary.engine,
+        searched = summary.searched,
+        reported = summary.reported,
+        novel = summary.novel,
+        failed = summary.failed,
+        "Literature cross-referencing finished"
+    );
+    analysis.summary.push_str(&format!(
+        " Literature cross-reference ({}): {} previously reported, {} novel among the top {}.",
+        engine_name(&summary.engine),
+        summary.reported,
+        summary.novel,
+        summary.searched
+    ));
+    if let Some(manifest) = &mut analysis.manifest {
+        let mut stage = Stage::finished("cross_reference", None, started);
+        stage.provider = Some(summary.engine.clone());
+        manifest.stage(stage);
+    }
+    analysis.cross_reference = Some(summary);
+}
+
+/// Gene name to search for `candidate`: its human ortholog or symbol, or a
+/// column name that looks like a gene symbol (`TP53`, `Cdkn2a`, `HLA-DRB1`)
+fn searchable_gene(candidate: &BiomarkerCandidate) -> Option<String> {
+    if candidate.human_ortholog.is_some() || candidate.symbol.is_some() {
+        return Some(candidate.human_label().to_string());
+    }
+    let column = candidate.column.trim();
+    let looks_like_symbol = (2..=15).contains(&column.len())
+        && column.starts_with(|c: char| c.is_ascii_alphabetic())
+        && column.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        && column.chars().any(|c| c.is_ascii_uppercase());
+    looks_like_symbol.then(|| column.to_string())
+}
+
+/// Publications matching `query` on `engine`
+async fn publications(config: &Config, engine: Engine, query: &str) -> Result<usize> {
+    if engine == Engine::PubMed {
+        let client = PubMedClient::from_config(&config.search)
+            .with_http_client(crate::utils::http::client(&config.network, "pubmed"));
+        return Ok(client.count(query).await?);
+    }
+    let results = SearchAggregator::new(config).search(query, &[engine]).await;
+    if results.scholar_results.is_empty() && !results.errors.is_empty() {
+        bail!("{}", results.errors.join("; "));
+    }
+    Ok(results.scholar_results.len())
+}
+
+fn engine_name(engine: &str) -> &str {
+    match Engine::parse(engine) {
+        Some(Engine::PubMed) => "PubMed",
+        Some(Engine::Scholar) => "Google Scholar",
+        Some(Engine::SemanticScholar) => "Semantic Scholar",
+        _ => engine,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(column: &str, symbol: Option<&str>, human_ortholog: Option<&str>) -> BiomarkerCandidate {
+        BiomarkerCandidate {
+            column: column.to_string(),
+            symbol: symbol.map(str::to_string),
+            biotype: None,
+            human_ortholog: human_ortholog.map(str::to_string),
+            score: 0.9,
+            correlation: 0.9,
+            n: 20,
+            p_value: 0.001,
+            q_value: 0.01,
+            auc: None,
+            direction: "positive".to_string(),
+            notes: String::new(),
+            prior_evidence: None,
+        }
+    }
+
+    #[test]
+    fn test_searchable_gene() {
+        let gene = |c: BiomarkerCandidate| searchable_gene(&c);
+        assert_eq!(gene(candidate("ENSMUSG00000059552", Some("Trp53"), Some("TP53"))).as_deref(), Some("TP53"));
+        assert_eq!(gene(candidate("ENSG00000141510", Some("TP53"), None)).as_deref(), Some("TP53"));
+        assert_eq!(gene(candidate("HLA-DRB1", None, None)).as_deref(), Some("HLA-DRB1"));
+        assert_eq!(gene(candidate("gene_a", None, None)), None);
+        assert_eq!(gene(candidate("batch", None, None)), None);
+    }
+}
@@ -0,0 +1,237 @@
+//! Reproducibility manifest of an analysis run
+//!
+//! `manifest.json` records what a run was made of: the crate version and
+//! git commit it was built from, the SHA-256 of the dataset, every
+//! analysis parameter, the seeds of the random draws and, for each stage
+//! (analysis, literature cross-referencing, the agents), the LLM provider
+//! and model it used and how long it took. [`super::run_analysis`] writes
+//! it first; later stages add themselves and the report rewrites it. The
+//! manuscript's Methods refer to it.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use super::{aggregate, gsea, regularized, AnalysisConfig};
+use crate::config::ResolvedLlm;
+use crate::data_registry::DatasetRecord;
+
+/// Name of the manifest next to the other analysis artifacts
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Commit the binary was built from, set by `build.rs` when built from a
+/// git checkout
+const GIT_HASH: Option<&str> = option_env!("OXBIO_GIT_HASH");
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    /// oxidized-bio version
+    pub version: &'static str,
+    pub git: Option<&'static str>,
+    pub started: DateTime<Utc>,
+    pub dataset: DatasetChecksum,
+    /// Every field of the [`AnalysisConfig`]
+    pub config: serde_json::Value,
+    /// Seed of each random draw, by what it drives
+    pub seeds: BTreeMap<&'static str, u64>,
+    /// In the order they ran
+    pub stages: Vec<Stage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetChecksum {
+    pub id: String,
+    pub filename: String,
+    /// Hex digest of the file analysed; `None` when it could not be read
+    pub sha256: Option<String>,
+    pub bytes: Option<u64>,
+    pub rows: usize,
+    pub columns: usize,
+}
+
+/// One step of the run
+#[derive(Debug, Clone, Serialize)]
+pub struct Stage {
+    /// "analysis", "planning", "drafting", ...
+    pub name: String,
+    /// LLM provider, or search engine, the stage called; `None` when it ran
+    /// offline
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub started: DateTime<Utc>,
+    /// Wall-clock time
+    pub seconds: f64,
+}
+
+impl Stage {
+    /// Stage `name` started at `started` and finished now
+    pub fn finished(name: &str, llm: Option<&ResolvedLlm>, started: DateTime<Utc>) -> Self {
+        Self {
+            name: name.to_string(),
+            provider: llm.map(|llm| llm.provider.clone()),
+            model: llm.map(|llm| llm.model.clone()),
+            started,
+            seconds: (Utc::now() - started).num_milliseconds() as f64 / 1000.0,
+        }
+    }
+}
+
+impl Manifest {
+    /// Manifest of analysing `record` with `config`, started now
+    pub fn new(record: &DatasetRecord, config: &AnalysisConfig) -> Self {
+        let path = Path::new(&record.local_path);
+        let sha256 = checksum(path)
+            .map_err(|e| warn!(path = %path.display(), error = %e, "Dataset checksum failed"))
+            .ok();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git: GIT_HASH,
+            started: Utc::now(),
+            dataset: DatasetChecksum {
+                id: record.dataset.id.clone(),
+                filename: record.dataset.filename.clone(),
+                sha256,
+                bytes: std::fs::metadata(path).ok().map(|m| m.len()),
+                rows: record.row_count,
+                columns: record.columns.len(),
+            },
+            config: parameters(config),
+            seeds: BTreeMap::from([
+                ("gsea_permutations", gsea::SEED),
+                ("penalty_folds", regularized::SEED),
+                ("reservoir_sample", aggregate::RESERVOIR_SEED),
+            ]),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Methods sentence pointing readers to the manifest
+    pub fn methods(&self) -> String {
+        let build = match self.git {
+            Some(git) => format!("oxidized-bio {} (commit {})", self.version, git),
+            None => format!("oxidized-bio {}", self.version),
+        };
+        let checksum = match &self.dataset.sha256 {
+            Some(sha256) => format!(", the SHA-256 checksum of the dataset ({})", &sha256[..sha256.len().min(12)]),
+            None => String::new(),
+        };
+        format!(
+            "Analyses were run with {}; the full parameters{}, random seeds and the model and run time of each \
+             step are recorded in {} for auditability.",
+            build, checksum, MANIFEST_FILE
+        )
+    }
+
+    pub fn stage(&mut self, stage: Stage) {
+        self.stages.push(stage);
+    }
+
+    /// The manifest as written, stamped as finished now
+    pub fn to_json(&self) -> Result<String> {
+        let mut json = serde_json::to_value(self)?;
+        json["finished"] = serde_json::to_value(Utc::now())?;
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+
+    /// Write `manifest.json` to `dir`
+    pub fn write(&self, dir: &Path) -> Result<PathBuf> {
+        let path = dir.join(MANIFEST_FILE);
+        std::fs::write(&path, self.to_json()?).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Every field of `config`, as the analysis JSON of the supplement and the
+/// manifest record it
+pub fn parameters(config: &AnalysisConfig) -> serde_json::Value {
+    serde_json::json!({
+        "target": config.target_column,
+        "group": config.group_column,
+        "covariates": config.covariates,
+        "formula": config.formula.as_ref().map(ToString::to_string),
+        "correlation": config.correlation,
+        "thresholds": config.thresholds,
+        "kruskal_wallis": config.kruskal_wallis,
+        "memory_limit": config.memory_limit,
+        "threads": config.threads,
+        "regularization": config.regularization,
+        "roc": config.roc,
+        "gene_sets": config.gene_sets,
+        "time": config.time_column,
+        "event": config.event_column,
+        "subject": config.subject_column,
+        "replicates": config.replicates,
+        "imputation": config.imputation,
+        "normalization": config.normalization,
+        "batch": config.batch_column,
+        "batch_correction": config.batch_column.as_ref().map(|_| config.batch_correction),
+        "tables": config.tables,
+        "numbers": config.numbers,
+        "boxplot": config.boxplot_column,
+        "markers": config.markers,
+        "max_columns": config.max_columns,
+        "max_groups": config.max_groups,
+        "platform": config.platform,
+        "collapse": config.platform.as_ref().map(|_| config.collapse),
+    })
+}
+
+/// Hex SHA-256 of the file at `path`
+fn checksum(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_registry::ParseOptions;
+
+    #[test]
+    fn test_manifest_records_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        std::fs::write(&path, "sample,age,gene_a\ns1,30,1.5\ns2,60,2.5\n").unwrap();
+        let record = DatasetRecord::from_path_with(&path, None, ParseOptions::default()).unwrap();
+        let config = AnalysisConfig {
+            target_column: Some("age".to_string()),
+            max_columns: 10,
+            max_groups: 5,
+            threads: 2,
+            ..Default::default()
+        };
+
+        let mut manifest = Manifest::new(&record, &config);
+        assert_eq!(
+            manifest.dataset.sha256.as_deref(),
+            Some(format!("{:x}", Sha256::digest(std::fs::read(&path).unwrap())).as_str())
+        );
+        assert_eq!(manifest.dataset.rows, 2);
+        assert_eq!(manifest.config["target"], "age");
+        assert_eq!(manifest.config["threads"], 2);
+        assert_eq!(manifest.seeds["penalty_folds"], regularized::SEED);
+        assert!(manifest.methods().contains(&manifest.dataset.sha256.as_ref().unwrap()[..12]));
+
+        let llm = ResolvedLlm {
+            provider: "mock".to_string(),
+            model: "mock-model".to_string(),
+            api_key: String::new(),
+            temperature: None,
+            max_tokens: None,
+        };
+        manifest.stage(Stage::finished("drafting", Some(&llm), manifest.started));
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(manifest.write(dir.path()).unwrap()).unwrap()).unwrap();
+        assert_eq!(written["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(written["stages"][0]["model"], "mock-model");
+        assert!(written["finished"].is_string());
+    }
+}
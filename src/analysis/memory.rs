@@ -0,0 +1,235 @@
+//! Memory estimate of an analysis
+//!
+//! The single pass over the rows keeps a bounded sample of each selected
+//! column and marker-target pair (see [`sample_size`]), later passes such as
+//! the regularized model hold the selected values a couple of times, the PCA
+//! scales and decomposes a copy of the matrix, and k-means holds all pairwise
+//! sample distances. The
+//! estimate adds these up from the row and selected-column counts before
+//! anything is read, so that a run too large for the machine stops with a
+//! suggestion instead of being killed halfway through.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::kmeans;
+
+/// Copies of each selected value held by the passes after the row pass
+/// (the regularized model's rows and design)
+const VALUE_COPIES: u64 = 2;
+
+/// Bytes of one sampled value in the row pass: the column's value and its
+/// pair with the target
+const SAMPLED_BYTES: u64 = 24;
+
+/// Bytes the row pass's samples may take over all columns
+const SAMPLE_BUDGET: u64 = 1 << 30;
+
+/// Fewest and most values sampled per column; columns with no more values
+/// than the sample are summarised exactly
+const MIN_SAMPLE: usize = 1_000;
+const MAX_SAMPLE: usize = 100_000;
+
+/// Copies of the PCA matrix: the scaled matrix and the SVD's factors
+const PCA_COPIES: u64 = 3;
+
+/// Fixed overhead of plotting, tables and the dataset record
+const BASELINE_BYTES: u64 = 64 << 20;
+
+/// Share of the limit above which the run goes ahead with a warning
+pub const WARN_FRACTION: f64 = 0.5;
+
+/// Most memory an analysis may be estimated to need
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum MemoryLimit {
+    /// The memory available when the analysis starts (Linux only; no
+    /// limit elsewhere)
+    #[default]
+    Auto,
+    Bytes(u64),
+    /// No check
+    Off,
+}
+
+impl MemoryLimit {
+    /// Limit in bytes; `None` when unchecked or unknown
+    pub fn bytes(self) -> Option<u64> {
+        match self {
+            Self::Auto => available(),
+            Self::Bytes(bytes) => Some(bytes),
+            Self::Off => None,
+        }
+    }
+}
+
+impl fmt::Display for MemoryLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::Off => f.write_str("off"),
+            Self::Bytes(bytes) => f.write_str(&format_bytes(*bytes)),
+        }
+    }
+}
+
+impl FromStr for MemoryLimit {
+    type Err = String;
+
+    /// `auto`, `off` or a size: bytes, or a number with a K, M, G or T
+    /// (binary) suffix such as `512M` or `1.5G`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        match s.as_str() {
+            "auto" => return Ok(Self::Auto),
+            "off" | "none" => return Ok(Self::Off),
+            _ => {}
+        }
+        let number = s.trim_end_matches(['b', 'i']);
+        let (number, shift) = match number.chars().last() {
+            Some('k') => (&number[..number.len() - 1], 10),
+            Some('m') => (&number[..number.len() - 1], 20),
+            Some('g') => (&number[..number.len() - 1], 30),
+            Some('t') => (&number[..number.len() - 1], 40),
+            _ => (number, 0),
+        };
+        match number.trim().parse::<f64>() {
+            Ok(value) if value > 0.0 && value.is_finite() => Ok(Self::Bytes((value * (1u64 << shift) as f64) as u64)),
+            _ => Err(format!("invalid memory limit '{}' (expected auto, off or a size such as 4G)", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for MemoryLimit {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<MemoryLimit> for String {
+    fn from(limit: MemoryLimit) -> Self {
+        limit.to_string()
+    }
+}
+
+/// Values the row pass samples per column when `columns` are selected, so
+/// that all samples fit in [`SAMPLE_BUDGET`]
+pub fn sample_size(columns: usize) -> usize {
+    let per_column = SAMPLE_BUDGET / (columns.max(1) as u64).saturating_mul(SAMPLED_BYTES);
+    (per_column as usize).clamp(MIN_SAMPLE, MAX_SAMPLE)
+}
+
+/// Estimated peak bytes of analysing `columns` selected columns over `rows`
+/// rows
+pub fn estimate(rows: usize, columns: usize) -> u64 {
+    let sampled = rows.min(sample_size(columns)) as u64;
+    let (rows, columns) = (rows as u64, columns as u64);
+    let cells = rows.saturating_mul(columns);
+    let clustered = rows.min(kmeans::MAX_SAMPLES as u64);
+    let per_value = (VALUE_COPIES + PCA_COPIES) * 8;
+    BASELINE_BYTES
+        .saturating_add(cells.saturating_mul(per_value))
+        .saturating_add(sampled.saturating_mul(columns).saturating_mul(SAMPLED_BYTES))
+        .saturating_add(clustered * clustered * 8)
+}
+
+/// Fail when analysing `columns` columns over `rows` rows is estimated to
+/// need more than `limit`, and warn above [`WARN_FRACTION`] of it
+pub fn check(rows: usize, columns: usize, limit: MemoryLimit) -> Result<()> {
+    let Some(limit_bytes) = limit.bytes() else {
+        return Ok(());
+    };
+    let needed = estimate(rows, columns);
+    if needed > limit_bytes {
+        let limit_name = match limit {
+            MemoryLimit::Auto => "memory available",
+            _ => "memory limit",
+        };
+        bail!(
+            "Analysing {} columns over {} rows needs about {} of memory, more than the {} {}. \
+             Analyse fewer columns (markers or max_columns), subset the rows (e.g. one cohort at a \
+             time), or raise the memory limit (memory_limit; `off` skips this check).",
+            columns,
+            rows,
+            format_bytes(needed),
+            format_bytes(limit_bytes),
+            limit_name
+        );
+    }
+    if needed as f64 > limit_bytes as f64 * WARN_FRACTION {
+        warn!(
+            rows,
+            columns,
+            estimate = %format_bytes(needed),
+            limit = %format_bytes(limit_bytes),
+            "Analysis will use most of the memory limit"
+        );
+    }
+    Ok(())
+}
+
+/// `MemAvailable` of /proc/meminfo; `None` where it cannot be read
+pub fn available() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))
+        .and_then(|rest| rest.trim().strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
+
+/// `bytes` in the largest binary unit that keeps it at least 1, e.g. `1.5G`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [(&str, u32); 4] = [("T", 40), ("G", 30), ("M", 20), ("K", 10)];
+    for (unit, shift) in UNITS {
+        if bytes >= 1 << shift {
+            let value = bytes as f64 / (1u64 << shift) as f64;
+            return if value.fract() == 0.0 {
+                format!("{}{}", value, unit)
+            } else {
+                format!("{:.1}{}", value, unit)
+            };
+        }
+    }
+    bytes.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_limit_and_estimate() {
+        assert_eq!("auto".parse::<MemoryLimit>(), Ok(MemoryLimit::Auto));
+        assert_eq!("Off".parse::<MemoryLimit>(), Ok(MemoryLimit::Off));
+        assert_eq!("512M".parse::<MemoryLimit>(), Ok(MemoryLimit::Bytes(512 << 20)));
+        assert_eq!("1.5GiB".parse::<MemoryLimit>(), Ok(MemoryLimit::Bytes(3 << 29)));
+        assert_eq!("2048".parse::<MemoryLimit>(), Ok(MemoryLimit::Bytes(2048)));
+        assert!("lots".parse::<MemoryLimit>().is_err());
+        assert!("-1G".parse::<MemoryLimit>().is_err());
+        assert_eq!(MemoryLimit::Bytes(3 << 29).to_string(), "1.5G");
+        assert_eq!(MemoryLimit::Bytes(4 << 30).to_string(), "4G");
+        assert_eq!(MemoryLimit::Off.bytes(), None);
+
+        // Grows with both dimensions; the pairwise distances are capped
+        let small = estimate(100, 50);
+        assert!(small > BASELINE_BYTES && small < BASELINE_BYTES + (1 << 20));
+        assert!(estimate(100, 5000) > estimate(100, 50));
+        let wide = estimate(100_000, 20_000);
+        assert!(wide > 64 << 30, "{}", format_bytes(wide));
+        assert_eq!((sample_size(10), sample_size(20_000), sample_size(50_000)), (MAX_SAMPLE, 2_236, MIN_SAMPLE));
+        assert_eq!(estimate(usize::MAX, usize::MAX), u64::MAX);
+
+        assert!(check(100, 50, MemoryLimit::Bytes(1 << 30)).is_ok());
+        assert!(check(100_000, 20_000, MemoryLimit::Off).is_ok());
+        let err = check(100_000, 20_000, MemoryLimit::Bytes(8 << 30)).unwrap_err().to_string();
+        assert!(err.contains("more than the 8G memory limit.") && err.contains("max_columns"), "{}", err);
+    }
+}
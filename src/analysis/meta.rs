@@ -0,0 +1,371 @@
+//! Meta-analysis of several analysis runs
+//!
+//! `/meta` pools each marker's correlation with the target across the runs
+//! of several datasets (a discovery cohort and its replications). The
+//! correlations are Fisher z-transformed with variance 1/(n - 3) and
+//! combined by inverse-variance weighting, both with a fixed effect and with
+//! DerSimonian–Laird random effects; Cochran's Q and I² measure how much the
+//! cohorts disagree. The markers with the strongest pooled evidence get a
+//! forest plot.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use serde::Serialize;
+
+use super::compare::RankedMarker;
+use super::stats;
+
+/// Per-marker meta-analysis table
+pub const META_FILE: &str = "meta_analysis.csv";
+
+/// Markers drawn as forest plots by default
+pub const FOREST_PLOTS: usize = 5;
+
+/// Fewest runs a marker has to appear in to be pooled
+const MIN_STUDIES: usize = 2;
+
+/// Two-sided 95% normal quantile
+const Z_95: f64 = 1.959_964;
+
+/// Index of a run, its r and n for one marker
+type Estimate = (usize, f64, usize);
+
+/// One run's estimate for a marker
+#[derive(Debug, Clone, Serialize)]
+pub struct StudyEffect {
+    pub study: String,
+    pub r: f64,
+    pub n: usize,
+    /// Share of the random-effects weight
+    pub weight: f64,
+}
+
+/// A pooled estimate: r with its 95% confidence interval and p-value
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Pooled {
+    pub r: f64,
+    pub ci: [f64; 2],
+    pub p_value: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetaMarker {
+    pub column: String,
+    pub label: String,
+    pub studies: Vec<StudyEffect>,
+    pub fixed: Pooled,
+    pub random: Pooled,
+    /// Benjamini–Hochberg q-value of the random-effects p-value
+    pub q_value: f64,
+    /// Cochran's Q and its p-value on k - 1 degrees of freedom
+    pub cochran_q: f64,
+    pub heterogeneity_p: f64,
+    /// Share of the variation due to heterogeneity rather than chance
+    pub i_squared: f64,
+    /// Between-study variance of z (DerSimonian–Laird)
+    pub tau_squared: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetaAnalysis {
+    pub studies: Vec<String>,
+    /// Pooled markers, smallest random-effects p-value first
+    pub markers: Vec<MetaMarker>,
+}
+
+impl MetaAnalysis {
+    /// Pooled markers with random-effects q below `fdr`
+    pub fn significant(&self, fdr: f64) -> impl Iterator<Item = &MetaMarker> {
+        self.markers.iter().filter(move |m| m.q_value < fdr)
+    }
+
+    /// Pooled markers whose cohorts disagree (I² of 50% or more)
+    pub fn heterogeneous(&self) -> usize {
+        self.markers.iter().filter(|m| m.i_squared >= 0.5).count()
+    }
+}
+
+/// Pool the markers of `runs` (name and ranked biomarker table) that appear
+/// in at least two of them with more than three samples each
+pub fn meta_analyze(runs: &[(String, Vec<RankedMarker>)]) -> Result<MetaAnalysis> {
+    if runs.len() < MIN_STUDIES {
+        bail!("A meta-analysis needs at least {} analysis runs", MIN_STUDIES);
+    }
+    // Marker column -> (label, per-run estimates), in first-seen order
+    let mut order: Vec<&str> = Vec::new();
+    let mut effects: HashMap<&str, (String, Vec<Estimate>)> = HashMap::new();
+    for (study, (_, markers)) in runs.iter().enumerate() {
+        for m in markers.iter().filter(|m| m.n > 3 && m.correlation.is_finite()) {
+            let entry = effects.entry(m.column.as_str()).or_insert_with(|| {
+                order.push(m.column.as_str());
+                (String::new(), Vec::new())
+            });
+            if entry.0.is_empty() && !m.symbol.is_empty() {
+                entry.0 = m.symbol.clone();
+            }
+            entry.1.push((study, m.correlation, m.n));
+        }
+    }
+
+    let mut markers: Vec<MetaMarker> = order
+        .into_iter()
+        .filter_map(|column| {
+            let (label, estimates) = &effects[column];
+            (estimates.len() >= MIN_STUDIES).then(|| {
+                let label = if label.is_empty() { column.to_string() } else { label.clone() };
+                pool(column, label, estimates, runs)
+            })
+        })
+        .collect();
+    let p_values: Vec<f64> = markers.iter().map(|m| m.random.p_value).collect();
+    for (marker, q) in markers.iter_mut().zip(stats::benjamini_hochberg(&p_values)) {
+        marker.q_value = q;
+    }
+    markers.sort_by(|a, b| a.random.p_value.total_cmp(&b.random.p_value));
+    Ok(MetaAnalysis {
+        studies: runs.iter().map(|(name, _)| name.clone()).collect(),
+        markers,
+    })
+}
+
+fn pool(column: &str, label: String, estimates: &[Estimate], runs: &[(String, Vec<RankedMarker>)]) -> MetaMarker {
+    // |r| = 1 has an infinite z; keep it just inside the boundary
+    let z: Vec<f64> = estimates.iter().map(|(_, r, _)| r.clamp(-0.999_999, 0.999_999).atanh()).collect();
+    let v: Vec<f64> = estimates.iter().map(|(_, _, n)| 1.0 / (*n as f64 - 3.0)).collect();
+
+    let w: Vec<f64> = v.iter().map(|v| 1.0 / v).collect();
+    let (fixed, fixed_se) = weighted_mean(&z, &w);
+    let cochran_q: f64 = z.iter().zip(&w).map(|(z, w)| w * (z - fixed).powi(2)).sum();
+    let df = (estimates.len() - 1) as f64;
+    let sum_w: f64 = w.iter().sum();
+    let c = sum_w - w.iter().map(|w| w * w).sum::<f64>() / sum_w;
+    let tau_squared = if c > 0.0 { ((cochran_q - df) / c).max(0.0) } else { 0.0 };
+    let i_squared = if cochran_q > 0.0 { ((cochran_q - df) / cochran_q).max(0.0) } else { 0.0 };
+
+    let w_random: Vec<f64> = v.iter().map(|v| 1.0 / (v + tau_squared)).collect();
+    let (random, random_se) = weighted_mean(&z, &w_random);
+    let total: f64 = w_random.iter().sum();
+
+    MetaMarker {
+        column: column.to_string(),
+        label,
+        studies: estimates
+            .iter()
+            .zip(&w_random)
+            .map(|((study, r, n), w)| StudyEffect {
+                study: runs[*study].0.clone(),
+                r: *r,
+                n: *n,
+                weight: w / total,
+            })
+            .collect(),
+        fixed: pooled(fixed, fixed_se),
+        random: pooled(random, random_se),
+        q_value: 1.0,
+        cochran_q,
+        heterogeneity_p: stats::chi_squared_p_value(cochran_q, df),
+        i_squared,
+        tau_squared,
+    }
+}
+
+/// Inverse-variance weighted mean of `z` and its standard error
+fn weighted_mean(z: &[f64], w: &[f64]) -> (f64, f64) {
+    let sum_w: f64 = w.iter().sum();
+    (z.iter().zip(w).map(|(z, w)| z * w).sum::<f64>() / sum_w, (1.0 / sum_w).sqrt())
+}
+
+/// Back-transform a pooled z and its standard error to r
+fn pooled(z: f64, se: f64) -> Pooled {
+    Pooled {
+        r: z.tanh(),
+        ci: [(z - Z_95 * se).tanh(), (z + Z_95 * se).tanh()],
+        p_value: stats::normal_p_value(z / se),
+    }
+}
+
+/// Write the meta-analysis table and a forest plot for each of the first
+/// `forest_plots` markers to `output_dir`; returns the files written
+pub fn write_meta(output_dir: &Path, meta: &MetaAnalysis, forest_plots: usize) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(output_dir).with_context(|| format!("Failed to create {}", output_dir.display()))?;
+    let table = output_dir.join(META_FILE);
+    let mut wtr = csv::Writer::from_path(&table)?;
+    let mut header: Vec<String> = ["column", "label", "studies", "n"].map(String::from).to_vec();
+    header.extend(meta.studies.iter().map(|s| format!("r_{}", s)));
+    header.extend(
+        [
+            "fixed_r",
+            "fixed_ci_low",
+            "fixed_ci_high",
+            "fixed_p",
+            "random_r",
+            "random_ci_low",
+            "random_ci_high",
+            "random_p",
+            "random_q",
+            "cochran_q",
+            "heterogeneity_p",
+            "i_squared",
+            "tau_squared",
+        ]
+        .map(String::from),
+    );
+    wtr.write_record(&header)?;
+    for m in &meta.markers {
+        let mut row = vec![
+            m.column.clone(),
+            m.label.clone(),
+            m.studies.len().to_string(),
+            m.studies.iter().map(|s| s.n).sum::<usize>().to_string(),
+        ];
+        row.extend(meta.studies.iter().map(|study| {
+            m.studies.iter().find(|s| &s.study == study).map(|s| s.r.to_string()).unwrap_or_default()
+        }));
+        row.extend(
+            [
+                m.fixed.r,
+                m.fixed.ci[0],
+                m.fixed.ci[1],
+                m.fixed.p_value,
+                m.random.r,
+                m.random.ci[0],
+                m.random.ci[1],
+                m.random.p_value,
+                m.q_value,
+                m.cochran_q,
+                m.heterogeneity_p,
+                m.i_squared,
+                m.tau_squared,
+            ]
+            .map(|v| v.to_string()),
+        );
+        wtr.write_record(&row)?;
+    }
+    wtr.flush()?;
+
+    let mut files = vec![table];
+    for (rank, marker) in meta.markers.iter().take(forest_plots).enumerate() {
+        let name: String = marker
+            .label
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        let plot = output_dir.join(format!("forest_{}_{}.png", rank + 1, name));
+        write_forest_plot(&plot, marker)?;
+        files.push(plot);
+    }
+    Ok(files)
+}
+
+/// Each run's r with its 95% confidence interval (squares sized by weight),
+/// then the fixed- and random-effects estimates as diamonds
+fn write_forest_plot(output_path: &Path, marker: &MetaMarker) -> Result<()> {
+    let mut labels: Vec<String> = marker.studies.iter().map(|s| format!("{} (n = {})", s.study, s.n)).collect();
+    labels.push("Fixed effect".to_string());
+    labels.push(format!("Random effects (I² = {:.0}%)", marker.i_squared * 100.0));
+    let rows = labels.len();
+
+    let root = BitMapBackend::new(output_path, (900, 120 + 40 * rows as u32)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .margin(20)
+        .caption(format!("{}: correlation with the target", marker.label), ("sans-serif", 22))
+        .x_label_area_size(40)
+        .y_label_area_size(260)
+        .build_cartesian_2d(-1f64..1f64, 0f64..rows as f64)?;
+
+    // The first run is drawn at the top, the pooled estimates at the bottom
+    let row = |idx: usize| (rows - 1 - idx) as f64 + 0.5;
+    chart.configure_mesh().disable_mesh().x_desc("r (95% CI)").y_labels(0).draw()?;
+    // Row labels right-aligned against the y axis, level with their row
+    let label_style = ("sans-serif", 14)
+        .into_font()
+        .into_text_style(&root)
+        .pos(Pos::new(HPos::Right, VPos::Center));
+    for (idx, label) in labels.iter().enumerate() {
+        let (x, y) = chart.backend_coord(&(-1.0, row(idx)));
+        root.draw(&Text::new(label.clone(), (x - 10, y), label_style.clone()))?;
+    }
+    chart.draw_series(std::iter::once(PathElement::new(vec![(0.0, 0.0), (0.0, rows as f64)], BLACK.mix(0.4))))?;
+
+    for (idx, study) in marker.studies.iter().enumerate() {
+        let y = row(idx);
+        let se = 1.0 / (study.n as f64 - 3.0).sqrt();
+        let z = study.r.clamp(-0.999_999, 0.999_999).atanh();
+        let ci = ((z - Z_95 * se).tanh(), (z + Z_95 * se).tanh());
+        chart.draw_series(std::iter::once(PathElement::new(vec![(ci.0, y), (ci.1, y)], BLACK)))?;
+        let half = 0.05 + 0.25 * study.weight.sqrt();
+        chart.draw_series(std::iter::once(Rectangle::new(
+            [(study.r - half * 0.1, y - half * 0.5), (study.r + half * 0.1, y + half * 0.5)],
+            BLUE.filled(),
+        )))?;
+    }
+    let studies = marker.studies.len();
+    for (idx, (estimate, color)) in [(marker.fixed, BLACK), (marker.random, RED)].into_iter().enumerate() {
+        let y = row(studies + idx);
+        let diamond = vec![(estimate.ci[0], y), (estimate.r, y + 0.25), (estimate.ci[1], y), (estimate.r, y - 0.25)];
+        chart.draw_series(std::iter::once(Polygon::new(diamond, color.mix(0.8).filled())))?;
+    }
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, markers: &[(&str, f64, usize)]) -> (String, Vec<RankedMarker>) {
+        let markers = markers
+            .iter()
+            .map(|(column, correlation, n)| RankedMarker {
+                column: column.to_string(),
+                symbol: String::new(),
+                correlation: *correlation,
+                n: *n,
+            })
+            .collect();
+        (name.to_string(), markers)
+    }
+
+    #[test]
+    fn test_fixed_and_random_effects() {
+        let runs = [
+            run("discovery", &[("g1", 0.5, 103), ("g2", 0.6, 53), ("g3", 0.4, 40)]),
+            run("replication", &[("g1", 0.5, 53), ("g2", -0.2, 53)]),
+            run("validation", &[("g1", 0.5, 28), ("g3", 0.9, 2)]),
+        ];
+        let meta = meta_analyze(&runs).unwrap();
+        // g3 has a usable estimate in one run only
+        assert_eq!(meta.markers.len(), 2);
+
+        let g1 = &meta.markers[0];
+        assert_eq!((g1.column.as_str(), g1.studies.len()), ("g1", 3));
+        // Identical estimates: no heterogeneity, both models agree
+        assert!((g1.fixed.r - 0.5).abs() < 1e-9 && (g1.random.r - 0.5).abs() < 1e-9);
+        assert!(g1.cochran_q < 1e-12 && g1.i_squared == 0.0 && g1.tau_squared == 0.0);
+        assert!((g1.studies[0].weight - 100.0 / 175.0).abs() < 1e-12);
+        // SE of the pooled z is 1/sqrt(100 + 50 + 25)
+        let z = 0.5f64.atanh() * 175f64.sqrt();
+        assert!((g1.fixed.p_value - stats::normal_p_value(z)).abs() < 1e-15);
+
+        let g2 = &meta.markers[1];
+        assert!(g2.i_squared > 0.8 && g2.tau_squared > 0.0);
+        assert!(g2.random.ci[1] - g2.random.ci[0] > g2.fixed.ci[1] - g2.fixed.ci[0]);
+        assert_eq!(meta.significant(0.05).count(), 1);
+        assert_eq!(meta.heterogeneous(), 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let files = write_meta(dir.path(), &meta, FOREST_PLOTS).unwrap();
+        assert_eq!(files.len(), 3);
+        assert!(dir.path().join("forest_1_g1.png").is_file());
+        let table = std::fs::read_to_string(dir.path().join(META_FILE)).unwrap();
+        assert!(table.starts_with("column,label,studies,n,r_discovery,r_replication,r_validation,fixed_r"));
+        assert!(table.lines().nth(2).unwrap().starts_with("g2,g2,2,106,0.6,-0.2,,"));
+
+        assert!(meta_analyze(&runs[..1]).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+//! Linear mixed-effects models for repeated measures
+//!
+//! Datasets with several samples per subject break the independence that
+//! ordinary least squares assumes: a subject's samples share its baseline,
+//! so there is less information than rows and OLS p-values are too small.
+//! With a subject column each model gets a random intercept per subject,
+//! `y = Xβ + u_subject + ε` with `u ~ N(0, σ²_u)` and `ε ~ N(0, σ²)`.
+//!
+//! The variance ratio `λ = σ²_u / σ²` maximises the REML likelihood
+//! profiled over β and σ², found by golden-section search on `ln λ` (and
+//! compared with `λ = 0`, the OLS fit). Given λ, β is the GLS estimate,
+//! computed as OLS on the rows quasi-demeaned within each subject. Wald t
+//! tests take subjects minus fixed effects as their degrees of freedom,
+//! which is conservative for markers that vary within subjects.
+
+use nalgebra::{Cholesky, DMatrix, DVector, Dyn};
+
+/// Search interval of `ln λ`
+const LOG_RATIO: (f64, f64) = (-12.0, 8.0);
+
+/// Golden-section steps; narrows the interval below 1e-9 of its width
+const SEARCH_STEPS: usize = 60;
+
+/// A random-intercept model fitted by REML
+#[derive(Debug, Clone)]
+pub struct MixedFit {
+    /// Fixed effects, in design-matrix column order (intercept first)
+    pub coefficients: Vec<f64>,
+    /// Standard errors of `coefficients`
+    pub se: Vec<f64>,
+    /// Residual variance σ²
+    pub residual_variance: f64,
+    /// Variance of the subject intercepts σ²_u
+    pub subject_variance: f64,
+    /// Subjects minus fixed effects
+    pub df: f64,
+    /// Variance of the fixed-effect predictions over the total variance
+    /// (Nakagawa's marginal R²)
+    pub r2: f64,
+}
+
+impl MixedFit {
+    /// Share of the variance between subjects (intraclass correlation)
+    pub fn icc(&self) -> f64 {
+        let total = self.subject_variance + self.residual_variance;
+        if total > 0.0 { self.subject_variance / total } else { 0.0 }
+    }
+
+    /// Wald t statistic of coefficient `j`
+    pub fn t(&self, j: usize) -> f64 {
+        self.coefficients[j] / self.se[j]
+    }
+}
+
+/// GLS pieces at one variance ratio
+struct Gls {
+    beta: DVector<f64>,
+    /// Inverse of X*'X*, the fixed effects' covariance over σ²
+    covariance: DMatrix<f64>,
+    rss: f64,
+    /// REML log likelihood profiled over β and σ², up to a constant
+    likelihood: f64,
+}
+
+/// `y ~ x + (1 | subject)`, with the intercept as a column of `x` and
+/// `subjects` the subject index of each row; `None` with no more subjects
+/// than fixed effects or a singular design
+pub fn fit(x: &DMatrix<f64>, y: &DVector<f64>, subjects: &[usize]) -> Option<MixedFit> {
+    let (n, p) = x.shape();
+    let mut members: Vec<Vec<usize>> = Vec::new();
+    for (row, &subject) in subjects.iter().enumerate() {
+        if subject >= members.len() {
+            members.resize(subject + 1, Vec::new());
+        }
+        members[subject].push(row);
+    }
+    members.retain(|rows| !rows.is_empty());
+    if members.len() <= p || n <= p + 1 {
+        return None;
+    }
+
+    let gls = |ratio: f64| -> Option<Gls> {
+        // Quasi-demeaning within each subject whitens V = I + λJ
+        let mut xs = x.clone();
+        let mut ys = y.clone();
+        let mut log_det_v = 0.0;
+        for rows in &members {
+            let k = rows.len() as f64;
+            let theta = 1.0 - 1.0 / (1.0 + k * ratio).sqrt();
+            log_det_v += (1.0 + k * ratio).ln();
+            if theta == 0.0 {
+                continue;
+            }
+            let y_mean = rows.iter().map(|&r| y[r]).sum::<f64>() / k;
+            for &r in rows {
+                ys[r] -= theta * y_mean;
+            }
+            for j in 0..p {
+                let x_mean = rows.iter().map(|&r| x[(r, j)]).sum::<f64>() / k;
+                for &r in rows {
+                    xs[(r, j)] -= theta * x_mean;
+                }
+            }
+        }
+        let cholesky = Cholesky::<f64, Dyn>::new(xs.transpose() * &xs)?;
+        let log_det_xtx = 2.0 * cholesky.l().diagonal().iter().map(|d| d.ln()).sum::<f64>();
+        let beta = cholesky.solve(&(xs.transpose() * &ys));
+        let rss = (&ys - &xs * &beta).norm_squared();
+        let dof = (n - p) as f64;
+        let likelihood = -0.5 * (dof * (rss / dof).ln() + log_det_v + log_det_xtx);
+        Some(Gls { beta, covariance: cholesky.inverse(), rss, likelihood })
+    };
+
+    // Golden-section search for the most likely ln λ
+    let golden = (5f64.sqrt() - 1.0) / 2.0;
+    let score = |log_ratio: f64| gls(log_ratio.exp()).map_or(f64::NEG_INFINITY, |g| g.likelihood);
+    let (mut lo, mut hi) = LOG_RATIO;
+    let mut a = hi - golden * (hi - lo);
+    let mut b = lo + golden * (hi - lo);
+    let (mut fa, mut fb) = (score(a), score(b));
+    for _ in 0..SEARCH_STEPS {
+        if fa >= fb {
+            hi = b;
+            (b, fb) = (a, fa);
+            a = hi - golden * (hi - lo);
+            fa = score(a);
+        } else {
+            lo = a;
+            (a, fa) = (b, fb);
+            b = lo + golden * (hi - lo);
+            fb = score(b);
+        }
+    }
+    let interior = gls(((lo + hi) / 2.0).exp());
+    let boundary = gls(0.0);
+    let (ratio, best) = match (interior, boundary) {
+        // λ is not identified without repeated samples, so ties go to OLS
+        (Some(interior), Some(boundary)) if interior.likelihood > boundary.likelihood + 1e-6 => {
+            (((lo + hi) / 2.0).exp(), interior)
+        }
+        (_, Some(boundary)) => (0.0, boundary),
+        (Some(interior), None) => (((lo + hi) / 2.0).exp(), interior),
+        (None, None) => return None,
+    };
+
+    let residual_variance = best.rss / (n - p) as f64;
+    let se: Vec<f64> = (0..p).map(|j| (residual_variance * best.covariance[(j, j)]).sqrt()).collect();
+    if se.iter().any(|s| !s.is_finite() || *s <= 0.0) {
+        return None;
+    }
+    let subject_variance = ratio * residual_variance;
+    let fitted = x * &best.beta;
+    let mean = fitted.mean();
+    let fixed_variance = fitted.iter().map(|f| (f - mean).powi(2)).sum::<f64>() / n as f64;
+    let total = fixed_variance + subject_variance + residual_variance;
+    Some(MixedFit {
+        coefficients: best.beta.iter().copied().collect(),
+        se,
+        residual_variance,
+        subject_variance,
+        df: (members.len() - p) as f64,
+        r2: if total > 0.0 { fixed_variance / total } else { 0.0 },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_intercept_fit() {
+        // 12 subjects with 4 samples each: a subject-level marker and a
+        // large subject effect on the target
+        let noise = |i: usize| ((i * 7919) % 13) as f64 / 13.0 - 0.5;
+        let subject_effect = |s: usize| ((s * 104_729) % 17) as f64 - 8.0;
+        let marker = |s: usize| ((s * 7919) % 11) as f64;
+        let mut rows = Vec::new();
+        let mut subjects = Vec::new();
+        let mut y = Vec::new();
+        for s in 0..12 {
+            for visit in 0..4 {
+                let i = s * 4 + visit;
+                rows.extend([1.0, marker(s)]);
+                y.push(40.0 + marker(s) + 2.0 * subject_effect(s) + noise(i));
+                subjects.push(s);
+            }
+        }
+        let x = DMatrix::from_row_slice(48, 2, &rows);
+        let y = DVector::from_vec(y);
+        let model = fit(&x, &y, &subjects).unwrap();
+        assert_eq!(model.df, 10.0);
+        assert!(model.icc() > 0.5, "ICC {}", model.icc());
+
+        // One sample per subject: no subject variance, and the OLS estimate
+        let independent: Vec<usize> = (0..48).collect();
+        let ols = fit(&x, &y, &independent).unwrap();
+        assert_eq!((ols.subject_variance, ols.df), (0.0, 46.0));
+        let (intercept, slope, _) = crate::analysis::ols_fit(&x, &y).unwrap();
+        assert!((ols.coefficients[0] - intercept).abs() < 1e-9 && (ols.coefficients[1] - slope[0]).abs() < 1e-9);
+        // Repeated samples carry less evidence than as many independent ones
+        assert!(model.t(1).abs() < ols.t(1).abs() / 1.5, "{} {}", model.t(1), ols.t(1));
+
+        assert!(fit(&x, &y, &[0; 48]).is_none());
+    }
+}
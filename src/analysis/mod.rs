@@ -1,59 +1,455 @@
-use std::collections::HashMap;
-use std::path::Path;
+pub mod aggregate;
+pub mod anova;
+pub mod batch;
+pub mod cluster;
+pub mod compare;
+pub mod contrast;
+pub mod expression;
+pub mod findings;
+pub mod formula;
+pub mod gsea;
+pub mod impute;
+pub mod kmeans;
+pub mod legends;
+pub mod literature;
+pub mod manifest;
+pub mod memory;
+pub mod meta;
+pub mod mixed;
+pub mod normalize;
+pub mod panel;
+pub mod pca;
+pub mod qc;
+pub mod regularized;
+pub mod report;
+pub mod roc;
+pub mod sex;
+pub mod stats;
+pub mod supplement;
+pub mod survival;
+pub mod threads;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
-use csv::ReaderBuilder;
 use nalgebra::{DMatrix, DVector};
 use plotters::prelude::*;
+use rayon::prelude::*;
+use tracing::warn;
 
-use crate::data_registry::DatasetRecord;
-use crate::models::{BiomarkerCandidate, DescriptiveStat, NoveltyScore, RegressionResult};
+use self::aggregate::{BoxStats, ColumnStats, GroupAggregator, PairStats, Reservoir};
+use self::batch::{BatchCorrection, BatchSummary};
+use self::contrast::GroupContrast;
+use self::formula::{Adjustment, Formula};
+use self::gsea::Gsea;
+use self::impute::{Imputation, ImputationSummary};
+use self::normalize::{Normalization, NormalizationSummary};
+use self::kmeans::KMeans;
+use self::legends::FigureLegend;
+use self::literature::CrossReferenceSummary;
+use self::manifest::{Manifest, Stage};
+use self::memory::MemoryLimit;
+use self::pca::Pca;
+use self::qc::{ReplicateSummary, Replicates, SampleQc};
+use self::regularized::{Regularization, RegularizedModel};
+use self::report::{ResultTables, TableFormat};
+use self::roc::{RocAnalysis, RocSplit};
+use self::survival::Survival;
+use self::sex::SexStratified;
+use self::stats::{CorrelationMethod, Thresholds};
+use crate::annotation::platform::{self, Collapse, CollapseSummary, ProbeMap};
+use crate::annotation::{self, GeneMap, GeneSets, Species};
+use crate::config::AnnotationConfig;
+use crate::data_registry::{ColumnKind, DatasetRecord};
+use crate::manuscript::JournalTemplate;
+use crate::utils::cancel::{self, CancellationToken};
+use crate::utils::number::{self, NumberFormat};
+use crate::models::{
+    Anova, BiomarkerCandidate, DescriptiveStat, EnrichmentResult, EvidenceStatus, GroupComparison, NoveltyScore,
+    RegressionResult,
+};
+use crate::types::{AppError, AppResult};
 
+#[derive(Clone)]
 pub struct AnalysisConfig {
     pub target_column: Option<String>,
     pub group_column: Option<String>,
     pub covariates: Vec<String>,
     pub boxplot_column: Option<String>,
+    /// Marker columns to analyse besides the target; empty analyses the
+    /// first `max_columns` numeric columns
+    pub markers: Vec<String>,
     pub max_columns: usize,
     pub max_groups: usize,
+    /// Platform annotation (GPL SOFT or Affymetrix CSV) for probe-level data
+    pub platform: Option<PathBuf>,
+    /// How probes of one gene are combined when `platform` is set
+    pub collapse: Collapse,
+    /// Model formula; its response replaces `target_column` and its terms
+    /// `covariates`, and candidates are ranked by adjusted association
+    pub formula: Option<Formula>,
+    /// How technical replicate rows of one sample are combined
+    pub replicates: Replicates,
+    /// How missing marker values are filled in before the analysis
+    pub imputation: Imputation,
+    /// Normalization steps applied to the markers before the analysis, in
+    /// order; empty leaves the values as they are
+    pub normalization: Vec<Normalization>,
+    /// Column naming each sample's batch; its batch effects are removed
+    /// from the markers before the analysis
+    pub batch_column: Option<String>,
+    /// How batch effects are removed when `batch_column` is set
+    pub batch_correction: BatchCorrection,
+    /// Coefficient markers are ranked by and the heatmap shows
+    pub correlation: CorrelationMethod,
+    /// Delimiter of the result tables written next to the plots
+    pub tables: TableFormat,
+    /// Decimal separator and grouping of the tables in manuscripts and
+    /// reports; the result tables stay plain
+    pub numbers: NumberFormat,
+    /// Significance cut-offs of every test, and of what plots and drafts
+    /// highlight
+    pub thresholds: Thresholds,
+    /// Add the Kruskal-Wallis test to the ANOVA of multi-level groups
+    pub kruskal_wallis: bool,
+    /// Runs estimated to need more memory than this fail before reading
+    /// any rows
+    pub memory_limit: MemoryLimit,
+    /// Threads computing the per-column statistics, correlations and
+    /// regressions; 0 uses one per core
+    pub threads: usize,
+    /// Penalty of the multi-marker model of the target
+    pub regularization: Regularization,
+    /// How the target is split into two classes for the candidates' ROC
+    /// curves
+    pub roc: RocSplit,
+    /// GMT gene set files tested by GSEA against the ranked candidates;
+    /// empty skips it
+    pub gene_sets: Vec<PathBuf>,
+    /// Time-to-event column; with `event_column`, enables the survival
+    /// analysis
+    pub time_column: Option<String>,
+    /// Column marking whether each sample's event happened or it was
+    /// censored
+    pub event_column: Option<String>,
+    /// Column naming the subject of each sample; repeated samples of one
+    /// subject get a random intercept in the ranking models
+    pub subject_column: Option<String>,
+}
+
+impl Default for AnalysisConfig {
+    /// No target or group, the first 50 numeric columns, at most 20 groups
+    /// and every option at its own default
+    fn default() -> Self {
+        Self {
+            target_column: None,
+            group_column: None,
+            covariates: Vec::new(),
+            boxplot_column: None,
+            markers: Vec::new(),
+            max_columns: 50,
+            max_groups: 20,
+            platform: None,
+            collapse: Default::default(),
+            formula: None,
+            replicates: Default::default(),
+            imputation: Default::default(),
+            normalization: Vec::new(),
+            batch_column: None,
+            batch_correction: Default::default(),
+            correlation: Default::default(),
+            tables: Default::default(),
+            numbers: Default::default(),
+            thresholds: Default::default(),
+            kruskal_wallis: false,
+            memory_limit: Default::default(),
+            threads: 0,
+            regularization: Default::default(),
+            roc: Default::default(),
+            gene_sets: Vec::new(),
+            time_column: None,
+            event_column: None,
+            subject_column: None,
+        }
+    }
 }
 
+/// Gene-level dataset written when probes are collapsed
+pub const COLLAPSED_FILE: &str = "gene_matrix.csv";
+
+/// Markers shown in the correlation heatmap
+const HEATMAP_MARKERS: usize = 20;
+
+/// GO terms shown in the enrichment plot
+const ENRICHMENT_PLOT_TERMS: usize = 10;
+
+/// Rows read between cancellation checks
+const CANCEL_CHECK_ROWS: usize = 1000;
+
+#[derive(Clone)]
 pub struct AnalysisArtifacts {
     pub descriptive_stats: Vec<DescriptiveStat>,
     pub regressions: Vec<RegressionResult>,
     pub novelty_scores: Vec<NoveltyScore>,
     pub biomarker_candidates: Vec<BiomarkerCandidate>,
+    /// Each group against the rest, per marker, when the data is grouped
+    pub group_comparisons: Vec<GroupComparison>,
+    /// Welch, Mann-Whitney and Cohen's d per marker, when the group column
+    /// has two levels
+    pub contrast: Option<GroupContrast>,
+    /// One-way ANOVA per marker, when the group column has more than two
+    /// levels
+    pub anova: Vec<Anova>,
+    /// Coefficient the candidates were ranked by, unless `adjustment`
+    /// replaced it with partial correlations
+    pub correlation: CorrelationMethod,
+    /// Cut-offs the tests were called significant at
+    pub thresholds: Thresholds,
+    /// How tables in drafts and reports write numbers
+    pub numbers: NumberFormat,
     pub summary: String,
     pub heatmap_path: Option<String>,
+    /// Samples × top markers, row-scaled, with a group annotation bar
+    pub expression_heatmap_path: Option<String>,
     pub boxplot_path: Option<String>,
+    /// Difference of means (log2 fold change on log2-scale data) against
+    /// Welch p, when the group column has two levels
+    pub volcano_path: Option<String>,
+    /// AUC of the candidates against the binary or split target
+    pub roc: Option<RocAnalysis>,
+    /// ROC curves of the best-discriminating candidates
+    pub roc_path: Option<String>,
+    /// Kaplan-Meier curves and per-marker Cox models, when time-to-event
+    /// and event columns were given
+    pub survival: Option<Survival>,
+    pub survival_path: Option<String>,
+    /// GO terms over-represented among the significant biomarkers
+    pub enrichment: Vec<EnrichmentResult>,
+    pub enrichment_plot_path: Option<String>,
+    /// Reactome and KEGG pathways over-represented among the significant
+    /// biomarkers
+    pub pathways: Vec<EnrichmentResult>,
+    /// Enrichment of the user's gene sets along the ranking of every tested
+    /// marker
+    pub gsea: Option<Gsea>,
+    /// Principal components of the analysed markers
+    pub pca: Option<Pca>,
+    pub pca_scree_path: Option<String>,
+    /// PC1 against PC2, coloured by group
+    pub pca_scores_path: Option<String>,
+    /// K-means clusters of the samples on the same scaled markers
+    pub kmeans: Option<KMeans>,
+    /// Ridge, lasso or elastic-net model of the target on every analysed
+    /// marker; its non-zero markers are the manuscript's panel
+    pub regularized: Option<RegularizedModel>,
+    /// Legends of the plots that were written, in figure order
+    pub figure_legends: Vec<FigureLegend>,
+    /// Probe-to-gene collapsing applied before the analysis
+    pub probe_collapse: Option<CollapseSummary>,
+    /// Technical replicate collapsing applied before the analysis
+    pub replicates: Option<ReplicateSummary>,
+    /// Missing values imputed before the analysis, per column
+    pub imputation: Option<ImputationSummary>,
+    /// Normalization applied before the analysis
+    pub normalization: Option<NormalizationSummary>,
+    /// Per-sample marker distributions before and after normalization
+    pub normalization_plot_path: Option<String>,
+    /// Batch effects removed before the analysis
+    pub batch: Option<BatchSummary>,
+    /// Per-sample QC metrics of the rows as measured
+    pub sample_qc: Option<SampleQc>,
+    /// Per-sex associations and marker-by-sex interactions, when the
+    /// dataset has a sex column
+    pub sex_stratified: Option<SexStratified>,
+    /// Covariate adjustment of the ranking, when a formula was given
+    pub adjustment: Option<Adjustment>,
+    /// Literature cross-referencing of the top candidates, once run
+    pub cross_reference: Option<CrossReferenceSummary>,
+    /// Result tables written to the output directory
+    pub tables: Option<ResultTables>,
+    /// Provenance of the run, with a stage per step so far
+    pub manifest: Option<Manifest>,
 }
 
+impl AnalysisArtifacts {
+    /// What the candidates were ranked by, for table captions
+    pub fn ranking(&self) -> String {
+        match &self.adjustment {
+            Some(adjustment) if adjustment.repeated.is_some() => "mixed-model partial correlation".to_string(),
+            Some(_) => "covariate-adjusted partial correlation".to_string(),
+            None => format!("{} correlation", self.correlation.name()),
+        }
+    }
+
+    /// Paths of the plots that were written
+    pub fn figure_paths(&self) -> Vec<&str> {
+        [
+            &self.heatmap_path,
+            &self.expression_heatmap_path,
+            &self.boxplot_path,
+            &self.volcano_path,
+            &self.roc_path,
+            &self.survival_path,
+            &self.enrichment_plot_path,
+            &self.pca_scree_path,
+            &self.pca_scores_path,
+            &self.normalization_plot_path,
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect()
+    }
+}
+
+/// Analyze `record` and write the plots to `output_dir`. Checks `cancel`
+/// every [`CANCEL_CHECK_ROWS`] rows and between steps, failing with
+/// [`AppError::Cancelled`] once it fires. Failures are categorized where
+/// they happen: the options naming missing files are configuration errors,
+/// writing the outputs file errors, and the rest the dataset's.
 pub fn run_analysis(
     record: &DatasetRecord,
     config: &AnalysisConfig,
     output_dir: &Path,
-) -> Result<AnalysisArtifacts> {
-    let mut rdr = ReaderBuilder::new()
-        .delimiter(record.delimiter)
-        .has_headers(record.has_headers)
-        .from_path(&record.local_path)
-        .with_context(|| format!("Failed to open dataset {}", record.local_path))?;
+    cancel: &CancellationToken,
+) -> AppResult<AnalysisArtifacts> {
+    let pool = threads::pool(config.threads)?;
+    run_in(&pool, record, config, output_dir, cancel)
+}
 
+/// [`run_analysis`] with the per-column work, independent once the rows
+/// are read, spread over `pool`
+fn run_in(
+    pool: &rayon::ThreadPool,
+    record: &DatasetRecord,
+    config: &AnalysisConfig,
+    output_dir: &Path,
+    cancel: &CancellationToken,
+) -> AppResult<AnalysisArtifacts> {
+    cancel::check(cancel)?;
+    let mut manifest = Manifest::new(record, config);
+    // The formula's covariates are fitted separately with categorical
+    // expansion, so the streaming pass below only sees the target. A
+    // subject column without a formula ranks by `target ~ marker`.
+    let formula = match (&config.formula, &config.subject_column, &config.target_column) {
+        (Some(formula), _, _) => Some(formula.clone()),
+        (None, Some(_), Some(target)) => Some(Formula {
+            response: target.clone(),
+            covariates: Vec::new(),
+            marker: true,
+        }),
+        (None, Some(subject), None) => {
+            return Err(AppError::Data(format!("Subject column '{}' needs a target column", subject)))
+        }
+        (None, None, _) => None,
+    };
+    let formula_config;
+    let config = match formula {
+        Some(formula) => {
+            formula_config = AnalysisConfig {
+                target_column: Some(formula.response.clone()),
+                covariates: Vec::new(),
+                formula: Some(formula),
+                ..config.clone()
+            };
+            &formula_config
+        }
+        None => config,
+    };
+    let (collapsed, probe_collapse) = match &config.platform {
+        Some(platform) => {
+            let probes = ProbeMap::load(platform).map_err(AppError::config)?;
+            let (collapsed, summary) =
+                platform::collapse_dataset(record, &probes, config.collapse, &output_dir.join(COLLAPSED_FILE))
+                    .map_err(AppError::data)?;
+            (Some(collapsed), Some(summary))
+        }
+        None => (None, None),
+    };
+    let record = collapsed.as_ref().unwrap_or(record);
+    let gene_sets = gsea::load(&config.gene_sets).map_err(AppError::config)?;
+
+    // QC describes the samples as measured, before replicates are merged
+    let exclude: Vec<&str> = config
+        .target_column
+        .iter()
+        .chain(&config.group_column)
+        .chain(&config.covariates)
+        .chain(config.formula.iter().flat_map(|f| &f.covariates))
+        .chain(&config.batch_column)
+        .chain(&config.time_column)
+        .chain(&config.event_column)
+        .chain(&config.subject_column)
+        .map(String::as_str)
+        .collect();
+    let sample_qc = qc::sample_metrics(record, &exclude).map_err(AppError::data)?;
+    cancel::check(cancel)?;
+    let (merged, replicates) =
+        match qc::collapse_replicates(record, config.replicates, &output_dir.join(qc::REPLICATES_FILE))
+            .map_err(AppError::data)?
+        {
+            Some((merged, summary)) => (Some(merged), Some(summary)),
+            None => (None, None),
+        };
+    let record = merged.as_ref().unwrap_or(record);
+    // Imputed per sample, once replicates are merged
+    let (imputed, imputation) =
+        match impute::impute(record, config.imputation, &exclude, &output_dir.join(impute::IMPUTED_FILE), cancel)
+            .map_err(AppError::data)?
+        {
+            Some((imputed, summary)) => (Some(imputed), Some(summary)),
+            None => (None, None),
+        };
+    let record = imputed.as_ref().unwrap_or(record);
+    // Normalized before batch correction, which assumes the final scale
+    let (normalized, normalization) = match normalize::normalize(
+        record,
+        &config.normalization,
+        &exclude,
+        &output_dir.join(normalize::NORMALIZED_FILE),
+        cancel,
+    )
+    .map_err(AppError::data)?
+    {
+        Some((normalized, summary)) => (Some(normalized), Some(summary)),
+        None => (None, None),
+    };
+    let record = normalized.as_ref().unwrap_or(record);
+    // Corrected once gaps are filled, so every batch has its markers' values
+    let (corrected, batch) = match &config.batch_column {
+        Some(column) => {
+            let (corrected, summary) = batch::correct(
+                record,
+                column,
+                config.group_column.as_deref(),
+                config.batch_correction,
+                &exclude,
+                &output_dir.join(batch::CORRECTED_FILE),
+                cancel,
+            )
+            .map_err(AppError::data)?;
+            (Some(corrected), Some(summary))
+        }
+        None => (None, None),
+    };
+    let record = corrected.as_ref().unwrap_or(record);
+
+    let mut rdr = record.reader().map_err(AppError::io)?;
+    let parsing = &record.parsing;
+
+    // Without a header row, `byte_headers` peeks at the first row without
+    // consuming it
     let headers: Vec<String> = if record.has_headers {
-        rdr.headers()?
+        rdr.headers()
+            .map_err(AppError::data)?
             .iter()
             .map(|h| h.to_string())
             .collect()
     } else {
-        let mut cols = Vec::new();
-        if let Some(first) = rdr.records().next() {
-            let first = first?;
-            for idx in 0..first.len() {
-                cols.push(format!("column_{}", idx + 1));
-            }
-        }
-        cols
+        (0..rdr.byte_headers().map_err(AppError::data)?.len()).map(|idx| format!("column_{}", idx + 1)).collect()
     };
+    record.species.check_ids(headers.iter().map(String::as_str)).map_err(AppError::data)?;
 
     let group_index = config
         .group_column
@@ -75,10 +471,17 @@ pub fn run_analysis(
         .as_ref()
         .and_then(|c| headers.iter().position(|h| h == c));
 
+    // Identifier, date and categorical columns are never analysed as values
     let mut selected_indices: Vec<usize> = headers
         .iter()
         .enumerate()
         .filter(|(idx, _)| Some(*idx) != group_index)
+        .filter(|(_, name)| config.batch_column.as_ref() != Some(*name) && config.subject_column.as_ref() != Some(*name))
+        .filter(|(_, name)| config.time_column.as_ref() != Some(*name) && config.event_column.as_ref() != Some(*name))
+        .filter(|(idx, _)| record.column_kind(*idx).is_none_or(|kind| kind == ColumnKind::Numeric))
+        .filter(|(idx, name)| {
+            config.markers.is_empty() || config.markers.contains(name) || Some(*idx) == target_index
+        })
         .map(|(idx, _)| idx)
         .take(config.max_columns)
         .collect();
@@ -86,82 +489,77 @@ pub fn run_analysis(
     if selected_indices.is_empty() {
         selected_indices = headers.iter().enumerate().map(|(idx, _)| idx).collect();
     }
+    memory::check(record.row_count, selected_indices.len(), config.memory_limit).map_err(AppError::data)?;
 
-    let mut stats_values: Vec<Vec<f64>> = vec![Vec::new(); selected_indices.len()];
-    let mut stats_min: Vec<f64> = vec![f64::INFINITY; selected_indices.len()];
-    let mut stats_max: Vec<f64> = vec![f64::NEG_INFINITY; selected_indices.len()];
+    // Columns and marker-target pairs are summarised as the rows stream
+    // past, in a fixed amount of memory per column. Medians, rank
+    // coefficients, AUCs and the heatmap come from a sample of the rows
+    // once a column has more values than the sample holds.
+    let sample_size = memory::sample_size(selected_indices.len());
+    let mut columns = vec![ColumnStats::new(sample_size); selected_indices.len()];
+    // Pearson's r needs only the running co-moments
+    let sample_pairs = config.correlation != CorrelationMethod::Pearson || config.roc != RocSplit::Off;
+    let mut pairs = vec![PairStats::new(sample_pairs.then_some(sample_size)); selected_indices.len()];
+    let heatmap_columns = selected_indices.len().min(HEATMAP_MARKERS);
+    let mut heatmap_rows: Reservoir<Vec<Option<f64>>> = Reservoir::new(sample_size);
 
-    let mut overall_sum: Vec<f64> = vec![0.0; selected_indices.len()];
-    let mut overall_sum_sq: Vec<f64> = vec![0.0; selected_indices.len()];
-    let mut overall_count: Vec<usize> = vec![0; selected_indices.len()];
-
-    let mut group_sums: HashMap<String, Vec<(f64, usize)>> = HashMap::new();
+    // Group statistics of the selected columns, plus the box plot column
+    // when it is not one of them
+    let boxplot_pos = boxplot_index.map(|idx| {
+        selected_indices
+            .iter()
+            .position(|col| *col == idx)
+            .unwrap_or(selected_indices.len())
+    });
+    let extra_boxplot = boxplot_pos == Some(selected_indices.len());
+    let mut groups = GroupAggregator::new(selected_indices.len() + usize::from(extra_boxplot));
 
     let mut regression_rows: Vec<Vec<f64>> = Vec::new();
     let mut regression_targets: Vec<f64> = Vec::new();
-    let mut univariate_x: Vec<Vec<f64>> = vec![Vec::new(); selected_indices.len()];
-    let mut univariate_y: Vec<Vec<f64>> = vec![Vec::new(); selected_indices.len()];
-    let mut biomarker_x: Vec<Vec<f64>> = vec![Vec::new(); selected_indices.len()];
-    let mut biomarker_y: Vec<Vec<f64>> = vec![Vec::new(); selected_indices.len()];
-
-    let mut boxplot_values: HashMap<String, Vec<f64>> = HashMap::new();
-
-    for record in rdr.records() {
-        let record = record?;
-
-        let group_value = group_index.and_then(|idx| record.get(idx).map(|v| v.to_string()));
-
-        for (pos, col_idx) in selected_indices.iter().enumerate() {
-            if let Some(val) = record.get(*col_idx) {
-                if let Ok(parsed) = val.parse::<f64>() {
-                    stats_values[pos].push(parsed);
-                    stats_min[pos] = stats_min[pos].min(parsed);
-                    stats_max[pos] = stats_max[pos].max(parsed);
-                    overall_sum[pos] += parsed;
-                    overall_sum_sq[pos] += parsed * parsed;
-                    overall_count[pos] += 1;
-
-                    if let Some(group_label) = &group_value {
-                        let entry = group_sums
-                            .entry(group_label.clone())
-                            .or_insert_with(|| vec![(0.0, 0); selected_indices.len()]);
-                        entry[pos].0 += parsed;
-                        entry[pos].1 += 1;
-                    }
+
+    // Rows are read as raw bytes and only the cells of the columns used
+    // below are decoded, each once per row; on wide matrices most columns
+    // are never selected. Cells that are not valid UTF-8 count as missing.
+    let mut raw = csv::ByteRecord::new();
+    let mut values: Vec<Option<f64>> = vec![None; selected_indices.len()];
+    let mut row = 0;
+    while rdr.read_byte_record(&mut raw).map_err(AppError::data)? {
+        if row % CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        row += 1;
+        let cell = |idx: usize| raw.get(idx).and_then(|v| std::str::from_utf8(v).ok());
+        let number = |idx: usize| cell(idx).and_then(|v| parsing.number(v));
+        for (value, col_idx) in values.iter_mut().zip(&selected_indices) {
+            *value = number(*col_idx);
+        }
+
+        let group_value = group_index.and_then(cell);
+
+        for (pos, parsed) in values.iter().enumerate() {
+            if let Some(parsed) = *parsed {
+                columns[pos].push(parsed);
+
+                if let Some(group_label) = group_value {
+                    groups.push(group_label, pos, parsed);
                 }
             }
         }
+        let shown = &values[..heatmap_columns];
+        if shown.iter().any(Option::is_some) {
+            heatmap_rows.push(shown.to_vec());
+        }
 
         if let Some(target_idx) = target_index {
-            if let Some(target_val) = record.get(target_idx).and_then(|v| v.parse::<f64>().ok()) {
-                for (pos, col_idx) in selected_indices.iter().enumerate() {
-                    if *col_idx == target_idx {
-                        continue;
-                    }
-                    if let Some(val) = record.get(*col_idx).and_then(|v| v.parse::<f64>().ok()) {
-                        biomarker_x[pos].push(val);
-                        biomarker_y[pos].push(target_val);
+            if let Some(target_val) = number(target_idx) {
+                for (pair, val) in pairs.iter_mut().zip(&values) {
+                    if let Some(val) = *val {
+                        pair.push(val, target_val);
                     }
                 }
-                if covariate_indices.is_empty() {
-                    for (pos, col_idx) in selected_indices.iter().enumerate() {
-                        if let Some(val) = record.get(*col_idx).and_then(|v| v.parse::<f64>().ok()) {
-                            univariate_x[pos].push(val);
-                            univariate_y[pos].push(target_val);
-                        }
-                    }
-                } else {
-                    let mut row: Vec<f64> = Vec::with_capacity(covariate_indices.len());
-                    let mut has_all = true;
-                    for (idx, _) in &covariate_indices {
-                        if let Some(val) = record.get(*idx).and_then(|v| v.parse::<f64>().ok()) {
-                            row.push(val);
-                        } else {
-                            has_all = false;
-                            break;
-                        }
-                    }
-                    if has_all {
+                if !covariate_indices.is_empty() {
+                    let row: Option<Vec<f64>> = covariate_indices.iter().map(|(idx, _)| number(*idx)).collect();
+                    if let Some(row) = row {
                         regression_rows.push(row);
                         regression_targets.push(target_val);
                     }
@@ -169,47 +567,174 @@ pub fn run_analysis(
             }
         }
 
-        if let (Some(group_label), Some(box_idx)) = (&group_value, boxplot_index) {
-            if let Some(val) = record.get(box_idx).and_then(|v| v.parse::<f64>().ok()) {
-                boxplot_values.entry(group_label.clone()).or_default().push(val);
+        if let (true, Some(group_label), Some(box_idx)) = (extra_boxplot, group_value, boxplot_index) {
+            if let Some(val) = number(box_idx) {
+                groups.push(group_label, selected_indices.len(), val);
             }
         }
     }
 
-    let descriptive_stats = build_descriptive_stats(&headers, &selected_indices, &stats_values, &stats_min, &stats_max)?;
-    let regressions = if covariate_indices.is_empty() {
-        build_univariate_regressions(
-            config.target_column.as_ref(),
-            &headers,
-            &selected_indices,
-            &univariate_x,
-            &univariate_y,
-        )?
+    cancel::check(cancel)?;
+    let sampled = columns.iter().filter(|c| c.is_sampled()).count();
+    if sampled > 0 {
+        warn!(
+            columns = sampled,
+            sample_size,
+            "Medians, rank correlations, AUCs and the heatmap are estimated from a sample of the rows"
+        );
+    }
+    let target_pos = target_index.and_then(|idx| selected_indices.iter().position(|col| *col == idx));
+    let descriptive_stats = pool.install(|| build_descriptive_stats(&headers, &selected_indices, &columns))
+        .map_err(AppError::data)?;
+    let mut regressions = if covariate_indices.is_empty() {
+        pool.install(|| build_univariate_regressions(config.target_column.as_ref(), &headers, &selected_indices, &pairs))
+            .map_err(AppError::data)?
     } else {
         build_regressions(
             config.target_column.as_ref(),
             &covariate_indices,
             &regression_rows,
             &regression_targets,
-        )?
+        )
+        .map_err(AppError::data)?
     };
-    let novelty_scores = build_novelty_scores(
-        &headers,
-        &selected_indices,
-        &overall_sum,
-        &overall_sum_sq,
-        &overall_count,
-        &group_sums,
-    );
-    let biomarker_candidates = build_biomarker_candidates(
-        config.target_column.as_ref(),
-        &headers,
-        &selected_indices,
-        &biomarker_x,
-        &biomarker_y,
+    let mut novelty_scores = build_novelty_scores(&headers, &selected_indices, &columns, &groups);
+    let group_comparisons = build_group_comparisons(&headers, &selected_indices, target_index, &groups, config.max_groups);
+    let mut anova = anova::build(&headers, &selected_indices, target_index, &groups);
+    if let Some(group_index) = group_index.filter(|_| config.kruskal_wallis && !anova.is_empty()) {
+        anova::kruskal_wallis(record, &headers, group_index, &mut anova, cancel).map_err(AppError::data)?;
+    }
+    anova::back_novelty(&mut novelty_scores, &anova);
+    cancel::check(cancel)?;
+    let pair_samples: Vec<Option<(Vec<f64>, Vec<f64>)>> = pairs.iter().map(PairStats::sample).collect();
+    // The target's own values, or those paired with the most markers when
+    // `max_columns` cut the target from the selection
+    let target_values = target_pos.map(|pos| columns[pos].sample()).or_else(|| {
+        pair_samples
+            .iter()
+            .flatten()
+            .max_by_key(|(x, _)| x.len())
+            .map(|(_, y)| y.as_slice())
+    });
+    let split = target_values.and_then(|values| config.roc.split(values));
+    let genes = crate::annotation::ensembl::shared();
+    let mut biomarker_candidates = pool.install(|| {
+        build_biomarker_candidates(
+            config.target_column.as_ref(),
+            config.correlation,
+            split.as_ref(),
+            &genes,
+            record.species,
+            &headers,
+            &selected_indices,
+            target_pos,
+            &pairs,
+            &pair_samples,
+        )
+    });
+    let adjustment = match &config.formula {
+        Some(formula) => {
+            let subject = config.subject_column.as_deref();
+            let (adjustment, model) =
+                formula::adjust(record, &headers, formula, subject, &mut biomarker_candidates, cancel)
+                    .map_err(AppError::data)?;
+            regressions = model.into_iter().collect();
+            Some(adjustment)
+        }
+        None => None,
+    };
+    // Enrichment uses every tested marker as its universe, and GSEA ranks
+    // them all, so both run before the list is cut to the top hits
+    let thresholds = config.thresholds;
+    let gene_annotation = AnnotationConfig::from_env();
+    let enrichment = build_enrichment(&biomarker_candidates, &thresholds, &GeneSets::load(&gene_annotation));
+    let pathways =
+        build_enrichment(&biomarker_candidates, &thresholds, &annotation::pathways::load(&gene_annotation));
+    let gsea = match &config.target_column {
+        Some(target) if !gene_sets.is_empty() => {
+            pool.install(|| gsea::analyze(target, &gsea::rank(&biomarker_candidates), &gene_sets))
+        }
+        _ => None,
+    };
+    let expression_markers = expression::top_markers(
+        &novelty_scores,
+        &biomarker_candidates,
+        group_index.is_some(),
+        config.target_column.as_deref(),
     );
+    let contrast = match group_index {
+        Some(group_index) => {
+            let markers: Vec<(usize, String)> = selected_indices
+                .iter()
+                .filter(|idx| Some(**idx) != target_index)
+                .map(|idx| (*idx, genes.label(&headers[*idx]).to_string()))
+                .collect();
+            let log2_scale = normalization.as_ref().is_some_and(NormalizationSummary::log2_scale);
+            contrast::analyze(record, &headers, group_index, &markers, thresholds, cancel)
+                .map_err(AppError::data)?
+                .map(|contrast| GroupContrast { log2_scale, ..contrast })
+        }
+        None => None,
+    };
+    let pca_markers: Vec<usize> = selected_indices.iter().copied().filter(|idx| Some(*idx) != target_index).collect();
+    let group = group_index.map(|idx| (idx, headers[idx].as_str()));
+    let scaled = pca::scaled(record, &headers, &pca_markers, group, cancel).map_err(AppError::data)?;
+    let pca = scaled.as_ref().and_then(pca::analyze);
+    cancel::check(cancel)?;
+    let kmeans = scaled.as_ref().and_then(kmeans::analyze);
+    let regularized = match target_index {
+        Some(target_index) => {
+            let markers: Vec<(usize, String)> =
+                pca_markers.iter().map(|idx| (*idx, genes.label(&headers[*idx]).to_string())).collect();
+            regularized::analyze(record, &headers, target_index, &markers, &config.regularization, cancel)
+                .map_err(AppError::data)?
+        }
+        None => None,
+    };
+    let survival = match (&config.time_column, &config.event_column) {
+        (Some(time), Some(event)) => {
+            let markers: Vec<(usize, String)> =
+                pca_markers.iter().map(|idx| (*idx, genes.label(&headers[*idx]).to_string())).collect();
+            let columns = (time.as_str(), event.as_str());
+            pool.install(|| survival::analyze(record, &headers, columns, group_index, &markers, cancel))
+                .map_err(AppError::data)?
+        }
+        _ => None,
+    };
+    // Likewise for the interaction tests' multiple-testing correction
+    let sex_stratified = match target_index {
+        Some(target_index) => {
+            sex::analyze(record, &headers, target_index, &biomarker_candidates, thresholds, cancel)
+                .map_err(AppError::data)?
+        }
+        None => None,
+    };
+    // Markers highlighted in the plots, over every tested marker
+    let passing: HashSet<&str> = biomarker_candidates
+        .iter()
+        .filter(|c| thresholds.passes(c.p_value, c.q_value, c.correlation))
+        .map(|c| c.column.as_str())
+        .collect();
+    let passing_markers = passing.len();
+    let passing: HashSet<String> = passing.into_iter().map(str::to_string).collect();
+    let passing_groups: HashSet<&str> = group_comparisons
+        .iter()
+        .filter(|c| thresholds.significant(c.p_value, c.q_value))
+        .map(|c| c.column.as_str())
+        .collect();
+    let roc = match (split, &config.target_column) {
+        (Some(split), Some(target)) => {
+            let series = |column: &str| {
+                let pos = selected_indices.iter().position(|idx| headers[*idx] == column)?;
+                pair_samples[pos].as_ref().map(|(x, y)| (x.as_slice(), y.as_slice()))
+            };
+            roc::analyze(target, split, &biomarker_candidates, series, |c| c.display_label())
+        }
+        _ => None,
+    };
+    biomarker_candidates.truncate(50);
 
-    let summary = format!(
+    let mut summary = format!(
         "Computed descriptive statistics for {} columns. Generated {} regression model(s). \
          Novelty scores computed for {} columns. Biomarker candidates ranked for {} columns.",
         descriptive_stats.len(),
@@ -217,80 +742,335 @@ pub fn run_analysis(
         novelty_scores.len(),
         biomarker_candidates.len()
     );
+    if config.target_column.is_some() {
+        summary.push_str(&format!(" {} marker(s) at {}.", passing_markers, thresholds.marker_criterion()));
+    }
+    if !group_comparisons.is_empty() {
+        summary.push_str(&format!(
+            " Group comparisons: {} of {} marker-group pair(s) at {}.",
+            group_comparisons.iter().filter(|c| thresholds.significant(c.p_value, c.q_value)).count(),
+            group_comparisons.len(),
+            thresholds.criterion()
+        ));
+    }
+    if let Some(collapse) = &probe_collapse {
+        summary.insert_str(
+            0,
+            &format!(
+                "Collapsed {} probes to {} genes ({}, {} dropped). ",
+                collapse.probes, collapse.genes, collapse.strategy, collapse.dropped
+            ),
+        );
+    }
+    if let Some(replicates) = &replicates {
+        summary.push_str(&format!(
+            " Collapsed technical replicates: {} rows to {} samples ({}).",
+            replicates.rows, replicates.samples, replicates.strategy
+        ));
+    }
+    if let Some(imputation) = &imputation {
+        summary.push_str(&format!(
+            " Imputed {} missing value(s) in {} column(s) ({}).",
+            imputation.imputed(),
+            imputation.columns.len(),
+            imputation.method
+        ));
+    }
+    if let Some(normalization) = &normalization {
+        summary.push_str(&format!(
+            " Normalized {} marker(s) ({}).",
+            normalization.markers,
+            normalization.steps.iter().map(|s| s.id()).collect::<Vec<_>>().join(", ")
+        ));
+    }
+    if let Some(batch) = &batch {
+        summary.push_str(&format!(
+            " Corrected {} marker(s) for {} batches of {} ({}).",
+            batch.markers,
+            batch.batches.len(),
+            batch.column,
+            batch.method
+        ));
+    }
+    if let Some(qc) = &sample_qc {
+        summary.push_str(&format!(" Sample QC: {} of {} sample(s) flagged.", qc.flagged().count(), qc.samples.len()));
+    }
+    if let Some(adjustment) = &adjustment {
+        summary.push_str(&format!(" Adjusted with {}", adjustment.formula));
+        if let Some(repeated) = &adjustment.repeated {
+            summary.push_str(&format!(" over {} subjects", repeated.subjects));
+        }
+        if adjustment.dropped > 0 {
+            summary.push_str(&format!(" ({} marker(s) could not be fitted)", adjustment.dropped));
+        }
+        summary.push('.');
+    }
+    if !enrichment.is_empty() {
+        summary.push_str(&format!(
+            " GO enrichment: {} term(s) at {}.",
+            enrichment.iter().filter(|e| thresholds.significant(e.p_value, e.q_value)).count(),
+            thresholds.criterion()
+        ));
+    }
+    if !pathways.is_empty() {
+        summary.push_str(&format!(
+            " Pathway enrichment: {} pathway(s) at {}.",
+            pathways.iter().filter(|e| thresholds.significant(e.p_value, e.q_value)).count(),
+            thresholds.criterion()
+        ));
+    }
+    if let Some(gsea) = &gsea {
+        summary.push_str(&format!(" {}", gsea.summary(&thresholds)));
+    }
+    if !anova.is_empty() {
+        summary.push_str(&format!(
+            " One-way ANOVA: {} of {} marker(s) at {}.",
+            anova.iter().filter(|a| thresholds.significant(a.p_value, a.q_value)).count(),
+            anova.len(),
+            thresholds.criterion()
+        ));
+    }
+    if let Some(contrast) = &contrast {
+        summary.push_str(&format!(
+            " {} vs {}: {} marker(s) differ by Welch's t test and {} by the Mann-Whitney U test at {}.",
+            contrast.groups[0],
+            contrast.groups[1],
+            contrast.differing().count(),
+            contrast.shifted().count(),
+            thresholds.criterion()
+        ));
+    }
+    if let Some(pca) = &pca {
+        let share = |i: usize| pca.components.get(i).map_or(0.0, |c| c.ratio * 100.0);
+        summary.push_str(&format!(
+            " PCA: PC1 and PC2 explain {:.1}% and {:.1}% of the variance of {} marker(s).",
+            share(0),
+            share(1),
+            pca.markers.len()
+        ));
+    }
+    if let Some(kmeans) = &kmeans {
+        summary.push_str(&format!(" {}", kmeans.summary()));
+    }
+    if let Some(model) = &regularized {
+        summary.push_str(&format!(" {}", model.summary()));
+    }
+    if let Some(roc) = &roc {
+        summary.push_str(&format!(" {}", roc.summary()));
+    }
+    if let Some(survival) = &survival {
+        summary.push_str(&format!(" {}", survival.summary(&thresholds)));
+    }
+    if let Some(stratified) = &sex_stratified {
+        summary.push_str(&format!(
+            " Sex-stratified: {} marker(s) with a {}-dependent association (interaction {}).",
+            stratified.differing().count(),
+            stratified.column,
+            thresholds.criterion()
+        ));
+    }
 
-    let heatmap_path = if !stats_values.is_empty() {
+    cancel::check(cancel)?;
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create {}", output_dir.display()))
+        .map_err(AppError::io)?;
+    let mut figure_legends = Vec::new();
+    let heatmap_path = if heatmap_columns > 0 {
         let path = output_dir.join("heatmap.png");
         let labels: Vec<String> = selected_indices
             .iter()
-            .map(|idx| headers.get(*idx).cloned().unwrap_or_default())
+            .map(|idx| headers.get(*idx).map_or_else(String::new, |h| marked(genes.label(h), passing.contains(h))))
             .collect();
-        write_heatmap(&path, &stats_values, &labels)?;
-        Some(path.to_string_lossy().to_string())
+        pool.install(|| write_heatmap(&path, heatmap_rows.items(), &labels, config.correlation)).map_err(AppError::io)?;
+        let path = path.to_string_lossy().to_string();
+        let shown = &columns[..heatmap_columns];
+        let samples = (
+            shown.iter().map(|c| c.count).min().unwrap_or(0),
+            shown.iter().map(|c| c.count).max().unwrap_or(0),
+        );
+        let starred = selected_indices
+            .iter()
+            .take(shown.len())
+            .filter(|idx| headers.get(**idx).is_some_and(|h| passing.contains(h)))
+            .count();
+        figure_legends.push(FigureLegend::heatmap(&path, shown.len(), samples, config.correlation, starred, &thresholds));
+        Some(path)
     } else {
         None
     };
-    let boxplot_path = if !boxplot_values.is_empty() {
+    let expression_matrix = expression::build(record, &headers, &expression_markers, group_index, cancel)
+        .map_err(AppError::data)?;
+    let expression_heatmap_path = match &expression_matrix {
+        Some(matrix) => {
+            let path = output_dir.join(expression::EXPRESSION_FILE);
+            let highlighted = |m: &String| passing.contains(m) || passing_groups.contains(m.as_str());
+            let labels: Vec<String> = matrix.markers.iter().map(|m| marked(genes.label(m), highlighted(m))).collect();
+            let starred = matrix.markers.iter().filter(|m| highlighted(m)).count();
+            expression::write_expression_heatmap(&path, matrix, &labels).map_err(AppError::io)?;
+            let path = path.to_string_lossy().to_string();
+            let group = config.group_column.as_deref().filter(|_| group_index.is_some());
+            let basis = match (group, &adjustment) {
+                (Some(group), _) => format!("whose group means differ most by {}", group),
+                (None, Some(_)) => "ranked highest by covariate-adjusted partial correlation".to_string(),
+                (None, None) => format!("ranked highest by {} correlation", config.correlation.name()),
+            };
+            figure_legends.push(FigureLegend::expression(&path, matrix, &basis, group, starred, &thresholds));
+            Some(path)
+        }
+        None => None,
+    };
+    let boxes = boxplot_pos
+        .map(|pos| groups.boxplot(pos, config.max_groups))
+        .unwrap_or_default();
+    let boxplot_path = if !boxes.is_empty() {
         let path = output_dir.join("boxplot.png");
-        write_boxplot(&path, &boxplot_values, config.max_groups)?;
-        Some(path.to_string_lossy().to_string())
+        write_boxplot(&path, &boxes).map_err(AppError::io)?;
+        let path = path.to_string_lossy().to_string();
+        let marker = config.boxplot_column.as_deref().map(|c| genes.label(c).to_string()).unwrap_or_default();
+        let group = config.group_column.as_deref().unwrap_or("group");
+        let total_groups = boxplot_pos.map_or(0, |pos| groups.column(pos).count());
+        figure_legends.push(FigureLegend::boxplot(&path, &marker, group, &boxes, total_groups));
+        Some(path)
     } else {
         None
     };
+    let volcano_path = match &contrast {
+        Some(contrast) if !contrast.markers.is_empty() => {
+            let path = output_dir.join(contrast::VOLCANO_FILE);
+            contrast::write_volcano_plot(&path, contrast).map_err(AppError::io)?;
+            let path = path.to_string_lossy().to_string();
+            figure_legends.push(FigureLegend::volcano(&path, contrast));
+            Some(path)
+        }
+        _ => None,
+    };
+    let roc_path = match &roc {
+        Some(roc) if !roc.curves.is_empty() => {
+            let path = output_dir.join(roc::ROC_FILE);
+            roc::write_roc_plot(&path, roc).map_err(AppError::io)?;
+            let path = path.to_string_lossy().to_string();
+            figure_legends.push(FigureLegend::roc(&path, roc));
+            Some(path)
+        }
+        _ => None,
+    };
+    let survival_path = match &survival {
+        Some(survival) => {
+            let path = output_dir.join(survival::KM_FILE);
+            survival::write_km_plot(&path, survival).map_err(AppError::io)?;
+            let path = path.to_string_lossy().to_string();
+            figure_legends.push(FigureLegend::survival(&path, survival));
+            Some(path)
+        }
+        None => None,
+    };
+    let enrichment_plot_path = if !enrichment.is_empty() {
+        let path = output_dir.join("go_enrichment.png");
+        write_enrichment_plot(&path, &enrichment, &thresholds).map_err(AppError::io)?;
+        let path = path.to_string_lossy().to_string();
+        let target = config.target_column.as_deref().unwrap_or("the target");
+        figure_legends.push(FigureLegend::enrichment(&path, &enrichment, ENRICHMENT_PLOT_TERMS, target, &thresholds));
+        Some(path)
+    } else {
+        None
+    };
+    let (pca_scree_path, pca_scores_path) = match &pca {
+        Some(pca) => {
+            let scree = output_dir.join(pca::SCREE_FILE);
+            pca::write_scree_plot(&scree, pca).map_err(AppError::io)?;
+            let scree = scree.to_string_lossy().to_string();
+            figure_legends.push(FigureLegend::scree(&scree, pca));
+            let scores = output_dir.join(pca::SCORES_FILE);
+            pca::write_score_plot(&scores, pca).map_err(AppError::io)?;
+            let scores = scores.to_string_lossy().to_string();
+            let group = pca.group_eta_squared.as_ref().map(|(group, _)| group.as_str());
+            figure_legends.push(FigureLegend::scores(&scores, pca, group));
+            (Some(scree), Some(scores))
+        }
+        None => (None, None),
+    };
+    let normalization_plot_path = match &normalization {
+        Some(normalization) => {
+            let path = output_dir.join(normalize::DISTRIBUTIONS_FILE);
+            normalize::write_distribution_plot(&path, normalization).map_err(AppError::io)?;
+            Some(path.to_string_lossy().to_string())
+        }
+        None => None,
+    };
 
-    Ok(AnalysisArtifacts {
+    let mut artifacts = AnalysisArtifacts {
         descriptive_stats,
         regressions,
         novelty_scores,
         biomarker_candidates,
+        group_comparisons,
+        contrast,
+        anova,
+        correlation: config.correlation,
+        thresholds,
+        numbers: config.numbers,
         summary,
         heatmap_path,
+        expression_heatmap_path,
         boxplot_path,
-    })
+        volcano_path,
+        roc,
+        roc_path,
+        survival,
+        survival_path,
+        enrichment,
+        enrichment_plot_path,
+        pathways,
+        gsea,
+        pca,
+        pca_scree_path,
+        pca_scores_path,
+        kmeans,
+        regularized,
+        figure_legends,
+        probe_collapse,
+        replicates,
+        imputation,
+        normalization,
+        normalization_plot_path,
+        batch,
+        sample_qc,
+        sex_stratified,
+        adjustment,
+        cross_reference: None,
+        tables: None,
+        manifest: None,
+    };
+    artifacts.tables = Some(report::write_tables(output_dir, &artifacts, config.tables).map_err(AppError::io)?);
+    manifest.stage(Stage::finished("analysis", None, manifest.started));
+    manifest.write(output_dir).map_err(AppError::io)?;
+    artifacts.manifest = Some(manifest);
+    Ok(artifacts)
 }
 
 fn build_descriptive_stats(
     headers: &[String],
     selected_indices: &[usize],
-    values: &[Vec<f64>],
-    mins: &[f64],
-    maxes: &[f64],
+    columns: &[ColumnStats],
 ) -> Result<Vec<DescriptiveStat>> {
-    let mut stats = Vec::new();
-    for (pos, col_idx) in selected_indices.iter().enumerate() {
-        let mut col = values[pos].clone();
-        if col.is_empty() {
-            continue;
-        }
-        col.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        let count = col.len();
-        let mean = col.iter().sum::<f64>() / count as f64;
-        let std_dev = std_dev(&col, mean);
-        let median = if count % 2 == 0 {
-            (col[count / 2 - 1] + col[count / 2]) / 2.0
-        } else {
-            col[count / 2]
-        };
-        stats.push(DescriptiveStat {
+    // Medians sort each column's sample
+    let stats = columns
+        .par_iter()
+        .zip(selected_indices)
+        .filter(|(column, _)| column.count > 0)
+        .map(|(column, col_idx)| DescriptiveStat {
             column: headers.get(*col_idx).cloned().unwrap_or_else(|| format!("column_{}", col_idx + 1)),
-            count,
-            mean,
-            std_dev,
-            min: mins[pos],
-            median,
-            max: maxes[pos],
-        });
-    }
+            count: column.count,
+            mean: column.mean,
+            std_dev: column.variance().sqrt(),
+            min: column.min,
+            median: column.median(),
+            max: column.max,
+        })
+        .collect();
     Ok(stats)
 }
 
-fn std_dev(values: &[f64], mean: f64) -> f64 {
-    if values.len() < 2 {
-        return 0.0;
-    }
-    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() as f64 - 1.0);
-    variance.sqrt()
-}
-
 fn build_regressions(
     target: Option<&String>,
     covariates: &[(usize, String)],
@@ -326,43 +1106,36 @@ fn build_regressions(
     Ok(results)
 }
 
+/// Least-squares line of the target on each selected column, from the
+/// pairs' running co-moments
 fn build_univariate_regressions(
     target: Option<&String>,
     headers: &[String],
     selected_indices: &[usize],
-    x_values: &[Vec<f64>],
-    y_values: &[Vec<f64>],
+    pairs: &[PairStats],
 ) -> Result<Vec<RegressionResult>> {
-    let mut results = Vec::new();
-    if let Some(target_name) = target {
-        for (pos, col_idx) in selected_indices.iter().enumerate() {
-            if x_values[pos].len() < 2 || x_values[pos].len() != y_values[pos].len() {
-                continue;
-            }
-            let n = x_values[pos].len();
-            let mut data = Vec::with_capacity(n * 2);
-            for val in &x_values[pos] {
-                data.push(1.0);
-                data.push(*val);
-            }
-            let x = DMatrix::from_row_slice(n, 2, &data);
-            let y = DVector::from_row_slice(&y_values[pos]);
-            if let Some((intercept, coefficients, r2)) = ols_fit(&x, &y) {
-                results.push(RegressionResult {
-                    target: target_name.clone(),
-                    predictors: vec![headers.get(*col_idx).cloned().unwrap_or_else(|| format!("column_{}", col_idx + 1))],
-                    intercept,
-                    coefficients,
-                    r2,
-                    n,
-                });
-            }
-        }
-    }
+    let Some(target_name) = target else {
+        return Ok(Vec::new());
+    };
+    let results = pairs
+        .par_iter()
+        .zip(selected_indices)
+        .filter_map(|(pair, col_idx)| {
+            let (intercept, slope, r2) = pair.line()?;
+            Some(RegressionResult {
+                target: target_name.clone(),
+                predictors: vec![headers.get(*col_idx).cloned().unwrap_or_else(|| format!("column_{}", col_idx + 1))],
+                intercept,
+                coefficients: vec![slope],
+                r2,
+                n: pair.count,
+            })
+        })
+        .collect();
     Ok(results)
 }
 
-fn ols_fit(x: &DMatrix<f64>, y: &DVector<f64>) -> Option<(f64, Vec<f64>, f64)> {
+pub(crate) fn ols_fit(x: &DMatrix<f64>, y: &DVector<f64>) -> Option<(f64, Vec<f64>, f64)> {
     let xtx = x.transpose() * x;
     let xtx_inv = xtx.try_inverse()?;
     let beta = xtx_inv * x.transpose() * y;
@@ -380,35 +1153,97 @@ fn ols_fit(x: &DMatrix<f64>, y: &DVector<f64>) -> Option<(f64, Vec<f64>, f64)> {
     Some((intercept, coefficients, r2))
 }
 
+/// Each of the first `max_groups` groups (by label) against the other
+/// groups, per marker: Welch's t test with Benjamini–Hochberg q-values
+/// across all marker-group pairs
+fn build_group_comparisons(
+    headers: &[String],
+    selected_indices: &[usize],
+    target_index: Option<usize>,
+    groups: &GroupAggregator,
+    max_groups: usize,
+) -> Vec<GroupComparison> {
+    let mut comparisons = Vec::new();
+    for (pos, col_idx) in selected_indices.iter().enumerate() {
+        if Some(*col_idx) == target_index {
+            continue;
+        }
+        let mut summaries: Vec<(&str, &aggregate::Summary)> = groups.column(pos).collect();
+        if summaries.len() < 2 {
+            continue;
+        }
+        summaries.sort_by(|a, b| a.0.cmp(b.0));
+        // Pooled count, sum and sum of squared deviations
+        let total_n: usize = summaries.iter().map(|(_, s)| s.count).sum();
+        let total_sum: f64 = summaries.iter().map(|(_, s)| s.mean * s.count as f64).sum();
+        let total_mean = total_sum / total_n as f64;
+        let total_ss: f64 = summaries
+            .iter()
+            .map(|(_, s)| s.variance() * (s.count as f64 - 1.0).max(0.0) + s.count as f64 * (s.mean - total_mean).powi(2))
+            .sum();
+        for (group, summary) in summaries.into_iter().take(max_groups) {
+            let n = summary.count;
+            let rest_n = total_n - n;
+            if n < 2 || rest_n < 2 {
+                continue;
+            }
+            let rest_mean = (total_sum - summary.mean * n as f64) / rest_n as f64;
+            let group_ss = summary.variance() * (n - 1) as f64;
+            // Removing the group's squared deviations from the pooled mean
+            let rest_ss = (total_ss
+                - group_ss
+                - n as f64 * (summary.mean - total_mean).powi(2)
+                - rest_n as f64 * (rest_mean - total_mean).powi(2))
+            .max(0.0);
+            let rest_var = rest_ss / (rest_n - 1) as f64;
+            let difference = summary.mean - rest_mean;
+            let (t, _, p_value) = stats::welch_t_test(summary.mean, summary.variance(), n, rest_mean, rest_var, rest_n);
+            comparisons.push(GroupComparison {
+                column: headers.get(*col_idx).cloned().unwrap_or_else(|| format!("column_{}", col_idx + 1)),
+                group: group.to_string(),
+                n,
+                mean: summary.mean,
+                std_dev: summary.variance().sqrt(),
+                rest_n,
+                rest_mean,
+                difference,
+                t,
+                p_value,
+                q_value: 1.0,
+            });
+        }
+    }
+    let p_values: Vec<f64> = comparisons.iter().map(|c| c.p_value).collect();
+    for (comparison, q) in comparisons.iter_mut().zip(stats::benjamini_hochberg(&p_values)) {
+        comparison.q_value = q;
+    }
+    comparisons
+}
+
 fn build_novelty_scores(
     headers: &[String],
     selected_indices: &[usize],
-    overall_sum: &[f64],
-    overall_sum_sq: &[f64],
-    overall_count: &[usize],
-    group_sums: &HashMap<String, Vec<(f64, usize)>>,
+    columns: &[ColumnStats],
+    groups: &GroupAggregator,
 ) -> Vec<NoveltyScore> {
     let mut scores = Vec::new();
     for (pos, col_idx) in selected_indices.iter().enumerate() {
-        if overall_count[pos] < 2 {
+        let column = &columns[pos];
+        if column.count < 2 {
             continue;
         }
-        let mean = overall_sum[pos] / overall_count[pos] as f64;
-        let variance = (overall_sum_sq[pos] / overall_count[pos] as f64) - mean * mean;
-        let std = variance.max(0.0).sqrt();
+        let std = column.population_variance().sqrt();
         let mut max_delta: f64 = 0.0;
-        for (_group, sums) in group_sums {
-            let (sum, count) = sums[pos];
-            if count > 0 {
-                let group_mean = sum / count as f64;
-                max_delta = max_delta.max((group_mean - mean).abs());
-            }
+        for (_group, summary) in groups.column(pos) {
+            max_delta = max_delta.max((summary.mean - column.mean).abs());
         }
         let score = if std > 0.0 { (max_delta / (3.0 * std)).min(1.0) } else { 0.0 };
         scores.push(NoveltyScore {
             column: headers.get(*col_idx).cloned().unwrap_or_else(|| format!("column_{}", col_idx + 1)),
             score,
             rationale: "Scaled deviation of group means from overall mean (0-1)".to_string(),
+            p_value: None,
+            q_value: None,
         });
     }
     scores
@@ -416,76 +1251,166 @@ fn build_novelty_scores(
 
 fn build_biomarker_candidates(
     target: Option<&String>,
+    method: CorrelationMethod,
+    split: Option<&roc::Split>,
+    genes: &GeneMap,
+    species: Species,
     headers: &[String],
     selected_indices: &[usize],
-    x_values: &[Vec<f64>],
-    y_values: &[Vec<f64>],
+    target_pos: Option<usize>,
+    pairs: &[PairStats],
+    samples: &[Option<(Vec<f64>, Vec<f64>)>],
 ) -> Vec<BiomarkerCandidate> {
-    let mut candidates = Vec::new();
-    if target.is_none() {
-        return candidates;
-    }
+    let Some(target) = target else {
+        return Vec::new();
+    };
+    // Mouse and rat markers are compared with the human literature
+    let orthologs = (species != Species::Human).then(crate::annotation::orthologs::shared);
 
-    for (pos, col_idx) in selected_indices.iter().enumerate() {
-        if x_values[pos].len() < 3 || x_values[pos].len() != y_values[pos].len() {
-            continue;
+    // Rank coefficients sort each marker's sample, and Kendall's tau
+    // compares its pairs, so the markers are scored in parallel
+    let candidate = |(pos, col_idx): (usize, &usize)| {
+        let n = pairs[pos].count;
+        if n < 3 || Some(pos) == target_pos {
+            return None;
         }
-        let corr = correlation(&x_values[pos], &y_values[pos]);
+        // Rank coefficients and AUCs over the sampled pairs
+        let sample = samples[pos].as_ref();
+        let corr = match (method, sample) {
+            (CorrelationMethod::Pearson, _) | (_, None) => pairs[pos].pearson(),
+            (_, Some((x, y))) => method.coefficient(x, y),
+        };
         let score = corr.abs();
         let direction = if corr >= 0.0 { "positive" } else { "negative" };
-        candidates.push(BiomarkerCandidate {
-            column: headers.get(*col_idx).cloned().unwrap_or_else(|| format!("column_{}", col_idx + 1)),
+        let column = headers.get(*col_idx).cloned().unwrap_or_else(|| format!("column_{}", col_idx + 1));
+        let gene = genes.get(&column);
+        let human_ortholog = orthologs.as_ref().and_then(|orthologs| {
+            orthologs
+                .human(species, &column)
+                .or_else(|| gene.and_then(|g| orthologs.human(species, &g.symbol)))
+                .map(str::to_string)
+        });
+        Some(BiomarkerCandidate {
+            symbol: gene.map(|g| g.symbol.clone()),
+            biotype: gene.map(|g| g.biotype.clone()).filter(|b| !b.is_empty()),
+            human_ortholog,
+            column,
             score,
             correlation: corr,
+            n,
+            p_value: method.p_value(corr, n),
+            q_value: 1.0,
+            auc: split.zip(sample).and_then(|(s, (x, y))| s.auc(x, y)),
             direction: direction.to_string(),
-            notes: "Pearson correlation with target (age). Higher absolute correlation suggests stronger biomarker signal.".to_string(),
-        });
+            notes: format!(
+                "{} correlation with target ({}). Higher absolute correlation suggests stronger biomarker signal.",
+                method.name(),
+                target
+            ),
+            prior_evidence: None,
+        })
+    };
+    let mut candidates: Vec<BiomarkerCandidate> =
+        selected_indices.par_iter().enumerate().filter_map(candidate).collect();
+
+    // Adjust across every tested marker, before truncating to the top hits
+    let p_values: Vec<f64> = candidates.iter().map(|c| c.p_value).collect();
+    for (candidate, q) in candidates.iter_mut().zip(stats::benjamini_hochberg(&p_values)) {
+        candidate.q_value = q;
     }
 
     candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
-    candidates.truncate(50);
     candidates
 }
 
+/// Plot label of a marker, starred when it passes the thresholds
+fn marked(label: &str, passes: bool) -> String {
+    if passes {
+        format!("{} *", label)
+    } else {
+        label.to_string()
+    }
+}
+
+/// Enrichment of `sets` among the candidates passing `thresholds` against
+/// all tested markers
+fn build_enrichment(candidates: &[BiomarkerCandidate], thresholds: &Thresholds, sets: &GeneSets) -> Vec<EnrichmentResult> {
+    let universe: Vec<&str> = candidates.iter().map(|c| c.human_label()).collect();
+    let hits: Vec<&str> = candidates
+        .iter()
+        .filter(|c| thresholds.passes(c.p_value, c.q_value, c.correlation))
+        .map(|c| c.human_label())
+        .collect();
+    sets.enrich(&universe, &hits)
+}
+
+/// Clustered correlation heatmap of the first [`HEATMAP_MARKERS`] of the
+/// `labels` columns, each pair correlated over the `rows` where both are
+/// present
 pub fn write_heatmap(
     output_path: &Path,
-    stats_values: &[Vec<f64>],
+    rows: &[Vec<Option<f64>>],
     labels: &[String],
+    method: CorrelationMethod,
 ) -> Result<()> {
-    let size = stats_values.len().min(20);
+    let size = labels.len().min(HEATMAP_MARKERS);
     if size == 0 {
         return Ok(());
     }
-    let mut corr = vec![vec![0.0; size]; size];
-    for i in 0..size {
-        for j in 0..size {
-            corr[i][j] = correlation(&stats_values[i], &stats_values[j]);
-        }
-    }
+    let coefficient = |i: usize, j: usize| {
+        let (x, y): (Vec<f64>, Vec<f64>) = rows
+            .iter()
+            .filter_map(|row| Some((row.get(i).copied().flatten()?, row.get(j).copied().flatten()?)))
+            .unzip();
+        method.coefficient(&x, &y)
+    };
+    let corr: Vec<Vec<f64>> = (0..size)
+        .into_par_iter()
+        .map(|i| (0..size).map(|j| coefficient(i, j)).collect())
+        .collect();
+    // Markers in cluster order on both axes, first at the top left
+    let distances: Vec<Vec<f64>> = corr.iter().map(|row| row.iter().map(|r| 1.0 - r).collect()).collect();
+    let tree = cluster::average_linkage(&distances);
+    let order = &tree.order;
 
-    let root = BitMapBackend::new(output_path, (800, 800)).into_drawing_area();
+    let root = BitMapBackend::new(output_path, (900, 960)).into_drawing_area();
     root.fill(&WHITE)?;
+    let root = root.titled("Correlation Heatmap", ("sans-serif", 24))?;
+    let (top, plot) = root.split_vertically(120);
 
-    let mut chart = ChartBuilder::on(&root)
+    // Same side margins and label area as the heatmap, so the leaves line
+    // up with its columns
+    let mut dendrogram = ChartBuilder::on(&top)
+        .margin(20)
+        .margin_bottom(0)
+        .y_label_area_size(140)
+        .build_cartesian_2d(0f64..size as f64, 0f64..tree.height().max(f64::EPSILON))?;
+    dendrogram.draw_series(tree.lines(0.0).into_iter().map(|line| PathElement::new(line.to_vec(), BLACK)))?;
+
+    let mut chart = ChartBuilder::on(&plot)
         .margin(20)
-        .caption("Correlation Heatmap", ("sans-serif", 24))
-        .build_cartesian_2d(0..size, 0..size)?;
+        .margin_top(0)
+        .y_label_area_size(140)
+        .build_cartesian_2d(0f64..size as f64, (0..size - 1).into_segmented())?;
+    let row = |p: usize| size - 1 - p;
 
     chart
         .configure_mesh()
         .disable_mesh()
-        .x_labels(size)
+        .x_labels(0)
         .y_labels(size)
-        .x_label_formatter(&|x| labels.get(*x).cloned().unwrap_or_default())
-        .y_label_formatter(&|y| labels.get(*y).cloned().unwrap_or_default())
+        .y_label_formatter(&|y| match y {
+            SegmentValue::CenterOf(y) => order.get(row(*y)).and_then(|i| labels.get(*i)).cloned().unwrap_or_default(),
+            _ => String::new(),
+        })
         .draw()?;
 
-    for i in 0..size {
-        for j in 0..size {
-            let val = corr[i][j];
+    for (x, i) in order.iter().enumerate() {
+        for (p, j) in order.iter().enumerate() {
+            let val = corr[*i][*j];
             let color = HSLColor(240.0 / 360.0 - (240.0 / 360.0) * ((val + 1.0) / 2.0), 0.7, 0.5);
             chart.draw_series(std::iter::once(Rectangle::new(
-                [(i, j), (i + 1, j + 1)],
+                [(x as f64, SegmentValue::Exact(row(p))), (x as f64 + 1.0, SegmentValue::Exact(row(p) + 1))],
                 color.filled(),
             )))?;
         }
@@ -494,39 +1419,14 @@ pub fn write_heatmap(
     Ok(())
 }
 
-pub fn write_boxplot(
-    output_path: &Path,
-    grouped: &HashMap<String, Vec<f64>>,
-    max_groups: usize,
-) -> Result<()> {
-    if grouped.is_empty() {
+/// Box plot of per-group five-number summaries (see
+/// [`GroupAggregator::boxplot`])
+pub fn write_boxplot(output_path: &Path, groups: &[BoxStats]) -> Result<()> {
+    if groups.is_empty() {
         return Ok(());
     }
-    let mut groups: Vec<(String, Vec<f64>)> = grouped
-        .iter()
-        .map(|(k, v)| (k.clone(), v.clone()))
-        .collect();
-    groups.sort_by(|a, b| a.0.cmp(&b.0));
-    groups.truncate(max_groups);
-
-    let mut global_min = f64::INFINITY;
-    let mut global_max = f64::NEG_INFINITY;
-    let mut stats = Vec::new();
-    for (_label, values) in &groups {
-        let mut v = values.clone();
-        v.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        if v.is_empty() {
-            continue;
-        }
-        global_min = global_min.min(*v.first().unwrap());
-        global_max = global_max.max(*v.last().unwrap());
-        let q1 = v[v.len() / 4];
-        let median = v[v.len() / 2];
-        let q3 = v[(v.len() * 3) / 4];
-        let min = *v.first().unwrap();
-        let max = *v.last().unwrap();
-        stats.push((q1, median, q3, min, max));
-    }
+    let global_min = groups.iter().map(|b| b.min).fold(f64::INFINITY, f64::min);
+    let global_max = groups.iter().map(|b| b.max).fold(f64::NEG_INFINITY, f64::max);
 
     let root = BitMapBackend::new(output_path, (900, 500)).into_drawing_area();
     root.fill(&WHITE)?;
@@ -542,12 +1442,12 @@ pub fn write_boxplot(
         .x_labels(groups.len())
         .x_label_formatter(&|x| {
             let idx = (*x).floor() as usize;
-            groups.get(idx).map(|g| g.0.clone()).unwrap_or_default()
+            groups.get(idx).map(|g| g.label.clone()).unwrap_or_default()
         })
         .draw()?;
 
-    for (idx, stat) in stats.iter().enumerate() {
-        let (q1, median, q3, min, max) = *stat;
+    for (idx, stat) in groups.iter().enumerate() {
+        let BoxStats { q1, median, q3, min, max, .. } = *stat;
         let idx_f = idx as f64;
         let rect = Rectangle::new([(idx_f, q1), (idx_f + 1.0, q3)], BLUE.mix(0.3).filled());
         chart.draw_series(std::iter::once(rect))?;
@@ -558,71 +1458,374 @@ pub fn write_boxplot(
     Ok(())
 }
 
+/// Horizontal bars of -log10(p) for the top enriched GO terms; terms with
+/// q < 0.05 are drawn solid
+pub fn write_enrichment_plot(output_path: &Path, enrichment: &[EnrichmentResult], thresholds: &Thresholds) -> Result<()> {
+    let terms: Vec<&EnrichmentResult> = enrichment.iter().take(ENRICHMENT_PLOT_TERMS).collect();
+    if terms.is_empty() {
+        return Ok(());
+    }
+    let scores: Vec<f64> = terms.iter().map(|t| -t.p_value.max(1e-300).log10()).collect();
+    let max_score = scores.iter().cloned().fold(1.0, f64::max) * 1.1;
+
+    let root = BitMapBackend::new(output_path, (900, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(20)
+        .caption("GO Enrichment", ("sans-serif", 24))
+        .x_label_area_size(40)
+        .y_label_area_size(260)
+        .build_cartesian_2d(0f64..max_score, 0f64..terms.len() as f64)?;
+
+    // The strongest term is drawn at the top
+    let row = |idx: usize| (terms.len() - 1 - idx) as f64;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("-log10(p)")
+        .y_labels(terms.len())
+        .y_label_formatter(&|y| {
+            let rank = terms.len().checked_sub(1 + (*y).floor() as usize);
+            rank.and_then(|r| terms.get(r)).map(|t| t.term_name.clone()).unwrap_or_default()
+        })
+        .draw()?;
+
+    for (idx, (term, score)) in terms.iter().zip(&scores).enumerate() {
+        let style = if thresholds.significant(term.p_value, term.q_value) {
+            BLUE.filled()
+        } else {
+            BLUE.mix(0.35).filled()
+        };
+        let y = row(idx);
+        chart.draw_series(std::iter::once(Rectangle::new([(0.0, y + 0.15), (*score, y + 0.85)], style)))?;
+    }
+    Ok(())
+}
+
 pub fn build_manuscript(
     dataset_id: &str,
     target: &str,
     group: &str,
     record: &crate::data_registry::DatasetRecord,
     analysis: &AnalysisArtifacts,
+    template: JournalTemplate,
 ) -> String {
     let project_id = format!("OXBIO-{}", dataset_id);
-    let top_biomarkers: Vec<String> = analysis
-        .biomarker_candidates
-        .iter()
-        .take(10)
-        .map(|b| format!("{} (r={:.3}, {})", b.column, b.correlation, b.direction))
-        .collect();
-    let top_list = if top_biomarkers.is_empty() {
-        "No biomarker candidates were identified.".to_string()
-    } else {
-        top_biomarkers.join(", ")
-    };
+    let findings = analysis.findings();
 
-    format!(
-        "Project ID: {project_id}\n\
-Title: Biomarker discovery in log2-normalized microarray data\n\
-\n\
-Abstract\n\
-We analyzed log2-normalized microarray data to identify aging-associated biomarkers. \
+    let sections = [
+        (
+            "abstract",
+            format!(
+                "We analyzed log2-normalized {organism}microarray data to identify aging-associated biomarkers. \
 The dataset contained {rows} rows and {cols} columns. Using descriptive statistics, \
 regression modeling, and biomarker ranking by correlation with {target}, we identified \
-candidate biomarkers with the strongest association to aging.\n\
-\n\
-Methods\n\
-Data ingestion validated CSV/TSV structure and inferred column headers. \
-Descriptive statistics were computed per numeric marker. Linear regression models were fit \
-to explain {target} from specified covariates. Biomarker candidates were ranked by Pearson \
-correlation with {target}. Group-level distributions were summarized by {group}. \
-Correlation heatmaps and box plots were generated for exploratory analysis.\n\
-\n\
-Results\n\
-Computed descriptive statistics for {stat_count} markers, regressions for {reg_count} model(s), \
-and novelty scores for {novelty_count} markers. Top biomarker candidates: {top_list}.\n\
-\n\
-Discussion\n\
-Markers with strong correlations to {target} represent candidate aging biomarkers in this \
-dataset. These findings provide a ranked shortlist for downstream validation (e.g., \
-replication cohorts, pathway analysis, or mechanistic experiments). \
+candidate biomarkers with the strongest association to aging.",
+                rows = record.row_count,
+                cols = record.columns.len(),
+                organism = match record.species {
+                    Species::Human => String::new(),
+                    species => format!("{} ", species),
+                },
+            ),
+        ),
+        (
+            "methods",
+            format!(
+                "Data ingestion validated CSV/TSV structure and inferred column headers. {collapse}{replicates}{imputation}{normalization}{batch}{qc}\
+Descriptive statistics were computed per numeric marker. {ranking} were adjusted for \
+multiple testing with the Benjamini-Hochberg procedure. Group-level distributions were summarized by {group}. \
+Correlation heatmaps and box plots were generated for exploratory analysis, with heatmap markers and samples \
+ordered by average-linkage hierarchical clustering on correlation distance (1 - r). GO term over-representation among \
+candidates with {criterion} was tested against all annotated markers with a one-sided hypergeometric test and \
+Benjamini-Hochberg correction across terms.{pathways}{gsea}{roc}{survival}{contrast}{anova}{sex}{pca}{panel}{orthologs}{literature} {thresholds}{manifest}",
+                criterion = analysis.thresholds.marker_criterion(),
+                pathways = if analysis.pathways.is_empty() {
+                    ""
+                } else {
+                    " Over-representation of Reactome and KEGG pathways was tested the same way, with \
+Benjamini-Hochberg correction across pathways."
+                },
+                thresholds = analysis.thresholds.methods(),
+                manifest = analysis.manifest.as_ref().map(|m| format!(" {}", m.methods())).unwrap_or_default(),
+                ranking = match &analysis.adjustment {
+                    Some(adjustment) => adjustment.methods(target),
+                    None => format!(
+                        "Linear regression models were fit to explain {target} from specified covariates. \
+Biomarker candidates were ranked by {} with {target}; two-sided p-values from {}",
+                        analysis.correlation.describe(),
+                        analysis.correlation.p_value_source(),
+                    ),
+                },
+                contrast = analysis
+                    .contrast
+                    .as_ref()
+                    .map(|c| format!(" {}", c.methods()))
+                    .unwrap_or_default(),
+                anova = if analysis.anova.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {}", anova::methods(&analysis.anova, group))
+                },
+                sex = analysis
+                    .sex_stratified
+                    .as_ref()
+                    .map(|s| format!(" {}", s.methods(target)))
+                    .unwrap_or_default(),
+                pca = analysis.pca.as_ref().map(|p| format!(" {}", p.methods())).unwrap_or_default(),
+                panel = analysis.regularized.as_ref().map(|m| format!(" {}", m.methods())).unwrap_or_default(),
+                gsea = analysis.gsea.as_ref().map(|g| format!(" {}", g.methods())).unwrap_or_default(),
+                roc = analysis.roc.as_ref().map(|r| format!(" {}", r.methods())).unwrap_or_default(),
+                survival = analysis.survival.as_ref().map(|s| format!(" {}", s.methods())).unwrap_or_default(),
+                orthologs = ortholog_methods(record.species, &analysis.biomarker_candidates),
+                literature = analysis
+                    .cross_reference
+                    .as_ref()
+                    .map(|c| format!(" {}", c.methods()))
+                    .unwrap_or_default(),
+                collapse = analysis
+                    .probe_collapse
+                    .as_ref()
+                    .map(|c| format!("{} ", c.methods()))
+                    .unwrap_or_default(),
+                replicates = analysis
+                    .replicates
+                    .as_ref()
+                    .map(|r| format!("{} ", r.methods()))
+                    .unwrap_or_default(),
+                imputation = analysis
+                    .imputation
+                    .as_ref()
+                    .map(|i| format!("{} ", i.methods()))
+                    .unwrap_or_default(),
+                normalization = analysis
+                    .normalization
+                    .as_ref()
+                    .map(|n| format!("{} ", n.methods()))
+                    .unwrap_or_default(),
+                batch = analysis.batch.as_ref().map(|b| format!("{} ", b.methods())).unwrap_or_default(),
+                qc = analysis
+                    .sample_qc
+                    .as_ref()
+                    .map(|q| format!("{} ", q.methods()))
+                    .unwrap_or_default(),
+            ),
+        ),
+        (
+            "results",
+            format!(
+                "Computed descriptive statistics for {stat_count} markers, regressions for {reg_count} model(s), \
+and novelty scores for {novelty_count} markers. {qc}{findings}{gsea}{roc}{survival}{contrast}{anova}{sex}{pca}{panel}",
+                stat_count = analysis.descriptive_stats.len(),
+                reg_count = analysis.regressions.len(),
+                novelty_count = analysis.novelty_scores.len(),
+                qc = analysis
+                    .sample_qc
+                    .as_ref()
+                    .map(|q| format!("{} ", q.results()))
+                    .unwrap_or_default(),
+                findings = findings.results_text(),
+                contrast = analysis
+                    .contrast
+                    .as_ref()
+                    .map(|c| format!(" {}", c.results()))
+                    .unwrap_or_default(),
+                anova = if analysis.anova.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {}", anova::results(&analysis.anova, group, &analysis.thresholds))
+                },
+                sex = analysis
+                    .sex_stratified
+                    .as_ref()
+                    .map(|s| format!(" {}", s.results(target)))
+                    .unwrap_or_default(),
+                pca = analysis.pca.as_ref().map(|p| format!(" {}", p.results())).unwrap_or_default(),
+                panel = analysis.regularized.as_ref().map(|m| format!(" {}", m.results())).unwrap_or_default(),
+                gsea = analysis
+                    .gsea
+                    .as_ref()
+                    .map(|g| format!(" {}", g.results(&analysis.thresholds)))
+                    .unwrap_or_default(),
+                roc = analysis.roc.as_ref().map(|r| format!(" {}", r.results())).unwrap_or_default(),
+                survival = analysis
+                    .survival
+                    .as_ref()
+                    .map(|s| format!(" {}", s.results(&analysis.thresholds)))
+                    .unwrap_or_default(),
+            ),
+        ),
+        (
+            "discussion",
+            format!(
+                "Markers with strong correlations to {target} represent candidate aging biomarkers in this \
+dataset.{enrichment}{pathways}{literature} These findings provide a ranked shortlist for downstream validation \
+(e.g., replication cohorts or mechanistic experiments). \
 Because the data are already log2-normalized, relative effect sizes are interpretable in \
 log2 space. The correlation-based ranking provides a fast triage; additional modeling \
-and multiple-testing correction are recommended for definitive claims.\n\
+and replication are recommended for definitive claims.",
+                enrichment = enrichment_discussion(&analysis.enrichment, &analysis.thresholds),
+                pathways = pathway_discussion(&analysis.pathways, &analysis.thresholds),
+                literature = literature_discussion(&analysis.biomarker_candidates),
+            ),
+        ),
+        (
+            "limitations",
+            format!(
+                "{normalized}{skipped}. {batch}{adjusted}, and \
+GO and pathway enrichment are limited to the configured gene sets (by default a compact GO slim and a \
+small set of Reactome and KEGG pathways).{literature}",
+                normalized = match &analysis.normalization {
+                    Some(n) => format!(
+                        "{} The analysis assumes the normalized values are comparable across samples",
+                        n.limitations()
+                    ),
+                    None => "The analysis assumes numeric columns are properly normalized".to_string(),
+                },
+                skipped = {
+                    let skipped: Vec<&str> = [
+                        analysis.batch.is_none().then_some("batch correction"),
+                        analysis.probe_collapse.is_none().then_some("probe re-annotation"),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                    if skipped.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" and does not perform {}", skipped.join(" or "))
+                    }
+                },
+                batch = analysis.batch.as_ref().map(|b| format!("{} ", b.limitations())).unwrap_or_default(),
+                adjusted = match &analysis.adjustment {
+                    Some(_) => "Adjustment covers only the covariates named in the model formula",
+                    None => "Correlations are unadjusted for covariates",
+                },
+                literature = match analysis.cross_reference {
+                    Some(_) => " Previously reported and novel flags rest on publication counts for a keyword \
+search and do not check the direction, tissue or species of earlier findings.",
+                    None => "",
+                },
+            ),
+        ),
+        ("supplementary", supplement::manuscript_section(analysis)),
+    ];
+
+    format!(
+        "Project ID: {project_id}\n\
+Title: Biomarker discovery in log2-normalized microarray data\n\
 \n\
-Limitations\n\
-The analysis assumes numeric columns are properly normalized and does not perform batch \
-correction or probe re-annotation. Statistical significance testing and biological \
-annotation are not yet included.\n",
-        rows = record.row_count,
-        cols = record.columns.len(),
-        target = target,
-        group = group,
-        stat_count = analysis.descriptive_stats.len(),
-        reg_count = analysis.regressions.len(),
-        novelty_count = analysis.novelty_scores.len(),
-        top_list = top_list
+{body}",
+        body = template.assemble(&sections)
+    )
+}
+
+/// Discussion sentence on the GO terms enriched among the significant biomarkers
+/// Methods sentence on the human ortholog mapping of mouse and rat markers
+fn ortholog_methods(species: Species, candidates: &[BiomarkerCandidate]) -> String {
+    if species == Species::Human {
+        return String::new();
+    }
+    let mapped = candidates.iter().filter(|c| c.human_ortholog.is_some()).count();
+    format!(
+        " {} genes were mapped to their human one-to-one orthologs (Ensembl Compara; {} of {} reported \
+candidates mapped) so that candidates could be compared with the human aging literature; GO enrichment used the \
+human orthologs.",
+        species.scientific_name(),
+        mapped,
+        candidates.len()
     )
 }
 
-fn correlation(x: &[f64], y: &[f64]) -> f64 {
+/// Discussion sentence contrasting previously reported and novel candidates
+fn literature_discussion(candidates: &[BiomarkerCandidate]) -> String {
+    let with_status = |status: EvidenceStatus| -> Vec<String> {
+        candidates
+            .iter()
+            .filter(|c| c.prior_evidence.as_ref().is_some_and(|e| e.status == status))
+            .map(|c| c.display_label())
+            .collect()
+    };
+    let (reported, novel) = (with_status(EvidenceStatus::PreviouslyReported), with_status(EvidenceStatus::Novel));
+    match (reported.is_empty(), novel.is_empty()) {
+        (true, true) => String::new(),
+        (false, true) => format!(
+            " All cross-referenced candidates ({}) have been linked to aging before, supporting the validity of the \
+ranking.",
+            reported.join(", ")
+        ),
+        (true, false) => format!(
+            " None of the cross-referenced candidates ({}) has an established link to aging in the literature, so \
+all of them are novel candidates that warrant replication.",
+            novel.join(", ")
+        ),
+        (false, false) => format!(
+            " {} recover previously reported aging genes, supporting the validity of the ranking, while {} have little \
+prior literature on aging and are novel candidates that warrant replication.",
+            reported.join(", "),
+            novel.join(", ")
+        ),
+    }
+}
+
+fn enrichment_discussion(enrichment: &[EnrichmentResult], thresholds: &Thresholds) -> String {
+    let significant: Vec<String> = enrichment
+        .iter()
+        .filter(|e| thresholds.significant(e.p_value, e.q_value))
+        .take(3)
+        .map(|e| format!("{} ({}; {}, {})", e.term_name, e.term_id, e.genes.join(", "), number::p_quote("q", e.q_value)))
+        .collect();
+    match (significant.is_empty(), enrichment.first()) {
+        (false, _) => format!(
+            " The significant biomarkers were enriched for {}, pointing to the processes most affected.",
+            significant.join("; ")
+        ),
+        (true, Some(top)) => format!(
+            " No GO term reached {} among the significant biomarkers; the strongest signal was {} \
+({}, {}).",
+            thresholds.criterion(), top.term_name, top.genes.join(", "), number::p_quote("p", top.p_value)
+        ),
+        (true, None) => String::new(),
+    }
+}
+
+/// Discussion sentence on the Reactome and KEGG pathways enriched among the
+/// significant biomarkers
+fn pathway_discussion(pathways: &[EnrichmentResult], thresholds: &Thresholds) -> String {
+    let significant: Vec<String> = pathways
+        .iter()
+        .filter(|e| thresholds.significant(e.p_value, e.q_value))
+        .take(3)
+        .map(|e| {
+            format!(
+                "{} ({} {}; {}, {})",
+                e.term_name,
+                annotation::pathways::database(&e.term_id),
+                e.term_id,
+                e.genes.join(", "),
+                number::p_quote("q", e.q_value)
+            )
+        })
+        .collect();
+    match (significant.is_empty(), pathways.first()) {
+        (false, _) => format!(
+            " At the pathway level they were over-represented in {}, suggesting the mechanisms through which \
+they relate to aging.",
+            significant.join("; ")
+        ),
+        (true, Some(top)) => format!(
+            " No Reactome or KEGG pathway reached {}; the closest was {} ({}, {}).",
+            thresholds.criterion(),
+            top.term_name,
+            top.genes.join(", "),
+            number::p_quote("p", top.p_value)
+        ),
+        (true, None) => String::new(),
+    }
+}
+
+pub(super) fn correlation(x: &[f64], y: &[f64]) -> f64 {
     let n = x.len().min(y.len());
     if n < 2 {
         return 0.0;
@@ -645,3 +1848,72 @@ fn correlation(x: &[f64], y: &[f64]) -> f64 {
         num / (denom_x.sqrt() * denom_y.sqrt())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demo::{self, DemoOptions};
+
+    /// Age against every marker, grouped by cell type
+    fn config() -> AnalysisConfig {
+        AnalysisConfig {
+            target_column: Some("age".to_string()),
+            group_column: Some("cell_type".to_string()),
+            correlation: CorrelationMethod::Spearman,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_results_do_not_depend_on_threads() {
+        let options = DemoOptions {
+            rows: 60,
+            markers: 40,
+            planted: 3,
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let files = demo::write(&demo::generate(&options).unwrap(), &dir.path().join("demo.csv")).unwrap();
+        let record = DatasetRecord::from_path(&files[0], None).unwrap();
+        let run = |threads: usize| {
+            // Pools of their own, since the shared ones stop at one per core
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            let out = dir.path().join(format!("threads_{}", threads));
+            run_in(&pool, &record, &config(), &out, &CancellationToken::new()).unwrap()
+        };
+        let (one, four) = (run(1), run(4));
+        fn json<T: serde::Serialize>(value: &T) -> String {
+            serde_json::to_string(value).unwrap()
+        }
+        assert_eq!(one.summary, four.summary);
+        assert_eq!(json(&one.descriptive_stats), json(&four.descriptive_stats));
+        assert_eq!(json(&one.regressions), json(&four.regressions));
+        assert_eq!(json(&one.biomarker_candidates), json(&four.biomarker_candidates));
+        assert_eq!(json(&one.novelty_scores), json(&four.novelty_scores));
+        assert_eq!(json(&one.group_comparisons), json(&four.group_comparisons));
+    }
+
+    #[test]
+    fn test_headerless_rows_are_all_analysed() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("matrix.csv");
+        std::fs::write(&input, "30,1.5,7\n40,2.5,9\n50,3.0,8\n60,4.5,12\n").unwrap();
+        let record = DatasetRecord {
+            has_headers: false,
+            columns: (1..=3).map(|idx| format!("column_{}", idx)).collect(),
+            row_count: 4,
+            ..DatasetRecord::from_path(&input, None).unwrap()
+        };
+        let config = AnalysisConfig {
+            target_column: Some("column_1".to_string()),
+            group_column: None,
+            ..config()
+        };
+
+        let result = run_analysis(&record, &config, &dir.path().join("out"), &CancellationToken::new()).unwrap();
+        let stats: Vec<(&str, usize, f64, f64)> =
+            result.descriptive_stats.iter().map(|s| (s.column.as_str(), s.count, s.min, s.max)).collect();
+        // The first row is data: 30 and 1.5 are its values
+        assert_eq!(stats, [("column_1", 4, 30.0, 60.0), ("column_2", 4, 1.5, 4.5), ("column_3", 4, 7.0, 12.0)]);
+    }
+}
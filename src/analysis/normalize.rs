@@ -0,0 +1,444 @@
+//! Marker normalization
+//!
+//! The statistics take the marker values as they are. Raw intensities or
+//! counts, or samples measured at different overall levels, are normalized
+//! first, after imputation and before batch correction, by one or more
+//! steps applied in the order given:
+//!
+//! - **log2** replaces each value with log2(value + 1); markers with
+//!   negative values (already on a log scale, or centred) are left as they
+//!   are.
+//! - **zscore** centres each marker on its mean and scales it to unit
+//!   standard deviation across samples.
+//! - **quantile** gives every sample the same distribution of marker
+//!   values: the value of each rank becomes the mean over samples of the
+//!   values at that rank (Bolstad et al., 2003). Samples with missing values
+//!   are matched by quantile rather than rank.
+//!
+//! The target, group, covariate and batch columns are never normalized. The
+//! normalized dataset is written next to the results, with box plots of
+//! each sample's marker values before and after.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::analysis::aggregate::BoxStats;
+use crate::data_registry::{ColumnKind, DatasetRecord};
+use crate::utils::cancel::{self, CancellationToken};
+
+/// Normalized dataset
+pub const NORMALIZED_FILE: &str = "normalized_matrix.csv";
+
+/// Per-sample distributions before and after normalization
+pub const DISTRIBUTIONS_FILE: &str = "normalization.png";
+
+/// Added before the log2 transform, so that zeros stay finite
+pub const LOG_PSEUDOCOUNT: f64 = 1.0;
+
+/// Samples drawn in the distribution plot, spread evenly over the dataset
+const PLOT_SAMPLES: usize = 40;
+
+/// Rows normalized between cancellation checks
+const CANCEL_CHECK_ROWS: usize = 1000;
+
+/// One normalization step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    /// log2(value + 1)
+    Log2,
+    /// Centre and scale each marker across samples
+    Zscore,
+    /// Give every sample the same distribution of marker values
+    Quantile,
+}
+
+impl Normalization {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Log2 => "log2",
+            Self::Zscore => "zscore",
+            Self::Quantile => "quantile",
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Log2 => "log2 transformation with a pseudocount of 1",
+            Self::Zscore => "z-scoring of each marker across samples",
+            Self::Quantile => "quantile normalization across samples",
+        }
+    }
+
+    fn limitations(self) -> &'static str {
+        match self {
+            Self::Log2 => "The pseudocount of the log2 transformation compresses differences among values near zero.",
+            Self::Zscore => {
+                "Z-scored markers share one scale, so effect sizes are in standard deviations rather than the \
+                 markers' own units."
+            }
+            Self::Quantile => {
+                "Quantile normalization assumes most markers do not differ between samples, and removes global \
+                 shifts between samples even where they are biological."
+            }
+        }
+    }
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Normalization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "log2" | "log" => Ok(Self::Log2),
+            "zscore" | "z-score" | "z" => Ok(Self::Zscore),
+            "quantile" => Ok(Self::Quantile),
+            other => Err(format!("unknown normalization '{}' (expected log2, zscore or quantile)", other)),
+        }
+    }
+}
+
+/// Comma-separated steps, e.g. `log2,quantile`; empty or `off` for none
+pub fn parse_steps(s: &str) -> Result<Vec<Normalization>, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "" | "off" | "none" => Ok(Vec::new()),
+        steps => steps.split(',').map(str::parse).collect(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NormalizationSummary {
+    /// Steps applied, in order
+    pub steps: Vec<Normalization>,
+    pub markers: usize,
+    pub samples: usize,
+    /// Markers the log2 step left alone for having negative values
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub not_logged: Vec<String>,
+    /// Normalized dataset
+    pub path: PathBuf,
+    /// Marker values of the plotted samples before and after
+    #[serde(skip)]
+    pub before: Vec<BoxStats>,
+    #[serde(skip)]
+    pub after: Vec<BoxStats>,
+}
+
+impl NormalizationSummary {
+    /// One sentence for the manuscript's Methods section
+    pub fn methods(&self) -> String {
+        let mut text = format!(
+            "Marker values ({} markers over {} samples) were normalized by {}",
+            self.markers,
+            self.samples,
+            self.steps.iter().map(|s| s.describe()).collect::<Vec<_>>().join(", followed by ")
+        );
+        if !self.not_logged.is_empty() {
+            text.push_str(&format!(
+                "; {} marker(s) with negative values were not log-transformed",
+                self.not_logged.len()
+            ));
+        }
+        text.push_str("; target, group, covariate and batch columns were not normalized.");
+        text
+    }
+
+    /// Every marker was log2-transformed and none rescaled afterwards, so
+    /// differences of means are log2 fold changes
+    pub fn log2_scale(&self) -> bool {
+        self.steps.contains(&Normalization::Log2)
+            && !self.steps.contains(&Normalization::Zscore)
+            && self.not_logged.is_empty()
+    }
+
+    /// Caveats for the Limitations section
+    pub fn limitations(&self) -> String {
+        self.steps.iter().map(|s| s.limitations()).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Write `record` with its numeric columns not named in `exclude`
+/// normalized by `steps` as `path`; `None` when there are no steps
+pub fn normalize(
+    record: &DatasetRecord,
+    steps: &[Normalization],
+    exclude: &[&str],
+    path: &Path,
+    cancel: &CancellationToken,
+) -> Result<Option<(DatasetRecord, NormalizationSummary)>> {
+    if steps.is_empty() {
+        return Ok(None);
+    }
+    let mut rdr = record.reader()?;
+    let rows: Vec<csv::StringRecord> = rdr.records().collect::<Result<_, _>>()?;
+    // Columns with any value, and values[column][row]
+    let (columns, mut values): (Vec<usize>, Vec<Vec<Option<f64>>>) = (0..record.columns.len())
+        .filter(|idx| record.column_kind(*idx).is_none_or(|kind| kind == ColumnKind::Numeric))
+        .filter(|idx| !exclude.contains(&record.columns[*idx].as_str()))
+        .map(|idx| (idx, rows.iter().map(|row| row.get(idx).and_then(|v| record.number(v))).collect::<Vec<_>>()))
+        .filter(|(_, col)| col.iter().any(Option::is_some))
+        .unzip();
+    if columns.is_empty() {
+        bail!("No numeric marker column to normalize");
+    }
+    if steps.contains(&Normalization::Quantile) && columns.len() < 2 {
+        bail!("Quantile normalization needs at least two marker columns");
+    }
+
+    let plotted: Vec<usize> = if rows.len() <= PLOT_SAMPLES {
+        (0..rows.len()).collect()
+    } else {
+        (0..PLOT_SAMPLES).map(|i| i * rows.len() / PLOT_SAMPLES).collect()
+    };
+    let before = distributions(&values, &plotted);
+
+    let mut not_logged = Vec::new();
+    for step in steps {
+        cancel::check(cancel)?;
+        match step {
+            Normalization::Log2 => {
+                for (idx, col) in columns.iter().zip(&mut values) {
+                    if col.iter().flatten().any(|v| *v < 0.0) {
+                        if !not_logged.contains(&record.columns[*idx]) {
+                            not_logged.push(record.columns[*idx].clone());
+                        }
+                    } else {
+                        col.iter_mut().flatten().for_each(|v| *v = (*v + LOG_PSEUDOCOUNT).log2());
+                    }
+                }
+            }
+            Normalization::Zscore => values.iter_mut().for_each(|col| zscore(col)),
+            Normalization::Quantile => quantile(&mut values, cancel)?,
+        }
+    }
+    if !not_logged.is_empty() {
+        warn!(markers = not_logged.len(), "Markers with negative values were not log2-transformed");
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .from_path(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    wtr.write_record(&record.columns)?;
+    let mut positions = vec![None; record.columns.len()];
+    for (pos, idx) in columns.iter().enumerate() {
+        positions[*idx] = Some(pos);
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let out: Vec<String> = (0..record.columns.len())
+            .map(|idx| match positions[idx].and_then(|pos: usize| values[pos][i]) {
+                Some(value) => value.to_string(),
+                None => row.get(idx).unwrap_or_default().to_string(),
+            })
+            .collect();
+        wtr.write_record(&out)?;
+    }
+    wtr.flush()?;
+
+    let summary = NormalizationSummary {
+        steps: steps.to_vec(),
+        markers: columns.len(),
+        samples: rows.len(),
+        not_logged,
+        path: path.to_path_buf(),
+        after: distributions(&values, &plotted),
+        before,
+    };
+    let mut normalized = DatasetRecord::from_path_with(path, Some(record.dataset.description.clone()), record.parsing.derived())?;
+    normalized.dataset.id = record.dataset.id.clone();
+    normalized.species = record.species;
+    Ok(Some((normalized, summary)))
+}
+
+/// `col` centred on its mean and scaled to unit (sample) standard
+/// deviation; constant columns are only centred
+fn zscore(col: &mut [Option<f64>]) {
+    let observed: Vec<f64> = col.iter().flatten().copied().collect();
+    let n = observed.len() as f64;
+    let mean = observed.iter().sum::<f64>() / n;
+    let sd = (observed.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0)).sqrt();
+    let sd = if sd > 0.0 { sd } else { 1.0 };
+    col.iter_mut().flatten().for_each(|v| *v = (*v - mean) / sd);
+}
+
+/// Quantile normalization of the rows of `values` (columns × rows). The
+/// reference distribution is the mean of the rows' quantiles at as many
+/// evenly spaced points as there are columns; a row's value at quantile p
+/// (ties sharing the mean of theirs) becomes the reference's.
+fn quantile(values: &mut [Vec<Option<f64>>], cancel: &CancellationToken) -> Result<()> {
+    let rows = values.first().map_or(0, Vec::len);
+    // Each row's observed values, sorted, with their columns
+    let mut sorted: Vec<Vec<(f64, usize)>> = Vec::with_capacity(rows);
+    for row in 0..rows {
+        if row % CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let mut observed: Vec<(f64, usize)> =
+            values.iter().enumerate().filter_map(|(c, col)| Some((col[row]?, c))).collect();
+        observed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        sorted.push(observed);
+    }
+
+    let points = values.len();
+    let mut reference = vec![0.0; points];
+    let filled: Vec<&Vec<(f64, usize)>> = sorted.iter().filter(|r| !r.is_empty()).collect();
+    for row in &filled {
+        let row: Vec<f64> = row.iter().map(|(v, _)| *v).collect();
+        for (k, point) in reference.iter_mut().enumerate() {
+            *point += at_quantile(&row, k as f64 / (points - 1) as f64);
+        }
+    }
+    reference.iter_mut().for_each(|point| *point /= filled.len().max(1) as f64);
+
+    for (row, observed) in sorted.iter().enumerate() {
+        let n = observed.len();
+        let p = |rank: usize| if n > 1 { rank as f64 / (n - 1) as f64 } else { 0.5 };
+        let mut start = 0;
+        while start < n {
+            let mut end = start;
+            while end + 1 < n && observed[end + 1].0 == observed[start].0 {
+                end += 1;
+            }
+            let tied = (start..=end).map(|rank| at_quantile(&reference, p(rank))).sum::<f64>() / (end - start + 1) as f64;
+            for (_, col) in &observed[start..=end] {
+                values[*col][row] = Some(tied);
+            }
+            start = end + 1;
+        }
+    }
+    Ok(())
+}
+
+/// Value of `sorted` at quantile `p` (0-1), interpolating linearly
+fn at_quantile(sorted: &[f64], p: f64) -> f64 {
+    let pos = p * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// Five-number summary of the marker values of each of `rows`
+fn distributions(values: &[Vec<Option<f64>>], rows: &[usize]) -> Vec<BoxStats> {
+    rows.iter()
+        .filter_map(|row| {
+            let mut observed: Vec<f64> = values.iter().filter_map(|col| col[*row]).collect();
+            if observed.is_empty() {
+                return None;
+            }
+            observed.sort_by(f64::total_cmp);
+            Some(BoxStats {
+                label: (row + 1).to_string(),
+                n: observed.len(),
+                min: observed[0],
+                q1: at_quantile(&observed, 0.25),
+                median: at_quantile(&observed, 0.5),
+                q3: at_quantile(&observed, 0.75),
+                max: observed[observed.len() - 1],
+            })
+        })
+        .collect()
+}
+
+/// Box plots of each plotted sample's marker values, before (left) and
+/// after (right) normalization
+pub fn write_distribution_plot(output_path: &Path, summary: &NormalizationSummary) -> Result<()> {
+    let root = BitMapBackend::new(output_path, (1200, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let panels = root.split_evenly((1, 2));
+    let steps = summary.steps.iter().map(|s| s.id()).collect::<Vec<_>>().join(" + ");
+    for (panel, (title, boxes)) in panels
+        .iter()
+        .zip([("Before normalization".to_string(), &summary.before), (format!("After {}", steps), &summary.after)])
+    {
+        if boxes.is_empty() {
+            continue;
+        }
+        let min = boxes.iter().map(|b| b.min).fold(f64::INFINITY, f64::min);
+        let max = boxes.iter().map(|b| b.max).fold(f64::NEG_INFINITY, f64::max);
+        let (min, max) = if max > min { (min, max) } else { (min - 1.0, max + 1.0) };
+        let mut chart = ChartBuilder::on(panel)
+            .margin(15)
+            .caption(title, ("sans-serif", 20))
+            .x_label_area_size(30)
+            .y_label_area_size(50)
+            .build_cartesian_2d(0f64..boxes.len() as f64, min..max)?;
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_desc("Sample")
+            .y_desc("Marker value")
+            .x_labels(boxes.len().min(10))
+            .x_label_formatter(&|x| boxes.get(x.floor() as usize).map(|b| b.label.clone()).unwrap_or_default())
+            .draw()?;
+        for (idx, stat) in boxes.iter().enumerate() {
+            let x = idx as f64;
+            chart.draw_series(std::iter::once(Rectangle::new([(x + 0.15, stat.q1), (x + 0.85, stat.q3)], BLUE.mix(0.3).filled())))?;
+            chart.draw_series(std::iter::once(PathElement::new(vec![(x + 0.15, stat.median), (x + 0.85, stat.median)], BLUE)))?;
+            chart.draw_series(std::iter::once(PathElement::new(vec![(x + 0.5, stat.q3), (x + 0.5, stat.max)], BLACK)))?;
+            chart.draw_series(std::iter::once(PathElement::new(vec![(x + 0.5, stat.q1), (x + 0.5, stat.min)], BLACK)))?;
+        }
+    }
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization() {
+        assert_eq!(parse_steps("log2, Quantile"), Ok(vec![Normalization::Log2, Normalization::Quantile]));
+        assert_eq!(parse_steps("off"), Ok(Vec::new()));
+        assert!(parse_steps("log10").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        // S2 is S1 at twice the level; S3 has a gap
+        let csv = "sample_id,age,gene_a,gene_b,gene_c\n\
+                   S1,30,1,3,7\nS2,35,2,6,14\nS3,60,4,,0\n";
+        std::fs::write(&input, csv).unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+        let path = dir.path().join(NORMALIZED_FILE);
+        let token = CancellationToken::new();
+
+        let (normalized, summary) =
+            normalize(&record, &[Normalization::Log2], &["age"], &path, &token).unwrap().unwrap();
+        assert_eq!((summary.markers, summary.samples), (3, 3));
+        let written = std::fs::read_to_string(&normalized.local_path).unwrap();
+        assert_eq!(written.lines().nth(1), Some("S1,30,1,2,3"));
+        assert_eq!(written.lines().nth(3), Some("S3,60,2.321928094887362,,0"));
+        assert!(summary.methods().starts_with("Marker values (3 markers over 3 samples) were normalized by log2"));
+        assert!(summary.log2_scale());
+
+        // Every complete sample gets the reference distribution: the mean of
+        // (1, 2), (3, 6) and (7, 14) rank by rank
+        let (_, summary) = normalize(&record, &[Normalization::Quantile], &["age"], &path, &token).unwrap().unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().nth(1), written.lines().nth(2).map(|l| l.replace("S2,35", "S1,30")).as_deref());
+        assert_eq!(summary.after[0].median, summary.after[1].median);
+        assert_eq!(summary.before.len(), 3);
+        assert!(!summary.log2_scale());
+
+        let (_, summary) = normalize(&record, &[Normalization::Zscore], &["age"], &path, &token).unwrap().unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().nth(2), Some("S2,35,-0.2182178902359925,0.7071067811865476,1"));
+        assert!(summary.limitations().starts_with("Z-scored markers"));
+
+        assert!(normalize(&record, &[], &[], &path, &token).unwrap().is_none());
+    }
+}
@@ -0,0 +1,405 @@
+//! Biomarker panel optimization
+//!
+//! Picks a small set of markers that together predict the target (age) for
+//! translational use: greedy forward selection over the top biomarker
+//! candidates, adding at each step the marker that most lowers the k-fold
+//! cross-validated RMSE of an OLS model, until the panel reaches its size
+//! limit or another marker no longer helps. Every step is kept with its CV
+//! performance, so a smaller panel can be read off when an assay needs one.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use nalgebra::{DMatrix, DVector};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Serialize;
+
+use super::{ols_fit, AnalysisArtifacts, COLLAPSED_FILE};
+use crate::data_registry::{DatasetRecord, ParseOptions};
+
+/// Panel table and model written next to the analysis
+pub const PANEL_CSV: &str = "panel.csv";
+pub const PANEL_JSON: &str = "panel.json";
+
+#[derive(Debug, Clone, Copy)]
+pub struct PanelOptions {
+    /// Largest panel considered
+    pub max_size: usize,
+    /// Cross-validation folds
+    pub folds: usize,
+    /// Top biomarker candidates the panel is picked from
+    pub pool: usize,
+    /// Smallest relative CV RMSE reduction worth another marker
+    pub min_gain: f64,
+    /// Seed of the fold assignment
+    pub seed: u64,
+}
+
+impl Default for PanelOptions {
+    fn default() -> Self {
+        Self {
+            max_size: 10,
+            folds: 5,
+            pool: 30,
+            min_gain: 0.01,
+            seed: 42,
+        }
+    }
+}
+
+/// One forward-selection step: the panel so far and how well it predicts
+#[derive(Debug, Clone, Serialize)]
+pub struct PanelStep {
+    pub size: usize,
+    /// Marker added at this step
+    pub column: String,
+    pub label: String,
+    pub cv_rmse: f64,
+    pub cv_mae: f64,
+    /// Out-of-fold R² (1 - SSE / SST)
+    pub cv_r2: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PanelMarker {
+    pub column: String,
+    pub label: String,
+    pub coefficient: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Panel {
+    pub target: String,
+    /// Samples with a target value
+    pub n: usize,
+    pub folds: usize,
+    /// Candidates the panel was picked from
+    pub candidates: usize,
+    /// CV RMSE of predicting the training mean
+    pub baseline_rmse: f64,
+    pub steps: Vec<PanelStep>,
+    /// Model of the full panel, fit on all samples
+    pub intercept: f64,
+    pub markers: Vec<PanelMarker>,
+}
+
+impl Panel {
+    /// Chat / log summary: each step with its CV performance
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "Panel for {} from the top {} candidates (n = {}, {}-fold CV; baseline RMSE {:.3}):",
+            self.target, self.candidates, self.n, self.folds, self.baseline_rmse
+        );
+        if self.steps.is_empty() {
+            text.push_str("\nNo marker improved on predicting the mean.");
+        }
+        for step in &self.steps {
+            text.push_str(&format!(
+                "\n{:>2}. + {} (RMSE {:.3}, MAE {:.3}, R² {:.3})",
+                step.size, step.label, step.cv_rmse, step.cv_mae, step.cv_r2
+            ));
+        }
+        text
+    }
+}
+
+/// Select a panel predicting `target` from the top candidates of
+/// `analysis`. Probe-level datasets are read from the collapsed gene matrix
+/// the analysis wrote to `output_dir`, datasets with technical replicates
+/// from the replicate-collapsed file, imputed datasets from the imputed
+/// file, and batch-corrected datasets from the corrected file.
+pub fn optimize(
+    record: &DatasetRecord,
+    output_dir: &Path,
+    analysis: &AnalysisArtifacts,
+    target: &str,
+    options: &PanelOptions,
+) -> Result<Panel> {
+    let derived = analysis
+        .batch
+        .as_ref()
+        .map(|batch| batch.path.clone())
+        .or_else(|| analysis.imputation.as_ref().map(|imputation| imputation.path.clone()))
+        .or_else(|| analysis.replicates.as_ref().map(|replicates| replicates.path.clone()))
+        .or_else(|| analysis.probe_collapse.as_ref().map(|_| output_dir.join(COLLAPSED_FILE)));
+    let (path, delimiter, has_headers, parsing) = match derived {
+        Some(path) => (path, b',', true, record.parsing.derived()),
+        None => (PathBuf::from(&record.local_path), record.delimiter, record.has_headers, record.parsing.clone()),
+    };
+    let candidates: Vec<_> = analysis
+        .biomarker_candidates
+        .iter()
+        .filter(|c| c.column != target)
+        .take(options.pool)
+        .collect();
+    if candidates.is_empty() {
+        bail!("No biomarker candidates to build a panel from");
+    }
+    let columns: Vec<&str> = candidates.iter().map(|c| c.column.as_str()).collect();
+    let (x, y) = read_matrix(&path, delimiter, has_headers, &parsing, target, &columns)?;
+    let min_samples = 2 * options.folds.max(2);
+    if y.len() < min_samples {
+        bail!(
+            "Panel selection needs at least {} samples with a {} value, found {}",
+            min_samples,
+            target,
+            y.len()
+        );
+    }
+
+    let (baseline_rmse, added) = forward_select(&x, &y, options);
+    let selected: Vec<usize> = added.iter().map(|(col, _)| *col).collect();
+    let steps = added
+        .iter()
+        .enumerate()
+        .map(|(i, (col, cv))| PanelStep {
+            size: i + 1,
+            column: columns[*col].to_string(),
+            label: candidates[*col].display_label(),
+            cv_rmse: cv.rmse,
+            cv_mae: cv.mae,
+            cv_r2: cv.r2,
+        })
+        .collect();
+
+    let rows: Vec<usize> = (0..y.len()).collect();
+    let (intercept, coefficients) = fit(&x, &y, &selected, &rows).unwrap_or((0.0, Vec::new()));
+    let markers = selected
+        .iter()
+        .zip(coefficients)
+        .map(|(&col, coefficient)| PanelMarker {
+            column: columns[col].to_string(),
+            label: candidates[col].display_label(),
+            coefficient,
+        })
+        .collect();
+    Ok(Panel {
+        target: target.to_string(),
+        n: y.len(),
+        folds: options.folds.max(2),
+        candidates: columns.len(),
+        baseline_rmse,
+        steps,
+        intercept,
+        markers,
+    })
+}
+
+/// Baseline CV RMSE and the columns of `x` added in order, each with the
+/// CV score of the panel up to it
+fn forward_select(x: &[Vec<f64>], y: &[f64], options: &PanelOptions) -> (f64, Vec<(usize, CvScore)>) {
+    let columns = x.first().map_or(0, Vec::len);
+    let folds = fold_assignment(y.len(), options.folds.max(2), options.seed);
+    let baseline_rmse = cross_validate(x, y, &[], &folds).map_or(f64::NAN, |cv| cv.rmse);
+    let mut selected: Vec<usize> = Vec::new();
+    let mut path = Vec::new();
+    let mut current = baseline_rmse;
+    while selected.len() < options.max_size.min(columns) {
+        let best = (0..columns)
+            .filter(|col| !selected.contains(col))
+            .filter_map(|col| {
+                let mut panel = selected.clone();
+                panel.push(col);
+                Some((col, cross_validate(x, y, &panel, &folds)?))
+            })
+            .min_by(|a, b| a.1.rmse.total_cmp(&b.1.rmse));
+        let Some((col, cv)) = best else { break };
+        // False against a NaN baseline too
+        let improves = cv.rmse < current * (1.0 - options.min_gain);
+        if !improves {
+            break;
+        }
+        selected.push(col);
+        current = cv.rmse;
+        path.push((col, cv));
+    }
+    (baseline_rmse, path)
+}
+
+/// `panel.csv` (one row per step) and `panel.json` (the full result)
+pub fn write_panel(output_dir: &Path, panel: &Panel) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(output_dir).with_context(|| format!("Failed to create {}", output_dir.display()))?;
+    let csv_path = output_dir.join(PANEL_CSV);
+    let mut wtr = csv::Writer::from_path(&csv_path)?;
+    wtr.write_record(["size", "column", "label", "coefficient", "cv_rmse", "cv_mae", "cv_r2"])?;
+    for step in &panel.steps {
+        let coefficient = panel
+            .markers
+            .iter()
+            .find(|m| m.column == step.column)
+            .map(|m| m.coefficient.to_string())
+            .unwrap_or_default();
+        wtr.write_record([
+            step.size.to_string(),
+            step.column.clone(),
+            step.label.clone(),
+            coefficient,
+            step.cv_rmse.to_string(),
+            step.cv_mae.to_string(),
+            step.cv_r2.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    let json_path = output_dir.join(PANEL_JSON);
+    std::fs::write(&json_path, serde_json::to_string_pretty(panel)?)?;
+    Ok(vec![csv_path, json_path])
+}
+
+/// Marker values (rows × `columns`) and targets of the rows with a target
+/// value. A missing marker value is filled with the marker's mean.
+fn read_matrix(
+    path: &Path,
+    delimiter: u8,
+    has_headers: bool,
+    parsing: &ParseOptions,
+    target: &str,
+    columns: &[&str],
+) -> Result<(Vec<Vec<f64>>, Vec<f64>)> {
+    let mut rdr = parsing
+        .reader_builder(delimiter)
+        .has_headers(has_headers)
+        .from_path(path)
+        .with_context(|| format!("Failed to open dataset {}", path.display()))?;
+    let headers: HashMap<String, usize> = if has_headers {
+        rdr.headers()?.iter().enumerate().map(|(i, h)| (h.to_string(), i)).collect()
+    } else {
+        HashMap::new()
+    };
+    // Headerless files name their columns column_1, column_2, ...
+    let index = |name: &str| {
+        headers
+            .get(name)
+            .copied()
+            .or_else(|| name.strip_prefix("column_")?.parse::<usize>().ok()?.checked_sub(1))
+    };
+    let target_index = index(target).with_context(|| format!("Target column {} not found", target))?;
+    let indices: Vec<Option<usize>> = columns.iter().map(|c| index(c)).collect();
+
+    let mut x: Vec<Vec<Option<f64>>> = Vec::new();
+    let mut y = Vec::new();
+    for row in rdr.records() {
+        let row = row?;
+        let value = |idx: usize| row.get(idx).and_then(|v| parsing.number(v));
+        let Some(target_value) = value(target_index) else { continue };
+        y.push(target_value);
+        x.push(indices.iter().map(|idx| idx.and_then(value)).collect());
+    }
+
+    let means: Vec<f64> = (0..columns.len())
+        .map(|col| {
+            let values: Vec<f64> = x.iter().filter_map(|row| row[col]).collect();
+            if values.is_empty() {
+                0.0
+            } else {
+                values.iter().sum::<f64>() / values.len() as f64
+            }
+        })
+        .collect();
+    let x = x
+        .into_iter()
+        .map(|row| row.into_iter().zip(&means).map(|(v, mean)| v.unwrap_or(*mean)).collect())
+        .collect();
+    Ok((x, y))
+}
+
+/// Fold of each of `n` samples, balanced and shuffled with `seed`
+pub(super) fn fold_assignment(n: usize, folds: usize, seed: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..n).collect();
+    order.shuffle(&mut StdRng::seed_from_u64(seed));
+    let mut assignment = vec![0; n];
+    for (pos, sample) in order.into_iter().enumerate() {
+        assignment[sample] = pos % folds;
+    }
+    assignment
+}
+
+/// OLS of `y` on the `panel` columns of `x` over `rows`: intercept and
+/// coefficients, `None` when the system is singular
+fn fit(x: &[Vec<f64>], y: &[f64], panel: &[usize], rows: &[usize]) -> Option<(f64, Vec<f64>)> {
+    let p = panel.len() + 1;
+    if rows.len() <= p {
+        return None;
+    }
+    let mut data = Vec::with_capacity(rows.len() * p);
+    for &row in rows {
+        data.push(1.0);
+        data.extend(panel.iter().map(|&col| x[row][col]));
+    }
+    let design = DMatrix::from_row_slice(rows.len(), p, &data);
+    let targets = DVector::from_iterator(rows.len(), rows.iter().map(|&row| y[row]));
+    let (intercept, coefficients, _) = ols_fit(&design, &targets)?;
+    Some((intercept, coefficients))
+}
+
+struct CvScore {
+    rmse: f64,
+    mae: f64,
+    r2: f64,
+}
+
+/// Out-of-fold errors of the OLS model on the `panel` columns; an empty
+/// panel predicts the training mean
+fn cross_validate(x: &[Vec<f64>], y: &[f64], panel: &[usize], folds: &[usize]) -> Option<CvScore> {
+    let k = folds.iter().max()? + 1;
+    let mut squared = 0.0;
+    let mut absolute = 0.0;
+    for fold in 0..k {
+        let (test, train): (Vec<usize>, Vec<usize>) = (0..y.len()).partition(|&row| folds[row] == fold);
+        let (intercept, coefficients) = fit(x, y, panel, &train)?;
+        for row in test {
+            let predicted = intercept + panel.iter().zip(&coefficients).map(|(&col, b)| b * x[row][col]).sum::<f64>();
+            let error = y[row] - predicted;
+            squared += error * error;
+            absolute += error.abs();
+        }
+    }
+    let n = y.len() as f64;
+    let mean = y.iter().sum::<f64>() / n;
+    let total = y.iter().map(|v| (v - mean).powi(2)).sum::<f64>();
+    Some(CvScore {
+        rmse: (squared / n).sqrt(),
+        mae: absolute / n,
+        r2: if total > 0.0 { 1.0 - squared / total } else { 0.0 },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forward_selection_finds_planted_markers() {
+        // age = 2 a - b + small noise; c and d are noise
+        let x: Vec<Vec<f64>> = (0..60)
+            .map(|i| {
+                let i = i as f64;
+                vec![(i * 0.37).sin() * 5.0, (i * 0.91).cos() * 3.0, (i * 1.7).sin(), (i * 2.3).cos()]
+            })
+            .collect();
+        let y: Vec<f64> = x
+            .iter()
+            .enumerate()
+            .map(|(i, row)| 50.0 + 2.0 * row[0] - row[1] + 0.05 * (i as f64 * 3.1).sin())
+            .collect();
+        let folds = fold_assignment(y.len(), 5, 42);
+        assert_eq!((0..5).map(|f| folds.iter().filter(|&&x| x == f).count()).collect::<Vec<_>>(), vec![12; 5]);
+
+        let baseline = cross_validate(&x, &y, &[], &folds).unwrap();
+        let both = cross_validate(&x, &y, &[0, 1], &folds).unwrap();
+        assert!(both.rmse < 0.1 && baseline.rmse > 3.0);
+        assert!(both.r2 > 0.99);
+        let (intercept, coefficients) = fit(&x, &y, &[0, 1], &(0..60).collect::<Vec<_>>()).unwrap();
+        assert!((intercept - 50.0).abs() < 0.05);
+        assert!((coefficients[0] - 2.0).abs() < 0.01 && (coefficients[1] + 1.0).abs() < 0.01);
+
+        // The noise markers do not earn a place
+        let options = PanelOptions { min_gain: 0.05, ..PanelOptions::default() };
+        let (_, path) = forward_select(&x, &y, &options);
+        assert_eq!(path.iter().map(|(col, _)| *col).collect::<Vec<_>>(), vec![0, 1]);
+        let (_, path) = forward_select(&x, &y, &PanelOptions { max_size: 1, ..options });
+        assert_eq!(path.len(), 1);
+    }
+}
@@ -0,0 +1,413 @@
+//! Principal component analysis
+//!
+//! Sample structure at a glance: the analysed markers are scaled to
+//! z-scores (missing values imputed with the marker mean, i.e. zero) and the
+//! samples × markers matrix is decomposed with a singular value
+//! decomposition. The run writes the loadings and explained variance of the
+//! first components, a scree plot and a PC1/PC2 score plot coloured by group,
+//! and reports how much of each of the first two components the grouping
+//! explains.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use nalgebra::DMatrix;
+use plotters::prelude::*;
+use serde::Serialize;
+
+use super::qc;
+use super::sex::MISSING;
+use crate::data_registry::DatasetRecord;
+use crate::utils::cancel::{self, CancellationToken};
+
+/// File names of the plots in the analysis directory
+pub const SCREE_FILE: &str = "pca_scree.png";
+pub const SCORES_FILE: &str = "pca_scores.png";
+
+/// Components kept in the tables and the scree plot
+pub const MAX_COMPONENTS: usize = 10;
+
+/// Groups given their own colour in the score plot; the rest are grey
+const LEGEND_GROUPS: usize = 10;
+
+/// Label of samples with no group value
+const NO_GROUP: &str = "NA";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Component {
+    /// 1-based
+    pub index: usize,
+    /// Variance of the component's scores (eigenvalue of the correlation
+    /// matrix)
+    pub variance: f64,
+    /// Share of the total variance
+    pub ratio: f64,
+    /// Share explained by this and the earlier components
+    pub cumulative: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Pca {
+    /// Marker columns in the decomposition
+    pub markers: Vec<String>,
+    pub samples: usize,
+    /// Leading components, largest variance first
+    pub components: Vec<Component>,
+    /// Per marker, its loading on each of `components`
+    pub loadings: Vec<Vec<f64>>,
+    /// PC1 and PC2 score of each sample
+    #[serde(skip)]
+    pub scores: Vec<[f64; 2]>,
+    /// Group of each sample, when grouped
+    #[serde(skip)]
+    pub groups: Option<Vec<String>>,
+    /// Group column and the share of PC1 and PC2 variance it explains (η²),
+    /// when grouped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_eta_squared: Option<(String, [f64; 2])>,
+}
+
+impl Pca {
+    /// Components needed to explain at least `share` of the variance
+    pub fn components_for(&self, share: f64) -> Option<usize> {
+        self.components.iter().find(|c| c.cumulative >= share).map(|c| c.index)
+    }
+
+    /// Samples per group, largest group first, when grouped
+    pub fn group_counts(&self) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for group in self.groups.iter().flatten() {
+            match counts.iter_mut().find(|(g, _)| g == group) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((group, 1)),
+            }
+        }
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        counts
+    }
+
+    pub fn methods(&self) -> String {
+        format!(
+            "Sample structure was summarized by principal component analysis of {} markers across {} samples: \
+             each marker was scaled to zero mean and unit variance, missing values were set to the marker mean, \
+             and components were obtained by singular value decomposition.",
+            self.markers.len(),
+            self.samples
+        )
+    }
+
+    pub fn results(&self) -> String {
+        let share = |i: usize| self.components.get(i).map_or(0.0, |c| c.ratio * 100.0);
+        let mut text = format!(
+            "The first two principal components explained {:.1}% and {:.1}% of the variance across {} samples",
+            share(0),
+            share(1),
+            self.samples
+        );
+        match self.components_for(0.8) {
+            Some(k) => text.push_str(&format!("; {} components explained 80%", k)),
+            None => text.push_str(&format!(
+                "; the first {} components explained {:.1}%",
+                self.components.len(),
+                self.components.last().map_or(0.0, |c| c.cumulative * 100.0)
+            )),
+        }
+        if let Some((group, [pc1, pc2])) = &self.group_eta_squared {
+            text.push_str(&format!(
+                ". {} accounted for {:.1}% of the PC1 and {:.1}% of the PC2 score variance (η²)",
+                group,
+                pc1 * 100.0,
+                pc2 * 100.0
+            ));
+        }
+        text.push('.');
+        text
+    }
+}
+
+/// Samples × markers matrix of z-scores, missing values set to zero (the
+/// marker mean), shared by the PCA and the k-means clustering
+#[derive(Debug, Clone)]
+pub struct Scaled {
+    pub markers: Vec<String>,
+    /// Sample ID of each row, or "row N" without an ID column
+    pub samples: Vec<String>,
+    /// Group column and the group of each row, when grouped
+    pub groups: Option<(String, Vec<String>)>,
+    pub values: DMatrix<f64>,
+}
+
+/// [`Scaled`] of `columns` (marker column indices) over the rows of
+/// `record`, with samples labelled by `group` (column index and name).
+/// Samples missing more than half of the markers and markers without
+/// variance are left out; `None` when fewer than two markers or three
+/// samples remain.
+pub fn scaled(
+    record: &DatasetRecord,
+    headers: &[String],
+    columns: &[usize],
+    group: Option<(usize, &str)>,
+    cancel: &CancellationToken,
+) -> Result<Option<Scaled>> {
+    let id_index = qc::sample_column(record);
+    let mut rdr = record.reader()?;
+    let mut rows: Vec<(String, String, Vec<Option<f64>>)> = Vec::new();
+    for (i, row) in rdr.records().enumerate() {
+        if i % super::CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let row = row?;
+        let values: Vec<Option<f64>> = columns.iter().map(|idx| row.get(*idx).and_then(|v| record.number(v))).collect();
+        if values.iter().filter(|v| v.is_some()).count() * 2 < values.len() || values.iter().all(Option::is_none) {
+            continue;
+        }
+        let sample = id_index
+            .and_then(|idx| row.get(idx))
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map_or_else(|| format!("row {}", i + 1), str::to_string);
+        let label = group
+            .and_then(|(idx, _)| row.get(idx))
+            .map(str::trim)
+            .filter(|g| !MISSING.contains(&g.to_ascii_lowercase().as_str()))
+            .unwrap_or(NO_GROUP);
+        rows.push((sample, label.to_string(), values));
+    }
+
+    // Mean and standard deviation of each marker over the kept samples
+    let mut kept: Vec<(usize, f64, f64)> = Vec::new();
+    for pos in 0..columns.len() {
+        let present: Vec<f64> = rows.iter().filter_map(|(_, _, values)| values[pos]).collect();
+        if present.len() < 2 {
+            continue;
+        }
+        let n = present.len() as f64;
+        let mean = present.iter().sum::<f64>() / n;
+        let sd = (present.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+        if sd > 0.0 {
+            kept.push((pos, mean, sd));
+        }
+    }
+    let (n, p) = (rows.len(), kept.len());
+    if p < 2 || n < 3 {
+        return Ok(None);
+    }
+    let values = DMatrix::from_fn(n, p, |i, j| {
+        let (pos, mean, sd) = kept[j];
+        rows[i].2[pos].map_or(0.0, |v| (v - mean) / sd)
+    });
+    let (samples, labels): (Vec<String>, Vec<String>) = rows.into_iter().map(|(sample, label, _)| (sample, label)).unzip();
+    Ok(Some(Scaled {
+        markers: kept.iter().map(|(pos, _, _)| headers[columns[*pos]].clone()).collect(),
+        samples,
+        groups: group.map(|(_, name)| (name.to_string(), labels)),
+        values,
+    }))
+}
+
+/// PCA of the scaled markers, by singular value decomposition
+pub fn analyze(scaled: &Scaled) -> Option<Pca> {
+    let (n, p) = scaled.values.shape();
+    let svd = scaled.values.clone().svd(true, true);
+    let (Some(u), Some(v_t)) = (svd.u, svd.v_t) else {
+        return None;
+    };
+    let mut order: Vec<usize> = (0..svd.singular_values.len()).collect();
+    order.sort_by(|a, b| svd.singular_values[*b].total_cmp(&svd.singular_values[*a]));
+    let total: f64 = svd.singular_values.iter().map(|s| s * s).sum();
+    let k = order.len().min(n - 1).min(MAX_COMPONENTS);
+
+    let mut cumulative = 0.0;
+    let components: Vec<Component> = order
+        .iter()
+        .take(k)
+        .enumerate()
+        .map(|(i, &c)| {
+            let s2 = svd.singular_values[c].powi(2);
+            let ratio = if total > 0.0 { s2 / total } else { 0.0 };
+            cumulative += ratio;
+            Component {
+                index: i + 1,
+                variance: s2 / (n - 1) as f64,
+                ratio,
+                cumulative,
+            }
+        })
+        .collect();
+    let loadings: Vec<Vec<f64>> = (0..p).map(|j| order.iter().take(k).map(|&c| v_t[(c, j)]).collect()).collect();
+    let score = |i: usize, c: Option<&usize>| c.map_or(0.0, |&c| u[(i, c)] * svd.singular_values[c]);
+    let scores: Vec<[f64; 2]> = (0..n).map(|i| [score(i, order.first()), score(i, order.get(1))]).collect();
+
+    let group_eta_squared = scaled.groups.as_ref().map(|(name, labels)| {
+        let eta = |pc: usize| {
+            let values: Vec<(&str, f64)> = labels.iter().zip(&scores).map(|(l, s)| (l.as_str(), s[pc])).collect();
+            eta_squared(&values)
+        };
+        (name.clone(), [eta(0), eta(1)])
+    });
+    Some(Pca {
+        markers: scaled.markers.clone(),
+        samples: n,
+        components,
+        loadings,
+        scores,
+        groups: scaled.groups.as_ref().map(|(_, labels)| labels.clone()),
+        group_eta_squared,
+    })
+}
+
+/// Share of the variance of the values between their labels, ignoring
+/// unlabelled samples
+fn eta_squared(values: &[(&str, f64)]) -> f64 {
+    let labelled: Vec<&(&str, f64)> = values.iter().filter(|(label, _)| *label != NO_GROUP).collect();
+    let n = labelled.len() as f64;
+    let mean = labelled.iter().map(|(_, v)| v).sum::<f64>() / n;
+    let total: f64 = labelled.iter().map(|(_, v)| (v - mean).powi(2)).sum();
+    let mut groups: HashMap<&str, (f64, usize)> = HashMap::new();
+    for (label, value) in &labelled {
+        let entry = groups.entry(label).or_default();
+        entry.0 += value;
+        entry.1 += 1;
+    }
+    let between: f64 = groups.values().map(|(sum, count)| *count as f64 * (sum / *count as f64 - mean).powi(2)).sum();
+    if total > 0.0 {
+        between / total
+    } else {
+        0.0
+    }
+}
+
+/// Bars of each component's share of the variance with the cumulative share
+/// as a line
+pub fn write_scree_plot(output_path: &Path, pca: &Pca) -> Result<()> {
+    let k = pca.components.len();
+    let root = BitMapBackend::new(output_path, (800, 500)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .margin(20)
+        .caption("Scree plot", ("sans-serif", 24))
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0.5f64..k as f64 + 0.5, 0f64..100f64)?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(k)
+        .x_label_formatter(&|x| format!("PC{}", x.round() as usize))
+        .x_desc("Component")
+        .y_desc("Variance explained (%)")
+        .draw()?;
+    chart.draw_series(pca.components.iter().map(|c| {
+        let x = c.index as f64;
+        Rectangle::new([(x - 0.35, 0.0), (x + 0.35, c.ratio * 100.0)], BLUE.mix(0.7).filled())
+    }))?;
+    let cumulative: Vec<(f64, f64)> = pca.components.iter().map(|c| (c.index as f64, c.cumulative * 100.0)).collect();
+    chart.draw_series(LineSeries::new(cumulative.clone(), RED.stroke_width(2)))?;
+    chart.draw_series(cumulative.into_iter().map(|p| Circle::new(p, 4, RED.filled())))?;
+    root.present()?;
+    Ok(())
+}
+
+/// PC1 against PC2 scores, one colour per group
+pub fn write_score_plot(output_path: &Path, pca: &Pca) -> Result<()> {
+    let axis = |pc: usize| {
+        let values = pca.scores.iter().map(|s| s[pc]);
+        let (min, max) = values.fold((0f64, 0f64), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        let pad = ((max - min) * 0.08).max(1e-6);
+        (min - pad)..(max + pad)
+    };
+    let share = |i: usize| pca.components.get(i).map_or(0.0, |c| c.ratio * 100.0);
+    let root = BitMapBackend::new(output_path, (800, 700)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .margin(20)
+        .caption("Principal component scores", ("sans-serif", 24))
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(axis(0), axis(1))?;
+    chart
+        .configure_mesh()
+        .x_desc(format!("PC1 ({:.1}%)", share(0)))
+        .y_desc(format!("PC2 ({:.1}%)", share(1)))
+        .draw()?;
+
+    let Some(groups) = &pca.groups else {
+        chart.draw_series(pca.scores.iter().map(|s| Circle::new((s[0], s[1]), 4, BLUE.mix(0.7).filled())))?;
+        root.present()?;
+        return Ok(());
+    };
+    // Largest groups first; the rest share grey
+    for (i, (group, n)) in pca.group_counts().into_iter().enumerate() {
+        let colour = if i < LEGEND_GROUPS && group != NO_GROUP {
+            Palette99::pick(i).to_rgba()
+        } else {
+            RGBColor(160, 160, 160).to_rgba()
+        };
+        let points = pca
+            .scores
+            .iter()
+            .zip(groups)
+            .filter(|(_, g)| *g == group)
+            .map(|(s, _)| Circle::new((s[0], s[1]), 4, colour.mix(0.8).filled()));
+        let series = chart.draw_series(points)?;
+        if i < LEGEND_GROUPS {
+            series
+                .label(format!("{} (n = {})", group, n))
+                .legend(move |(x, y)| Circle::new((x + 8, y), 4, colour.filled()));
+        }
+    }
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pca_components_and_group_structure() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        // gene_a and gene_b both follow the group; gene_c is independent noise
+        let mut csv = String::from("sample,tissue,gene_a,gene_b,gene_c,flat\n");
+        let noise = [0.3, -0.2, 0.1, -0.4, 0.2, 0.0, -0.1, 0.4];
+        for (i, c) in noise.iter().enumerate() {
+            let (tissue, level) = if i < 4 { ("liver", 0.0) } else { ("brain", 5.0) };
+            csv.push_str(&format!("S{},{},{},{},{},1\n", i, tissue, level + c, 2.0 * level - c, c * 3.0));
+        }
+        csv.push_str("S9,liver,NA,NA,1.0,NA\n");
+        std::fs::write(&input, csv).unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+        let headers: Vec<String> = record.columns.clone();
+
+        let scaled = scaled(&record, &headers, &[2, 3, 4, 5], Some((1, "tissue")), &CancellationToken::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(scaled.samples[..2], ["S0", "S1"]);
+        let pca = analyze(&scaled).unwrap();
+        // The constant marker and the mostly-missing sample are left out
+        assert_eq!((pca.markers.len(), pca.samples), (3, 8));
+        assert_eq!(pca.components.len(), 3);
+        let ratios: f64 = pca.components.iter().map(|c| c.ratio).sum();
+        assert!((ratios - 1.0).abs() < 1e-9 && (pca.components[2].cumulative - 1.0).abs() < 1e-9);
+        assert!(pca.components[0].ratio > pca.components[1].ratio);
+        // Eigenvalues of a correlation matrix sum to the number of markers
+        let variances: f64 = pca.components.iter().map(|c| c.variance).sum();
+        assert!((variances - 3.0).abs() < 1e-9);
+        let (_, [pc1, _]) = pca.group_eta_squared.clone().unwrap();
+        assert!(pc1 > 0.9, "{}", pc1);
+        assert!(pca.results().starts_with("The first two principal components explained"));
+        assert!(pca.results().contains("tissue accounted for"));
+
+        write_scree_plot(&dir.path().join(SCREE_FILE), &pca).unwrap();
+        write_score_plot(&dir.path().join(SCORES_FILE), &pca).unwrap();
+        assert!(dir.path().join(SCORES_FILE).is_file());
+        assert!(super::scaled(&record, &headers, &[2, 5], None, &CancellationToken::new()).unwrap().is_none());
+    }
+}
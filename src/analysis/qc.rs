@@ -0,0 +1,470 @@
+//! Sample quality control and technical replicates
+//!
+//! Every sample (row) gets its missing-value rate, median intensity and
+//! interquartile range over the marker columns. Samples missing more than
+//! [`MAX_MISSING`] of their values, or whose median or IQR lies more than
+//! [`MAX_ROBUST_Z`] robust z-units (scaled median absolute deviation) from
+//! the cohort, are flagged; they stay in the analysis and are listed for
+//! review.
+//!
+//! Technical replicates are rows of the same sample: rows sharing a sample
+//! ID when the dataset has a `replicate` column, or rows whose IDs differ
+//! only by a replicate suffix (`S1_rep1`, `S1_rep2`, `S1-r3`, ...). They are
+//! collapsed to one row per sample by their mean or median before the
+//! analysis, like probes are collapsed to genes.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::data_registry::{ColumnKind, DatasetRecord};
+
+/// Replicate-collapsed dataset written when replicates are found
+pub const REPLICATES_FILE: &str = "replicates_collapsed.csv";
+
+/// Share of missing marker values above which a sample is flagged
+pub const MAX_MISSING: f64 = 0.2;
+
+/// Robust z-score of the median or IQR above which a sample is flagged
+pub const MAX_ROBUST_Z: f64 = 3.5;
+
+/// Fewest marker columns for per-sample metrics to mean anything
+const MIN_MARKERS: usize = 3;
+
+/// Flagged samples listed in the QC table and the Results
+const LISTED: usize = 20;
+
+/// Headers of an explicit technical replicate column
+const REPLICATE_COLUMNS: [&str; 4] = ["replicate", "rep", "technical_replicate", "replicate_id"];
+
+/// Headers taken as the sample ID before any other identifier column
+const SAMPLE_COLUMNS: [&str; 5] = ["sample", "sample_id", "sample_name", "sample id", "id"];
+
+/// Values read as missing
+const MISSING: [&str; 7] = ["", "na", "n/a", "nan", "null", "none", "."];
+
+/// How technical replicates of one sample are combined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Replicates {
+    /// Analyse replicate rows as separate samples
+    Keep,
+    /// Average the replicates of each sample
+    #[default]
+    Mean,
+    /// Take the median of the replicates of each sample
+    Median,
+}
+
+impl Replicates {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Keep => "keep",
+            Self::Mean => "mean",
+            Self::Median => "median",
+        }
+    }
+
+    fn combine(self, values: &mut [f64]) -> f64 {
+        match self {
+            Self::Median => median(values),
+            Self::Keep | Self::Mean => values.iter().sum::<f64>() / values.len() as f64,
+        }
+    }
+}
+
+impl fmt::Display for Replicates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Replicates {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "keep" => Ok(Self::Keep),
+            "mean" => Ok(Self::Mean),
+            "median" => Ok(Self::Median),
+            other => Err(format!("unknown replicate strategy '{}' (expected keep, mean or median)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicateSummary {
+    pub strategy: Replicates,
+    /// Sample ID column the replicates were grouped by
+    pub sample_column: String,
+    /// The explicit replicate column, when replicates were not found by ID
+    /// suffix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replicate_column: Option<String>,
+    /// Rows before collapsing
+    pub rows: usize,
+    /// Rows after collapsing, one per sample
+    pub samples: usize,
+    /// Samples that had two or more replicate rows
+    pub replicated: usize,
+    /// Collapsed dataset
+    pub path: PathBuf,
+}
+
+impl ReplicateSummary {
+    /// One sentence for the manuscript's Methods section
+    pub fn methods(&self) -> String {
+        let found_by = match &self.replicate_column {
+            Some(column) => format!("the {} column", column),
+            None => format!("replicate suffixes of {}", self.sample_column),
+        };
+        format!(
+            "Technical replicates, identified by {}, were collapsed to one profile per sample by their {} \
+             ({} rows to {} samples, {} with replicates).",
+            found_by, self.strategy, self.rows, self.samples, self.replicated
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleMetrics {
+    pub sample: String,
+    /// 1-based data row in the dataset before replicate collapsing
+    pub row: usize,
+    pub missing_rate: f64,
+    /// `None` when the sample has no marker values
+    pub median: Option<f64>,
+    pub iqr: Option<f64>,
+    /// Why the sample was flagged; empty when it passed
+    pub flags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleQc {
+    /// Marker columns the metrics were computed over
+    pub markers: usize,
+    pub samples: Vec<SampleMetrics>,
+}
+
+impl SampleQc {
+    pub fn flagged(&self) -> impl Iterator<Item = &SampleMetrics> {
+        self.samples.iter().filter(|s| !s.flags.is_empty())
+    }
+
+    pub fn methods(&self) -> String {
+        format!(
+            "Per-sample quality control computed the missing-value rate, median intensity and interquartile range \
+             (IQR) over the {} marker columns; samples missing more than {:.0}% of values, or with a median or IQR \
+             more than {} robust z-units (scaled median absolute deviation) from the cohort, were flagged for review \
+             and retained.",
+            self.markers,
+            MAX_MISSING * 100.0,
+            MAX_ROBUST_Z
+        )
+    }
+
+    pub fn results(&self) -> String {
+        let flagged: Vec<&SampleMetrics> = self.flagged().collect();
+        if flagged.is_empty() {
+            return format!("No sample was flagged in quality control (n = {}).", self.samples.len());
+        }
+        let listed = flagged
+            .iter()
+            .take(LISTED)
+            .map(|s| format!("{} ({})", s.sample, s.flags.join(", ")))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let more = flagged.len().saturating_sub(LISTED);
+        format!(
+            "{} of {} samples were flagged in quality control: {}{}.",
+            flagged.len(),
+            self.samples.len(),
+            listed,
+            if more > 0 { format!("; and {} more", more) } else { String::new() }
+        )
+    }
+
+    /// QC section of the chat output: counts plus a table of the flagged
+    /// samples
+    pub fn to_markdown(&self) -> String {
+        let flagged: Vec<&SampleMetrics> = self.flagged().collect();
+        let mut out = format!(
+            "### Sample QC\n{} samples over {} marker columns; {} flagged.\n",
+            self.samples.len(),
+            self.markers,
+            flagged.len()
+        );
+        if flagged.is_empty() {
+            return out;
+        }
+        let number = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:.3}", v));
+        out.push_str("\n| Sample | Row | Missing | Median | IQR | Flags |\n|---|---|---|---|---|---|\n");
+        for sample in flagged.iter().take(LISTED) {
+            out.push_str(&format!(
+                "| {} | {} | {:.1}% | {} | {} | {} |\n",
+                sample.sample,
+                sample.row,
+                sample.missing_rate * 100.0,
+                number(sample.median),
+                number(sample.iqr),
+                sample.flags.join(", ")
+            ));
+        }
+        out
+    }
+}
+
+fn is_missing(value: &str) -> bool {
+    MISSING.iter().any(|m| value.eq_ignore_ascii_case(m))
+}
+
+/// Median of `values`, which it sorts
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let n = values.len();
+    if n.is_multiple_of(2) {
+        (values[n / 2 - 1] + values[n / 2]) / 2.0
+    } else {
+        values[n / 2]
+    }
+}
+
+/// Linear-interpolated quantile of sorted `values`
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// Robust z-scores of `values` around their median, `None` when their
+/// median absolute deviation is zero
+fn robust_z(values: &[f64]) -> Option<Vec<f64>> {
+    let center = median(&mut values.to_vec());
+    let mad = median(&mut values.iter().map(|v| (v - center).abs()).collect::<Vec<_>>()) * 1.4826;
+    (mad > 0.0).then(|| values.iter().map(|v| (v - center) / mad).collect())
+}
+
+/// Index of the sample ID column
+pub(super) fn sample_column(record: &DatasetRecord) -> Option<usize> {
+    record
+        .columns
+        .iter()
+        .position(|h| SAMPLE_COLUMNS.contains(&h.trim().to_ascii_lowercase().as_str()))
+        .or_else(|| (0..record.columns.len()).find(|idx| record.column_kind(*idx) == Some(ColumnKind::Identifier)))
+}
+
+/// `id` without a technical replicate suffix (`_rep2`, `-r1`, `.tr3`,
+/// `_replicate2`), or `None` when it has none
+fn strip_replicate_suffix(id: &str) -> Option<&str> {
+    let split = id.rfind(['_', '-', '.', ' '])?;
+    let (base, suffix) = (&id[..split], id[split + 1..].to_ascii_lowercase());
+    let digits = ["replicate", "rep", "tr", "r"]
+        .iter()
+        .find_map(|prefix| suffix.strip_prefix(prefix))?;
+    (!base.is_empty() && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())).then_some(base)
+}
+
+fn read_rows(record: &DatasetRecord) -> Result<Vec<csv::StringRecord>> {
+    let mut rdr = record.reader()?;
+    Ok(rdr.records().collect::<Result<_, _>>()?)
+}
+
+/// Per-sample metrics over the numeric columns of `record` not named in
+/// `exclude` (target, covariates, ...); `None` when fewer than three marker
+/// columns remain
+pub fn sample_metrics(record: &DatasetRecord, exclude: &[&str]) -> Result<Option<SampleQc>> {
+    let markers: Vec<usize> = (0..record.columns.len())
+        .filter(|idx| record.column_kind(*idx).is_none_or(|kind| kind == ColumnKind::Numeric))
+        .filter(|idx| !exclude.contains(&record.columns[*idx].as_str()))
+        .filter(|idx| !REPLICATE_COLUMNS.contains(&record.columns[*idx].trim().to_ascii_lowercase().as_str()))
+        .collect();
+    if markers.len() < MIN_MARKERS {
+        return Ok(None);
+    }
+    let id_index = sample_column(record);
+
+    let mut samples: Vec<SampleMetrics> = read_rows(record)?
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut values: Vec<f64> = markers
+                .iter()
+                .filter_map(|idx| row.get(*idx).map(str::trim).filter(|v| !is_missing(v)))
+                .filter_map(|v| record.number(v))
+                .collect();
+            values.sort_by(f64::total_cmp);
+            let missing_rate = 1.0 - values.len() as f64 / markers.len() as f64;
+            let mut flags = Vec::new();
+            if missing_rate > MAX_MISSING {
+                flags.push(format!("missing {:.0}%", missing_rate * 100.0));
+            }
+            SampleMetrics {
+                sample: id_index
+                    .and_then(|idx| row.get(idx))
+                    .map(str::trim)
+                    .filter(|id| !id.is_empty())
+                    .map_or_else(|| format!("row {}", i + 1), str::to_string),
+                row: i + 1,
+                missing_rate,
+                median: (!values.is_empty()).then(|| quantile(&values, 0.5)),
+                iqr: (!values.is_empty()).then(|| quantile(&values, 0.75) - quantile(&values, 0.25)),
+                flags,
+            }
+        })
+        .collect();
+
+    let measured: Vec<usize> = (0..samples.len()).filter(|i| samples[*i].median.is_some()).collect();
+    if measured.len() >= 3 {
+        for (metric, label) in [(0, "median"), (1, "IQR")] {
+            let values: Vec<f64> = measured
+                .iter()
+                .map(|i| if metric == 0 { samples[*i].median } else { samples[*i].iqr }.unwrap_or_default())
+                .collect();
+            let Some(z) = robust_z(&values) else { continue };
+            for (i, z) in measured.iter().zip(z) {
+                if z.abs() > MAX_ROBUST_Z {
+                    samples[*i].flags.push(format!("{} z = {:.1}", label, z));
+                }
+            }
+        }
+    }
+    Ok(Some(SampleQc { markers: markers.len(), samples }))
+}
+
+/// Write `record` with its technical replicates collapsed as `path`;
+/// `None` when `strategy` is `Keep` or no sample has replicates
+pub fn collapse_replicates(
+    record: &DatasetRecord,
+    strategy: Replicates,
+    path: &Path,
+) -> Result<Option<(DatasetRecord, ReplicateSummary)>> {
+    if strategy == Replicates::Keep || !record.has_headers {
+        return Ok(None);
+    }
+    let replicate_index = record
+        .columns
+        .iter()
+        .position(|h| REPLICATE_COLUMNS.contains(&h.trim().to_ascii_lowercase().as_str()));
+    let Some(sample_index) = sample_column(record).filter(|idx| Some(*idx) != replicate_index) else {
+        if let Some(idx) = replicate_index {
+            warn!(column = %record.columns[idx], "Replicate column without a sample ID column, keeping replicates");
+        }
+        return Ok(None);
+    };
+    let rows = read_rows(record)?;
+
+    // Sample key of each row, in order of first appearance
+    let mut order: Vec<String> = Vec::new();
+    let mut members: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        let id = row.get(sample_index).map(str::trim).unwrap_or_default();
+        let key = match replicate_index {
+            Some(_) => id,
+            None => strip_replicate_suffix(id).unwrap_or(id),
+        };
+        let key = if key.is_empty() { format!("row {}", i + 1) } else { key.to_string() };
+        let group = members.entry(key.clone()).or_default();
+        if group.is_empty() {
+            order.push(key);
+        }
+        group.push(i);
+    }
+    let replicated = members.values().filter(|rows| rows.len() > 1).count();
+    if replicated == 0 {
+        return Ok(None);
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .from_path(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let columns: Vec<usize> = (0..record.columns.len()).filter(|idx| Some(*idx) != replicate_index).collect();
+    wtr.write_record(columns.iter().map(|idx| record.columns[*idx].as_str()))?;
+    for key in &order {
+        let group: Vec<&csv::StringRecord> = members[key].iter().map(|i| &rows[*i]).collect();
+        let out: Vec<String> = columns
+            .iter()
+            .map(|idx| {
+                let present = || group.iter().filter_map(|row| row.get(*idx).map(str::trim).filter(|v| !is_missing(v)));
+                if *idx == sample_index {
+                    key.clone()
+                } else if record.column_kind(*idx).is_none_or(|kind| kind == ColumnKind::Numeric) {
+                    let mut values: Vec<f64> = present().filter_map(|v| v.parse().ok()).collect();
+                    if values.is_empty() {
+                        String::new()
+                    } else {
+                        strategy.combine(&mut values).to_string()
+                    }
+                } else {
+                    present().next().unwrap_or_default().to_string()
+                }
+            })
+            .collect();
+        wtr.write_record(&out)?;
+    }
+    wtr.flush()?;
+
+    let summary = ReplicateSummary {
+        strategy,
+        sample_column: record.columns[sample_index].clone(),
+        replicate_column: replicate_index.map(|idx| record.columns[idx].clone()),
+        rows: rows.len(),
+        samples: order.len(),
+        replicated,
+        path: path.to_path_buf(),
+    };
+    let mut collapsed = DatasetRecord::from_path_with(path, Some(record.dataset.description.clone()), record.parsing.derived())?;
+    collapsed.dataset.id = record.dataset.id.clone();
+    collapsed.species = record.species;
+    Ok(Some((collapsed, summary)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replicates_and_sample_qc() {
+        assert_eq!(strip_replicate_suffix("S1_rep2"), Some("S1"));
+        assert_eq!(strip_replicate_suffix("GSM100-R1"), Some("GSM100"));
+        assert_eq!(strip_replicate_suffix("donor_1"), None);
+        assert_eq!(strip_replicate_suffix("S1"), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        let mut csv = String::from("sample_id,age,gene_a,gene_b,gene_c,gene_d\n");
+        csv.push_str("S1_rep1,30,1.0,2.0,3.0,4.0\nS1_rep2,30,1.5,2.5,3.5,4.5\n");
+        for i in 2..10 {
+            csv.push_str(&format!("S{},{},{}.1,2.{},3.0,4.{}\n", i, 30 + i, i % 3, i, i % 4));
+        }
+        // Intensities an order of magnitude above the rest, half missing
+        csv.push_str("S10,40,30.0,NA,NA,40.0\n");
+        std::fs::write(&input, csv).unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+
+        let qc = sample_metrics(&record, &["age"]).unwrap().unwrap();
+        assert_eq!(qc.markers, 4);
+        assert_eq!(qc.samples.len(), 11);
+        let flagged: Vec<&str> = qc.flagged().map(|s| s.sample.as_str()).collect();
+        assert_eq!(flagged, ["S10"]);
+        assert_eq!(qc.samples[10].flags[0], "missing 50%");
+        assert!(qc.results().starts_with("1 of 11 samples were flagged in quality control: S10 (missing 50%, median"));
+
+        let (collapsed, summary) = collapse_replicates(&record, Replicates::Mean, &dir.path().join(REPLICATES_FILE))
+            .unwrap()
+            .unwrap();
+        assert_eq!((summary.rows, summary.samples, summary.replicated), (11, 10, 1));
+        assert_eq!(collapsed.row_count, 10);
+        let written = std::fs::read_to_string(&summary.path).unwrap();
+        assert_eq!(written.lines().nth(1), Some("S1,30,1.25,2.25,3.25,4.25"));
+        assert!(collapse_replicates(&record, Replicates::Keep, &summary.path).unwrap().is_none());
+    }
+}
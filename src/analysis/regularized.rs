@@ -0,0 +1,555 @@
+//! Regularized regression
+//!
+//! A multi-marker model of the target. With thousands of probes and a few
+//! dozen samples ordinary least squares has no unique fit, so the
+//! coefficients are shrunk instead: the markers are standardized and the
+//! elastic-net problem, in glmnet's form
+//!
+//! ```text
+//! 1/(2n) Σ (y − β₀ − xβ)² + λ (α ‖β‖₁ + (1 − α)/2 ‖β‖²)
+//! ```
+//!
+//! is solved by cyclic coordinate descent along a decreasing path of λ,
+//! each fit starting from the previous one. Ridge is α = 0 and lasso α = 1.
+//! λ is chosen by k-fold cross-validation unless given. Lasso and the
+//! elastic net set most coefficients to exactly zero; the markers they keep
+//! are the panel the manuscript reports.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+use nalgebra::DMatrix;
+use serde::{Deserialize, Serialize};
+
+use super::panel::fold_assignment;
+use crate::data_registry::DatasetRecord;
+use crate::utils::cancel::{self, CancellationToken};
+
+/// λ values on the path, from the smallest that zeroes every coefficient
+const PATH_LENGTH: usize = 50;
+
+/// Coordinate descent stops when no standardized coefficient moves more
+const TOLERANCE: f64 = 1e-7;
+
+/// Sweeps over the markers per λ
+const MAX_SWEEPS: usize = 1000;
+
+/// Seed of the fold assignment
+pub(super) const SEED: u64 = 42;
+
+/// Markers named in the manuscript's results
+const REPORTED_MARKERS: usize = 5;
+
+/// Penalty of the multi-marker model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Penalty {
+    /// Squared (L2) penalty: shrinks every coefficient and keeps every
+    /// marker
+    Ridge,
+    /// Absolute (L1) penalty: keeps few markers
+    Lasso,
+    /// Both, weighted by `l1_ratio`: selects markers like the lasso but
+    /// keeps correlated markers together
+    #[default]
+    ElasticNet,
+    /// No multi-marker model
+    Off,
+}
+
+impl Penalty {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Ridge => "ridge",
+            Self::Lasso => "lasso",
+            Self::ElasticNet => "elastic-net",
+            Self::Off => "off",
+        }
+    }
+}
+
+impl fmt::Display for Penalty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Penalty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "ridge" | "l2" => Ok(Self::Ridge),
+            "lasso" | "l1" => Ok(Self::Lasso),
+            "elastic-net" | "elasticnet" | "enet" => Ok(Self::ElasticNet),
+            "off" | "none" => Ok(Self::Off),
+            other => Err(format!("unknown penalty '{}' (expected ridge, lasso, elastic-net or off)", other)),
+        }
+    }
+}
+
+/// How the multi-marker model is fit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Regularization {
+    pub penalty: Penalty,
+    /// Share of the L1 penalty (α) of the elastic net
+    pub l1_ratio: f64,
+    /// Penalty strength λ; chosen by cross-validation when unset
+    pub lambda: Option<f64>,
+    /// Cross-validation folds
+    pub folds: usize,
+}
+
+impl Default for Regularization {
+    fn default() -> Self {
+        Self {
+            penalty: Penalty::default(),
+            l1_ratio: 0.5,
+            lambda: None,
+            folds: 5,
+        }
+    }
+}
+
+impl Regularization {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.l1_ratio > 0.0 && self.l1_ratio < 1.0) {
+            return Err(format!("l1_ratio must be in (0, 1), got {}", self.l1_ratio));
+        }
+        if let Some(lambda) = self.lambda {
+            if !(lambda > 0.0 && lambda.is_finite()) {
+                return Err(format!("lambda must be positive, got {}", lambda));
+            }
+        }
+        if self.folds < 2 {
+            return Err(format!("folds must be at least 2, got {}", self.folds));
+        }
+        Ok(())
+    }
+
+    /// Mixing α of the penalty: 0 for ridge, 1 for lasso
+    pub fn alpha(&self) -> f64 {
+        match self.penalty {
+            Penalty::Ridge => 0.0,
+            Penalty::Lasso => 1.0,
+            Penalty::ElasticNet | Penalty::Off => self.l1_ratio,
+        }
+    }
+}
+
+/// A marker kept by the model
+#[derive(Debug, Clone, Serialize)]
+pub struct RegularizedMarker {
+    pub column: String,
+    pub label: String,
+    /// Change in the target per unit of the marker
+    pub coefficient: f64,
+    /// Change in the target per standard deviation of the marker
+    pub standardized: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegularizedModel {
+    pub target: String,
+    pub penalty: Penalty,
+    /// Mixing α: 0 for ridge, 1 for lasso
+    pub alpha: f64,
+    pub lambda: f64,
+    /// λ was chosen by cross-validation rather than given
+    pub cross_validated: bool,
+    pub folds: usize,
+    /// Samples with a target value
+    pub n: usize,
+    /// Markers the model was fit on
+    pub candidates: usize,
+    pub intercept: f64,
+    /// Markers with a non-zero coefficient, largest standardized
+    /// coefficient first
+    pub markers: Vec<RegularizedMarker>,
+    /// Out-of-fold errors at `lambda`
+    pub cv_rmse: f64,
+    /// Out-of-fold R² (1 - SSE / SST)
+    pub cv_r2: f64,
+    /// CV RMSE of predicting the training mean
+    pub baseline_rmse: f64,
+}
+
+impl RegularizedModel {
+    /// One-line summary for the analysis summary
+    pub fn summary(&self) -> String {
+        format!(
+            "{} regression of {}: {} of {} marker(s) kept (CV RMSE {:.3}, R² {:.3}).",
+            capitalized(self.penalty.id()),
+            self.target,
+            self.markers.len(),
+            self.candidates,
+            self.cv_rmse,
+            self.cv_r2
+        )
+    }
+
+    pub fn methods(&self) -> String {
+        let mixing = match self.penalty {
+            Penalty::ElasticNet => format!(" with an L1 share of α = {}", self.alpha),
+            _ => String::new(),
+        };
+        let lambda = if self.cross_validated {
+            format!(
+                "the penalty λ = {:.4} minimized the {}-fold cross-validated prediction error over a path of {} values",
+                self.lambda, self.folds, PATH_LENGTH
+            )
+        } else {
+            format!("the penalty was set to λ = {}", self.lambda)
+        };
+        format!(
+            "A multi-marker model of {} was fit by {} regression{} on all {} markers, standardized to unit variance \
+             with missing values set to the marker mean, by cyclic coordinate descent; {}.",
+            self.target,
+            self.penalty.id(),
+            mixing,
+            self.candidates,
+            lambda
+        )
+    }
+
+    pub fn results(&self) -> String {
+        let top: Vec<String> = self
+            .markers
+            .iter()
+            .take(REPORTED_MARKERS)
+            .map(|m| format!("{} (β = {:.3} per SD)", m.label, m.standardized))
+            .collect();
+        let kept = if self.markers.is_empty() {
+            format!("kept none of the {} markers", self.candidates)
+        } else if self.penalty == Penalty::Ridge {
+            format!(
+                "shrank the coefficients of all {} markers; the largest were {}",
+                self.candidates,
+                top.join(", ")
+            )
+        } else {
+            format!(
+                "selected a panel of {} of {} markers, led by {}",
+                self.markers.len(),
+                self.candidates,
+                top.join(", ")
+            )
+        };
+        format!(
+            "{} regression of {} {}. Its {}-fold cross-validated RMSE was {:.3} (R² = {:.3}), against {:.3} for the \
+             sample mean.",
+            capitalized(self.penalty.id()),
+            self.target,
+            kept,
+            self.folds,
+            self.cv_rmse,
+            self.cv_r2,
+            self.baseline_rmse
+        )
+    }
+}
+
+fn capitalized(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Fit the model of `target_index` on `markers` (column index and label)
+/// over the rows of `record` with a target value. Missing marker values
+/// are set to the marker mean and markers without variance are left out;
+/// `None` when the penalty is off, no marker remains or there are fewer
+/// than two samples per fold.
+pub fn analyze(
+    record: &DatasetRecord,
+    headers: &[String],
+    target_index: usize,
+    markers: &[(usize, String)],
+    options: &Regularization,
+    cancel: &CancellationToken,
+) -> Result<Option<RegularizedModel>> {
+    if options.penalty == Penalty::Off || markers.is_empty() {
+        return Ok(None);
+    }
+    let mut rdr = record.reader()?;
+    let mut rows: Vec<Vec<Option<f64>>> = Vec::new();
+    let mut y = Vec::new();
+    for (i, row) in rdr.records().enumerate() {
+        if i % super::CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let row = row?;
+        let Some(target) = row.get(target_index).and_then(|v| record.number(v)) else {
+            continue;
+        };
+        y.push(target);
+        rows.push(markers.iter().map(|(idx, _)| row.get(*idx).and_then(|v| record.number(v))).collect());
+    }
+
+    let mut kept: Vec<(usize, f64)> = Vec::new();
+    for pos in 0..markers.len() {
+        let present: Vec<f64> = rows.iter().filter_map(|values| values[pos]).collect();
+        if present.len() < 2 {
+            continue;
+        }
+        let mean = present.iter().sum::<f64>() / present.len() as f64;
+        if present.iter().any(|v| *v != mean) {
+            kept.push((pos, mean));
+        }
+    }
+    let folds = options.folds.max(2);
+    if kept.is_empty() || y.len() < 2 * folds {
+        return Ok(None);
+    }
+    let x = DMatrix::from_fn(y.len(), kept.len(), |i, j| {
+        let (pos, mean) = kept[j];
+        rows[i][pos].unwrap_or(mean)
+    });
+    cancel::check(cancel)?;
+    let Some(fit) = fit(&x, &y, options) else {
+        return Ok(None);
+    };
+
+    let mut selected: Vec<RegularizedMarker> = kept
+        .iter()
+        .zip(fit.coefficients.iter().zip(&fit.standardized))
+        .filter(|(_, (_, standardized))| **standardized != 0.0)
+        .map(|((pos, _), (coefficient, standardized))| RegularizedMarker {
+            column: headers[markers[*pos].0].clone(),
+            label: markers[*pos].1.clone(),
+            coefficient: *coefficient,
+            standardized: *standardized,
+        })
+        .collect();
+    selected.sort_by(|a, b| b.standardized.abs().total_cmp(&a.standardized.abs()));
+    Ok(Some(RegularizedModel {
+        target: headers[target_index].clone(),
+        penalty: options.penalty,
+        alpha: options.alpha(),
+        lambda: fit.lambda,
+        cross_validated: options.lambda.is_none(),
+        folds,
+        n: y.len(),
+        candidates: kept.len(),
+        intercept: fit.intercept,
+        markers: selected,
+        cv_rmse: fit.cv_rmse,
+        cv_r2: fit.cv_r2,
+        baseline_rmse: fit.baseline_rmse,
+    }))
+}
+
+/// Model at the chosen λ, with coefficients on the markers' own scale
+struct Fit {
+    lambda: f64,
+    intercept: f64,
+    coefficients: Vec<f64>,
+    /// Coefficients per standard deviation of the marker over all samples
+    standardized: Vec<f64>,
+    cv_rmse: f64,
+    cv_r2: f64,
+    baseline_rmse: f64,
+}
+
+/// Fit `y` on the columns of `x`, choosing λ by cross-validation unless
+/// `options` sets it; `None` when `y` is constant
+fn fit(x: &DMatrix<f64>, y: &[f64], options: &Regularization) -> Option<Fit> {
+    let alpha = options.alpha();
+    let all: Vec<usize> = (0..y.len()).collect();
+    let lambdas = match options.lambda {
+        Some(lambda) => vec![lambda],
+        None => lambda_path(x, y, &all, alpha)?,
+    };
+
+    // Out-of-fold squared errors per λ, and of the training mean
+    let folds = fold_assignment(y.len(), options.folds.max(2), SEED);
+    let mut squared = vec![0.0; lambdas.len()];
+    let mut baseline = 0.0;
+    for fold in 0..options.folds.max(2) {
+        let (test, train): (Vec<usize>, Vec<usize>) = (0..y.len()).partition(|&row| folds[row] == fold);
+        let train_mean = train.iter().map(|&row| y[row]).sum::<f64>() / train.len() as f64;
+        baseline += test.iter().map(|&row| (y[row] - train_mean).powi(2)).sum::<f64>();
+        for (sse, (intercept, coefficients)) in squared.iter_mut().zip(path(x, y, &train, alpha, &lambdas)) {
+            *sse += test
+                .iter()
+                .map(|&row| (y[row] - predict(x, row, intercept, &coefficients)).powi(2))
+                .sum::<f64>();
+        }
+    }
+    let best = (0..lambdas.len()).min_by(|a, b| squared[*a].total_cmp(&squared[*b]))?;
+
+    // Refit on every sample along the path down to the chosen λ
+    let (intercept, coefficients) = path(x, y, &all, alpha, &lambdas[..=best]).pop()?;
+    let n = y.len() as f64;
+    let mean = y.iter().sum::<f64>() / n;
+    let total = y.iter().map(|v| (v - mean).powi(2)).sum::<f64>();
+    let standardized = coefficients
+        .iter()
+        .enumerate()
+        .map(|(j, b)| b * column_scale(x, &all, j).1)
+        .collect();
+    Some(Fit {
+        lambda: lambdas[best],
+        intercept,
+        coefficients,
+        standardized,
+        cv_rmse: (squared[best] / n).sqrt(),
+        cv_r2: if total > 0.0 { 1.0 - squared[best] / total } else { 0.0 },
+        baseline_rmse: (baseline / n).sqrt(),
+    })
+}
+
+/// Decreasing λ values from the smallest that zeroes every coefficient, as
+/// in glmnet (ridge's starting point uses α = 0.001); `None` when `y` is
+/// constant over `rows`
+fn lambda_path(x: &DMatrix<f64>, y: &[f64], rows: &[usize], alpha: f64) -> Option<Vec<f64>> {
+    let n = rows.len() as f64;
+    let y_mean = rows.iter().map(|&row| y[row]).sum::<f64>() / n;
+    let max = (0..x.ncols())
+        .map(|j| {
+            let (mean, scale) = column_scale(x, rows, j);
+            if scale == 0.0 {
+                return 0.0;
+            }
+            let dot: f64 = rows.iter().map(|&row| (x[(row, j)] - mean) / scale * (y[row] - y_mean)).sum();
+            dot.abs() / n
+        })
+        .fold(0.0, f64::max);
+    if max <= 0.0 {
+        return None;
+    }
+    let lambda_max = max / alpha.max(1e-3);
+    let ratio: f64 = if rows.len() < x.ncols() { 1e-2 } else { 1e-4 };
+    Some(
+        (0..PATH_LENGTH)
+            .map(|i| lambda_max * ratio.powf(i as f64 / (PATH_LENGTH - 1) as f64))
+            .collect(),
+    )
+}
+
+/// Mean and population standard deviation of column `j` over `rows`
+fn column_scale(x: &DMatrix<f64>, rows: &[usize], j: usize) -> (f64, f64) {
+    let n = rows.len() as f64;
+    let mean = rows.iter().map(|&row| x[(row, j)]).sum::<f64>() / n;
+    let variance = rows.iter().map(|&row| (x[(row, j)] - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+/// Intercept and coefficients (on the columns' own scale) fit over `rows`
+/// at each of `lambdas` in turn, by coordinate descent on the standardized
+/// columns
+fn path(x: &DMatrix<f64>, y: &[f64], rows: &[usize], alpha: f64, lambdas: &[f64]) -> Vec<(f64, Vec<f64>)> {
+    let n = rows.len() as f64;
+    let p = x.ncols();
+    let scales: Vec<(f64, f64)> = (0..p).map(|j| column_scale(x, rows, j)).collect();
+    let z: Vec<Vec<f64>> = scales
+        .iter()
+        .enumerate()
+        .map(|(j, (mean, scale))| {
+            rows.iter()
+                .map(|&row| if *scale > 0.0 { (x[(row, j)] - mean) / scale } else { 0.0 })
+                .collect()
+        })
+        .collect();
+    let y_mean = rows.iter().map(|&row| y[row]).sum::<f64>() / n;
+    let mut residual: Vec<f64> = rows.iter().map(|&row| y[row] - y_mean).collect();
+    let mut beta = vec![0.0; p];
+
+    let mut fits = Vec::with_capacity(lambdas.len());
+    for &lambda in lambdas {
+        let threshold = lambda * alpha;
+        let shrink = 1.0 + lambda * (1.0 - alpha);
+        for _ in 0..MAX_SWEEPS {
+            let mut largest_step: f64 = 0.0;
+            for j in (0..p).filter(|&j| scales[j].1 > 0.0) {
+                let zj = &z[j];
+                let rho = zj.iter().zip(&residual).map(|(a, b)| a * b).sum::<f64>() / n + beta[j];
+                let updated = soft_threshold(rho, threshold) / shrink;
+                let step = updated - beta[j];
+                if step != 0.0 {
+                    for (r, zij) in residual.iter_mut().zip(zj) {
+                        *r -= step * zij;
+                    }
+                    beta[j] = updated;
+                    largest_step = largest_step.max(step.abs());
+                }
+            }
+            if largest_step < TOLERANCE {
+                break;
+            }
+        }
+        let coefficients: Vec<f64> = beta
+            .iter()
+            .zip(&scales)
+            .map(|(b, (_, scale))| if *scale > 0.0 { b / scale } else { 0.0 })
+            .collect();
+        let intercept = y_mean - coefficients.iter().zip(&scales).map(|(b, (mean, _))| b * mean).sum::<f64>();
+        fits.push((intercept, coefficients));
+    }
+    fits
+}
+
+fn soft_threshold(value: f64, threshold: f64) -> f64 {
+    if value > threshold {
+        value - threshold
+    } else if value < -threshold {
+        value + threshold
+    } else {
+        0.0
+    }
+}
+
+fn predict(x: &DMatrix<f64>, row: usize, intercept: f64, coefficients: &[f64]) -> f64 {
+    intercept + coefficients.iter().enumerate().map(|(j, b)| b * x[(row, j)]).sum::<f64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_penalties_recover_planted_markers() {
+        // 40 samples, 200 markers: age = 3 m0 - 2 m1 + small noise
+        let (n, p) = (40, 200);
+        let mut rng = StdRng::seed_from_u64(7);
+        let x = DMatrix::from_fn(n, p, |_, j| rng.gen_range(-1.0..1.0) * (1.0 + (j % 3) as f64));
+        let y: Vec<f64> = (0..n)
+            .map(|i| 50.0 + 3.0 * x[(i, 0)] - 2.0 * x[(i, 1)] + 0.05 * (i as f64 * 3.1).sin())
+            .collect();
+
+        let lasso = Regularization { penalty: Penalty::Lasso, ..Regularization::default() };
+        let fit_lasso = fit(&x, &y, &lasso).unwrap();
+        let kept: Vec<usize> = (0..p).filter(|&j| fit_lasso.coefficients[j] != 0.0).collect();
+        assert!(kept.contains(&0) && kept.contains(&1), "{:?}", kept);
+        assert!(kept.len() < 20, "{:?}", kept);
+        assert!((fit_lasso.coefficients[0] - 3.0).abs() < 0.3, "{}", fit_lasso.coefficients[0]);
+        assert!(fit_lasso.cv_r2 > 0.95 && fit_lasso.cv_rmse < fit_lasso.baseline_rmse / 4.0);
+
+        let elastic = fit(&x, &y, &Regularization::default()).unwrap();
+        let mut order: Vec<usize> = (0..p).collect();
+        order.sort_by(|a, b| elastic.standardized[*b].abs().total_cmp(&elastic.standardized[*a].abs()));
+        // m1 moves age by 2 × 1.15 per SD, m0 by 3 × 0.58
+        assert_eq!(&order[..2], &[1, 0]);
+        assert!(elastic.cv_r2 > 0.7, "{}", elastic.cv_r2);
+        assert!(elastic.coefficients.iter().filter(|b| **b != 0.0).count() < p);
+
+        // Ridge keeps every marker; a huge λ shrinks them towards zero
+        let ridge = Regularization { penalty: Penalty::Ridge, ..Regularization::default() };
+        let fit_ridge = fit(&x, &y, &ridge).unwrap();
+        assert!(fit_ridge.coefficients.iter().all(|b| *b != 0.0));
+        let heavy = fit(&x, &y, &Regularization { lambda: Some(1e6), ..ridge }).unwrap();
+        assert!(heavy.standardized.iter().all(|b| b.abs() < 1e-3));
+        assert!((heavy.intercept - y.iter().sum::<f64>() / n as f64).abs() < 0.1);
+
+        assert_eq!("elastic_net".parse::<Penalty>(), Ok(Penalty::ElasticNet));
+        assert_eq!(Penalty::ElasticNet.to_string(), "elastic-net");
+        assert!(Regularization { l1_ratio: 1.5, ..Regularization::default() }.validate().is_err());
+        assert!(fit(&x, &vec![1.0; n], &Regularization::default()).is_none());
+    }
+}
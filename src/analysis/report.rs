@@ -0,0 +1,799 @@
+//! Result table and report export
+//!
+//! Writes the CSV (or TSV) tables behind an [`AnalysisArtifacts`] plus, for
+//! a full report, the template manuscript, a JSON summary and the
+//! supplementary bundle. [`run_analysis`](super::run_analysis) writes the
+//! tables itself; the full report is shared by the `/api/analysis` route,
+//! workflows and the headless `analyze` subcommand.
+
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::contrast::GroupContrast;
+use super::gsea::{Gsea, GSEA_FILE};
+use super::survival::{Survival, COX_FILE};
+use super::impute::ImputationSummary;
+use super::kmeans::KMeans;
+use super::pca::Pca;
+use super::qc::SampleQc;
+use super::regularized::RegularizedModel;
+use super::sex::SexStratified;
+use super::{supplement, AnalysisArtifacts, AnalysisConfig};
+use crate::data_registry::DatasetRecord;
+use crate::manuscript::JournalTemplate;
+use crate::models::{
+    Anova, BiomarkerCandidate, DescriptiveStat, EnrichmentResult, GroupComparison, NoveltyScore, RegressionResult,
+};
+
+/// GO enrichment table next to the other result tables
+pub const GO_ENRICHMENT_FILE: &str = "go_enrichment.csv";
+
+/// Reactome/KEGG pathway enrichment table
+pub const PATHWAY_ENRICHMENT_FILE: &str = "pathway_enrichment.csv";
+
+/// Per-sex associations and interaction tests
+pub const SEX_INTERACTIONS_FILE: &str = "sex_interactions.csv";
+
+/// Per-sample QC metrics and flags
+pub const SAMPLE_QC_FILE: &str = "sample_qc.csv";
+
+/// Each group against the other groups, per marker
+pub const GROUP_COMPARISONS_FILE: &str = "group_comparisons.csv";
+
+/// Welch, Mann-Whitney and Cohen's d per marker between two groups
+pub const GROUP_CONTRAST_FILE: &str = "group_contrast.csv";
+
+/// One-way ANOVA per marker across more than two groups
+pub const ANOVA_FILE: &str = "anova.csv";
+
+/// Loadings of each marker on the principal components
+pub const PCA_LOADINGS_FILE: &str = "pca_loadings.csv";
+
+/// Variance explained by each principal component
+pub const PCA_VARIANCE_FILE: &str = "pca_variance.csv";
+
+/// K-means cluster of each sample
+pub const KMEANS_FILE: &str = "kmeans_clusters.csv";
+
+/// Intercept and non-zero coefficients of the regularized model
+pub const REGULARIZED_FILE: &str = "regularized_model.csv";
+
+/// Values observed and imputed per column
+pub const IMPUTATION_FILE: &str = "imputation.csv";
+
+/// Delimiter of the result tables
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TableFormat {
+    /// Comma-separated, `.csv`
+    #[default]
+    Csv,
+    /// Tab-separated, `.tsv`
+    Tsv,
+}
+
+impl TableFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+        }
+    }
+
+    pub fn delimiter(self) -> u8 {
+        match self {
+            Self::Csv => b',',
+            Self::Tsv => b'\t',
+        }
+    }
+
+    /// `name` (a `.csv` file name) in this format under `dir`
+    pub fn path(self, dir: &Path, name: &str) -> PathBuf {
+        dir.join(name).with_extension(self.extension())
+    }
+
+    /// Format of a table written to `path`, by its extension
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("tsv") => Self::Tsv,
+            _ => Self::Csv,
+        }
+    }
+}
+
+impl fmt::Display for TableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for TableFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "tsv" | "tab" => Ok(Self::Tsv),
+            other => Err(format!("unknown table format '{}' (expected csv or tsv)", other)),
+        }
+    }
+}
+
+/// Paths of the tables written by [`write_tables`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ResultTables {
+    pub descriptive_stats: PathBuf,
+    pub regressions: PathBuf,
+    pub novelty_scores: PathBuf,
+    pub biomarker_candidates: PathBuf,
+    /// Written only when the dataset was grouped
+    pub group_comparisons: Option<PathBuf>,
+    /// Written only when the group column has two levels
+    pub group_contrast: Option<PathBuf>,
+    /// Written only when the group column has more than two levels
+    pub anova: Option<PathBuf>,
+    /// Written only when enrichment produced results
+    pub go_enrichment: Option<PathBuf>,
+    /// Written only when pathway enrichment produced results
+    pub pathway_enrichment: Option<PathBuf>,
+    /// Written only when gene set files were given for GSEA
+    pub gsea: Option<PathBuf>,
+    /// Written only when time-to-event and event columns were given
+    pub cox: Option<PathBuf>,
+    /// Written only when the dataset has a sex column
+    pub sex_interactions: Option<PathBuf>,
+    /// Written only when there were enough marker columns for sample QC
+    pub sample_qc: Option<PathBuf>,
+    /// Written only when there were enough markers and samples for PCA
+    pub pca_loadings: Option<PathBuf>,
+    pub pca_variance: Option<PathBuf>,
+    /// Written only when the samples were clustered
+    pub kmeans: Option<PathBuf>,
+    /// Written only when the regularized model was fit
+    pub regularized: Option<PathBuf>,
+    /// Written only when missing values were imputed
+    pub imputation: Option<PathBuf>,
+}
+
+impl ResultTables {
+    /// Every table written, in the order above
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![
+            self.descriptive_stats.clone(),
+            self.regressions.clone(),
+            self.novelty_scores.clone(),
+            self.biomarker_candidates.clone(),
+        ];
+        paths.extend(self.group_comparisons.clone());
+        paths.extend(self.group_contrast.clone());
+        paths.extend(self.anova.clone());
+        paths.extend(self.go_enrichment.clone());
+        paths.extend(self.pathway_enrichment.clone());
+        paths.extend(self.gsea.clone());
+        paths.extend(self.cox.clone());
+        paths.extend(self.sex_interactions.clone());
+        paths.extend(self.sample_qc.clone());
+        paths.extend(self.pca_loadings.clone());
+        paths.extend(self.pca_variance.clone());
+        paths.extend(self.kmeans.clone());
+        paths.extend(self.regularized.clone());
+        paths.extend(self.imputation.clone());
+        paths
+    }
+}
+
+/// Write the descriptive, regression, novelty, biomarker, group comparison,
+/// two-group contrast, ANOVA, GO and pathway enrichment, GSEA, Cox model,
+/// sex interaction, sample QC, PCA, k-means, regularized model and imputation
+/// tables to `output_dir`
+pub fn write_tables(output_dir: &Path, analysis: &AnalysisArtifacts, format: TableFormat) -> Result<ResultTables> {
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create {}", output_dir.display()))?;
+    let table = |name: &str| format.path(output_dir, name);
+    let tables = ResultTables {
+        descriptive_stats: table("descriptive_stats.csv"),
+        regressions: table("regressions.csv"),
+        novelty_scores: table("novelty_scores.csv"),
+        biomarker_candidates: table("biomarker_candidates.csv"),
+        group_comparisons: (!analysis.group_comparisons.is_empty()).then(|| table(GROUP_COMPARISONS_FILE)),
+        group_contrast: analysis.contrast.as_ref().map(|_| table(GROUP_CONTRAST_FILE)),
+        anova: (!analysis.anova.is_empty()).then(|| table(ANOVA_FILE)),
+        go_enrichment: (!analysis.enrichment.is_empty()).then(|| table(GO_ENRICHMENT_FILE)),
+        pathway_enrichment: (!analysis.pathways.is_empty()).then(|| table(PATHWAY_ENRICHMENT_FILE)),
+        gsea: analysis.gsea.as_ref().map(|_| table(GSEA_FILE)),
+        cox: analysis.survival.as_ref().map(|_| table(COX_FILE)),
+        sex_interactions: analysis.sex_stratified.as_ref().map(|_| table(SEX_INTERACTIONS_FILE)),
+        sample_qc: analysis.sample_qc.as_ref().map(|_| table(SAMPLE_QC_FILE)),
+        pca_loadings: analysis.pca.as_ref().map(|_| table(PCA_LOADINGS_FILE)),
+        pca_variance: analysis.pca.as_ref().map(|_| table(PCA_VARIANCE_FILE)),
+        kmeans: analysis.kmeans.as_ref().map(|_| table(KMEANS_FILE)),
+        regularized: analysis.regularized.as_ref().map(|_| table(REGULARIZED_FILE)),
+        imputation: analysis.imputation.as_ref().map(|_| table(IMPUTATION_FILE)),
+    };
+    write_stats_csv(&tables.descriptive_stats, &analysis.descriptive_stats)?;
+    write_regression_csv(&tables.regressions, &analysis.regressions)?;
+    write_novelty_csv(&tables.novelty_scores, &analysis.novelty_scores)?;
+    write_biomarker_csv(&tables.biomarker_candidates, &analysis.biomarker_candidates)?;
+    if let Some(path) = &tables.group_comparisons {
+        write_group_comparisons_csv(path, &analysis.group_comparisons)?;
+    }
+    if let (Some(path), Some(contrast)) = (&tables.group_contrast, &analysis.contrast) {
+        write_contrast_csv(path, contrast)?;
+    }
+    if let Some(path) = &tables.anova {
+        write_anova_csv(path, &analysis.anova)?;
+    }
+    if let Some(path) = &tables.go_enrichment {
+        write_enrichment_csv(path, &analysis.enrichment)?;
+    }
+    if let Some(path) = &tables.pathway_enrichment {
+        write_enrichment_csv(path, &analysis.pathways)?;
+    }
+    if let (Some(path), Some(gsea)) = (&tables.gsea, &analysis.gsea) {
+        let mut wtr = writer(path)?;
+        gsea_rows(&mut wtr, gsea)?;
+        wtr.flush()?;
+    }
+    if let (Some(path), Some(survival)) = (&tables.cox, &analysis.survival) {
+        let mut wtr = writer(path)?;
+        cox_rows(&mut wtr, survival)?;
+        wtr.flush()?;
+    }
+    if let (Some(path), Some(stratified)) = (&tables.sex_interactions, &analysis.sex_stratified) {
+        write_sex_csv(path, stratified)?;
+    }
+    if let (Some(path), Some(qc)) = (&tables.sample_qc, &analysis.sample_qc) {
+        write_sample_qc_csv(path, qc)?;
+    }
+    if let (Some(loadings), Some(variance), Some(pca)) = (&tables.pca_loadings, &tables.pca_variance, &analysis.pca) {
+        let mut wtr = writer(loadings)?;
+        pca_loading_rows(&mut wtr, pca)?;
+        wtr.flush()?;
+        write_pca_variance_csv(variance, pca)?;
+    }
+    if let (Some(path), Some(kmeans)) = (&tables.kmeans, &analysis.kmeans) {
+        let mut wtr = writer(path)?;
+        kmeans_rows(&mut wtr, kmeans)?;
+        wtr.flush()?;
+    }
+    if let (Some(path), Some(model)) = (&tables.regularized, &analysis.regularized) {
+        let mut wtr = writer(path)?;
+        regularized_rows(&mut wtr, model)?;
+        wtr.flush()?;
+    }
+    if let (Some(path), Some(imputation)) = (&tables.imputation, &analysis.imputation) {
+        let mut wtr = writer(path)?;
+        imputation_rows(&mut wtr, imputation)?;
+        wtr.flush()?;
+    }
+    Ok(tables)
+}
+
+/// Write the tables, `manuscript.md`, `summary.json` and `supplementary.zip`;
+/// returns every file written
+pub fn write_report(
+    output_dir: &Path,
+    record: &DatasetRecord,
+    config: &AnalysisConfig,
+    analysis: &AnalysisArtifacts,
+    template: JournalTemplate,
+) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create report directory {}", output_dir.display()))?;
+
+    let target = config.target_column.as_deref().unwrap_or("age");
+    let group = config.group_column.as_deref().unwrap_or("cell_type");
+    let tables = write_tables(output_dir, analysis, config.tables)?;
+    let mut files = tables.paths();
+
+    let manuscript = super::build_manuscript(&record.dataset.id, target, group, record, analysis, template);
+    let manuscript_path = output_dir.join("manuscript.md");
+    std::fs::write(&manuscript_path, manuscript)?;
+    files.push(manuscript_path);
+
+    let summary = serde_json::json!({
+        "dataset": record.dataset.filename,
+        "rows": record.row_count,
+        "columns": record.columns.len(),
+        "species": record.species,
+        "target": target,
+        "group": group,
+        "summary": analysis.summary,
+        "thresholds": analysis.thresholds,
+        "findings": analysis.findings(),
+        "figure_legends": analysis.figure_legends,
+        "cross_reference": analysis.cross_reference,
+        "top_biomarkers": analysis.biomarker_candidates.iter().take(10).collect::<Vec<_>>(),
+        "heatmap": analysis.heatmap_path,
+        "expression_heatmap": analysis.expression_heatmap_path,
+        "boxplot": analysis.boxplot_path,
+        "volcano": analysis.volcano_path,
+        "roc": analysis.roc.as_ref().map(|roc| serde_json::json!({
+            "classes": roc.classes(),
+            "split": roc.split,
+            "markers": roc.markers,
+            "auc": roc.curves.iter().map(|c| (&c.label, c.auc)).collect::<Vec<_>>(),
+        })),
+        "roc_plot": analysis.roc_path,
+        "survival": analysis.survival.as_ref().map(|survival| serde_json::json!({
+            "time": survival.time,
+            "event": survival.event,
+            "samples": survival.samples,
+            "events": survival.events,
+            "strata": survival.strata,
+            "log_rank": survival.log_rank,
+            "median": survival.curves.iter().map(|c| (&c.label, c.median)).collect::<Vec<_>>(),
+            "cox": survival.cox.iter().take(10).collect::<Vec<_>>(),
+        })),
+        "survival_plot": analysis.survival_path,
+        "go_enrichment": analysis.enrichment.iter().take(10).collect::<Vec<_>>(),
+        "go_enrichment_plot": analysis.enrichment_plot_path,
+        "pathway_enrichment": analysis.pathways.iter().take(10).collect::<Vec<_>>(),
+        "gsea": analysis.gsea.as_ref().map(|gsea| serde_json::json!({
+            "ranked": gsea.ranked,
+            "sources": gsea.sources,
+            "sets": gsea.results.iter().take(10).collect::<Vec<_>>(),
+        })),
+        "contrast": analysis.contrast,
+        "anova": analysis.anova,
+        "sex_stratified": analysis.sex_stratified,
+        "pca": analysis.pca.as_ref().map(|pca| serde_json::json!({
+            "markers": pca.markers.len(),
+            "samples": pca.samples,
+            "components": pca.components,
+            "group_eta_squared": pca.group_eta_squared,
+        })),
+        "kmeans": analysis.kmeans.as_ref().map(|kmeans| serde_json::json!({
+            "k": kmeans.k,
+            "markers": kmeans.markers,
+            "silhouette": kmeans.silhouette,
+            "clusters": kmeans.clusters,
+        })),
+        "regularized": analysis.regularized,
+        "pca_scree": analysis.pca_scree_path,
+        "pca_scores": analysis.pca_scores_path,
+        "adjustment": analysis.adjustment,
+        "replicates": analysis.replicates,
+        "imputation": analysis.imputation,
+        "normalization": analysis.normalization,
+        "normalization_plot": analysis.normalization_plot_path,
+        "batch": analysis.batch,
+        "sample_qc": analysis.sample_qc.as_ref().map(|qc| serde_json::json!({
+            "markers": qc.markers,
+            "samples": qc.samples.len(),
+            "flagged": qc.flagged().collect::<Vec<_>>(),
+        })),
+        "tables": tables,
+    });
+    let summary_path = output_dir.join("summary.json");
+    std::fs::write(&summary_path, serde_json::to_string_pretty(&summary)?)?;
+    files.push(summary_path);
+
+    files.extend(analysis.figure_paths().into_iter().map(PathBuf::from));
+    if let Some(manifest) = &analysis.manifest {
+        files.push(manifest.write(output_dir)?);
+    }
+    files.push(supplement::write_supplement(output_dir, record, config, analysis, template)?);
+    Ok(files)
+}
+
+/// `rows` written as CSV to memory, for tables packed into the supplement
+pub(super) fn csv_bytes(rows: impl FnOnce(&mut csv::Writer<Vec<u8>>) -> Result<()>) -> Result<Vec<u8>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    rows(&mut wtr)?;
+    wtr.into_inner().context("Failed to write table")
+}
+
+/// Table writer with the delimiter of `path`'s extension
+fn writer(path: &Path) -> Result<csv::Writer<File>> {
+    csv::WriterBuilder::new()
+        .delimiter(TableFormat::of(path).delimiter())
+        .from_path(path)
+        .with_context(|| format!("Failed to create {}", path.display()))
+}
+
+fn write_stats_csv(path: &Path, stats: &[DescriptiveStat]) -> Result<()> {
+    let mut wtr = writer(path)?;
+    wtr.write_record(["column", "count", "mean", "std_dev", "min", "median", "max"])?;
+    for stat in stats {
+        wtr.write_record([
+            &stat.column,
+            &stat.count.to_string(),
+            &stat.mean.to_string(),
+            &stat.std_dev.to_string(),
+            &stat.min.to_string(),
+            &stat.median.to_string(),
+            &stat.max.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_regression_csv(path: &Path, regressions: &[RegressionResult]) -> Result<()> {
+    let mut wtr = writer(path)?;
+    wtr.write_record(["target", "predictors", "intercept", "coefficients", "r2", "n"])?;
+    for reg in regressions {
+        wtr.write_record([
+            &reg.target,
+            &reg.predictors.join(";"),
+            &reg.intercept.to_string(),
+            &reg.coefficients.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(";"),
+            &reg.r2.to_string(),
+            &reg.n.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_novelty_csv(path: &Path, novelty: &[NoveltyScore]) -> Result<()> {
+    let mut wtr = writer(path)?;
+    wtr.write_record(["column", "score", "p_value", "q_value", "rationale"])?;
+    for score in novelty {
+        wtr.write_record([
+            score.column.clone(),
+            score.score.to_string(),
+            score.p_value.map(|p| p.to_string()).unwrap_or_default(),
+            score.q_value.map(|q| q.to_string()).unwrap_or_default(),
+            score.rationale.clone(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_group_comparisons_csv(path: &Path, comparisons: &[GroupComparison]) -> Result<()> {
+    let mut wtr = writer(path)?;
+    wtr.write_record([
+        "column", "group", "n", "mean", "std_dev", "rest_n", "rest_mean", "difference", "t", "p_value", "q_value",
+    ])?;
+    for c in comparisons {
+        wtr.write_record([
+            &c.column,
+            &c.group,
+            &c.n.to_string(),
+            &c.mean.to_string(),
+            &c.std_dev.to_string(),
+            &c.rest_n.to_string(),
+            &c.rest_mean.to_string(),
+            &c.difference.to_string(),
+            &c.t.to_string(),
+            &c.p_value.to_string(),
+            &c.q_value.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_anova_csv(path: &Path, tests: &[Anova]) -> Result<()> {
+    let mut wtr = writer(path)?;
+    wtr.write_record([
+        "column",
+        "groups",
+        "n",
+        "f",
+        "df_between",
+        "df_within",
+        "p_value",
+        "q_value",
+        "eta_squared",
+        "kruskal_h",
+        "kruskal_p",
+        "kruskal_q",
+    ])?;
+    let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+    for t in tests {
+        wtr.write_record([
+            t.column.clone(),
+            t.groups.to_string(),
+            t.n.to_string(),
+            t.f.to_string(),
+            t.df_between.to_string(),
+            t.df_within.to_string(),
+            t.p_value.to_string(),
+            t.q_value.to_string(),
+            t.eta_squared.to_string(),
+            optional(t.kruskal_h),
+            optional(t.kruskal_p),
+            optional(t.kruskal_q),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_contrast_csv(path: &Path, contrast: &GroupContrast) -> Result<()> {
+    let mut wtr = writer(path)?;
+    let [a, b] = &contrast.groups;
+    wtr.write_record([
+        "column".to_string(),
+        "label".to_string(),
+        format!("n_{}", a),
+        format!("mean_{}", a),
+        format!("sd_{}", a),
+        format!("n_{}", b),
+        format!("mean_{}", b),
+        format!("sd_{}", b),
+        "difference".to_string(),
+        "cohens_d".to_string(),
+        "t".to_string(),
+        "df".to_string(),
+        "t_p".to_string(),
+        "t_q".to_string(),
+        "u".to_string(),
+        "u_p".to_string(),
+        "u_q".to_string(),
+    ])?;
+    for m in &contrast.markers {
+        wtr.write_record([
+            &m.column,
+            &m.label,
+            &m.n[0].to_string(),
+            &m.mean[0].to_string(),
+            &m.std_dev[0].to_string(),
+            &m.n[1].to_string(),
+            &m.mean[1].to_string(),
+            &m.std_dev[1].to_string(),
+            &m.difference.to_string(),
+            &m.cohens_d.to_string(),
+            &m.t.to_string(),
+            &m.df.to_string(),
+            &m.t_p.to_string(),
+            &m.t_q.to_string(),
+            &m.u.to_string(),
+            &m.u_p.to_string(),
+            &m.u_q.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_biomarker_csv(path: &Path, biomarkers: &[BiomarkerCandidate]) -> Result<()> {
+    let mut wtr = writer(path)?;
+    biomarker_rows(&mut wtr, biomarkers)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+pub(super) fn biomarker_rows<W: Write>(wtr: &mut csv::Writer<W>, biomarkers: &[BiomarkerCandidate]) -> Result<()> {
+    wtr.write_record([
+        "column",
+        "symbol",
+        "biotype",
+        "human_ortholog",
+        "score",
+        "correlation",
+        "n",
+        "p_value",
+        "q_value",
+        "auc",
+        "direction",
+        "notes",
+        "prior_publications",
+        "literature_status",
+    ])?;
+    for bm in biomarkers {
+        wtr.write_record([
+            bm.column.clone(),
+            bm.symbol.clone().unwrap_or_default(),
+            bm.biotype.clone().unwrap_or_default(),
+            bm.human_ortholog.clone().unwrap_or_default(),
+            bm.score.to_string(),
+            bm.correlation.to_string(),
+            bm.n.to_string(),
+            bm.p_value.to_string(),
+            bm.q_value.to_string(),
+            bm.auc.map(|auc| auc.to_string()).unwrap_or_default(),
+            bm.direction.clone(),
+            bm.notes.clone(),
+            bm.prior_evidence.as_ref().map(|e| e.publications.to_string()).unwrap_or_default(),
+            bm.prior_evidence.as_ref().map(|e| e.status.as_str().to_string()).unwrap_or_default(),
+        ])?;
+    }
+    Ok(())
+}
+
+fn write_enrichment_csv(path: &Path, enrichment: &[EnrichmentResult]) -> Result<()> {
+    let mut wtr = writer(path)?;
+    enrichment_rows(&mut wtr, enrichment)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+pub(super) fn enrichment_rows<W: Write>(wtr: &mut csv::Writer<W>, enrichment: &[EnrichmentResult]) -> Result<()> {
+    wtr.write_record([
+        "term_id", "term_name", "overlap", "term_size", "hits", "universe", "fold_enrichment", "p_value", "q_value",
+        "genes",
+    ])?;
+    for term in enrichment {
+        wtr.write_record([
+            &term.term_id,
+            &term.term_name,
+            &term.genes.len().to_string(),
+            &term.term_size.to_string(),
+            &term.hits.to_string(),
+            &term.universe.to_string(),
+            &term.fold_enrichment.to_string(),
+            &term.p_value.to_string(),
+            &term.q_value.to_string(),
+            &term.genes.join(";"),
+        ])?;
+    }
+    Ok(())
+}
+
+/// One row per tested gene set, by p-value; the leading-edge genes in rank
+/// order
+pub(super) fn gsea_rows<W: Write>(wtr: &mut csv::Writer<W>, gsea: &Gsea) -> Result<()> {
+    wtr.write_record([
+        "term_id", "term_name", "source", "size", "es", "nes", "p_value", "q_value", "leading_edge_size",
+        "leading_edge",
+    ])?;
+    for set in &gsea.results {
+        wtr.write_record([
+            &set.term_id,
+            &set.term_name,
+            &set.source,
+            &set.size.to_string(),
+            &set.es.to_string(),
+            &set.nes.to_string(),
+            &set.p_value.to_string(),
+            &set.q_value.to_string(),
+            &set.leading_edge.len().to_string(),
+            &set.leading_edge.join(";"),
+        ])?;
+    }
+    Ok(())
+}
+
+/// One row per marker with a Cox model, by p-value
+pub(super) fn cox_rows<W: Write>(wtr: &mut csv::Writer<W>, survival: &Survival) -> Result<()> {
+    wtr.write_record([
+        "column", "label", "n", "events", "coefficient", "se", "hazard_ratio", "ci_low", "ci_high", "p_value",
+        "q_value",
+    ])?;
+    for result in &survival.cox {
+        wtr.write_record([
+            &result.column,
+            &result.label,
+            &result.n.to_string(),
+            &result.events.to_string(),
+            &result.coefficient.to_string(),
+            &result.se.to_string(),
+            &result.hazard_ratio.to_string(),
+            &result.ci_low.to_string(),
+            &result.ci_high.to_string(),
+            &result.p_value.to_string(),
+            &result.q_value.to_string(),
+        ])?;
+    }
+    Ok(())
+}
+
+/// One row per tested marker; per-stratum columns are suffixed with the
+/// stratum value
+fn write_sex_csv(path: &Path, stratified: &SexStratified) -> Result<()> {
+    let mut wtr = writer(path)?;
+    let [a, b] = &stratified.strata;
+    wtr.write_record([
+        "column".to_string(),
+        "label".to_string(),
+        format!("n_{}", a),
+        format!("r_{}", a),
+        format!("p_{}", a),
+        format!("n_{}", b),
+        format!("r_{}", b),
+        format!("p_{}", b),
+        "interaction".to_string(),
+        "interaction_p".to_string(),
+        "interaction_q".to_string(),
+    ])?;
+    for marker in &stratified.markers {
+        wtr.write_record([
+            &marker.column,
+            &marker.label,
+            &marker.n[0].to_string(),
+            &marker.r[0].to_string(),
+            &marker.p_value[0].to_string(),
+            &marker.n[1].to_string(),
+            &marker.r[1].to_string(),
+            &marker.p_value[1].to_string(),
+            &marker.interaction.to_string(),
+            &marker.interaction_p.to_string(),
+            &marker.interaction_q.to_string(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+fn write_sample_qc_csv(path: &Path, qc: &SampleQc) -> Result<()> {
+    let mut wtr = writer(path)?;
+    sample_qc_rows(&mut wtr, qc)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+pub(super) fn sample_qc_rows<W: Write>(wtr: &mut csv::Writer<W>, qc: &SampleQc) -> Result<()> {
+    wtr.write_record(["sample", "row", "missing_rate", "median", "iqr", "flagged", "flags"])?;
+    let number = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+    for sample in &qc.samples {
+        wtr.write_record([
+            &sample.sample,
+            &sample.row.to_string(),
+            &sample.missing_rate.to_string(),
+            &number(sample.median),
+            &number(sample.iqr),
+            &(!sample.flags.is_empty()).to_string(),
+            &sample.flags.join("; "),
+        ])?;
+    }
+    Ok(())
+}
+
+/// One row per marker, one column per component
+pub(super) fn pca_loading_rows<W: Write>(wtr: &mut csv::Writer<W>, pca: &Pca) -> Result<()> {
+    let mut header = vec!["column".to_string()];
+    header.extend(pca.components.iter().map(|c| format!("PC{}", c.index)));
+    wtr.write_record(&header)?;
+    for (marker, loadings) in pca.markers.iter().zip(&pca.loadings) {
+        let mut row = vec![marker.clone()];
+        row.extend(loadings.iter().map(f64::to_string));
+        wtr.write_record(&row)?;
+    }
+    Ok(())
+}
+
+fn write_pca_variance_csv(path: &Path, pca: &Pca) -> Result<()> {
+    let mut wtr = writer(path)?;
+    wtr.write_record(["component", "variance", "ratio", "cumulative"])?;
+    for c in &pca.components {
+        wtr.write_record([format!("PC{}", c.index), c.variance.to_string(), c.ratio.to_string(), c.cumulative.to_string()])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// One row per clustered sample, with its group when grouped
+pub(super) fn kmeans_rows<W: Write>(wtr: &mut csv::Writer<W>, kmeans: &KMeans) -> Result<()> {
+    let mut header = vec!["sample", "cluster", "distance", "silhouette"];
+    if let Some(group) = &kmeans.group {
+        header.push(group);
+    }
+    wtr.write_record(&header)?;
+    for a in &kmeans.assignments {
+        let mut row = vec![a.sample.clone(), a.cluster.to_string(), a.distance.to_string(), a.silhouette.to_string()];
+        row.extend(a.group.clone());
+        wtr.write_record(&row)?;
+    }
+    Ok(())
+}
+
+/// The intercept, then one row per marker kept, largest standardized
+/// coefficient first
+pub(super) fn regularized_rows<W: Write>(wtr: &mut csv::Writer<W>, model: &RegularizedModel) -> Result<()> {
+    wtr.write_record(["column", "label", "coefficient", "standardized"])?;
+    wtr.write_record(["(intercept)", "", model.intercept.to_string().as_str(), ""])?;
+    for m in &model.markers {
+        wtr.write_record([m.column.clone(), m.label.clone(), m.coefficient.to_string(), m.standardized.to_string()])?;
+    }
+    Ok(())
+}
+
+/// One row per column with imputed values
+pub(super) fn imputation_rows<W: Write>(wtr: &mut csv::Writer<W>, imputation: &ImputationSummary) -> Result<()> {
+    wtr.write_record(["column", "observed", "imputed"])?;
+    for c in &imputation.columns {
+        wtr.write_record([c.column.clone(), c.observed.to_string(), c.imputed.to_string()])?;
+    }
+    Ok(())
+}
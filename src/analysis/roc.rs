@@ -0,0 +1,387 @@
+//! ROC curves of the biomarker candidates
+//!
+//! When the target is binary, or is split at a cut-off, each candidate gets
+//! the area under its ROC curve: the probability that a randomly chosen
+//! positive sample has a higher marker value than a randomly chosen
+//! negative one, computed from the Mann-Whitney rank sum with ties counted
+//! half. Unlike the correlation it does not assume a linear relationship,
+//! and it states how well the marker alone separates the two classes. An AUC
+//! below 0.5 means the marker is lower in the positive class.
+//!
+//! The ROC plot overlays the curves of the markers that separate the classes
+//! best, each drawn in the direction in which it discriminates.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::Result;
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::stats;
+use crate::models::BiomarkerCandidate;
+
+pub const ROC_FILE: &str = "roc_curves.png";
+
+/// Markers whose curves are plotted, best-discriminating first
+pub const PLOT_MARKERS: usize = 5;
+
+/// Fewest samples in each class for an AUC
+const MIN_CLASS: usize = 2;
+
+/// How the target is split into the positive and negative classes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum RocSplit {
+    /// Binary targets only, the higher of their two values positive
+    #[default]
+    Auto,
+    /// Values above the median are positive; binary targets are split
+    /// between their two values
+    Median,
+    /// Values above the cut-off are positive
+    Above(f64),
+    /// No ROC analysis
+    Off,
+}
+
+impl RocSplit {
+    /// The split of `target`, the target values of every sample; `None`
+    /// when it does not apply or leaves a class with fewer than two samples
+    pub fn split(self, target: &[f64]) -> Option<Split> {
+        let mut sorted: Vec<f64> = target.iter().copied().filter(|v| v.is_finite()).collect();
+        sorted.sort_by(f64::total_cmp);
+        let mut levels = sorted.clone();
+        levels.dedup();
+        let binary = levels.len() == 2;
+        let threshold = match self {
+            Self::Off => return None,
+            Self::Above(threshold) => threshold,
+            _ if binary => levels[0],
+            Self::Auto => return None,
+            Self::Median if sorted.is_empty() => return None,
+            Self::Median => {
+                let mid = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    (sorted[mid - 1] + sorted[mid]) / 2.0
+                } else {
+                    sorted[mid]
+                }
+            }
+        };
+        let positives = target.iter().filter(|v| **v > threshold).count();
+        let negatives = target.iter().filter(|v| **v <= threshold).count();
+        (positives >= MIN_CLASS && negatives >= MIN_CLASS).then_some(Split {
+            threshold,
+            binary,
+            positives,
+            negatives,
+        })
+    }
+}
+
+impl fmt::Display for RocSplit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::Median => f.write_str("median"),
+            Self::Above(threshold) => write!(f, "{}", threshold),
+            Self::Off => f.write_str("off"),
+        }
+    }
+}
+
+impl FromStr for RocSplit {
+    type Err = String;
+
+    /// `auto`, `median`, `off` or a cut-off value
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "median" => Ok(Self::Median),
+            "off" | "none" => Ok(Self::Off),
+            value => match value.parse::<f64>() {
+                Ok(threshold) if threshold.is_finite() => Ok(Self::Above(threshold)),
+                _ => Err(format!("invalid ROC split '{}' (expected auto, median, off or a cut-off value)", s)),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for RocSplit {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<RocSplit> for String {
+    fn from(split: RocSplit) -> Self {
+        split.to_string()
+    }
+}
+
+/// Target values above `threshold` are positive
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Split {
+    pub threshold: f64,
+    /// The target has exactly two values and is split between them
+    pub binary: bool,
+    /// Samples in each class, over every sample with a target value
+    pub positives: usize,
+    pub negatives: usize,
+}
+
+impl Split {
+    fn positive(&self, target: f64) -> bool {
+        target > self.threshold
+    }
+
+    /// AUC of `values` against the classes of `targets` (paired), `None`
+    /// when either class has fewer than two samples
+    pub fn auc(&self, values: &[f64], targets: &[f64]) -> Option<f64> {
+        let positive: Vec<bool> = targets.iter().map(|t| self.positive(*t)).collect();
+        auc(values, &positive)
+    }
+}
+
+/// Area under the ROC curve of `values` for the `positive` classes, from the
+/// Mann-Whitney rank sum
+pub fn auc(values: &[f64], positive: &[bool]) -> Option<f64> {
+    let positives = positive.iter().filter(|p| **p).count();
+    let negatives = positive.len() - positives;
+    if positives < MIN_CLASS || negatives < MIN_CLASS {
+        return None;
+    }
+    let rank_sum: f64 = stats::ranks(values).iter().zip(positive).filter(|(_, p)| **p).map(|(r, _)| r).sum();
+    let (p, n) = (positives as f64, negatives as f64);
+    Some((rank_sum - p * (p + 1.0) / 2.0) / (p * n))
+}
+
+/// False and true positive rates at every cut-off of `values`, from (0, 0)
+/// to (1, 1), calling samples above the cut-off positive
+pub fn curve(values: &[f64], positive: &[bool]) -> Vec<(f64, f64)> {
+    let positives = positive.iter().filter(|p| **p).count().max(1) as f64;
+    let negatives = positive.iter().filter(|p| !**p).count().max(1) as f64;
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&i, &j| values[j].total_cmp(&values[i]));
+    let mut points = vec![(0.0, 0.0)];
+    let (mut tp, mut fp) = (0.0, 0.0);
+    for (k, &i) in order.iter().enumerate() {
+        if positive[i] {
+            tp += 1.0;
+        } else {
+            fp += 1.0;
+        }
+        // Tied values cross the cut-off together
+        if order.get(k + 1).is_none_or(|&next| values[next] != values[i]) {
+            points.push((fp / negatives, tp / positives));
+        }
+    }
+    points
+}
+
+/// ROC curve of one marker
+#[derive(Debug, Clone, Serialize)]
+pub struct RocCurve {
+    pub column: String,
+    pub label: String,
+    /// AUC with higher values called positive
+    pub auc: f64,
+    /// The curve: false against true positive rate, in the direction in
+    /// which the marker discriminates
+    pub points: Vec<(f64, f64)>,
+}
+
+impl RocCurve {
+    /// AUC in the direction in which the marker discriminates
+    pub fn discrimination(&self) -> f64 {
+        self.auc.max(1.0 - self.auc)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RocAnalysis {
+    pub target: String,
+    pub split: Split,
+    /// Candidates with an AUC
+    pub markers: usize,
+    /// Curves of the best-discriminating markers, best first
+    pub curves: Vec<RocCurve>,
+}
+
+impl RocAnalysis {
+    /// The positive class, "age > 45"
+    pub fn classes(&self) -> String {
+        format!("{} > {}", self.target, format_threshold(self.split.threshold))
+    }
+
+    /// One sentence for the analysis summary
+    pub fn summary(&self) -> String {
+        match self.curves.first() {
+            Some(best) => format!(
+                "ROC ({}): AUC for {} marker(s), best {} at {:.3}.",
+                self.classes(),
+                self.markers,
+                best.label,
+                best.discrimination()
+            ),
+            None => format!("ROC ({}): AUC for {} marker(s).", self.classes(), self.markers),
+        }
+    }
+
+    pub fn methods(&self) -> String {
+        let classes = if self.split.binary {
+            format!("the higher of the two values of {} as the positive class", self.target)
+        } else {
+            format!("{} > {} as the positive class", self.target, format_threshold(self.split.threshold))
+        };
+        format!(
+            "Each candidate's ability to separate the samples was summarized by the area under its ROC curve (AUC), \
+             with {} ({} positive and {} negative samples), computed from the Mann-Whitney rank sum with ties \
+             counted half.",
+            classes, self.split.positives, self.split.negatives
+        )
+    }
+
+    pub fn results(&self) -> String {
+        let top: Vec<String> = self
+            .curves
+            .iter()
+            .map(|c| {
+                let lower = if c.auc < 0.5 { ", lower in the positive class" } else { "" };
+                format!("{} (AUC = {:.3}{})", c.label, c.discrimination(), lower)
+            })
+            .collect();
+        format!(
+            "Of {} candidates with an AUC, the best separated the {} positive from the {} negative samples were {}.",
+            self.markers,
+            self.split.positives,
+            self.split.negatives,
+            top.join(", ")
+        )
+    }
+}
+
+/// Cut-off as quoted, without trailing zeros
+fn format_threshold(threshold: f64) -> String {
+    let formatted = format!("{:.4}", threshold);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// ROC analysis of the [`PLOT_MARKERS`] `candidates` that discriminate
+/// best, with `series` giving each candidate's paired (marker, target)
+/// values and `label` its plot label; `None` when no candidate has an AUC
+pub fn analyze<'a>(
+    target: &str,
+    split: Split,
+    candidates: &[BiomarkerCandidate],
+    series: impl Fn(&str) -> Option<(&'a [f64], &'a [f64])>,
+    label: impl Fn(&BiomarkerCandidate) -> String,
+) -> Option<RocAnalysis> {
+    let mut scored: Vec<(&BiomarkerCandidate, f64)> =
+        candidates.iter().filter_map(|c| c.auc.map(|auc| (c, auc))).collect();
+    if scored.is_empty() {
+        return None;
+    }
+    let markers = scored.len();
+    scored.sort_by(|a, b| (b.1 - 0.5).abs().total_cmp(&(a.1 - 0.5).abs()));
+    let curves = scored
+        .into_iter()
+        .filter_map(|(candidate, auc)| {
+            let (values, targets) = series(&candidate.column)?;
+            let positive: Vec<bool> = targets.iter().map(|t| split.positive(*t)).collect();
+            // Markers lower in the positive class are drawn reversed
+            let oriented: Vec<f64> = values.iter().map(|v| if auc < 0.5 { -v } else { *v }).collect();
+            Some(RocCurve {
+                column: candidate.column.clone(),
+                label: label(candidate),
+                auc,
+                points: curve(&oriented, &positive),
+            })
+        })
+        .take(PLOT_MARKERS)
+        .collect();
+    Some(RocAnalysis {
+        target: target.to_string(),
+        split,
+        markers,
+        curves,
+    })
+}
+
+/// ROC curves of `roc`'s markers over the diagonal of chance
+pub fn write_roc_plot(output_path: &Path, roc: &RocAnalysis) -> Result<()> {
+    let root = BitMapBackend::new(output_path, (800, 700)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .margin(20)
+        .caption(format!("ROC curves: {}", roc.classes()), ("sans-serif", 24))
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0f64..1f64, 0f64..1f64)?;
+    chart
+        .configure_mesh()
+        .x_desc("False positive rate (1 - specificity)")
+        .y_desc("True positive rate (sensitivity)")
+        .draw()?;
+
+    chart.draw_series(DashedLineSeries::new(vec![(0.0, 0.0), (1.0, 1.0)], 6, 4, BLACK.mix(0.5).into()))?;
+    for (i, roc_curve) in roc.curves.iter().enumerate() {
+        let colour = Palette99::pick(i).to_rgba();
+        let lower = if roc_curve.auc < 0.5 { ", lower" } else { "" };
+        chart
+            .draw_series(LineSeries::new(roc_curve.points.iter().copied(), colour.stroke_width(2)))?
+            .label(format!("{} (AUC {:.3}{})", roc_curve.label, roc_curve.discrimination(), lower))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 16, y)], colour.stroke_width(2)));
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::LowerRight)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_auc_and_curve() {
+        assert_eq!("median".parse::<RocSplit>(), Ok(RocSplit::Median));
+        assert_eq!("45.5".parse::<RocSplit>(), Ok(RocSplit::Above(45.5)));
+        assert!("old".parse::<RocSplit>().is_err());
+
+        // Binary targets split between their values whatever the mode
+        let status = [0.0, 1.0, 0.0, 1.0, 1.0, 0.0];
+        let split = RocSplit::Auto.split(&status).unwrap();
+        assert_eq!((split.threshold, split.binary, split.positives, split.negatives), (0.0, true, 3, 3));
+        assert_eq!(RocSplit::Median.split(&status), Some(split));
+        let ages = [20.0, 30.0, 40.0, 50.0, 60.0, 70.0];
+        assert!(RocSplit::Auto.split(&ages).is_none());
+        assert!(RocSplit::Off.split(&status).is_none());
+        assert_eq!(RocSplit::Median.split(&ages).unwrap().threshold, 45.0);
+        assert!(RocSplit::Above(65.0).split(&ages).is_none(), "one positive sample");
+
+        // One of nine positive-negative pairs misordered, one tied
+        let marker = [1.0, 5.0, 2.0, 4.0, 2.0, 3.0];
+        let auc = split.auc(&marker, &status).unwrap();
+        assert!((auc - 7.5 / 9.0).abs() < 1e-12, "{}", auc);
+        let reversed: Vec<f64> = marker.iter().map(|v| -v).collect();
+        assert!((split.auc(&reversed, &status).unwrap() - 1.5 / 9.0).abs() < 1e-12);
+
+        let positive: Vec<bool> = status.iter().map(|s| *s > 0.0).collect();
+        let points = curve(&marker, &positive);
+        assert_eq!(points.first(), Some(&(0.0, 0.0)));
+        assert_eq!(points.last(), Some(&(1.0, 1.0)));
+        // The tied 2.0s cross the cut-off together
+        assert_eq!(points.len(), 6);
+        let trapezoids: f64 = points.windows(2).map(|w| (w[1].0 - w[0].0) * (w[0].1 + w[1].1) / 2.0).sum();
+        assert!((trapezoids - auc).abs() < 1e-12);
+    }
+}
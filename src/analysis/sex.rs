@@ -0,0 +1,283 @@
+//! Sex-stratified and interaction analysis
+//!
+//! When the dataset has a `sex` (or `gender`) column with two values, each
+//! biomarker candidate's association with the target is re-estimated within
+//! each sex, and a marker × sex interaction is tested in the model
+//! `target = b0 + b1 marker + b2 sex + b3 marker·sex` (t test on b3,
+//! Benjamini–Hochberg across markers). Markers whose interaction passes the
+//! configured p- and q-value cut-offs are reported as having a
+//! sex-dependent association.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use nalgebra::{DMatrix, DVector};
+use serde::Serialize;
+use tracing::warn;
+
+use super::correlation;
+use super::stats::{self, Thresholds};
+use crate::data_registry::DatasetRecord;
+use crate::models::BiomarkerCandidate;
+use crate::utils::cancel::{self, CancellationToken};
+use crate::utils::number;
+
+/// Markers named in the manuscript's results sentence
+const REPORTED: usize = 5;
+
+/// Values read as missing rather than as a third stratum
+pub(super) const MISSING: [&str; 6] = ["", "na", "nan", "n/a", "unknown", "?"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SexStratified {
+    pub column: String,
+    /// The two values of the sex column, sorted; the second is coded 1 in
+    /// the interaction model
+    pub strata: [String; 2],
+    /// Samples with a target value in each stratum
+    pub samples: [usize; 2],
+    /// Tested markers, smallest interaction p-value first
+    pub markers: Vec<SexInteraction>,
+    /// Cut-offs an interaction has to pass
+    #[serde(skip)]
+    pub thresholds: Thresholds,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SexInteraction {
+    pub column: String,
+    pub label: String,
+    /// Per stratum: pairs, Pearson r with the target and its p-value
+    pub n: [usize; 2],
+    pub r: [f64; 2],
+    pub p_value: [f64; 2],
+    /// b3: how much the target's slope on the marker changes in the second
+    /// stratum
+    pub interaction: f64,
+    pub interaction_p: f64,
+    pub interaction_q: f64,
+}
+
+impl SexStratified {
+    /// Markers whose association differs between the strata
+    pub fn differing(&self) -> impl Iterator<Item = &SexInteraction> {
+        self.markers
+            .iter()
+            .filter(|m| self.thresholds.significant(m.interaction_p, m.interaction_q))
+    }
+
+    pub fn methods(&self, target: &str) -> String {
+        format!(
+            "Associations with {target} were re-estimated within each level of {column} ({a}, {b}), and a \
+             marker-by-{column} interaction term was tested in the linear model {target} ~ marker + {column} + \
+             marker:{column} (t test on the interaction coefficient, Benjamini-Hochberg correction across markers).",
+            column = self.column,
+            a = self.strata[0],
+            b = self.strata[1],
+        )
+    }
+
+    /// Results sentence naming the first markers with a sex-dependent
+    /// association. Per-stratum statistics are written r_<stratum> so they
+    /// are not mistaken for the overall r of the marker.
+    pub fn results(&self, target: &str) -> String {
+        let intro = format!(
+            "Stratified by {} ({}: n = {}; {}: n = {}),",
+            self.column, self.strata[0], self.samples[0], self.strata[1], self.samples[1]
+        );
+        let differing: Vec<&SexInteraction> = self.differing().collect();
+        if differing.is_empty() {
+            return format!(
+                "{} no marker's association with {} differed between strata (none of the {} tested markers reached \
+                 interaction {}).",
+                intro,
+                target,
+                self.markers.len(),
+                self.thresholds.criterion_for("p_int", "q_int")
+            );
+        }
+        let listed = differing
+            .iter()
+            .take(REPORTED)
+            .map(|m| {
+                format!(
+                    "{} (r_{} = {:.3}, r_{} = {:.3}, {})",
+                    m.label,
+                    self.strata[0],
+                    m.r[0],
+                    self.strata[1],
+                    m.r[1],
+                    number::p_quote("q_int", m.interaction_q)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "{} {} of {} markers showed an association with {} that differed between strata \
+             (marker-by-{} interaction {}): {}.",
+            intro,
+            differing.len(),
+            self.markers.len(),
+            target,
+            self.column,
+            self.thresholds.criterion_for("p_int", "q_int"),
+            listed
+        )
+    }
+}
+
+/// Index of the sex column among `headers`
+pub fn sex_column(headers: &[String]) -> Option<usize> {
+    headers
+        .iter()
+        .position(|h| matches!(h.trim().to_ascii_lowercase().as_str(), "sex" | "gender"))
+}
+
+/// Stratified and interaction analysis of `candidates` against the target
+/// column, or `None` when there is no sex column with exactly two values
+pub fn analyze(
+    record: &DatasetRecord,
+    headers: &[String],
+    target_index: usize,
+    candidates: &[BiomarkerCandidate],
+    thresholds: Thresholds,
+    cancel: &CancellationToken,
+) -> Result<Option<SexStratified>> {
+    let Some(sex_index) = sex_column(headers) else {
+        return Ok(None);
+    };
+    let column = headers[sex_index].clone();
+    let index: HashMap<&str, usize> = headers.iter().enumerate().map(|(i, h)| (h.as_str(), i)).collect();
+    let markers: Vec<(&BiomarkerCandidate, usize)> = candidates
+        .iter()
+        .filter_map(|c| Some((c, *index.get(c.column.as_str())?)))
+        .filter(|(_, idx)| *idx != sex_index)
+        .collect();
+
+    let mut rdr = record.reader()?;
+    // Stratum value (lower case) -> first spelling seen
+    let mut spellings: HashMap<String, String> = HashMap::new();
+    let mut rows: Vec<(String, f64, Vec<Option<f64>>)> = Vec::new();
+    for (i, row) in rdr.records().enumerate() {
+        if i % super::CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let row = row?;
+        let value = |idx: usize| row.get(idx).and_then(|v| record.number(v));
+        let Some(sex) = row.get(sex_index).map(str::trim) else { continue };
+        let key = sex.to_ascii_lowercase();
+        let Some(target) = value(target_index).filter(|_| !MISSING.contains(&key.as_str())) else {
+            continue;
+        };
+        spellings.entry(key.clone()).or_insert_with(|| sex.to_string());
+        rows.push((key, target, markers.iter().map(|(_, idx)| value(*idx)).collect()));
+    }
+
+    let mut keys: Vec<&String> = spellings.keys().collect();
+    keys.sort();
+    let [first, second] = keys[..] else {
+        if !keys.is_empty() {
+            warn!(column = %column, values = keys.len(), "Sex column does not have two values, skipping stratified analysis");
+        }
+        return Ok(None);
+    };
+    let samples = [first, second].map(|key| rows.iter().filter(|(s, _, _)| s == key).count());
+
+    let mut tested: Vec<SexInteraction> = markers
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, (candidate, _))| {
+            let pairs: Vec<(f64, f64, f64)> = rows
+                .iter()
+                .filter_map(|(sex, target, values)| Some((values[pos]?, *target, f64::from(u8::from(sex == second)))))
+                .collect();
+            let stratum = |code: f64| -> (Vec<f64>, Vec<f64>) {
+                pairs.iter().filter(|(_, _, s)| *s == code).map(|(x, y, _)| (*x, *y)).unzip()
+            };
+            let ((x0, y0), (x1, y1)) = (stratum(0.0), stratum(1.0));
+            if x0.len() < 3 || x1.len() < 3 {
+                return None;
+            }
+            let (interaction, interaction_p) = interaction_test(&pairs)?;
+            let (r0, r1) = (correlation(&x0, &y0), correlation(&x1, &y1));
+            Some(SexInteraction {
+                column: candidate.column.clone(),
+                label: candidate.display_label(),
+                n: [x0.len(), x1.len()],
+                r: [r0, r1],
+                p_value: [stats::correlation_p_value(r0, x0.len()), stats::correlation_p_value(r1, x1.len())],
+                interaction,
+                interaction_p,
+                interaction_q: 1.0,
+            })
+        })
+        .collect();
+    let p_values: Vec<f64> = tested.iter().map(|m| m.interaction_p).collect();
+    for (marker, q) in tested.iter_mut().zip(stats::benjamini_hochberg(&p_values)) {
+        marker.interaction_q = q;
+    }
+    tested.sort_by(|a, b| a.interaction_p.total_cmp(&b.interaction_p));
+
+    Ok(Some(SexStratified {
+        column,
+        strata: [spellings[first].clone(), spellings[second].clone()],
+        samples,
+        markers: tested,
+        thresholds,
+    }))
+}
+
+/// Interaction coefficient and its two-sided p-value in
+/// `y ~ x + s + x:s` over (x, y, s) triples; `None` when the model is
+/// singular or has no residual degrees of freedom
+fn interaction_test(pairs: &[(f64, f64, f64)]) -> Option<(f64, f64)> {
+    let n = pairs.len();
+    let p = 4;
+    if n <= p {
+        return None;
+    }
+    let data: Vec<f64> = pairs.iter().flat_map(|(x, _, s)| [1.0, *x, *s, x * s]).collect();
+    let design = DMatrix::from_row_slice(n, p, &data);
+    let y = DVector::from_iterator(n, pairs.iter().map(|(_, y, _)| *y));
+    let xtx_inv = (design.transpose() * &design).try_inverse()?;
+    let beta = &xtx_inv * design.transpose() * &y;
+    let residuals = &y - &design * &beta;
+    let df = (n - p) as f64;
+    let variance = residuals.norm_squared() / df;
+    let se = (variance * xtx_inv[(3, 3)]).sqrt();
+    if !se.is_finite() || se <= 0.0 {
+        return None;
+    }
+    Some((beta[3], stats::student_t_p_value(beta[3] / se, df)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interaction_detects_slope_difference() {
+        // Same slope in both strata: no interaction
+        let noise = |i: usize| ((i * 7919) % 13) as f64 / 13.0 - 0.5;
+        let same: Vec<(f64, f64, f64)> = (0..40)
+            .map(|i| {
+                let x = i as f64 / 4.0;
+                let s = (i % 2) as f64;
+                (x, 30.0 + 3.0 * x + 5.0 * s + noise(i), s)
+            })
+            .collect();
+        let (b3, p) = interaction_test(&same).unwrap();
+        assert!(b3.abs() < 0.2 && p > 0.05, "b3 {} p {}", b3, p);
+
+        // Slope 3 in stratum 0, -1 in stratum 1
+        let differing: Vec<(f64, f64, f64)> = same
+            .iter()
+            .map(|&(x, y, s)| (x, y - 4.0 * x * s, s))
+            .collect();
+        let (b3, p) = interaction_test(&differing).unwrap();
+        assert!((b3 + 4.0).abs() < 0.2 && p < 1e-6, "b3 {} p {}", b3, p);
+
+        assert_eq!(sex_column(&["age".to_string(), " Gender".to_string()]), Some(1));
+        assert_eq!(sex_column(&["sex_m".to_string()]), None);
+    }
+}
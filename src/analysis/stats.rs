@@ -0,0 +1,640 @@
+//! Statistical distributions and multiple-testing correction
+//!
+//! Small, dependency-free implementations of the special functions needed
+//! for significance tests (Numerical Recipes style), and the correlation
+//! coefficients markers are ranked by.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Correlation coefficient used to rank markers and draw the heatmap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CorrelationMethod {
+    /// Linear association
+    #[default]
+    Pearson,
+    /// Monotonic association: Pearson correlation of the ranks
+    Spearman,
+    /// Monotonic association from concordant and discordant pairs (tau-b)
+    Kendall,
+}
+
+impl CorrelationMethod {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Pearson => "pearson",
+            Self::Spearman => "spearman",
+            Self::Kendall => "kendall",
+        }
+    }
+
+    /// "Pearson", "Spearman" or "Kendall"
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pearson => "Pearson",
+            Self::Spearman => "Spearman",
+            Self::Kendall => "Kendall",
+        }
+    }
+
+    /// Methods wording: the coefficient and where its p-value comes from
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Pearson => "Pearson correlation",
+            Self::Spearman => "Spearman rank correlation",
+            Self::Kendall => "Kendall rank correlation (tau-b)",
+        }
+    }
+
+    pub fn p_value_source(self) -> &'static str {
+        match self {
+            Self::Pearson | Self::Spearman => "the t distribution",
+            Self::Kendall => "the normal approximation",
+        }
+    }
+
+    /// Coefficient over the first `min(x.len(), y.len())` pairs
+    pub fn coefficient(self, x: &[f64], y: &[f64]) -> f64 {
+        let n = x.len().min(y.len());
+        let (x, y) = (&x[..n], &y[..n]);
+        match self {
+            Self::Pearson => super::correlation(x, y),
+            Self::Spearman => super::correlation(&ranks(x), &ranks(y)),
+            Self::Kendall => kendall_tau_b(x, y),
+        }
+    }
+
+    /// Two-sided p-value of `coefficient` over `n` pairs
+    pub fn p_value(self, coefficient: f64, n: usize) -> f64 {
+        match self {
+            Self::Pearson | Self::Spearman => correlation_p_value(coefficient, n),
+            Self::Kendall => {
+                if n < 3 || coefficient.is_nan() {
+                    return 1.0;
+                }
+                let n = n as f64;
+                normal_p_value(3.0 * coefficient * (n * (n - 1.0)).sqrt() / (2.0 * (2.0 * n + 5.0)).sqrt())
+            }
+        }
+    }
+}
+
+impl fmt::Display for CorrelationMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for CorrelationMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "pearson" => Ok(Self::Pearson),
+            "spearman" => Ok(Self::Spearman),
+            "kendall" => Ok(Self::Kendall),
+            other => Err(format!(
+                "unknown correlation method '{}' (expected pearson, spearman or kendall)",
+                other
+            )),
+        }
+    }
+}
+
+/// Significance cut-offs applied to every test of the analysis: marker
+/// associations, group comparisons, interaction tests and GO enrichment
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    /// Nominal two-sided p-value cut-off
+    pub alpha: f64,
+    /// Benjamini–Hochberg q-value (false discovery rate) cut-off
+    pub fdr: f64,
+    /// Smallest |r| (partial r when adjusted) of a significant marker
+    /// association; 0 disables the effect-size filter
+    pub min_effect: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            alpha: 0.05,
+            fdr: 0.05,
+            min_effect: 0.0,
+        }
+    }
+}
+
+impl Thresholds {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("alpha", self.alpha), ("fdr", self.fdr)] {
+            if !(value > 0.0 && value <= 1.0) {
+                return Err(format!("{} must be in (0, 1], got {}", name, value));
+            }
+        }
+        if !(0.0..1.0).contains(&self.min_effect) {
+            return Err(format!("min_effect must be in [0, 1), got {}", self.min_effect));
+        }
+        Ok(())
+    }
+
+    /// Whether a test with p-value `p` and BH q-value `q` passes
+    pub fn significant(&self, p: f64, q: f64) -> bool {
+        p < self.alpha && q < self.fdr
+    }
+
+    /// Whether a marker association with coefficient `r` passes, effect size
+    /// included
+    pub fn passes(&self, p: f64, q: f64, r: f64) -> bool {
+        self.significant(p, q) && r.abs() >= self.min_effect
+    }
+
+    /// The p- and q-value cut-offs as quoted, naming the statistics `p` and
+    /// `q` ("q_int < 0.05"). The p cut-off is left out when it is implied:
+    /// a BH q-value is never below its p-value.
+    pub fn criterion_for(&self, p: &str, q: &str) -> String {
+        if self.alpha < self.fdr {
+            format!("{} < {} and {} < {}", p, self.alpha, q, self.fdr)
+        } else {
+            format!("{} < {}", q, self.fdr)
+        }
+    }
+
+    /// Cut-offs of tests without an effect-size filter: "q < 0.05"
+    pub fn criterion(&self) -> String {
+        self.criterion_for("p", "q")
+    }
+
+    /// Cut-offs of marker associations: "q < 0.05 and |r| ≥ 0.3"
+    pub fn marker_criterion(&self) -> String {
+        if self.min_effect > 0.0 {
+            format!("{} and |r| ≥ {}", self.criterion(), self.min_effect)
+        } else {
+            self.criterion()
+        }
+    }
+
+    /// Methods sentence recording the cut-offs
+    pub fn methods(&self) -> String {
+        let mut text = format!(
+            "Marker associations, group comparisons, interaction tests and GO terms were called significant at a \
+             two-sided alpha of {} and a Benjamini-Hochberg false discovery rate of {} ({}).",
+            self.alpha,
+            self.fdr,
+            self.criterion()
+        );
+        if self.min_effect > 0.0 {
+            text.push_str(&format!(
+                " Marker associations additionally required an absolute correlation of at least {}.",
+                self.min_effect
+            ));
+        }
+        text
+    }
+}
+
+/// 1-based ranks, ties sharing their average rank
+pub fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&i, &j| values[i].total_cmp(&values[j]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &idx in &order[start..end] {
+            ranks[idx] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+/// Kendall's tau-b, which corrects for ties in either variable
+fn kendall_tau_b(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len().min(y.len());
+    let (mut score, mut tied_x, mut tied_y, mut pairs) = (0i64, 0u64, 0u64, 0u64);
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let dx = x[i].total_cmp(&x[j]) as i64;
+            let dy = y[i].total_cmp(&y[j]) as i64;
+            pairs += 1;
+            tied_x += u64::from(dx == 0);
+            tied_y += u64::from(dy == 0);
+            score += dx * dy;
+        }
+    }
+    let denominator = (((pairs - tied_x) as f64) * ((pairs - tied_y) as f64)).sqrt();
+    if denominator == 0.0 {
+        0.0
+    } else {
+        score as f64 / denominator
+    }
+}
+
+/// Complementary error function (Chebyshev fit, relative error < 1.2e-7)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98 + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let r = t * poly.exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+/// Two-sided p-value of a standard normal `z`
+pub fn normal_p_value(z: f64) -> f64 {
+    if z.is_nan() {
+        return 1.0;
+    }
+    erfc(z.abs() / std::f64::consts::SQRT_2).clamp(0.0, 1.0)
+}
+
+/// Natural log of the gamma function (Lanczos approximation, x > 0)
+pub fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.120_865_097_386_617_9e-2,
+        -0.539_523_938_495_3e-5,
+    ];
+    let mut y = x;
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000_000_000_190_015;
+    for c in COEFFICIENTS {
+        y += 1.0;
+        series += c / y;
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+/// Regularized incomplete beta function I_x(a, b)
+pub fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 300;
+    const EPSILON: f64 = 3e-14;
+    const TINY: f64 = 1e-300;
+    let guard = |v: f64| if v.abs() < TINY { TINY } else { v };
+
+    let (qab, qap, qam) = (a + b, a + 1.0, a - 1.0);
+    let mut c = 1.0;
+    let mut d = 1.0 / guard(1.0 - qab * x / qap);
+    let mut h = d;
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1.0 / guard(1.0 + aa * d);
+        c = guard(1.0 + aa / c);
+        h *= d * c;
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1.0 / guard(1.0 + aa * d);
+        c = guard(1.0 + aa / c);
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
+
+/// Regularized upper incomplete gamma function Q(a, x): series below
+/// x = a + 1, continued fraction above
+pub fn upper_incomplete_gamma(a: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 300;
+    const EPSILON: f64 = 3e-14;
+    const TINY: f64 = 1e-300;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let front = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        let (mut term, mut sum, mut ap) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..MAX_ITERATIONS {
+            ap += 1.0;
+            term *= x / ap;
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        return (1.0 - sum * front).clamp(0.0, 1.0);
+    }
+    let guard = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / TINY;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..=MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = 1.0 / guard(an * d + b);
+        c = guard(b + an / c);
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    (front * h).clamp(0.0, 1.0)
+}
+
+/// Upper-tail p-value of a chi-squared statistic with `df` degrees of freedom
+pub fn chi_squared_p_value(x: f64, df: f64) -> f64 {
+    if x.is_nan() || df <= 0.0 {
+        return 1.0;
+    }
+    if x.is_infinite() {
+        return 0.0;
+    }
+    upper_incomplete_gamma(df / 2.0, x / 2.0)
+}
+
+/// Upper-tail p-value of an F statistic with `df1` and `df2` degrees of
+/// freedom
+pub fn f_p_value(f: f64, df1: f64, df2: f64) -> f64 {
+    if f.is_nan() || df1 <= 0.0 || df2 <= 0.0 {
+        return 1.0;
+    }
+    if f.is_infinite() {
+        return 0.0;
+    }
+    incomplete_beta(df2 / 2.0, df1 / 2.0, df2 / (df2 + df1 * f)).clamp(0.0, 1.0)
+}
+
+/// Kruskal-Wallis H across `groups` (tie-corrected) and its chi-squared
+/// p-value with one degree of freedom fewer than the non-empty groups
+pub fn kruskal_wallis(groups: &[Vec<f64>]) -> (f64, f64) {
+    let groups: Vec<&Vec<f64>> = groups.iter().filter(|g| !g.is_empty()).collect();
+    let mut pooled: Vec<f64> = groups.iter().flat_map(|g| g.iter().copied()).collect();
+    let n = pooled.len() as f64;
+    if groups.len() < 2 || n < 3.0 {
+        return (0.0, 1.0);
+    }
+    let ranks = ranks(&pooled);
+    let mut start = 0;
+    let mut sum = 0.0;
+    for group in &groups {
+        let rank_sum: f64 = ranks[start..start + group.len()].iter().sum();
+        sum += rank_sum * rank_sum / group.len() as f64;
+        start += group.len();
+    }
+    pooled.sort_by(f64::total_cmp);
+    let ties: f64 = pooled
+        .chunk_by(|x, y| x == y)
+        .map(|tied| (tied.len() as f64).powi(3) - tied.len() as f64)
+        .sum();
+    let correction = 1.0 - ties / (n * n * n - n);
+    if correction <= 0.0 {
+        return (0.0, 1.0);
+    }
+    let h = (12.0 / (n * (n + 1.0)) * sum - 3.0 * (n + 1.0)) / correction;
+    (h, chi_squared_p_value(h, (groups.len() - 1) as f64))
+}
+
+/// Two-sided p-value of Student's t statistic with `df` degrees of freedom
+pub fn student_t_p_value(t: f64, df: f64) -> f64 {
+    if t.is_nan() || df <= 0.0 {
+        return 1.0;
+    }
+    if t.is_infinite() {
+        return 0.0;
+    }
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t)).clamp(0.0, 1.0)
+}
+
+/// Two-sided p-value for a Pearson correlation `r` over `n` pairs
+pub fn correlation_p_value(r: f64, n: usize) -> f64 {
+    if n < 3 || r.is_nan() {
+        return 1.0;
+    }
+    if r.abs() >= 1.0 {
+        return 0.0;
+    }
+    let df = (n - 2) as f64;
+    student_t_p_value(r * (df / (1.0 - r * r)).sqrt(), df)
+}
+
+/// Welch's t test of a sample against another from their means, sample
+/// variances and sizes: t of `a` minus `b`, its Welch-Satterthwaite
+/// degrees of freedom and two-sided p-value. (0, 0, 1) when neither sample
+/// varies or either has fewer than two values.
+pub fn welch_t_test(mean_a: f64, var_a: f64, n_a: usize, mean_b: f64, var_b: f64, n_b: usize) -> (f64, f64, f64) {
+    if n_a < 2 || n_b < 2 {
+        return (0.0, 0.0, 1.0);
+    }
+    let (a, b) = (var_a / n_a as f64, var_b / n_b as f64);
+    let se = (a + b).sqrt();
+    if se <= 0.0 {
+        return (0.0, 0.0, 1.0);
+    }
+    let df = (a + b).powi(2) / (a * a / (n_a - 1) as f64 + b * b / (n_b - 1) as f64);
+    let t = (mean_a - mean_b) / se;
+    (t, df, student_t_p_value(t, df))
+}
+
+/// Mann-Whitney U of `a` against `b` and its two-sided p-value (normal
+/// approximation with tie and continuity corrections)
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> (f64, f64) {
+    if a.is_empty() || b.is_empty() {
+        return (0.0, 1.0);
+    }
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let mut pooled: Vec<f64> = a.iter().chain(b).copied().collect();
+    let rank_sum: f64 = ranks(&pooled)[..a.len()].iter().sum();
+    let u = rank_sum - n_a * (n_a + 1.0) / 2.0;
+    pooled.sort_by(f64::total_cmp);
+    let ties: f64 = pooled
+        .chunk_by(|x, y| x == y)
+        .map(|tied| (tied.len() as f64).powi(3) - tied.len() as f64)
+        .sum();
+    let n = n_a + n_b;
+    let variance = n_a * n_b / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return (u, 1.0);
+    }
+    let z = ((u - n_a * n_b / 2.0).abs() - 0.5).max(0.0) / variance.sqrt();
+    (u, normal_p_value(z))
+}
+
+/// Cohen's d of `a` minus `b` in units of the pooled standard deviation,
+/// 0 when that is zero
+pub fn cohens_d(mean_a: f64, var_a: f64, n_a: usize, mean_b: f64, var_b: f64, n_b: usize) -> f64 {
+    if n_a + n_b < 3 {
+        return 0.0;
+    }
+    let pooled = ((n_a.saturating_sub(1) as f64 * var_a + n_b.saturating_sub(1) as f64 * var_b)
+        / (n_a + n_b - 2) as f64)
+        .sqrt();
+    if pooled > 0.0 {
+        (mean_a - mean_b) / pooled
+    } else {
+        0.0
+    }
+}
+
+/// ln C(n, k)
+fn ln_choose(n: usize, k: usize) -> f64 {
+    ln_gamma(n as f64 + 1.0) - ln_gamma(k as f64 + 1.0) - ln_gamma((n - k) as f64 + 1.0)
+}
+
+/// Upper-tail hypergeometric p-value P(X >= k): the chance of drawing at
+/// least `k` of the `successes` items when drawing `draws` from `population`
+pub fn hypergeometric_p_value(k: usize, population: usize, successes: usize, draws: usize) -> f64 {
+    if k == 0 {
+        return 1.0;
+    }
+    let max = successes.min(draws);
+    if k > max || draws > population || successes > population {
+        return 0.0;
+    }
+    let total = ln_choose(population, draws);
+    let p: f64 = (k..=max)
+        .filter(|i| draws - i <= population - successes)
+        .map(|i| (ln_choose(successes, i) + ln_choose(population - successes, draws - i) - total).exp())
+        .sum();
+    p.clamp(0.0, 1.0)
+}
+
+/// Benjamini–Hochberg adjusted p-values (q-values), in input order
+pub fn benjamini_hochberg(p_values: &[f64]) -> Vec<f64> {
+    let m = p_values.len();
+    let mut order: Vec<usize> = (0..m).collect();
+    order.sort_by(|&a, &b| p_values[a].partial_cmp(&p_values[b]).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut q_values = vec![1.0; m];
+    let mut running_min: f64 = 1.0;
+    for (rank, &idx) in order.iter().enumerate().rev() {
+        let adjusted = p_values[idx] * m as f64 / (rank + 1) as f64;
+        running_min = running_min.min(adjusted);
+        q_values[idx] = running_min.clamp(0.0, 1.0);
+    }
+    q_values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_student_t_p_value() {
+        assert!((student_t_p_value(2.0, 10.0) - 0.0734).abs() < 1e-4);
+        assert!((student_t_p_value(2.228_139, 10.0) - 0.05).abs() < 1e-5);
+        assert_eq!(student_t_p_value(0.0, 5.0), 1.0);
+    }
+
+    #[test]
+    fn test_correlation_p_value() {
+        assert!((correlation_p_value(0.5, 20) - 0.0248).abs() < 1e-3);
+        assert_eq!(correlation_p_value(1.0, 10), 0.0);
+        assert_eq!(correlation_p_value(0.9, 2), 1.0);
+    }
+
+    #[test]
+    fn test_rank_correlations() {
+        // Monotonic but not linear: both rank coefficients are exactly 1
+        let x: Vec<f64> = (1..=10).map(f64::from).collect();
+        let y: Vec<f64> = x.iter().map(|v| v.powi(3)).collect();
+        assert!(CorrelationMethod::Pearson.coefficient(&x, &y) < 0.95);
+        assert!((CorrelationMethod::Spearman.coefficient(&x, &y) - 1.0).abs() < 1e-12);
+        assert!((CorrelationMethod::Kendall.coefficient(&x, &y) - 1.0).abs() < 1e-12);
+        // Eight concordant, one discordant and one tied-in-y pair of ten:
+        // tau-b = (8 - 1) / sqrt(10 * 9)
+        let tau = CorrelationMethod::Kendall.coefficient(&[1.0, 2.0, 3.0, 4.0, 5.0], &[1.0, 3.0, 2.0, 5.0, 5.0]);
+        assert!((tau - 7.0 / 90f64.sqrt()).abs() < 1e-12, "tau {}", tau);
+        assert!((normal_p_value(1.959_964) - 0.05).abs() < 1e-6);
+        assert_eq!("Spearman".parse::<CorrelationMethod>(), Ok(CorrelationMethod::Spearman));
+    }
+
+    #[test]
+    fn test_hypergeometric_p_value() {
+        // 3 of 5 hits among 10 annotated of 50: sum of exact terms
+        assert!((hypergeometric_p_value(3, 50, 10, 5) - 0.048_260).abs() < 1e-5);
+        assert_eq!(hypergeometric_p_value(0, 50, 10, 5), 1.0);
+        assert!((hypergeometric_p_value(5, 10, 5, 5) - 1.0 / 252.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_benjamini_hochberg() {
+        let q = benjamini_hochberg(&[0.01, 0.04, 0.03, 0.005]);
+        let expected = [0.02, 0.04, 0.04, 0.02];
+        for (q, e) in q.iter().zip(expected) {
+            assert!((q - e).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_two_sample_tests() {
+        let (a, b) = ([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]);
+        let (t, df, p) = welch_t_test(2.0, 1.0, 3, 5.0, 1.0, 3);
+        assert!((t + 3.674_235).abs() < 1e-6);
+        assert!((df - 4.0).abs() < 1e-12);
+        assert!((p - 0.021_311).abs() < 1e-5, "{}", p);
+        // Normal approximation with continuity correction, as in R's wilcox.test(exact = FALSE)
+        let (u, p) = mann_whitney_u(&a, &b);
+        assert_eq!(u, 0.0);
+        assert!((p - 0.080_856).abs() < 1e-5, "{}", p);
+        assert_eq!(mann_whitney_u(&b, &a).0, 9.0);
+        assert!((cohens_d(2.0, 1.0, 3, 5.0, 1.0, 3) + 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_anova_distributions() {
+        // With df1 = 2 the F tail is (1 + 2F/df2)^(-df2/2), the chi-squared tail exp(-x/2)
+        assert!((f_p_value(27.0, 2.0, 6.0) - 0.001).abs() < 1e-9);
+        assert!((chi_squared_p_value(7.2, 2.0) - (-3.6f64).exp()).abs() < 1e-9);
+        assert!((chi_squared_p_value(1.0, 1.0) - 0.317_310_5).abs() < 1e-6);
+        let groups = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0], vec![7.0, 8.0, 9.0]];
+        let (h, p) = kruskal_wallis(&groups);
+        assert!((h - 7.2).abs() < 1e-9 && (p - (-3.6f64).exp()).abs() < 1e-9, "{} {}", h, p);
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let defaults = Thresholds::default();
+        assert!(defaults.passes(0.01, 0.04, 0.1));
+        assert!(!defaults.passes(0.01, 0.06, 0.9));
+        assert_eq!(defaults.marker_criterion(), "q < 0.05");
+
+        let strict = Thresholds { alpha: 0.01, fdr: 0.1, min_effect: 0.3 };
+        assert!(!strict.passes(0.02, 0.05, 0.5));
+        assert!(!strict.passes(0.005, 0.05, -0.2));
+        assert!(strict.passes(0.005, 0.05, -0.4));
+        assert_eq!(strict.marker_criterion(), "p < 0.01 and q < 0.1 and |r| ≥ 0.3");
+        assert!(Thresholds { fdr: 0.0, ..defaults }.validate().is_err());
+        assert!(Thresholds { min_effect: 1.0, ..defaults }.validate().is_err());
+    }
+}
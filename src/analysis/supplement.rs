@@ -0,0 +1,495 @@
+//! Supplementary materials bundle
+//!
+//! Packs the full biomarker table, the plots, the analysis as JSON, a
+//! script that reruns the analysis, the run's manifest and a README into
+//! `supplementary.zip`.
+//! The manuscript's Supplementary Materials section lists the same items
+//! under the same labels (Table S1, Figure S1, ...).
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::gsea::GSEA_FILE;
+use super::survival::COX_FILE;
+use super::report::{
+    self, GO_ENRICHMENT_FILE, KMEANS_FILE, PATHWAY_ENRICHMENT_FILE, PCA_LOADINGS_FILE, REGULARIZED_FILE, SAMPLE_QC_FILE,
+};
+use super::manifest::{self, MANIFEST_FILE};
+use super::memory::MemoryLimit;
+use super::impute::Imputation;
+use super::regularized::{Penalty, Regularization};
+use super::roc::RocSplit;
+use super::{AnalysisArtifacts, AnalysisConfig};
+use crate::data_registry::{DatasetRecord, ParseOptions};
+use crate::manuscript::JournalTemplate;
+use crate::utils::number::NumberFormat;
+
+/// Name of the bundle next to the other analysis artifacts
+pub const SUPPLEMENT_FILE: &str = "supplementary.zip";
+
+const BIOMARKER_TABLE: &str = "biomarker_candidates.csv";
+const ANALYSIS_JSON: &str = "analysis.json";
+const REPRODUCE_SCRIPT: &str = "reproduce.sh";
+const README_FILE: &str = "README.txt";
+
+/// One labelled file of the bundle
+#[derive(Debug, Clone, PartialEq)]
+pub struct SupplementItem {
+    /// "Table S1", "Figure S2", ...
+    pub label: String,
+    /// File name inside the zip
+    pub file: String,
+    pub description: String,
+}
+
+/// Bundle contents for `analysis`, in manuscript order. Figures are listed
+/// only when the analysis produced them.
+pub fn items(analysis: &AnalysisArtifacts) -> Vec<SupplementItem> {
+    let item = |label: &str, file: &str, description: &str| SupplementItem {
+        label: label.to_string(),
+        file: file.to_string(),
+        description: description.to_string(),
+    };
+    let mut items = vec![item(
+        "Table S1",
+        BIOMARKER_TABLE,
+        &format!(
+            "All biomarker candidates ranked by {}, with n, r, p-value, BH q-value and direction",
+            analysis.ranking()
+        ),
+    )];
+    if !analysis.enrichment.is_empty() {
+        items.push(item(
+            "Table S2",
+            GO_ENRICHMENT_FILE,
+            "GO terms tested for enrichment among the significant biomarkers, with overlap, fold enrichment, \
+             hypergeometric p-value and BH q-value",
+        ));
+    }
+    if !analysis.pathways.is_empty() {
+        items.push(item(
+            &format!("Table S{}", items.len() + 1),
+            PATHWAY_ENRICHMENT_FILE,
+            "Reactome and KEGG pathways tested for enrichment among the significant biomarkers, with overlap, \
+             fold enrichment, hypergeometric p-value and BH q-value",
+        ));
+    }
+    if let Some(gsea) = &analysis.gsea {
+        items.push(item(
+            &format!("Table S{}", items.len() + 1),
+            GSEA_FILE,
+            &format!(
+                "Gene sets tested by GSEA along the ranking by correlation with {}, with ES, NES, permutation \
+                 p-value, BH q-value and leading-edge genes",
+                gsea.target
+            ),
+        ));
+    }
+    if let Some(survival) = &analysis.survival {
+        items.push(item(
+            &format!("Table S{}", items.len() + 1),
+            COX_FILE,
+            &format!(
+                "Univariate Cox proportional hazards models of {} with event {}: hazard ratio per unit of each \
+                 marker, Wald 95% CI, p-value and BH q-value",
+                survival.time, survival.event
+            ),
+        ));
+    }
+    if analysis.sample_qc.is_some() {
+        items.push(item(
+            &format!("Table S{}", items.len() + 1),
+            SAMPLE_QC_FILE,
+            "Per-sample missing-value rate, median intensity, IQR and QC flags",
+        ));
+    }
+    if analysis.pca.is_some() {
+        items.push(item(
+            &format!("Table S{}", items.len() + 1),
+            PCA_LOADINGS_FILE,
+            "Loadings of the scaled markers on the leading principal components",
+        ));
+    }
+    if analysis.kmeans.is_some() {
+        items.push(item(
+            &format!("Table S{}", items.len() + 1),
+            KMEANS_FILE,
+            "K-means cluster of each sample, with its distance to the centroid and silhouette width",
+        ));
+    }
+    if let Some(model) = &analysis.regularized {
+        items.push(item(
+            &format!("Table S{}", items.len() + 1),
+            REGULARIZED_FILE,
+            &format!(
+                "Intercept and non-zero coefficients of the {} model of {}, per unit and per standard deviation of each marker",
+                model.penalty, model.target
+            ),
+        ));
+    }
+    let figures = [
+        (&analysis.heatmap_path, "Correlation heatmap of the analyzed markers"),
+        (&analysis.expression_heatmap_path, "Row-scaled expression of the top markers across samples, by group"),
+        (&analysis.boxplot_path, "Marker distribution by group"),
+        (&analysis.volcano_path, "Difference of means against Welch p-value between the two groups"),
+        (&analysis.roc_path, "ROC curves of the candidates that best separate the two classes of the target"),
+        (&analysis.survival_path, "Kaplan-Meier survival curves, with censored samples marked"),
+        (&analysis.enrichment_plot_path, "GO terms enriched among the significant biomarkers"),
+        (&analysis.pca_scree_path, "Variance explained by the principal components"),
+        (&analysis.pca_scores_path, "Samples on the first two principal components, by group"),
+        (&analysis.normalization_plot_path, "Marker values of each sample before and after normalization"),
+    ];
+    let mut figure = 0;
+    for (path, description) in figures {
+        let Some(name) = path.as_deref().and_then(|p| Path::new(p).file_name()).and_then(|n| n.to_str()) else {
+            continue;
+        };
+        figure += 1;
+        items.push(item(&format!("Figure S{}", figure), name, description));
+    }
+    items.push(item(
+        "Data S1",
+        ANALYSIS_JSON,
+        "Analysis parameters, descriptive statistics, regressions, novelty scores and biomarker candidates",
+    ));
+    items.push(item("Code S1", REPRODUCE_SCRIPT, "Shell script that reruns this analysis with oxidized-bio"));
+    items
+}
+
+/// Body of the manuscript's Supplementary Materials section
+pub fn manuscript_section(analysis: &AnalysisArtifacts) -> String {
+    let mut section = format!("Supplementary materials are provided in {}:", SUPPLEMENT_FILE);
+    for item in items(analysis) {
+        section.push_str(&format!("\n- {} ({}): {}.", item.label, item.file, item.description));
+    }
+    section
+}
+
+/// Write `supplementary.zip` to `output_dir` and return its path
+pub fn write_supplement(
+    output_dir: &Path,
+    record: &DatasetRecord,
+    config: &AnalysisConfig,
+    analysis: &AnalysisArtifacts,
+    template: JournalTemplate,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create {}", output_dir.display()))?;
+    let path = output_dir.join(SUPPLEMENT_FILE);
+    let file = std::fs::File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for item in items(analysis) {
+        let contents = match item.file.as_str() {
+            BIOMARKER_TABLE => report::csv_bytes(|wtr| report::biomarker_rows(wtr, &analysis.biomarker_candidates))?,
+            GO_ENRICHMENT_FILE => report::csv_bytes(|wtr| report::enrichment_rows(wtr, &analysis.enrichment))?,
+            PATHWAY_ENRICHMENT_FILE => report::csv_bytes(|wtr| report::enrichment_rows(wtr, &analysis.pathways))?,
+            GSEA_FILE => {
+                let gsea = analysis.gsea.as_ref().context("GSEA missing")?;
+                report::csv_bytes(|wtr| report::gsea_rows(wtr, gsea))?
+            }
+            COX_FILE => {
+                let survival = analysis.survival.as_ref().context("survival analysis missing")?;
+                report::csv_bytes(|wtr| report::cox_rows(wtr, survival))?
+            }
+            SAMPLE_QC_FILE => {
+                let qc = analysis.sample_qc.as_ref().context("sample QC missing")?;
+                report::csv_bytes(|wtr| report::sample_qc_rows(wtr, qc))?
+            }
+            PCA_LOADINGS_FILE => {
+                let pca = analysis.pca.as_ref().context("PCA missing")?;
+                report::csv_bytes(|wtr| report::pca_loading_rows(wtr, pca))?
+            }
+            KMEANS_FILE => {
+                let kmeans = analysis.kmeans.as_ref().context("k-means clusters missing")?;
+                report::csv_bytes(|wtr| report::kmeans_rows(wtr, kmeans))?
+            }
+            REGULARIZED_FILE => {
+                let model = analysis.regularized.as_ref().context("regularized model missing")?;
+                report::csv_bytes(|wtr| report::regularized_rows(wtr, model))?
+            }
+            ANALYSIS_JSON => serde_json::to_vec_pretty(&analysis_json(record, config, analysis, template))?,
+            REPRODUCE_SCRIPT => reproduce_script(record, config, template).into_bytes(),
+            _ => {
+                let figure = analysis
+                    .figure_paths()
+                    .into_iter()
+                    .find(|p| Path::new(p).file_name().and_then(|n| n.to_str()) == Some(item.file.as_str()))
+                    .context("figure path missing")?;
+                std::fs::read(figure).with_context(|| format!("Failed to read {}", figure))?
+            }
+        };
+        let options = if item.file == REPRODUCE_SCRIPT {
+            options.unix_permissions(0o755)
+        } else {
+            options
+        };
+        zip.start_file(item.file.as_str(), options)?;
+        zip.write_all(&contents)?;
+    }
+    if let Some(manifest) = &analysis.manifest {
+        zip.start_file(MANIFEST_FILE, options)?;
+        zip.write_all(manifest.to_json()?.as_bytes())?;
+    }
+    zip.start_file(README_FILE, options)?;
+    zip.write_all(readme(record, analysis).as_bytes())?;
+    zip.finish()?;
+    Ok(path)
+}
+
+fn analysis_json(
+    record: &DatasetRecord,
+    config: &AnalysisConfig,
+    analysis: &AnalysisArtifacts,
+    template: JournalTemplate,
+) -> serde_json::Value {
+    let mut parameters = manifest::parameters(config);
+    parameters["template"] = serde_json::json!(template);
+    serde_json::json!({
+        "dataset": {
+            "id": record.dataset.id,
+            "filename": record.dataset.filename,
+            "rows": record.row_count,
+            "columns": record.columns.len(),
+            "species": record.species,
+            "parsing": record.parsing,
+        },
+        "parameters": parameters,
+        "probe_collapse": analysis.probe_collapse,
+        "replicates": analysis.replicates,
+        "imputation": analysis.imputation,
+        "normalization": analysis.normalization,
+        "batch": analysis.batch,
+        "sample_qc": analysis.sample_qc,
+        "summary": analysis.summary,
+        "descriptive_stats": analysis.descriptive_stats,
+        "regressions": analysis.regressions,
+        "novelty_scores": analysis.novelty_scores,
+        "biomarker_candidates": analysis.biomarker_candidates,
+        "contrast": analysis.contrast,
+        "anova": analysis.anova,
+        "pca": analysis.pca,
+        "kmeans": analysis.kmeans,
+        "regularized": analysis.regularized,
+        "roc": analysis.roc,
+        "survival": analysis.survival,
+        "go_enrichment": analysis.enrichment,
+        "pathway_enrichment": analysis.pathways,
+        "gsea": analysis.gsea,
+        "cross_reference": analysis.cross_reference,
+    })
+}
+
+/// `oxidized-bio analyze` invocation with the parameters of this analysis.
+/// The dataset path defaults to the original file name and can be passed as
+/// the first argument.
+pub fn reproduce_script(record: &DatasetRecord, config: &AnalysisConfig, template: JournalTemplate) -> String {
+    let mut args = vec!["--input \"$DATASET\"".to_string(), format!("--species {}", record.species)];
+    let parsing = &record.parsing;
+    let defaults = ParseOptions::default();
+    if parsing.decimal != defaults.decimal {
+        args.push(format!("--decimal {}", shell_quote(&parsing.decimal.to_string())));
+    }
+    if parsing.na_values != defaults.na_values {
+        args.push(format!("--na {}", shell_quote(&parsing.na_values.join(","))));
+    }
+    if parsing.quote != defaults.quote {
+        args.push(format!("--quote {}", shell_quote(&parsing.quote.to_string())));
+    }
+    if let Some(target) = &config.target_column {
+        args.push(format!("--target {}", shell_quote(target)));
+    }
+    if let Some(group) = &config.group_column {
+        args.push(format!("--group {}", shell_quote(group)));
+    }
+    if !config.covariates.is_empty() {
+        args.push(format!("--covariates {}", shell_quote(&config.covariates.join(","))));
+    }
+    if let Some(formula) = &config.formula {
+        args.push(format!("--formula {}", shell_quote(&formula.to_string())));
+    }
+    if let Some(boxplot) = &config.boxplot_column {
+        args.push(format!("--boxplot {}", shell_quote(boxplot)));
+    }
+    if !config.markers.is_empty() {
+        args.push(format!("--markers {}", shell_quote(&config.markers.join(","))));
+    }
+    if let Some(platform) = &config.platform {
+        args.push(format!("--platform {}", shell_quote(&platform.to_string_lossy())));
+        args.push(format!("--collapse {}", config.collapse));
+    }
+    args.push(format!("--replicates {}", config.replicates));
+    if config.imputation != Imputation::Off {
+        args.push(format!("--impute {}", config.imputation));
+    }
+    if !config.normalization.is_empty() {
+        let steps: Vec<&str> = config.normalization.iter().map(|s| s.id()).collect();
+        args.push(format!("--normalize {}", steps.join(",")));
+    }
+    if let Some(batch) = &config.batch_column {
+        args.push(format!("--batch {}", shell_quote(batch)));
+        args.push(format!("--batch-correction {}", config.batch_correction));
+    }
+    args.push(format!("--correlation {}", config.correlation));
+    args.push(format!("--alpha {}", config.thresholds.alpha));
+    args.push(format!("--fdr {}", config.thresholds.fdr));
+    args.push(format!("--min-effect {}", config.thresholds.min_effect));
+    if config.kruskal_wallis {
+        args.push("--kruskal-wallis".to_string());
+    }
+    args.push(format!("--tables {}", config.tables));
+    if config.numbers != NumberFormat::Plain {
+        args.push(format!("--numbers {}", config.numbers));
+    }
+    if config.memory_limit != MemoryLimit::Auto {
+        args.push(format!("--memory-limit {}", config.memory_limit));
+    }
+    let regularization = &config.regularization;
+    args.push(format!("--penalty {}", regularization.penalty));
+    if regularization.penalty == Penalty::ElasticNet {
+        args.push(format!("--l1-ratio {}", regularization.l1_ratio));
+    }
+    if let Some(lambda) = regularization.lambda {
+        args.push(format!("--lambda {}", lambda));
+    }
+    if regularization.folds != Regularization::default().folds {
+        args.push(format!("--penalty-folds {}", regularization.folds));
+    }
+    if config.roc != RocSplit::Auto {
+        args.push(format!("--roc-split {}", config.roc));
+    }
+    if !config.gene_sets.is_empty() {
+        let files: Vec<String> = config.gene_sets.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        args.push(format!("--gene-sets {}", shell_quote(&files.join(","))));
+    }
+    if let (Some(time), Some(event)) = (&config.time_column, &config.event_column) {
+        args.push(format!("--time-column {} --event-column {}", shell_quote(time), shell_quote(event)));
+    }
+    if let Some(subject) = &config.subject_column {
+        args.push(format!("--subject-column {}", shell_quote(subject)));
+    }
+    args.push(format!("--max-columns {}", config.max_columns));
+    args.push(format!("--max-groups {}", config.max_groups));
+    args.push(format!("--template {}", template.id()));
+    args.push("--out reproduced".to_string());
+
+    format!(
+        "#!/usr/bin/env sh\n\
+         # Reruns the analysis behind this supplement with oxidized-bio.\n\
+         # Dataset: {filename} ({rows} rows, {cols} columns)\n\
+         # Usage: sh {script} [path/to/{filename}]\n\
+         set -e\n\
+         DATASET=\"${{1:-{default}}}\"\n\
+         oxidized-bio analyze \\\n  {args}\n",
+        filename = record.dataset.filename,
+        rows = record.row_count,
+        cols = record.columns.len(),
+        script = REPRODUCE_SCRIPT,
+        default = record.dataset.filename.replace(['"', '$', '`', '\\'], "_"),
+        args = args.join(" \\\n  "),
+    )
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn readme(record: &DatasetRecord, analysis: &AnalysisArtifacts) -> String {
+    let mut readme = format!(
+        "Supplementary materials\n\
+         =======================\n\n\
+         Dataset: {} ({} rows, {} columns)\n\n\
+         {}\n\n\
+         Files\n-----\n",
+        record.dataset.filename,
+        record.row_count,
+        record.columns.len(),
+        analysis.summary,
+    );
+    for item in items(analysis) {
+        readme.push_str(&format!("{} - {}: {}.\n", item.file, item.label, item.description));
+    }
+    if analysis.manifest.is_some() {
+        readme.push_str(&format!(
+            "{} - Versions, dataset checksum, parameters, seeds and stage timings of the run.\n",
+            MANIFEST_FILE
+        ));
+    }
+    readme.push_str(&format!("{} - This file.\n", README_FILE));
+    readme
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifacts(heatmap: Option<&str>, boxplot: Option<&str>) -> AnalysisArtifacts {
+        AnalysisArtifacts {
+            descriptive_stats: Vec::new(),
+            regressions: Vec::new(),
+            novelty_scores: Vec::new(),
+            group_comparisons: Vec::new(),
+            contrast: None,
+            anova: Vec::new(),
+            biomarker_candidates: Vec::new(),
+            correlation: Default::default(),
+            thresholds: Default::default(),
+            numbers: Default::default(),
+            summary: String::new(),
+            heatmap_path: heatmap.map(str::to_string),
+            expression_heatmap_path: None,
+            boxplot_path: boxplot.map(str::to_string),
+            volcano_path: None,
+            roc: None,
+            roc_path: None,
+            survival: None,
+            survival_path: None,
+            enrichment: Vec::new(),
+            enrichment_plot_path: None,
+            pathways: Vec::new(),
+            gsea: None,
+            pca: None,
+            pca_scree_path: None,
+            pca_scores_path: None,
+            kmeans: None,
+            regularized: None,
+            figure_legends: Vec::new(),
+            probe_collapse: None,
+            replicates: None,
+            imputation: None,
+            normalization: None,
+            normalization_plot_path: None,
+            batch: None,
+            sample_qc: None,
+            sex_stratified: None,
+            adjustment: None,
+            cross_reference: None,
+            tables: None,
+            manifest: None,
+        }
+    }
+
+    #[test]
+    fn test_items_number_present_figures() {
+        let labels: Vec<(String, String)> = items(&artifacts(None, Some("out/boxplot.png")))
+            .into_iter()
+            .map(|i| (i.label, i.file))
+            .collect();
+        assert_eq!(labels[1], ("Figure S1".to_string(), "boxplot.png".to_string()));
+        assert_eq!(labels.len(), 4);
+
+        let section = manuscript_section(&artifacts(Some("out/heatmap.png"), Some("out/boxplot.png")));
+        assert!(section.contains("- Figure S2 (boxplot.png): Marker distribution by group."));
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("cell_type"), "'cell_type'");
+        assert_eq!(shell_quote("donor's age"), "'donor'\\''s age'");
+    }
+}
@@ -0,0 +1,610 @@
+//! Survival analysis
+//!
+//! For datasets with a time-to-event column and an event (censoring)
+//! column, Kaplan-Meier curves estimate the fraction of samples still free
+//! of the event over time, and each marker gets a univariate Cox
+//! proportional hazards model of the hazard of the event.
+//!
+//! The curves are split by the group column when it has two to
+//! [`MAX_CURVES`] levels, otherwise at the median of the marker with the
+//! strongest Cox association, and compared with the log-rank test. A split
+//! chosen that way is illustrative: the marker was picked for its
+//! association, so its log-rank p-value is optimistic.
+//!
+//! Cox models are fit by Newton-Raphson on the partial likelihood with
+//! Breslow's handling of tied times. Hazard ratios are per unit of the
+//! marker (a doubling on log2-scale data), with Wald 95% confidence
+//! intervals and p-values, Benjamini-Hochberg adjusted across markers. A
+//! marker that separates the events perfectly has no finite estimate and is
+//! left out.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use nalgebra::{DMatrix, DVector};
+use plotters::prelude::*;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use super::stats::{self, Thresholds};
+use crate::data_registry::DatasetRecord;
+use crate::utils::cancel::{self, CancellationToken};
+use crate::utils::number;
+
+/// Cox model table next to the other result tables
+pub const COX_FILE: &str = "cox_survival.csv";
+
+/// Kaplan-Meier plot
+pub const KM_FILE: &str = "kaplan_meier.png";
+
+/// Most group levels the curves are split by
+pub const MAX_CURVES: usize = 6;
+
+/// Fewest events for curves and Cox models
+const MIN_EVENTS: usize = 3;
+
+/// Newton-Raphson iterations before a Cox fit is given up as not converging
+const MAX_ITERATIONS: usize = 30;
+
+/// z of a two-sided 95% confidence interval
+const Z_95: f64 = 1.959964;
+
+/// Kaplan-Meier estimate of one stratum
+#[derive(Debug, Clone, Serialize)]
+pub struct KmCurve {
+    pub label: String,
+    pub n: usize,
+    pub events: usize,
+    /// Survival after each event time, from (0, 1)
+    pub steps: Vec<(f64, f64)>,
+    /// Censoring times with the survival at that time
+    pub censored: Vec<(f64, f64)>,
+    /// First time the survival falls to 0.5 or below; `None` when it is not
+    /// reached
+    pub median: Option<f64>,
+}
+
+/// Kaplan-Meier estimate of `times` with their `events` (false: censored)
+pub fn kaplan_meier(label: &str, times: &[f64], events: &[bool]) -> KmCurve {
+    let mut order: Vec<usize> = (0..times.len()).collect();
+    order.sort_by(|&a, &b| times[a].total_cmp(&times[b]));
+    let mut at_risk = times.len();
+    let mut survival = 1.0;
+    let mut steps = vec![(0.0, 1.0)];
+    let mut censored = Vec::new();
+    let mut k = 0;
+    while k < order.len() {
+        let time = times[order[k]];
+        let end = k + order[k..].iter().take_while(|&&i| times[i] == time).count();
+        let died = order[k..end].iter().filter(|&&i| events[i]).count();
+        if died > 0 {
+            survival *= 1.0 - died as f64 / at_risk as f64;
+            steps.push((time, survival));
+        }
+        censored.extend(std::iter::repeat_n((time, survival), end - k - died));
+        at_risk -= end - k;
+        k = end;
+    }
+    KmCurve {
+        label: label.to_string(),
+        n: times.len(),
+        events: events.iter().filter(|e| **e).count(),
+        median: steps.iter().find(|(_, s)| *s <= 0.5).map(|(t, _)| *t),
+        steps,
+        censored,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct LogRank {
+    pub chi_squared: f64,
+    pub df: usize,
+    pub p_value: f64,
+}
+
+/// Log-rank test of the `strata` (index per sample, below `levels`);
+/// `None` with fewer than two non-empty strata or a singular variance
+pub fn log_rank(strata: &[usize], levels: usize, times: &[f64], events: &[bool]) -> Option<LogRank> {
+    let mut at_risk = vec![0.0; levels];
+    for &s in strata {
+        at_risk[s] += 1.0;
+    }
+    if at_risk.iter().filter(|n| **n > 0.0).count() < 2 {
+        return None;
+    }
+    let mut order: Vec<usize> = (0..times.len()).collect();
+    order.sort_by(|&a, &b| times[a].total_cmp(&times[b]));
+    // Observed minus expected events and their covariance, first k - 1 strata
+    let df = levels - 1;
+    let mut u = DVector::<f64>::zeros(df);
+    let mut v = DMatrix::<f64>::zeros(df, df);
+    let mut k = 0;
+    while k < order.len() {
+        let time = times[order[k]];
+        let end = k + order[k..].iter().take_while(|&&i| times[i] == time).count();
+        let mut died = vec![0.0; levels];
+        for &i in order[k..end].iter().filter(|&&i| events[i]) {
+            died[strata[i]] += 1.0;
+        }
+        let total: f64 = at_risk.iter().sum();
+        let deaths: f64 = died.iter().sum();
+        if deaths > 0.0 {
+            // A lone sample at risk adds to neither the score nor the variance
+            let spread = if total > 1.0 { deaths * (total - deaths) / (total - 1.0) } else { 0.0 };
+            for j in 0..df {
+                u[j] += died[j] - deaths * at_risk[j] / total;
+                for l in 0..df {
+                    let same = if j == l { 1.0 } else { 0.0 };
+                    v[(j, l)] += spread * at_risk[j] / total * (same - at_risk[l] / total);
+                }
+            }
+        }
+        for &i in &order[k..end] {
+            at_risk[strata[i]] -= 1.0;
+        }
+        k = end;
+    }
+    let chi_squared = (u.transpose() * v.try_inverse()? * &u)[(0, 0)];
+    chi_squared.is_finite().then(|| LogRank {
+        chi_squared,
+        df,
+        p_value: stats::chi_squared_p_value(chi_squared, df as f64),
+    })
+}
+
+/// Univariate Cox model of `x`: the coefficient and its standard error;
+/// `None` when the fit does not converge, as when `x` separates the events
+pub fn cox(x: &[f64], times: &[f64], events: &[bool]) -> Option<(f64, f64)> {
+    let n = x.len();
+    let mean = x.iter().sum::<f64>() / n as f64;
+    let x: Vec<f64> = x.iter().map(|v| v - mean).collect();
+    // Latest first, so that each time's risk set is everything before it
+    // and its ties
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| times[b].total_cmp(&times[a]));
+    // Log partial likelihood, score and information at `beta`
+    let fit = |beta: f64| {
+        let (mut s0, mut s1, mut s2) = (0.0, 0.0, 0.0);
+        let (mut likelihood, mut score, mut information) = (0.0, 0.0, 0.0);
+        let mut k = 0;
+        while k < n {
+            let time = times[order[k]];
+            let end = k + order[k..].iter().take_while(|&&i| times[i] == time).count();
+            for &i in &order[k..end] {
+                let w = (beta * x[i]).exp();
+                s0 += w;
+                s1 += w * x[i];
+                s2 += w * x[i] * x[i];
+            }
+            for &i in order[k..end].iter().filter(|&&i| events[i]) {
+                likelihood += beta * x[i] - s0.ln();
+                score += x[i] - s1 / s0;
+                information += s2 / s0 - (s1 / s0).powi(2);
+            }
+            k = end;
+        }
+        (likelihood, score, information)
+    };
+    let mut beta = 0.0;
+    let (mut likelihood, mut score, mut information) = fit(beta);
+    for _ in 0..MAX_ITERATIONS {
+        if information.is_nan() || information <= 0.0 {
+            return None;
+        }
+        let mut step = score / information;
+        let mut next = fit(beta + step);
+        // Steps that lower the likelihood are halved
+        for _ in 0..20 {
+            if next.0 >= likelihood {
+                break;
+            }
+            step /= 2.0;
+            next = fit(beta + step);
+        }
+        beta += step;
+        (likelihood, score, information) = next;
+        if step.abs() < 1e-9 {
+            let se = 1.0 / information.sqrt();
+            return (beta.is_finite() && se.is_finite()).then_some((beta, se));
+        }
+    }
+    None
+}
+
+/// Cox model of one marker
+#[derive(Debug, Clone, Serialize)]
+pub struct CoxResult {
+    pub column: String,
+    pub label: String,
+    /// Samples with a time, an event status and a value
+    pub n: usize,
+    pub events: usize,
+    /// Log hazard ratio per unit of the marker
+    pub coefficient: f64,
+    pub se: f64,
+    pub hazard_ratio: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+    pub p_value: f64,
+    pub q_value: f64,
+}
+
+impl CoxResult {
+    fn format(&self) -> String {
+        format!(
+            "{} (HR = {:.2}, 95% CI {:.2}-{:.2}, {})",
+            self.label,
+            self.hazard_ratio,
+            self.ci_low,
+            self.ci_high,
+            number::p_quote("q", self.q_value)
+        )
+    }
+}
+
+/// What the Kaplan-Meier curves are split by
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "by", rename_all = "lowercase")]
+pub enum Strata {
+    /// The levels of the group column
+    Group { column: String },
+    /// Above and at or below the median of the marker with the strongest
+    /// Cox association
+    Marker { label: String, median: f64 },
+}
+
+impl Strata {
+    fn describe(&self) -> String {
+        match self {
+            Self::Group { column } => format!("by {}", column),
+            Self::Marker { label, median } => format!("by {} above or at most its median ({:.3})", label, median),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Survival {
+    /// Time-to-event column
+    pub time: String,
+    /// Event column; other samples are censored
+    pub event: String,
+    /// Samples with a time and an event status
+    pub samples: usize,
+    pub events: usize,
+    /// `None` for one curve of every sample
+    pub strata: Option<Strata>,
+    pub curves: Vec<KmCurve>,
+    pub log_rank: Option<LogRank>,
+    /// Markers with a Cox model, by p-value
+    pub cox: Vec<CoxResult>,
+}
+
+impl Survival {
+    /// One sentence for the analysis summary
+    pub fn summary(&self, thresholds: &Thresholds) -> String {
+        let associated = self.cox.iter().filter(|c| thresholds.significant(c.p_value, c.q_value)).count();
+        let log_rank = match (&self.strata, &self.log_rank) {
+            (Some(strata), Some(test)) => format!("; log-rank {} {}", strata.describe(), number::p_quote("p", test.p_value)),
+            _ => String::new(),
+        };
+        format!(
+            "Survival ({} events of {} samples): {} of {} marker(s) associated with the hazard at {}{}.",
+            self.events,
+            self.samples,
+            associated,
+            self.cox.len(),
+            thresholds.criterion(),
+            log_rank
+        )
+    }
+
+    pub fn methods(&self) -> String {
+        let curves = match &self.strata {
+            Some(strata @ Strata::Group { .. }) => {
+                format!(" {} and compared with the log-rank test", strata.describe())
+            }
+            Some(strata @ Strata::Marker { .. }) => format!(
+                " {} and compared with the log-rank test, for illustration only since that marker was chosen for \
+                 its association",
+                strata.describe()
+            ),
+            None => String::new(),
+        };
+        format!(
+            "Time to event was taken from {} and the event from {} ({} samples, {} events, the others censored). \
+             Kaplan-Meier survival curves were estimated{}. Each marker was tested with a univariate Cox \
+             proportional hazards model (Breslow ties, Newton-Raphson), giving hazard ratios per unit of the marker \
+             with Wald 95% confidence intervals and p-values, Benjamini-Hochberg adjusted across markers.",
+            self.time, self.event, self.samples, self.events, curves
+        )
+    }
+
+    pub fn results(&self, thresholds: &Thresholds) -> String {
+        let top: Vec<String> = self
+            .cox
+            .iter()
+            .filter(|c| thresholds.significant(c.p_value, c.q_value))
+            .take(5)
+            .map(CoxResult::format)
+            .collect();
+        let cox = if top.is_empty() {
+            format!(
+                "No marker was associated with the hazard of the event at {} ({} markers modelled).",
+                thresholds.criterion(),
+                self.cox.len()
+            )
+        } else {
+            format!(
+                "Of {} markers modelled, those most strongly associated with the hazard of the event were {}.",
+                self.cox.len(),
+                top.join(", ")
+            )
+        };
+        let curves = match (&self.strata, &self.log_rank) {
+            (Some(strata), Some(test)) => {
+                let medians: Vec<String> = self
+                    .curves
+                    .iter()
+                    .map(|c| match c.median {
+                        Some(median) => format!("{} {:.3}", c.label, median),
+                        None => format!("{} not reached", c.label),
+                    })
+                    .collect();
+                format!(
+                    " Survival {} differed with log-rank chi-squared = {:.2} ({} df, {}); median time to \
+                     event: {}.",
+                    strata.describe(),
+                    test.chi_squared,
+                    test.df,
+                    number::p_quote("p", test.p_value),
+                    medians.join(", ")
+                )
+            }
+            _ => String::new(),
+        };
+        format!("{}{}", cox, curves)
+    }
+}
+
+/// Whether `value` records an event: non-zero numbers and words such as
+/// `dead` or `yes`; `None` when it is missing or not recognised
+fn parse_event(record: &DatasetRecord, value: &str) -> Option<bool> {
+    if let Some(number) = record.number(value) {
+        return Some(number != 0.0);
+    }
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "y" | "dead" | "deceased" | "died" | "event" => Some(true),
+        "false" | "no" | "n" | "alive" | "living" | "censored" => Some(false),
+        _ => None,
+    }
+}
+
+/// Survival analysis of `record` with the `time` and `event` columns, Cox
+/// models of the `markers` (column index and label) and curves split by
+/// `group_index` when given; `None` with fewer than [`MIN_EVENTS`] events
+pub fn analyze(
+    record: &DatasetRecord,
+    headers: &[String],
+    (time, event): (&str, &str),
+    group_index: Option<usize>,
+    markers: &[(usize, String)],
+    cancel: &CancellationToken,
+) -> Result<Option<Survival>> {
+    let position = |name: &str| headers.iter().position(|h| h == name);
+    let (Some(time_index), Some(event_index)) = (position(time), position(event)) else {
+        bail!("Survival analysis needs the columns '{}' and '{}' in the dataset", time, event);
+    };
+
+    let mut times = Vec::new();
+    let mut events = Vec::new();
+    let mut groups: Vec<Option<String>> = Vec::new();
+    // Per marker, its value in each sample (NaN when missing)
+    let mut values: Vec<Vec<f64>> = vec![Vec::new(); markers.len()];
+    let mut rdr = record.reader()?;
+    for (i, row) in rdr.records().enumerate() {
+        if i % super::CANCEL_CHECK_ROWS == 0 {
+            cancel::check(cancel)?;
+        }
+        let row = row?;
+        let sample_time = row.get(time_index).and_then(|v| record.number(v)).filter(|t| *t >= 0.0);
+        let sample_event = row.get(event_index).and_then(|v| parse_event(record, v));
+        let (Some(sample_time), Some(sample_event)) = (sample_time, sample_event) else { continue };
+        times.push(sample_time);
+        events.push(sample_event);
+        groups.push(group_index.and_then(|idx| row.get(idx)).map(str::trim).filter(|g| !g.is_empty()).map(String::from));
+        for (column, (idx, _)) in values.iter_mut().zip(markers) {
+            column.push(row.get(*idx).and_then(|v| record.number(v)).unwrap_or(f64::NAN));
+        }
+    }
+    let total_events = events.iter().filter(|e| **e).count();
+    if total_events < MIN_EVENTS {
+        return Ok(None);
+    }
+
+    let mut cox: Vec<CoxResult> = markers
+        .par_iter()
+        .zip(&values)
+        .filter_map(|((idx, label), column)| {
+            let present: Vec<usize> = (0..times.len()).filter(|&i| column[i].is_finite()).collect();
+            let x: Vec<f64> = present.iter().map(|&i| column[i]).collect();
+            let t: Vec<f64> = present.iter().map(|&i| times[i]).collect();
+            let e: Vec<bool> = present.iter().map(|&i| events[i]).collect();
+            let n_events = e.iter().filter(|e| **e).count();
+            if n_events < MIN_EVENTS {
+                return None;
+            }
+            let (coefficient, se) = cox(&x, &t, &e)?;
+            Some(CoxResult {
+                column: headers[*idx].clone(),
+                label: label.clone(),
+                n: x.len(),
+                events: n_events,
+                coefficient,
+                se,
+                hazard_ratio: coefficient.exp(),
+                ci_low: (coefficient - Z_95 * se).exp(),
+                ci_high: (coefficient + Z_95 * se).exp(),
+                p_value: stats::normal_p_value(coefficient / se),
+                q_value: 1.0,
+            })
+        })
+        .collect();
+    let p_values: Vec<f64> = cox.iter().map(|c| c.p_value).collect();
+    for (result, q) in cox.iter_mut().zip(stats::benjamini_hochberg(&p_values)) {
+        result.q_value = q;
+    }
+    cox.sort_by(|a, b| a.p_value.total_cmp(&b.p_value).then_with(|| a.column.cmp(&b.column)));
+
+    // Samples of each curve, by stratum label in first-seen order
+    let mut levels: Vec<String> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for group in groups.iter().flatten() {
+        if !index.contains_key(group) {
+            index.insert(group.clone(), levels.len());
+            levels.push(group.clone());
+        }
+    }
+    let (strata, assigned): (Option<Strata>, Vec<Option<usize>>) = if (2..=MAX_CURVES).contains(&levels.len()) {
+        let column = headers[group_index.unwrap_or_default()].clone();
+        let assigned = groups.iter().map(|g| g.as_ref().map(|g| index[g])).collect();
+        (Some(Strata::Group { column }), assigned)
+    } else if let Some(best) = cox.first() {
+        let pos = markers.iter().position(|(idx, _)| headers[*idx] == best.column).unwrap_or_default();
+        let column = &values[pos];
+        let mut present: Vec<f64> = column.iter().copied().filter(|v| v.is_finite()).collect();
+        present.sort_by(f64::total_cmp);
+        let mid = present.len() / 2;
+        let median = if present.len().is_multiple_of(2) { (present[mid - 1] + present[mid]) / 2.0 } else { present[mid] };
+        levels = vec![format!("{} high", best.label), format!("{} low", best.label)];
+        let assigned = column.iter().map(|v| v.is_finite().then_some(usize::from(*v <= median))).collect();
+        (Some(Strata::Marker { label: best.label.clone(), median }), assigned)
+    } else {
+        levels = vec!["All samples".to_string()];
+        (None, vec![Some(0); times.len()])
+    };
+
+    let members: Vec<usize> = (0..times.len()).filter(|&i| assigned[i].is_some()).collect();
+    let curves = levels
+        .iter()
+        .enumerate()
+        .map(|(level, label)| {
+            let (t, e): (Vec<f64>, Vec<bool>) =
+                members.iter().filter(|&&i| assigned[i] == Some(level)).map(|&i| (times[i], events[i])).unzip();
+            kaplan_meier(label, &t, &e)
+        })
+        .collect();
+    let log_rank = strata.as_ref().and_then(|_| {
+        let strata: Vec<usize> = members.iter().filter_map(|&i| assigned[i]).collect();
+        let t: Vec<f64> = members.iter().map(|&i| times[i]).collect();
+        let e: Vec<bool> = members.iter().map(|&i| events[i]).collect();
+        log_rank(&strata, levels.len(), &t, &e)
+    });
+
+    Ok(Some(Survival {
+        time: time.to_string(),
+        event: event.to_string(),
+        samples: times.len(),
+        events: total_events,
+        strata,
+        curves,
+        log_rank,
+        cox,
+    }))
+}
+
+/// Kaplan-Meier step curves of `survival`, censored samples marked with
+/// a cross
+pub fn write_km_plot(output_path: &Path, survival: &Survival) -> Result<()> {
+    let last = survival
+        .curves
+        .iter()
+        .flat_map(|c| c.steps.iter().chain(&c.censored))
+        .map(|(t, _)| *t)
+        .fold(0.0, f64::max);
+    let root = BitMapBackend::new(output_path, (800, 600)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let caption = match &survival.strata {
+        Some(strata) => format!("Kaplan-Meier {}", strata.describe()),
+        None => "Kaplan-Meier survival".to_string(),
+    };
+    let mut chart = ChartBuilder::on(&root)
+        .margin(20)
+        .caption(caption, ("sans-serif", 22))
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0f64..last.max(1e-9) * 1.02, 0f64..1.05f64)?;
+    chart
+        .configure_mesh()
+        .x_desc(survival.time.as_str())
+        .y_desc("Survival probability")
+        .draw()?;
+
+    for (i, curve) in survival.curves.iter().enumerate() {
+        let colour = Palette99::pick(i).to_rgba();
+        // Horizontal to each event time, then down to the new survival
+        let mut path = Vec::with_capacity(curve.steps.len() * 2 + 1);
+        for window in curve.steps.windows(2) {
+            path.push(window[0]);
+            path.push((window[1].0, window[0].1));
+        }
+        let end = curve.steps.last().copied().unwrap_or((0.0, 1.0));
+        path.push(end);
+        let tail = curve.censored.iter().map(|(t, _)| *t).fold(end.0, f64::max);
+        path.push((tail, end.1));
+        chart
+            .draw_series(LineSeries::new(path, colour.stroke_width(2)))?
+            .label(format!("{} (n = {}, {} events)", curve.label, curve.n, curve.events))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 16, y)], colour.stroke_width(2)));
+        chart.draw_series(curve.censored.iter().map(|&point| Cross::new(point, 4, colour.stroke_width(1))))?;
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kaplan_meier_log_rank_and_cox() {
+        // Textbook example: events at 1, 3 (two), 5; censored at 2 and 4
+        let times = [1.0, 2.0, 3.0, 3.0, 4.0, 5.0];
+        let events = [true, false, true, true, false, true];
+        let curve = kaplan_meier("all", &times, &events);
+        let expected = [(0.0, 1.0), (1.0, 5.0 / 6.0), (3.0, 5.0 / 6.0 * 2.0 / 4.0), (5.0, 0.0)];
+        assert_eq!(curve.steps.len(), expected.len());
+        for ((t, s), (et, es)) in curve.steps.iter().zip(expected) {
+            assert_eq!(*t, et);
+            assert!((s - es).abs() < 1e-12, "{} {}", s, es);
+        }
+        assert_eq!(curve.censored, vec![(2.0, 5.0 / 6.0), (4.0, 5.0 / 12.0)]);
+        assert_eq!((curve.n, curve.events, curve.median), (6, 4, Some(3.0)));
+
+        // Group 1 fails early, group 0 late: log-rank by hand for two strata
+        let strata = [1, 1, 1, 0, 0, 0];
+        let times = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let events = [true; 6];
+        let test = log_rank(&strata, 2, &times, &events).unwrap();
+        // O - E for stratum 0 = 3 - (3/6 + 3/5 + 3/4 + 1 + 1 + 1)
+        let observed_minus_expected: f64 = 3.0 - (0.5 + 0.6 + 0.75 + 3.0);
+        let variance: f64 = 3.0 * 3.0 / 36.0 + 3.0 * 2.0 / 25.0 + 3.0 / 16.0;
+        assert!((test.chi_squared - observed_minus_expected.powi(2) / variance).abs() < 1e-9);
+        assert_eq!(test.df, 1);
+        assert!(log_rank(&[0; 6], 2, &times, &events).is_none());
+
+        // The hazard rises with the marker; a separating marker has no fit
+        let marker = [2.0, 1.0, 1.5, 0.5, 1.2, 0.2];
+        let (beta, se) = cox(&marker, &times, &events).unwrap();
+        assert!(beta > 0.0 && se > 0.0, "{} {}", beta, se);
+        let (flipped, _) = cox(&marker.map(|v| -v), &times, &events).unwrap();
+        assert!((flipped + beta).abs() < 1e-6);
+        assert!(cox(&[6.0, 5.0, 4.0, 3.0, 2.0, 1.0], &times, &events).is_none());
+    }
+}
@@ -0,0 +1,69 @@
+//! Threads of the per-column statistics
+//!
+//! An analysis may use at most one thread per core. Larger requests are
+//! refused where they come in (CLI, workflow file, `/analyze`, API) and
+//! clamped by [`pool`] otherwise. Each thread count gets one process-wide
+//! pool, created by the first analysis that asks for it, so concurrent
+//! and repeated runs share their threads instead of starting new ones.
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::types::{AppError, AppResult};
+
+/// Most threads an analysis may use: one per core
+pub fn max() -> usize {
+    std::thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Check a requested thread count; 0 uses one per core
+pub fn validate(threads: usize) -> Result<(), String> {
+    let max = max();
+    if threads > max {
+        return Err(format!("threads must be at most {} (one per core), got {}", max, threads));
+    }
+    Ok(())
+}
+
+/// Shared pool of `threads` threads; 0, or more than [`max`], is one per
+/// core
+pub fn pool(threads: usize) -> AppResult<Arc<ThreadPool>> {
+    let threads = match threads {
+        0 => max(),
+        threads => threads.min(max()),
+    };
+    static POOLS: OnceLock<Mutex<HashMap<usize, Arc<ThreadPool>>>> = OnceLock::new();
+    let mut pools = POOLS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(pool.clone());
+    }
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|idx| format!("analysis-{}", idx))
+        .build()
+        .map(Arc::new)
+        .map_err(|e| AppError::Internal(format!("Failed to start the analysis threads: {}", e)))?;
+    pools.insert(threads, pool.clone());
+    Ok(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pools_are_shared_and_clamped() {
+        assert!(validate(0).is_ok());
+        assert!(validate(max()).is_ok());
+        assert!(validate(max() + 1).unwrap_err().contains("at most"));
+
+        let pool = pool(1).unwrap();
+        assert_eq!(pool.current_num_threads(), 1);
+        assert!(Arc::ptr_eq(&pool, &super::pool(1).unwrap()));
+        assert_eq!(super::pool(max() + 8).unwrap().current_num_threads(), max());
+        assert!(Arc::ptr_eq(&super::pool(0).unwrap(), &super::pool(max()).unwrap()));
+    }
+}
@@ -0,0 +1,257 @@
+//! Ensembl gene ID → symbol mapping
+//!
+//! Expression matrices keyed by Ensembl gene IDs (`ENSG00000141510`, with or
+//! without a `.17` version suffix) are labelled with gene symbols in
+//! biomarker tables, plots and manuscripts. A small seed table of well-known
+//! genes is compiled in; the full table of each species in use is
+//! downloaded once from Ensembl BioMart and cached in the local data
+//! directory, so later runs work offline. Species share one map since their
+//! ID prefixes differ. `ANNOTATION_ENSEMBL_FILE` points at a table of your
+//! own, in the same three-column TSV layout.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use tracing::{info, warn};
+
+use super::{orthologs, Species};
+use crate::config::{AnnotationConfig, NetworkConfig};
+
+const SEED: &str = include_str!("ensembl_genes.tsv");
+
+pub(super) const BIOMART_URL: &str = "https://www.ensembl.org/biomart/martservice";
+
+static SHARED: RwLock<Option<Arc<GeneMap>>> = RwLock::new(None);
+
+/// Annotation of one gene
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneInfo {
+    pub symbol: String,
+    /// Ensembl biotype (`protein_coding`, `lncRNA`, ...)
+    pub biotype: String,
+}
+
+/// Gene annotation keyed by unversioned Ensembl ID
+#[derive(Debug, Default)]
+pub struct GeneMap {
+    genes: HashMap<String, GeneInfo>,
+    /// Where the entries beyond the seed table came from
+    sources: Vec<PathBuf>,
+}
+
+impl GeneMap {
+    /// The compiled-in seed table
+    pub fn seed() -> Self {
+        let mut map = Self::default();
+        map.extend_tsv(SEED);
+        map
+    }
+
+    /// Seed table extended with the configured file, or else the cached
+    /// BioMart download of every species. Unreadable files are logged and
+    /// skipped.
+    pub fn load(config: &AnnotationConfig) -> Self {
+        let mut map = Self::seed();
+        let paths: Vec<PathBuf> = match &config.ensembl_file {
+            Some(path) => vec![path.clone()],
+            None => Species::ALL
+                .into_iter()
+                .filter_map(cache_path)
+                .filter(|p| p.exists())
+                .collect(),
+        };
+        for path in paths {
+            match std::fs::read_to_string(&path) {
+                Ok(tsv) => {
+                    map.extend_tsv(&tsv);
+                    map.sources.push(path);
+                }
+                Err(e) => warn!(error = %e, path = %path.display(), "Ignoring unreadable gene annotation"),
+            }
+        }
+        map
+    }
+
+    /// Add `gene_id<TAB>symbol[<TAB>biotype]` rows. Comment lines, header
+    /// rows and genes without a symbol are skipped.
+    pub fn extend_tsv(&mut self, tsv: &str) {
+        for line in tsv.lines() {
+            let mut fields = line.trim_end_matches('\r').split('\t');
+            let (Some(id), Some(symbol)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let (id, symbol) = (id.trim(), symbol.trim());
+            if !id.starts_with("ENS") || symbol.is_empty() {
+                continue;
+            }
+            let biotype = fields.next().map(str::trim).unwrap_or_default();
+            self.genes.insert(
+                unversioned(id).to_ascii_uppercase(),
+                GeneInfo {
+                    symbol: symbol.to_string(),
+                    biotype: biotype.to_string(),
+                },
+            );
+        }
+    }
+
+    /// Annotation for an Ensembl ID, ignoring any version suffix
+    pub fn get(&self, id: &str) -> Option<&GeneInfo> {
+        let id = unversioned(id.trim()).to_ascii_uppercase();
+        if !id.starts_with("ENS") {
+            return None;
+        }
+        self.genes.get(&id)
+    }
+
+    /// Symbol for `column` when it is a known Ensembl ID, else `column`
+    pub fn label<'a>(&'a self, column: &'a str) -> &'a str {
+        self.get(column).map_or(column, |gene| gene.symbol.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.genes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genes.is_empty()
+    }
+
+    /// Files the map was extended from; empty when only the seed is loaded
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+}
+
+pub(super) fn unversioned(id: &str) -> &str {
+    id.split_once('.').map_or(id, |(id, _)| id)
+}
+
+/// `<data dir>/oxidized-bio/annotation`, where downloaded annotation is cached
+pub fn annotation_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("oxidized-bio").join("annotation"))
+}
+
+/// `<data dir>/oxidized-bio/annotation/<file>`
+pub(super) fn annotation_dir_file(file: &str) -> Option<PathBuf> {
+    annotation_dir().map(|dir| dir.join(file))
+}
+
+/// Location of the cached BioMart download for `species`
+pub fn cache_path(species: Species) -> Option<PathBuf> {
+    annotation_dir_file(&format!("ensembl_{}_genes.tsv", species))
+}
+
+/// BioMart XML query for `attributes` of the `species` gene dataset
+pub(super) fn biomart_query(species: Species, attributes: &[&str]) -> String {
+    let attributes: String = attributes
+        .iter()
+        .map(|name| format!("<Attribute name=\"{}\"/>", name))
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><!DOCTYPE Query><Query virtualSchemaName="default" formatter="TSV" header="0" uniqueRows="1" datasetConfigVersion="0.6"><Dataset name="{}_gene_ensembl" interface="default">{}</Dataset></Query>"#,
+        species.biomart_name(),
+        attributes
+    )
+}
+
+/// Run a BioMart query and return the TSV body
+pub(super) async fn biomart_fetch(network: &NetworkConfig, query: &str) -> Result<String> {
+    let response = crate::utils::http::build_client(network, "download")?
+        .get(BIOMART_URL)
+        .query(&[("query", query)])
+        .timeout(Duration::from_secs(120))
+        .send()
+        .await
+        .context("Ensembl BioMart request failed")?
+        .error_for_status()
+        .context("Ensembl BioMart request failed")?;
+    Ok(response.text().await?)
+}
+
+/// Write a downloaded table, creating the cache directory
+pub(super) fn write_cache(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Process-wide map, loaded on first use from the installed config sources
+pub fn shared() -> Arc<GeneMap> {
+    if let Some(map) = SHARED.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return map.clone();
+    }
+    let map = Arc::new(GeneMap::load(&AnnotationConfig::from_env()));
+    *SHARED.write().unwrap_or_else(|e| e.into_inner()) = Some(map.clone());
+    map
+}
+
+/// Drop the shared map so the next [`shared`] call reloads it
+pub fn reload() {
+    *SHARED.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Download the gene table of `species` from BioMart into the cache and
+/// return its path and number of genes
+pub async fn download(network: &NetworkConfig, species: Species) -> Result<(PathBuf, usize)> {
+    let path = cache_path(species).context("No local data directory for the gene annotation cache")?;
+    let query = biomart_query(species, &["ensembl_gene_id", "external_gene_name", "gene_biotype"]);
+    let tsv = biomart_fetch(network, &query).await?;
+
+    // BioMart reports query errors as a 200 with a plain-text message
+    let mut parsed = GeneMap::default();
+    parsed.extend_tsv(&tsv);
+    if parsed.is_empty() {
+        bail!(
+            "Ensembl BioMart returned no genes: {}",
+            tsv.lines().next().unwrap_or_default().chars().take(200).collect::<String>()
+        );
+    }
+
+    write_cache(&path, &tsv)?;
+    reload();
+    info!(%species, genes = parsed.len(), path = %path.display(), "Cached Ensembl gene annotation");
+    Ok((path, parsed.len()))
+}
+
+/// Download the gene table of `species`, and for mouse and rat its human
+/// orthologs, unless cached, a custom file is configured or downloads are
+/// disabled. Failures are logged; the seed tables still apply.
+pub async fn ensure_cached(config: &AnnotationConfig, network: &NetworkConfig, species: Species) {
+    if !config.download {
+        return;
+    }
+    let missing = cache_path(species).is_some_and(|p| !p.exists());
+    if config.ensembl_file.is_none() && missing {
+        if let Err(e) = download(network, species).await {
+            warn!(error = %e, %species, "Gene annotation download failed, using the bundled seed table");
+        }
+    }
+    if species != Species::Human && orthologs::cache_path(species).is_some_and(|p| !p.exists()) {
+        if let Err(e) = orthologs::download(network, species).await {
+            warn!(error = %e, %species, "Ortholog download failed, using the bundled seed table");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gene_map_lookup() {
+        let mut map = GeneMap::seed();
+        assert_eq!(map.label("ENSG00000141510"), "TP53");
+        assert_eq!(map.label("ENSG00000141510.17"), "TP53");
+        assert_eq!(map.get("ensg00000229807").unwrap().biotype, "lncRNA");
+        assert_eq!(map.label("age"), "age");
+
+        map.extend_tsv("Gene stable ID\tGene name\tGene type\nENSMUSG00000059552\tTrp53\tprotein_coding\nENSG00000283563\t\tlncRNA\n");
+        assert_eq!(map.label("ENSMUSG00000059552.8"), "Trp53");
+        assert!(map.get("ENSG00000283563").is_none());
+    }
+}
@@ -0,0 +1,178 @@
+//! GO term enrichment
+//!
+//! Tests whether GO terms are over-represented among the significant
+//! biomarkers (one-sided hypergeometric test, Benjamini–Hochberg across
+//! terms). The universe is every tested marker annotated with at least one
+//! term. A compact GO slim of aging-relevant biological processes is
+//! compiled in; `ANNOTATION_GO_FILE` swaps in any gene set collection in
+//! GMT format (term ID, name, gene symbols), e.g. a full GO release from
+//! MSigDB.
+
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::analysis::stats;
+use crate::config::AnnotationConfig;
+use crate::models::EnrichmentResult;
+
+const GO_SLIM: &str = include_str!("go_slim.gmt");
+
+/// A GO term and the gene symbols annotated with it
+#[derive(Debug, Clone)]
+pub struct GeneSet {
+    pub id: String,
+    pub name: String,
+    /// Upper-case gene symbols
+    pub genes: HashSet<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct GeneSets {
+    sets: Vec<GeneSet>,
+    /// GMT file the sets came from; `None` for the bundled slim
+    source: Option<PathBuf>,
+}
+
+impl GeneSets {
+    /// The bundled GO slim
+    pub fn bundled() -> Self {
+        Self::parse_gmt(GO_SLIM)
+    }
+
+    /// The configured GMT file, or the bundled slim when none is set or it
+    /// cannot be read
+    pub fn load(config: &AnnotationConfig) -> Self {
+        Self::load_or(config.go_file.as_deref(), GO_SLIM)
+    }
+
+    /// The GMT file at `path`, or the `bundled` GMT when there is none or it
+    /// cannot be read
+    pub fn load_or(path: Option<&Path>, bundled: &str) -> Self {
+        let Some(path) = path else {
+            return Self::parse_gmt(bundled);
+        };
+        match std::fs::read_to_string(path) {
+            Ok(gmt) => {
+                let mut sets = Self::parse_gmt(&gmt);
+                sets.source = Some(path.to_path_buf());
+                sets
+            }
+            Err(e) => {
+                warn!(error = %e, path = %path.display(), "Ignoring unreadable gene set file, using the bundled sets");
+                Self::parse_gmt(bundled)
+            }
+        }
+    }
+
+    /// Parse GMT: `id<TAB>name<TAB>gene<TAB>gene...` per line
+    pub fn parse_gmt(gmt: &str) -> Self {
+        let sets = gmt
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.trim_end_matches('\r').split('\t');
+                let id = fields.next()?.trim().to_string();
+                let name = fields.next()?.trim().to_string();
+                let genes: HashSet<String> = fields
+                    .map(|g| g.trim().to_ascii_uppercase())
+                    .filter(|g| !g.is_empty())
+                    .collect();
+                (!genes.is_empty()).then_some(GeneSet { id, name, genes })
+            })
+            .collect();
+        Self { sets, source: None }
+    }
+
+    pub fn sets(&self) -> &[GeneSet] {
+        &self.sets
+    }
+
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Enrichment of each term among `hits` against the tested `universe`
+    /// (gene symbols), sorted by p-value. Terms without any hit are tested
+    /// and counted in the adjustment but not returned.
+    pub fn enrich(&self, universe: &[&str], hits: &[&str]) -> Vec<EnrichmentResult> {
+        let annotated: BTreeSet<String> = universe
+            .iter()
+            .map(|g| g.to_ascii_uppercase())
+            .filter(|g| self.sets.iter().any(|set| set.genes.contains(g)))
+            .collect();
+        let hits: BTreeSet<String> = hits
+            .iter()
+            .map(|g| g.to_ascii_uppercase())
+            .filter(|g| annotated.contains(g))
+            .collect();
+        if hits.is_empty() {
+            return Vec::new();
+        }
+
+        let (population, draws) = (annotated.len(), hits.len());
+        let mut results: Vec<EnrichmentResult> = self
+            .sets
+            .iter()
+            .filter_map(|set| {
+                let term_size = annotated.iter().filter(|g| set.genes.contains(*g)).count();
+                if term_size == 0 {
+                    return None;
+                }
+                let genes: Vec<String> = hits.iter().filter(|g| set.genes.contains(*g)).cloned().collect();
+                let overlap = genes.len();
+                Some(EnrichmentResult {
+                    term_id: set.id.clone(),
+                    term_name: set.name.clone(),
+                    fold_enrichment: (overlap as f64 / draws as f64) / (term_size as f64 / population as f64),
+                    p_value: stats::hypergeometric_p_value(overlap, population, term_size, draws),
+                    q_value: 1.0,
+                    genes,
+                    term_size,
+                    hits: draws,
+                    universe: population,
+                })
+            })
+            .collect();
+
+        let p_values: Vec<f64> = results.iter().map(|r| r.p_value).collect();
+        for (result, q) in results.iter_mut().zip(stats::benjamini_hochberg(&p_values)) {
+            result.q_value = q;
+        }
+        results.retain(|r| !r.genes.is_empty());
+        results.sort_by(|a, b| a.p_value.total_cmp(&b.p_value).then_with(|| a.term_id.cmp(&b.term_id)));
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enrich() {
+        let sets = GeneSets::parse_gmt("GO:1\tsenescence\tCDKN2A\tCDKN1A\tTP53\nGO:2\ttranslation\tRPS6\tRPL3\n");
+        let universe = ["CDKN2A", "CDKN1A", "TP53", "RPS6", "RPL3", "ACTB"];
+        let results = sets.enrich(&universe, &["cdkn2a", "CDKN1A", "ACTB"]);
+
+        assert_eq!(results.len(), 1);
+        let senescence = &results[0];
+        assert_eq!(senescence.genes, vec!["CDKN1A", "CDKN2A"]);
+        // ACTB is unannotated: 2 of 2 hits among 3 of 5 annotated genes
+        assert_eq!((senescence.term_size, senescence.hits, senescence.universe), (3, 2, 5));
+        assert!((senescence.p_value - 0.3).abs() < 1e-9);
+        assert!((senescence.fold_enrichment - 5.0 / 3.0).abs() < 1e-9);
+
+        assert!(sets.enrich(&universe, &["ACTB"]).is_empty());
+        assert!(GeneSets::bundled().sets().iter().any(|s| s.id == "GO:0090398"));
+    }
+}
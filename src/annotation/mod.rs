@@ -0,0 +1,19 @@
+//! Gene annotation
+//!
+//! Maps identifiers found in dataset headers to human-readable gene names
+//! for tables, plots and manuscripts, microarray probes to genes, mouse and
+//! rat genes to their human orthologs, and genes to GO terms and Reactome/KEGG
+//! pathways for enrichment analysis.
+
+pub mod ensembl;
+pub mod go;
+pub mod orthologs;
+pub mod pathways;
+pub mod platform;
+pub mod species;
+
+pub use ensembl::{GeneInfo, GeneMap};
+pub use go::{GeneSet, GeneSets};
+pub use orthologs::OrthologMap;
+pub use platform::{Collapse, CollapseSummary, ProbeMap};
+pub use species::Species;
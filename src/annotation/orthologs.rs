@@ -0,0 +1,182 @@
+//! Mouse and rat → human ortholog mapping
+//!
+//! Aging markers found in mouse or rat data are compared against the human
+//! literature through their human one-to-one orthologs (Ensembl Compara).
+//! A seed table of common aging genes is compiled in; the full table of a
+//! species is downloaded from BioMart alongside its gene annotation and
+//! cached, so later runs work offline. Genes with one-to-many or
+//! many-to-many orthologs are left unmapped rather than guessed.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, Context, Result};
+use tracing::{info, warn};
+
+use super::ensembl::{annotation_dir_file, biomart_fetch, biomart_query, unversioned, write_cache};
+use super::Species;
+use crate::config::NetworkConfig;
+
+const SEED: &str = include_str!("orthologs.tsv");
+
+/// Only unambiguous orthologs are kept
+const ONE_TO_ONE: &str = "ortholog_one2one";
+
+static SHARED: RwLock<Option<Arc<OrthologMap>>> = RwLock::new(None);
+
+/// Human ortholog symbols keyed by species and upper-case gene ID or symbol
+#[derive(Debug, Default)]
+pub struct OrthologMap {
+    human: HashMap<(Species, String), String>,
+}
+
+impl OrthologMap {
+    /// The compiled-in seed table
+    pub fn seed() -> Self {
+        let mut map = Self::default();
+        for line in SEED.lines().filter(|l| !l.starts_with('#')) {
+            let mut fields = line.split('\t').map(str::trim);
+            let (Some(species), Some(symbol), Some(human)) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            if let Ok(species) = species.parse() {
+                map.insert(species, symbol, human);
+            }
+        }
+        map
+    }
+
+    /// Seed table extended with every cached BioMart download. Unreadable
+    /// files are logged and skipped.
+    pub fn load() -> Self {
+        let mut map = Self::seed();
+        for species in Species::ALL.into_iter().filter(|s| *s != Species::Human) {
+            let Some(path) = cache_path(species).filter(|p| p.exists()) else {
+                continue;
+            };
+            match std::fs::read_to_string(&path) {
+                Ok(tsv) => map.extend_tsv(species, &tsv),
+                Err(e) => warn!(error = %e, path = %path.display(), "Ignoring unreadable ortholog table"),
+            }
+        }
+        map
+    }
+
+    /// Add BioMart rows: `gene_id<TAB>symbol<TAB>human_symbol<TAB>orthology_type`.
+    /// Rows that are not one-to-one orthologs are skipped.
+    pub fn extend_tsv(&mut self, species: Species, tsv: &str) {
+        for line in tsv.lines() {
+            let fields: Vec<&str> = line.trim_end_matches('\r').split('\t').map(str::trim).collect();
+            let [id, symbol, human, kind, ..] = fields.as_slice() else {
+                continue;
+            };
+            if *kind != ONE_TO_ONE || human.is_empty() {
+                continue;
+            }
+            self.insert(species, unversioned(id), human);
+            if !symbol.is_empty() {
+                self.insert(species, symbol, human);
+            }
+        }
+    }
+
+    fn insert(&mut self, species: Species, key: &str, human: &str) {
+        self.human.insert((species, key.to_ascii_uppercase()), human.to_string());
+    }
+
+    /// Human ortholog of a `species` gene, by Ensembl ID (any version) or
+    /// symbol. Human genes map to themselves.
+    pub fn human<'a>(&'a self, species: Species, gene: &'a str) -> Option<&'a str> {
+        if species == Species::Human {
+            return Some(gene);
+        }
+        let key = unversioned(gene.trim()).to_ascii_uppercase();
+        self.human.get(&(species, key)).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.human.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.human.is_empty()
+    }
+}
+
+/// Location of the cached ortholog table of `species`
+pub fn cache_path(species: Species) -> Option<PathBuf> {
+    annotation_dir_file(&format!("orthologs_{}_human.tsv", species))
+}
+
+/// Process-wide map, loaded on first use
+pub fn shared() -> Arc<OrthologMap> {
+    if let Some(map) = SHARED.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return map.clone();
+    }
+    let map = Arc::new(OrthologMap::load());
+    *SHARED.write().unwrap_or_else(|e| e.into_inner()) = Some(map.clone());
+    map
+}
+
+/// Drop the shared map so the next [`shared`] call reloads it
+pub fn reload() {
+    *SHARED.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Download the human orthologs of `species` from BioMart into the cache
+/// and return its path and number of one-to-one orthologs
+pub async fn download(network: &NetworkConfig, species: Species) -> Result<(PathBuf, usize)> {
+    if species == Species::Human {
+        bail!("Human genes need no ortholog table");
+    }
+    let path = cache_path(species).context("No local data directory for the ortholog cache")?;
+    let query = biomart_query(
+        species,
+        &[
+            "ensembl_gene_id",
+            "external_gene_name",
+            "hsapiens_homolog_associated_gene_name",
+            "hsapiens_homolog_orthology_type",
+        ],
+    );
+    let tsv = biomart_fetch(network, &query).await?;
+
+    let mut parsed = OrthologMap::default();
+    parsed.extend_tsv(species, &tsv);
+    if parsed.is_empty() {
+        bail!(
+            "Ensembl BioMart returned no orthologs: {}",
+            tsv.lines().next().unwrap_or_default().chars().take(200).collect::<String>()
+        );
+    }
+
+    write_cache(&path, &tsv)?;
+    reload();
+    // Each gene is indexed by ID and symbol
+    let genes = tsv.lines().filter(|l| l.contains(ONE_TO_ONE)).count();
+    info!(%species, genes, path = %path.display(), "Cached human orthologs");
+    Ok((path, genes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ortholog_lookup() {
+        let mut map = OrthologMap::seed();
+        assert_eq!(map.human(Species::Mouse, "Trp53"), Some("TP53"));
+        assert_eq!(map.human(Species::Rat, "TP53"), Some("TP53"));
+        assert_eq!(map.human(Species::Human, "ELOVL2"), Some("ELOVL2"));
+        assert_eq!(map.human(Species::Mouse, "Xist"), None);
+
+        map.extend_tsv(
+            Species::Mouse,
+            "ENSMUSG00000086503\tXist\tXIST\tortholog_one2one\nENSMUSG00000091537\tTgtp1\tIRGM\tortholog_one2many\n",
+        );
+        assert_eq!(map.human(Species::Mouse, "ENSMUSG00000086503.3"), Some("XIST"));
+        assert_eq!(map.human(Species::Mouse, "xist"), Some("XIST"));
+        assert_eq!(map.human(Species::Mouse, "Tgtp1"), None);
+    }
+}
@@ -0,0 +1,63 @@
+//! Pathway enrichment
+//!
+//! Over-representation of Reactome and KEGG pathways among the significant
+//! biomarkers, tested like GO terms (see [`super::go`]): hypergeometric test
+//! against the annotated tested markers, Benjamini–Hochberg across
+//! pathways. A small set of aging-relevant pathways is compiled in, so runs
+//! need no network access; `ANNOTATION_PATHWAY_FILE` swaps in a full
+//! collection in GMT format, e.g. Reactome's `ReactomePathways.gmt` or the
+//! KEGG sets from MSigDB.
+
+use crate::config::AnnotationConfig;
+
+use super::go::GeneSets;
+
+const PATHWAYS: &str = include_str!("pathways.gmt");
+
+/// The bundled Reactome and KEGG pathways
+pub fn bundled() -> GeneSets {
+    GeneSets::parse_gmt(PATHWAYS)
+}
+
+/// The configured GMT file, or the bundled pathways when none is set or it
+/// cannot be read
+pub fn load(config: &AnnotationConfig) -> GeneSets {
+    GeneSets::load_or(config.pathway_file.as_deref(), PATHWAYS)
+}
+
+/// Database a pathway ID belongs to, from its prefix
+pub fn database(pathway_id: &str) -> &'static str {
+    if pathway_id.starts_with("R-") {
+        "Reactome"
+    } else if pathway_id.starts_with("hsa") || pathway_id.starts_with("map") || pathway_id.starts_with("KEGG") {
+        "KEGG"
+    } else {
+        "custom"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_pathways_enrich_senescence_genes() {
+        let pathways = bundled();
+        assert_eq!(pathways.len(), 20);
+        assert!(pathways.sets().iter().all(|p| database(&p.id) != "custom"));
+
+        let mut universe: Vec<&str> = pathways
+            .sets()
+            .iter()
+            .flat_map(|p| p.genes.iter().map(String::as_str))
+            .collect();
+        universe.sort_unstable();
+        universe.dedup();
+        let hits = ["CDKN2A", "CDKN1A", "TP53", "RB1", "IL6", "CXCL8"];
+        let results = pathways.enrich(&universe, &hits);
+        let top = &results[0];
+        assert_eq!(database(&top.term_id), "Reactome");
+        assert!(top.term_name.contains("Senescence"), "{}", top.term_name);
+        assert!(top.p_value < 0.05);
+    }
+}
@@ -0,0 +1,404 @@
+//! Microarray platform annotation
+//!
+//! Maps probe IDs to gene symbols from a GEO platform file (GPL SOFT or
+//! `.annot`) or an Affymetrix annotation CSV, and collapses datasets whose
+//! columns are probes into one column per gene before analysis. Probes that
+//! match no gene or several genes (`A /// B`) are dropped; columns that are
+//! not probes of the platform (age, cell type, ...) are kept as they are.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::data_registry::DatasetRecord;
+
+/// Columns that hold the probe ID, in order of preference
+const PROBE_COLUMNS: &[&str] = &["ID", "Probe Set ID", "ProbeID", "Probe_Id", "Transcript Cluster ID", "ID_REF"];
+
+/// Columns that hold the gene symbol, in order of preference
+const SYMBOL_COLUMNS: &[&str] = &["Gene Symbol", "GENE_SYMBOL", "Symbol", "Gene_Symbol", "ILMN_Gene"];
+
+/// Affymetrix Gene/Exon ST column: `NM_000546 // TP53 // tumor protein p53 // ...`
+const ASSIGNMENT_COLUMN: &str = "gene_assignment";
+
+/// How probes of the same gene are combined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Collapse {
+    /// Keep the probe with the highest mean expression
+    #[default]
+    Max,
+    /// Average the probes in each sample
+    Mean,
+}
+
+impl Collapse {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Max => "max",
+            Self::Mean => "mean",
+        }
+    }
+
+    /// Methods wording
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Max => "keeping the probe with the highest mean expression",
+            Self::Mean => "averaging probes per sample",
+        }
+    }
+}
+
+impl fmt::Display for Collapse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Collapse {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "max" => Ok(Self::Max),
+            "mean" => Ok(Self::Mean),
+            other => Err(format!("unknown collapse strategy '{}' (expected max or mean)", other)),
+        }
+    }
+}
+
+/// Probe → gene mapping of one platform
+#[derive(Debug, Default)]
+pub struct ProbeMap {
+    /// GEO accession or Affymetrix chip type, when the file names it
+    pub platform: Option<String>,
+    genes: HashMap<String, String>,
+    /// Probes of the platform without exactly one gene
+    unassigned: HashSet<String>,
+}
+
+impl ProbeMap {
+    /// Read a GPL SOFT / `.annot` file or an Affymetrix annotation CSV
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read platform annotation {}", path.display()))?;
+        let map = Self::parse(&text).with_context(|| format!("Invalid platform annotation {}", path.display()))?;
+        if map.genes.is_empty() {
+            bail!("Platform annotation {} maps no probes to genes", path.display());
+        }
+        Ok(map)
+    }
+
+    /// Parse annotation text; the format is detected from its contents
+    pub fn parse(text: &str) -> Result<Self> {
+        let lower = text.to_ascii_lowercase();
+        match lower.find("!platform_table_begin") {
+            Some(begin) => {
+                // `^PLATFORM = GPL570` in SOFT files, `!Annotation_platform = GPL570` in .annot files
+                let platform = text
+                    .lines()
+                    .find_map(|l| l.strip_prefix("^PLATFORM").or_else(|| l.strip_prefix("!Annotation_platform")))
+                    .map(|rest| rest.trim_start_matches([' ', '=']).trim().to_string());
+                let start = text[begin..].find('\n').map_or(text.len(), |i| begin + i + 1);
+                let end = lower[start..].find("!platform_table_end").map_or(text.len(), |i| start + i);
+                let mut map = Self::parse_table(&text[start..end], b'\t')?;
+                map.platform = platform.filter(|p| !p.is_empty());
+                Ok(map)
+            }
+            None => {
+                let platform = text
+                    .lines()
+                    .find_map(|l| l.strip_prefix("#%chip_type="))
+                    .map(|chip| chip.trim().to_string());
+                let table: String = text
+                    .lines()
+                    .filter(|l| !l.starts_with('#') && !l.trim().is_empty())
+                    .map(|l| format!("{}\n", l))
+                    .collect();
+                let header = table.lines().next().unwrap_or_default();
+                let delimiter = if header.contains('\t') { b'\t' } else { b',' };
+                let mut map = Self::parse_table(&table, delimiter)?;
+                map.platform = platform;
+                Ok(map)
+            }
+        }
+    }
+
+    fn parse_table(table: &str, delimiter: u8) -> Result<Self> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(table.as_bytes());
+        let headers = rdr.headers()?.clone();
+        let find = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name)))
+        };
+        let probe_idx = find(PROBE_COLUMNS).unwrap_or(0);
+        let (gene_idx, assignment) = match find(SYMBOL_COLUMNS) {
+            Some(idx) => (idx, false),
+            None => match find(&[ASSIGNMENT_COLUMN]) {
+                Some(idx) => (idx, true),
+                None => bail!(
+                    "no gene symbol column (expected one of: {}, {})",
+                    SYMBOL_COLUMNS.join(", "),
+                    ASSIGNMENT_COLUMN
+                ),
+            },
+        };
+
+        let mut map = Self::default();
+        for row in rdr.records() {
+            let row = row?;
+            let Some(probe) = row.get(probe_idx).map(str::trim).filter(|p| !p.is_empty()) else {
+                continue;
+            };
+            let value = row.get(gene_idx).unwrap_or_default();
+            match single_gene(value, assignment) {
+                Some(gene) => {
+                    map.genes.insert(probe.to_string(), gene);
+                }
+                None => {
+                    map.unassigned.insert(probe.to_string());
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    /// Gene of `probe`, if it has exactly one
+    pub fn gene(&self, probe: &str) -> Option<&str> {
+        self.genes.get(probe).map(String::as_str)
+    }
+
+    /// Whether `column` is a probe of this platform
+    pub fn contains(&self, column: &str) -> bool {
+        self.genes.contains_key(column) || self.unassigned.contains(column)
+    }
+
+    /// Number of probes mapped to a gene
+    pub fn len(&self) -> usize {
+        self.genes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genes.is_empty()
+    }
+}
+
+/// The one gene named by an annotation value, or `None` for blanks
+/// (`---`) and multi-gene probes
+fn single_gene(value: &str, assignment: bool) -> Option<String> {
+    let mut genes: Vec<&str> = value
+        .split("///")
+        .map(|entry| match assignment {
+            true => entry.split("//").nth(1).unwrap_or_default(),
+            false => entry,
+        })
+        .map(str::trim)
+        .filter(|g| !g.is_empty() && *g != "---")
+        .collect();
+    genes.sort_unstable();
+    genes.dedup();
+    match genes.as_slice() {
+        [gene] => Some(gene.to_string()),
+        _ => None,
+    }
+}
+
+/// Outcome of collapsing a probe-level dataset
+#[derive(Debug, Clone, Serialize)]
+pub struct CollapseSummary {
+    pub platform: Option<String>,
+    pub strategy: Collapse,
+    /// Probe columns mapped to a gene
+    pub probes: usize,
+    /// Probe columns dropped for matching no or several genes
+    pub dropped: usize,
+    /// Gene columns written
+    pub genes: usize,
+    /// Collapsed dataset
+    pub path: PathBuf,
+}
+
+impl CollapseSummary {
+    /// One sentence for the manuscript's Methods section
+    pub fn methods(&self) -> String {
+        format!(
+            "Probes were mapped to genes with the {} annotation; {} probe(s) matching no gene or several genes \
+were removed and the remaining {} probe(s) were collapsed to {} genes by {}.",
+            self.platform.as_deref().unwrap_or("platform"),
+            self.dropped,
+            self.probes,
+            self.genes,
+            self.strategy.describe()
+        )
+    }
+}
+
+/// Write `record` with its probe columns collapsed to genes as `path`.
+/// Gene columns take the place of the first probe of each gene.
+pub fn collapse_dataset(
+    record: &DatasetRecord,
+    probes: &ProbeMap,
+    strategy: Collapse,
+    path: &Path,
+) -> Result<(DatasetRecord, CollapseSummary)> {
+    let mut rdr = record.reader()?;
+    if !record.has_headers {
+        bail!("Probe collapsing needs a header row with probe IDs");
+    }
+    let headers: Vec<String> = rdr.headers()?.iter().map(str::to_string).collect();
+    let rows: Vec<csv::StringRecord> = rdr.records().collect::<Result<_, _>>()?;
+
+    enum Output {
+        Column(usize),
+        Gene(String),
+    }
+    let mut outputs = Vec::new();
+    let mut members: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut dropped = 0;
+    for (idx, header) in headers.iter().enumerate() {
+        match probes.gene(header) {
+            Some(gene) => {
+                let probe_columns = members.entry(gene.to_string()).or_default();
+                if probe_columns.is_empty() {
+                    outputs.push(Output::Gene(gene.to_string()));
+                }
+                probe_columns.push(idx);
+            }
+            None if probes.contains(header) => dropped += 1,
+            None => outputs.push(Output::Column(idx)),
+        }
+    }
+    if members.is_empty() {
+        bail!(
+            "No columns of {} are probes of the platform annotation",
+            record.dataset.filename
+        );
+    }
+
+    let value = |row: &csv::StringRecord, idx: usize| row.get(idx).and_then(|v| record.number(v));
+    // Max: the gene's representative probe is fixed across samples
+    let representative: HashMap<&str, usize> = match strategy {
+        Collapse::Mean => HashMap::new(),
+        Collapse::Max => members
+            .iter()
+            .map(|(gene, columns)| {
+                let mean = |idx: usize| {
+                    let values: Vec<f64> = rows.iter().filter_map(|row| value(row, idx)).collect();
+                    if values.is_empty() {
+                        f64::NEG_INFINITY
+                    } else {
+                        values.iter().sum::<f64>() / values.len() as f64
+                    }
+                };
+                let best = columns
+                    .iter()
+                    .copied()
+                    .max_by(|a, b| mean(*a).total_cmp(&mean(*b)))
+                    .expect("genes have at least one probe");
+                (gene.as_str(), best)
+            })
+            .collect(),
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .from_path(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let header: Vec<&str> = outputs
+        .iter()
+        .map(|output| match output {
+            Output::Column(idx) => headers[*idx].as_str(),
+            Output::Gene(gene) => gene.as_str(),
+        })
+        .collect();
+    wtr.write_record(&header)?;
+    for row in &rows {
+        let record: Vec<String> = outputs
+            .iter()
+            .map(|output| match output {
+                Output::Column(idx) => row.get(*idx).unwrap_or_default().to_string(),
+                Output::Gene(gene) => match strategy {
+                    Collapse::Max => row.get(representative[gene.as_str()]).unwrap_or_default().trim().to_string(),
+                    Collapse::Mean => {
+                        let values: Vec<f64> = members[gene].iter().filter_map(|idx| value(row, *idx)).collect();
+                        if values.is_empty() {
+                            String::new()
+                        } else {
+                            (values.iter().sum::<f64>() / values.len() as f64).to_string()
+                        }
+                    }
+                },
+            })
+            .collect();
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+
+    let summary = CollapseSummary {
+        platform: probes.platform.clone(),
+        strategy,
+        probes: members.values().map(Vec::len).sum(),
+        dropped,
+        genes: members.len(),
+        path: path.to_path_buf(),
+    };
+    let mut collapsed = DatasetRecord::from_path_with(path, Some(record.dataset.description.clone()), record.parsing.derived())?;
+    collapsed.dataset.id = record.dataset.id.clone();
+    collapsed.species = record.species;
+    Ok((collapsed, summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOFT: &str = "^PLATFORM = GPL570\n!Platform_title = [HG-U133_Plus_2]\n#ID = Affymetrix Probe Set ID\n\
+                        !platform_table_begin\nID\tGB_ACC\tGene Symbol\n\
+                        1_at\tNM_1\tTP53\n2_at\tNM_2\tTP53\n3_at\tNM_3\tDDR1 /// MIR4640\n4_at\tNM_4\t\n\
+                        !platform_table_end\n";
+
+    #[test]
+    fn test_parse_soft_and_affymetrix() {
+        let map = ProbeMap::parse(SOFT).unwrap();
+        assert_eq!(map.platform.as_deref(), Some("GPL570"));
+        assert_eq!(map.gene("2_at"), Some("TP53"));
+        assert_eq!(map.gene("3_at"), None);
+        assert!(map.contains("4_at"));
+        assert_eq!(map.len(), 2);
+
+        let csv = "#%chip_type=HuGene-2_0-st\n\"Transcript Cluster ID\",\"gene_assignment\"\n\
+                   \"16657436\",\"NM_000546 // TP53 // tumor protein p53 // 17p13.1 // 7157\"\n\
+                   \"16657440\",\"---\"\n";
+        let map = ProbeMap::parse(csv).unwrap();
+        assert_eq!(map.platform.as_deref(), Some("HuGene-2_0-st"));
+        assert_eq!(map.gene("16657436"), Some("TP53"));
+    }
+
+    #[test]
+    fn test_collapse_dataset() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("probes.csv");
+        std::fs::write(&input, "age,1_at,2_at,3_at\n30,1.0,5.0,9\n50,3.0,7.0,9\n").unwrap();
+        let record = DatasetRecord::from_path(&input, None).unwrap();
+        let probes = ProbeMap::parse(SOFT).unwrap();
+
+        let (collapsed, summary) = collapse_dataset(&record, &probes, Collapse::Max, &dir.path().join("max.csv")).unwrap();
+        assert_eq!(collapsed.columns, vec!["age", "TP53"]);
+        assert_eq!((summary.probes, summary.dropped, summary.genes), (2, 1, 1));
+        assert_eq!(std::fs::read_to_string(&summary.path).unwrap(), "age,TP53\n30,5.0\n50,7.0\n");
+
+        let (_, summary) = collapse_dataset(&record, &probes, Collapse::Mean, &dir.path().join("mean.csv")).unwrap();
+        assert_eq!(std::fs::read_to_string(&summary.path).unwrap(), "age,TP53\n30,3\n50,5\n");
+    }
+}
@@ -0,0 +1,144 @@
+//! Species of a dataset
+//!
+//! Workflows run on human, mouse or rat data. The species decides which
+//! Ensembl gene ID prefix is valid (`ENSG`, `ENSMUSG`, `ENSRNOG`), which
+//! BioMart dataset annotates the genes and whether markers are mapped to
+//! human orthologs before they are compared with the literature.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Species {
+    #[default]
+    Human,
+    Mouse,
+    Rat,
+}
+
+impl Species {
+    pub const ALL: [Species; 3] = [Self::Human, Self::Mouse, Self::Rat];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Human => "human",
+            Self::Mouse => "mouse",
+            Self::Rat => "rat",
+        }
+    }
+
+    pub fn scientific_name(self) -> &'static str {
+        match self {
+            Self::Human => "Homo sapiens",
+            Self::Mouse => "Mus musculus",
+            Self::Rat => "Rattus norvegicus",
+        }
+    }
+
+    /// Prefix of Ensembl gene IDs of this species
+    pub fn ensembl_prefix(self) -> &'static str {
+        match self {
+            Self::Human => "ENSG",
+            Self::Mouse => "ENSMUSG",
+            Self::Rat => "ENSRNOG",
+        }
+    }
+
+    /// BioMart dataset name prefix (`hsapiens` in `hsapiens_gene_ensembl`)
+    pub fn biomart_name(self) -> &'static str {
+        match self {
+            Self::Human => "hsapiens",
+            Self::Mouse => "mmusculus",
+            Self::Rat => "rnorvegicus",
+        }
+    }
+
+    /// Species of an Ensembl gene ID (`ENSMUSG00000059552.8`), or `None`
+    /// when `id` is not a human, mouse or rat gene ID
+    pub fn of_ensembl_id(id: &str) -> Option<Self> {
+        let id = id.trim();
+        let id = id.split_once('.').map_or(id, |(id, _)| id).to_ascii_uppercase();
+        Self::ALL.into_iter().find(|species| {
+            id.strip_prefix(species.ensembl_prefix())
+                .is_some_and(|digits| digits.len() == 11 && digits.bytes().all(|b| b.is_ascii_digit()))
+        })
+    }
+
+    /// Most common species among the Ensembl gene IDs in `ids`
+    pub fn detect<'a>(ids: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let mut counts = [0usize; 3];
+        for species in ids.into_iter().filter_map(Self::of_ensembl_id) {
+            counts[species as usize] += 1;
+        }
+        Self::ALL
+            .into_iter()
+            .filter(|species| counts[*species as usize] > 0)
+            .max_by_key(|species| counts[*species as usize])
+    }
+
+    /// Fail when any Ensembl gene ID in `ids` belongs to another species
+    pub fn check_ids<'a>(self, ids: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let mismatched: Vec<(&str, Species)> = ids
+            .into_iter()
+            .filter_map(|id| Self::of_ensembl_id(id).filter(|s| *s != self).map(|s| (id, s)))
+            .collect();
+        if let Some((_, other)) = mismatched.first() {
+            let examples: Vec<&str> = mismatched.iter().take(3).map(|(id, _)| id.trim()).collect();
+            bail!(
+                "Dataset is set to {} but {} gene ID(s) are {} ({}); expected IDs starting with {}",
+                self,
+                mismatched.len(),
+                other,
+                examples.join(", "),
+                self.ensembl_prefix()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Species {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for Species {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "human" | "homo sapiens" | "hsapiens" | "hs" => Ok(Self::Human),
+            "mouse" | "mus musculus" | "mmusculus" | "mm" => Ok(Self::Mouse),
+            "rat" | "rattus norvegicus" | "rnorvegicus" | "rn" => Ok(Self::Rat),
+            other => Err(format!("unknown species '{}' (expected human, mouse or rat)", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_species_from_ensembl_ids() {
+        assert_eq!(Species::of_ensembl_id("ENSG00000141510.17"), Some(Species::Human));
+        assert_eq!(Species::of_ensembl_id("ensmusg00000059552"), Some(Species::Mouse));
+        assert_eq!(Species::of_ensembl_id("ENSRNOG00000010756"), Some(Species::Rat));
+        assert_eq!(Species::of_ensembl_id("ENST00000269305"), None);
+        assert_eq!(Species::of_ensembl_id("age"), None);
+
+        let columns = ["age", "ENSMUSG00000059552", "ENSMUSG00000044303", "ENSG00000141510"];
+        assert_eq!(Species::detect(columns), Some(Species::Mouse));
+        assert_eq!(Species::detect(["age", "sex"]), None);
+
+        let err = Species::Mouse.check_ids(columns).unwrap_err().to_string();
+        assert!(err.contains("1 gene ID(s) are human (ENSG00000141510)"), "{}", err);
+        assert!(Species::Human.check_ids(["ENSG00000141510", "age"]).is_ok());
+        assert_eq!("Mus musculus".parse::<Species>(), Ok(Species::Mouse));
+    }
+}
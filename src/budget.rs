@@ -0,0 +1,307 @@
+//! Usage ledger and budget checks
+//!
+//! Every LLM completion and SerpAPI query is recorded here, both for the
+//! current process (session) and per calendar month. Monthly totals are kept in
+//! `usage.json` under the local data directory so they survive restarts.
+//!
+//! Agents ask [`llm_exceeded`] / [`search_exceeded`] before spending; once a
+//! limit from [`BudgetConfig`] is reached they fall back to their offline or
+//! cached paths instead of failing.
+//!
+//! Both recorders also forward to [`crate::metering`], which attributes usage
+//! to an account in the database when one is configured.
+
+use crate::config::BudgetConfig;
+use crate::metering::{self, UsageMetric};
+use crate::types::TokenUsage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+
+/// Approximate USD prices per million tokens (input, output), matched by
+/// longest model-name prefix. Costs are estimates for budgeting only.
+const PRICES_PER_MTOK: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("gpt-4-turbo", 10.00, 30.00),
+    ("gpt-4", 30.00, 60.00),
+    ("gpt-3.5", 0.50, 1.50),
+    ("o1-mini", 1.10, 4.40),
+    ("o1", 15.00, 60.00),
+    ("o3-mini", 1.10, 4.40),
+    ("o3", 2.00, 8.00),
+    ("claude-3-haiku", 0.25, 1.25),
+    ("claude-3-5-haiku", 0.80, 4.00),
+    ("claude-haiku", 0.80, 4.00),
+    ("claude-3-opus", 15.00, 75.00),
+    ("claude-opus", 15.00, 75.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-3-7-sonnet", 3.00, 15.00),
+    ("claude-sonnet", 3.00, 15.00),
+    ("gemini-1.5-flash", 0.075, 0.30),
+    ("gemini-1.5-pro", 1.25, 5.00),
+    ("gemini-2.0-flash", 0.10, 0.40),
+    ("gemini-2.5-flash", 0.30, 2.50),
+    ("gemini-2.5-pro", 1.25, 10.00),
+    ("llama", 0.59, 0.79),
+    ("mixtral", 0.24, 0.24),
+];
+
+/// Price used for models missing from the table
+const DEFAULT_PRICE_PER_MTOK: (f64, f64) = (1.00, 3.00);
+
+/// Prompt-cache writes and reads, relative to the input price (Anthropic's
+/// five-minute cache)
+const CACHE_WRITE_PRICE: f64 = 1.25;
+const CACHE_READ_PRICE: f64 = 0.10;
+
+const LEDGER_FILE: &str = "usage.json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub tokens: u64,
+    pub usd: f64,
+    pub search_queries: u32,
+}
+
+/// Usage for this session and the current month
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UsageSnapshot {
+    pub session: UsageTotals,
+    pub month: UsageTotals,
+}
+
+impl UsageSnapshot {
+    /// Reason the LLM budget is exhausted, if it is
+    pub fn llm_exceeded(&self, budget: &BudgetConfig) -> Option<String> {
+        if let Some(limit) = budget.session_usd {
+            if self.session.usd >= limit {
+                return Some(format!("session spend ${:.2} reached limit ${:.2}", self.session.usd, limit));
+            }
+        }
+        if let Some(limit) = budget.monthly_usd {
+            if self.month.usd >= limit {
+                return Some(format!("monthly spend ${:.2} reached limit ${:.2}", self.month.usd, limit));
+            }
+        }
+        if let Some(limit) = budget.session_tokens {
+            if self.session.tokens >= limit {
+                return Some(format!("session tokens {} reached limit {}", self.session.tokens, limit));
+            }
+        }
+        if let Some(limit) = budget.monthly_tokens {
+            if self.month.tokens >= limit {
+                return Some(format!("monthly tokens {} reached limit {}", self.month.tokens, limit));
+            }
+        }
+        None
+    }
+
+    /// Reason the SerpAPI quota is exhausted, if it is
+    pub fn search_exceeded(&self, budget: &BudgetConfig) -> Option<String> {
+        if let Some(limit) = budget.session_search_queries {
+            if self.session.search_queries >= limit {
+                return Some(format!("session SerpAPI queries {} reached quota {}", self.session.search_queries, limit));
+            }
+        }
+        if let Some(limit) = budget.monthly_search_queries {
+            if self.month.search_queries >= limit {
+                return Some(format!("monthly SerpAPI queries {} reached quota {}", self.month.search_queries, limit));
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Ledger {
+    /// Totals keyed by `YYYY-MM`
+    months: BTreeMap<String, UsageTotals>,
+}
+
+struct State {
+    session: UsageTotals,
+    ledger: Ledger,
+    path: Option<PathBuf>,
+}
+
+impl State {
+    fn load() -> Self {
+        let path = dirs::data_local_dir().map(|d| d.join("oxidized-bio").join(LEDGER_FILE));
+        let ledger = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Self {
+            session: UsageTotals::default(),
+            ledger,
+            path,
+        }
+    }
+
+    fn month_mut(&mut self) -> &mut UsageTotals {
+        self.ledger.months.entry(current_month()).or_default()
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else { return };
+        let result = path
+            .parent()
+            .map(std::fs::create_dir_all)
+            .transpose()
+            .and_then(|_| {
+                let json = serde_json::to_string_pretty(&self.ledger).unwrap_or_default();
+                std::fs::write(path, json)
+            });
+        if let Err(e) = result {
+            warn!(error = %e, path = %path.display(), "Failed to persist usage ledger");
+        }
+    }
+}
+
+fn state() -> &'static Mutex<State> {
+    static STATE: OnceLock<Mutex<State>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(State::load()))
+}
+
+fn current_month() -> String {
+    chrono::Local::now().format("%Y-%m").to_string()
+}
+
+/// (input, output) price per million tokens of `model`
+fn prices(model: &str) -> (f64, f64) {
+    // OpenRouter models are namespaced ("anthropic/claude-sonnet-4")
+    let name = model.rsplit('/').next().unwrap_or(model).to_ascii_lowercase();
+    PRICES_PER_MTOK
+        .iter()
+        .filter(|(prefix, _, _)| name.starts_with(prefix))
+        .max_by_key(|(prefix, _, _)| prefix.len())
+        .map(|(_, i, o)| (*i, *o))
+        .unwrap_or(DEFAULT_PRICE_PER_MTOK)
+}
+
+/// Estimated USD cost of a completion
+pub fn estimate_cost(model: &str, prompt_tokens: u32, completion_tokens: u32) -> f64 {
+    let (input, output) = prices(model);
+    (prompt_tokens as f64 * input + completion_tokens as f64 * output) / 1_000_000.0
+}
+
+/// Estimated USD cost of a completion, with prompt-cache reads and writes
+pub fn estimate_usage_cost(model: &str, usage: &TokenUsage) -> f64 {
+    let cached = usage.cache_write_tokens as f64 * CACHE_WRITE_PRICE + usage.cache_read_tokens as f64 * CACHE_READ_PRICE;
+    estimate_cost(model, usage.prompt_tokens, usage.completion_tokens) + cached * prices(model).0 / 1_000_000.0
+}
+
+/// Record a completed LLM call
+pub fn record_llm(model: &str, usage: &TokenUsage) {
+    let (prompt_tokens, completion_tokens) = (usage.prompt_tokens, usage.completion_tokens);
+    let tokens = [prompt_tokens, completion_tokens, usage.cache_write_tokens, usage.cache_read_tokens]
+        .iter()
+        .map(|&t| t as u64)
+        .sum::<u64>();
+    let usd = estimate_usage_cost(model, usage);
+    let mut guard = state().lock().unwrap_or_else(|e| e.into_inner());
+    let state = &mut *guard;
+    state.session.tokens += tokens;
+    state.session.usd += usd;
+    let month = state.month_mut();
+    month.tokens += tokens;
+    month.usd += usd;
+    state.persist();
+    drop(guard);
+    metering::record(
+        UsageMetric::LlmTokens,
+        tokens,
+        usd,
+        serde_json::json!({
+            "model": model,
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "cache_write_tokens": usage.cache_write_tokens,
+            "cache_read_tokens": usage.cache_read_tokens,
+        }),
+    );
+}
+
+/// Record SerpAPI queries actually sent
+pub fn record_search(queries: u32) {
+    if queries == 0 {
+        return;
+    }
+    let mut state = state().lock().unwrap_or_else(|e| e.into_inner());
+    state.session.search_queries += queries;
+    state.month_mut().search_queries += queries;
+    state.persist();
+    drop(state);
+    metering::record(UsageMetric::SearchQueries, queries as u64, 0.0, serde_json::json!({ "engine": "serpapi" }));
+}
+
+pub fn snapshot() -> UsageSnapshot {
+    let state = state().lock().unwrap_or_else(|e| e.into_inner());
+    UsageSnapshot {
+        session: state.session,
+        month: state.ledger.months.get(&current_month()).copied().unwrap_or_default(),
+    }
+}
+
+pub fn llm_exceeded(budget: &BudgetConfig) -> Option<String> {
+    snapshot().llm_exceeded(budget)
+}
+
+pub fn search_exceeded(budget: &BudgetConfig) -> Option<String> {
+    snapshot().search_exceeded(budget)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost_uses_longest_prefix() {
+        // gpt-4o-mini must not be priced as gpt-4o or gpt-4
+        let mini = estimate_cost("gpt-4o-mini", 1_000_000, 0);
+        assert!((mini - 0.15).abs() < 1e-9);
+        let full = estimate_cost("gpt-4o", 0, 1_000_000);
+        assert!((full - 10.0).abs() < 1e-9);
+        let routed = estimate_cost("anthropic/claude-sonnet-4", 1_000_000, 0);
+        assert!((routed - 3.0).abs() < 1e-9);
+        let unknown = estimate_cost("some-new-model", 1_000_000, 0);
+        assert!((unknown - DEFAULT_PRICE_PER_MTOK.0).abs() < 1e-9);
+
+        // A cached prefix is billed at 1.25x once, then at a tenth
+        let write = TokenUsage { cache_write_tokens: 1_000_000, ..Default::default() };
+        assert!((estimate_usage_cost("claude-sonnet-4", &write) - 3.75).abs() < 1e-9);
+        let read = TokenUsage { prompt_tokens: 1_000_000, cache_read_tokens: 1_000_000, ..Default::default() };
+        assert!((estimate_usage_cost("claude-sonnet-4", &read) - 3.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_limits() {
+        let budget = BudgetConfig {
+            monthly_usd: Some(10.0),
+            session_tokens: Some(1000),
+            session_search_queries: Some(5),
+            ..Default::default()
+        };
+        let mut usage = UsageSnapshot::default();
+        assert!(usage.llm_exceeded(&budget).is_none());
+        assert!(usage.search_exceeded(&budget).is_none());
+
+        usage.session.tokens = 1000;
+        assert!(usage.llm_exceeded(&budget).unwrap().contains("session tokens"));
+
+        usage.session.tokens = 0;
+        usage.month.usd = 12.5;
+        assert!(usage.llm_exceeded(&budget).unwrap().contains("monthly spend"));
+
+        usage.session.search_queries = 5;
+        assert!(usage.search_exceeded(&budget).is_some());
+        assert!(usage.llm_exceeded(&BudgetConfig::default()).is_none());
+    }
+}
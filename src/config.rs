@@ -1,17 +1,36 @@
 use anyhow::Result;
-use serde::Deserialize;
-use std::env;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub mod sources;
+
+pub use sources::ConfigSources;
+
+use crate::manuscript::csl::CitationStyle;
+use crate::manuscript::template::JournalTemplate;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub database: DatabaseConfig,
     pub redis: RedisConfig,
+    pub queue: QueueConfig,
     pub llm: LLMConfig,
     pub search: SearchConfig,
+    pub network: NetworkConfig,
+    pub budget: BudgetConfig,
     pub storage: StorageConfig,
     pub auth: AuthConfig,
     pub payment: PaymentConfig,
+    pub manuscript: ManuscriptConfig,
+    pub annotation: AnnotationConfig,
+    pub cross_reference: CrossReferenceConfig,
+    pub logging: LoggingConfig,
+    pub deep_research: DeepResearchConfig,
+    pub workflow: WorkflowConfig,
+    /// Settings profile to load instead of the active one (`--profile`)
+    pub settings_profile: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -34,6 +53,49 @@ pub struct RedisConfig {
     pub enabled: bool,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueueConfig {
+    /// Number of worker tasks to run in `--worker` mode
+    pub workers: usize,
+    /// How many of those workers only serve the interactive lane; fewer
+    /// than `workers`, so one is left for batch and scheduled jobs
+    pub interactive_workers: usize,
+    /// How long an idle worker waits before polling again
+    pub poll_interval_ms: u64,
+    /// Lease length for a claimed job before another worker may take it
+    pub visibility_timeout_secs: u64,
+    /// Attempts before a job is moved to the dead letter state
+    pub max_attempts: i32,
+    /// Backoff before the first retry of a failed job (doubles per attempt)
+    pub retry_delay_secs: u64,
+    /// Upper bound on the retry backoff
+    pub retry_max_delay_secs: u64,
+    /// Wake idle workers via Postgres LISTEN/NOTIFY instead of only polling
+    pub listen_notify: bool,
+}
+
+impl QueueConfig {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.interactive_workers > 0 && self.interactive_workers >= self.workers {
+            return Err(format!(
+                "QUEUE_INTERACTIVE_WORKERS ({}) must be less than QUEUE_WORKERS ({}) so batch and scheduled jobs \
+                 still run",
+                self.interactive_workers, self.workers
+            ));
+        }
+        Ok(())
+    }
+
+    /// Default retry policy for jobs enqueued without their own
+    pub fn retry_policy(&self) -> crate::queue::RetryPolicy {
+        crate::queue::RetryPolicy {
+            max_attempts: self.max_attempts.max(1),
+            base_delay: std::time::Duration::from_secs(self.retry_delay_secs),
+            max_delay: std::time::Duration::from_secs(self.retry_max_delay_secs),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct LLMConfig {
     pub openai_api_key: String,
@@ -43,16 +105,226 @@ pub struct LLMConfig {
     pub groq_api_key: String,
     pub default_provider: String,
     pub default_model: String,
+    /// Per-task provider/model routing; tasks without a route use the default
+    #[serde(default)]
+    pub routes: HashMap<LlmTask, LlmRoute>,
+    /// Vendor preferences, fallback models and attribution of OpenRouter
+    /// requests
+    #[serde(default)]
+    pub openrouter: OpenRouterConfig,
+    /// Concurrency, request and token limits per provider; providers
+    /// without an entry are not throttled
+    #[serde(default)]
+    pub rate_limits: HashMap<String, RateLimit>,
+}
+
+/// Hosted LLM providers, i.e. all but the offline mock
+pub const PROVIDERS: [&str; 5] = ["openai", "anthropic", "google", "openrouter", "groq"];
+
+/// Client-side limits on calls to one provider, kept below the provider's
+/// own so parallel tasks queue instead of being rejected with 429s. Unset
+/// limits are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct RateLimit {
+    /// Calls in flight at once
+    pub max_concurrent: Option<usize>,
+    /// Calls started in any 60 seconds
+    pub requests_per_minute: Option<u32>,
+    /// Prompt and completion tokens (estimated) in any 60 seconds
+    pub tokens_per_minute: Option<u32>,
+}
+
+impl RateLimit {
+    /// Limits of `provider` from `<PROVIDER>_MAX_CONCURRENT`,
+    /// `<PROVIDER>_REQUESTS_PER_MINUTE` and `<PROVIDER>_TOKENS_PER_MINUTE`
+    fn from_sources(sources: &ConfigSources, provider: &str) -> Result<Self> {
+        let var = |name: &str| format!("{}_{}", provider.to_uppercase(), name);
+        let limit = Self {
+            max_concurrent: sources.parse_opt(&var("MAX_CONCURRENT"))?,
+            requests_per_minute: sources.parse_opt(&var("REQUESTS_PER_MINUTE"))?,
+            tokens_per_minute: sources.parse_opt(&var("TOKENS_PER_MINUTE"))?,
+        };
+        if limit.max_concurrent == Some(0) || limit.requests_per_minute == Some(0) || limit.tokens_per_minute == Some(0) {
+            anyhow::bail!("{} rate limits must be at least 1", provider);
+        }
+        Ok(limit)
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// OpenRouter-specific request controls. OpenRouter serves each model from
+/// one of several upstream vendors; these pin or exclude vendors, name
+/// models to fall back to, and set the headers that credit the app on
+/// openrouter.ai.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct OpenRouterConfig {
+    /// Vendors tried first, in this order (`anthropic`, `together`, ...)
+    pub order: Vec<String>,
+    /// Vendors requests are restricted to
+    pub only: Vec<String>,
+    /// Vendors never used
+    pub ignore: Vec<String>,
+    /// Whether vendors outside `order` may serve a request once those fail;
+    /// OpenRouter allows it when unset
+    pub allow_fallbacks: Option<bool>,
+    /// Models tried in turn when the requested one fails (`route: fallback`)
+    pub fallback_models: Vec<String>,
+    /// `HTTP-Referer` attribution header, the app's URL
+    pub referer: Option<String>,
+    /// `X-Title` attribution header, the app's name
+    pub title: Option<String>,
+}
+
+impl OpenRouterConfig {
+    /// Controls from the installed config sources
+    pub fn from_env() -> Self {
+        Self::from_sources(ConfigSources::current()).unwrap_or_default()
+    }
+
+    fn from_sources(sources: &ConfigSources) -> Result<Self> {
+        let list = |var: &str| -> Vec<String> {
+            sources
+                .non_empty(var)
+                .map(|v| v.split(',').map(str::trim).filter(|v| !v.is_empty()).map(String::from).collect())
+                .unwrap_or_default()
+        };
+        let config = Self {
+            order: list("OPENROUTER_PROVIDER_ORDER"),
+            only: list("OPENROUTER_PROVIDER_ONLY"),
+            ignore: list("OPENROUTER_PROVIDER_IGNORE"),
+            allow_fallbacks: sources.parse_opt("OPENROUTER_ALLOW_FALLBACKS")?,
+            fallback_models: list("OPENROUTER_FALLBACK_MODELS"),
+            referer: sources.non_empty("OPENROUTER_HTTP_REFERER"),
+            title: sources.non_empty("OPENROUTER_X_TITLE"),
+        };
+        config.validate().map_err(anyhow::Error::msg)?;
+        Ok(config)
+    }
+
+    /// Vendors both required and excluded, and attribution values that
+    /// cannot be sent as headers, are errors
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if let Some(vendor) = self.only.iter().find(|v| self.ignore.contains(v)) {
+            return Err(format!("OpenRouter vendor '{}' is both in only and ignore", vendor));
+        }
+        for (name, value) in [("referer", &self.referer), ("title", &self.title)] {
+            if let Some(value) = value {
+                reqwest::header::HeaderValue::from_str(value)
+                    .map_err(|_| format!("OpenRouter {} '{}' is not a valid header value", name, value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pipeline step that makes LLM calls and can be routed independently
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmTask {
+    Planning,
+    Literature,
+    Reply,
+    /// Manuscript draft revision (Draft 1-3 workflow stages)
+    Drafting,
+}
+
+impl LlmTask {
+    pub const ALL: [LlmTask; 4] = [LlmTask::Planning, LlmTask::Literature, LlmTask::Reply, LlmTask::Drafting];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LlmTask::Planning => "planning",
+            LlmTask::Literature => "literature",
+            LlmTask::Reply => "reply",
+            LlmTask::Drafting => "drafting",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "planning" => Some(LlmTask::Planning),
+            "literature" => Some(LlmTask::Literature),
+            "reply" => Some(LlmTask::Reply),
+            "drafting" => Some(LlmTask::Drafting),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LlmTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Provider (and optionally model and sampling parameters) a task is routed to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LlmRoute {
+    pub provider: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+}
+
+/// Provider, model and key to use for one LLM call
+#[derive(Debug, Clone)]
+pub struct ResolvedLlm {
+    pub provider: String,
+    pub model: String,
+    pub api_key: String,
+    /// Overrides for the agent's own temperature/max_tokens defaults
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+}
+
+impl ResolvedLlm {
+    pub fn temperature_or(&self, default: f32) -> f32 {
+        self.temperature.unwrap_or(default)
+    }
+
+    pub fn max_tokens_or(&self, default: u32) -> u32 {
+        self.max_tokens.unwrap_or(default)
+    }
+
+    pub fn capabilities(&self) -> crate::llm::capabilities::ModelCapabilities {
+        crate::llm::capabilities::lookup(&self.provider, &self.model)
+    }
+
+    /// Output budget: the configured max_tokens, else the task's `default`
+    /// (the model's default budget when `None`), within the model's limit
+    pub fn output_tokens(&self, default: Option<u32>) -> u32 {
+        self.capabilities().output_tokens(self.max_tokens.or(default))
+    }
+}
+
+/// Model used for a provider when nothing more specific is configured
+pub fn default_model_for(provider: &str) -> &'static str {
+    match provider {
+        "anthropic" => "claude-sonnet-4-20250514",
+        "google" => "gemini-2.0-flash",
+        "openrouter" => "anthropic/claude-sonnet-4",
+        "groq" => "groq/compound",
+        "mock" => "mock",
+        _ => "gpt-4o",
+    }
 }
 
 impl LLMConfig {
-    pub fn active_api_key(&self) -> Option<String> {
-        let key = match self.default_provider.as_str() {
+    pub fn api_key_for(&self, provider: &str) -> Option<String> {
+        let key = match provider {
             "openai" => &self.openai_api_key,
             "anthropic" => &self.anthropic_api_key,
             "google" => &self.google_api_key,
             "openrouter" => &self.openrouter_api_key,
             "groq" => &self.groq_api_key,
+            // The offline mock adapter needs no key
+            "mock" => "mock",
             _ => "",
         };
         if key.is_empty() {
@@ -61,6 +333,68 @@ impl LLMConfig {
             Some(key.to_string())
         }
     }
+
+    /// Configured limits of `provider`, unlimited when none are set
+    pub fn rate_limit(&self, provider: &str) -> RateLimit {
+        self.rate_limits.get(provider).copied().unwrap_or_default()
+    }
+
+    /// Hosted providers with an API key
+    pub fn configured_providers(&self) -> Vec<&'static str> {
+        PROVIDERS.into_iter().filter(|p| self.api_key_for(p).is_some()).collect()
+    }
+
+    pub fn active_api_key(&self) -> Option<String> {
+        self.api_key_for(&self.default_provider)
+    }
+
+    /// Resolve the provider/model/key for `task`. A route whose provider has
+    /// no key falls back to the default provider rather than failing; its
+    /// temperature/max_tokens overrides still apply.
+    pub fn for_task(&self, task: LlmTask) -> Option<ResolvedLlm> {
+        let route = self.routes.get(&task);
+        let temperature = route.and_then(|r| r.temperature);
+        let max_tokens = route.and_then(|r| r.max_tokens);
+
+        if let Some(route) = route {
+            if let Some(api_key) = self.api_key_for(&route.provider) {
+                let model = match &route.model {
+                    Some(model) if !model.is_empty() => model.clone(),
+                    _ if route.provider == self.default_provider => self.default_model.clone(),
+                    _ => default_model_for(&route.provider).to_string(),
+                };
+                return Some(ResolvedLlm {
+                    provider: route.provider.clone(),
+                    model,
+                    api_key,
+                    temperature,
+                    max_tokens,
+                });
+            }
+        }
+
+        self.active_api_key().map(|api_key| ResolvedLlm {
+            provider: self.default_provider.clone(),
+            model: self.default_model.clone(),
+            api_key,
+            temperature,
+            max_tokens,
+        })
+    }
+}
+
+/// Read `<PREFIX>_LLM_PROVIDER` / `<PREFIX>_LLM_MODEL` (plus optional
+/// `<PREFIX>_LLM_TEMPERATURE` / `<PREFIX>_LLM_MAX_TOKENS`) into a route, if set
+fn route_from_env(sources: &ConfigSources, prefix: &str) -> Result<Option<LlmRoute>> {
+    let Some(provider) = sources.non_empty(&format!("{}_LLM_PROVIDER", prefix)) else {
+        return Ok(None);
+    };
+    Ok(Some(LlmRoute {
+        provider,
+        model: sources.non_empty(&format!("{}_LLM_MODEL", prefix)),
+        temperature: sources.parse_opt(&format!("{}_LLM_TEMPERATURE", prefix))?,
+        max_tokens: sources.parse_opt(&format!("{}_LLM_MAX_TOKENS", prefix))?,
+    }))
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -73,6 +407,24 @@ pub struct SearchConfig {
     pub light_enabled: bool,
     /// Maximum results per search
     pub max_results: usize,
+    /// NCBI E-utilities key for PubMed (optional)
+    pub ncbi_api_key: String,
+    /// Contact email sent with E-utilities requests
+    pub ncbi_email: String,
+    pub pubmed_enabled: bool,
+    /// Semantic Scholar API key (optional)
+    pub semantic_scholar_api_key: String,
+    pub semantic_scholar_enabled: bool,
+    /// Per-engine result limits (`scholar`, `light`, `pubmed`,
+    /// `semantic_scholar`); engines without one use `max_results`
+    #[serde(default)]
+    pub engine_max_results: HashMap<String, usize>,
+    /// Answer every search from the offline mock engine (tests and demos)
+    #[serde(default)]
+    pub mock_enabled: bool,
+    /// Results scoring below this (0 to 1, see [`crate::search::quality`])
+    /// are dropped
+    pub min_source_score: f64,
 }
 
 impl SearchConfig {
@@ -80,6 +432,371 @@ impl SearchConfig {
     pub fn serpapi_available(&self) -> bool {
         !self.serpapi_key.is_empty() && (self.scholar_enabled || self.light_enabled)
     }
+
+    pub fn max_results_for(&self, engine: &str) -> usize {
+        self.engine_max_results.get(engine).copied().unwrap_or(self.max_results)
+    }
+}
+
+/// Outbound HTTP settings shared by every reqwest client (LLM adapters,
+/// SerpAPI, downloads). Without a proxy configured here, reqwest still honours
+/// the standard `HTTP_PROXY`/`HTTPS_PROXY` environment variables.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NetworkConfig {
+    /// Proxy for all requests, e.g. `http://proxy:3128` or `socks5h://host:1080`
+    pub proxy_url: Option<String>,
+    /// Comma-separated hosts that bypass the proxy
+    pub no_proxy: Option<String>,
+    /// PEM file with extra root certificates (institute TLS inspection)
+    pub ca_cert_path: Option<String>,
+    /// Per-target proxy overrides keyed by provider id (`openai`, `serpapi`, ...)
+    #[serde(default)]
+    pub provider_proxies: HashMap<String, String>,
+}
+
+impl NetworkConfig {
+    /// Proxy to use when talking to `target`, if any
+    pub fn proxy_for(&self, target: &str) -> Option<&str> {
+        self.provider_proxies
+            .get(target)
+            .or(self.proxy_url.as_ref())
+            .map(|p| p.as_str())
+            .filter(|p| !p.is_empty())
+    }
+
+    pub fn from_env() -> Self {
+        let sources = ConfigSources::current();
+        Self {
+            proxy_url: sources.non_empty("PROXY_URL"),
+            no_proxy: sources.non_empty("NO_PROXY"),
+            ca_cert_path: sources.non_empty("CA_CERT_PATH"),
+            provider_proxies: HashMap::new(),
+        }
+    }
+}
+
+/// Spending limits for LLM calls and SerpAPI queries. `None` means unlimited.
+/// Session limits reset on every start; monthly limits follow the calendar
+/// month of the local usage ledger (see [`crate::budget`]).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BudgetConfig {
+    pub monthly_usd: Option<f64>,
+    pub session_usd: Option<f64>,
+    pub monthly_tokens: Option<u64>,
+    pub session_tokens: Option<u64>,
+    pub monthly_search_queries: Option<u32>,
+    pub session_search_queries: Option<u32>,
+}
+
+impl BudgetConfig {
+    /// Limits from the installed config sources. Invalid values are rejected
+    /// by [`Config::from_env`] at startup and treated as unlimited here.
+    pub fn from_env() -> Self {
+        Self::from_sources(ConfigSources::current()).unwrap_or_default()
+    }
+
+    fn from_sources(sources: &ConfigSources) -> Result<Self> {
+        Ok(Self {
+            monthly_usd: sources.parse_opt("BUDGET_MONTHLY_USD")?,
+            session_usd: sources.parse_opt("BUDGET_SESSION_USD")?,
+            monthly_tokens: sources.parse_opt("BUDGET_MONTHLY_TOKENS")?,
+            session_tokens: sources.parse_opt("BUDGET_SESSION_TOKENS")?,
+            monthly_search_queries: sources.parse_opt("SERPAPI_MONTHLY_QUOTA")?,
+            session_search_queries: sources.parse_opt("SERPAPI_SESSION_QUOTA")?,
+        })
+    }
+}
+
+/// Manuscript formatting
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ManuscriptConfig {
+    /// Journal template for drafts and LaTeX export
+    pub template: JournalTemplate,
+    /// CSL style for Markdown and DOCX exports
+    pub citation_style: CitationStyle,
+    /// Have the drafting model reword the generated figure legends
+    pub polish_legends: bool,
+}
+
+impl ManuscriptConfig {
+    /// Settings from the installed config sources. Invalid values are
+    /// rejected by [`Config::from_env`] at startup; here one drops the
+    /// section back to the default template and citation style.
+    pub fn from_env() -> Self {
+        Self::from_sources(ConfigSources::current()).unwrap_or_default()
+    }
+
+    fn from_sources(sources: &ConfigSources) -> Result<Self> {
+        Ok(Self {
+            template: sources.parse_or("MANUSCRIPT_TEMPLATE", JournalTemplate::default())?,
+            citation_style: sources.parse_or("MANUSCRIPT_CITATION_STYLE", CitationStyle::default())?,
+            polish_legends: sources.parse_or("MANUSCRIPT_POLISH_LEGENDS", false)?,
+        })
+    }
+}
+
+/// Gene annotation sources
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnnotationConfig {
+    /// Ensembl gene table (`gene_id<TAB>symbol<TAB>biotype`) used instead
+    /// of the cached BioMart download
+    pub ensembl_file: Option<PathBuf>,
+    /// Download and cache the human gene table from Ensembl BioMart
+    pub download: bool,
+    /// GMT gene sets for GO enrichment instead of the bundled GO slim
+    pub go_file: Option<PathBuf>,
+    /// GMT pathways (e.g. Reactome's `ReactomePathways.gmt` or a KEGG
+    /// collection) for pathway enrichment instead of the bundled set
+    pub pathway_file: Option<PathBuf>,
+}
+
+impl Default for AnnotationConfig {
+    fn default() -> Self {
+        Self {
+            ensembl_file: None,
+            download: true,
+            go_file: None,
+            pathway_file: None,
+        }
+    }
+}
+
+impl AnnotationConfig {
+    /// Settings from the installed config sources. [`Config::from_env`]
+    /// rejects invalid values at startup; here they mean downloading the
+    /// annotations without local files.
+    pub fn from_env() -> Self {
+        Self::from_sources(ConfigSources::current()).unwrap_or_default()
+    }
+
+    fn from_sources(sources: &ConfigSources) -> Result<Self> {
+        Ok(Self {
+            ensembl_file: sources.non_empty("ANNOTATION_ENSEMBL_FILE").map(PathBuf::from),
+            download: sources.parse_or("ANNOTATION_DOWNLOAD", true)?,
+            go_file: sources.non_empty("ANNOTATION_GO_FILE").map(PathBuf::from),
+            pathway_file: sources.non_empty("ANNOTATION_PATHWAY_FILE").map(PathBuf::from),
+        })
+    }
+}
+
+/// Literature cross-referencing of biomarker candidates
+#[derive(Debug, Clone, Deserialize)]
+pub struct CrossReferenceConfig {
+    /// Top-ranked candidates searched after each analysis; 0 disables the step
+    pub top_n: usize,
+    /// Publications needed to flag a gene as previously reported
+    pub min_reports: usize,
+    /// Search query, with `{gene}` replaced by the gene symbol
+    pub query: String,
+}
+
+impl Default for CrossReferenceConfig {
+    fn default() -> Self {
+        Self {
+            top_n: 10,
+            min_reports: 3,
+            query: "{gene} aging".to_string(),
+        }
+    }
+}
+
+impl CrossReferenceConfig {
+    /// Settings from the installed config sources, or the top 10 genes and
+    /// the `{gene} aging` query when one is invalid (which
+    /// [`Config::from_env`] refuses at startup).
+    pub fn from_env() -> Self {
+        Self::from_sources(ConfigSources::current()).unwrap_or_default()
+    }
+
+    fn from_sources(sources: &ConfigSources) -> Result<Self> {
+        let defaults = Self::default();
+        let query = sources.string_or("CROSS_REFERENCE_QUERY", &defaults.query);
+        if !query.contains("{gene}") {
+            anyhow::bail!("CROSS_REFERENCE_QUERY must contain {{gene}}, got '{}'", query);
+        }
+        Ok(Self {
+            top_n: sources.parse_or("CROSS_REFERENCE_TOP_N", defaults.top_n)?,
+            min_reports: sources.parse_or("CROSS_REFERENCE_MIN_REPORTS", defaults.min_reports)?,
+            query,
+        })
+    }
+}
+
+/// Retention of the TUI's log files
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoggingConfig {
+    /// A session's log file is rolled over once it reaches this size
+    pub max_file_mb: u64,
+    /// Log files older than this are deleted; 0 keeps them regardless of age
+    pub max_age_days: u64,
+    /// Newest log files kept; 0 keeps any number
+    pub max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            max_file_mb: 10,
+            max_age_days: 14,
+            max_files: 20,
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// Settings from the installed config sources; an invalid one, refused
+    /// by [`Config::from_env`] at startup, keeps the default rotation here.
+    pub fn from_env() -> Self {
+        Self::from_sources(ConfigSources::current()).unwrap_or_default()
+    }
+
+    fn from_sources(sources: &ConfigSources) -> Result<Self> {
+        let defaults = Self::default();
+        let max_file_mb = sources.parse_or("LOG_MAX_FILE_MB", defaults.max_file_mb)?;
+        if max_file_mb == 0 {
+            anyhow::bail!("LOG_MAX_FILE_MB must be at least 1");
+        }
+        Ok(Self {
+            max_file_mb,
+            max_age_days: sources.parse_or("LOG_MAX_AGE_DAYS", defaults.max_age_days)?,
+            max_files: sources.parse_or("LOG_MAX_FILES", defaults.max_files)?,
+        })
+    }
+}
+
+/// Iterative research runs (`POST /api/deep-research/start`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeepResearchConfig {
+    /// Iterations when a request does not set `depth`
+    pub depth: u32,
+    /// Upper bound on a request's `depth`
+    pub max_depth: u32,
+    /// Searches run per iteration
+    pub max_tasks: usize,
+}
+
+impl Default for DeepResearchConfig {
+    fn default() -> Self {
+        Self {
+            depth: 3,
+            max_depth: 6,
+            max_tasks: 3,
+        }
+    }
+}
+
+impl DeepResearchConfig {
+    /// Settings from the installed config sources. Invalid values stop
+    /// [`Config::from_env`] at startup and fall back to the default depths
+    /// and search count here.
+    pub fn from_env() -> Self {
+        Self::from_sources(ConfigSources::current()).unwrap_or_default()
+    }
+
+    fn from_sources(sources: &ConfigSources) -> Result<Self> {
+        let defaults = Self::default();
+        let depth = sources.parse_or("DEEP_RESEARCH_DEPTH", defaults.depth)?;
+        let max_depth = sources.parse_or("DEEP_RESEARCH_MAX_DEPTH", defaults.max_depth)?;
+        if depth == 0 || depth > max_depth {
+            anyhow::bail!(
+                "DEEP_RESEARCH_DEPTH must be between 1 and DEEP_RESEARCH_MAX_DEPTH ({}), got {}",
+                max_depth,
+                depth
+            );
+        }
+        Ok(Self {
+            depth,
+            max_depth,
+            max_tasks: sources.parse_or("DEEP_RESEARCH_MAX_TASKS", defaults.max_tasks)?.max(1),
+        })
+    }
+
+    /// Iterations for a request asking for `depth`
+    pub fn depth_for(&self, depth: Option<u32>) -> u32 {
+        depth.unwrap_or(self.depth).clamp(1, self.max_depth)
+    }
+}
+
+/// Point of the TUI's automated workflow where it can wait for the
+/// researcher
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalGate {
+    /// After the research plan
+    Plan,
+    /// After the analysis findings
+    Findings,
+    /// After each of drafts 1-3
+    Drafts,
+}
+
+impl ApprovalGate {
+    pub const ALL: [ApprovalGate; 3] = [Self::Plan, Self::Findings, Self::Drafts];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Plan => "plan",
+            Self::Findings => "findings",
+            Self::Drafts => "drafts",
+        }
+    }
+
+    /// Gates in a comma-separated list; `all` and `none` are accepted too
+    pub fn parse_list(list: &str) -> Result<Vec<Self>> {
+        match list.trim().to_ascii_lowercase().as_str() {
+            "all" => return Ok(Self::ALL.to_vec()),
+            "" | "none" => return Ok(Vec::new()),
+            _ => {}
+        }
+        let mut gates = Vec::new();
+        for name in list.split(',').map(|n| n.trim().to_ascii_lowercase()).filter(|n| !n.is_empty()) {
+            let gate = Self::ALL
+                .into_iter()
+                .find(|g| g.as_str() == name || g.as_str().trim_end_matches('s') == name)
+                .ok_or_else(|| anyhow::anyhow!("unknown approval gate '{}' (expected plan, findings or drafts)", name))?;
+            if !gates.contains(&gate) {
+                gates.push(gate);
+            }
+        }
+        Ok(gates)
+    }
+}
+
+/// Approval gates of the TUI's automated workflow
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WorkflowConfig {
+    /// Gates the workflow waits at for `/approve` or `/feedback`; none by
+    /// default, so the workflow runs straight through
+    pub gates: Vec<ApprovalGate>,
+    /// Seconds a gate waits before continuing on its own; 0 waits
+    /// indefinitely
+    pub gate_timeout_secs: u64,
+}
+
+impl WorkflowConfig {
+    /// Gates from the installed config sources. Invalid values are rejected
+    /// by [`Config::from_env`] at startup and leave the workflow ungated here.
+    pub fn from_env() -> Self {
+        Self::from_sources(ConfigSources::current()).unwrap_or_default()
+    }
+
+    fn from_sources(sources: &ConfigSources) -> Result<Self> {
+        let gates = ApprovalGate::parse_list(&sources.string_or("WORKFLOW_APPROVAL_GATES", "none"))
+            .map_err(|e| anyhow::anyhow!("{}: {}", sources::describe("WORKFLOW_APPROVAL_GATES"), e))?;
+        Ok(Self {
+            gates,
+            gate_timeout_secs: sources.parse_or("WORKFLOW_GATE_TIMEOUT_SECS", 0)?,
+        })
+    }
+
+    pub fn gate_enabled(&self, gate: ApprovalGate) -> bool {
+        self.gates.contains(&gate)
+    }
+
+    /// How long a gate waits, `None` for indefinitely
+    pub fn gate_timeout(&self) -> Option<std::time::Duration> {
+        (self.gate_timeout_secs > 0).then(|| std::time::Duration::from_secs(self.gate_timeout_secs))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -106,22 +823,72 @@ pub struct PaymentConfig {
     pub x402_environment: String,
     pub x402_payment_address: Option<String>,
     pub x402_network: String,
+    /// Facilitator that verifies and settles x402 payments
+    pub x402_facilitator_url: String,
+    /// Public base URL of the API, e.g. `https://api.example.org`; paid
+    /// resources are named under it rather than under the request's Host
+    pub x402_public_url: Option<String>,
+    /// Price per deep-research request in USD
+    pub x402_deep_research_price_usd: f64,
+    /// Price per analysis request in USD
+    pub x402_analysis_price_usd: f64,
     pub cdp_api_key_id: Option<String>,
     pub cdp_api_key_secret: Option<String>,
 }
 
 impl Config {
+    /// Load configuration from the installed [`ConfigSources`]: CLI
+    /// overrides, then environment variables (and `.env`), then the config
+    /// file. Invalid values are reported with their config key and env name.
     pub fn from_env() -> Result<Self> {
         dotenvy::dotenv().ok();
+        let s = ConfigSources::current();
 
-        Ok(Self {
+        let mut routes = HashMap::new();
+        for (task, prefix) in [
+            (LlmTask::Planning, "PLANNING"),
+            (LlmTask::Literature, "LITERATURE"),
+            (LlmTask::Drafting, "DRAFTING"),
+        ] {
+            if let Some(route) = route_from_env(s, prefix)? {
+                routes.insert(task, route);
+            }
+        }
+
+        let mut rate_limits = HashMap::new();
+        for provider in PROVIDERS {
+            let limit = RateLimit::from_sources(s, provider)?;
+            if !limit.is_unlimited() {
+                rate_limits.insert(provider.to_string(), limit);
+            }
+        }
+
+        let mut engine_max_results = HashMap::new();
+        for (engine, var) in [
+            ("scholar", "SCHOLAR_MAX_RESULTS"),
+            ("light", "LIGHT_MAX_RESULTS"),
+            ("pubmed", "PUBMED_MAX_RESULTS"),
+            ("semantic_scholar", "SEMANTIC_SCHOLAR_MAX_RESULTS"),
+        ] {
+            if let Some(n) = s.parse_opt(var)? {
+                engine_max_results.insert(engine.to_string(), n);
+            }
+        }
+
+        let min_source_score = s.parse_or("SEARCH_MIN_SOURCE_SCORE", crate::search::quality::DEFAULT_MIN_SCORE)?;
+        if !(0.0..=1.0).contains(&min_source_score) {
+            anyhow::bail!("SEARCH_MIN_SOURCE_SCORE must be between 0 and 1, got {}", min_source_score);
+        }
+
+        // One interactive-only worker by default, unless it is the only one
+        let queue_workers: usize = s.parse_or("QUEUE_WORKERS", 2)?;
+
+        let config = Self {
             server: ServerConfig {
-                port: env::var("PORT")
-                    .unwrap_or_else(|_| "3000".to_string())
-                    .parse()?,
-                host: env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
-                cors_allowed_origins: env::var("ALLOWED_ORIGINS")
-                    .unwrap_or_else(|_| "http://localhost:3000,http://localhost:5173".to_string())
+                port: s.parse_or("PORT", 3000)?,
+                host: s.string_or("HOST", "0.0.0.0"),
+                cors_allowed_origins: s
+                    .string_or("ALLOWED_ORIGINS", "http://localhost:3000,http://localhost:5173")
                     .split(',')
                     .map(|s| s.trim().to_string())
                     .collect(),
@@ -129,82 +896,198 @@ impl Config {
             database: DatabaseConfig {
                 // DATABASE_URL is optional - the app can run without a database
                 // (e.g., TUI mode, or server mode with just API key validation)
-                url: env::var("DATABASE_URL")
-                    .unwrap_or_else(|_| String::new()),
-                max_connections: env::var("DB_MAX_CONNECTIONS")
-                    .unwrap_or_else(|_| "10".to_string())
-                    .parse()?,
-                min_connections: env::var("DB_MIN_CONNECTIONS")
-                    .unwrap_or_else(|_| "1".to_string())
-                    .parse()?,
+                url: s.get("DATABASE_URL").unwrap_or_default(),
+                max_connections: s.parse_or("DB_MAX_CONNECTIONS", 10)?,
+                min_connections: s.parse_or("DB_MIN_CONNECTIONS", 1)?,
             },
             redis: RedisConfig {
-                url: env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string()),
-                enabled: env::var("USE_JOB_QUEUE")
-                    .unwrap_or_else(|_| "false".to_string())
-                    .parse()?,
+                url: s.string_or("REDIS_URL", "redis://localhost:6379"),
+                enabled: s.parse_or("USE_JOB_QUEUE", false)?,
+            },
+            queue: QueueConfig {
+                workers: queue_workers,
+                interactive_workers: s.parse_or("QUEUE_INTERACTIVE_WORKERS", queue_workers.clamp(1, 2) - 1)?,
+                poll_interval_ms: s.parse_or("QUEUE_POLL_INTERVAL_MS", 1000)?,
+                visibility_timeout_secs: s.parse_or("QUEUE_VISIBILITY_TIMEOUT_SECS", 300)?,
+                max_attempts: s.parse_or("QUEUE_MAX_ATTEMPTS", 3)?,
+                retry_delay_secs: s.parse_or("QUEUE_RETRY_DELAY_SECS", 30)?,
+                retry_max_delay_secs: s.parse_or("QUEUE_RETRY_MAX_DELAY_SECS", 3600)?,
+                listen_notify: s.parse_or("QUEUE_LISTEN_NOTIFY", true)?,
             },
             llm: LLMConfig {
-                openai_api_key: env::var("OPENAI_API_KEY").unwrap_or_default(),
-                anthropic_api_key: env::var("ANTHROPIC_API_KEY").unwrap_or_default(),
-                google_api_key: env::var("GOOGLE_API_KEY").unwrap_or_default(),
-                openrouter_api_key: env::var("OPENROUTER_API_KEY").unwrap_or_default(),
-                groq_api_key: env::var("GROQ_API_KEY").unwrap_or_default(),
-                default_provider: env::var("REPLY_LLM_PROVIDER").unwrap_or_else(|_| "openai".to_string()),
-                default_model: env::var("REPLY_LLM_MODEL").unwrap_or_else(|_| "gpt-4".to_string()),
+                openai_api_key: s.get("OPENAI_API_KEY").unwrap_or_default(),
+                anthropic_api_key: s.get("ANTHROPIC_API_KEY").unwrap_or_default(),
+                google_api_key: s.get("GOOGLE_API_KEY").unwrap_or_default(),
+                openrouter_api_key: s.get("OPENROUTER_API_KEY").unwrap_or_default(),
+                groq_api_key: s.get("GROQ_API_KEY").unwrap_or_default(),
+                default_provider: s.string_or("REPLY_LLM_PROVIDER", "openai"),
+                default_model: s.string_or("REPLY_LLM_MODEL", "gpt-4"),
+                routes,
+                openrouter: OpenRouterConfig::from_sources(s)?,
+                rate_limits,
             },
             search: SearchConfig {
-                serpapi_key: env::var("SERPAPI_KEY").unwrap_or_default(),
-                scholar_enabled: env::var("SERPAPI_SCHOLAR_ENABLED")
-                    .unwrap_or_else(|_| "true".to_string())
-                    .parse()
-                    .unwrap_or(true),
-                light_enabled: env::var("SERPAPI_LIGHT_ENABLED")
-                    .unwrap_or_else(|_| "true".to_string())
-                    .parse()
-                    .unwrap_or(true),
-                max_results: env::var("SERPAPI_MAX_RESULTS")
-                    .unwrap_or_else(|_| "10".to_string())
-                    .parse()
-                    .unwrap_or(10),
+                serpapi_key: s.get("SERPAPI_KEY").unwrap_or_default(),
+                scholar_enabled: s.parse_or("SERPAPI_SCHOLAR_ENABLED", true)?,
+                light_enabled: s.parse_or("SERPAPI_LIGHT_ENABLED", true)?,
+                max_results: s.parse_or("SERPAPI_MAX_RESULTS", 10)?,
+                ncbi_api_key: s.get("NCBI_API_KEY").unwrap_or_default(),
+                ncbi_email: s.get("NCBI_EMAIL").unwrap_or_default(),
+                pubmed_enabled: s.parse_or("PUBMED_ENABLED", true)?,
+                semantic_scholar_api_key: s.get("SEMANTIC_SCHOLAR_API_KEY").unwrap_or_default(),
+                semantic_scholar_enabled: s.parse_or("SEMANTIC_SCHOLAR_ENABLED", true)?,
+                engine_max_results,
+                mock_enabled: s.parse_or("SEARCH_MOCK_ENABLED", false)?,
+                min_source_score,
             },
+            network: NetworkConfig::from_env(),
+            budget: BudgetConfig::from_sources(s)?,
             storage: StorageConfig {
-                provider: env::var("STORAGE_PROVIDER").unwrap_or_else(|_| "s3".to_string()),
-                s3_bucket: env::var("S3_BUCKET").unwrap_or_default(),
-                s3_region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
-                s3_access_key_id: env::var("AWS_ACCESS_KEY_ID").ok(),
-                s3_secret_access_key: env::var("AWS_SECRET_ACCESS_KEY").ok(),
-                s3_endpoint: env::var("S3_ENDPOINT").ok(),
+                provider: s.string_or("STORAGE_PROVIDER", "s3"),
+                s3_bucket: s.get("S3_BUCKET").unwrap_or_default(),
+                s3_region: s.string_or("S3_REGION", "us-east-1"),
+                s3_access_key_id: s.get("AWS_ACCESS_KEY_ID"),
+                s3_secret_access_key: s.get("AWS_SECRET_ACCESS_KEY"),
+                s3_endpoint: s.get("S3_ENDPOINT"),
             },
             auth: AuthConfig {
                 // BIOAGENTS_SECRET is optional - defaults to a random value if not set
                 // For production, this should always be explicitly set
-                secret: env::var("BIOAGENTS_SECRET")
-                    .unwrap_or_else(|_| {
+                secret: s.get("BIOAGENTS_SECRET")
+                    .unwrap_or_else(|| {
                         // Generate a random secret for dev/local use
                         use std::collections::hash_map::RandomState;
                         use std::hash::{BuildHasher, Hasher};
                         let random = RandomState::new().build_hasher().finish();
                         format!("dev-secret-{:016x}", random)
                     }),
-                mode: env::var("AUTH_MODE").unwrap_or_else(|_| "none".to_string()),
-                max_jwt_expiration: env::var("MAX_JWT_EXPIRATION")
-                    .unwrap_or_else(|_| "3600".to_string())
-                    .parse()?,
+                mode: s.string_or("AUTH_MODE", "none"),
+                max_jwt_expiration: s.parse_or("MAX_JWT_EXPIRATION", 3600)?,
             },
             payment: PaymentConfig {
-                x402_enabled: env::var("X402_ENABLED")
-                    .unwrap_or_else(|_| "false".to_string())
-                    .parse()?,
-                b402_enabled: env::var("B402_ENABLED")
-                    .unwrap_or_else(|_| "false".to_string())
-                    .parse()?,
-                x402_environment: env::var("X402_ENVIRONMENT").unwrap_or_else(|_| "testnet".to_string()),
-                x402_payment_address: env::var("X402_PAYMENT_ADDRESS").ok(),
-                x402_network: env::var("X402_NETWORK").unwrap_or_else(|_| "base-sepolia".to_string()),
-                cdp_api_key_id: env::var("CDP_API_KEY_ID").ok(),
-                cdp_api_key_secret: env::var("CDP_API_KEY_SECRET").ok(),
+                x402_enabled: s.parse_or("X402_ENABLED", false)?,
+                b402_enabled: s.parse_or("B402_ENABLED", false)?,
+                x402_environment: s.string_or("X402_ENVIRONMENT", "testnet"),
+                x402_payment_address: s.get("X402_PAYMENT_ADDRESS"),
+                x402_network: s.string_or("X402_NETWORK", "base-sepolia"),
+                x402_facilitator_url: s.string_or("X402_FACILITATOR_URL", "https://x402.org/facilitator"),
+                x402_public_url: s.get("X402_PUBLIC_URL"),
+                x402_deep_research_price_usd: s.parse_or("X402_PRICE_DEEP_RESEARCH_USD", 0.10)?,
+                x402_analysis_price_usd: s.parse_or("X402_PRICE_ANALYSIS_USD", 0.05)?,
+                cdp_api_key_id: s.get("CDP_API_KEY_ID"),
+                cdp_api_key_secret: s.get("CDP_API_KEY_SECRET"),
             },
-        })
+            manuscript: ManuscriptConfig::from_sources(s)?,
+            annotation: AnnotationConfig::from_sources(s)?,
+            cross_reference: CrossReferenceConfig::from_sources(s)?,
+            logging: LoggingConfig::from_sources(s)?,
+            deep_research: DeepResearchConfig::from_sources(s)?,
+            workflow: WorkflowConfig::from_sources(s)?,
+            settings_profile: s.non_empty("OXIDIZED_BIO_PROFILE"),
+        };
+        config.queue.validate().map_err(anyhow::Error::msg)?;
+        config.register_secrets();
+        Ok(config)
+    }
+
+    /// Have logs and error messages mask this configuration's API keys and
+    /// other secrets ([`crate::utils::redact`])
+    pub fn register_secrets(&self) {
+        let llm = &self.llm;
+        let search = &self.search;
+        for secret in [
+            &llm.openai_api_key,
+            &llm.anthropic_api_key,
+            &llm.google_api_key,
+            &llm.openrouter_api_key,
+            &llm.groq_api_key,
+            &search.serpapi_key,
+            &search.ncbi_api_key,
+            &search.semantic_scholar_api_key,
+            &self.auth.secret,
+        ]
+        .into_iter()
+        .chain(self.storage.s3_secret_access_key.as_ref())
+        .chain(self.payment.cdp_api_key_secret.as_ref())
+        {
+            crate::utils::redact::register(secret);
+        }
+    }
+
+    /// Resolve the LLM for `task`, or `None` once the LLM budget is spent so
+    /// agents fall back to their offline behaviour.
+    pub fn llm_for(&self, task: LlmTask) -> Option<ResolvedLlm> {
+        if let Some(reason) = crate::budget::llm_exceeded(&self.budget) {
+            tracing::warn!(task = %task, reason = %reason, "LLM budget exceeded, using offline mode");
+            return None;
+        }
+        self.llm.for_task(task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn llm_config() -> LLMConfig {
+        LLMConfig {
+            openai_api_key: "sk-openai".to_string(),
+            anthropic_api_key: String::new(),
+            google_api_key: String::new(),
+            openrouter_api_key: String::new(),
+            groq_api_key: "gsk-groq".to_string(),
+            default_provider: "openai".to_string(),
+            default_model: "gpt-4o".to_string(),
+            routes: HashMap::new(),
+            openrouter: OpenRouterConfig::default(),
+            rate_limits: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_for_task_uses_route_when_key_present() {
+        let mut llm = llm_config();
+        llm.routes.insert(
+            LlmTask::Literature,
+            LlmRoute { provider: "groq".to_string(), model: None, temperature: None, max_tokens: None },
+        );
+
+        let literature = llm.for_task(LlmTask::Literature).unwrap();
+        assert_eq!(literature.provider, "groq");
+        assert_eq!(literature.model, "groq/compound");
+        assert_eq!(literature.api_key, "gsk-groq");
+
+        let reply = llm.for_task(LlmTask::Reply).unwrap();
+        assert_eq!(reply.provider, "openai");
+        assert_eq!(reply.model, "gpt-4o");
+    }
+
+    #[test]
+    fn test_parse_approval_gates() {
+        assert_eq!(ApprovalGate::parse_list("plan, draft").unwrap(), [ApprovalGate::Plan, ApprovalGate::Drafts]);
+        assert_eq!(ApprovalGate::parse_list("all").unwrap(), ApprovalGate::ALL);
+        assert!(ApprovalGate::parse_list("none").unwrap().is_empty());
+        assert!(ApprovalGate::parse_list("plan,review").is_err());
+    }
+
+    #[test]
+    fn test_for_task_falls_back_without_route_key() {
+        let mut llm = llm_config();
+        llm.routes.insert(
+            LlmTask::Planning,
+            LlmRoute {
+                provider: "anthropic".to_string(),
+                model: Some("claude-opus".to_string()),
+                temperature: Some(0.1),
+                max_tokens: None,
+            },
+        );
+
+        let planning = llm.for_task(LlmTask::Planning).unwrap();
+        assert_eq!(planning.provider, "openai");
+        assert_eq!(planning.api_key, "sk-openai");
+        // Parameter overrides survive the provider fallback
+        assert_eq!(planning.temperature_or(0.7), 0.1);
+        assert_eq!(planning.max_tokens_or(1024), 1024);
     }
 }
@@ -0,0 +1,364 @@
+//! Layered configuration sources
+//!
+//! Every setting has an environment variable name (see `.env.example`) and a
+//! dotted key in `config.toml`. Values are looked up, highest precedence
+//! first, in:
+//!
+//! 1. CLI overrides (`--set llm.default_provider=anthropic`)
+//! 2. Environment variables (including `.env`)
+//! 3. The config file (`~/.config/oxidized-bio/config.toml` or `--config`)
+//!
+//! ```toml
+//! [llm]
+//! default_provider = "anthropic"
+//!
+//! [llm.routes.literature]
+//! provider = "groq"
+//!
+//! [queue]
+//! workers = 4
+//! ```
+//!
+//! Unknown keys and unparsable values are errors that name the offending
+//! field.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// Config file key → environment variable
+const FIELDS: &[(&str, &str)] = &[
+    ("profile", "OXIDIZED_BIO_PROFILE"),
+    ("server.port", "PORT"),
+    ("server.host", "HOST"),
+    ("server.allowed_origins", "ALLOWED_ORIGINS"),
+    ("database.url", "DATABASE_URL"),
+    ("database.max_connections", "DB_MAX_CONNECTIONS"),
+    ("database.min_connections", "DB_MIN_CONNECTIONS"),
+    ("redis.url", "REDIS_URL"),
+    ("redis.enabled", "USE_JOB_QUEUE"),
+    ("queue.workers", "QUEUE_WORKERS"),
+    ("queue.interactive_workers", "QUEUE_INTERACTIVE_WORKERS"),
+    ("queue.poll_interval_ms", "QUEUE_POLL_INTERVAL_MS"),
+    ("queue.visibility_timeout_secs", "QUEUE_VISIBILITY_TIMEOUT_SECS"),
+    ("queue.max_attempts", "QUEUE_MAX_ATTEMPTS"),
+    ("queue.retry_delay_secs", "QUEUE_RETRY_DELAY_SECS"),
+    ("queue.retry_max_delay_secs", "QUEUE_RETRY_MAX_DELAY_SECS"),
+    ("queue.listen_notify", "QUEUE_LISTEN_NOTIFY"),
+    ("llm.openai_api_key", "OPENAI_API_KEY"),
+    ("llm.anthropic_api_key", "ANTHROPIC_API_KEY"),
+    ("llm.google_api_key", "GOOGLE_API_KEY"),
+    ("llm.openrouter_api_key", "OPENROUTER_API_KEY"),
+    ("llm.groq_api_key", "GROQ_API_KEY"),
+    ("llm.default_provider", "REPLY_LLM_PROVIDER"),
+    ("llm.default_model", "REPLY_LLM_MODEL"),
+    ("llm.openrouter.order", "OPENROUTER_PROVIDER_ORDER"),
+    ("llm.openrouter.only", "OPENROUTER_PROVIDER_ONLY"),
+    ("llm.openrouter.ignore", "OPENROUTER_PROVIDER_IGNORE"),
+    ("llm.openrouter.allow_fallbacks", "OPENROUTER_ALLOW_FALLBACKS"),
+    ("llm.openrouter.fallback_models", "OPENROUTER_FALLBACK_MODELS"),
+    ("llm.openrouter.referer", "OPENROUTER_HTTP_REFERER"),
+    ("llm.openrouter.title", "OPENROUTER_X_TITLE"),
+    ("llm.rate_limits.openai.max_concurrent", "OPENAI_MAX_CONCURRENT"),
+    ("llm.rate_limits.openai.requests_per_minute", "OPENAI_REQUESTS_PER_MINUTE"),
+    ("llm.rate_limits.openai.tokens_per_minute", "OPENAI_TOKENS_PER_MINUTE"),
+    ("llm.rate_limits.anthropic.max_concurrent", "ANTHROPIC_MAX_CONCURRENT"),
+    ("llm.rate_limits.anthropic.requests_per_minute", "ANTHROPIC_REQUESTS_PER_MINUTE"),
+    ("llm.rate_limits.anthropic.tokens_per_minute", "ANTHROPIC_TOKENS_PER_MINUTE"),
+    ("llm.rate_limits.google.max_concurrent", "GOOGLE_MAX_CONCURRENT"),
+    ("llm.rate_limits.google.requests_per_minute", "GOOGLE_REQUESTS_PER_MINUTE"),
+    ("llm.rate_limits.google.tokens_per_minute", "GOOGLE_TOKENS_PER_MINUTE"),
+    ("llm.rate_limits.openrouter.max_concurrent", "OPENROUTER_MAX_CONCURRENT"),
+    ("llm.rate_limits.openrouter.requests_per_minute", "OPENROUTER_REQUESTS_PER_MINUTE"),
+    ("llm.rate_limits.openrouter.tokens_per_minute", "OPENROUTER_TOKENS_PER_MINUTE"),
+    ("llm.rate_limits.groq.max_concurrent", "GROQ_MAX_CONCURRENT"),
+    ("llm.rate_limits.groq.requests_per_minute", "GROQ_REQUESTS_PER_MINUTE"),
+    ("llm.rate_limits.groq.tokens_per_minute", "GROQ_TOKENS_PER_MINUTE"),
+    ("llm.routes.planning.provider", "PLANNING_LLM_PROVIDER"),
+    ("llm.routes.planning.model", "PLANNING_LLM_MODEL"),
+    ("llm.routes.planning.temperature", "PLANNING_LLM_TEMPERATURE"),
+    ("llm.routes.planning.max_tokens", "PLANNING_LLM_MAX_TOKENS"),
+    ("llm.routes.literature.provider", "LITERATURE_LLM_PROVIDER"),
+    ("llm.routes.literature.model", "LITERATURE_LLM_MODEL"),
+    ("llm.routes.literature.temperature", "LITERATURE_LLM_TEMPERATURE"),
+    ("llm.routes.literature.max_tokens", "LITERATURE_LLM_MAX_TOKENS"),
+    ("llm.routes.drafting.provider", "DRAFTING_LLM_PROVIDER"),
+    ("llm.routes.drafting.model", "DRAFTING_LLM_MODEL"),
+    ("llm.routes.drafting.temperature", "DRAFTING_LLM_TEMPERATURE"),
+    ("llm.routes.drafting.max_tokens", "DRAFTING_LLM_MAX_TOKENS"),
+    ("search.serpapi_key", "SERPAPI_KEY"),
+    ("search.scholar_enabled", "SERPAPI_SCHOLAR_ENABLED"),
+    ("search.light_enabled", "SERPAPI_LIGHT_ENABLED"),
+    ("search.max_results", "SERPAPI_MAX_RESULTS"),
+    ("search.scholar_max_results", "SCHOLAR_MAX_RESULTS"),
+    ("search.light_max_results", "LIGHT_MAX_RESULTS"),
+    ("search.ncbi_api_key", "NCBI_API_KEY"),
+    ("search.ncbi_email", "NCBI_EMAIL"),
+    ("search.pubmed_enabled", "PUBMED_ENABLED"),
+    ("search.pubmed_max_results", "PUBMED_MAX_RESULTS"),
+    ("search.semantic_scholar_api_key", "SEMANTIC_SCHOLAR_API_KEY"),
+    ("search.semantic_scholar_enabled", "SEMANTIC_SCHOLAR_ENABLED"),
+    ("search.semantic_scholar_max_results", "SEMANTIC_SCHOLAR_MAX_RESULTS"),
+    ("search.min_source_score", "SEARCH_MIN_SOURCE_SCORE"),
+    ("network.proxy_url", "PROXY_URL"),
+    ("network.no_proxy", "NO_PROXY"),
+    ("network.ca_cert_path", "CA_CERT_PATH"),
+    ("budget.monthly_usd", "BUDGET_MONTHLY_USD"),
+    ("budget.session_usd", "BUDGET_SESSION_USD"),
+    ("budget.monthly_tokens", "BUDGET_MONTHLY_TOKENS"),
+    ("budget.session_tokens", "BUDGET_SESSION_TOKENS"),
+    ("budget.monthly_search_queries", "SERPAPI_MONTHLY_QUOTA"),
+    ("budget.session_search_queries", "SERPAPI_SESSION_QUOTA"),
+    ("manuscript.template", "MANUSCRIPT_TEMPLATE"),
+    ("manuscript.citation_style", "MANUSCRIPT_CITATION_STYLE"),
+    ("manuscript.polish_legends", "MANUSCRIPT_POLISH_LEGENDS"),
+    ("annotation.ensembl_file", "ANNOTATION_ENSEMBL_FILE"),
+    ("annotation.download", "ANNOTATION_DOWNLOAD"),
+    ("annotation.go_file", "ANNOTATION_GO_FILE"),
+    ("annotation.pathway_file", "ANNOTATION_PATHWAY_FILE"),
+    ("cross_reference.top_n", "CROSS_REFERENCE_TOP_N"),
+    ("cross_reference.min_reports", "CROSS_REFERENCE_MIN_REPORTS"),
+    ("cross_reference.query", "CROSS_REFERENCE_QUERY"),
+    ("logging.max_file_mb", "LOG_MAX_FILE_MB"),
+    ("logging.max_age_days", "LOG_MAX_AGE_DAYS"),
+    ("logging.max_files", "LOG_MAX_FILES"),
+    ("deep_research.depth", "DEEP_RESEARCH_DEPTH"),
+    ("deep_research.max_depth", "DEEP_RESEARCH_MAX_DEPTH"),
+    ("deep_research.max_tasks", "DEEP_RESEARCH_MAX_TASKS"),
+    ("workflow.approval_gates", "WORKFLOW_APPROVAL_GATES"),
+    ("workflow.gate_timeout_secs", "WORKFLOW_GATE_TIMEOUT_SECS"),
+    ("storage.provider", "STORAGE_PROVIDER"),
+    ("storage.s3_bucket", "S3_BUCKET"),
+    ("storage.s3_region", "S3_REGION"),
+    ("storage.s3_access_key_id", "AWS_ACCESS_KEY_ID"),
+    ("storage.s3_secret_access_key", "AWS_SECRET_ACCESS_KEY"),
+    ("storage.s3_endpoint", "S3_ENDPOINT"),
+    ("auth.secret", "BIOAGENTS_SECRET"),
+    ("auth.mode", "AUTH_MODE"),
+    ("auth.max_jwt_expiration", "MAX_JWT_EXPIRATION"),
+    ("payment.x402_enabled", "X402_ENABLED"),
+    ("payment.b402_enabled", "B402_ENABLED"),
+    ("payment.x402_environment", "X402_ENVIRONMENT"),
+    ("payment.x402_payment_address", "X402_PAYMENT_ADDRESS"),
+    ("payment.x402_network", "X402_NETWORK"),
+    ("payment.x402_facilitator_url", "X402_FACILITATOR_URL"),
+    ("payment.x402_public_url", "X402_PUBLIC_URL"),
+    ("payment.x402_deep_research_price_usd", "X402_PRICE_DEEP_RESEARCH_USD"),
+    ("payment.x402_analysis_price_usd", "X402_PRICE_ANALYSIS_USD"),
+    ("payment.cdp_api_key_id", "CDP_API_KEY_ID"),
+    ("payment.cdp_api_key_secret", "CDP_API_KEY_SECRET"),
+];
+
+static INSTALLED: OnceLock<ConfigSources> = OnceLock::new();
+
+/// Configuration layers, keyed by environment variable name
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    overrides: HashMap<String, String>,
+    file: HashMap<String, String>,
+    file_path: Option<PathBuf>,
+}
+
+impl ConfigSources {
+    /// `~/.config/oxidized-bio/config.toml` (platform config dir)
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("oxidized-bio").join("config.toml"))
+    }
+
+    /// Sources for this process: `path` if given (it must exist), else the
+    /// default config file if present, plus `overrides` (`key=value` pairs).
+    pub fn discover(path: Option<&Path>, overrides: &[String]) -> Result<Self> {
+        let mut sources = Self::default();
+        match path {
+            Some(path) => sources = sources.with_file(path)?,
+            None => {
+                if let Some(default) = Self::default_path().filter(|p| p.is_file()) {
+                    sources = sources.with_file(&default)?;
+                }
+            }
+        }
+        for assignment in overrides {
+            sources = sources.with_override(assignment)?;
+        }
+        Ok(sources)
+    }
+
+    /// Make these the sources read by every `from_env` constructor. Only the
+    /// first call takes effect.
+    pub fn install(self) -> &'static ConfigSources {
+        INSTALLED.get_or_init(|| self)
+    }
+
+    /// Installed sources, or environment-only if none were installed
+    pub fn current() -> &'static ConfigSources {
+        INSTALLED.get_or_init(ConfigSources::default)
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    /// Read a TOML config file
+    pub fn with_file(mut self, path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        self.file = parse_file(&text).with_context(|| format!("Invalid config file {}", path.display()))?;
+        self.file_path = Some(path.to_path_buf());
+        Ok(self)
+    }
+
+    /// Apply a `key=value` override; `key` is a config file key or an
+    /// environment variable name
+    pub fn with_override(mut self, assignment: &str) -> Result<Self> {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| anyhow!("--set {}: expected key=value", assignment))?;
+        let var = resolve_key(key.trim()).ok_or_else(|| anyhow!("--set {}: unknown setting", key.trim()))?;
+        self.overrides.insert(var.to_string(), value.trim().to_string());
+        Ok(self)
+    }
+
+    /// Raw value of `var`, from the highest-precedence layer that sets it
+    pub fn get(&self, var: &str) -> Option<String> {
+        self.overrides
+            .get(var)
+            .cloned()
+            .or_else(|| std::env::var(var).ok())
+            .or_else(|| self.file.get(var).cloned())
+    }
+
+    /// Value of `var`, treating empty strings as unset
+    pub fn non_empty(&self, var: &str) -> Option<String> {
+        self.get(var).filter(|v| !v.trim().is_empty())
+    }
+
+    pub fn string_or(&self, var: &str, default: &str) -> String {
+        self.get(var).unwrap_or_else(|| default.to_string())
+    }
+
+    /// Parse `var`, or `default` when unset
+    pub fn parse_or<T>(&self, var: &str, default: T) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        Ok(self.parse_opt(var)?.unwrap_or(default))
+    }
+
+    /// Parse `var` if set and non-empty
+    pub fn parse_opt<T>(&self, var: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.non_empty(var) {
+            None => Ok(None),
+            Some(raw) => raw
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|e| anyhow!("{}: invalid value '{}': {}", describe(var), raw, e)),
+        }
+    }
+}
+
+/// "server.port (PORT)" for error messages
+pub fn describe(var: &str) -> String {
+    match FIELDS.iter().find(|(_, v)| *v == var) {
+        Some((key, _)) => format!("{} ({})", key, var),
+        None => var.to_string(),
+    }
+}
+
+fn resolve_key(key: &str) -> Option<&'static str> {
+    FIELDS
+        .iter()
+        .find(|(k, v)| *k == key || *v == key)
+        .map(|(_, v)| *v)
+}
+
+/// Flatten a TOML document into environment variable names
+fn parse_file(text: &str) -> Result<HashMap<String, String>> {
+    let table: toml::Table = text.parse()?;
+    let mut flat = Vec::new();
+    flatten("", &toml::Value::Table(table), &mut flat);
+
+    let mut values = HashMap::new();
+    for (key, value) in flat {
+        let Some(var) = FIELDS.iter().find(|(k, _)| *k == key).map(|(_, v)| *v) else {
+            bail!("{}: unknown setting", key);
+        };
+        values.insert(var.to_string(), value);
+    }
+    Ok(values)
+}
+
+fn flatten(prefix: &str, value: &toml::Value, out: &mut Vec<(String, String)>) {
+    let key = |k: &str| if prefix.is_empty() { k.to_string() } else { format!("{}.{}", prefix, k) };
+    match value {
+        toml::Value::Table(table) => {
+            for (k, v) in table {
+                flatten(&key(k), v, out);
+            }
+        }
+        // Lists (e.g. allowed_origins) use the env var's comma-separated form
+        toml::Value::Array(items) => {
+            let joined = items
+                .iter()
+                .map(|item| match item {
+                    toml::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            out.push((prefix.to_string(), joined));
+        }
+        toml::Value::String(s) => out.push((prefix.to_string(), s.clone())),
+        other => out.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_keys_map_to_env_names() {
+        let file = parse_file(
+            "profile = \"work\"\n[server]\nport = 8080\nallowed_origins = [\"http://a\", \"http://b\"]\n\
+             [llm.routes.literature]\nprovider = \"groq\"\ntemperature = 0.2\n",
+        )
+        .unwrap();
+        assert_eq!(file["OXIDIZED_BIO_PROFILE"], "work");
+        assert_eq!(file["PORT"], "8080");
+        assert_eq!(file["ALLOWED_ORIGINS"], "http://a,http://b");
+        assert_eq!(file["LITERATURE_LLM_PROVIDER"], "groq");
+        assert_eq!(file["LITERATURE_LLM_TEMPERATURE"], "0.2");
+    }
+
+    #[test]
+    fn test_unknown_key_is_named() {
+        let err = parse_file("[queue]\nworkerz = 4\n").unwrap_err();
+        assert_eq!(err.to_string(), "queue.workerz: unknown setting");
+    }
+
+    #[test]
+    fn test_precedence_and_parse_errors() {
+        let mut sources = ConfigSources::default();
+        sources.file.insert("OXBIO_TEST_QUEUE_WORKERS".to_string(), "3".to_string());
+        assert_eq!(sources.parse_or("OXBIO_TEST_QUEUE_WORKERS", 1usize).unwrap(), 3);
+
+        sources.overrides.insert("OXBIO_TEST_QUEUE_WORKERS".to_string(), "5".to_string());
+        assert_eq!(sources.parse_or("OXBIO_TEST_QUEUE_WORKERS", 1usize).unwrap(), 5);
+
+        let sources = ConfigSources::default().with_override("queue.workers=many").unwrap();
+        let err = sources.parse_or("QUEUE_WORKERS", 2usize).unwrap_err();
+        assert!(err.to_string().starts_with("queue.workers (QUEUE_WORKERS): invalid value 'many'"));
+
+        assert!(ConfigSources::default().with_override("queue.nope=1").is_err());
+    }
+}
@@ -1,8 +1,158 @@
-use std::collections::HashMap;
+pub mod geo;
+pub mod parquet;
+pub mod sequence;
+pub mod vcf;
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::annotation::Species;
+use crate::models::{AnalysisArtifact, UploadedDataset};
+use crate::utils::cancel::{self, CancellationToken};
+
+/// Bytes copied between progress reports and cancellation checks when
+/// importing a dataset
+const COPY_CHUNK: usize = 1 << 20;
+
+/// Rows scanned for gene IDs in long-format tables
+const GENE_ID_SCAN_ROWS: usize = 500;
+
+/// Distinct values remembered per column when looking for identifiers
+const DISTINCT_LIMIT: usize = 10_000;
+
+/// Rows needed before an all-distinct text column counts as an identifier
+const IDENTIFIER_MIN_ROWS: usize = 20;
+
+/// Cell values treated as missing by default (compared case-insensitively)
+const MISSING_VALUES: [&str; 7] = ["", "na", "n/a", "nan", "null", "none", "."];
+
+/// Date formats recognised besides RFC 3339
+const DATE_FORMATS: [&str; 4] = ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y", "%m/%d/%Y"];
+
+/// Directory uploaded datasets are copied to, relative to the working directory
+pub const UPLOAD_DIR: &str = "uploads";
+
+/// Why an uploaded dataset was rejected
+#[derive(Debug, Error)]
+pub enum UploadError {
+    #[error("Only .csv, .tsv, .parquet, .vcf, FASTA/FASTQ or GEO series matrix/SOFT files are supported, got .{0}")]
+    UnsupportedType(String),
+    #[error("Malformed dataset: {0}")]
+    Malformed(String),
+    #[error("Dataset must include Ensembl ID and Age columns")]
+    MissingColumns,
+    #[error("VCF samples need an age: add ##SAMPLE=<ID=...,Age=...> header lines")]
+    MissingAge,
+    #[error("{0}")]
+    WrongSpecies(String),
+    #[error("Failed to store dataset: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// How the cells of a dataset are read: decimal separator, missing-value
+/// tokens and quote character. Ingestion and every analysis pass over the
+/// file go through the same options.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParseOptions {
+    /// `.` or `,`
+    pub decimal: char,
+    /// Values read as missing, compared case-insensitively after trimming;
+    /// empty cells always are
+    pub na_values: Vec<String>,
+    /// Quote character around fields
+    pub quote: char,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            decimal: '.',
+            na_values: MISSING_VALUES.iter().filter(|v| !v.is_empty()).map(|v| v.to_string()).collect(),
+            quote: '"',
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(self.decimal, '.' | ',') {
+            return Err(format!("decimal separator must be '.' or ',', got '{}'", self.decimal));
+        }
+        if !self.quote.is_ascii() || self.quote.is_ascii_alphanumeric() {
+            return Err(format!("quote must be an ASCII punctuation character, got '{}'", self.quote));
+        }
+        Ok(())
+    }
 
-use crate::models::UploadedDataset;
+    pub fn is_missing(&self, value: &str) -> bool {
+        let value = value.trim();
+        value.is_empty() || self.na_values.iter().any(|na| value.eq_ignore_ascii_case(na.trim()))
+    }
+
+    /// Finite number in `value`, `None` when it is missing or not numeric.
+    /// With a decimal comma, `1,5` and `1.5` both read as 1.5.
+    pub fn number(&self, value: &str) -> Option<f64> {
+        if self.is_missing(value) {
+            return None;
+        }
+        let value = value.trim();
+        let parsed = if self.decimal == ',' {
+            value.replace(',', ".").parse::<f64>()
+        } else {
+            value.parse::<f64>()
+        };
+        parsed.ok().filter(|v| v.is_finite())
+    }
+
+    /// Set one option from its text form, as given to `/upload` and the
+    /// upload endpoints: `decimal`, `na` (comma-separated tokens) or `quote`
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let single = |value: &str| {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(format!("{} must be a single character, got '{}'", name, value)),
+            }
+        };
+        match name {
+            "decimal" => self.decimal = single(value)?,
+            "quote" => self.quote = single(value)?,
+            "na" | "na_values" => {
+                self.na_values = value.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect()
+            }
+            other => return Err(format!("Unknown parsing option '{}'", other)),
+        }
+        self.validate()
+    }
+
+    /// Options for a file derived from the dataset and written by this crate:
+    /// its missing-value tokens, with the default quote character
+    pub fn derived(&self) -> Self {
+        Self { quote: '"', ..self.clone() }
+    }
+
+    /// Options for the CSV a Parquet, GEO or VCF dataset is converted to,
+    /// which always has a decimal point
+    fn converted(&self) -> Self {
+        Self { decimal: '.', ..self.derived() }
+    }
+
+    /// CSV reader settings for a file delimited by `delimiter`
+    pub fn reader_builder(&self, delimiter: u8) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder.delimiter(delimiter).quote(self.quote as u8);
+        builder
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct DatasetRecord {
@@ -13,6 +163,713 @@ pub struct DatasetRecord {
     pub has_headers: bool,
     pub columns: Vec<String>,
     pub row_count: usize,
+    /// Detected from the gene IDs on upload; `/species` or `--species` overrides it
+    pub species: Species,
+    /// Inferred type of each column, in `columns` order
+    pub schema: Vec<ColumnSchema>,
+    /// Decimal separator, missing-value tokens and quote character
+    pub parsing: ParseOptions,
+    /// Read statistics of a FASTA/FASTQ file, summarized on ingestion
+    pub sequence: Option<sequence::SequenceSummary>,
+}
+
+/// What a column holds, inferred from its header and values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnKind {
+    Numeric,
+    Categorical,
+    /// Gene, probe or sample IDs; never analysed as values
+    Identifier,
+    Date,
+}
+
+impl std::fmt::Display for ColumnKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Numeric => "numeric",
+            Self::Categorical => "categorical",
+            Self::Identifier => "identifier",
+            Self::Date => "date",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub kind: ColumnKind,
+    /// Share of rows with a missing value (empty, `NA`, `NaN`, ...)
+    pub missing_rate: f64,
+}
+
+/// Infers a [`ColumnSchema`] per column from the rows it is fed
+pub struct SchemaBuilder {
+    columns: Vec<ColumnStats>,
+    rows: usize,
+    parsing: ParseOptions,
+}
+
+struct ColumnStats {
+    name: String,
+    missing: usize,
+    numeric: usize,
+    dates: usize,
+    gene_ids: usize,
+    distinct: HashSet<String>,
+    duplicates: bool,
+}
+
+impl SchemaBuilder {
+    pub fn new(headers: &[String], parsing: &ParseOptions) -> Self {
+        Self {
+            columns: headers
+                .iter()
+                .map(|name| ColumnStats {
+                    name: name.clone(),
+                    missing: 0,
+                    numeric: 0,
+                    dates: 0,
+                    gene_ids: 0,
+                    distinct: HashSet::new(),
+                    duplicates: false,
+                })
+                .collect(),
+            rows: 0,
+            parsing: parsing.clone(),
+        }
+    }
+
+    pub fn push(&mut self, row: &csv::StringRecord) {
+        self.rows += 1;
+        for (column, value) in self.columns.iter_mut().zip(row.iter().chain(std::iter::repeat(""))) {
+            let value = value.trim();
+            if self.parsing.is_missing(value) {
+                column.missing += 1;
+                continue;
+            }
+            if self.parsing.number(value).is_some() {
+                column.numeric += 1;
+            } else if is_date(value) {
+                column.dates += 1;
+            } else if is_ensembl_id(value) {
+                column.gene_ids += 1;
+            }
+            if !column.duplicates && column.distinct.len() < DISTINCT_LIMIT {
+                column.duplicates = !column.distinct.insert(value.to_string());
+            }
+        }
+    }
+
+    pub fn finish(self) -> Vec<ColumnSchema> {
+        let rows = self.rows;
+        self.columns
+            .into_iter()
+            .map(|column| {
+                let values = rows - column.missing;
+                let kind = if is_identifier_header(&column.name) || (values > 0 && column.gene_ids == values) {
+                    ColumnKind::Identifier
+                } else if values > 0 && column.numeric == values {
+                    ColumnKind::Numeric
+                } else if values > 0 && column.dates == values {
+                    ColumnKind::Date
+                } else if values >= IDENTIFIER_MIN_ROWS && column.numeric == 0 && !column.duplicates {
+                    ColumnKind::Identifier
+                } else {
+                    ColumnKind::Categorical
+                };
+                ColumnSchema {
+                    name: column.name,
+                    kind,
+                    missing_rate: if rows == 0 { 0.0 } else { column.missing as f64 / rows as f64 },
+                }
+            })
+            .collect()
+    }
+}
+
+/// Headers like `ensembl_id`, `id`, `sample_id` or `gene_symbol`
+fn is_identifier_header(name: &str) -> bool {
+    let name = name.trim().to_ascii_lowercase();
+    name == "id"
+        || name == "gene"
+        || name.ends_with("_id")
+        || name.ends_with(" id")
+        || name.contains("ensembl")
+        || name.contains("symbol")
+        || name.contains("probe")
+}
+
+/// `ENSG00000000003`, `ENSMUSG00000000001`, optionally versioned
+fn is_ensembl_id(value: &str) -> bool {
+    let Some(rest) = value.strip_prefix("ENS") else {
+        return false;
+    };
+    let digits = rest.trim_start_matches(|c: char| c.is_ascii_uppercase());
+    let digits = digits.split_once('.').map_or(digits, |(id, _)| id);
+    digits.len() >= 6 && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_date(value: &str) -> bool {
+    DateTime::parse_from_rfc3339(value).is_ok()
+        || DATE_FORMATS.iter().any(|format| NaiveDate::parse_from_str(value, format).is_ok())
+}
+
+/// Dataset formats stored as the CSV they are converted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Converted {
+    Parquet,
+    /// GEO series matrix or SOFT file
+    Geo,
+    /// Variants, as allele dosages per sample
+    Vcf,
+    /// FASTA/FASTQ, as one row per read
+    Sequence,
+}
+
+impl Converted {
+    fn of(filename: &str) -> Option<Self> {
+        if geo::is_geo(filename) {
+            return Some(Self::Geo);
+        }
+        if vcf::is_vcf(filename) {
+            return Some(Self::Vcf);
+        }
+        if sequence::is_sequence(filename) {
+            return Some(Self::Sequence);
+        }
+        let extension = Path::new(filename).extension().and_then(|e| e.to_str())?;
+        extension.eq_ignore_ascii_case(parquet::EXTENSION).then_some(Self::Parquet)
+    }
+
+    fn convert(self, input: std::fs::File, output: impl std::io::Write) -> Result<usize> {
+        match self {
+            Self::Parquet => parquet::to_csv(input, output),
+            Self::Geo => geo::to_csv(input, output),
+            Self::Vcf => vcf::to_csv(input, output),
+            Self::Sequence => sequence::to_csv(input, output),
+        }
+    }
+
+    fn convert_bytes(self, bytes: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Parquet => parquet::to_csv_bytes(bytes),
+            Self::Geo | Self::Vcf | Self::Sequence => {
+                let mut csv = Vec::new();
+                match self {
+                    Self::Geo => geo::to_csv(bytes, &mut csv)?,
+                    Self::Vcf => vcf::to_csv(bytes, &mut csv)?,
+                    _ => sequence::to_csv(bytes, &mut csv)?,
+                };
+                Ok(csv)
+            }
+        }
+    }
+
+    /// Name of the CSV `filename` is stored as
+    fn csv_name(filename: &str) -> String {
+        let stem = Path::new(filename).file_stem().and_then(|s| s.to_str()).unwrap_or("dataset");
+        let stem = [".txt", ".soft", ".vcf"]
+            .iter()
+            .find_map(|suffix| stem.strip_suffix(suffix))
+            .or_else(|| sequence::EXTENSIONS.iter().find_map(|e| stem.strip_suffix(e)?.strip_suffix('.')))
+            .unwrap_or(stem);
+        format!("{}.csv", stem)
+    }
+}
+
+impl DatasetRecord {
+    /// Describe a CSV/TSV file in place, without copying it into `uploads/`.
+    /// Parquet, VCF, FASTA/FASTQ and GEO series matrix/SOFT files are
+    /// converted to CSV in `uploads/` first.
+    pub fn from_path(path: &Path, description: Option<String>) -> Result<Self> {
+        Self::from_path_with(path, description, ParseOptions::default())
+    }
+
+    /// [`Self::from_path`] with non-default parsing options
+    pub fn from_path_with(path: &Path, description: Option<String>, parsing: ParseOptions) -> Result<Self> {
+        parsing.validate().map_err(anyhow::Error::msg)?;
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let filename = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        if let Some(format) = Converted::of(filename) {
+            let id = uuid::Uuid::new_v4().to_string();
+            return Self::from_converted(format, path, Path::new(UPLOAD_DIR), &id, description, parsing);
+        }
+        let delimiter = match extension.as_str() {
+            "csv" => b',',
+            "tsv" => b'\t',
+            other => return Err(UploadError::UnsupportedType(other.to_string()).into()),
+        };
+
+        let mut rdr = parsing
+            .reader_builder(delimiter)
+            .has_headers(true)
+            .from_path(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let columns: Vec<String> = rdr.headers()?.iter().map(|h| h.to_string()).collect();
+        let mut schema = SchemaBuilder::new(&columns, &parsing);
+        let mut reads = sequence::is_table(&columns).then(sequence::Summarizer::default);
+        let mut row_count = 0usize;
+        for record in rdr.records() {
+            let record = record.with_context(|| format!("Malformed row {} in {}", row_count + 2, path.display()))?;
+            schema.push(&record);
+            if let Some(reads) = &mut reads {
+                reads.push(&record);
+            }
+            row_count += 1;
+        }
+
+        let filename = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("dataset")
+            .to_string();
+        let size = std::fs::metadata(path).map(|m| m.len() as i64).ok();
+        let local_path = path.to_string_lossy().to_string();
+        Ok(Self {
+            dataset: UploadedDataset {
+                id: uuid::Uuid::new_v4().to_string(),
+                description: description.unwrap_or_else(|| format!("Dataset {}", filename)),
+                filename,
+                path: Some(local_path.clone()),
+                content: None,
+                size,
+            },
+            local_path,
+            content_type: if delimiter == b'\t' { "text/tab-separated-values" } else { "text/csv" }.to_string(),
+            delimiter,
+            has_headers: true,
+            columns,
+            row_count,
+            species: Species::default(),
+            schema: schema.finish(),
+            parsing,
+            sequence: reads.map(sequence::Summarizer::finish),
+        }
+        .with_detected_species())
+    }
+
+    /// Convert the Parquet or GEO file at `path` to
+    /// `<upload_dir>/<id>-<stem>.csv` and describe the CSV under the original
+    /// file's name and size. Nothing is left in `upload_dir` on failure.
+    fn from_converted(
+        format: Converted,
+        path: &Path,
+        upload_dir: &Path,
+        id: &str,
+        description: Option<String>,
+        parsing: ParseOptions,
+    ) -> Result<Self> {
+        let filename = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("dataset")
+            .to_string();
+        std::fs::create_dir_all(upload_dir)?;
+        let csv_path = upload_dir.join(format!("{}-{}", id, Converted::csv_name(&filename)));
+        let description = description.unwrap_or_else(|| format!("Dataset {}", filename));
+        let convert = || {
+            let input = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+            let output = std::io::BufWriter::new(std::fs::File::create(&csv_path)?);
+            format.convert(input, output).with_context(|| format!("Failed to read {}", path.display()))?;
+            Self::from_path_with(&csv_path, Some(description), parsing.converted())
+        };
+        match convert() {
+            Ok(mut record) => {
+                record.dataset.id = id.to_string();
+                record.dataset.filename = filename;
+                record.dataset.size = std::fs::metadata(path).map(|m| m.len() as i64).ok();
+                Ok(record)
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&csv_path);
+                Err(e)
+            }
+        }
+    }
+
+    /// This dataset with the columns of the sample sheet at `sheet` (CSV or
+    /// TSV) added, e.g. the age and sex of a VCF's samples, written to
+    /// `<upload_dir>/<id>-<stem>-samples.csv`. The sheet's first column names
+    /// the samples as the dataset's column of the same name does (`sample`
+    /// in a converted VCF or GEO file); columns the dataset already has are
+    /// kept as they are, and samples missing from the sheet get empty cells.
+    pub fn join_samples(&self, sheet: &Path, upload_dir: &Path) -> Result<Self> {
+        let delimiter = match sheet.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("tsv") => b'\t',
+            _ => b',',
+        };
+        let mut rdr = self
+            .parsing
+            .reader_builder(delimiter)
+            .has_headers(true)
+            .from_path(sheet)
+            .with_context(|| format!("Failed to open {}", sheet.display()))?;
+        let headers: Vec<String> = rdr.headers()?.iter().map(|h| h.trim().to_string()).collect();
+        let Some(key) = headers.first().filter(|k| !k.is_empty()) else {
+            bail!("{} has no header row", sheet.display());
+        };
+        let Some(key_column) = self.columns.iter().position(|c| c.eq_ignore_ascii_case(key)) else {
+            bail!(
+                "{} names its samples in a '{}' column, which the dataset does not have",
+                sheet.display(),
+                key
+            );
+        };
+        let added: Vec<usize> = (1..headers.len())
+            .filter(|&i| !self.columns.iter().any(|c| c.eq_ignore_ascii_case(&headers[i])))
+            .collect();
+        let mut rows: HashMap<String, Vec<String>> = HashMap::new();
+        for (n, row) in rdr.records().enumerate() {
+            let row = row.with_context(|| format!("Malformed row {} in {}", n + 2, sheet.display()))?;
+            let values = added.iter().map(|&i| row.get(i).unwrap_or("").to_string()).collect();
+            rows.insert(row.get(0).unwrap_or("").trim().to_string(), values);
+        }
+
+        std::fs::create_dir_all(upload_dir)?;
+        let stem = Path::new(&self.local_path).file_stem().and_then(|s| s.to_str()).unwrap_or("dataset");
+        let joined = upload_dir.join(format!("{}-{}-samples.csv", uuid::Uuid::new_v4(), stem));
+        let join = || -> Result<usize> {
+            let mut rdr = self
+                .parsing
+                .reader_builder(self.delimiter)
+                .has_headers(true)
+                .from_path(&self.local_path)?;
+            let mut writer = csv::Writer::from_path(&joined)?;
+            writer.write_record(self.columns.iter().chain(added.iter().map(|&i| &headers[i])))?;
+            let empty = vec![String::new(); added.len()];
+            let mut matched = 0;
+            for row in rdr.records() {
+                let row = row?;
+                let extra = match rows.get(row.get(key_column).unwrap_or("").trim()) {
+                    Some(extra) => {
+                        matched += 1;
+                        extra
+                    }
+                    None => &empty,
+                };
+                writer.write_record(row.iter().chain(extra.iter().map(String::as_str)))?;
+            }
+            writer.flush()?;
+            Ok(matched)
+        };
+        let record = match join() {
+            Ok(0) => Err(anyhow::anyhow!("No sample of the dataset is in {}", sheet.display())),
+            Ok(matched) => {
+                if matched < self.row_count {
+                    warn!(matched, samples = self.row_count, sheet = %sheet.display(), "Samples missing from the sample sheet");
+                }
+                Self::from_path_with(&joined, Some(self.dataset.description.clone()), self.parsing.derived())
+            }
+            Err(e) => Err(e),
+        };
+        match record {
+            Ok(mut record) => {
+                record.dataset.filename = self.dataset.filename.clone();
+                record.species = self.species;
+                Ok(record)
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&joined);
+                Err(e)
+            }
+        }
+    }
+
+    /// Validate an uploaded CSV/TSV file and store a copy as
+    /// `<upload_dir>/<id>-<filename>`; Parquet, VCF, FASTA/FASTQ and GEO series
+    /// matrix/SOFT files are stored converted, as `<upload_dir>/<id>-<stem>.csv`. The file needs Ensembl IDs (an ID
+    /// column, or gene columns named by ID) and an Age column and
+    /// well-formed rows; `species`, when given, must match its
+    /// gene IDs, otherwise the species is detected from them. The TUI's
+    /// `/upload` and `POST /api/files` both go through here.
+    pub async fn from_upload(
+        upload_dir: &Path,
+        filename: &str,
+        bytes: &[u8],
+        description: Option<String>,
+        species: Option<Species>,
+        parsing: ParseOptions,
+    ) -> Result<Self, UploadError> {
+        parsing.validate().map_err(UploadError::Malformed)?;
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let size = bytes.len() as i64;
+        let converted;
+        let format = Converted::of(filename);
+        let (bytes, delimiter, stored_name, parsing) = match (format, extension.as_str()) {
+            (Some(format), _) => {
+                converted = format.convert_bytes(bytes).map_err(|e| UploadError::Malformed(format!("{:#}", e)))?;
+                (&converted[..], b',', Converted::csv_name(filename), parsing.converted())
+            }
+            (None, "csv") => (bytes, b',', filename.to_string(), parsing),
+            (None, "tsv") => (bytes, b'\t', filename.to_string(), parsing),
+            (None, other) => return Err(UploadError::UnsupportedType(other.to_string())),
+        };
+
+        let mut rdr = parsing.reader_builder(delimiter).has_headers(true).from_reader(bytes);
+        let columns: Vec<String> = rdr
+            .headers()
+            .map_err(|e| UploadError::Malformed(e.to_string()))?
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        let mut schema = SchemaBuilder::new(&columns, &parsing);
+        let mut reads = sequence::is_table(&columns).then(sequence::Summarizer::default);
+        let mut row_count = 0usize;
+        for record in rdr.records() {
+            let record = record.map_err(|e| UploadError::Malformed(format!("row {}: {}", row_count + 2, e)))?;
+            schema.push(&record);
+            if let Some(reads) = &mut reads {
+                reads.push(&record);
+            }
+            row_count += 1;
+        }
+        validate_headers(&columns, format)?;
+
+        let ids = gene_ids(bytes, delimiter, &parsing);
+        let species = match species {
+            Some(species) => {
+                species
+                    .check_ids(ids.iter().map(String::as_str))
+                    .map_err(|e| UploadError::WrongSpecies(e.to_string()))?;
+                species
+            }
+            None => Species::detect(ids.iter().map(String::as_str)).unwrap_or_default(),
+        };
+
+        let id = uuid::Uuid::new_v4().to_string();
+        tokio::fs::create_dir_all(upload_dir).await?;
+        let local_path = upload_dir.join(format!("{}-{}", id, stored_name));
+        tokio::fs::write(&local_path, bytes).await?;
+        let local_path = local_path.to_string_lossy().to_string();
+
+        Ok(Self {
+            dataset: UploadedDataset {
+                id,
+                description: description.unwrap_or_else(|| format!("Uploaded dataset {}", filename)),
+                filename: filename.to_string(),
+                path: Some(local_path.clone()),
+                content: None,
+                size: Some(size),
+            },
+            local_path,
+            content_type: if delimiter == b'\t' { "text/tab-separated-values" } else { "text/csv" }.to_string(),
+            delimiter,
+            has_headers: true,
+            columns,
+            row_count,
+            species,
+            schema: schema.finish(),
+            parsing,
+            sequence: reads.map(sequence::Summarizer::finish),
+        })
+    }
+
+    /// Copy the dataset at `source` to `<upload_dir>/<id>-<filename>` and
+    /// validate the copy like [`Self::from_upload`], without holding the
+    /// file in memory. `progress` gets the bytes copied so far and the file
+    /// size after every chunk; cancelling `cancel` stops the copy with
+    /// [`cancel::Cancelled`]. Nothing is left in `upload_dir` on failure.
+    pub async fn import(
+        upload_dir: &Path,
+        source: &Path,
+        description: Option<String>,
+        parsing: ParseOptions,
+        cancel: &CancellationToken,
+        mut progress: impl FnMut(u64, u64),
+    ) -> Result<Self> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        parsing.validate().map_err(anyhow::Error::msg)?;
+        let filename = source
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("dataset.csv")
+            .to_string();
+        let extension = Path::new(&filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let format = Converted::of(&filename);
+        if format.is_none() && !matches!(extension.as_str(), "csv" | "tsv") {
+            return Err(UploadError::UnsupportedType(extension).into());
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        tokio::fs::create_dir_all(upload_dir).await?;
+        let local_path = upload_dir.join(format!("{}-{}", id, filename));
+        let copy = async {
+            let mut input = tokio::fs::File::open(source)
+                .await
+                .with_context(|| format!("Failed to read {}", source.display()))?;
+            let total = input.metadata().await?.len();
+            let mut output = tokio::fs::File::create(&local_path).await?;
+            let mut buffer = vec![0; COPY_CHUNK];
+            let mut copied = 0u64;
+            loop {
+                cancel::check(cancel)?;
+                let read = input.read(&mut buffer).await?;
+                if read == 0 {
+                    break;
+                }
+                output.write_all(&buffer[..read]).await?;
+                copied += read as u64;
+                progress(copied, total.max(copied));
+            }
+            output.flush().await?;
+            let path = local_path.clone();
+            let record = if let Some(format) = format {
+                // Only the converted CSV is kept
+                let (dir, id) = (upload_dir.to_path_buf(), id.clone());
+                let record =
+                    tokio::task::spawn_blocking(move || Self::from_converted(format, &path, &dir, &id, None, parsing))
+                        .await??;
+                tokio::fs::remove_file(&local_path).await?;
+                record
+            } else {
+                tokio::task::spawn_blocking(move || Self::from_path_with(&path, None, parsing)).await??
+            };
+            if let Err(e) = validate_headers(&record.columns, format) {
+                let _ = tokio::fs::remove_file(&record.local_path).await;
+                return Err(e.into());
+            }
+            anyhow::Ok(record)
+        };
+        match copy.await {
+            Ok(mut record) => {
+                record.dataset.id = id;
+                record.dataset.description = description.unwrap_or_else(|| format!("Uploaded dataset {}", filename));
+                record.dataset.filename = filename;
+                Ok(record)
+            }
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&local_path).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Reader over the dataset's rows with its delimiter, header row and
+    /// quote character
+    pub fn reader(&self) -> Result<csv::Reader<std::fs::File>> {
+        self.parsing
+            .reader_builder(self.delimiter)
+            .has_headers(self.has_headers)
+            .from_path(&self.local_path)
+            .with_context(|| format!("Failed to open dataset {}", self.local_path))
+    }
+
+    /// Numeric value of a cell under the dataset's parsing options
+    pub fn number(&self, value: &str) -> Option<f64> {
+        self.parsing.number(value)
+    }
+
+    /// Inferred kind of column `idx`, `None` when unknown
+    pub fn column_kind(&self, idx: usize) -> Option<ColumnKind> {
+        self.schema.get(idx).map(|c| c.kind)
+    }
+
+    /// Column counts per kind, e.g. `12 numeric, 1 identifier`
+    pub fn schema_summary(&self) -> String {
+        let kinds = [ColumnKind::Numeric, ColumnKind::Categorical, ColumnKind::Identifier, ColumnKind::Date];
+        let parts: Vec<String> = kinds
+            .iter()
+            .filter_map(|kind| {
+                let count = self.schema.iter().filter(|c| c.kind == *kind).count();
+                (count > 0).then(|| format!("{} {}", count, kind))
+            })
+            .collect();
+        if parts.is_empty() {
+            "no columns typed".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    /// Ensembl-style gene IDs in the dataset: wide tables carry them as
+    /// column names, long tables in an `ensembl` column
+    pub fn gene_ids(&self) -> Result<Vec<String>> {
+        let bytes = std::fs::read(&self.local_path).with_context(|| format!("Failed to read {}", self.local_path))?;
+        Ok(gene_ids(&bytes, self.delimiter, &self.parsing))
+    }
+
+    /// Set `species` from the gene IDs, keeping the current value when the
+    /// dataset has none or cannot be read
+    pub fn with_detected_species(mut self) -> Self {
+        let detected = self
+            .gene_ids()
+            .ok()
+            .and_then(|ids| Species::detect(ids.iter().map(String::as_str)));
+        if let Some(species) = detected {
+            self.species = species;
+        }
+        self
+    }
+
+    /// Switch to `species`, failing when the gene IDs belong to another one
+    pub fn set_species(&mut self, species: Species) -> Result<()> {
+        species.check_ids(self.gene_ids()?.iter().map(String::as_str))?;
+        self.species = species;
+        Ok(())
+    }
+}
+
+/// Uploads need a column of Ensembl IDs and one with the donor age; VCFs,
+/// whose markers are variants, only the age
+fn validate_headers(headers: &[String], format: Option<Converted>) -> Result<(), UploadError> {
+    let lowered: Vec<String> = headers.iter().map(|h| h.to_lowercase()).collect();
+    // Reads have no samples to analyse
+    if format == Some(Converted::Sequence) {
+        return Ok(());
+    }
+    if format == Some(Converted::Vcf) {
+        if !lowered.iter().any(|h| h.contains("age")) {
+            return Err(UploadError::MissingAge);
+        }
+        return Ok(());
+    }
+    // Long tables have an ID column, wide ones gene IDs as column names
+    let has_ensembl = lowered.iter().any(|h| h.contains("ensembl")) || headers.iter().any(|h| is_ensembl_id(h));
+    let has_age = lowered.iter().any(|h| h.contains("age"));
+    if !has_ensembl || !has_age {
+        return Err(UploadError::MissingColumns);
+    }
+    Ok(())
+}
+
+/// Column names plus the values of columns named like `ensembl_id` in the
+/// first rows of a delimited file
+pub fn gene_ids(bytes: &[u8], delimiter: u8, parsing: &ParseOptions) -> Vec<String> {
+    let mut rdr = parsing
+        .reader_builder(delimiter)
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(bytes);
+    let Ok(headers) = rdr.headers().cloned() else {
+        return Vec::new();
+    };
+    let id_columns: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| h.to_ascii_lowercase().contains("ensembl"))
+        .map(|(i, _)| i)
+        .collect();
+    let mut ids: Vec<String> = headers.iter().map(str::to_string).collect();
+    if !id_columns.is_empty() {
+        for row in rdr.records().take(GENE_ID_SCAN_ROWS).flatten() {
+            ids.extend(id_columns.iter().filter_map(|i| row.get(*i)).map(str::to_string));
+        }
+    }
+    ids
 }
 
 #[derive(Clone, Default)]
@@ -31,8 +888,283 @@ impl DatasetRegistry {
         guard.get(dataset_id).cloned()
     }
 
+    pub async fn remove(&self, dataset_id: &str) -> Option<DatasetRecord> {
+        let mut guard = self.inner.write().await;
+        guard.remove(dataset_id)
+    }
+
     pub async fn snapshot(&self) -> Vec<DatasetRecord> {
         let guard = self.inner.read().await;
         guard.values().cloned().collect()
     }
+
+    /// Sequence summaries of the FASTA/FASTQ datasets, as artifacts the
+    /// reply agent can cite
+    pub async fn artifacts(&self) -> Vec<AnalysisArtifact> {
+        let guard = self.inner.read().await;
+        guard
+            .values()
+            .filter_map(|record| Some(record.sequence.as_ref()?.artifact(&record.dataset)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUSE: &str = "ensembl_id,age,expression\nENSMUSG00000000001,12,1.5\nENSMUSG00000000028,24,2.4\n";
+
+    #[tokio::test]
+    async fn test_from_upload_stores_and_detects_species() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = DatasetRecord::from_upload(dir.path(), "mice.csv", MOUSE.as_bytes(), None, None, ParseOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(record.row_count, 2);
+        assert_eq!(record.columns, ["ensembl_id", "age", "expression"]);
+        assert_eq!(record.species, Species::Mouse);
+        assert_eq!(record.dataset.description, "Uploaded dataset mice.csv");
+        assert_eq!(record.column_kind(0), Some(ColumnKind::Identifier));
+        assert_eq!(record.schema_summary(), "2 numeric, 1 identifier");
+        let stored = Path::new(&record.local_path);
+        assert_eq!(stored.parent(), Some(dir.path()));
+        assert_eq!(std::fs::read_to_string(stored).unwrap(), MOUSE);
+
+        let registry = DatasetRegistry::default();
+        registry.insert(record.clone()).await;
+        assert!(registry.get(&record.dataset.id).await.is_some());
+
+        // Wide tables name their gene columns by ID
+        let wide = "age,ENSMUSG00000000001,ENSMUSG00000000028\n12,1.5,2.0\n24,2.4,1.1\n";
+        let record = DatasetRecord::from_upload(dir.path(), "wide.csv", wide.as_bytes(), None, None, ParseOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(record.species, Species::Mouse);
+    }
+
+    #[test]
+    fn test_schema_inference() {
+        let mut csv = String::from("sample,age,tissue,collected,score\n");
+        for i in 0..25 {
+            let score = if i % 5 == 0 { "NA".to_string() } else { format!("{}.5", i) };
+            csv.push_str(&format!("S{},{},{},2024-01-{:02},{}\n", i, 20 + i, ["liver", "brain"][i % 2], i % 28 + 1, score));
+        }
+        let mut rdr = csv::Reader::from_reader(csv.as_bytes());
+        let headers: Vec<String> = rdr.headers().unwrap().iter().map(str::to_string).collect();
+        let mut builder = SchemaBuilder::new(&headers, &ParseOptions::default());
+        for row in rdr.records() {
+            builder.push(&row.unwrap());
+        }
+
+        let schema = builder.finish();
+        let kinds: Vec<ColumnKind> = schema.iter().map(|c| c.kind).collect();
+        assert_eq!(
+            kinds,
+            [ColumnKind::Identifier, ColumnKind::Numeric, ColumnKind::Categorical, ColumnKind::Date, ColumnKind::Numeric]
+        );
+        assert_eq!(schema[4].missing_rate, 0.2);
+        assert_eq!(schema[1].missing_rate, 0.0);
+        assert!(is_ensembl_id("ENSMUSG00000000001.3"));
+        assert!(!is_ensembl_id("ENSEMBL"));
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.csv");
+        std::fs::write(
+            &path,
+            "ensembl_id,age,score,note\nENSG1,'41,5',-,'a, b'\nENSG2,'38',NULL,c\nENSG3,52,'1,25',d\n",
+        )
+        .unwrap();
+        let mut parsing = ParseOptions::default();
+        parsing.set("decimal", ",").unwrap();
+        parsing.set("quote", "'").unwrap();
+        parsing.set("na", "-, null").unwrap();
+        let record = DatasetRecord::from_path_with(&path, None, parsing).unwrap();
+        assert_eq!(record.column_kind(1), Some(ColumnKind::Numeric));
+        assert_eq!(record.column_kind(2), Some(ColumnKind::Numeric));
+        assert!((record.schema[2].missing_rate - 2.0 / 3.0).abs() < 1e-12);
+        let rows: Vec<csv::StringRecord> = record.reader().unwrap().records().map(Result::unwrap).collect();
+        assert_eq!(&rows[0][3], "a, b");
+        let ages: Vec<Option<f64>> = rows.iter().map(|row| record.number(&row[1])).collect();
+        assert_eq!(ages, [Some(41.5), Some(38.0), Some(52.0)]);
+        assert_eq!(record.number(&rows[2][2]), Some(1.25));
+
+        // The default options read the same cells as text
+        std::fs::write(&path, "ensembl_id,age\nENSG1,\"41,5\"\nENSG2,n/a\n").unwrap();
+        assert_eq!(DatasetRecord::from_path(&path, None).unwrap().column_kind(1), Some(ColumnKind::Categorical));
+        assert!(ParseOptions::default().set("decimal", ";").is_err());
+        assert!(ParseOptions::default().set("quote", "''").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_from_upload_rejects_invalid_datasets() {
+        let dir = tempfile::tempdir().unwrap();
+        let upload = |name: &'static str, body: &'static str, species: Option<Species>| {
+            let dir = dir.path().to_path_buf();
+            async move { DatasetRecord::from_upload(&dir, name, body.as_bytes(), None, species, ParseOptions::default()).await }
+        };
+
+        assert!(matches!(upload("data.xlsx", MOUSE, None).await, Err(UploadError::UnsupportedType(ext)) if ext == "xlsx"));
+        assert!(matches!(upload("data.csv", "ensembl_id,expression\nENSG00000000003,1.0\n", None).await, Err(UploadError::MissingColumns)));
+        assert!(matches!(upload("data.csv", "ensembl_id,age\nENSG00000000003,2\n3\n", None).await, Err(UploadError::Malformed(_))));
+        assert!(matches!(upload("data.csv", MOUSE, Some(Species::Human)).await, Err(UploadError::WrongSpecies(_))));
+        // Nothing is stored for rejected uploads
+        assert_eq!(std::fs::read_dir(dir.path()).map(|d| d.count()).unwrap_or(0), 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_reports_progress_and_cancels() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("mice.csv");
+        std::fs::write(&source, MOUSE).unwrap();
+        let uploads = dir.path().join("uploads");
+
+        let mut reports = Vec::new();
+        let cancel = CancellationToken::new();
+        let record = DatasetRecord::import(&uploads, &source, None, ParseOptions::default(), &cancel, |copied, total| {
+            reports.push((copied, total))
+        })
+        .await
+        .unwrap();
+        let size = MOUSE.len() as u64;
+        assert_eq!(reports, [(size, size)]);
+        assert_eq!(record.dataset.filename, "mice.csv");
+        assert_eq!(record.dataset.description, "Uploaded dataset mice.csv");
+        assert_eq!(record.species, Species::Mouse);
+        assert_eq!(record.row_count, 2);
+        assert!(record.local_path.contains(&record.dataset.id));
+        assert_eq!(std::fs::read_to_string(&record.local_path).unwrap(), MOUSE);
+        std::fs::remove_file(&record.local_path).unwrap();
+
+        cancel.cancel();
+        let err = DatasetRecord::import(&uploads, &source, None, ParseOptions::default(), &cancel, |_, _| {})
+            .await
+            .unwrap_err();
+        assert!(cancel::is_cancelled(&err));
+        std::fs::write(dir.path().join("notes.csv"), "gene,value
+A,1
+").unwrap();
+        let err = DatasetRecord::import(&uploads, &dir.path().join("notes.csv"), None, ParseOptions::default(), &CancellationToken::new(), |_, _| {})
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Ensembl ID and Age"), "{}", err);
+        assert_eq!(std::fs::read_dir(&uploads).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_parquet_is_stored_as_csv() {
+        use arrow::array::{ArrayRef, Float64Array, StringArray};
+
+        let batch = arrow::record_batch::RecordBatch::try_from_iter([
+            ("ensembl_id", Arc::new(StringArray::from(vec!["ENSMUSG00000000001", "ENSMUSG00000000028"])) as ArrayRef),
+            ("age", Arc::new(Float64Array::from(vec![12.0, 24.0])) as ArrayRef),
+            ("expression", Arc::new(Float64Array::from(vec![Some(1.5), None])) as ArrayRef),
+        ])
+        .unwrap();
+        let mut bytes = Vec::new();
+        let mut writer = ::parquet::arrow::ArrowWriter::try_new(&mut bytes, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut parsing = ParseOptions::default();
+        parsing.set("decimal", ",").unwrap();
+        let record = DatasetRecord::from_upload(dir.path(), "mice.parquet", &bytes, None, None, parsing.clone())
+            .await
+            .unwrap();
+        assert_eq!(record.dataset.filename, "mice.parquet");
+        assert_eq!(record.dataset.size, Some(bytes.len() as i64));
+        assert!(record.local_path.ends_with("-mice.csv"));
+        assert_eq!((record.row_count, record.species, record.parsing.decimal), (2, Species::Mouse, '.'));
+        assert_eq!(record.schema_summary(), "2 numeric, 1 identifier");
+
+        let source = dir.path().join("mice.parquet");
+        std::fs::write(&source, &bytes).unwrap();
+        let uploads = dir.path().join("uploads");
+        let record = DatasetRecord::import(&uploads, &source, None, parsing, &CancellationToken::new(), |_, _| {})
+            .await
+            .unwrap();
+        assert_eq!(record.dataset.filename, "mice.parquet");
+        assert_eq!(
+            std::fs::read_to_string(&record.local_path).unwrap(),
+            "ensembl_id,age,expression\nENSMUSG00000000001,12.0,1.5\nENSMUSG00000000028,24.0,\n"
+        );
+        // The Parquet copy is not kept
+        assert_eq!(std::fs::read_dir(&uploads).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_geo_series_matrix_is_stored_as_csv() {
+        let matrix = "!Series_geo_accession\t\"GSE1\"\n\
+            !Sample_geo_accession\t\"GSM1\"\t\"GSM2\"\n\
+            !Sample_characteristics_ch1\t\"age: 12 months\"\t\"age: 24 months\"\n\
+            !Sample_characteristics_ch1\t\"strain: C57BL/6\"\t\"strain: C57BL/6\"\n\
+            !series_matrix_table_begin\n\
+            \"ID_REF\"\t\"GSM1\"\t\"GSM2\"\n\
+            \"ENSMUSG00000000001\"\t1.5\t2.5\n\
+            !series_matrix_table_end\n";
+        let dir = tempfile::tempdir().unwrap();
+        let record = DatasetRecord::from_upload(
+            dir.path(),
+            "GSE1_series_matrix.txt",
+            matrix.as_bytes(),
+            None,
+            None,
+            ParseOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(record.dataset.filename, "GSE1_series_matrix.txt");
+        assert!(record.local_path.ends_with("-GSE1_series_matrix.csv"));
+        assert_eq!(record.columns, ["sample", "title", "source_name", "age", "strain", "ENSMUSG00000000001"]);
+        assert_eq!((record.row_count, record.species), (2, Species::Mouse));
+    }
+
+    #[tokio::test]
+    async fn test_fastq_upload_is_summarized() {
+        let fastq = "@r1\nACGT\n+\nIIII\n@r2\nGGCCAA\n+\nIIII++\n";
+        let dir = tempfile::tempdir().unwrap();
+        let record = DatasetRecord::from_upload(dir.path(), "reads.fastq", fastq.as_bytes(), None, None, ParseOptions::default())
+            .await
+            .unwrap();
+        assert!(record.local_path.ends_with("-reads.csv"));
+        assert_eq!(record.row_count, 2);
+        let summary = record.sequence.clone().unwrap();
+        assert_eq!((summary.reads, summary.bases, summary.n50), (2, 10, 6));
+        assert!((summary.gc_content - 0.6).abs() < 1e-9);
+
+        let registry = DatasetRegistry::default();
+        registry.insert(record).await;
+        let artifacts = registry.artifacts().await;
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].artifact_type, sequence::ARTIFACT_TYPE);
+        assert!(artifacts[0].description.starts_with("Sequence summary of reads.fastq: 2 reads, 10 bases"));
+    }
+
+    #[tokio::test]
+    async fn test_vcf_without_ages_takes_them_from_a_sample_sheet() {
+        let vcf = "##fileformat=VCFv4.2\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\n\
+            1\t100\trs1\tA\tG\t50\tPASS\t.\tGT\t0/1\t1/1\n";
+        let dir = tempfile::tempdir().unwrap();
+        let upload = || DatasetRecord::from_upload(dir.path(), "cohort.vcf", vcf.as_bytes(), None, None, ParseOptions::default());
+        let error = upload().await.unwrap_err();
+        assert!(matches!(error, UploadError::MissingAge), "{}", error);
+
+        let path = dir.path().join("cohort.csv");
+        vcf::to_csv(vcf.as_bytes(), std::fs::File::create(&path).unwrap()).unwrap();
+        let sheet = dir.path().join("samples.csv");
+        std::fs::write(&sheet, "sample,age,sex\nS2,71,M\nS1,34,F\n").unwrap();
+        let record = DatasetRecord::from_path(&path, None).unwrap();
+        assert_eq!(record.columns, ["sample", "rs1"]);
+        let joined = record.join_samples(&sheet, dir.path()).unwrap();
+        assert_eq!(joined.dataset.filename, "cohort.csv");
+        assert_eq!(joined.columns, ["sample", "rs1", "age", "sex"]);
+        let csv = std::fs::read_to_string(&joined.local_path).unwrap();
+        assert_eq!(csv, "sample,rs1,age,sex\nS1,1,34,F\nS2,2,71,M\n");
+    }
 }
@@ -0,0 +1,368 @@
+//! GEO series matrix and SOFT files
+//!
+//! Datasets downloaded from NCBI GEO come as a series matrix
+//! (`GSE…_series_matrix.txt.gz`: sample metadata as `!Sample_…` rows, then a
+//! probe × sample table) or a family SOFT file (`GSE…_family.soft.gz`: one
+//! `^SAMPLE` block per sample with its own `ID_REF`/`VALUE` table). Both are
+//! converted to one CSV row per sample: its accession, title and source, a
+//! column per sample characteristic (`age: 34 years` becomes `age` = 34) and
+//! a column per probe or gene. The covariates are then there for the target,
+//! `--group`, `--covariates` and `--batch` without editing the download.
+//! Gzipped files are read as they are.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use anyhow::{bail, Result};
+use flate2::read::MultiGzDecoder;
+
+/// Columns written before the characteristics
+const SAMPLE_COLUMNS: [&str; 3] = ["sample", "title", "source_name"];
+
+/// Whether `filename` names a series matrix or SOFT file, gzipped or not
+pub fn is_geo(filename: &str) -> bool {
+    let name = filename.to_ascii_lowercase();
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+    name.ends_with("_series_matrix.txt") || name.ends_with(".soft")
+}
+
+#[derive(Debug, Default)]
+struct Sample {
+    accession: String,
+    title: String,
+    source: String,
+    /// Characteristic name and value, in file order
+    characteristics: Vec<(String, String)>,
+    /// Value per feature, by index into [`Series::features`]
+    values: Vec<Option<String>>,
+}
+
+#[derive(Debug, Default)]
+struct Series {
+    samples: Vec<Sample>,
+    /// Probe or gene IDs, in first-seen order
+    features: Vec<String>,
+    index: HashMap<String, usize>,
+}
+
+impl Series {
+    fn feature(&mut self, id: &str) -> usize {
+        if let Some(&i) = self.index.get(id) {
+            return i;
+        }
+        self.features.push(id.to_string());
+        self.index.insert(id.to_string(), self.features.len() - 1);
+        self.features.len() - 1
+    }
+}
+
+/// Write the series matrix or SOFT file read from `input` (gzipped or not)
+/// to `output` as CSV, one row per sample; returns the number of samples
+pub fn to_csv(input: impl Read, output: impl Write) -> Result<usize> {
+    let mut input = BufReader::new(input);
+    let gzipped = input.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let lines: Box<dyn BufRead> = if gzipped {
+        Box::new(BufReader::new(MultiGzDecoder::new(input)))
+    } else {
+        Box::new(input)
+    };
+    let mut lines = lines.lines().peekable();
+    let first = loop {
+        match lines.peek() {
+            Some(Ok(line)) if line.trim().is_empty() => {
+                lines.next();
+            }
+            Some(Ok(line)) => break line.clone(),
+            Some(Err(_)) => return Err(lines.next().unwrap().unwrap_err().into()),
+            None => bail!("Empty GEO file"),
+        }
+    };
+    let series = if first.starts_with('^') {
+        parse_soft(lines)?
+    } else if first.starts_with('!') {
+        parse_matrix(lines)?
+    } else {
+        bail!("Not a GEO series matrix or SOFT file");
+    };
+    if series.samples.is_empty() {
+        bail!("The GEO file has no samples");
+    }
+    write_csv(&series, output)?;
+    Ok(series.samples.len())
+}
+
+/// Cells of a tab-separated series matrix line, unquoted
+fn cells(line: &str) -> Vec<String> {
+    line.split('\t')
+        .map(|cell| {
+            let cell = cell.trim();
+            cell.strip_prefix('"').and_then(|c| c.strip_suffix('"')).unwrap_or(cell).to_string()
+        })
+        .collect()
+}
+
+fn parse_matrix(lines: impl Iterator<Item = std::io::Result<String>>) -> Result<Series> {
+    let mut series = Series::default();
+    let mut in_table = false;
+    let mut header_read = false;
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut row = cells(&line);
+        let key = row.remove(0);
+        if in_table {
+            if key.eq_ignore_ascii_case("!series_matrix_table_end") {
+                break;
+            }
+            if !header_read {
+                // `ID_REF` then the sample accessions, in metadata order
+                header_read = true;
+                if series.samples.is_empty() {
+                    series.samples = row.into_iter().map(|accession| Sample { accession, ..Sample::default() }).collect();
+                }
+                continue;
+            }
+            let feature = series.feature(&key);
+            for (sample, value) in series.samples.iter_mut().zip(row) {
+                // Missing values are written as `null`
+                let value = if value == "null" { String::new() } else { value };
+                set_value(&mut sample.values, feature, value);
+            }
+            continue;
+        }
+        match key.to_ascii_lowercase().as_str() {
+            "!series_matrix_table_begin" => in_table = true,
+            "!sample_geo_accession" => {
+                if series.samples.len() < row.len() {
+                    series.samples.resize_with(row.len(), Sample::default);
+                }
+                for (sample, accession) in series.samples.iter_mut().zip(row) {
+                    sample.accession = accession;
+                }
+            }
+            "!sample_title" | "!sample_source_name_ch1" | "!sample_characteristics_ch1"
+            | "!sample_characteristics_ch2" => {
+                if series.samples.len() < row.len() {
+                    series.samples.resize_with(row.len(), Sample::default);
+                }
+                for (sample, value) in series.samples.iter_mut().zip(row) {
+                    sample.describe(&key[1..], &value);
+                }
+            }
+            _ => {}
+        }
+    }
+    if !header_read {
+        bail!("The series matrix has no data table");
+    }
+    Ok(series)
+}
+
+fn parse_soft(lines: impl Iterator<Item = std::io::Result<String>>) -> Result<Series> {
+    let mut series = Series::default();
+    let mut current: Option<Sample> = None;
+    let mut in_table = false;
+    let mut value_column = None;
+    for line in lines {
+        let line = line?;
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.trim().is_empty() {
+            continue;
+        }
+        if in_table {
+            if line.eq_ignore_ascii_case("!sample_table_end") {
+                in_table = false;
+                continue;
+            }
+            let row: Vec<&str> = line.split('\t').collect();
+            let Some(column) = value_column else {
+                // Header: ID_REF, VALUE and optional extra columns
+                value_column = Some(row.iter().position(|c| c.trim().eq_ignore_ascii_case("VALUE")).unwrap_or(1));
+                continue;
+            };
+            if let (Some(sample), Some(id)) = (current.as_mut(), row.first()) {
+                let feature = series.feature(id.trim());
+                let value = row.get(column).map(|v| v.trim().to_string()).unwrap_or_default();
+                set_value(&mut sample.values, feature, value);
+            }
+            continue;
+        }
+        let (key, value) = line.split_once('=').map_or((line, ""), |(k, v)| (k.trim(), v.trim()));
+        if let Some(entity) = key.strip_prefix('^') {
+            series.samples.extend(current.take());
+            if entity.eq_ignore_ascii_case("SAMPLE") {
+                current = Some(Sample { accession: value.to_string(), ..Sample::default() });
+            }
+            continue;
+        }
+        let Some(sample) = current.as_mut() else {
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "!sample_table_begin" => {
+                in_table = true;
+                value_column = None;
+            }
+            "!sample_title" | "!sample_source_name_ch1" | "!sample_characteristics_ch1"
+            | "!sample_characteristics_ch2" => sample.describe(&key[1..], value),
+            _ => {}
+        }
+    }
+    series.samples.extend(current);
+    // Platform or series blocks without a table are not samples
+    series.samples.retain(|s| !s.accession.is_empty());
+    Ok(series)
+}
+
+fn set_value(values: &mut Vec<Option<String>>, feature: usize, value: String) {
+    if values.len() <= feature {
+        values.resize(feature + 1, None);
+    }
+    values[feature] = Some(value);
+}
+
+impl Sample {
+    /// Record a `Sample_title`, `Sample_source_name_ch1` or
+    /// `Sample_characteristics_ch*` value
+    fn describe(&mut self, key: &str, value: &str) {
+        match key.to_ascii_lowercase().as_str() {
+            "sample_title" => self.title = value.to_string(),
+            "sample_source_name_ch1" => self.source = value.to_string(),
+            _ if value.is_empty() => {}
+            _ => {
+                let (name, value) = match value.split_once(':') {
+                    Some((name, value)) if !name.trim().is_empty() => (column_name(name), strip_unit(value.trim())),
+                    _ => ("characteristics".to_string(), value),
+                };
+                // Repeated names (e.g. per channel) get a numbered column
+                let mut unique = name.clone();
+                let mut n = 1;
+                while self.characteristics.iter().any(|(existing, _)| *existing == unique) {
+                    n += 1;
+                    unique = format!("{}_{}", name, n);
+                }
+                self.characteristics.push((unique, value.to_string()));
+            }
+        }
+    }
+}
+
+/// `cell type` → `cell_type`, `Age (yrs)` → `age_yrs`
+fn column_name(name: &str) -> String {
+    let mut column = String::new();
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            column.extend(c.to_lowercase());
+        } else if !column.ends_with('_') && !column.is_empty() {
+            column.push('_');
+        }
+    }
+    column.trim_end_matches('_').to_string()
+}
+
+/// `34 years` → `34`; anything else unchanged
+fn strip_unit(value: &str) -> &str {
+    let mut parts = value.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(number), Some(unit), None)
+            if number.parse::<f64>().is_ok() && unit.chars().all(|c| c.is_alphabetic() || c == '.') =>
+        {
+            number
+        }
+        _ => value,
+    }
+}
+
+fn write_csv(series: &Series, output: impl Write) -> Result<()> {
+    let mut characteristics: Vec<&str> = Vec::new();
+    for sample in &series.samples {
+        for (name, _) in &sample.characteristics {
+            if !characteristics.contains(&name.as_str()) && !SAMPLE_COLUMNS.contains(&name.as_str()) {
+                characteristics.push(name);
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(output);
+    writer.write_record(
+        SAMPLE_COLUMNS
+            .iter()
+            .copied()
+            .chain(characteristics.iter().copied())
+            .chain(series.features.iter().map(String::as_str)),
+    )?;
+    let mut record: Vec<&str> = Vec::new();
+    for sample in &series.samples {
+        record.clear();
+        record.extend([sample.accession.as_str(), &sample.title, &sample.source]);
+        record.extend(characteristics.iter().map(|name| {
+            sample
+                .characteristics
+                .iter()
+                .find(|(n, _)| n == name)
+                .map_or("", |(_, v)| v.as_str())
+        }));
+        record.extend((0..series.features.len()).map(|i| sample.values.get(i).and_then(|v| v.as_deref()).unwrap_or("")));
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATRIX: &str = "!Series_title\t\"Aging liver\"\n\
+        !Series_geo_accession\t\"GSE1\"\n\
+        !Sample_title\t\"liver young\"\t\"liver old\"\n\
+        !Sample_geo_accession\t\"GSM1\"\t\"GSM2\"\n\
+        !Sample_source_name_ch1\t\"liver\"\t\"liver\"\n\
+        !Sample_characteristics_ch1\t\"age: 34 years\"\t\"age: 71 years\"\n\
+        !Sample_characteristics_ch1\t\"Cell type: hepatocyte\"\t\"Cell type: hepatocyte\"\n\
+        !series_matrix_table_begin\n\
+        \"ID_REF\"\t\"GSM1\"\t\"GSM2\"\n\
+        \"ENSG00000141510\"\t1.5\t2.25\n\
+        \"ENSG00000012048\"\t0.5\tnull\n\
+        !series_matrix_table_end\n";
+
+    const SOFT: &str = "^DATABASE = GeoMiame\n!Database_name = Gene Expression Omnibus (GEO)\n\
+        ^SERIES = GSE1\n!Series_title = Aging liver\n\
+        ^PLATFORM = GPL1\n!Platform_title = Array\n\
+        ^SAMPLE = GSM1\n!Sample_title = liver young\n!Sample_characteristics_ch1 = age: 34 years\n\
+        !sample_table_begin\nID_REF\tVALUE\tDETECTION\nENSG00000141510\t1.5\tP\nENSG00000012048\t0.5\tP\n!sample_table_end\n\
+        ^SAMPLE = GSM2\n!Sample_title = liver old\n!Sample_characteristics_ch1 = age: 71 years\n\
+        !Sample_characteristics_ch1 = sex: F\n\
+        !sample_table_begin\nID_REF\tVALUE\nENSG00000012048\t0.75\n!sample_table_end\n";
+
+    fn convert(input: &[u8]) -> String {
+        let mut csv = Vec::new();
+        to_csv(input, &mut csv).unwrap();
+        String::from_utf8(csv).unwrap()
+    }
+
+    #[test]
+    fn test_matrix_and_soft_become_one_row_per_sample() {
+        assert_eq!(
+            convert(MATRIX.as_bytes()),
+            "sample,title,source_name,age,cell_type,ENSG00000141510,ENSG00000012048\n\
+             GSM1,liver young,liver,34,hepatocyte,1.5,0.5\n\
+             GSM2,liver old,liver,71,hepatocyte,2.25,\n"
+        );
+        assert_eq!(
+            convert(SOFT.as_bytes()),
+            "sample,title,source_name,age,sex,ENSG00000141510,ENSG00000012048\n\
+             GSM1,liver young,,34,,1.5,0.5\n\
+             GSM2,liver old,,71,F,,0.75\n"
+        );
+
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzipped.write_all(MATRIX.as_bytes()).unwrap();
+        assert_eq!(convert(&gzipped.finish().unwrap()), convert(MATRIX.as_bytes()));
+
+        assert!(to_csv("ensembl_id,age\n".as_bytes(), Vec::new()).is_err());
+        assert!(is_geo("GSE1_series_matrix.txt.gz") && is_geo("GSE1_family.soft") && !is_geo("GSE1.txt"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Parquet datasets
+//!
+//! Expression matrices exported from pandas, polars or R's arrow package
+//! often come as Parquet. They are converted to CSV once, on ingestion, so
+//! schema inference and every analysis pass read them like any other
+//! dataset. Nulls become empty cells, which read as missing; numbers are
+//! written with a decimal point.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use arrow::record_batch::RecordBatchReader;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::file::reader::ChunkReader;
+
+/// File extension of Parquet datasets
+pub const EXTENSION: &str = "parquet";
+
+/// Write the Parquet file read from `input` to `output` as CSV with a
+/// header row of its column names; returns the number of rows
+pub fn to_csv<R: ChunkReader + 'static>(input: R, output: impl Write) -> Result<usize> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(input)
+        .context("Not a readable Parquet file")?
+        .build()?;
+    let mut writer = csv::Writer::from_writer(output);
+    writer.write_record(reader.schema().fields().iter().map(|f| f.name()))?;
+
+    let options = FormatOptions::new();
+    let mut rows = 0usize;
+    let mut record = Vec::new();
+    for batch in reader {
+        let batch = batch.with_context(|| format!("Malformed Parquet data after row {}", rows))?;
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()?;
+        for row in 0..batch.num_rows() {
+            record.clear();
+            record.extend(columns.iter().map(|column| column.value(row).to_string()));
+            writer.write_record(&record)?;
+        }
+        rows += batch.num_rows();
+    }
+    writer.flush()?;
+    Ok(rows)
+}
+
+/// [`to_csv`] of Parquet bytes held in memory
+pub fn to_csv_bytes(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut csv = Vec::new();
+    to_csv(bytes::Bytes::copy_from_slice(bytes), &mut csv)?;
+    Ok(csv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, Float64Array, Int32Array, StringArray};
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+
+    #[test]
+    fn test_parquet_to_csv() {
+        let batch = RecordBatch::try_from_iter([
+            ("ensembl_id", Arc::new(StringArray::from(vec!["ENSG00000141510", "ENSG00000012048"])) as ArrayRef),
+            ("Age", Arc::new(Int32Array::from(vec![34, 71])) as ArrayRef),
+            ("expr", Arc::new(Float64Array::from(vec![Some(1.5), None])) as ArrayRef),
+        ])
+        .unwrap();
+        let mut parquet = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut parquet, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let csv = to_csv_bytes(&parquet).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "ensembl_id,Age,expr\nENSG00000141510,34,1.5\nENSG00000012048,71,\n"
+        );
+        assert!(to_csv_bytes(b"ensembl_id,Age\n").is_err());
+    }
+}
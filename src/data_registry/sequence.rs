@@ -0,0 +1,384 @@
+//! FASTA and FASTQ sequence files
+//!
+//! Reads and contigs are not samples to analyse, so a FASTA/FASTQ file (or
+//! its gzipped form) is converted to a per-read table instead: `read`,
+//! `length`, `gc_bases`, `n_bases` and, for FASTQ, `mean_quality` and
+//! `q30_bases` (Phred+33). The table is summarized as it is ingested into a
+//! [`SequenceSummary`] — length distribution and N50, GC content and base
+//! qualities — which the reply agent sees as a `SEQUENCE_SUMMARY` artifact.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use anyhow::{bail, Context, Result};
+use flate2::read::MultiGzDecoder;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{AnalysisArtifact, UploadedDataset};
+
+/// Extensions of FASTA and FASTQ files, each also taken gzipped
+pub const EXTENSIONS: [&str; 7] = ["fasta", "fa", "fna", "ffn", "frn", "fastq", "fq"];
+
+/// Columns of the per-read table
+pub const COLUMNS: [&str; 6] = ["read", "length", "gc_bases", "n_bases", "mean_quality", "q30_bases"];
+
+/// `artifact_type` of the summary handed to the reply agent
+pub const ARTIFACT_TYPE: &str = "SEQUENCE_SUMMARY";
+
+/// Bins of the length histogram
+const LENGTH_BINS: u64 = 10;
+
+/// Reads with a lower mean quality count as low quality
+const LOW_QUALITY: f64 = 20.0;
+
+/// Whether `filename` names a FASTA or FASTQ file, gzipped or not
+pub fn is_sequence(filename: &str) -> bool {
+    let name = filename.to_ascii_lowercase();
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+    EXTENSIONS.iter().any(|extension| name.strip_suffix(extension).is_some_and(|stem| stem.ends_with('.')))
+}
+
+/// Whether `columns` are those of a converted sequence file
+pub fn is_table(columns: &[String]) -> bool {
+    columns.iter().map(String::as_str).eq(COLUMNS)
+}
+
+/// Write the FASTA or FASTQ read from `input` (gzipped or not) to `output`
+/// as the per-read table; returns the number of reads
+pub fn to_csv(input: impl Read, output: impl Write) -> Result<usize> {
+    let mut input = BufReader::new(input);
+    let gzipped = input.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let mut lines: Box<dyn BufRead> = if gzipped {
+        Box::new(BufReader::new(MultiGzDecoder::new(input)))
+    } else {
+        Box::new(input)
+    };
+    let mut writer = csv::Writer::from_writer(output);
+    writer.write_record(COLUMNS).context("Failed to write the read table")?;
+
+    let mut reads = 0;
+    let mut line = String::new();
+    let mut next = |line: &mut String| -> Result<bool> {
+        line.clear();
+        let read = lines.read_line(line)?;
+        let end = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(end);
+        Ok(read > 0)
+    };
+    // The first line tells FASTA (`>`) from FASTQ (`@`)
+    while next(&mut line)? && line.trim().is_empty() {}
+    if let Some(header) = line.strip_prefix('>') {
+        let (mut read_id, mut bases) = (id(header), Bases::default());
+        while next(&mut line)? {
+            match line.strip_prefix('>') {
+                Some(header) => {
+                    bases.write(&mut writer, &read_id, None)?;
+                    reads += 1;
+                    (read_id, bases) = (id(header), Bases::default());
+                }
+                None => bases.push(line.trim().as_bytes()),
+            }
+        }
+        bases.write(&mut writer, &read_id, None)?;
+        reads += 1;
+    } else if line.starts_with('@') {
+        loop {
+            if line.trim().is_empty() {
+                if !next(&mut line)? {
+                    break;
+                }
+                continue;
+            }
+            let Some(header) = line.strip_prefix('@') else {
+                bail!("Read {}: expected a FASTQ header starting with '@', got {:?}", reads + 1, line);
+            };
+            let read_id = id(header);
+            let mut sequence = String::new();
+            let mut separator = String::new();
+            let mut quality = String::new();
+            if !next(&mut sequence)? || !next(&mut separator)? || !separator.starts_with('+') || !next(&mut quality)? {
+                bail!("Read {} ({}) is truncated: a FASTQ record is 4 lines", reads + 1, read_id);
+            }
+            if quality.len() != sequence.len() {
+                bail!(
+                    "Read {} ({}) has {} bases but {} quality scores",
+                    reads + 1,
+                    read_id,
+                    sequence.len(),
+                    quality.len()
+                );
+            }
+            let mut bases = Bases::default();
+            bases.push(sequence.as_bytes());
+            bases.write(&mut writer, &read_id, Some(quality.as_bytes()))?;
+            reads += 1;
+            if !next(&mut line)? {
+                break;
+            }
+        }
+    } else {
+        bail!("Not a FASTA or FASTQ file: it must start with '>' or '@'");
+    }
+    writer.flush()?;
+    Ok(reads)
+}
+
+/// Read ID: the header up to the first whitespace
+fn id(header: &str) -> String {
+    header.split_whitespace().next().unwrap_or("").to_string()
+}
+
+/// Base counts of one sequence
+#[derive(Default)]
+struct Bases {
+    length: u64,
+    gc: u64,
+    n: u64,
+}
+
+impl Bases {
+    fn push(&mut self, sequence: &[u8]) {
+        self.length += sequence.len() as u64;
+        for base in sequence {
+            match base.to_ascii_uppercase() {
+                b'G' | b'C' | b'S' => self.gc += 1,
+                b'N' => self.n += 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn write(&self, writer: &mut csv::Writer<impl Write>, id: &str, quality: Option<&[u8]>) -> Result<()> {
+        let (mean, q30) = match quality {
+            Some(quality) if !quality.is_empty() => {
+                let scores = quality.iter().map(|q| q.saturating_sub(33) as u64);
+                let total: u64 = scores.clone().sum();
+                let q30 = scores.filter(|&q| q >= 30).count();
+                (format!("{:.2}", total as f64 / quality.len() as f64), q30.to_string())
+            }
+            _ => (String::new(), String::new()),
+        };
+        writer.write_record([
+            id,
+            &self.length.to_string(),
+            &self.gc.to_string(),
+            &self.n.to_string(),
+            &mean,
+            &q30,
+        ])?;
+        Ok(())
+    }
+}
+
+/// Read statistics of a FASTA or FASTQ file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SequenceSummary {
+    pub reads: usize,
+    pub bases: u64,
+    pub min_length: u64,
+    pub max_length: u64,
+    pub mean_length: f64,
+    pub median_length: u64,
+    /// Length such that reads at least this long hold half the bases
+    pub n50: u64,
+    /// Reads per length range, in up to 10 equal-width bins
+    pub length_histogram: Vec<LengthBin>,
+    /// Share of the called (non-N) bases that are G or C
+    pub gc_content: f64,
+    /// Share of the bases that are N
+    pub n_content: f64,
+    /// Base qualities; FASTQ only
+    pub quality: Option<QualitySummary>,
+}
+
+/// Reads of length `from..=to`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LengthBin {
+    pub from: u64,
+    pub to: u64,
+    pub reads: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualitySummary {
+    /// Mean Phred score over all bases
+    pub mean: f64,
+    /// Share of bases at Q30 or above
+    pub q30: f64,
+    /// Reads with a mean score under 20
+    pub low_quality_reads: usize,
+}
+
+/// Builds a [`SequenceSummary`] from the rows of the per-read table
+#[derive(Debug, Default)]
+pub struct Summarizer {
+    /// Reads per length
+    lengths: BTreeMap<u64, usize>,
+    bases: u64,
+    gc: u64,
+    n: u64,
+    quality_sum: f64,
+    quality_bases: u64,
+    q30: u64,
+    low_quality: usize,
+}
+
+impl Summarizer {
+    pub fn push(&mut self, row: &csv::StringRecord) {
+        let number = |i: usize| row.get(i).and_then(|v| v.trim().parse::<u64>().ok());
+        let Some(length) = number(1) else {
+            return;
+        };
+        *self.lengths.entry(length).or_insert(0) += 1;
+        self.bases += length;
+        self.gc += number(2).unwrap_or(0);
+        self.n += number(3).unwrap_or(0);
+        if let Some(mean) = row.get(4).and_then(|v| v.trim().parse::<f64>().ok()) {
+            self.quality_sum += mean * length as f64;
+            self.quality_bases += length;
+            self.q30 += number(5).unwrap_or(0);
+            if mean < LOW_QUALITY {
+                self.low_quality += 1;
+            }
+        }
+    }
+
+    pub fn finish(self) -> SequenceSummary {
+        let reads: usize = self.lengths.values().sum();
+        let min_length = self.lengths.keys().next().copied().unwrap_or(0);
+        let max_length = self.lengths.keys().next_back().copied().unwrap_or(0);
+
+        let mut median_length = 0;
+        let mut seen = 0;
+        for (&length, &count) in &self.lengths {
+            seen += count;
+            if seen * 2 >= reads {
+                median_length = length;
+                break;
+            }
+        }
+        let mut n50 = 0;
+        let mut covered = 0;
+        for (&length, &count) in self.lengths.iter().rev() {
+            covered += length * count as u64;
+            if covered * 2 >= self.bases {
+                n50 = length;
+                break;
+            }
+        }
+
+        let width = ((max_length - min_length) / LENGTH_BINS + 1).max(1);
+        let mut length_histogram: Vec<LengthBin> = Vec::new();
+        for (&length, &count) in &self.lengths {
+            let from = min_length + (length - min_length) / width * width;
+            match length_histogram.last_mut() {
+                Some(bin) if bin.from == from => bin.reads += count,
+                _ => length_histogram.push(LengthBin { from, to: (from + width - 1).min(max_length), reads: count }),
+            }
+        }
+
+        let ratio = |part: u64, whole: u64| if whole == 0 { 0.0 } else { part as f64 / whole as f64 };
+        SequenceSummary {
+            reads,
+            bases: self.bases,
+            min_length,
+            max_length,
+            mean_length: ratio(self.bases, reads as u64),
+            median_length,
+            n50,
+            length_histogram,
+            gc_content: ratio(self.gc, self.bases - self.n),
+            n_content: ratio(self.n, self.bases),
+            quality: (self.quality_bases > 0).then(|| QualitySummary {
+                mean: self.quality_sum / self.quality_bases as f64,
+                q30: ratio(self.q30, self.quality_bases),
+                low_quality_reads: self.low_quality,
+            }),
+        }
+    }
+}
+
+impl SequenceSummary {
+    /// One-paragraph description, e.g. for the TUI and the artifact
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "{} reads, {} bases; length {}-{} (mean {:.1}, median {}, N50 {}); GC {:.1}%, N {:.2}%",
+            self.reads,
+            self.bases,
+            self.min_length,
+            self.max_length,
+            self.mean_length,
+            self.median_length,
+            self.n50,
+            self.gc_content * 100.0,
+            self.n_content * 100.0
+        );
+        if let Some(quality) = &self.quality {
+            text.push_str(&format!(
+                "; mean quality Q{:.1}, {:.1}% of bases at Q30+, {} reads under Q{}",
+                quality.mean,
+                quality.q30 * 100.0,
+                quality.low_quality_reads,
+                LOW_QUALITY
+            ));
+        }
+        text
+    }
+
+    /// The summary of `dataset` as an artifact the reply agent can cite,
+    /// with the statistics as JSON in `content`
+    pub fn artifact(&self, dataset: &UploadedDataset) -> AnalysisArtifact {
+        AnalysisArtifact {
+            id: format!("{}-sequence-summary", dataset.id),
+            description: format!("Sequence summary of {}: {}", dataset.filename, self.describe()),
+            artifact_type: ARTIFACT_TYPE.to_string(),
+            content: serde_json::to_string(self).ok(),
+            name: dataset.filename.clone(),
+            path: dataset.path.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summarize(csv: &[u8]) -> SequenceSummary {
+        let mut summarizer = Summarizer::default();
+        for row in csv::Reader::from_reader(csv).records() {
+            summarizer.push(&row.unwrap());
+        }
+        summarizer.finish()
+    }
+
+    #[test]
+    fn test_fasta_and_fastq_summaries() {
+        let fasta = ">contig1 assembled\nACGT\nGGNN\n\n>contig2\nAT\n>contig3\nGGGGCCCCAA\n";
+        let mut csv = Vec::new();
+        assert_eq!(to_csv(fasta.as_bytes(), &mut csv).unwrap(), 3);
+        assert_eq!(
+            String::from_utf8(csv.clone()).unwrap(),
+            "read,length,gc_bases,n_bases,mean_quality,q30_bases\n\
+             contig1,8,4,2,,\n\
+             contig2,2,0,0,,\n\
+             contig3,10,8,0,,\n"
+        );
+        let summary = summarize(&csv);
+        assert_eq!((summary.reads, summary.bases, summary.median_length, summary.n50), (3, 20, 8, 10));
+        assert!((summary.gc_content - 12.0 / 18.0).abs() < 1e-9);
+        assert_eq!(summary.quality, None);
+        assert_eq!(summary.length_histogram.iter().map(|b| b.reads).sum::<usize>(), 3);
+
+        // Q40 ('I') and Q10 ('+')
+        let fastq = "@r1 1:N:0\nACGG\n+\nIIII\n@r2\nNTTA\n+r2\n++II\n";
+        let mut csv = Vec::new();
+        assert_eq!(to_csv(fastq.as_bytes(), &mut csv).unwrap(), 2);
+        assert!(String::from_utf8_lossy(&csv).ends_with("r1,4,3,0,40.00,4\nr2,4,0,1,25.00,2\n"));
+        let quality = summarize(&csv).quality.unwrap();
+        assert_eq!((quality.mean, quality.q30, quality.low_quality_reads), (32.5, 0.75, 0));
+
+        assert!(to_csv("@r1\nACGT\n+\nII\n".as_bytes(), Vec::new()).is_err());
+        assert!(to_csv("gene,age\n".as_bytes(), Vec::new()).is_err());
+        assert!(is_sequence("reads.fastq.gz") && is_sequence("Genome.FA") && !is_sequence("data.csv"));
+    }
+}
@@ -0,0 +1,202 @@
+//! VCF variant files
+//!
+//! A VCF (`.vcf` or bgzipped `.vcf.gz`) holds one line per variant with a
+//! genotype per sample. It is converted to one CSV row per sample: a
+//! `sample` column, a column per field of the `##SAMPLE=<ID=…,Age=…>` header
+//! lines (covariates such as age or sex, when the file carries them) and a
+//! column per variant with the sample's alternate allele dosage — 0, 1 or 2
+//! copies from `GT`, or the imputed dosage from `DS` when present — so the
+//! correlation and regression passes screen variants like any other marker.
+//! Variants are named by their ID, or `<chrom>_<pos>_<ref>_<alt>` without
+//! one; variants failing a filter are left out, missing genotypes are
+//! empty cells.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use anyhow::{bail, Context, Result};
+use flate2::read::MultiGzDecoder;
+
+/// Whether `filename` names a VCF, bgzipped or not
+pub fn is_vcf(filename: &str) -> bool {
+    let name = filename.to_ascii_lowercase();
+    name.ends_with(".vcf") || name.ends_with(".vcf.gz") || name.ends_with(".vcf.bgz")
+}
+
+/// Write the VCF read from `input` (gzipped or not) to `output` as CSV, one
+/// row per sample; returns the number of variants
+pub fn to_csv(input: impl Read, output: impl Write) -> Result<usize> {
+    let mut input = BufReader::new(input);
+    let gzipped = input.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let lines: Box<dyn BufRead> = if gzipped {
+        Box::new(BufReader::new(MultiGzDecoder::new(input)))
+    } else {
+        Box::new(input)
+    };
+
+    let mut metadata: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    let mut samples: Vec<String> = Vec::new();
+    let mut variants: Vec<String> = Vec::new();
+    let mut names: HashMap<String, usize> = HashMap::new();
+    // Dosage per sample, per variant
+    let mut dosages: Vec<Vec<Option<String>>> = Vec::new();
+    for (n, line) in lines.lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if n == 0 && !line.starts_with("##fileformat=VCF") {
+            bail!("Not a VCF file: it must start with ##fileformat=VCF");
+        }
+        if let Some(meta) = line.strip_prefix("##SAMPLE=<").and_then(|m| m.strip_suffix('>')) {
+            let fields = meta_fields(meta);
+            if let Some((_, id)) = fields.iter().find(|(key, _)| key == "ID") {
+                metadata.insert(id.clone(), fields.into_iter().filter(|(key, _)| key != "ID").collect());
+            }
+            continue;
+        }
+        if line.starts_with("##") || line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix("#CHROM") {
+            samples = header.split('\t').skip(9).map(str::to_string).collect();
+            continue;
+        }
+        if samples.is_empty() {
+            bail!("Line {}: variant before the #CHROM header, or no sample columns", n + 1);
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 + samples.len() {
+            bail!("Line {}: expected {} columns, got {}", n + 1, 9 + samples.len(), fields.len());
+        }
+        let (chrom, pos, id, reference, alt, filter) = (fields[0], fields[1], fields[2], fields[3], fields[4], fields[6]);
+        if !matches!(filter, "PASS" | ".") {
+            continue;
+        }
+        let format: Vec<&str> = fields[8].split(':').collect();
+        let gt = format.iter().position(|f| *f == "GT");
+        let ds = format.iter().position(|f| *f == "DS");
+        if gt.is_none() && ds.is_none() {
+            continue;
+        }
+
+        let name = if id != "." {
+            id.to_string()
+        } else {
+            format!("{}_{}_{}_{}", chrom, pos, reference, alt.replace(',', "_"))
+        };
+        let count = names.entry(name.clone()).or_insert(0);
+        *count += 1;
+        variants.push(if *count > 1 { format!("{}_{}", name, count) } else { name });
+        dosages.push(
+            fields[9..9 + samples.len()]
+                .iter()
+                .map(|call| dosage(call, gt, ds))
+                .collect(),
+        );
+    }
+    if samples.is_empty() {
+        bail!("The VCF has no #CHROM header with sample columns");
+    }
+
+    // Covariate columns in the order of the first sample declaring each
+    let mut covariates: Vec<(String, String)> = Vec::new();
+    for sample in &samples {
+        for (key, _) in metadata.get(sample).into_iter().flatten() {
+            let column = key.to_ascii_lowercase();
+            if column != "sample" && !covariates.iter().any(|(c, _)| *c == column) {
+                covariates.push((column, key.clone()));
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(output);
+    writer
+        .write_record(
+            std::iter::once("sample")
+                .chain(covariates.iter().map(|(column, _)| column.as_str()))
+                .chain(variants.iter().map(String::as_str)),
+        )
+        .context("Failed to write the converted VCF")?;
+    let mut record: Vec<&str> = Vec::new();
+    for (s, sample) in samples.iter().enumerate() {
+        record.clear();
+        record.push(sample);
+        let fields = metadata.get(sample);
+        record.extend(covariates.iter().map(|(_, key)| {
+            fields
+                .and_then(|fields| fields.iter().find(|(k, _)| k == key))
+                .map_or("", |(_, v)| v.as_str())
+        }));
+        record.extend(dosages.iter().map(|variant| variant[s].as_deref().unwrap_or("")));
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    Ok(variants.len())
+}
+
+/// `ID=S1,Age=34,Description="a, b"` as key-value pairs
+fn meta_fields(meta: &str) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    for c in meta.chars().chain(std::iter::once(',')) {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                if let Some((key, value)) = field.split_once('=') {
+                    fields.push((key.trim().to_string(), value.trim().to_string()));
+                }
+                field.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Alternate allele dosage of one sample's call: `DS` when present and
+/// set, else the alternate alleles in `GT`; `None` when missing
+fn dosage(call: &str, gt: Option<usize>, ds: Option<usize>) -> Option<String> {
+    let values: Vec<&str> = call.split(':').collect();
+    if let Some(ds) = ds.and_then(|i| values.get(i)).filter(|v| v.parse::<f64>().is_ok()) {
+        return Some(ds.to_string());
+    }
+    let genotype = values.get(gt?)?;
+    let mut alternate = 0;
+    for allele in genotype.split(['/', '|']) {
+        match allele {
+            "0" => {}
+            "." | "" => return None,
+            _ => alternate += 1,
+        }
+    }
+    Some(alternate.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vcf_to_dosage_rows() {
+        let vcf = "##fileformat=VCFv4.3\n\
+            ##SAMPLE=<ID=S1,Age=34,Sex=F>\n\
+            ##SAMPLE=<ID=S2,Age=71,Sex=M,Description=\"donor, second visit\">\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tS1\tS2\tS3\n\
+            1\t100\trs1\tA\tG\t50\tPASS\t.\tGT\t0/1\t1|1\t./.\n\
+            1\t200\t.\tC\tT,G\t50\t.\t.\tGT:DS\t0/2:0.9\t0/0:.\t1/2:1.8\n\
+            2\t300\trs3\tG\tA\t10\tLowQual\t.\tGT\t1/1\t1/1\t1/1\n";
+        let mut csv = Vec::new();
+        assert_eq!(to_csv(vcf.as_bytes(), &mut csv).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "sample,age,sex,description,rs1,1_200_C_T_G\n\
+             S1,34,F,,1,0.9\n\
+             S2,71,M,\"donor, second visit\",2,0\n\
+             S3,,,,,1.8\n"
+        );
+
+        assert!(to_csv("ensembl_id,age\n".as_bytes(), Vec::new()).is_err());
+        assert!(is_vcf("cohort.vcf.gz") && is_vcf("Cohort.VCF") && !is_vcf("cohort.csv"));
+    }
+}
@@ -0,0 +1,238 @@
+//! Synthetic datasets
+//!
+//! `oxidized-bio demo-data` writes a wide log2 microarray-style matrix
+//! (samples as rows, Ensembl gene IDs as columns) with a few planted markers
+//! whose expression tracks age or cell type, so the analysis can be tried and
+//! tested without real data. Every other gene is baseline plus noise, and a
+//! given seed always produces the same file.
+
+use std::f64::consts::PI;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::annotation::Species;
+
+/// Cell types assigned to the samples in turn
+const CELL_TYPES: [&str; 4] = ["t_cell", "b_cell", "monocyte", "nk_cell"];
+
+/// Planted markers go among the first gene columns, which an analysis with
+/// the default `--max-columns` covers
+const PLANTED_WINDOW: usize = 50;
+
+/// Suffix of the file listing the planted markers
+const TRUTH_SUFFIX: &str = "_planted.csv";
+
+/// What the planted markers respond to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DemoEffect {
+    /// Linear trend with age
+    #[default]
+    Age,
+    /// Shift in one cell type
+    CellType,
+    /// No signal: every gene is noise
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct DemoOptions {
+    /// Samples
+    pub rows: usize,
+    /// Gene columns
+    pub markers: usize,
+    pub effect: DemoEffect,
+    /// Markers carrying the effect (at most the first 50 columns' worth)
+    pub planted: usize,
+    pub species: Species,
+    pub seed: u64,
+}
+
+impl Default for DemoOptions {
+    fn default() -> Self {
+        Self {
+            rows: 200,
+            markers: 500,
+            effect: DemoEffect::Age,
+            planted: 10,
+            species: Species::Human,
+            seed: 42,
+        }
+    }
+}
+
+/// A marker with a planted effect: log2 change per year of age, or the
+/// log2 shift of `cell_type`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlantedMarker {
+    pub column: String,
+    pub effect: DemoEffect,
+    pub size: f64,
+    pub cell_type: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DemoSample {
+    pub id: String,
+    pub age: u32,
+    pub cell_type: &'static str,
+    /// log2 expression of each gene column
+    pub values: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DemoDataset {
+    pub genes: Vec<String>,
+    pub samples: Vec<DemoSample>,
+    pub planted: Vec<PlantedMarker>,
+}
+
+/// Generate a dataset; deterministic for `options.seed`
+pub fn generate(options: &DemoOptions) -> Result<DemoDataset> {
+    if options.rows < 3 || options.markers == 0 {
+        bail!("Demo data needs at least 3 rows and 1 marker");
+    }
+    let mut rng = StdRng::seed_from_u64(options.seed);
+
+    // Spaced like real IDs rather than consecutive
+    let genes: Vec<String> = (0..options.markers)
+        .map(|i| format!("{}{:011}", options.species.ensembl_prefix(), 3 + i * 37))
+        .collect();
+
+    // Per-gene baseline intensity and noise, as on a log2 array
+    let baselines: Vec<(f64, f64)> = (0..options.markers)
+        .map(|_| (normal(&mut rng, 8.0, 1.5).clamp(3.0, 14.0), rng.gen_range(0.2..0.6)))
+        .collect();
+
+    let mut planted: Vec<PlantedMarker> = Vec::new();
+    let mut effects: Vec<Option<(f64, Option<&str>)>> = vec![None; options.markers];
+    if options.effect != DemoEffect::None {
+        let mut window: Vec<usize> = (0..options.markers.min(PLANTED_WINDOW)).collect();
+        window.shuffle(&mut rng);
+        window.truncate(options.planted);
+        window.sort_unstable();
+        for idx in window {
+            let sign = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+            let (size, cell_type) = match options.effect {
+                DemoEffect::CellType => (sign * rng.gen_range(1.0..2.0), CELL_TYPES.choose(&mut rng).copied()),
+                _ => (sign * rng.gen_range(0.02..0.05), None),
+            };
+            effects[idx] = Some((size, cell_type));
+            planted.push(PlantedMarker {
+                column: genes[idx].clone(),
+                effect: options.effect,
+                size,
+                cell_type: cell_type.map(str::to_string),
+            });
+        }
+    }
+
+    let samples = (0..options.rows)
+        .map(|i| {
+            let age = rng.gen_range(20..=90u32);
+            let cell_type = CELL_TYPES[i % CELL_TYPES.len()];
+            let values = baselines
+                .iter()
+                .zip(&effects)
+                .map(|(&(mean, sd), effect)| {
+                    let shift = match effect {
+                        Some((size, None)) => size * (age as f64 - 55.0),
+                        Some((size, Some(shifted))) if *shifted == cell_type => *size,
+                        _ => 0.0,
+                    };
+                    normal(&mut rng, mean + shift, sd)
+                })
+                .collect();
+            DemoSample {
+                id: format!("S{:04}", i + 1),
+                age,
+                cell_type,
+                values,
+            }
+        })
+        .collect();
+
+    Ok(DemoDataset { genes, samples, planted })
+}
+
+/// Write the matrix to `path` and the planted markers next to it
+/// (`<stem>_planted.csv`); returns the files written
+pub fn write(dataset: &DemoDataset, path: &Path) -> Result<Vec<PathBuf>> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut wtr = csv::Writer::from_path(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut header = vec!["sample_id".to_string(), "age".to_string(), "cell_type".to_string()];
+    header.extend(dataset.genes.iter().cloned());
+    wtr.write_record(&header)?;
+    for sample in &dataset.samples {
+        let mut row = vec![sample.id.clone(), sample.age.to_string(), sample.cell_type.to_string()];
+        row.extend(sample.values.iter().map(|v| format!("{:.3}", v)));
+        wtr.write_record(&row)?;
+    }
+    wtr.flush()?;
+
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("demo_data");
+    let truth = path.with_file_name(format!("{}{}", stem, TRUTH_SUFFIX));
+    let mut wtr = csv::Writer::from_path(&truth).with_context(|| format!("Failed to create {}", truth.display()))?;
+    for marker in &dataset.planted {
+        wtr.serialize(marker)?;
+    }
+    wtr.flush()?;
+    Ok(vec![path.to_path_buf(), truth])
+}
+
+/// Normal draw by Box-Muller
+fn normal(rng: &mut StdRng, mean: f64, sd: f64) -> f64 {
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen();
+    mean + sd * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{run_analysis, AnalysisConfig};
+    use crate::data_registry::DatasetRecord;
+    use crate::utils::cancel::CancellationToken;
+
+    #[test]
+    fn test_planted_age_markers_rank_first() {
+        let options = DemoOptions {
+            rows: 80,
+            markers: 60,
+            planted: 5,
+            ..Default::default()
+        };
+        let dataset = generate(&options).unwrap();
+        assert_eq!(dataset.planted.len(), 5);
+        assert_eq!(generate(&options).unwrap().samples[3].values, dataset.samples[3].values);
+
+        let dir = tempfile::tempdir().unwrap();
+        let files = write(&dataset, &dir.path().join("demo.csv")).unwrap();
+        assert!(files[1].ends_with("demo_planted.csv"));
+
+        let record = DatasetRecord::from_path(&files[0], None).unwrap();
+        let config = AnalysisConfig {
+            target_column: Some("age".to_string()),
+            group_column: Some("cell_type".to_string()),
+            ..Default::default()
+        };
+        let result = run_analysis(&record, &config, &dir.path().join("out"), &CancellationToken::new()).unwrap();
+        let top: Vec<&str> = result
+            .biomarker_candidates
+            .iter()
+            .map(|c| c.column.as_str())
+            .filter(|c| *c != "age")
+            .take(5)
+            .collect();
+        for marker in &dataset.planted {
+            assert!(top.contains(&marker.column.as_str()), "{} not in the top markers", marker.column);
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! Headless subcommands
+//!
+//! Batch entry points that run without the TUI so Oxidized Bio can be used as
+//! a step in Snakemake/Nextflow pipelines or CI. Logs go to stderr, results to
+//! the output directory and stdout; any failure is returned as an error so the
+//! process exits non-zero. Like worker mode, credentials come from the
+//! environment (or `.env`), not the TUI's saved settings.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use tracing::info;
+
+use crate::analysis::batch::BatchCorrection;
+use crate::analysis::formula::Formula;
+use crate::analysis::impute::Imputation;
+use crate::analysis::normalize::Normalization;
+use crate::analysis::memory::MemoryLimit;
+use crate::analysis::panel::{self, PanelOptions};
+use crate::analysis::qc::Replicates;
+use crate::analysis::regularized::Regularization;
+use crate::analysis::report::TableFormat;
+use crate::analysis::roc::RocSplit;
+use crate::analysis::stats::{CorrelationMethod, Thresholds};
+use crate::analysis::{legends, literature, report, run_analysis, AnalysisConfig};
+use crate::annotation::{Collapse, Species};
+use crate::config::Config;
+use crate::data_registry::{DatasetRecord, ParseOptions, UPLOAD_DIR};
+use crate::manuscript::JournalTemplate;
+use crate::search::serpapi::CombinedSearchResults;
+use crate::search::{bibtex, Engine, SearchAggregator};
+use crate::types::{AppError, AppResult};
+use crate::utils::cancel::CancellationToken;
+use crate::utils::number::NumberFormat;
+
+/// Options for `oxidized-bio analyze`
+#[derive(Debug, Clone)]
+pub struct AnalyzeOptions {
+    pub input: PathBuf,
+    pub target: String,
+    pub group: String,
+    pub covariates: Vec<String>,
+    /// Model formula; replaces `target` and `covariates` when set
+    pub formula: Option<Formula>,
+    pub boxplot: Option<String>,
+    /// Marker columns to analyse; empty analyses every numeric column
+    pub markers: Vec<String>,
+    pub out: PathBuf,
+    pub max_columns: usize,
+    pub max_groups: usize,
+    /// Platform annotation for probe-level data
+    pub platform: Option<PathBuf>,
+    /// Sample sheet whose columns are added to the dataset's samples
+    pub samples: Option<PathBuf>,
+    pub collapse: Collapse,
+    /// How technical replicate rows are combined
+    pub replicates: Replicates,
+    /// How missing marker values are filled in
+    pub imputation: Imputation,
+    /// Normalization steps, in order
+    pub normalization: Vec<Normalization>,
+    /// Column naming each sample's batch, to correct for
+    pub batch: Option<String>,
+    pub batch_correction: BatchCorrection,
+    pub correlation: CorrelationMethod,
+    pub tables: TableFormat,
+    pub numbers: NumberFormat,
+    pub thresholds: Thresholds,
+    pub kruskal_wallis: bool,
+    pub memory_limit: MemoryLimit,
+    /// Threads of the per-column statistics; 0 uses one per core
+    pub threads: usize,
+    /// Penalty of the multi-marker model
+    pub regularization: Regularization,
+    /// Split of the target for the candidates' ROC curves
+    pub roc: RocSplit,
+    /// GMT gene set files tested by GSEA against the ranked candidates
+    pub gene_sets: Vec<PathBuf>,
+    /// Time-to-event and event columns of the survival analysis
+    pub survival: Option<(String, String)>,
+    /// Column naming each sample's subject, for mixed models of repeated
+    /// samples
+    pub subject: Option<String>,
+    /// Decimal separator, missing-value tokens and quote character
+    pub parsing: ParseOptions,
+    /// Overrides the species detected from the gene IDs
+    pub species: Option<Species>,
+    /// Top candidates cross-referenced against the literature; 0 skips it
+    pub cross_reference: usize,
+    pub template: JournalTemplate,
+    /// Biomarker panel selection; `None` skips it
+    pub panel: Option<PanelOptions>,
+}
+
+/// Ingest `input`, run the analysis, cross-reference the top candidates
+/// against the literature and export the report (and the biomarker panel,
+/// when requested) to `out`.
+///
+/// Returns the files written.
+pub async fn analyze(config: &Config, options: &AnalyzeOptions) -> Result<Vec<PathBuf>> {
+    let input = expand_home(&options.input);
+    if !input.is_file() {
+        bail!("Input file not found: {}", input.display());
+    }
+    let mut record = DatasetRecord::from_path_with(&input, None, options.parsing.clone())?;
+    if let Some(samples) = &options.samples {
+        let samples = expand_home(samples);
+        if !samples.is_file() {
+            bail!("Sample sheet not found: {}", samples.display());
+        }
+        record = record.join_samples(&samples, Path::new(UPLOAD_DIR))?;
+    }
+    if let Some(species) = options.species {
+        record.set_species(species)?;
+    }
+    info!(
+        file = %input.display(),
+        rows = record.row_count,
+        columns = record.columns.len(),
+        species = %record.species,
+        "Dataset loaded"
+    );
+    if record.row_count == 0 {
+        bail!("{} has no data rows", input.display());
+    }
+    let platform = options.platform.as_deref().map(expand_home);
+    if let Some(platform) = &platform {
+        if !platform.is_file() {
+            bail!("Platform annotation not found: {}", platform.display());
+        }
+    }
+    let gene_sets: Vec<PathBuf> = options.gene_sets.iter().map(|p| expand_home(p)).collect();
+    if let Some(missing) = gene_sets.iter().find(|p| !p.is_file()) {
+        bail!("Gene set file not found: {}", missing.display());
+    }
+
+    let target = options.formula.as_ref().map_or(&options.target, |f| &f.response);
+    let required = [target, &options.group]
+        .into_iter()
+        .chain(&options.covariates)
+        .chain(options.formula.iter().flat_map(|f| &f.covariates))
+        .chain(&options.markers)
+        .chain(&options.boxplot)
+        .chain(options.survival.iter().flat_map(|(time, event)| [time, event]))
+        .chain(&options.subject);
+    let missing: Vec<&str> = required
+        .filter(|c| !record.columns.contains(*c))
+        .map(|c| c.as_str())
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "Column(s) not found in {}: {}\nAvailable: {}",
+            input.display(),
+            missing.join(", "),
+            record.columns.join(", ")
+        );
+    }
+
+    std::fs::create_dir_all(&options.out)
+        .with_context(|| format!("Failed to create output directory {}", options.out.display()))?;
+
+    let analysis_config = AnalysisConfig {
+        target_column: Some(target.clone()),
+        group_column: Some(options.group.clone()),
+        covariates: options.covariates.clone(),
+        boxplot_column: options.boxplot.clone(),
+        markers: options.markers.clone(),
+        max_columns: options.max_columns,
+        max_groups: options.max_groups,
+        platform,
+        collapse: options.collapse,
+        formula: options.formula.clone(),
+        replicates: options.replicates,
+        imputation: options.imputation,
+        normalization: options.normalization.clone(),
+        batch_column: options.batch.clone(),
+        batch_correction: options.batch_correction,
+        correlation: options.correlation,
+        tables: options.tables,
+        numbers: options.numbers,
+        thresholds: options.thresholds,
+        kruskal_wallis: options.kruskal_wallis,
+        memory_limit: options.memory_limit,
+        threads: options.threads,
+        regularization: options.regularization,
+        roc: options.roc,
+        gene_sets,
+        time_column: options.survival.as_ref().map(|(time, _)| time.clone()),
+        event_column: options.survival.as_ref().map(|(_, event)| event.clone()),
+        subject_column: options.subject.clone(),
+    };
+    let started = std::time::Instant::now();
+    let mut analysis = run_analysis(&record, &analysis_config, &options.out, &CancellationToken::new()).context("Analysis failed")?;
+    crate::metering::record_analysis(started, &record.dataset.id);
+    info!(elapsed_ms = started.elapsed().as_millis() as u64, "Analysis finished");
+    literature::cross_reference(config, &mut analysis, options.cross_reference).await;
+    legends::polish(config, &mut analysis).await;
+    if let (Some(panel_options), Some(manifest)) = (&options.panel, &mut analysis.manifest) {
+        manifest.seeds.insert("panel_folds", panel_options.seed);
+    }
+
+    let mut files = report::write_report(&options.out, &record, &analysis_config, &analysis, options.template)
+        .context("Report export failed")?;
+    if let Some(panel_options) = &options.panel {
+        let panel = panel::optimize(&record, &options.out, &analysis, target, panel_options)
+            .context("Panel selection failed")?;
+        info!(markers = panel.markers.len(), "{}", panel.summary());
+        files.extend(panel::write_panel(&options.out, &panel)?);
+    }
+    info!(out = %options.out.display(), files = files.len(), "Report written");
+    Ok(files)
+}
+
+/// Output format of `oxidized-bio search`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SearchFormat {
+    Json,
+    Bibtex,
+}
+
+/// Search `engines` (default: every enabled engine) through the same
+/// aggregator the literature agent uses.
+///
+/// Fails if nothing was found and at least one engine reported an error, so
+/// an outage is not mistaken for an empty result set.
+pub async fn search(config: &Config, query: &str, engines: Option<Vec<Engine>>) -> AppResult<CombinedSearchResults> {
+    let engines = match engines {
+        Some(engines) => engines,
+        None => Engine::enabled(&config.search),
+    };
+    if engines.is_empty() {
+        return Err(AppError::Config(
+            "No search engines available; set SERPAPI_API_KEY or enable PubMed/Semantic Scholar".to_string(),
+        ));
+    }
+    info!(query, engines = ?engines, "Searching");
+
+    let results = SearchAggregator::new(config).search(query, &engines).await;
+    let empty = results.scholar_results.is_empty() && results.light_results.is_empty();
+    if empty && !results.errors.is_empty() {
+        return Err(AppError::Network(format!("Search failed: {}", results.errors.join("; "))));
+    }
+    for error in &results.errors {
+        tracing::warn!("{}", error);
+    }
+    Ok(results)
+}
+
+pub fn format_search_results(results: &CombinedSearchResults, format: SearchFormat) -> Result<String> {
+    Ok(match format {
+        SearchFormat::Json => serde_json::to_string_pretty(results)?,
+        SearchFormat::Bibtex => bibtex::bibliography(results),
+    })
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir().map(|home| home.join(rest)).unwrap_or_else(|| path.to_path_buf()),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(input: PathBuf, out: PathBuf) -> AnalyzeOptions {
+        AnalyzeOptions {
+            input,
+            target: "age".to_string(),
+            group: "cell_type".to_string(),
+            covariates: Vec::new(),
+            formula: None,
+            boxplot: None,
+            markers: Vec::new(),
+            out,
+            max_columns: 50,
+            max_groups: 20,
+            platform: None,
+            samples: None,
+            collapse: Collapse::Max,
+            replicates: Replicates::Mean,
+            imputation: Imputation::Off,
+            normalization: Vec::new(),
+            batch: None,
+            batch_correction: BatchCorrection::Combat,
+            correlation: CorrelationMethod::Pearson,
+            tables: TableFormat::Csv,
+            numbers: NumberFormat::Plain,
+            thresholds: Default::default(),
+            kruskal_wallis: false,
+            memory_limit: MemoryLimit::Off,
+            threads: 0,
+            regularization: Regularization::default(),
+            roc: RocSplit::default(),
+            gene_sets: Vec::new(),
+            survival: None,
+            subject: None,
+            parsing: Default::default(),
+            species: None,
+            cross_reference: 0,
+            template: JournalTemplate::Generic,
+            panel: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_analyze_writes_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        let mut csv = String::from("age,cell_type,gene_a,gene_b\n");
+        for i in 0..12 {
+            let group = if i % 2 == 0 { "t_cell" } else { "b_cell" };
+            csv.push_str(&format!("{},{},{},{}\n", 20 + i * 3, group, i as f64 * 0.5, 10.0 - i as f64));
+        }
+        std::fs::write(&input, csv).unwrap();
+
+        let out = dir.path().join("report");
+        let options = AnalyzeOptions {
+            panel: Some(PanelOptions::default()),
+            ..options(input, out.clone())
+        };
+        let files = analyze(&Config::from_env().unwrap(), &options).await.unwrap();
+        assert!(out.join("manuscript.md").is_file());
+        assert!(out.join("biomarker_candidates.csv").is_file());
+        assert!(files.contains(&out.join("summary.json")));
+        // Both markers, each cell type against the other
+        let comparisons = std::fs::read_to_string(out.join(report::GROUP_COMPARISONS_FILE)).unwrap();
+        assert_eq!(comparisons.lines().count(), 5);
+        assert!(comparisons.lines().nth(1).unwrap().starts_with("gene_a,b_cell,6,3,1.87"));
+        // Two cell types, so each marker is also contrasted between them
+        let contrast = std::fs::read_to_string(out.join(report::GROUP_CONTRAST_FILE)).unwrap();
+        assert_eq!(contrast.lines().count(), 3);
+        assert!(contrast.starts_with("column,label,n_b_cell,mean_b_cell,sd_b_cell,n_"), "{}", contrast);
+        // gene_a and gene_b are both exact linear functions of age
+        let panel: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(out.join(panel::PANEL_JSON)).unwrap()).unwrap();
+        assert_eq!(panel["markers"].as_array().unwrap().len(), 1);
+
+        let bundle = std::fs::File::open(out.join("supplementary.zip")).unwrap();
+        let names: Vec<String> = zip::ZipArchive::new(bundle).unwrap().file_names().map(str::to_string).collect();
+        for name in ["biomarker_candidates.csv", "analysis.json", "reproduce.sh", "README.txt"] {
+            assert!(names.iter().any(|n| n == name), "{} missing from bundle", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_analyze_rejects_missing_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("data.csv");
+        std::fs::write(&input, "age,gene_a\n30,1.0\n40,2.0\n").unwrap();
+        let err = analyze(&Config::from_env().unwrap(), &options(input, dir.path().join("out")))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("cell_type"));
+    }
+}
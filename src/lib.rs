@@ -1,4 +1,6 @@
 // Oxidized Bio - High-performance AI agent framework for biological research
+// The analysis summary's json! outgrows the default macro recursion limit
+#![recursion_limit = "256"]
 
 pub mod config;
 pub mod db;
@@ -16,9 +18,18 @@ pub mod payment;
 pub mod utils;
 pub mod rfc;       // Remote Function Call system for Docker container access
 pub mod settings;  // User settings and API key management
+pub mod budget;    // LLM spend and SerpAPI quota tracking
+pub mod metering;  // Per-account usage events and billing records
 pub mod tui;       // Terminal User Interface
 pub mod data_registry;
+pub mod session;   // Event bus shared by the TUI and the embedded server (--serve)
 pub mod analysis;
+pub mod annotation;  // Gene annotation (Ensembl IDs to symbols)
+pub mod headless;  // Batch subcommands (analyze, search) for pipelines and CI
+pub mod pipeline;  // Workflow-from-YAML batch runs
+pub mod manuscript;
+pub mod project;   // Projects grouping a study's datasets, analyses and drafts
+pub mod demo;      // Synthetic datasets (demo-data subcommand)
 
 // Re-exports for convenience
 pub use config::Config;
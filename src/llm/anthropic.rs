@@ -1,39 +1,333 @@
-// Anthropic adapter stub
-// TODO: Implement full Anthropic adapter
+//! Anthropic adapter
+//!
+//! Client for the Messages API. The system prompt is sent as text blocks,
+//! with `cache_control` on the [`SystemBlock`]s marked `cache`: Anthropic then
+//! caches the prompt up to that block for five minutes, and successive
+//! requests sharing it (e.g. replies over the same literature context) pay a
+//! tenth of the input price for the cached part.
 
 use crate::llm::provider::LLMAdapter;
-use crate::types::{AppResult, LLMRequest, LLMResponse, AppError};
+use crate::types::{AppError, AppResult, ContentPart, LLMRequest, LLMResponse, MessageContent, TokenUsage};
+use crate::utils::retry::{RetryError, RetryPolicy};
 use async_trait::async_trait;
-use futures::stream::BoxStream;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// The Messages API requires a limit; used when the request sets none
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Cache breakpoints Anthropic accepts per request
+const MAX_CACHE_BREAKPOINTS: usize = 4;
+
+#[derive(Debug, Error)]
+enum AnthropicError {
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("HTTP {status}: {message}")]
+    Http { status: u16, message: String },
+}
+
+impl AnthropicError {
+    /// Network errors, rate limiting, server errors and overload (529)
+    fn is_transient(&self) -> bool {
+        match self {
+            Self::Request(e) => e.status().is_none_or(|s| s.is_server_error()),
+            Self::Http { status, .. } => *status == 429 || *status >= 500,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    #[serde(default)]
+    content: Vec<ResponseBlock>,
+    stop_reason: Option<String>,
+    usage: Usage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Usage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+    #[serde(default)]
+    cache_creation_input_tokens: u32,
+    #[serde(default)]
+    cache_read_input_tokens: u32,
+}
+
+impl From<Usage> for TokenUsage {
+    fn from(usage: Usage) -> Self {
+        Self {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            total_tokens: usage.input_tokens
+                + usage.output_tokens
+                + usage.cache_creation_input_tokens
+                + usage.cache_read_input_tokens,
+            cache_write_tokens: usage.cache_creation_input_tokens,
+            cache_read_tokens: usage.cache_read_input_tokens,
+        }
+    }
+}
 
 pub struct AnthropicAdapter {
     api_key: String,
+    http: reqwest::Client,
+    retry: RetryPolicy,
 }
 
 impl AnthropicAdapter {
     pub fn new(api_key: &str) -> Self {
         Self {
             api_key: api_key.to_string(),
+            http: reqwest::Client::new(),
+            retry: RetryPolicy::for_target("anthropic"),
+        }
+    }
+
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Messages API body of `request`. System messages join the system
+    /// prompt; when more cached blocks are marked than Anthropic accepts, the
+    /// last ones (the longest prefixes) keep their breakpoint.
+    fn request_body(request: &LLMRequest, stream: bool) -> Value {
+        let cached = request.system_blocks.iter().filter(|b| b.cache).count();
+        let mut skip_breakpoints = cached.saturating_sub(MAX_CACHE_BREAKPOINTS);
+
+        let system_messages = request
+            .messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .filter_map(|m| m.content.as_text());
+        let mut system: Vec<Value> = request
+            .system_instruction
+            .as_deref()
+            .into_iter()
+            .chain(system_messages)
+            .map(|text| json!({"type": "text", "text": text}))
+            .collect();
+        for block in &request.system_blocks {
+            let mut value = json!({"type": "text", "text": block.text});
+            if block.cache {
+                if skip_breakpoints > 0 {
+                    skip_breakpoints -= 1;
+                } else {
+                    value["cache_control"] = json!({"type": "ephemeral"});
+                }
+            }
+            system.push(value);
+        }
+        system.retain(|block| block["text"].as_str().is_some_and(|t| !t.is_empty()));
+
+        let messages: Vec<Value> = request
+            .messages
+            .iter()
+            .filter(|m| m.role != "system")
+            .map(|m| json!({"role": m.role, "content": Self::content_blocks(&m.content)}))
+            .collect();
+
+        let mut body = json!({
+            "model": request.model,
+            "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            "messages": messages,
+        });
+        if !system.is_empty() {
+            body["system"] = Value::Array(system);
+        }
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if stream {
+            body["stream"] = json!(true);
+        }
+        body
+    }
+
+    fn content_blocks(content: &MessageContent) -> Vec<Value> {
+        let parts = match content {
+            MessageContent::Text(text) => return vec![json!({"type": "text", "text": text})],
+            MessageContent::Multimodal(parts) => parts,
+        };
+        parts
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text { text } => json!({"type": "text", "text": text}),
+                ContentPart::ImageUrl { url, .. } => {
+                    // Data URLs carry the image inline
+                    let inline = url
+                        .strip_prefix("data:")
+                        .and_then(|rest| rest.split_once(";base64,"));
+                    let source = match inline {
+                        Some((media_type, data)) => json!({"type": "base64", "media_type": media_type, "data": data}),
+                        None => json!({"type": "url", "url": url}),
+                    };
+                    json!({"type": "image", "source": source})
+                }
+                ContentPart::ImageBase64 { base64, media_type, .. } => json!({
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": media_type, "data": base64},
+                }),
+            })
+            .collect()
+    }
+
+    /// POST `body` to the Messages API, failing on error statuses
+    async fn send(&self, body: &Value) -> Result<reqwest::Response, AnthropicError> {
+        let response = self
+            .http
+            .post(format!("{}/messages", ANTHROPIC_API_BASE))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(body)
+            .send()
+            .await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let text = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|v| v["error"]["message"].as_str().map(str::to_string))
+            .unwrap_or(text);
+        Err(AnthropicError::Http { status: status.as_u16(), message })
+    }
+
+    fn api_error(error: RetryError<AnthropicError>) -> AppError {
+        let retryable = error.is_retryable(AnthropicError::is_transient);
+        AppError::provider(format!("Anthropic API error: {}", error), retryable)
+    }
+
+    /// Text of one server-sent event: `Some` for text deltas and errors
+    fn stream_event(event: &str) -> Option<AppResult<String>> {
+        let data = event.lines().find_map(|line| line.strip_prefix("data:"))?;
+        let value: Value = serde_json::from_str(data.trim()).ok()?;
+        match value["type"].as_str()? {
+            "content_block_delta" if value["delta"]["type"] == "text_delta" => {
+                value["delta"]["text"].as_str().map(|text| Ok(text.to_string()))
+            }
+            "error" => Some(Err(AppError::provider(
+                format!("Anthropic stream error: {}", value["error"]["message"].as_str().unwrap_or("unknown error")),
+                value["error"]["type"] == "overloaded_error",
+            ))),
+            _ => None,
         }
     }
 }
 
 #[async_trait]
 impl LLMAdapter for AnthropicAdapter {
-    async fn create_chat_completion(&self, _request: &LLMRequest) -> AppResult<LLMResponse> {
-        // Placeholder implementation
+    async fn create_chat_completion(&self, request: &LLMRequest) -> AppResult<LLMResponse> {
+        let body = Self::request_body(request, false);
+        let response: MessagesResponse = self
+            .retry
+            .run(|| async { Ok(self.send(&body).await?.json().await?) }, AnthropicError::is_transient)
+            .await
+            .map_err(Self::api_error)?;
+
+        let content = response
+            .content
+            .iter()
+            .filter(|block| block.kind == "text")
+            .map(|block| block.text.as_str())
+            .collect();
         Ok(LLMResponse {
-            content: String::new(),
-            finish_reason: "stop".to_string(),
-            usage: crate::types::TokenUsage {
-                prompt_tokens: 0,
-                completion_tokens: 0,
-                total_tokens: 0,
-            },
+            content,
+            finish_reason: response.stop_reason.unwrap_or_else(|| "unknown".to_string()),
+            usage: response.usage.into(),
         })
     }
 
-    async fn create_chat_completion_stream(&self, _request: &LLMRequest) -> AppResult<BoxStream<'static, AppResult<String>>> {
-        Err(AppError::LLMApi("Streaming not supported for Anthropic adapter".to_string()))
+    async fn create_chat_completion_stream(&self, request: &LLMRequest) -> AppResult<BoxStream<'static, AppResult<String>>> {
+        let body = Self::request_body(request, true);
+        let response = self
+            .retry
+            .run(|| self.send(&body), AnthropicError::is_transient)
+            .await
+            .map_err(Self::api_error)?;
+
+        // Events end with a blank line; chunks may split them, and UTF-8
+        // characters, anywhere
+        let mut buffer: Vec<u8> = Vec::new();
+        let events = response.bytes_stream().flat_map(move |chunk| {
+            let bytes = match chunk {
+                Ok(bytes) => bytes,
+                Err(e) => return stream::iter(vec![Err(AppError::provider(format!("Anthropic stream error: {}", e), true))]),
+            };
+            buffer.extend_from_slice(&bytes);
+            let mut texts = Vec::new();
+            while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+                let event: Vec<u8> = buffer.drain(..end + 2).collect();
+                texts.extend(Self::stream_event(&String::from_utf8_lossy(&event)));
+            }
+            stream::iter(texts)
+        });
+        Ok(Box::pin(events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{LLMMessage, SystemBlock};
+
+    #[test]
+    fn test_request_body() {
+        let request = LLMRequest {
+            provider: "anthropic".to_string(),
+            model: "claude-sonnet-4-20250514".to_string(),
+            messages: vec![LLMMessage::user_with_base64_image("What is shown?", "aGk=", "image/png")],
+            max_tokens: None,
+            temperature: Some(0.5),
+            system_instruction: Some("You are a research assistant.".to_string()),
+            system_blocks: vec![SystemBlock::cached("RESEARCH FINDINGS:\n...")],
+        };
+        assert_eq!(
+            AnthropicAdapter::request_body(&request, true),
+            json!({
+                "model": "claude-sonnet-4-20250514",
+                "max_tokens": DEFAULT_MAX_TOKENS,
+                "temperature": 0.5,
+                "stream": true,
+                "system": [
+                    {"type": "text", "text": "You are a research assistant."},
+                    {"type": "text", "text": "RESEARCH FINDINGS:\n...", "cache_control": {"type": "ephemeral"}},
+                ],
+                "messages": [{"role": "user", "content": [
+                    {"type": "text", "text": "What is shown?"},
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "aGk="}},
+                ]}],
+            })
+        );
+
+        let usage: Usage = serde_json::from_value(json!({
+            "input_tokens": 20, "output_tokens": 100, "cache_creation_input_tokens": 0, "cache_read_input_tokens": 5000,
+        }))
+        .unwrap();
+        let usage = TokenUsage::from(usage);
+        assert_eq!((usage.prompt_tokens, usage.cache_read_tokens, usage.total_tokens), (20, 5000, 5120));
+
+        let delta = "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n";
+        assert_eq!(AnthropicAdapter::stream_event(delta).unwrap().unwrap(), "Hi");
+        assert!(AnthropicAdapter::stream_event("event: ping\ndata: {\"type\": \"ping\"}\n\n").is_none());
     }
 }
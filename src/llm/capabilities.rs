@@ -0,0 +1,328 @@
+//! Model capability table
+//!
+//! Context length, output limit, default output budget, vision and tool
+//! support per provider and model. Agents size `max_tokens` and trim their
+//! prompts from it instead of special-casing providers. A compiled-in table
+//! covers the models offered in settings (matched by longest model-ID
+//! prefix, then per-provider defaults); providers whose model APIs report
+//! limits (OpenRouter, Groq, Google) can be refreshed, and the refreshed
+//! entries are cached in the local data directory and take precedence.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::config::NetworkConfig;
+
+/// Rough size of a token, as for streamed usage estimates
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Appended to prompts cut to fit the context window
+const TRUNCATION_NOTE: &str = "\n\n[Truncated to fit the model's context window]";
+
+/// Limits fetched from provider APIs, by provider and model ID
+type RefreshedTable = HashMap<(String, String), ModelCapabilities>;
+
+static REFRESHED: RwLock<Option<Arc<RefreshedTable>>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelCapabilities {
+    /// Input plus output tokens
+    pub context_length: u32,
+    /// Most tokens one completion may produce
+    pub max_output: u32,
+    /// Output budget agents request when their task sets none
+    pub default_output: u32,
+    pub vision: bool,
+    /// Function/tool calling (or built-in tools, for Groq Compound)
+    pub tools: bool,
+}
+
+const fn caps(context_length: u32, max_output: u32, default_output: u32, vision: bool, tools: bool) -> ModelCapabilities {
+    ModelCapabilities {
+        context_length,
+        max_output,
+        default_output,
+        vision,
+        tools,
+    }
+}
+
+/// Used for providers and models the table does not know
+pub const FALLBACK: ModelCapabilities = caps(8_192, 4_096, 2_048, false, false);
+
+/// `(provider, model ID prefix, capabilities)`; an empty prefix is the
+/// provider's default
+const BUILTIN: &[(&str, &str, ModelCapabilities)] = &[
+    ("openai", "", caps(128_000, 16_384, 2_048, true, true)),
+    ("openai", "gpt-4o", caps(128_000, 16_384, 2_048, true, true)),
+    ("openai", "gpt-4-turbo", caps(128_000, 4_096, 2_048, true, true)),
+    ("openai", "gpt-4", caps(8_192, 8_192, 2_048, false, true)),
+    ("openai", "o1", caps(200_000, 100_000, 2_048, false, true)),
+    ("openai", "o1-mini", caps(128_000, 65_536, 2_048, false, false)),
+    ("anthropic", "", caps(200_000, 8_192, 2_048, true, true)),
+    ("anthropic", "claude-sonnet-4", caps(200_000, 64_000, 2_048, true, true)),
+    ("anthropic", "claude-3-5-sonnet", caps(200_000, 8_192, 2_048, true, true)),
+    ("anthropic", "claude-3-opus", caps(200_000, 4_096, 2_048, true, true)),
+    ("anthropic", "claude-3-haiku", caps(200_000, 4_096, 2_048, true, true)),
+    ("google", "", caps(1_000_000, 8_192, 2_048, true, true)),
+    ("google", "gemini-1.5-pro", caps(2_000_000, 8_192, 2_048, true, true)),
+    // Compound runs web search and code tools server-side, so its answers
+    // (and those of the other Groq models) get a larger default budget
+    ("groq", "", caps(128_000, 8_192, 4_096, false, true)),
+    ("groq", "groq/compound", caps(131_072, 8_192, 4_096, false, true)),
+    ("groq", "llama-3.3-70b-versatile", caps(128_000, 32_768, 4_096, false, true)),
+    ("groq", "llama-3.1-70b-versatile", caps(128_000, 8_192, 4_096, false, true)),
+    ("groq", "llama-3.1-8b-instant", caps(128_000, 8_192, 4_096, false, true)),
+    ("groq", "mixtral-8x7b-32768", caps(32_768, 32_768, 4_096, false, true)),
+    ("groq", "gemma2-9b-it", caps(8_192, 8_192, 4_096, false, false)),
+    ("mock", "", caps(1_000_000, 100_000, 2_048, true, true)),
+];
+
+impl ModelCapabilities {
+    /// `requested` tokens, or the model's default budget, within its output limit
+    pub fn output_tokens(&self, requested: Option<u32>) -> u32 {
+        requested.unwrap_or(self.default_output).min(self.max_output)
+    }
+
+    /// Characters of prompt that fit next to `output_tokens` of completion
+    pub fn prompt_chars(&self, output_tokens: u32) -> usize {
+        self.context_length.saturating_sub(output_tokens) as usize * CHARS_PER_TOKEN
+    }
+
+    pub fn fits(&self, prompt: &str, output_tokens: u32) -> bool {
+        prompt.len() <= self.prompt_chars(output_tokens)
+    }
+
+    /// `prompt`, cut at the end (on a character boundary) when it would not
+    /// fit the context window next to `output_tokens`
+    pub fn fit_prompt<'a>(&self, prompt: &'a str, output_tokens: u32) -> Cow<'a, str> {
+        if self.fits(prompt, output_tokens) {
+            return Cow::Borrowed(prompt);
+        }
+        let budget = self.prompt_chars(output_tokens).saturating_sub(TRUNCATION_NOTE.len());
+        let mut end = budget.min(prompt.len());
+        while !prompt.is_char_boundary(end) {
+            end -= 1;
+        }
+        warn!(chars = prompt.len(), kept = end, "Prompt truncated to fit the context window");
+        Cow::Owned(format!("{}{}", &prompt[..end], TRUNCATION_NOTE))
+    }
+}
+
+/// Capabilities of `model` on `provider`: a refreshed entry, else the
+/// longest matching prefix of the built-in table, else the provider's
+/// default. OpenRouter models (`vendor/model`) fall back to the vendor's row.
+pub fn lookup(provider: &str, model: &str) -> ModelCapabilities {
+    match refreshed().get(&(provider.to_string(), model.to_string())) {
+        Some(found) => *found,
+        None => table_lookup(provider, model),
+    }
+}
+
+/// [`lookup`] in the built-in table only
+fn table_lookup(provider: &str, model: &str) -> ModelCapabilities {
+    if let Some(found) = builtin(provider, model) {
+        return found;
+    }
+    if provider == "openrouter" {
+        if let Some((vendor, model)) = model.split_once('/') {
+            if let Some(found) = builtin(vendor, model) {
+                return found;
+            }
+        }
+    }
+    BUILTIN
+        .iter()
+        .find(|(p, prefix, _)| *p == provider && prefix.is_empty())
+        .map_or(FALLBACK, |(_, _, caps)| *caps)
+}
+
+fn builtin(provider: &str, model: &str) -> Option<ModelCapabilities> {
+    BUILTIN
+        .iter()
+        .filter(|(p, prefix, _)| *p == provider && !prefix.is_empty() && model.starts_with(prefix))
+        .max_by_key(|(_, prefix, _)| prefix.len())
+        .map(|(_, _, caps)| *caps)
+}
+
+/// `<data dir>/oxidized-bio/model_capabilities.json`
+pub fn cache_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("oxidized-bio").join("model_capabilities.json"))
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedModel {
+    provider: String,
+    model: String,
+    #[serde(flatten)]
+    capabilities: ModelCapabilities,
+}
+
+/// Refreshed entries, loaded from the cache on first use
+fn refreshed() -> Arc<RefreshedTable> {
+    if let Some(map) = REFRESHED.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return map.clone();
+    }
+    let cached: Vec<CachedModel> = cache_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    let map = Arc::new(
+        cached
+            .into_iter()
+            .map(|m| ((m.provider, m.model), m.capabilities))
+            .collect::<HashMap<_, _>>(),
+    );
+    *REFRESHED.write().unwrap_or_else(|e| e.into_inner()) = Some(map.clone());
+    map
+}
+
+/// Fetch the model list of `provider` and store the limits it reports;
+/// returns the number of models updated
+pub async fn refresh(provider: &str, api_key: &str, network: &NetworkConfig) -> Result<usize> {
+    let client = crate::utils::http::client(network, provider);
+    let request = match provider {
+        "openrouter" => client.get("https://openrouter.ai/api/v1/models").bearer_auth(api_key),
+        "groq" => client.get("https://api.groq.com/openai/v1/models").bearer_auth(api_key),
+        "google" => client
+            .get("https://generativelanguage.googleapis.com/v1beta/models")
+            .query(&[("key", api_key), ("pageSize", "1000")]),
+        other => bail!("{} does not report model limits; using the built-in table", other),
+    };
+    let response = request.send().await.context("Model list request failed")?;
+    if !response.status().is_success() {
+        bail!("Model list request returned {}", response.status());
+    }
+    let body: Value = response.json().await.context("Malformed model list")?;
+    let models = parse_models(provider, &body);
+    if models.is_empty() {
+        bail!("No model limits in the {} model list", provider);
+    }
+
+    let mut map = (*refreshed()).clone();
+    map.retain(|(p, _), _| p != provider);
+    let count = models.len();
+    map.extend(models.into_iter().map(|(model, caps)| ((provider.to_string(), model), caps)));
+    if let Some(path) = cache_path() {
+        let cached: Vec<CachedModel> = map
+            .iter()
+            .map(|((provider, model), caps)| CachedModel {
+                provider: provider.clone(),
+                model: model.clone(),
+                capabilities: *caps,
+            })
+            .collect();
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, serde_json::to_string(&cached).unwrap_or_default()));
+        if let Err(e) = written {
+            warn!(error = %e, "Failed to cache model capabilities");
+        }
+    }
+    *REFRESHED.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(map));
+    info!(provider, models = count, "Model capabilities refreshed");
+    Ok(count)
+}
+
+/// Models and limits from a provider's model list; values a provider does
+/// not report come from the built-in table
+fn parse_models(provider: &str, body: &Value) -> Vec<(String, ModelCapabilities)> {
+    let number = |v: &Value| v.as_u64().map(|n| n.min(u32::MAX as u64) as u32);
+    let (list, id_key) = match provider {
+        "google" => (&body["models"], "name"),
+        _ => (&body["data"], "id"),
+    };
+    let Some(list) = list.as_array() else {
+        return Vec::new();
+    };
+    list.iter()
+        .filter_map(|model| {
+            let id = model[id_key].as_str()?;
+            let id = id.strip_prefix("models/").unwrap_or(id).to_string();
+            let mut caps = table_lookup(provider, &id);
+            match provider {
+                "openrouter" => {
+                    caps.context_length = number(&model["context_length"])?;
+                    caps.max_output = number(&model["top_provider"]["max_completion_tokens"]).unwrap_or(caps.context_length);
+                    let lists = |list: &Value, value: &str| list.as_array().is_some_and(|a| a.iter().any(|v| v == value));
+                    caps.vision = lists(&model["architecture"]["input_modalities"], "image");
+                    caps.tools = lists(&model["supported_parameters"], "tools");
+                }
+                "groq" => {
+                    caps.context_length = number(&model["context_window"])?;
+                    caps.max_output = number(&model["max_completion_tokens"]).unwrap_or(caps.max_output);
+                }
+                "google" => {
+                    caps.context_length = number(&model["inputTokenLimit"])?;
+                    caps.max_output = number(&model["outputTokenLimit"]).unwrap_or(caps.max_output);
+                }
+                _ => return None,
+            }
+            caps.max_output = caps.max_output.min(caps.context_length);
+            Some((id, caps))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_lookup_prefers_longest_prefix_then_provider() {
+        assert_eq!(table_lookup("openai", "gpt-4o-mini").max_output, 16_384);
+        assert_eq!(table_lookup("openai", "gpt-4").context_length, 8_192);
+        assert_eq!(table_lookup("groq", "groq/compound-mini").default_output, 4_096);
+        assert_eq!(table_lookup("groq", "qwen-unknown"), table_lookup("groq", ""));
+        assert_eq!(table_lookup("openrouter", "anthropic/claude-3-haiku"), table_lookup("anthropic", "claude-3-haiku-20240307"));
+        assert_eq!(table_lookup("nobody", "model"), FALLBACK);
+    }
+
+    #[test]
+    fn test_output_and_prompt_budget() {
+        let small = table_lookup("groq", "gemma2-9b-it");
+        assert_eq!(small.output_tokens(None), 4_096);
+        assert_eq!(small.output_tokens(Some(20_000)), 8_192);
+
+        let prompt = "é".repeat(20_000);
+        let fitted = small.fit_prompt(&prompt, 4_096);
+        assert!(fitted.len() <= small.prompt_chars(4_096));
+        assert!(fitted.ends_with(TRUNCATION_NOTE));
+        assert!(matches!(small.fit_prompt("short", 4_096), Cow::Borrowed("short")));
+    }
+
+    #[test]
+    fn test_parse_provider_model_lists() {
+        let groq = json!({"data": [
+            {"id": "llama-3.3-70b-versatile", "context_window": 131072, "max_completion_tokens": 32768},
+            {"id": "whisper-large-v3"}
+        ]});
+        let models = parse_models("groq", &groq);
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].1.context_length, 131_072);
+        assert_eq!(models[0].1.default_output, 4_096);
+
+        let openrouter = json!({"data": [{
+            "id": "openai/gpt-4o",
+            "context_length": 128000,
+            "top_provider": {"max_completion_tokens": 16384},
+            "architecture": {"input_modalities": ["text", "image"]},
+            "supported_parameters": ["tools", "temperature"]
+        }]});
+        let (_, caps) = &parse_models("openrouter", &openrouter)[0];
+        assert!(caps.vision && caps.tools);
+        assert_eq!(caps.max_output, 16_384);
+
+        let google = json!({"models": [{"name": "models/gemini-2.0-flash", "inputTokenLimit": 1048576, "outputTokenLimit": 8192}]});
+        assert_eq!(parse_models("google", &google)[0].0, "gemini-2.0-flash");
+    }
+}
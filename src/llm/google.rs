@@ -25,15 +25,11 @@ impl LLMAdapter for GoogleAdapter {
         Ok(LLMResponse {
             content: String::new(),
             finish_reason: "STOP".to_string(),
-            usage: crate::types::TokenUsage {
-                prompt_tokens: 0,
-                completion_tokens: 0,
-                total_tokens: 0,
-            },
+            usage: crate::types::TokenUsage::default(),
         })
     }
 
     async fn create_chat_completion_stream(&self, _request: &LLMRequest) -> AppResult<BoxStream<'static, AppResult<String>>> {
-        Err(AppError::LLMApi("Streaming not supported for Google adapter".to_string()))
+        Err(AppError::provider("Streaming not supported for Google adapter", false))
     }
 }
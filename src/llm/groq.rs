@@ -12,9 +12,15 @@ pub struct GroqAdapter {
 impl GroqAdapter {
     pub fn new(api_key: &str) -> Self {
         Self {
-            inner: crate::llm::openai::OpenAIAdapter::new_with_api_base(api_key, GROQ_API_BASE),
+            inner: crate::llm::openai::OpenAIAdapter::new_with_api_base(api_key, GROQ_API_BASE)
+                .with_retry(crate::utils::retry::RetryPolicy::for_target("groq")),
         }
     }
+
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.inner = self.inner.with_http_client(http);
+        self
+    }
 }
 
 #[async_trait]
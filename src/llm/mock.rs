@@ -0,0 +1,134 @@
+//! Offline LLM adapter for tests and demos
+//!
+//! Selected with the `mock` provider (e.g. `REPLY_LLM_PROVIDER=mock`, no API
+//! key needed). Replies are derived from the prompt only, so a workflow run
+//! against it is deterministic and needs no network: prompts asking for a
+//! research plan, search queries or literature JSON get well-formed canned
+//! JSON, draft revisions get the draft back unchanged, anything else a short
+//! acknowledgement.
+
+use crate::llm::provider::LLMAdapter;
+use crate::types::{AppResult, LLMRequest, LLMResponse, TokenUsage};
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde_json::json;
+
+/// Marker of the drafting agent's prompt; the draft follows it
+const DRAFT_MARKER: &str = "\n\nDraft:\n";
+
+#[derive(Debug, Clone, Default)]
+pub struct MockLLMAdapter;
+
+impl MockLLMAdapter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Canned reply to `prompt`
+    pub fn reply(prompt: &str) -> String {
+        if prompt.contains("\"currentObjective\"") {
+            let topic = section(prompt, "USER'S MESSAGE:").unwrap_or("the research question");
+            json!({
+                "currentObjective": format!("Mock plan: {}", excerpt(topic)),
+                "plan": [
+                    { "objective": format!("{} aging biomarkers", excerpt(topic)), "datasets": [], "type": "LITERATURE" },
+                    { "objective": "Epigenetic and transcriptomic clocks of chronological age", "datasets": [], "type": "LITERATURE" },
+                ],
+            })
+            .to_string()
+        } else if prompt.contains("\"queries\"") {
+            let objective = excerpt(section(prompt, "RESEARCH OBJECTIVE:").unwrap_or("the research objective"));
+            json!({
+                "queries": [
+                    objective.clone(),
+                    format!("({}) AND (aging OR \"chronological age\")", objective),
+                ],
+            })
+            .to_string()
+        } else if prompt.contains("\"key_insights\"") {
+            let objective = section(prompt, "RESEARCH OBJECTIVE:").unwrap_or("the research objective");
+            json!({
+                "findings": format!("Mock literature summary for {}. Expression of several genes changes \
+                    monotonically with age across tissues, and transcriptomic clocks built from them \
+                    predict chronological age.", excerpt(objective)),
+                "sources": [{
+                    "title": "Mock study of age-associated gene expression",
+                    "authors": "Doe J, Roe R",
+                    "year": 2020,
+                    "doi": "10.5555/mock.llm.1",
+                    "url": null,
+                    "summary": "Synthetic source returned by the mock LLM."
+                }],
+                "key_insights": ["Gene expression tracks chronological age (mock)"],
+            })
+            .to_string()
+        } else if let Some((_, draft)) = prompt.rsplit_once(DRAFT_MARKER) {
+            draft.to_string()
+        } else {
+            format!("Mock response to: {}", excerpt(prompt.lines().next().unwrap_or_default()))
+        }
+    }
+}
+
+/// First paragraph after the `heading` line
+fn section<'a>(prompt: &'a str, heading: &str) -> Option<&'a str> {
+    let rest = prompt.split_once(heading)?.1.trim_start();
+    rest.split("\n\n").next().map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn excerpt(text: &str) -> String {
+    text.split_whitespace().take(12).collect::<Vec<_>>().join(" ")
+}
+
+fn prompt_text(request: &LLMRequest) -> String {
+    request
+        .messages
+        .iter()
+        .filter_map(|m| m.content.as_text())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[async_trait]
+impl LLMAdapter for MockLLMAdapter {
+    async fn create_chat_completion(&self, request: &LLMRequest) -> AppResult<LLMResponse> {
+        let prompt = prompt_text(request);
+        let content = Self::reply(&prompt);
+        // ~4 characters per token, as for streamed responses
+        let (prompt_tokens, completion_tokens) = ((prompt.len() / 4) as u32, (content.len() / 4) as u32);
+        Ok(LLMResponse {
+            content,
+            finish_reason: "stop".to_string(),
+            usage: TokenUsage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+                ..Default::default()
+            },
+        })
+    }
+
+    async fn create_chat_completion_stream(&self, request: &LLMRequest) -> AppResult<BoxStream<'static, AppResult<String>>> {
+        let content = Self::reply(&prompt_text(request));
+        let chunks: Vec<AppResult<String>> = content.split_inclusive(' ').map(|chunk| Ok(chunk.to_string())).collect();
+        Ok(stream::iter(chunks).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replies_follow_the_prompt() {
+        let plan = MockLLMAdapter::reply(
+            "OUTPUT FORMAT:\n{\"currentObjective\": \"...\"}\n\nUSER'S MESSAGE:\nWhich genes track age?\n\nRespond",
+        );
+        let plan: serde_json::Value = serde_json::from_str(&plan).unwrap();
+        assert_eq!(plan["currentObjective"], "Mock plan: Which genes track age?");
+        assert_eq!(plan["plan"].as_array().unwrap().len(), 2);
+
+        assert_eq!(MockLLMAdapter::reply("Rewrite this.\n\nDraft:\n# Title\n\nBody"), "# Title\n\nBody");
+        assert_eq!(MockLLMAdapter::reply("Hello there\nmore"), "Mock response to: Hello there");
+    }
+}
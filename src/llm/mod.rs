@@ -1,11 +1,14 @@
 // LLM abstraction layer
 
 pub mod provider;
+pub mod capabilities;
+pub mod throttle;
 pub mod openai;
 pub mod anthropic;
 pub mod google;
 pub mod openrouter;
 pub mod groq;
+pub mod mock;
 
 pub use provider::*;
 pub use crate::types::*;
@@ -1,12 +1,16 @@
 use crate::llm::provider::LLMAdapter;
 use crate::types::{AppResult, AppError, LLMRequest, LLMResponse, TokenUsage, MessageContent, ContentPart};
+use crate::utils::retry::{RetryError, RetryPolicy};
 use async_trait::async_trait;
 use async_openai::{
     Client,
     config::OpenAIConfig,
+    error::OpenAIError,
     types::chat::{
         CreateChatCompletionRequestArgs,
         CreateChatCompletionRequest,
+        CreateChatCompletionResponse,
+        CreateChatCompletionStreamResponse,
         ChatCompletionRequestMessage,
         ChatCompletionRequestSystemMessage,
         ChatCompletionRequestSystemMessageContent,
@@ -26,22 +30,67 @@ use futures::stream::BoxStream;
 
 pub struct OpenAIAdapter {
     client: Client<OpenAIConfig>,
+    retry: RetryPolicy,
+    /// Fields added to every request body, for OpenAI-compatible APIs that
+    /// read more than OpenAI's
+    extra_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 impl OpenAIAdapter {
     pub fn new(api_key: &str) -> Self {
         // Create config with the provided API key
-        let config = OpenAIConfig::new().with_api_key(api_key);
-        let client = Client::with_config(config);
-        Self { client }
+        Self::with_config(OpenAIConfig::new().with_api_key(api_key))
     }
 
     pub fn new_with_api_base(api_key: &str, api_base: &str) -> Self {
-        let config = OpenAIConfig::new()
-            .with_api_key(api_key)
-            .with_api_base(api_base);
-        let client = Client::with_config(config);
-        Self { client }
+        Self::with_config(
+            OpenAIConfig::new()
+                .with_api_key(api_key)
+                .with_api_base(api_base),
+        )
+    }
+
+    /// Adapter for a fully built client config, e.g. with custom headers
+    pub fn with_config(config: OpenAIConfig) -> Self {
+        Self {
+            client: Client::with_config(config),
+            retry: RetryPolicy::for_target("openai"),
+            extra_fields: serde_json::Map::new(),
+        }
+    }
+
+    /// Add `fields` to every request body
+    pub fn with_extra_fields(mut self, fields: serde_json::Map<String, serde_json::Value>) -> Self {
+        self.extra_fields = fields;
+        self
+    }
+
+    /// Use a preconfigured HTTP client (proxy, custom CA)
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.client = self.client.with_http_client(http);
+        self
+    }
+
+    /// Replace the retry policy, e.g. to report to another provider's breaker
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Network failures and server errors. Rate limiting is retried inside
+    /// the client already.
+    fn is_transient(error: &OpenAIError) -> bool {
+        match error {
+            OpenAIError::Reqwest(e) => e.status().is_none_or(|s| s.is_server_error()),
+            OpenAIError::ApiError(e) => e.r#type.as_deref() == Some("server_error"),
+            OpenAIError::StreamError(_) => true,
+            _ => false,
+        }
+    }
+
+    fn api_error(error: RetryError<OpenAIError>) -> AppError {
+        let retryable = error.is_retryable(Self::is_transient);
+        AppError::provider(format!("OpenAI API error: {}", error), retryable)
     }
 
     /// Convert internal ContentPart to OpenAI format
@@ -156,10 +205,10 @@ impl OpenAIAdapter {
             })
             .collect();
 
-        if let Some(system) = request.system_instruction.as_ref() {
+        if let Some(system) = request.system_text() {
             messages.insert(0, ChatCompletionRequestMessage::System(
                 ChatCompletionRequestSystemMessage {
-                    content: ChatCompletionRequestSystemMessageContent::Text(system.clone()),
+                    content: ChatCompletionRequestSystemMessageContent::Text(system),
                     name: None,
                 }
             ));
@@ -181,7 +230,17 @@ impl OpenAIAdapter {
 
         request_builder
             .build()
-            .map_err(|e| AppError::LLMApi(format!("Failed to build request: {}", e)))
+            .map_err(|e| AppError::Internal(format!("Failed to build request: {}", e)))
+    }
+
+    /// Body of `request` with the extra fields added
+    fn request_body(&self, request: &CreateChatCompletionRequest) -> AppResult<serde_json::Value> {
+        let mut body = serde_json::to_value(request)
+            .map_err(|e| AppError::Internal(format!("Failed to build request: {}", e)))?;
+        if let Some(object) = body.as_object_mut() {
+            object.extend(self.extra_fields.clone());
+        }
+        Ok(body)
     }
 }
 
@@ -189,14 +248,13 @@ impl OpenAIAdapter {
 impl LLMAdapter for OpenAIAdapter {
     #[allow(deprecated)]
     async fn create_chat_completion(&self, request: &LLMRequest) -> AppResult<LLMResponse> {
-        let openai_request = Self::build_openai_request(request, false)?;
+        let body = self.request_body(&Self::build_openai_request(request, false)?)?;
 
-        let response = self
-            .client
-            .chat()
-            .create(openai_request)
+        let response: CreateChatCompletionResponse = self
+            .retry
+            .run(|| async { self.client.chat().create_byot(body.clone()).await }, Self::is_transient)
             .await
-            .map_err(|e| AppError::LLMApi(format!("OpenAI API error: {}", e)))?;
+            .map_err(Self::api_error)?;
 
         let content = response.choices.get(0)
             .and_then(|c| c.message.content.clone())
@@ -206,11 +264,8 @@ impl LLMAdapter for OpenAIAdapter {
             prompt_tokens: u.prompt_tokens,
             completion_tokens: u.completion_tokens,
             total_tokens: u.total_tokens,
-        }).unwrap_or(TokenUsage {
-            prompt_tokens: 0,
-            completion_tokens: 0,
-            total_tokens: 0,
-        });
+            ..Default::default()
+        }).unwrap_or_default();
 
         let finish_reason = response.choices.get(0)
             .and_then(|c| c.finish_reason.as_ref())
@@ -225,17 +280,19 @@ impl LLMAdapter for OpenAIAdapter {
     }
 
     async fn create_chat_completion_stream(&self, request: &LLMRequest) -> AppResult<BoxStream<'static, AppResult<String>>> {
-        let openai_request = Self::build_openai_request(request, true)?;
+        let body = self.request_body(&Self::build_openai_request(request, true)?)?;
 
         let stream = self
-            .client
-            .chat()
-            .create_stream(openai_request)
+            .retry
+            .run(
+                || async { self.client.chat().create_stream_byot::<_, CreateChatCompletionStreamResponse>(body.clone()).await },
+                Self::is_transient,
+            )
             .await
-            .map_err(|e| AppError::LLMApi(format!("OpenAI API error: {}", e)))?;
+            .map_err(Self::api_error)?;
 
         let mapped = stream.map(|chunk| {
-            let chunk = chunk.map_err(|e| AppError::LLMApi(format!("OpenAI stream error: {}", e)))?;
+            let chunk = chunk.map_err(|e| AppError::provider(format!("OpenAI stream error: {}", e), Self::is_transient(&e)))?;
             let delta = chunk.choices.get(0)
                 .and_then(|c| c.delta.content.clone())
                 .unwrap_or_default();
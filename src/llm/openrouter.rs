@@ -1,39 +1,120 @@
-// OpenRouter adapter stub
-// TODO: Implement full OpenRouter adapter
+//! OpenRouter adapter
+//!
+//! OpenRouter speaks the OpenAI chat API and serves each model from one of
+//! several upstream vendors. Requests carry the vendor preferences and
+//! fallback models of [`OpenRouterConfig`] as extra body fields, and its
+//! attribution headers.
 
+use crate::config::OpenRouterConfig;
 use crate::llm::provider::LLMAdapter;
-use crate::types::{AppResult, LLMRequest, LLMResponse, AppError};
+use crate::types::{AppResult, LLMRequest, LLMResponse};
+use async_openai::config::OpenAIConfig;
 use async_trait::async_trait;
 use futures::stream::BoxStream;
+use serde_json::{json, Map, Value};
+use tracing::warn;
+
+const OPENROUTER_API_BASE: &str = "https://openrouter.ai/api/v1";
 
 pub struct OpenRouterAdapter {
-    api_key: String,
+    inner: crate::llm::openai::OpenAIAdapter,
 }
 
 impl OpenRouterAdapter {
-    pub fn new(api_key: &str) -> Self {
+    pub fn new(api_key: &str, routing: &OpenRouterConfig) -> Self {
+        let mut config = OpenAIConfig::new()
+            .with_api_key(api_key)
+            .with_api_base(OPENROUTER_API_BASE);
+        for (name, value) in attribution_headers(routing) {
+            config = match config.clone().with_header(name, value.as_str()) {
+                Ok(config) => config,
+                Err(e) => {
+                    warn!(header = name, error = %e, "Skipping OpenRouter attribution header");
+                    config
+                }
+            };
+        }
         Self {
-            api_key: api_key.to_string(),
+            inner: crate::llm::openai::OpenAIAdapter::with_config(config)
+                .with_retry(crate::utils::retry::RetryPolicy::for_target("openrouter"))
+                .with_extra_fields(request_fields(routing)),
         }
     }
+
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.inner = self.inner.with_http_client(http);
+        self
+    }
+}
+
+/// `HTTP-Referer` and `X-Title`, where set
+pub fn attribution_headers(routing: &OpenRouterConfig) -> Vec<(&'static str, String)> {
+    [("HTTP-Referer", &routing.referer), ("X-Title", &routing.title)]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.clone()?)))
+        .collect()
+}
+
+/// Fields OpenRouter reads besides OpenAI's: `provider` preferences, and the
+/// fallback `models` with `route: "fallback"`
+pub fn request_fields(routing: &OpenRouterConfig) -> Map<String, Value> {
+    let mut provider = Map::new();
+    for (name, vendors) in [("order", &routing.order), ("only", &routing.only), ("ignore", &routing.ignore)] {
+        if !vendors.is_empty() {
+            provider.insert(name.to_string(), json!(vendors));
+        }
+    }
+    if let Some(allow) = routing.allow_fallbacks {
+        provider.insert("allow_fallbacks".to_string(), json!(allow));
+    }
+
+    let mut fields = Map::new();
+    if !provider.is_empty() {
+        fields.insert("provider".to_string(), Value::Object(provider));
+    }
+    if !routing.fallback_models.is_empty() {
+        fields.insert("models".to_string(), json!(routing.fallback_models));
+        fields.insert("route".to_string(), json!("fallback"));
+    }
+    fields
 }
 
 #[async_trait]
 impl LLMAdapter for OpenRouterAdapter {
-    async fn create_chat_completion(&self, _request: &LLMRequest) -> AppResult<LLMResponse> {
-        // Placeholder implementation
-        Ok(LLMResponse {
-            content: String::new(),
-            finish_reason: "stop".to_string(),
-            usage: crate::types::TokenUsage {
-                prompt_tokens: 0,
-                completion_tokens: 0,
-                total_tokens: 0,
-            },
-        })
-    }
-
-    async fn create_chat_completion_stream(&self, _request: &LLMRequest) -> AppResult<BoxStream<'static, AppResult<String>>> {
-        Err(AppError::LLMApi("Streaming not supported for OpenRouter adapter".to_string()))
+    async fn create_chat_completion(&self, request: &LLMRequest) -> AppResult<LLMResponse> {
+        self.inner.create_chat_completion(request).await
+    }
+
+    async fn create_chat_completion_stream(&self, request: &LLMRequest) -> AppResult<BoxStream<'static, AppResult<String>>> {
+        self.inner.create_chat_completion_stream(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_fields() {
+        assert!(request_fields(&OpenRouterConfig::default()).is_empty());
+        assert!(attribution_headers(&OpenRouterConfig::default()).is_empty());
+
+        let routing = OpenRouterConfig {
+            order: vec!["anthropic".to_string(), "amazon-bedrock".to_string()],
+            ignore: vec!["deepinfra".to_string()],
+            allow_fallbacks: Some(false),
+            fallback_models: vec!["openai/gpt-4o".to_string()],
+            title: Some("Oxidized Bio".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(request_fields(&routing)),
+            json!({
+                "provider": {"order": ["anthropic", "amazon-bedrock"], "ignore": ["deepinfra"], "allow_fallbacks": false},
+                "models": ["openai/gpt-4o"],
+                "route": "fallback",
+            })
+        );
+        assert_eq!(attribution_headers(&routing), [("X-Title", "Oxidized Bio".to_string())]);
     }
 }
@@ -1,6 +1,10 @@
 use async_trait::async_trait;
-use crate::types::{LLMRequest, LLMResponse, AppResult};
-use futures::stream::BoxStream;
+use crate::types::{LLMRequest, LLMResponse, AppResult, TokenUsage};
+use futures::stream::{BoxStream, StreamExt};
+use std::sync::Arc;
+
+use crate::llm::capabilities::CHARS_PER_TOKEN;
+use crate::llm::throttle::{Permit, Throttle};
 
 #[async_trait]
 pub trait LLMAdapter: Send + Sync {
@@ -12,38 +16,126 @@ pub trait LLMAdapter: Send + Sync {
 pub struct LLMProviderConfig {
     pub name: String,
     pub api_key: String,
+    /// Proxy/CA settings for the adapter's HTTP client
+    pub network: crate::config::NetworkConfig,
+    /// Vendor preferences and fallbacks, for the OpenRouter adapter
+    pub openrouter: crate::config::OpenRouterConfig,
+    /// Client-side limits shared by every call to this provider
+    pub rate_limit: crate::config::RateLimit,
 }
 
 pub struct LLM {
     adapter: Box<dyn LLMAdapter>,
     provider_name: String,
+    /// `None` when the provider has no rate limits configured
+    throttle: Option<Arc<Throttle>>,
 }
 
 impl LLM {
     pub fn new(provider: LLMProviderConfig) -> Self {
         let adapter: Box<dyn LLMAdapter> = match provider.name.as_str() {
-            "openai" => Box::new(crate::llm::openai::OpenAIAdapter::new(&provider.api_key)),
-            "anthropic" => Box::new(crate::llm::anthropic::AnthropicAdapter::new(&provider.api_key)),
+            "openai" => Box::new(
+                crate::llm::openai::OpenAIAdapter::new(&provider.api_key)
+                    .with_http_client(crate::utils::http::client(&provider.network, "openai")),
+            ),
+            "anthropic" => Box::new(
+                crate::llm::anthropic::AnthropicAdapter::new(&provider.api_key)
+                    .with_http_client(crate::utils::http::client(&provider.network, "anthropic")),
+            ),
             "google" => Box::new(crate::llm::google::GoogleAdapter::new(&provider.api_key)),
-            "openrouter" => Box::new(crate::llm::openrouter::OpenRouterAdapter::new(&provider.api_key)),
-            "groq" => Box::new(crate::llm::groq::GroqAdapter::new(&provider.api_key)),
+            "openrouter" => Box::new(
+                crate::llm::openrouter::OpenRouterAdapter::new(&provider.api_key, &provider.openrouter)
+                    .with_http_client(crate::utils::http::client(&provider.network, "openrouter")),
+            ),
+            "groq" => Box::new(
+                crate::llm::groq::GroqAdapter::new(&provider.api_key)
+                    .with_http_client(crate::utils::http::client(&provider.network, "groq")),
+            ),
+            "mock" => Box::new(crate::llm::mock::MockLLMAdapter::new()),
             _ => panic!("Unsupported provider: {}", provider.name),
         };
 
+        let throttle = (!provider.rate_limit.is_unlimited())
+            .then(|| Throttle::shared(&provider.name, provider.rate_limit));
+
         Self {
             adapter,
             provider_name: provider.name,
+            throttle,
         }
     }
 
+    /// Wait for the provider's rate limits to admit `request`, counted at
+    /// its prompt plus its full output budget until the actual usage is known
+    async fn acquire(&self, request: &LLMRequest) -> Option<Permit> {
+        let throttle = self.throttle.as_ref()?;
+        let tokens = (prompt_chars(request) / CHARS_PER_TOKEN) as u32 + request.max_tokens.unwrap_or(0);
+        Some(throttle.acquire(tokens).await)
+    }
+
     pub async fn create_chat_completion(&self, request: &LLMRequest) -> AppResult<LLMResponse> {
-        self.adapter.create_chat_completion(request).await
+        let permit = self.acquire(request).await;
+        let response = self.adapter.create_chat_completion(request).await?;
+        if let Some(permit) = &permit {
+            permit.settle(response.usage.total_tokens);
+        }
+        crate::budget::record_llm(&request.model, &response.usage);
+        Ok(response)
     }
 
+    /// Streams don't report usage, so spend is estimated at ~4 characters per
+    /// token and recorded once the stream ends. The rate limit permit is held
+    /// until then too.
     pub async fn create_chat_completion_stream(
         &self,
         request: &LLMRequest,
     ) -> AppResult<BoxStream<'static, AppResult<String>>> {
-        self.adapter.create_chat_completion_stream(request).await
+        let permit = self.acquire(request).await;
+        let stream = self.adapter.create_chat_completion_stream(request).await?;
+
+        let prompt_chars = prompt_chars(request);
+        let model = request.model.clone();
+        let completion_chars = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = completion_chars.clone();
+
+        let counted = stream
+            .inspect(move |chunk| {
+                if let Ok(text) = chunk {
+                    counter.fetch_add(text.len(), std::sync::atomic::Ordering::Relaxed);
+                }
+            })
+            .chain(futures::stream::once(async move {
+                let completion = completion_chars.load(std::sync::atomic::Ordering::Relaxed);
+                let (prompt_tokens, completion_tokens) =
+                    ((prompt_chars / CHARS_PER_TOKEN) as u32, (completion / CHARS_PER_TOKEN) as u32);
+                if let Some(permit) = permit {
+                    permit.settle(prompt_tokens + completion_tokens);
+                }
+                crate::budget::record_llm(
+                    &model,
+                    &TokenUsage {
+                        prompt_tokens,
+                        completion_tokens,
+                        total_tokens: prompt_tokens + completion_tokens,
+                        ..Default::default()
+                    },
+                );
+                Ok(String::new())
+            }))
+            .filter(|chunk| futures::future::ready(!matches!(chunk, Ok(s) if s.is_empty())));
+
+        Ok(Box::pin(counted))
     }
 }
+
+/// Characters of `request`'s prompt: messages and system instructions
+fn prompt_chars(request: &LLMRequest) -> usize {
+    request
+        .messages
+        .iter()
+        .filter_map(|m| m.content.as_text())
+        .chain(request.system_instruction.as_deref())
+        .chain(request.system_blocks.iter().map(|b| b.text.as_str()))
+        .map(str::len)
+        .sum()
+}
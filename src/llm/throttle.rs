@@ -0,0 +1,259 @@
+//! Client-side rate limiting of LLM calls
+//!
+//! Literature tasks and other agents call providers in parallel, and a burst
+//! of them can exceed the provider's own limits, which answers with a storm
+//! of 429s. Each provider with a configured [`RateLimit`] gets one
+//! process-wide [`Throttle`]; calls acquire a [`Permit`] from it first and
+//! wait, rather than fail, while the provider is at its concurrency limit or
+//! its requests or tokens in the last minute would exceed the per-minute
+//! limits.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+use tracing::debug;
+
+use crate::config::RateLimit;
+
+/// Span the per-minute limits are counted over
+const WINDOW: Duration = Duration::from_secs(60);
+
+pub struct Throttle {
+    provider: String,
+    state: Mutex<State>,
+    released: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    limit: RateLimit,
+    in_flight: usize,
+    /// Calls started within the last [`WINDOW`], oldest first
+    window: VecDeque<Call>,
+    next_id: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Call {
+    id: u64,
+    at: Instant,
+    tokens: u32,
+}
+
+/// What a call has to wait for before it may start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wait {
+    /// Another call to finish
+    Slot,
+    /// Older calls to leave the window
+    Until(Instant),
+}
+
+impl State {
+    fn prune(&mut self, now: Instant) {
+        while self.window.front().is_some_and(|call| now.duration_since(call.at) >= WINDOW) {
+            self.window.pop_front();
+        }
+    }
+
+    /// Why a call of `tokens` cannot start, or `None` when it can.
+    /// A call larger than the token limit on its own starts once the window
+    /// is empty.
+    fn wait(&self, tokens: u32) -> Option<Wait> {
+        if self.limit.max_concurrent.is_some_and(|max| self.in_flight >= max) {
+            return Some(Wait::Slot);
+        }
+        if let Some(rpm) = self.limit.requests_per_minute {
+            if self.window.len() >= rpm as usize {
+                // Room once all but `rpm - 1` calls have left the window
+                return Some(Wait::Until(self.window[self.window.len() - rpm as usize].at + WINDOW));
+            }
+        }
+        if let Some(tpm) = self.limit.tokens_per_minute {
+            let used: u64 = self.window.iter().map(|call| u64::from(call.tokens)).sum();
+            let mut over = (used + u64::from(tokens)).saturating_sub(u64::from(tpm));
+            if over > 0 {
+                for call in &self.window {
+                    over = over.saturating_sub(u64::from(call.tokens));
+                    if over == 0 {
+                        return Some(Wait::Until(call.at + WINDOW));
+                    }
+                }
+                return self.window.back().map(|call| Wait::Until(call.at + WINDOW));
+            }
+        }
+        None
+    }
+
+    fn admit(&mut self, tokens: u32, now: Instant) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.in_flight += 1;
+        self.window.push_back(Call { id, at: now, tokens });
+        id
+    }
+}
+
+impl Throttle {
+    pub fn new(provider: impl Into<String>, limit: RateLimit) -> Self {
+        Self {
+            provider: provider.into(),
+            state: Mutex::new(State {
+                limit,
+                ..State::default()
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Process-wide throttle of `provider`, created on first use; a later
+    /// call with different limits replaces them without forgetting the
+    /// calls already made
+    pub fn shared(provider: &str, limit: RateLimit) -> Arc<Self> {
+        static THROTTLES: OnceLock<Mutex<HashMap<String, Arc<Throttle>>>> = OnceLock::new();
+        let mut throttles = THROTTLES
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let throttle = throttles
+            .entry(provider.to_string())
+            .or_insert_with(|| Arc::new(Self::new(provider, limit)))
+            .clone();
+        let changed = std::mem::replace(&mut throttle.state().limit, limit) != limit;
+        if changed {
+            throttle.released.notify_waiters();
+        }
+        throttle
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait until a call of about `tokens` prompt and completion tokens may
+    /// start. The call counts as in flight until the permit is dropped.
+    pub async fn acquire(self: &Arc<Self>, tokens: u32) -> Permit {
+        let started = Instant::now();
+        loop {
+            let released = self.released.notified();
+            tokio::pin!(released);
+            // Registered before the state is checked, so a release in between
+            // is not missed
+            released.as_mut().enable();
+
+            let wait = {
+                let mut state = self.state();
+                let now = Instant::now();
+                state.prune(now);
+                match state.wait(tokens) {
+                    None => {
+                        let id = state.admit(tokens, now);
+                        let waited = started.elapsed();
+                        if waited >= Duration::from_millis(100) {
+                            debug!(provider = %self.provider, waited_ms = waited.as_millis() as u64, "LLM call waited for rate limit");
+                        }
+                        return Permit { throttle: self.clone(), id };
+                    }
+                    Some(wait) => wait,
+                }
+            };
+            match wait {
+                Wait::Slot => released.await,
+                Wait::Until(at) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(at.into()) => {}
+                        _ = released => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A started call; dropping it frees its concurrency slot
+pub struct Permit {
+    throttle: Arc<Throttle>,
+    id: u64,
+}
+
+impl Permit {
+    /// Replace the call's estimated tokens with those it actually used
+    pub fn settle(&self, tokens: u32) {
+        if tokens == 0 {
+            return;
+        }
+        let mut state = self.throttle.state();
+        if let Some(call) = state.window.iter_mut().find(|call| call.id == self.id) {
+            call.tokens = tokens;
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.throttle.state();
+        state.in_flight = state.in_flight.saturating_sub(1);
+        drop(state);
+        self.throttle.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_for_each_limit() {
+        let start = Instant::now();
+        let mut state = State {
+            limit: RateLimit {
+                max_concurrent: Some(2),
+                requests_per_minute: Some(3),
+                tokens_per_minute: Some(1_000),
+            },
+            ..State::default()
+        };
+        assert_eq!(state.wait(400), None);
+        state.admit(400, start);
+        state.admit(400, start + Duration::from_secs(10));
+        assert_eq!(state.wait(100), Some(Wait::Slot));
+
+        // Both finished: 800 tokens in the window, so 300 more waits for the first call to expire
+        state.in_flight = 0;
+        assert_eq!(state.wait(200), None);
+        assert_eq!(state.wait(300), Some(Wait::Until(start + WINDOW)));
+        // Larger than the limit on its own: waits for the window to empty
+        assert_eq!(state.wait(5_000), Some(Wait::Until(start + Duration::from_secs(70))));
+
+        state.admit(0, start + Duration::from_secs(20));
+        state.in_flight = 0;
+        assert_eq!(state.wait(0), Some(Wait::Until(start + WINDOW)));
+
+        state.prune(start + Duration::from_secs(65));
+        assert_eq!(state.window.len(), 2);
+        assert_eq!(state.wait(500), None);
+    }
+
+    #[tokio::test]
+    async fn test_permits_limit_concurrency() {
+        let throttle = Arc::new(Throttle::new(
+            "test",
+            RateLimit {
+                max_concurrent: Some(1),
+                ..RateLimit::default()
+            },
+        ));
+        let first = throttle.acquire(10).await;
+        let waiting = tokio::spawn({
+            let throttle = throttle.clone();
+            async move { throttle.acquire(10).await.id }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+        drop(first);
+        let id = tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+        assert_eq!(id, 1);
+    }
+}
@@ -8,11 +8,50 @@
 //!   ```bash
 //!   oxidized-bio
 //!   ```
+//!
+//! - **Combined Mode**: TUI plus the HTTP/RFC server on `127.0.0.1:PORT`,
+//!   sharing datasets, settings and session events
+//!   ```bash
+//!   oxidized-bio --serve
+//!   ```
+//!
+//! - **Worker Mode**: Process background jobs from the Postgres job queue
+//!   ```bash
+//!   oxidized-bio --worker
+//!   ```
+//!
+//! - **Headless analysis**: Ingest, analyze and export a report without the TUI
+//!   ```bash
+//!   oxidized-bio analyze --input data.csv --target age --group cell_type --out report/
+//!   ```
+//!
+//! - **Headless literature search**: Collect references as BibTeX or JSON
+//!   ```bash
+//!   oxidized-bio search "senolytics aging" --engines scholar,pubmed --format bibtex
+//!   ```
+//!
+//! - **Pipeline**: Run the whole guided workflow from a YAML file
+//!   ```bash
+//!   oxidized-bio run workflow.yaml
+//!   ```
+//!
+//! - **Demo data**: Synthetic microarray matrix with planted age markers
+//!   ```bash
+//!   oxidized-bio demo-data --rows 200 --markers 500 --effect age
+//!   ```
 
-use clap::Parser;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use clap::{Args, Parser, Subcommand};
 
-use oxidized_bio::{config::Config, tui};
+use oxidized_bio::{
+    annotation::{self, Species},
+    config::{Config, ConfigSources},
+    data_registry::{DatasetRecord, DatasetRegistry, ParseOptions},
+    session::{EventBus, LiveConfig},
+    db,
+    manuscript::JournalTemplate,
+    queue, tui,
+    utils::logger,
+};
 
 /// Oxidized Bio - AI Research Agent for biological and scientific research
 #[derive(Parser, Debug)]
@@ -22,6 +61,307 @@ struct Cli {
     /// Enable verbose logging
     #[arg(short, long)]
     verbose: bool,
+
+    /// Log level (trace, debug, info, warn, error) or a filter directive such
+    /// as `oxidized_bio=debug,sqlx=warn`; overrides RUST_LOG
+    #[arg(long, global = true, value_name = "LEVEL")]
+    log_level: Option<String>,
+
+    /// Run as a headless queue worker instead of the TUI
+    #[arg(long)]
+    worker: bool,
+
+    /// Also serve the HTTP/RFC API while the TUI runs, on ADDR or
+    /// 127.0.0.1:PORT; datasets pushed over HTTP or RFC show up in the TUI
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, conflicts_with = "worker")]
+    serve: Option<Option<String>>,
+
+    /// Settings profile to use for this run (e.g. work, offline, demo)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Config file to use instead of ~/.config/oxidized-bio/config.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<std::path::PathBuf>,
+
+    /// Override a setting for this run (e.g. --set queue.workers=4); repeatable
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run ingestion, analysis and report export without the TUI
+    Analyze(Box<AnalyzeArgs>),
+    /// Search the literature and print the references
+    Search(SearchArgs),
+    /// Run the guided workflow declared in a YAML file
+    Run {
+        /// Workflow file (datasets, analysis, agents, outputs)
+        workflow: std::path::PathBuf,
+    },
+    /// Write a synthetic dataset with planted markers for demos and tests
+    DemoData(DemoDataArgs),
+}
+
+#[derive(Args, Debug)]
+struct DemoDataArgs {
+    /// Number of samples
+    #[arg(long, default_value_t = 200)]
+    rows: usize,
+
+    /// Number of gene columns
+    #[arg(long, default_value_t = 500)]
+    markers: usize,
+
+    /// What the planted markers respond to
+    #[arg(long, value_enum, default_value = "age")]
+    effect: oxidized_bio::demo::DemoEffect,
+
+    /// Number of markers carrying the effect
+    #[arg(long, default_value_t = 10)]
+    planted: usize,
+
+    /// Species of the gene IDs
+    #[arg(long, value_enum, default_value = "human")]
+    species: Species,
+
+    /// Random seed; the same seed writes the same file
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+
+    /// CSV file to write (the planted markers go to <name>_planted.csv)
+    #[arg(long, default_value = "demo_data.csv")]
+    out: std::path::PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct SearchArgs {
+    /// Search query
+    query: String,
+
+    /// Comma-separated engines (scholar, light, pubmed, semantic_scholar);
+    /// defaults to every enabled engine
+    #[arg(long)]
+    engines: Option<String>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value = "json")]
+    format: oxidized_bio::headless::SearchFormat,
+}
+
+#[derive(Args, Debug)]
+struct AnalyzeArgs {
+    /// Dataset to analyze: CSV, TSV, Parquet, a GEO series matrix or SOFT
+    /// file, or a VCF
+    #[arg(long)]
+    input: std::path::PathBuf,
+
+    /// Column to correlate biomarkers against
+    #[arg(long, default_value = "age")]
+    target: String,
+
+    /// Column used to group samples
+    #[arg(long, default_value = "cell_type")]
+    group: String,
+
+    /// Regression covariates (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    covariates: Vec<String>,
+
+    /// Model formula ranking markers by adjusted association, e.g.
+    /// "age ~ marker + sex + batch", or a preset (sex, batch, demographics,
+    /// full); overrides --target and --covariates
+    #[arg(long)]
+    formula: Option<String>,
+
+    /// Marker column to draw a box plot for
+    #[arg(long)]
+    boxplot: Option<String>,
+
+    /// Marker columns to analyze (comma-separated; default: all numeric columns)
+    #[arg(long, value_delimiter = ',')]
+    markers: Vec<String>,
+
+    /// Directory the report is written to
+    #[arg(long, default_value = "report")]
+    out: std::path::PathBuf,
+
+    /// Maximum number of numeric columns to analyze
+    #[arg(long, default_value_t = 50)]
+    max_columns: usize,
+
+    /// Maximum number of groups to plot
+    #[arg(long, default_value_t = 20)]
+    max_groups: usize,
+
+    /// Platform annotation (GPL SOFT or Affymetrix CSV) mapping probe columns to genes
+    #[arg(long)]
+    platform: Option<std::path::PathBuf>,
+
+    /// Sample sheet (CSV, first column the sample ID) whose columns, such as
+    /// age, are added to the dataset's samples, e.g. for a VCF without them
+    #[arg(long)]
+    samples: Option<std::path::PathBuf>,
+
+    /// How probes of the same gene are combined when --platform is set
+    #[arg(long, value_enum, default_value = "max")]
+    collapse: annotation::Collapse,
+
+    /// How technical replicate rows of one sample (a `replicate` column, or
+    /// IDs like S1_rep1, S1_rep2) are combined
+    #[arg(long, value_enum, default_value = "mean")]
+    replicates: oxidized_bio::analysis::qc::Replicates,
+
+    /// How missing marker values are filled in before the analysis: off,
+    /// mean, median, or knn (knn:<k> for other than 5 neighbours)
+    #[arg(long, default_value = "off")]
+    impute: oxidized_bio::analysis::impute::Imputation,
+
+    /// Normalization applied to the markers before the analysis, in order
+    /// (comma-separated): log2, zscore, quantile
+    #[arg(long, value_enum, value_delimiter = ',')]
+    normalize: Vec<oxidized_bio::analysis::normalize::Normalization>,
+
+    /// Column naming each sample's batch (e.g. processing date or GEO
+    /// series); its batch effects are removed from the markers
+    #[arg(long)]
+    batch: Option<String>,
+
+    /// How batch effects are removed when --batch is set
+    #[arg(long, value_enum, default_value = "combat")]
+    batch_correction: oxidized_bio::analysis::batch::BatchCorrection,
+
+    /// Correlation coefficient markers are ranked by and the heatmap shows
+    #[arg(long, value_enum, default_value = "pearson")]
+    correlation: oxidized_bio::analysis::stats::CorrelationMethod,
+
+    /// Format of the result tables
+    #[arg(long, value_enum, default_value = "csv")]
+    tables: oxidized_bio::analysis::report::TableFormat,
+
+    /// Number format of the tables in drafts and reports: plain, en
+    /// (1,234.5), de (1.234,5), fr (1 234,5) or ch (1'234.5)
+    #[arg(long, default_value = "plain")]
+    numbers: oxidized_bio::utils::number::NumberFormat,
+
+    /// Two-sided p-value cut-off for significant results
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f64,
+
+    /// Benjamini-Hochberg q-value cut-off for significant results
+    #[arg(long, default_value_t = 0.05)]
+    fdr: f64,
+
+    /// Minimum absolute correlation for a marker to count as significant
+    #[arg(long, default_value_t = 0.0)]
+    min_effect: f64,
+
+    /// Add the Kruskal-Wallis test to the ANOVA across more than two groups
+    #[arg(long)]
+    kruskal_wallis: bool,
+
+    /// Refuse to start when the analysis is estimated to need more memory:
+    /// auto (the memory available), off, or a size such as 8G
+    #[arg(long, default_value = "auto")]
+    memory_limit: oxidized_bio::analysis::memory::MemoryLimit,
+
+    /// Threads computing the per-column statistics, correlations and
+    /// regressions; 0 uses one per core
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+
+    /// Penalty of the multi-marker model of the target, whose non-zero
+    /// markers are the manuscript's panel
+    #[arg(long, value_enum, default_value = "elastic-net")]
+    penalty: oxidized_bio::analysis::regularized::Penalty,
+
+    /// Share of the L1 penalty of the elastic net (0-1)
+    #[arg(long, default_value_t = 0.5)]
+    l1_ratio: f64,
+
+    /// Penalty strength λ (chosen by cross-validation by default)
+    #[arg(long)]
+    lambda: Option<f64>,
+
+    /// Cross-validation folds for choosing λ
+    #[arg(long, default_value_t = 5)]
+    penalty_folds: usize,
+
+    /// Split of the target into two classes for the candidates' ROC curves:
+    /// auto (binary targets only), median, off, or a cut-off above which
+    /// samples are positive
+    #[arg(long, default_value = "auto")]
+    roc_split: oxidized_bio::analysis::roc::RocSplit,
+
+    /// GMT gene set files (comma-separated) tested by GSEA against the
+    /// candidates ranked by their correlation with the target
+    #[arg(long, value_delimiter = ',', value_name = "GMT")]
+    gene_sets: Vec<std::path::PathBuf>,
+
+    /// Time-to-event column; with --event-column, adds Kaplan-Meier curves
+    /// and per-marker Cox models
+    #[arg(long, requires = "event_column")]
+    time_column: Option<String>,
+
+    /// Column marking whether each sample's event happened (1, yes, dead)
+    /// or it was censored (0, no, alive)
+    #[arg(long, requires = "time_column")]
+    event_column: Option<String>,
+
+    /// Column naming each sample's subject; repeated samples of a subject
+    /// get a random intercept in the models ranking the markers
+    #[arg(long)]
+    subject_column: Option<String>,
+
+    /// Species of the dataset (detected from its Ensembl IDs by default)
+    #[arg(long, value_enum)]
+    species: Option<Species>,
+
+    /// Decimal separator of the dataset's numbers: . or ,
+    #[arg(long, default_value_t = '.')]
+    decimal: char,
+
+    /// Values read as missing (comma-separated, case-insensitive; empty
+    /// cells always are). Defaults to NA, N/A, NaN, null, none and "."
+    #[arg(long, value_delimiter = ',', value_name = "TOKENS")]
+    na: Vec<String>,
+
+    /// Quote character around fields
+    #[arg(long, default_value_t = '"')]
+    quote: char,
+
+    /// Top candidates to cross-reference against the literature, 0 to skip
+    /// (defaults to cross_reference.top_n)
+    #[arg(long, value_name = "N")]
+    cross_reference: Option<usize>,
+
+    /// Journal template for the manuscript (defaults to manuscript.template)
+    #[arg(long, value_enum)]
+    template: Option<JournalTemplate>,
+
+    /// Select a biomarker panel of at most N markers predicting the target
+    /// (greedy forward selection, written to panel.csv/panel.json)
+    #[arg(long, value_name = "N")]
+    panel: Option<usize>,
+
+    /// Cross-validation folds for --panel
+    #[arg(long, default_value_t = 5)]
+    panel_folds: usize,
+}
+
+impl AnalyzeArgs {
+    fn parsing(&self) -> ParseOptions {
+        let defaults = ParseOptions::default();
+        ParseOptions {
+            decimal: self.decimal,
+            na_values: if self.na.is_empty() { defaults.na_values } else { self.na.clone() },
+            quote: self.quote,
+        }
+    }
 }
 
 #[tokio::main]
@@ -31,84 +371,234 @@ async fn main() -> anyhow::Result<()> {
     // Load environment variables
     dotenvy::dotenv().ok();
 
+    // Load configuration: --set overrides, then env, then the config file
+    let sources = ConfigSources::discover(cli.config.as_deref(), &cli.overrides)?.install();
+
     // Initialize tracing based on mode
     // IMPORTANT: In TUI mode, we must NOT write logs to stdout/stderr as it corrupts
-    // the alternate screen display. Instead, we either write to a log file or disable
-    // logging entirely.
-    if cli.verbose {
-        // Server/verbose mode - write logs to stdout
-        let log_level = if cli.verbose {
+    // the alternate screen display, so logs go to a session file instead.
+    let log_level = cli.log_level.as_deref();
+    if cli.verbose || cli.worker || cli.command.is_some() {
+        let default = if cli.verbose {
             "oxidized_bio=debug,tower_http=debug,axum=debug"
         } else {
             "oxidized_bio=info,tower_http=info"
         };
-
-        tracing_subscriber::registry()
-            .with(
-                tracing_subscriber::EnvFilter::try_from_default_env()
-                    .unwrap_or_else(|_| log_level.into()),
-            )
-            .with(tracing_subscriber::fmt::layer())
-            .init();
+        logger::init_stderr(logger::filter(log_level, default)?);
     } else {
-        // TUI mode - write logs to a file to avoid corrupting the display
-        // Create log directory if needed
-        let log_dir = dirs::data_local_dir()
-            .unwrap_or_else(|| std::path::PathBuf::from("."))
-            .join("oxidized-bio")
-            .join("logs");
-        
-        if let Err(_) = std::fs::create_dir_all(&log_dir) {
-            // If we can't create log dir, just disable logging entirely for TUI
-            // This is better than corrupting the display
-            tracing_subscriber::registry()
-                .with(tracing_subscriber::EnvFilter::new("off"))
-                .init();
-        } else {
-            // Create log file with timestamp
-            let log_file = log_dir.join(format!(
-                "oxidized-bio-{}.log",
-                chrono::Local::now().format("%Y%m%d-%H%M%S")
-            ));
-            
-            match std::fs::File::create(&log_file) {
-                Ok(file) => {
-                    // Write logs to file instead of stdout
-                    let file_layer = tracing_subscriber::fmt::layer()
-                        .with_writer(std::sync::Mutex::new(file))
-                        .with_ansi(false); // No ANSI colors in log file
-                    
-                    tracing_subscriber::registry()
-                        .with(
-                            tracing_subscriber::EnvFilter::try_from_default_env()
-                    .unwrap_or_else(|_| "oxidized_bio=info".into()),
-                )
-                .with(file_layer)
-                .init();
-            }
-            Err(_) => {
-                // Fall back to no logging if file creation fails
-                tracing_subscriber::registry()
-                    .with(tracing_subscriber::EnvFilter::new("off"))
-                    .init();
-            }
+        let logging = oxidized_bio::config::LoggingConfig::from_env();
+        logger::init_file(&logging, logger::filter(log_level, "oxidized_bio=info")?);
+    }
+
+    if let Some(path) = sources.file_path() {
+        tracing::info!(path = %path.display(), "Loaded config file");
+    }
+    let mut config = Config::from_env()?;
+    if let Some(profile) = cli.profile {
+        oxidized_bio::settings::validate_profile_name(&profile)?;
+        config.settings_profile = Some(profile);
+    }
+
+    // Gene symbols for Ensembl IDs: batch analyses wait for the one-time
+    // download so their tables are annotated; interactive modes fetch it in
+    // the background. Workflows fetch other species per dataset.
+    match &cli.command {
+        Some(Command::Analyze(args)) => {
+            let species = args.species.unwrap_or_else(|| {
+                DatasetRecord::from_path_with(&args.input, None, args.parsing())
+                    .map(|record| record.species)
+                    .unwrap_or_default()
+            });
+            annotation::ensembl::ensure_cached(&config.annotation, &config.network, species).await;
         }
+        Some(Command::Run { .. }) => {
+            annotation::ensembl::ensure_cached(&config.annotation, &config.network, Species::Human).await;
+        }
+        None => {
+            let (annotation, network) = (config.annotation.clone(), config.network.clone());
+            tokio::spawn(async move { annotation::ensembl::ensure_cached(&annotation, &network, Species::Human).await });
+        }
+        _ => {}
+    }
+
+    match cli.command {
+        Some(Command::Analyze(args)) => return run_analyze(&config, *args).await,
+        Some(Command::Search(args)) => return run_search(&config, args).await,
+        Some(Command::Run { workflow }) => return run_pipeline(&config, &workflow).await,
+        Some(Command::DemoData(args)) => return run_demo_data(args),
+        None => {}
     }
+
+    if cli.worker {
+        return run_worker(config).await;
+    }
+
+    run_tui(config, cli.serve).await
+}
+
+/// Run as a queue worker until interrupted
+async fn run_worker(config: Config) -> anyhow::Result<()> {
+    if config.database.url.is_empty() {
+        anyhow::bail!("DATABASE_URL is required in worker mode");
     }
 
-    // Load configuration
-    let config = Config::from_env()?;
+    let pool = db::create_pool(&config.database).await?;
+    sqlx::migrate!("./migrations").run(&pool).await?;
+    oxidized_bio::metering::install(pool.clone());
+
+    let job_queue = queue::JobQueue::new(pool).with_default_retry(config.queue.retry_policy());
+    let pool_handle = queue::WorkerPool::spawn(job_queue, config.clone());
+    tracing::info!(workers = config.queue.workers, "Queue workers running");
+
+    shutdown_signal().await?;
+    tracing::info!("Shutdown requested, waiting for in-flight jobs");
+    pool_handle.shutdown().await;
+
+    Ok(())
+}
+
+/// Run `analyze` and list the files written on stdout
+async fn run_analyze(config: &Config, args: AnalyzeArgs) -> anyhow::Result<()> {
+    let formula = args
+        .formula
+        .as_deref()
+        .map(|spec| oxidized_bio::analysis::formula::Formula::resolve(spec, &args.target))
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+    let thresholds = oxidized_bio::analysis::stats::Thresholds {
+        alpha: args.alpha,
+        fdr: args.fdr,
+        min_effect: args.min_effect,
+    };
+    thresholds.validate().map_err(anyhow::Error::msg)?;
+    let regularization = oxidized_bio::analysis::regularized::Regularization {
+        penalty: args.penalty,
+        l1_ratio: args.l1_ratio,
+        lambda: args.lambda,
+        folds: args.penalty_folds,
+    };
+    regularization.validate().map_err(anyhow::Error::msg)?;
+    oxidized_bio::analysis::threads::validate(args.threads).map_err(anyhow::Error::msg)?;
+    let parsing = args.parsing();
+    parsing.validate().map_err(anyhow::Error::msg)?;
+    let options = oxidized_bio::headless::AnalyzeOptions {
+        input: args.input,
+        target: args.target,
+        group: args.group,
+        covariates: args.covariates,
+        formula,
+        boxplot: args.boxplot,
+        markers: args.markers,
+        out: args.out,
+        max_columns: args.max_columns,
+        max_groups: args.max_groups,
+        platform: args.platform,
+        samples: args.samples,
+        collapse: args.collapse,
+        replicates: args.replicates,
+        imputation: args.impute,
+        normalization: args.normalize,
+        batch: args.batch,
+        batch_correction: args.batch_correction,
+        correlation: args.correlation,
+        tables: args.tables,
+        numbers: args.numbers,
+        thresholds,
+        kruskal_wallis: args.kruskal_wallis,
+        memory_limit: args.memory_limit,
+        threads: args.threads,
+        regularization,
+        roc: args.roc_split,
+        gene_sets: args.gene_sets,
+        survival: args.time_column.zip(args.event_column),
+        subject: args.subject_column,
+        parsing,
+        species: args.species,
+        cross_reference: args.cross_reference.unwrap_or(config.cross_reference.top_n),
+        template: args.template.unwrap_or(config.manuscript.template),
+        panel: args.panel.map(|max_size| oxidized_bio::analysis::panel::PanelOptions {
+            max_size,
+            folds: args.panel_folds,
+            ..Default::default()
+        }),
+    };
+    let files = oxidized_bio::headless::analyze(config, &options).await?;
+    for file in files {
+        println!("{}", file.display());
+    }
+    Ok(())
+}
 
-    run_tui(config).await
+/// Run `search` and print the results on stdout
+async fn run_search(config: &Config, args: SearchArgs) -> anyhow::Result<()> {
+    let engines = args
+        .engines
+        .as_deref()
+        .map(oxidized_bio::search::Engine::parse_list)
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+    let results = oxidized_bio::headless::search(config, &args.query, engines).await?;
+    println!("{}", oxidized_bio::headless::format_search_results(&results, args.format)?);
+    Ok(())
 }
 
-/// Run in TUI mode (default)
-async fn run_tui(config: Config) -> anyhow::Result<()> {
+/// Run a workflow file and list the files written on stdout
+async fn run_pipeline(config: &Config, workflow: &std::path::Path) -> anyhow::Result<()> {
+    let spec = oxidized_bio::pipeline::WorkflowSpec::load(workflow)?;
+    let files = oxidized_bio::pipeline::run(&spec, config).await?;
+    for file in files {
+        println!("{}", file.display());
+    }
+    Ok(())
+}
+
+/// Write a demo dataset and list the files written on stdout
+fn run_demo_data(args: DemoDataArgs) -> anyhow::Result<()> {
+    let options = oxidized_bio::demo::DemoOptions {
+        rows: args.rows,
+        markers: args.markers,
+        effect: args.effect,
+        planted: args.planted,
+        species: args.species,
+        seed: args.seed,
+    };
+    let dataset = oxidized_bio::demo::generate(&options)?;
+    for file in oxidized_bio::demo::write(&dataset, &args.out)? {
+        println!("{}", file.display());
+    }
+    Ok(())
+}
+
+/// Wait for Ctrl+C, or SIGTERM from `docker stop` / orchestrators
+async fn shutdown_signal() -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = sigterm.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Run in TUI mode (default), with the HTTP/RFC server alongside when
+/// `serve` is set (to `Some(None)` for the default address)
+async fn run_tui(config: Config, serve: Option<Option<String>>) -> anyhow::Result<()> {
     // For TUI mode, we don't need the database connection at startup
     // The agents will use the config to make API calls directly
+    let dataset_registry = DatasetRegistry::default();
+    let events = EventBus::default();
+    let live_config = LiveConfig::new(config);
+    if let Some(addr) = serve {
+        let addr = addr.unwrap_or_else(|| format!("127.0.0.1:{}", live_config.current().server.port));
+        spawn_server(&live_config, &addr, dataset_registry.clone(), events.clone()).await?;
+    }
 
     // Run the TUI
-    let result = tui::run(config).await;
+    let result = tui::run(live_config, dataset_registry, events).await;
 
     // Handle any errors from TUI
     if let Err(ref e) = result {
@@ -119,4 +609,38 @@ async fn run_tui(config: Config) -> anyhow::Result<()> {
     result
 }
 
-// Server mode removed. This build runs as a single-user TUI application.
+/// Serve the HTTP/RFC API on `addr` in the background, sharing the TUI's
+/// config, dataset registry and event bus. The database is optional: without
+/// DATABASE_URL the pool connects lazily and only the routes that need it
+/// fail.
+async fn spawn_server(
+    live_config: &LiveConfig,
+    addr: &str,
+    dataset_registry: DatasetRegistry,
+    events: EventBus,
+) -> anyhow::Result<()> {
+    let config = live_config.current();
+    let pool = if config.database.url.is_empty() {
+        sqlx::postgres::PgPoolOptions::new().connect_lazy("postgres://localhost/oxidized_bio")?
+    } else {
+        db::create_pool(&config.database).await?
+    };
+    let state = oxidized_bio::AppState {
+        pool,
+        config: live_config.clone(),
+        dataset_registry,
+        events,
+    };
+    let router = oxidized_bio::middleware::apply_cors(oxidized_bio::create_router(state));
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to bind {}: {}", addr, e))?;
+    tracing::info!(addr = %addr, "Serving the HTTP/RFC API alongside the TUI");
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            tracing::error!(error = %e, "HTTP server stopped");
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,978 @@
+//! Manuscript drafts and LaTeX rendering
+//!
+//! Shared by the TUI's guided workflow and the headless pipeline runner.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+pub mod csl;
+mod docx;
+pub mod history;
+pub mod pdf;
+pub mod template;
+
+pub use csl::CitationStyle;
+pub use history::{DraftHistory, DraftRecord};
+pub use template::JournalTemplate;
+
+use crate::agents::{LiteratureResult, PlanningResult, SourceReference};
+use crate::analysis::legends::{self, Figure, FigureLegend};
+use crate::analysis::stats::Thresholds;
+use crate::analysis::AnalysisArtifacts;
+use crate::models::BiomarkerCandidate;
+use crate::queue::alerts::paper_key;
+use crate::search::bibtex::{self, CiteKeys};
+use crate::utils::number::NumberFormat;
+
+/// Name of the BibTeX file next to `manuscript.tex`
+pub const BIB_FILE: &str = "references.bib";
+
+/// Markdown and Word exports of the current draft, with CSL citations
+pub const MARKDOWN_FILE: &str = "draft.md";
+pub const DOCX_FILE: &str = "draft.docx";
+
+/// Sources listed per draft under "Key sources"
+const DRAFT_SOURCES: usize = 8;
+
+/// Titles shorter than this are not matched in prose (too ambiguous)
+const MIN_TITLE_MATCH: usize = 12;
+
+/// Template draft for the automated workflow (no researcher feedback)
+pub fn automated_draft(
+    version: usize,
+    manuscript: &str,
+    plan: Option<&PlanningResult>,
+    literature_results: &[LiteratureResult],
+) -> String {
+    let literature = literature_section(literature_results);
+    let plan = plan
+        .map(|p| p.current_objective.as_str())
+        .unwrap_or("No research plan generated.");
+    format!("Draft {version}\n\n{manuscript}\n\nResearch Plan:\n{plan}\n\nLiterature Review:\n{literature}\n")
+}
+
+/// "Literature Review" body: the literature tasks plus the verified sources
+/// they found, listed by title so [`render_latex`] can cite them
+pub fn literature_section(literature_results: &[LiteratureResult]) -> String {
+    if literature_results.is_empty() {
+        return "No literature sources available.".to_string();
+    }
+    let items = literature_results
+        .iter()
+        .take(5)
+        .map(|r| r.objective.clone())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut section = format!("Key literature tasks: {}", items);
+
+    let bibliography = Bibliography::from_literature(literature_results);
+    if !bibliography.is_empty() {
+        section.push_str("\nKey sources:");
+        for citation in bibliography.citations().iter().take(DRAFT_SOURCES) {
+            let source = &citation.source;
+            let byline = match (source.authors.as_deref(), source.year) {
+                (Some(authors), Some(year)) => format!(" ({}, {})", authors, year),
+                (Some(authors), None) => format!(" ({})", authors),
+                (None, Some(year)) => format!(" ({})", year),
+                (None, None) => String::new(),
+            };
+            let quality = source.quality.map(|q| format!(" [quality {:.2}]", q)).unwrap_or_default();
+            section.push_str(&format!("\n- {}{}.{}", source.title.trim_end_matches('.'), byline, quality));
+        }
+    }
+    section
+}
+
+/// A source with its cite key
+#[derive(Debug, Clone)]
+pub struct Citation {
+    pub key: String,
+    pub source: SourceReference,
+}
+
+/// Verified literature sources (those with a DOI or URL), deduplicated and
+/// keyed for BibTeX
+#[derive(Debug, Clone, Default)]
+pub struct Bibliography {
+    citations: Vec<Citation>,
+}
+
+impl Bibliography {
+    pub fn from_literature(results: &[LiteratureResult]) -> Self {
+        let mut keys = CiteKeys::new();
+        let mut seen = HashSet::new();
+        let citations = results
+            .iter()
+            .flat_map(|r| &r.sources)
+            .filter(|s| is_verified(s) && seen.insert(paper_key(s)))
+            .map(|source| Citation {
+                key: keys.key(source.authors.as_deref(), source.year, &source.title),
+                source: source.clone(),
+            })
+            .collect();
+        Self { citations }
+    }
+
+    pub fn citations(&self) -> &[Citation] {
+        &self.citations
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.citations.is_empty()
+    }
+
+    /// Contents of `references.bib`
+    pub fn to_bibtex(&self) -> String {
+        self.citations
+            .iter()
+            .map(|c| bibtex::source_entry(&c.key, &c.source))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Add `\citep{}` to the sentences of an escaped LaTeX line that mention
+    /// a source by title, DOI or "Surname et al."
+    pub fn cite_line(&self, line: &str) -> String {
+        let points = self.citation_points(line, true);
+        insert_citations(
+            line,
+            points.into_iter().map(|(position, keys)| (position, format!("\\citep{{{}}}", keys.join(",")))),
+        )
+    }
+
+    /// Where `line` cites sources: the offset before the period of each
+    /// sentence that mentions one, with the keys cited there, in line order.
+    /// With `latex`, titles and DOIs are matched in their escaped form.
+    fn citation_points(&self, line: &str, latex: bool) -> Vec<(usize, Vec<&str>)> {
+        // ASCII lowercasing keeps byte offsets valid for `line`
+        let lower = line.to_ascii_lowercase();
+        let mut inserts: Vec<(usize, &str)> = self
+            .citations
+            .iter()
+            .filter_map(|c| {
+                let end = mention_end(&lower, &c.source, latex)?;
+                Some((sentence_end(line, end), c.key.as_str()))
+            })
+            .collect();
+        inserts.sort();
+
+        let mut points: Vec<(usize, Vec<&str>)> = Vec::new();
+        for (position, key) in inserts {
+            match points.last_mut() {
+                Some((last, keys)) if *last == position => keys.push(key),
+                _ => points.push((position, vec![key])),
+            }
+        }
+        points
+    }
+}
+
+/// Insert each citation text, preceded by a space, at its offset in `line`
+fn insert_citations(line: &str, citations: impl IntoIterator<Item = (usize, String)>) -> String {
+    let mut out = String::with_capacity(line.len() + 32);
+    let mut cursor = 0;
+    for (position, text) in citations {
+        out.push_str(line[cursor..position].trim_end());
+        out.push(' ');
+        out.push_str(&text);
+        cursor = position;
+    }
+    out.push_str(&line[cursor..]);
+    out
+}
+
+fn is_verified(source: &SourceReference) -> bool {
+    let present = |v: &Option<String>| v.as_deref().is_some_and(|v| !v.trim().is_empty());
+    present(&source.doi) || present(&source.url)
+}
+
+/// Byte offset just past the first mention of `source` in `lower`, matching
+/// LaTeX-escaped titles and DOIs when `latex`
+fn mention_end(lower: &str, source: &SourceReference, latex: bool) -> Option<usize> {
+    let text = |s: &str| (if latex { bibtex::escape(s) } else { s.to_string() }).to_ascii_lowercase();
+    let title = text(source.title.trim().trim_end_matches('.'));
+    let mut patterns = Vec::new();
+    if title.chars().count() >= MIN_TITLE_MATCH {
+        patterns.push(title);
+    }
+    if let Some(doi) = source.doi.as_deref().filter(|d| !d.trim().is_empty()) {
+        patterns.push(text(doi.trim()));
+    }
+    if let Some(surname) = source.authors.as_deref().and_then(bibtex::first_surname) {
+        patterns.push(format!("{} et al.", surname));
+    }
+    patterns
+        .iter()
+        .filter_map(|p| lower.find(p.as_str()).map(|start| start + p.len()))
+        .min()
+}
+
+/// Offset of the period ending the sentence that continues past `from`, or
+/// the end of the line
+fn sentence_end(line: &str, from: usize) -> usize {
+    let rest = &line[from..];
+    let mut chars = rest.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?') && chars.peek().map_or(true, |(_, next)| next.is_whitespace()) {
+            return from + i;
+        }
+    }
+    from + rest.trim_end().len()
+}
+
+/// Section headings recognised in plain-text and markdown drafts
+const SECTION_HEADINGS: &[&str] = &[
+    "Abstract",
+    "Introduction",
+    "Background",
+    "Methods",
+    "Materials and Methods",
+    "Experimental Procedures",
+    "Results",
+    "Discussion",
+    "Conclusion",
+    "Conclusions",
+    "Limitations",
+    "Supplementary Materials",
+    "Supporting Information",
+    "Research Plan",
+    "Literature Review",
+    "Feedback Incorporated",
+];
+
+/// Biomarkers listed in the LaTeX results table
+const TABLE_BIOMARKERS: usize = 10;
+
+enum Block {
+    Paragraph(String),
+    List(Vec<String>),
+}
+
+struct Section {
+    heading: Option<String>,
+    blocks: Vec<Block>,
+}
+
+/// A draft split into title, project ID and sections
+struct DraftDocument {
+    title: Option<String>,
+    project_id: Option<String>,
+    sections: Vec<Section>,
+}
+
+impl DraftDocument {
+    fn parse(draft: &str) -> Self {
+        let mut doc = Self {
+            title: None,
+            project_id: None,
+            sections: vec![Section { heading: None, blocks: Vec::new() }],
+        };
+        let mut paragraph: Vec<&str> = Vec::new();
+        let mut list: Vec<String> = Vec::new();
+
+        for line in draft.lines().map(str::trim) {
+            let is_item = line.starts_with("- ") || line.starts_with("* ");
+            if line.is_empty() || is_item || !list.is_empty() {
+                doc.flush(&mut paragraph, &mut list, is_item);
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if is_item {
+                list.push(line[2..].trim().to_string());
+            } else if let Some(title) = line.strip_prefix("Title:") {
+                doc.flush(&mut paragraph, &mut list, false);
+                doc.title = Some(title.trim().to_string());
+            } else if let Some(id) = line.strip_prefix("Project ID:") {
+                doc.flush(&mut paragraph, &mut list, false);
+                doc.project_id = Some(id.trim().to_string());
+            } else if is_version_line(line) {
+                doc.flush(&mut paragraph, &mut list, false);
+            } else if let Some(heading) = section_heading(line) {
+                doc.flush(&mut paragraph, &mut list, false);
+                doc.sections.push(Section { heading: Some(heading), blocks: Vec::new() });
+            } else {
+                paragraph.push(line);
+            }
+        }
+        doc.flush(&mut paragraph, &mut list, false);
+        doc
+    }
+
+    /// Close the open paragraph, and the open list unless `keep_list`
+    fn flush(&mut self, paragraph: &mut Vec<&str>, list: &mut Vec<String>, keep_list: bool) {
+        let blocks = &mut self.sections.last_mut().expect("at least one section").blocks;
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(paragraph.join(" ")));
+            paragraph.clear();
+        }
+        if !keep_list && !list.is_empty() {
+            blocks.push(Block::List(std::mem::take(list)));
+        }
+    }
+
+    /// Reorder sections and rename headings for `template`. The unheaded
+    /// opening section stays first; unknown sections keep their relative
+    /// order after the template's own.
+    fn apply_template(&mut self, template: JournalTemplate) {
+        let opening = self.sections.remove(0);
+        self.sections
+            .sort_by_key(|s| s.heading.as_deref().map_or(usize::MAX, |h| template.rank(h)));
+        for section in &mut self.sections {
+            let renamed = section
+                .heading
+                .as_deref()
+                .and_then(template::canonical_section)
+                .and_then(|c| template.heading(c));
+            if let Some(heading) = renamed {
+                section.heading = Some(heading.to_string());
+            }
+        }
+        self.sections.insert(0, opening);
+    }
+
+    fn word_limit_warnings(&self, template: JournalTemplate) -> Vec<String> {
+        let texts: Vec<(&str, String)> = self
+            .sections
+            .iter()
+            .filter_map(|s| Some((s.heading.as_deref()?, s.text())))
+            .collect();
+        template.word_limit_warnings(texts.iter().map(|(h, t)| (*h, t.as_str())))
+    }
+}
+
+impl DraftDocument {
+    /// Every paragraph and list item, in document order
+    fn texts_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.sections
+            .iter_mut()
+            .flat_map(|s| s.blocks.iter_mut())
+            .flat_map(|b| match b {
+                Block::Paragraph(text) => std::slice::from_mut(text).iter_mut(),
+                Block::List(items) => items.iter_mut(),
+            })
+    }
+}
+
+impl Section {
+    /// Plain text of the section's paragraphs and list items
+    fn text(&self) -> String {
+        self.blocks
+            .iter()
+            .flat_map(|b| match b {
+                Block::Paragraph(text) => vec![text.as_str()],
+                Block::List(items) => items.iter().map(String::as_str).collect(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// "Draft 2" header added by the drafting workflow
+fn is_version_line(line: &str) -> bool {
+    line.strip_prefix("Draft ")
+        .is_some_and(|rest| rest.trim().chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Heading text for markdown headings ("## Methods") and known section
+/// names ("Methods", "Research Plan:", "**Results**")
+fn section_heading(line: &str) -> Option<String> {
+    let markdown = line.starts_with('#');
+    let text = line
+        .trim_start_matches('#')
+        .trim()
+        .trim_matches('*')
+        .trim_end_matches(':')
+        .trim();
+    if text.is_empty() {
+        return None;
+    }
+    if markdown || SECTION_HEADINGS.iter().any(|h| h.eq_ignore_ascii_case(text)) {
+        Some(text.to_string())
+    } else {
+        None
+    }
+}
+
+/// Escape prose for LaTeX, converting markdown `**bold**` and adding citations
+fn latex_text(text: &str, bibliography: &Bibliography) -> String {
+    let escaped = bibliography.cite_line(&bibtex::escape(text));
+    // Only balanced markers become bold; a stray ** is dropped
+    if escaped.matches("**").count() % 2 == 1 {
+        return escaped.replace("**", "");
+    }
+    escaped
+        .split("**")
+        .enumerate()
+        .map(|(i, part)| if i % 2 == 1 { format!("\\textbf{{{}}}", part) } else { part.to_string() })
+        .collect()
+}
+
+/// A formatted number for a table cell, with the bound and the French
+/// thousands separator written for LaTeX
+fn latex_number(formatted: String) -> String {
+    formatted.replace('<', "$<$").replace('\u{202f}', "\\,")
+}
+
+/// booktabs table of the top biomarker candidates, with an AUC column when
+/// the target was binary or split and a literature column once they have
+/// been cross-referenced. Markers passing the `thresholds` are set in bold.
+fn biomarker_table(
+    candidates: &[BiomarkerCandidate],
+    ranking: &str,
+    thresholds: &Thresholds,
+    numbers: NumberFormat,
+) -> String {
+    let shown = &candidates[..candidates.len().min(TABLE_BIOMARKERS)];
+    let literature = shown.iter().any(|c| c.prior_evidence.is_some());
+    let roc = shown.iter().any(|c| c.auc.is_some());
+    let mut table = String::new();
+    table.push_str("\\begin{table}[htbp]\n\\centering\n");
+    table.push_str(&format!(
+        "\\caption{{Top biomarker candidates ranked by absolute {} with the target. \
+         $q$ values are Benjamini--Hochberg adjusted; markers with {} in bold.{}}}\n",
+        ranking,
+        legends::latex_escape(&thresholds.marker_criterion()),
+        if roc { " AUC is the area under the ROC curve for the two classes of the target." } else { "" }
+    ));
+    table.push_str("\\label{tab:biomarkers}\n");
+    let (mut columns, mut header) = ("lrrrr".to_string(), "Marker & $n$ & $r$ & $p$ & $q$".to_string());
+    if roc {
+        columns.push('r');
+        header.push_str(" & AUC");
+    }
+    columns.push('l');
+    header.push_str(" & Direction");
+    if literature {
+        columns.push('l');
+        header.push_str(" & Literature");
+    }
+    table.push_str(&format!("\\begin{{tabular}}{{{}}}\n\\toprule\n", columns));
+    table.push_str(&format!("{} \\\\\n\\midrule\n", header));
+    for candidate in shown {
+        let evidence = match (&candidate.prior_evidence, literature) {
+            (_, false) => String::new(),
+            (Some(e), true) => format!(" & {} ({})", e.status.describe(), e.publications),
+            (None, true) => " & --".to_string(),
+        };
+        let label = bibtex::escape(&candidate.display_label());
+        let label = if thresholds.passes(candidate.p_value, candidate.q_value, candidate.correlation) {
+            format!("\\textbf{{{}}}", label)
+        } else {
+            label
+        };
+        let auc = match (roc, candidate.auc) {
+            (false, _) => String::new(),
+            (true, Some(auc)) => format!(" & {}", latex_number(numbers.fixed(auc, 3))),
+            (true, None) => " & --".to_string(),
+        };
+        table.push_str(&format!(
+            "{} & {} & {} & {} & {}{} & {}{} \\\\\n",
+            label,
+            latex_number(numbers.integer(candidate.n)),
+            latex_number(numbers.fixed(candidate.correlation, 3)),
+            latex_number(numbers.p_value(candidate.p_value)),
+            latex_number(numbers.p_value(candidate.q_value)),
+            auc,
+            candidate.direction,
+            evidence
+        ));
+    }
+    table.push_str("\\bottomrule\n\\end{tabular}\n\\end{table}\n\n");
+    table
+}
+
+fn figure(path: &str, caption: &str, label: &str) -> String {
+    let file = Path::new(path)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+    format!(
+        "\\begin{{figure}}[htbp]\n\\centering\n\\includegraphics[width=0.9\\linewidth]{{{}}}\n\\caption{{{}}}\n\\label{{{}}}\n\\end{{figure}}\n\n",
+        file, caption, label
+    )
+}
+
+/// Results table and figures for `analysis`, with a sentence pointing to them.
+/// Figures are captioned with their generated legends.
+fn results_floats(analysis: &AnalysisArtifacts) -> String {
+    let mut references = Vec::new();
+    let mut floats = String::new();
+    if !analysis.biomarker_candidates.is_empty() {
+        references.push("Table~\\ref{tab:biomarkers} lists the top biomarker candidates".to_string());
+        floats.push_str(&biomarker_table(
+            &analysis.biomarker_candidates,
+            &analysis.ranking(),
+            &analysis.thresholds,
+            analysis.numbers,
+        ));
+    }
+    let enrichment = format!(
+        "GO terms enriched among biomarkers with {} ($-\\log_{{10}} p$, hypergeometric test; solid bars: {}).",
+        legends::latex_escape(&analysis.thresholds.marker_criterion()),
+        legends::latex_escape(&analysis.thresholds.criterion())
+    );
+    let figures = [
+        (Figure::Heatmap, &analysis.heatmap_path, "shows the marker heatmap", "Heatmap of the analyzed markers."),
+        (
+            Figure::Expression,
+            &analysis.expression_heatmap_path,
+            "shows the top markers across samples",
+            "Row-scaled expression of the top markers across samples.",
+        ),
+        (Figure::Boxplot, &analysis.boxplot_path, "shows the distribution by group", "Marker distribution by group."),
+        (
+            Figure::Volcano,
+            &analysis.volcano_path,
+            "shows the markers differing between the two groups",
+            "Difference of means against Welch p-value between the two groups.",
+        ),
+        (
+            Figure::Roc,
+            &analysis.roc_path,
+            "shows the ROC curves of the candidates that best separate the two classes of the target",
+            "ROC curves of the best-discriminating candidates.",
+        ),
+        (
+            Figure::Survival,
+            &analysis.survival_path,
+            "shows the Kaplan-Meier survival curves",
+            "Kaplan-Meier survival curves; crosses mark censored samples.",
+        ),
+        (
+            Figure::Enrichment,
+            &analysis.enrichment_plot_path,
+            "shows the enriched GO terms",
+            enrichment.as_str(),
+        ),
+        (
+            Figure::Scree,
+            &analysis.pca_scree_path,
+            "shows the variance explained by the principal components",
+            "Variance explained by the principal components.",
+        ),
+        (
+            Figure::Scores,
+            &analysis.pca_scores_path,
+            "shows the samples on the first two principal components",
+            "Samples on the first two principal components.",
+        ),
+    ];
+    for (kind, path, reference, fallback) in figures {
+        let Some(path) = path else { continue };
+        let caption = analysis
+            .figure_legends
+            .iter()
+            .find(|legend| legend.figure == kind)
+            .map(FigureLegend::to_latex)
+            .unwrap_or_else(|| fallback.to_string());
+        references.push(format!("Figure~\\ref{{{}}} {}", kind.latex_label(), reference));
+        floats.push_str(&figure(path, &caption, kind.latex_label()));
+    }
+    if references.is_empty() {
+        return String::new();
+    }
+    format!("{}.\n\n{}", references.join("; "), floats)
+}
+
+/// Render a draft as a compilable LaTeX manuscript: title block, abstract,
+/// sections, the biomarker table and figures from `analysis` after the
+/// Results section, and natbib citations of `bibliography` sources.
+///
+/// `template` sets the document class, section order and headings, and the
+/// citation style; word-limit overruns are noted as comments in the preamble.
+/// Figures are referenced by file name; [`export_latex`] places them next to
+/// the `.tex` file.
+pub fn render_latex(
+    draft: &str,
+    bibliography: &Bibliography,
+    analysis: Option<&AnalysisArtifacts>,
+    template: JournalTemplate,
+) -> String {
+    let mut doc = DraftDocument::parse(draft);
+    doc.apply_template(template);
+
+    let mut latex = String::new();
+    latex.push_str(&format!("% Template: {}\n", template.name()));
+    for warning in doc.word_limit_warnings(template) {
+        latex.push_str(&format!("% Warning: {}\n", warning));
+    }
+    latex.push_str(template.latex_class());
+    latex.push_str("\\usepackage{graphicx}\n");
+    latex.push_str("\\usepackage{booktabs}\n");
+    latex.push_str("\\usepackage{url}\n");
+    if template.line_numbers() {
+        latex.push_str("\\usepackage{lineno}\n");
+    }
+    latex.push_str(&format!("\\usepackage[{}]{{natbib}}\n\n", template.natbib_options()));
+    let title = doc.title.as_deref().unwrap_or("Research Manuscript");
+    latex.push_str(&format!("\\title{{{}}}\n", bibtex::escape(title)));
+    latex.push_str("\\author{Oxidized Bio}\n");
+    match &doc.project_id {
+        Some(id) => latex.push_str(&format!("\\date{{Project ID: {}}}\n\n", bibtex::escape(id))),
+        None => latex.push_str("\\date{\\today}\n\n"),
+    }
+    latex.push_str("\\begin{document}\n\\maketitle\n");
+    if template.line_numbers() {
+        latex.push_str("\\linenumbers\n");
+    }
+    latex.push('\n');
+
+    let mut floats = analysis.map(results_floats).unwrap_or_default();
+    for section in &doc.sections {
+        let canonical = section.heading.as_deref().and_then(template::canonical_section);
+        let abstract_section = canonical == Some("abstract");
+        match &section.heading {
+            Some(_) if abstract_section => latex.push_str("\\begin{abstract}\n"),
+            Some(heading) => latex.push_str(&format!("\\section{{{}}}\n", bibtex::escape(heading))),
+            None => {}
+        }
+        for block in &section.blocks {
+            match block {
+                Block::Paragraph(text) => {
+                    latex.push_str(&latex_text(text, bibliography));
+                    latex.push_str("\n\n");
+                }
+                Block::List(items) => {
+                    latex.push_str("\\begin{itemize}\n");
+                    for item in items {
+                        latex.push_str(&format!("  \\item {}\n", latex_text(item, bibliography)));
+                    }
+                    latex.push_str("\\end{itemize}\n\n");
+                }
+            }
+        }
+        if abstract_section {
+            latex.push_str("\\end{abstract}\n\n");
+        }
+        if canonical == Some("results") {
+            latex.push_str(&std::mem::take(&mut floats));
+        }
+    }
+    // Drafts without a Results section get the floats at the end
+    latex.push_str(&floats);
+
+    if !bibliography.is_empty() {
+        // List every verified source, including ones the draft no longer mentions
+        latex.push_str("\\nocite{*}\n");
+        latex.push_str(&format!("\\bibliographystyle{{{}}}\n", template.bibliography_style()));
+        latex.push_str(&format!("\\bibliography{{{}}}\n", BIB_FILE.trim_end_matches(".bib")));
+    }
+    latex.push_str("\\end{document}\n");
+    latex
+}
+
+/// Word-limit overruns of `draft` under `template`
+pub fn word_limit_warnings(draft: &str, template: JournalTemplate) -> Vec<String> {
+    DraftDocument::parse(draft).word_limit_warnings(template)
+}
+
+/// Write `manuscript.tex`, `references.bib` (when there are sources) and
+/// copies of the analysis figures to `dir`. Returns the files written.
+pub fn export_latex(
+    dir: &Path,
+    draft: &str,
+    literature_results: &[LiteratureResult],
+    analysis: Option<&AnalysisArtifacts>,
+    template: JournalTemplate,
+) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let bibliography = Bibliography::from_literature(literature_results);
+
+    let tex = dir.join(pdf::TEX_FILE);
+    std::fs::write(&tex, render_latex(draft, &bibliography, analysis, template))?;
+    let mut written = vec![tex];
+    if !bibliography.is_empty() {
+        let bib = dir.join(BIB_FILE);
+        std::fs::write(&bib, bibliography.to_bibtex())?;
+        written.push(bib);
+    }
+
+    let figures = analysis.into_iter().flat_map(|a| a.figure_paths()).map(Path::new);
+    for figure in figures {
+        let Some(name) = figure.file_name() else { continue };
+        let target = dir.join(name);
+        if figure.parent() != Some(dir) && figure.is_file() {
+            std::fs::copy(figure, &target)
+                .with_context(|| format!("Failed to copy figure {}", figure.display()))?;
+            written.push(target);
+        }
+    }
+    Ok(written)
+}
+
+/// Parse `draft` for `template` and add in-text citations in `style`.
+/// Returns the document and its reference list.
+fn cite_draft(
+    draft: &str,
+    bibliography: &Bibliography,
+    style: &CitationStyle,
+    template: JournalTemplate,
+) -> Result<(DraftDocument, Vec<String>)> {
+    let mut doc = DraftDocument::parse(draft);
+    doc.apply_template(template);
+    if bibliography.is_empty() {
+        return Ok((doc, Vec::new()));
+    }
+
+    let points: Vec<Vec<(usize, Vec<&str>)>> =
+        doc.texts_mut().map(|text| bibliography.citation_points(text, false)).collect();
+    let groups: Vec<Vec<&str>> = points.iter().flatten().map(|(_, keys)| keys.clone()).collect();
+    let formatted = csl::format(bibliography, &groups, &style.load()?)?;
+
+    let mut citations = formatted.citations.into_iter();
+    for (text, points) in doc.texts_mut().zip(&points) {
+        let rendered: Vec<(usize, String)> = points
+            .iter()
+            .map(|(position, _)| (*position, citations.next().unwrap_or_default()))
+            .collect();
+        *text = insert_citations(text, rendered);
+    }
+    Ok((doc, formatted.references))
+}
+
+/// Render a draft as Markdown with CSL citations and a References section
+pub fn render_markdown(
+    draft: &str,
+    bibliography: &Bibliography,
+    style: &CitationStyle,
+    template: JournalTemplate,
+) -> Result<String> {
+    let (doc, references) = cite_draft(draft, bibliography, style, template)?;
+    let mut markdown = format!("# {}\n\n", doc.title.as_deref().unwrap_or("Research Manuscript"));
+    if let Some(id) = &doc.project_id {
+        markdown.push_str(&format!("Project ID: {}\n\n", id));
+    }
+    for section in &doc.sections {
+        if let Some(heading) = &section.heading {
+            markdown.push_str(&format!("## {}\n\n", heading));
+        }
+        for block in &section.blocks {
+            match block {
+                Block::Paragraph(text) => markdown.push_str(&format!("{}\n\n", text)),
+                Block::List(items) => {
+                    for item in items {
+                        markdown.push_str(&format!("- {}\n", item));
+                    }
+                    markdown.push('\n');
+                }
+            }
+        }
+    }
+    if !references.is_empty() {
+        markdown.push_str("## References\n\n");
+        for reference in references {
+            markdown.push_str(&format!("{}\n\n", reference));
+        }
+    }
+    Ok(markdown)
+}
+
+/// Write `draft.md` to `dir`, citing verified sources in `style`
+pub fn export_markdown(
+    dir: &Path,
+    draft: &str,
+    literature_results: &[LiteratureResult],
+    style: &CitationStyle,
+    template: JournalTemplate,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let bibliography = Bibliography::from_literature(literature_results);
+    let path = dir.join(MARKDOWN_FILE);
+    std::fs::write(&path, render_markdown(draft, &bibliography, style, template)?)?;
+    Ok(path)
+}
+
+/// Write `draft.docx` to `dir`, citing verified sources in `style`, with the
+/// legends of the `analysis` figures at the end
+pub fn export_docx(
+    dir: &Path,
+    draft: &str,
+    literature_results: &[LiteratureResult],
+    analysis: Option<&AnalysisArtifacts>,
+    style: &CitationStyle,
+    template: JournalTemplate,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let bibliography = Bibliography::from_literature(literature_results);
+    let (doc, references) = cite_draft(draft, &bibliography, style, template)?;
+    let path = dir.join(DOCX_FILE);
+    let legends = analysis.map(|a| a.figure_legends.as_slice()).unwrap_or_default();
+    docx::write(&path, &doc, &references, legends)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literature() -> Vec<LiteratureResult> {
+        let source = |title: &str, authors: Option<&str>, doi: Option<&str>| SourceReference {
+            title: title.to_string(),
+            authors: authors.map(str::to_string),
+            year: Some(2018),
+            doi: doi.map(str::to_string),
+            url: None,
+            summary: String::new(),
+            quality: Some(0.8),
+        };
+        vec![LiteratureResult {
+            task_id: "t1".to_string(),
+            objective: "Epigenetic clocks".to_string(),
+            findings: String::new(),
+            sources: vec![
+                source("DNA methylation-based biomarkers of aging", Some("Horvath S, Raj K"), Some("10.1038/x")),
+                source("DNA methylation-based biomarkers of aging", Some("Horvath S"), Some("10.1038/X")),
+                source("Unverified claim about aging", None, None),
+            ],
+            key_insights: Vec::new(),
+            queries: Vec::new(),
+        }]
+    }
+
+    #[test]
+    fn test_bibliography_keeps_verified_unique_sources() {
+        let bibliography = Bibliography::from_literature(&literature());
+        assert_eq!(bibliography.citations().len(), 1);
+        assert_eq!(bibliography.citations()[0].key, "horvath2018dna");
+        assert!(bibliography.to_bibtex().starts_with("@article{horvath2018dna,"));
+    }
+
+    #[test]
+    fn test_cite_line_inserts_before_period() {
+        let bibliography = Bibliography::from_literature(&literature());
+        assert_eq!(
+            bibliography.cite_line("As Horvath et al. showed, clocks tick. Next sentence."),
+            "As Horvath et al. showed, clocks tick \\citep{horvath2018dna}. Next sentence."
+        );
+        assert_eq!(bibliography.cite_line("Nothing to cite here."), "Nothing to cite here.");
+    }
+
+    #[test]
+    fn test_render_latex_cites_draft_sources() {
+        let literature = literature();
+        let draft = automated_draft(1, "Title: Aging", None, &literature);
+        let latex = render_latex(&draft, &Bibliography::from_literature(&literature), None, JournalTemplate::Generic);
+        assert!(latex.contains("\\usepackage[numbers,sort&compress]{natbib}"));
+        assert!(latex.contains("biomarkers of aging (Horvath S, Raj K, 2018) \\citep{horvath2018dna}."));
+        assert!(latex.contains("\\bibliography{references}"));
+    }
+
+    #[test]
+    fn test_render_markdown_csl() {
+        let literature = literature();
+        let draft = automated_draft(1, "Title: Aging\n\nResults\nHorvath et al. describe clocks.", None, &literature);
+        let style = "vancouver".parse::<CitationStyle>().unwrap();
+        let markdown =
+            render_markdown(&draft, &Bibliography::from_literature(&literature), &style, JournalTemplate::Generic)
+                .unwrap();
+
+        assert!(markdown.starts_with("# Aging\n\n## Results\n\nHorvath et al. describe clocks "));
+        assert!(!markdown.contains("\\citep"));
+        let references = &markdown[markdown.find("## References").unwrap()..];
+        assert!(references.contains("DNA methylation-based biomarkers of aging"));
+    }
+
+    #[test]
+    fn test_render_latex_structure() {
+        let analysis = AnalysisArtifacts {
+            descriptive_stats: Vec::new(),
+            regressions: Vec::new(),
+            novelty_scores: Vec::new(),
+            group_comparisons: Vec::new(),
+            contrast: None,
+            anova: Vec::new(),
+            correlation: Default::default(),
+            thresholds: Default::default(),
+            numbers: Default::default(),
+            biomarker_candidates: vec![BiomarkerCandidate {
+                column: "gene_a".to_string(),
+                symbol: None,
+                biotype: None,
+                human_ortholog: None,
+                score: 0.91,
+                correlation: 0.91,
+                n: 40,
+                p_value: 3.2e-16,
+                q_value: 0.004,
+                auc: None,
+                direction: "positive".to_string(),
+                notes: String::new(),
+                prior_evidence: None,
+            }],
+            summary: String::new(),
+            heatmap_path: Some("artifacts/analysis/ds1/heatmap.png".to_string()),
+            expression_heatmap_path: None,
+            boxplot_path: None,
+            volcano_path: None,
+            roc: None,
+            roc_path: None,
+            survival: None,
+            survival_path: None,
+            enrichment: Vec::new(),
+            enrichment_plot_path: None,
+            pathways: Vec::new(),
+            gsea: None,
+            pca: None,
+            pca_scree_path: None,
+            pca_scores_path: None,
+            kmeans: None,
+            regularized: None,
+            figure_legends: vec![FigureLegend::heatmap(
+                "artifacts/analysis/ds1/heatmap.png",
+                1,
+                (40, 40),
+                Default::default(),
+                0,
+                &Thresholds::default(),
+            )],
+            probe_collapse: None,
+            replicates: None,
+            imputation: None,
+            normalization: None,
+            normalization_plot_path: None,
+            batch: None,
+            sample_qc: None,
+            sex_stratified: None,
+            adjustment: None,
+            cross_reference: None,
+            tables: None,
+            manifest: None,
+        };
+        let draft = "Draft 2\n\nProject ID: OXBIO-ds1\nTitle: Aging markers\n\nAbstract\nWe study **aging**.\n\n\
+                     ## Results\ngene_a rises with age.\n\nDiscussion\nMore work is needed.\n";
+        let latex = render_latex(draft, &Bibliography::default(), Some(&analysis), JournalTemplate::Generic);
+
+        assert!(latex.contains("\\title{Aging markers}"));
+        assert!(latex.contains("\\date{Project ID: OXBIO-ds1}"));
+        assert!(latex.contains("\\begin{abstract}\nWe study \\textbf{aging}.\n\n\\end{abstract}"));
+        assert!(latex.contains("\\textbf{gene\\_a} & 40 & 0.910 & $<$0.001 & 0.004 & positive \\\\"));
+        assert!(latex.contains("\\includegraphics[width=0.9\\linewidth]{heatmap.png}"));
+        assert!(latex.contains("\\caption{Correlation heatmap of the analyzed markers. Pairwise Pearson"));
+        assert!(!latex.contains("Draft 2"));
+        assert!(!latex.contains("\\bibliography{"));
+
+        let results = latex.find("\\section{Results}").unwrap();
+        let table = latex.find("\\begin{table}").unwrap();
+        let discussion = latex.find("\\section{Discussion}").unwrap();
+        assert!(results < table && table < discussion);
+    }
+
+    #[test]
+    fn test_render_latex_template() {
+        let draft = format!(
+            "Title: Aging markers\n\nAbstract\n{}\n\nMethods\nWe measured things.\n\nResults\nThings changed.\n",
+            "word ".repeat(260)
+        );
+        let latex = render_latex(&draft, &Bibliography::default(), None, JournalTemplate::AgingCell);
+
+        assert!(latex.contains("% Warning: Abstract has 260 words; Aging Cell allows 250"));
+        assert!(latex.contains("\\usepackage[round,authoryear]{natbib}"));
+        assert!(latex.contains("\\linenumbers"));
+        let results = latex.find("\\section{Results}").unwrap();
+        let methods = latex.find("\\section{Experimental Procedures}").unwrap();
+        assert!(results < methods);
+    }
+}
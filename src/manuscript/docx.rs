@@ -0,0 +1,144 @@
+//! Minimal DOCX writer
+//!
+//! Writes a parsed draft as WordprocessingML: a title, Heading 1 sections,
+//! paragraphs with `**bold**` runs, bulleted items, the reference list and
+//! the figure legends.
+//! Only the parts Word needs to open the file are included.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::{Block, DraftDocument};
+use crate::analysis::legends::FigureLegend;
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>
+<Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>
+</Types>"#;
+
+const PACKAGE_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+</Relationships>"#;
+
+const DOCUMENT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+</Relationships>"#;
+
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:pPr><w:spacing w:after="160" w:line="276" w:lineRule="auto"/></w:pPr><w:rPr><w:sz w:val="22"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:after="240"/></w:pPr><w:rPr><w:b/><w:sz w:val="36"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:pPr><w:keepNext/><w:spacing w:before="240" w:after="120"/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:b/><w:sz w:val="28"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/><w:basedOn w:val="Normal"/><w:pPr><w:ind w:left="720" w:hanging="360"/></w:pPr></w:style>
+<w:style w:type="paragraph" w:styleId="Bibliography"><w:name w:val="Bibliography"/><w:basedOn w:val="Normal"/><w:pPr><w:ind w:left="720" w:hanging="720"/></w:pPr></w:style>
+</w:styles>"#;
+
+/// Write `doc`, its reference list and the figure legends to `path`
+pub(super) fn write(path: &Path, doc: &DraftDocument, references: &[String], legends: &[FigureLegend]) -> Result<()> {
+    let file = std::fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let parts = [
+        ("[Content_Types].xml", CONTENT_TYPES.to_string()),
+        ("_rels/.rels", PACKAGE_RELS.to_string()),
+        ("word/_rels/document.xml.rels", DOCUMENT_RELS.to_string()),
+        ("word/styles.xml", STYLES.to_string()),
+        ("word/document.xml", document_xml(doc, references, legends)),
+    ];
+    for (name, contents) in parts {
+        zip.start_file(name, options)?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn document_xml(doc: &DraftDocument, references: &[String], legends: &[FigureLegend]) -> String {
+    let mut body = String::new();
+    body.push_str(&paragraph(Some("Title"), doc.title.as_deref().unwrap_or("Research Manuscript")));
+    if let Some(id) = &doc.project_id {
+        body.push_str(&paragraph(None, &format!("Project ID: {}", id)));
+    }
+    for section in &doc.sections {
+        if let Some(heading) = &section.heading {
+            body.push_str(&paragraph(Some("Heading1"), heading));
+        }
+        for block in &section.blocks {
+            match block {
+                Block::Paragraph(text) => body.push_str(&paragraph(None, text)),
+                Block::List(items) => {
+                    for item in items {
+                        body.push_str(&paragraph(Some("ListParagraph"), &format!("•\t{}", item)));
+                    }
+                }
+            }
+        }
+    }
+    if !references.is_empty() {
+        body.push_str(&paragraph(Some("Heading1"), "References"));
+        for reference in references {
+            body.push_str(&paragraph(Some("Bibliography"), reference));
+        }
+    }
+    if !legends.is_empty() {
+        body.push_str(&paragraph(Some("Heading1"), "Figure legends"));
+        for (i, legend) in legends.iter().enumerate() {
+            let text = format!("**Figure {}. {}.** {}", i + 1, legend.title, legend.body);
+            body.push_str(&paragraph(None, &text));
+        }
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <w:document xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\">\
+         <w:body>{}<w:sectPr/></w:body></w:document>",
+        body
+    )
+}
+
+/// A paragraph whose `**bold**` spans become bold runs
+fn paragraph(style: Option<&str>, text: &str) -> String {
+    let properties = style
+        .map(|s| format!("<w:pPr><w:pStyle w:val=\"{}\"/></w:pPr>", s))
+        .unwrap_or_default();
+    let runs: String = text
+        .split("**")
+        .enumerate()
+        .filter(|(_, span)| !span.is_empty())
+        .map(|(i, span)| {
+            let bold = if i % 2 == 1 { "<w:rPr><w:b/></w:rPr>" } else { "" };
+            let span = escape(span).replace('\t', "</w:t><w:tab/><w:t xml:space=\"preserve\">");
+            format!("<w:r>{}<w:t xml:space=\"preserve\">{}</w:t></w:r>", bold, span)
+        })
+        .collect();
+    format!("<w:p>{}{}</w:p>", properties, runs)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paragraph_runs() {
+        assert_eq!(
+            paragraph(None, "p < 0.05 for **gene_a**"),
+            "<w:p><w:r><w:t xml:space=\"preserve\">p &lt; 0.05 for </w:t></w:r>\
+             <w:r><w:rPr><w:b/></w:rPr><w:t xml:space=\"preserve\">gene_a</w:t></w:r></w:p>"
+        );
+    }
+}
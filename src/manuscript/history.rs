@@ -0,0 +1,168 @@
+//! Draft history
+//!
+//! Every draft the workflow produces is kept with its creation time, the
+//! researcher feedback it was revised with and the model that wrote it, and
+//! saved to `drafts.json` in the local data directory so drafts survive a
+//! restart. One draft is current: the newest, or an earlier one after a
+//! rollback. `/latex` and `/pdf` render the current draft.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// History file under `<data dir>/oxidized-bio/`
+pub const DRAFTS_FILE: &str = "drafts.json";
+
+/// A saved draft
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DraftRecord {
+    /// Position in the history, from 1
+    pub id: usize,
+    /// Workflow draft number (1-3)
+    pub version: usize,
+    pub created_at: DateTime<Utc>,
+    /// Dataset the draft was written for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dataset_id: Option<String>,
+    /// Researcher feedback the draft was revised with
+    #[serde(default)]
+    pub feedback: Vec<String>,
+    /// "provider/model" that revised the draft; `None` for the template draft
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DraftHistory {
+    drafts: Vec<DraftRecord>,
+    /// Id of the current draft
+    #[serde(default)]
+    current: Option<usize>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl DraftHistory {
+    /// History from the local data directory; empty when there is none yet
+    pub fn load_default() -> Self {
+        match dirs::data_local_dir() {
+            Some(dir) => Self::load(&dir.join("oxidized-bio").join(DRAFTS_FILE)),
+            None => Self::default(),
+        }
+    }
+
+    /// History from `path`. A missing or unreadable file starts an empty
+    /// history that will be written to `path`.
+    pub fn load(path: &Path) -> Self {
+        let mut history = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(error = %e, path = %path.display(), "Ignoring unreadable draft history");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        history.path = Some(path.to_path_buf());
+        history
+    }
+
+    pub fn drafts(&self) -> &[DraftRecord] {
+        &self.drafts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.drafts.is_empty()
+    }
+
+    /// The current draft
+    pub fn current(&self) -> Option<&DraftRecord> {
+        let id = self.current?;
+        self.drafts.iter().find(|d| d.id == id)
+    }
+
+    /// Add a draft and make it current
+    pub fn push(
+        &mut self,
+        version: usize,
+        text: String,
+        feedback: Vec<String>,
+        model: Option<String>,
+        dataset_id: Option<String>,
+    ) -> &DraftRecord {
+        let id = self.drafts.last().map_or(1, |d| d.id + 1);
+        self.drafts.push(DraftRecord {
+            id,
+            version,
+            created_at: Utc::now(),
+            dataset_id,
+            feedback,
+            model,
+            text,
+        });
+        self.current = Some(id);
+        self.persist();
+        self.drafts.last().expect("draft just pushed")
+    }
+
+    /// Make draft `id` current again
+    pub fn rollback(&mut self, id: usize) -> Result<&DraftRecord> {
+        if !self.drafts.iter().any(|d| d.id == id) {
+            match self.drafts.last() {
+                Some(last) => bail!("No draft #{} (drafts are numbered 1-{})", id, last.id),
+                None => bail!("No drafts saved yet"),
+            }
+        }
+        self.current = Some(id);
+        self.persist();
+        Ok(self.current().expect("current draft exists"))
+    }
+
+    /// Write the history to its file
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self.save() {
+            warn!(error = %e, "Failed to save draft history");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_persists_and_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DRAFTS_FILE);
+
+        let mut history = DraftHistory::load(&path);
+        history.push(1, "first".to_string(), Vec::new(), None, Some("ds1".to_string()));
+        history.push(
+            2,
+            "second".to_string(),
+            vec!["shorter abstract".to_string()],
+            Some("anthropic/claude-sonnet-4-5".to_string()),
+            Some("ds1".to_string()),
+        );
+        assert_eq!(history.current().unwrap().text, "second");
+
+        let mut reloaded = DraftHistory::load(&path);
+        assert_eq!(reloaded.drafts().len(), 2);
+        assert_eq!(reloaded.drafts()[1].feedback, vec!["shorter abstract".to_string()]);
+        assert_eq!(reloaded.rollback(1).unwrap().text, "first");
+        assert!(reloaded.rollback(3).unwrap_err().to_string().contains("1-2"));
+
+        assert_eq!(DraftHistory::load(&path).current().unwrap().id, 1);
+    }
+}
@@ -0,0 +1,170 @@
+//! PDF compilation of exported manuscripts
+//!
+//! Uses the Tectonic engine (built with `--features pdf`), which fetches TeX
+//! packages on first use and runs BibTeX automatically. Compile errors are
+//! reduced to the TeX `!` error lines so they can be shown in the chat.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+/// TeX source compiled by [`compile`]
+pub const TEX_FILE: &str = "manuscript.tex";
+/// Output of [`compile`]
+pub const PDF_FILE: &str = "manuscript.pdf";
+
+/// Whether this build can compile PDFs
+pub fn available() -> bool {
+    cfg!(feature = "pdf")
+}
+
+/// Compile `dir/manuscript.tex` (with `references.bib` and figures next to
+/// it) to `dir/manuscript.pdf`. Blocking; can take minutes on first use while
+/// Tectonic downloads its bundle.
+#[cfg(feature = "pdf")]
+pub fn compile(dir: &Path) -> Result<PathBuf> {
+    use anyhow::{anyhow, bail, Context};
+    use tectonic::driver::{OutputFormat, ProcessingSessionBuilder};
+
+    let tex = std::fs::read_to_string(dir.join(TEX_FILE))
+        .with_context(|| format!("No {} in {}; run /latex first", TEX_FILE, dir.display()))?;
+
+    let mut status = engine::CollectingStatus::default();
+    let config = tectonic::config::PersistentConfig::open(false)
+        .map_err(|e| anyhow!("Failed to open the Tectonic configuration: {}", e))?;
+    let bundle = config
+        .default_bundle(false, &mut status)
+        .map_err(|e| anyhow!("Failed to load the TeX bundle: {}", e))?;
+    let format_cache_path = config
+        .format_cache_path()
+        .map_err(|e| anyhow!("Failed to set up the format cache: {}", e))?;
+
+    let mut builder = ProcessingSessionBuilder::default();
+    builder
+        .bundle(bundle)
+        .primary_input_buffer(tex.as_bytes())
+        .tex_input_name(TEX_FILE)
+        .filesystem_root(dir)
+        .format_name("latex")
+        .format_cache_path(format_cache_path)
+        .keep_logs(false)
+        .keep_intermediates(false)
+        .print_stdout(false)
+        .output_format(OutputFormat::Pdf)
+        .do_not_write_output_files();
+
+    let mut files = {
+        let mut session = builder
+            .create(&mut status)
+            .map_err(|e| anyhow!("Failed to start LaTeX: {}", e))?;
+        if let Err(e) = session.run(&mut status) {
+            bail!("{}", status.failure(&e.to_string()));
+        }
+        session.into_file_data()
+    };
+    let Some(pdf) = files.remove(PDF_FILE) else {
+        bail!("LaTeX finished without producing {}", PDF_FILE);
+    };
+    let path = dir.join(PDF_FILE);
+    std::fs::write(&path, pdf.data).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn compile(_dir: &Path) -> Result<PathBuf> {
+    anyhow::bail!("PDF export is not available in this build; rebuild with `cargo build --features pdf`")
+}
+
+#[cfg(feature = "pdf")]
+mod engine {
+    use tectonic::status::{MessageKind, StatusBackend};
+
+    /// Keeps warnings, errors and the TeX log of a failed run
+    #[derive(Default)]
+    pub struct CollectingStatus {
+        messages: Vec<String>,
+        log: String,
+    }
+
+    impl CollectingStatus {
+        /// Error message for a failed run: the TeX errors if any, otherwise
+        /// the engine's own messages
+        pub fn failure(&self, error: &str) -> String {
+            let details = super::tex_errors(&self.log);
+            let details = if details.is_empty() { self.messages.clone() } else { details };
+            if details.is_empty() {
+                format!("LaTeX compilation failed: {}", error)
+            } else {
+                format!("LaTeX compilation failed:\n{}", details.join("\n"))
+            }
+        }
+    }
+
+    impl StatusBackend for CollectingStatus {
+        fn report(&mut self, kind: MessageKind, args: std::fmt::Arguments<'_>, err: Option<&anyhow::Error>) {
+            if kind == MessageKind::Note {
+                return;
+            }
+            let mut message = args.to_string();
+            if let Some(err) = err {
+                for cause in err.chain() {
+                    message.push_str(&format!(": {}", cause));
+                }
+            }
+            self.messages.push(message);
+        }
+
+        fn dump_error_logs(&mut self, output: &[u8]) {
+            self.log.push_str(&String::from_utf8_lossy(output));
+        }
+    }
+}
+
+/// Error lines of a TeX log: each `! message` with the `l.<n>` line that
+/// locates it
+pub fn tex_errors(log: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut lines = log.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(message) = line.strip_prefix("! ") else {
+            continue;
+        };
+        let mut error = message.trim().to_string();
+        // The location follows within a few lines of context
+        for _ in 0..4 {
+            match lines.peek() {
+                Some(next) if next.starts_with("l.") => {
+                    error.push_str(&format!(" ({})", next.trim()));
+                    lines.next();
+                    break;
+                }
+                Some(next) if next.starts_with("! ") => break,
+                Some(_) => {
+                    lines.next();
+                }
+                None => break,
+            }
+        }
+        errors.push(error);
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tex_errors() {
+        let log = "This is XeTeX\n! Undefined control sequence.\n<recently read> \\foo\n\nl.12 Some \\foo\n              bar\n\
+                   ! Missing $ inserted.\n<inserted text>\n";
+        assert_eq!(
+            tex_errors(log),
+            vec![
+                "Undefined control sequence. (l.12 Some \\foo)".to_string(),
+                "Missing $ inserted.".to_string(),
+            ]
+        );
+        assert!(tex_errors("Output written on manuscript.pdf").is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+//! Journal templates
+//!
+//! A template decides the section order and headings, the abstract and
+//! main-text word limits, the citation style and the LaTeX document class
+//! of a manuscript. Sections are matched by canonical name, so "Methods",
+//! "Materials and Methods" and "Experimental Procedures" are the same
+//! section under different templates.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalTemplate {
+    #[default]
+    Generic,
+    AgingCell,
+    Plos,
+    Biorxiv,
+}
+
+/// How citations are rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceStyle {
+    /// [1], numbered
+    Numeric,
+    /// (Horvath, 2013)
+    AuthorYear,
+}
+
+impl JournalTemplate {
+    pub const ALL: [JournalTemplate; 4] = [Self::Generic, Self::AgingCell, Self::Plos, Self::Biorxiv];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Generic => "generic",
+            Self::AgingCell => "aging_cell",
+            Self::Plos => "plos",
+            Self::Biorxiv => "biorxiv",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Generic => "Generic article",
+            Self::AgingCell => "Aging Cell",
+            Self::Plos => "PLOS",
+            Self::Biorxiv => "bioRxiv preprint",
+        }
+    }
+
+    /// Canonical section names in manuscript order. Sections a template does
+    /// not list keep their relative order after these.
+    pub fn section_order(&self) -> &'static [&'static str] {
+        match self {
+            Self::Generic => &[
+                "abstract",
+                "introduction",
+                "methods",
+                "results",
+                "discussion",
+                "limitations",
+                "conclusion",
+                "supplementary",
+            ],
+            Self::AgingCell => &[
+                "abstract",
+                "introduction",
+                "results",
+                "discussion",
+                "limitations",
+                "conclusion",
+                "methods",
+                "supplementary",
+            ],
+            Self::Plos => &[
+                "abstract",
+                "introduction",
+                "methods",
+                "results",
+                "discussion",
+                "limitations",
+                "conclusion",
+                "supplementary",
+            ],
+            Self::Biorxiv => &[
+                "abstract",
+                "introduction",
+                "results",
+                "discussion",
+                "limitations",
+                "conclusion",
+                "methods",
+                "supplementary",
+            ],
+        }
+    }
+
+    /// Heading for a canonical section under this template
+    pub fn heading(&self, canonical: &str) -> Option<&'static str> {
+        let heading = match (self, canonical) {
+            (Self::AgingCell, "methods") => "Experimental Procedures",
+            (Self::Plos, "methods") => "Materials and Methods",
+            (Self::Plos, "supplementary") => "Supporting Information",
+            (_, "abstract") => "Abstract",
+            (_, "introduction") => "Introduction",
+            (_, "methods") => "Methods",
+            (_, "results") => "Results",
+            (_, "discussion") => "Discussion",
+            (_, "limitations") => "Limitations",
+            (_, "conclusion") => "Conclusion",
+            (_, "supplementary") => "Supplementary Materials",
+            _ => return None,
+        };
+        Some(heading)
+    }
+
+    pub fn abstract_word_limit(&self) -> Option<usize> {
+        match self {
+            Self::AgingCell => Some(250),
+            Self::Plos => Some(300),
+            Self::Generic | Self::Biorxiv => None,
+        }
+    }
+
+    /// Limit for the main text (all sections except the abstract)
+    pub fn main_text_word_limit(&self) -> Option<usize> {
+        match self {
+            Self::AgingCell => Some(5000),
+            Self::Generic | Self::Plos | Self::Biorxiv => None,
+        }
+    }
+
+    pub fn reference_style(&self) -> ReferenceStyle {
+        match self {
+            Self::AgingCell => ReferenceStyle::AuthorYear,
+            Self::Generic | Self::Plos | Self::Biorxiv => ReferenceStyle::Numeric,
+        }
+    }
+
+    /// natbib package options
+    pub fn natbib_options(&self) -> &'static str {
+        match self.reference_style() {
+            ReferenceStyle::Numeric => "numbers,sort&compress",
+            ReferenceStyle::AuthorYear => "round,authoryear",
+        }
+    }
+
+    /// BibTeX style: citation order for PLOS (Vancouver), alphabetical
+    /// otherwise
+    pub fn bibliography_style(&self) -> &'static str {
+        match self {
+            Self::Plos => "unsrtnat",
+            Self::Generic | Self::AgingCell | Self::Biorxiv => "plainnat",
+        }
+    }
+
+    /// `\documentclass` and page layout lines of the preamble
+    pub fn latex_class(&self) -> &'static str {
+        match self {
+            Self::Generic => "\\documentclass[11pt]{article}\n\\usepackage[margin=1in]{geometry}\n",
+            Self::AgingCell => {
+                "\\documentclass[12pt]{article}\n\\usepackage[margin=1in]{geometry}\n\
+                 \\usepackage{setspace}\n\\doublespacing\n"
+            }
+            Self::Plos => {
+                "\\documentclass[10pt,letterpaper]{article}\n\
+                 \\usepackage[top=0.85in,left=2.75in,footskip=0.75in]{geometry}\n"
+            }
+            Self::Biorxiv => {
+                "\\documentclass[11pt]{article}\n\\usepackage[margin=1in]{geometry}\n\
+                 \\usepackage{setspace}\n\\onehalfspacing\n"
+            }
+        }
+    }
+
+    /// Whether submissions need line numbers
+    pub fn line_numbers(&self) -> bool {
+        matches!(self, Self::AgingCell | Self::Plos)
+    }
+
+    /// Ordering key for a section heading: its position in
+    /// [`section_order`](Self::section_order), or after all listed sections
+    pub fn rank(&self, heading: &str) -> usize {
+        let order = self.section_order();
+        canonical_section(heading)
+            .and_then(|c| order.iter().position(|s| *s == c))
+            .unwrap_or(order.len())
+    }
+
+    /// Arrange `(canonical section, body)` pairs in template order under
+    /// template headings, trimming the abstract to the word limit
+    pub fn assemble(&self, sections: &[(&str, String)]) -> String {
+        let mut ordered: Vec<&(&str, String)> = sections.iter().collect();
+        ordered.sort_by_key(|(name, _)| self.rank(name));
+        ordered
+            .into_iter()
+            .map(|(name, body)| {
+                let heading = self.heading(name).unwrap_or(name);
+                let body = match self.abstract_word_limit() {
+                    Some(limit) if *name == "abstract" => truncate_words(body, limit),
+                    _ => body.clone(),
+                };
+                format!("{}\n{}\n", heading, body)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Word-limit overruns in `sections` (heading, text) as messages
+    pub fn word_limit_warnings<'a>(&self, sections: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut main_words = 0;
+        for (heading, text) in sections {
+            let words = word_count(text);
+            match canonical_section(heading) {
+                Some("abstract") => {
+                    if let Some(limit) = self.abstract_word_limit().filter(|l| words > *l) {
+                        warnings.push(format!("Abstract has {} words; {} allows {}", words, self.name(), limit));
+                    }
+                }
+                // Supplementary material does not count toward the main text
+                Some("supplementary") => {}
+                Some(_) => main_words += words,
+                None => {}
+            }
+        }
+        if let Some(limit) = self.main_text_word_limit().filter(|l| main_words > *l) {
+            warnings.push(format!("Main text has {} words; {} allows {}", main_words, self.name(), limit));
+        }
+        warnings
+    }
+}
+
+impl fmt::Display for JournalTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for JournalTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().to_ascii_lowercase().replace(['-', ' '], "_");
+        Self::ALL
+            .into_iter()
+            .find(|t| t.id() == normalized)
+            .ok_or_else(|| {
+                let ids: Vec<&str> = Self::ALL.iter().map(|t| t.id()).collect();
+                format!("unknown template '{}' (expected one of: {})", s, ids.join(", "))
+            })
+    }
+}
+
+/// Canonical name of a manuscript section heading, if it is one
+pub fn canonical_section(heading: &str) -> Option<&'static str> {
+    let canonical = match heading.trim().to_ascii_lowercase().as_str() {
+        "abstract" | "summary" => "abstract",
+        "introduction" | "background" => "introduction",
+        "methods" | "materials and methods" | "experimental procedures" => "methods",
+        "results" => "results",
+        "discussion" => "discussion",
+        "limitations" => "limitations",
+        "conclusion" | "conclusions" => "conclusion",
+        "supplementary materials" | "supplementary information" | "supporting information" => "supplementary",
+        _ => return None,
+    };
+    Some(canonical)
+}
+
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// First `limit` words of `text`, cut back to the last complete sentence
+/// when there is one
+pub fn truncate_words(text: &str, limit: usize) -> String {
+    if word_count(text) <= limit {
+        return text.to_string();
+    }
+    let cut = text.split_whitespace().take(limit).collect::<Vec<_>>().join(" ");
+    match cut.rfind(['.', '!', '?']) {
+        Some(end) => cut[..=end].to_string(),
+        None => format!("{}…", cut),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        assert_eq!("aging-cell".parse::<JournalTemplate>().unwrap(), JournalTemplate::AgingCell);
+        assert_eq!("bioRxiv".parse::<JournalTemplate>().unwrap(), JournalTemplate::Biorxiv);
+        assert!("nature".parse::<JournalTemplate>().unwrap_err().contains("generic, aging_cell, plos, biorxiv"));
+    }
+
+    #[test]
+    fn test_assemble_orders_and_trims() {
+        let sections = [
+            ("abstract", "One two three. Four five six seven.".to_string()),
+            ("methods", "We did things.".to_string()),
+            ("results", "It worked.".to_string()),
+        ];
+        let generic = JournalTemplate::Generic.assemble(&sections);
+        assert!(generic.find("Methods").unwrap() < generic.find("Results").unwrap());
+
+        let aging_cell = JournalTemplate::AgingCell.assemble(&sections);
+        assert!(aging_cell.find("Results").unwrap() < aging_cell.find("Experimental Procedures").unwrap());
+
+        assert_eq!(truncate_words("One two three. Four five six seven.", 5), "One two three.");
+        assert_eq!(truncate_words("One two three four", 2), "One two…");
+    }
+
+    #[test]
+    fn test_word_limit_warnings() {
+        let long_abstract = "word ".repeat(260);
+        let warnings = JournalTemplate::AgingCell
+            .word_limit_warnings([("Abstract", long_abstract.as_str()), ("Results", "short")]);
+        assert_eq!(warnings, vec!["Abstract has 260 words; Aging Cell allows 250".to_string()]);
+        assert!(JournalTemplate::Generic
+            .word_limit_warnings([("Abstract", long_abstract.as_str())])
+            .is_empty());
+    }
+}
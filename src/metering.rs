@@ -0,0 +1,275 @@
+//! Usage metering and billing records
+//!
+//! Billable units (LLM tokens, search queries, analysis compute time and
+//! stored bytes) are appended to the `usage_events` table, attributed to an
+//! account: the `X-User-Id` header or verified x402 payer for API requests,
+//! and `local` for the TUI. [`close_period`] rolls events up into
+//! `billing_records`, which the payment module settles.
+//!
+//! Recording is fire-and-forget and a no-op until [`install`] has been given a
+//! pool, so the TUI keeps working without a database.
+
+use anyhow::Result;
+use axum::{extract::Request, middleware::Next, response::Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::future::Future;
+use std::sync::OnceLock;
+use tracing::warn;
+
+/// Account used when no user can be identified
+pub const LOCAL_ACCOUNT: &str = "local";
+
+/// Header naming the account API usage is billed to
+pub const ACCOUNT_HEADER: &str = "X-User-Id";
+
+tokio::task_local! {
+    static ACCOUNT: String;
+}
+
+static POOL: OnceLock<PgPool> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageMetric {
+    LlmTokens,
+    SearchQueries,
+    AnalysisCpuMs,
+    StorageBytes,
+}
+
+impl UsageMetric {
+    pub const ALL: [UsageMetric; 4] = [
+        UsageMetric::LlmTokens,
+        UsageMetric::SearchQueries,
+        UsageMetric::AnalysisCpuMs,
+        UsageMetric::StorageBytes,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageMetric::LlmTokens => "llm_tokens",
+            UsageMetric::SearchQueries => "search_queries",
+            UsageMetric::AnalysisCpuMs => "analysis_cpu_ms",
+            UsageMetric::StorageBytes => "storage_bytes",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.as_str() == s)
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            UsageMetric::LlmTokens => "tokens",
+            UsageMetric::SearchQueries => "queries",
+            UsageMetric::AnalysisCpuMs => "ms",
+            UsageMetric::StorageBytes => "bytes",
+        }
+    }
+}
+
+/// Enable metering; later calls are ignored
+pub fn install(pool: PgPool) {
+    let _ = POOL.set(pool);
+}
+
+pub fn pool() -> Option<&'static PgPool> {
+    POOL.get()
+}
+
+/// Account the current task's usage is billed to
+pub fn current_account() -> String {
+    ACCOUNT
+        .try_with(|account| account.clone())
+        .unwrap_or_else(|_| LOCAL_ACCOUNT.to_string())
+}
+
+/// Run `fut` with its usage billed to `account`
+pub async fn scope<F: Future>(account: String, fut: F) -> F::Output {
+    ACCOUNT.scope(account, fut).await
+}
+
+/// Record usage for the current account without waiting for the insert
+pub fn record(metric: UsageMetric, quantity: u64, cost_usd: f64, detail: serde_json::Value) {
+    let Some(pool) = POOL.get() else { return };
+    if quantity == 0 {
+        return;
+    }
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let pool = pool.clone();
+    let account = current_account();
+    handle.spawn(async move {
+        if let Err(e) = insert_event(&pool, &account, metric, quantity, cost_usd, &detail).await {
+            warn!(error = %e, metric = metric.as_str(), "Failed to record usage");
+        }
+    });
+}
+
+/// Record analysis compute time measured from `started`
+pub fn record_analysis(started: std::time::Instant, dataset_id: &str) {
+    let ms = started.elapsed().as_millis().min(u64::MAX as u128) as u64;
+    record(UsageMetric::AnalysisCpuMs, ms.max(1), 0.0, serde_json::json!({ "dataset_id": dataset_id }));
+}
+
+pub async fn insert_event(
+    pool: &PgPool,
+    account: &str,
+    metric: UsageMetric,
+    quantity: u64,
+    cost_usd: f64,
+    detail: &serde_json::Value,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO usage_events (account, metric, quantity, cost_usd, detail) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(account)
+    .bind(metric.as_str())
+    .bind(quantity.min(i64::MAX as u64) as i64)
+    .bind(cost_usd)
+    .bind(detail)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Usage totals for one metric
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct UsageTotal {
+    pub metric: String,
+    pub quantity: i64,
+    pub cost_usd: f64,
+    pub events: i64,
+}
+
+/// Totals per metric for `account` since `since`
+pub async fn summary(pool: &PgPool, account: &str, since: DateTime<Utc>) -> Result<Vec<UsageTotal>> {
+    let totals = sqlx::query_as::<_, UsageTotal>(
+        r#"
+        SELECT metric,
+               SUM(quantity)::BIGINT AS quantity,
+               SUM(cost_usd)::DOUBLE PRECISION AS cost_usd,
+               COUNT(*) AS events
+        FROM usage_events
+        WHERE account = $1 AND created_at >= $2
+        GROUP BY metric
+        ORDER BY metric
+        "#,
+    )
+    .bind(account)
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+    Ok(totals)
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct BillingRecord {
+    pub id: uuid::Uuid,
+    pub account: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub metric: String,
+    pub quantity: i64,
+    pub amount_usd: f64,
+    pub status: String,
+    pub payment_id: Option<uuid::Uuid>,
+}
+
+/// Roll `account`'s usage in `[start, end)` into billing records.
+///
+/// Re-running for the same period refreshes records that are still open;
+/// paid or void records are left untouched.
+pub async fn close_period(
+    pool: &PgPool,
+    account: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<BillingRecord>> {
+    let records = sqlx::query_as::<_, BillingRecord>(
+        r#"
+        INSERT INTO billing_records (account, period_start, period_end, metric, quantity, amount_usd)
+        SELECT $1, $2, $3, metric, SUM(quantity)::BIGINT, SUM(cost_usd)
+        FROM usage_events
+        WHERE account = $1 AND created_at >= $2 AND created_at < $3
+        GROUP BY metric
+        ON CONFLICT (account, period_start, period_end, metric) DO UPDATE
+            SET quantity = EXCLUDED.quantity, amount_usd = EXCLUDED.amount_usd
+            WHERE billing_records.status = 'open'
+        RETURNING id, account, period_start, period_end, metric, quantity, amount_usd, status, payment_id
+        "#,
+    )
+    .bind(account)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
+    Ok(records)
+}
+
+/// Start of the current calendar month (UTC)
+pub fn month_start() -> DateTime<Utc> {
+    use chrono::{Datelike, TimeZone};
+    let now = Utc::now();
+    Utc.with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(now)
+}
+
+/// Human-readable summary for the TUI
+pub fn format_summary(totals: &[UsageTotal]) -> String {
+    if totals.is_empty() {
+        return "No metered usage recorded.".to_string();
+    }
+    let mut lines: Vec<String> = totals
+        .iter()
+        .map(|t| {
+            let unit = UsageMetric::parse(&t.metric).map(|m| m.unit()).unwrap_or("");
+            format!("  {:<16} {:>12} {:<7} ${:.4}", t.metric, t.quantity, unit, t.cost_usd)
+        })
+        .collect();
+    let total: f64 = totals.iter().map(|t| t.cost_usd).sum();
+    lines.push(format!("  {:<16} {:>12} {:<7} ${:.4}", "total", "", "", total));
+    lines.join("\n")
+}
+
+/// Middleware attributing a request's usage to the `X-User-Id` account
+pub async fn attribute_usage(req: Request, next: Next) -> Response {
+    let account = req
+        .headers()
+        .get(ACCOUNT_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .unwrap_or(LOCAL_ACCOUNT)
+        .to_string();
+    scope(account, next.run(req)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metric_names_match_schema() {
+        for metric in UsageMetric::ALL {
+            assert_eq!(UsageMetric::parse(metric.as_str()), Some(metric));
+            assert_eq!(
+                serde_json::to_value(metric).unwrap(),
+                serde_json::Value::String(metric.as_str().to_string())
+            );
+        }
+        assert_eq!(UsageMetric::parse("bandwidth"), None);
+    }
+
+    #[tokio::test]
+    async fn test_account_scope() {
+        assert_eq!(current_account(), LOCAL_ACCOUNT);
+        let inner = scope("0xabc".to_string(), async { current_account() }).await;
+        assert_eq!(inner, "0xabc");
+        assert_eq!(current_account(), LOCAL_ACCOUNT);
+    }
+}
@@ -1,12 +1,15 @@
 use sqlx::PgPool;
-use crate::config::Config;
 use crate::data_registry::DatasetRegistry;
+use crate::session::{EventBus, LiveConfig};
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: PgPool,
-    pub config: Config,
+    /// Read per request with `current()`; updated when settings are saved
+    pub config: LiveConfig,
     pub dataset_registry: DatasetRegistry,
+    /// Announces changes made over HTTP/RFC to the TUI in `--serve` mode
+    pub events: EventBus,
 }
 
 // Core models based on TypeScript definitions
@@ -62,6 +65,14 @@ pub struct ConversationStateValues {
     pub suggested_next_steps: Option<Vec<PlanTask>>,
     pub research_mode: Option<String>,
     pub uploaded_datasets: Option<Vec<UploadedDataset>>,
+    /// Artifacts of the user's data the reply can cite, such as sequence
+    /// summaries of FASTA/FASTQ uploads
+    pub artifacts: Option<Vec<AnalysisArtifact>>,
+    /// Brief and glossary of the user's project, for planning and the
+    /// reply to take as given
+    pub project_context: Option<String>,
+    /// Reply mode the user picked; guessed from each message when unset
+    pub reply_mode: Option<crate::agents::ReplyMode>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -129,7 +140,7 @@ pub struct DiscoveryEvidence {
 pub struct AnalysisArtifact {
     pub id: String,
     pub description: String,
-    pub artifact_type: String, // "FILE" or "FOLDER"
+    pub artifact_type: String, // "FILE", "FOLDER" or "SEQUENCE_SUMMARY"
     pub content: Option<String>,
     pub name: String,
     pub path: Option<String>,
@@ -142,6 +153,9 @@ pub struct ChatRequest {
     pub message: String,
     pub conversation_id: Option<uuid::Uuid>,
     pub files: Option<Vec<FileUpload>>,
+    /// `answer`, `report` or `chat` instead of guessing from the message
+    #[serde(default)]
+    pub mode: Option<crate::agents::ReplyMode>,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -160,6 +174,56 @@ pub struct AnalysisRequest {
     pub boxplot_column: Option<String>,
     pub max_columns: Option<usize>,
     pub max_groups: Option<usize>,
+    /// Correlation coefficient for ranking and the heatmap; Pearson by
+    /// default
+    pub correlation: Option<crate::analysis::stats::CorrelationMethod>,
+    /// Result table format; CSV by default
+    pub tables: Option<crate::analysis::report::TableFormat>,
+    /// Decimal separator and grouping of the draft tables; plain by default
+    pub numbers: Option<crate::utils::number::NumberFormat>,
+    /// Significance cut-offs; alpha and FDR 0.05 with no minimum effect by
+    /// default
+    pub thresholds: Option<crate::analysis::stats::Thresholds>,
+    /// Add the Kruskal-Wallis test to the ANOVA across more than two groups
+    pub kruskal_wallis: Option<bool>,
+    /// `auto` (the memory available, by default), `off` or a size such as
+    /// `"4G"`; runs estimated to need more are refused
+    pub memory_limit: Option<crate::analysis::memory::MemoryLimit>,
+    /// Threads of the per-column statistics; one per core by default
+    pub threads: Option<usize>,
+    /// Penalty of the multi-marker model: `penalty` (ridge, lasso,
+    /// elastic-net or off), `l1_ratio`, `lambda` and `folds`; an
+    /// elastic net with λ chosen by 5-fold CV by default
+    pub regularization: Option<crate::analysis::regularized::Regularization>,
+    /// How the target is split into two classes for the candidates' AUC:
+    /// `"auto"` (binary targets only, by default), `"median"`, `"off"` or a
+    /// cut-off such as `"60"`
+    pub roc: Option<crate::analysis::roc::RocSplit>,
+    /// How missing marker values are filled in: `"off"` (by default),
+    /// `"mean"`, `"median"`, `"knn"` or `"knn:<k>"`
+    pub imputation: Option<crate::analysis::impute::Imputation>,
+    /// Normalization steps applied in order: `"log2"`, `"zscore"` and/or
+    /// `"quantile"`; none by default
+    pub normalization: Option<Vec<crate::analysis::normalize::Normalization>>,
+    /// Column naming each sample's batch; its batch effects are removed
+    /// from the markers
+    pub batch_column: Option<String>,
+    /// `"combat"` (by default) or `"center"`
+    pub batch_correction: Option<crate::analysis::batch::BatchCorrection>,
+    /// Time-to-event column; with `event_column`, adds Kaplan-Meier curves
+    /// and per-marker Cox models
+    pub time_column: Option<String>,
+    /// Column marking whether each sample's event happened or it was
+    /// censored
+    pub event_column: Option<String>,
+    /// Column naming each sample's subject; repeated samples of a subject
+    /// get a random intercept in the ranking models
+    pub subject_column: Option<String>,
+    /// Top candidates cross-referenced against the literature; defaults to
+    /// `cross_reference.top_n`, 0 skips the step
+    pub cross_reference: Option<usize>,
+    /// Journal template; defaults to `manuscript.template`
+    pub template: Option<crate::manuscript::JournalTemplate>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -167,6 +231,8 @@ pub struct AnalysisResponse {
     pub status: String,
     pub dataset_id: String,
     pub summary: String,
+    /// Top markers, key statistics and figures, as quoted in the manuscript
+    pub findings: crate::analysis::findings::Findings,
     pub descriptive_stats: Vec<DescriptiveStat>,
     pub regressions: Vec<RegressionResult>,
     pub novelty_scores: Vec<NoveltyScore>,
@@ -201,15 +267,159 @@ pub struct NoveltyScore {
     pub column: String,
     pub score: f64,
     pub rationale: String,
+    /// One-way ANOVA p- and q-value of the group means, when the group
+    /// column has more than two levels
+    pub p_value: Option<f64>,
+    pub q_value: Option<f64>,
+}
+
+/// One-way ANOVA of a marker across the levels of the group column
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Anova {
+    pub column: String,
+    /// Levels with values
+    pub groups: usize,
+    pub n: usize,
+    pub f: f64,
+    pub df_between: usize,
+    pub df_within: usize,
+    pub p_value: f64,
+    /// Benjamini–Hochberg adjusted p-value across markers
+    pub q_value: f64,
+    /// Between-group share of the total sum of squares
+    pub eta_squared: f64,
+    /// Kruskal-Wallis H, p- and q-value, when requested
+    pub kruskal_h: Option<f64>,
+    pub kruskal_p: Option<f64>,
+    pub kruskal_q: Option<f64>,
+}
+
+/// One group of a marker against the rest of the grouped samples
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GroupComparison {
+    pub column: String,
+    pub group: String,
+    pub n: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub rest_n: usize,
+    pub rest_mean: f64,
+    /// Group mean minus the mean of the other groups
+    pub difference: f64,
+    /// Welch's t statistic
+    pub t: f64,
+    pub p_value: f64,
+    /// Benjamini–Hochberg adjusted p-value across all marker-group pairs
+    pub q_value: f64,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct BiomarkerCandidate {
     pub column: String,
+    /// Gene symbol when `column` is an Ensembl ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// Ensembl biotype of the gene
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub biotype: Option<String>,
+    /// Human one-to-one ortholog of a mouse or rat gene
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub human_ortholog: Option<String>,
     pub score: f64,
     pub correlation: f64,
+    /// Complete (marker, target) pairs
+    pub n: usize,
+    /// Two-sided p-value of the correlation
+    pub p_value: f64,
+    /// Benjamini–Hochberg adjusted p-value across all tested markers
+    pub q_value: f64,
+    /// Area under the ROC curve for the binary or split target; below 0.5
+    /// when the marker is lower in the positive class
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auc: Option<f64>,
     pub direction: String,
     pub notes: String,
+    /// Prior literature on the gene; `None` when it was not cross-referenced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prior_evidence: Option<PriorEvidence>,
+}
+
+impl BiomarkerCandidate {
+    /// Gene symbol if known, else the column name
+    pub fn label(&self) -> &str {
+        self.symbol.as_deref().unwrap_or(&self.column)
+    }
+
+    /// Name to search the human literature and GO annotation with: the
+    /// human ortholog when known, else [`label`](Self::label)
+    pub fn human_label(&self) -> &str {
+        self.human_ortholog.as_deref().unwrap_or(self.label())
+    }
+
+    /// [`label`](Self::label), followed by the human ortholog when its name
+    /// differs (`Trp53 (TP53)`)
+    pub fn display_label(&self) -> String {
+        match self.human_ortholog.as_deref() {
+            Some(human) if human != self.label() => format!("{} ({})", self.label(), human),
+            _ => self.label().to_string(),
+        }
+    }
+}
+
+/// Publications found for a biomarker gene by literature cross-referencing
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PriorEvidence {
+    /// Query searched, e.g. `TP53 aging`
+    pub query: String,
+    /// Matching publications: the PubMed total, or the Scholar results up to
+    /// the engine's result limit
+    pub publications: usize,
+    pub status: EvidenceStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvidenceStatus {
+    PreviouslyReported,
+    Novel,
+}
+
+impl EvidenceStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PreviouslyReported => "previously_reported",
+            Self::Novel => "novel",
+        }
+    }
+
+    /// Wording for tables and manuscripts
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::PreviouslyReported => "previously reported",
+            Self::Novel => "novel",
+        }
+    }
+}
+
+/// Over-representation of a GO term among the significant biomarkers
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EnrichmentResult {
+    pub term_id: String,
+    pub term_name: String,
+    /// Significant biomarkers annotated with the term
+    pub genes: Vec<String>,
+    /// Tested markers annotated with the term
+    pub term_size: usize,
+    /// Significant biomarkers with any annotation
+    pub hits: usize,
+    /// Tested markers with any annotation
+    pub universe: usize,
+    /// Observed over expected overlap
+    pub fold_enrichment: f64,
+    /// One-sided hypergeometric p-value
+    pub p_value: f64,
+    /// Benjamini–Hochberg adjusted p-value across the tested terms
+    pub q_value: f64,
 }
 
 /// Chat response format matching frontend expectations
@@ -242,6 +452,9 @@ pub struct DeepResearchRequest {
     pub message: String,
     pub conversation_id: Option<uuid::Uuid>,
     pub research_mode: Option<String>, // "semi-autonomous", "fully-autonomous", "steering"
+    /// Research iterations; defaults to `deep_research.depth`
+    #[serde(default)]
+    pub depth: Option<u32>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -249,6 +462,8 @@ pub struct DeepResearchResponse {
     pub message_id: uuid::Uuid,
     pub status: String,
     pub conversation_id: uuid::Uuid,
+    /// Iterations the run will go through at most
+    pub depth: u32,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -258,3 +473,33 @@ pub struct HealthResponse {
     pub database: String,
     pub redis: Option<String>,
 }
+
+/// `GET /readyz` body: whether the server can take traffic, and why not
+#[derive(Debug, serde::Serialize)]
+pub struct ReadinessResponse {
+    /// `ready` when every check passed, else `unavailable`
+    pub status: String,
+    pub timestamp: String,
+    pub checks: ReadinessChecks,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ReadinessChecks {
+    pub database: ComponentHealth,
+    /// Upload directory datasets are stored in
+    pub storage: ComponentHealth,
+    /// Persistent job queue table
+    pub queue: ComponentHealth,
+    /// At least one provider with an API key
+    pub llm: ComponentHealth,
+}
+
+/// Outcome of one readiness check
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ComponentHealth {
+    /// `ok` or `error`
+    pub status: String,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
@@ -1,5 +1,5 @@
 // Payment protocols (x402, b402)
-// TODO: Implement full payment protocols with blockchain integration
+// x402 is enforced through a facilitator; b402 is still a stub
 
 pub mod x402;
 pub mod b402;
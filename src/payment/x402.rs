@@ -1,20 +1,572 @@
-// x402 payment protocol stub (Base/USDC)
-// TODO: Implement full x402 payment protocol
+//! x402 payment protocol (Base/USDC)
+//!
+//! Metered routes answer unpaid requests with `402 Payment Required` and a
+//! JSON body listing the accepted payment requirements. The client retries
+//! with an `X-PAYMENT` header carrying a base64-encoded, signed payment
+//! payload. The payload is checked with the facilitator's `/verify` before the
+//! handler runs and settled through `/settle`; the settlement receipt is
+//! returned in `X-PAYMENT-RESPONSE`.
+//!
+//! Non-idempotent requests (starting research or an analysis) are settled
+//! before the handler runs, so a failed settlement has no side effects; the
+//! payment stands if the handler then fails. Idempotent requests are settled
+//! only once the handler succeeds; if that settlement fails the response is
+//! withheld and the client gets `402`, which it can retry safely.
+//!
+//! Routes refuse requests they would reject (unknown dataset, invalid
+//! options) in a layer in front of [`require_payment`], using [`peek_json`],
+//! so that only requests that will run are settled.
+//!
+//! Resources are named under `X402_PUBLIC_URL`, not the client's `Host` or
+//! `X-Forwarded-Proto` headers, so clients cannot pick what they sign for.
+//!
+//! Signature checking and on-chain settlement are delegated to the
+//! facilitator, so no wallet or chain client is needed here.
 
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    body::Body,
+    extract::{FromRequest, Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
 
+use crate::config::Config;
+use crate::metering;
+
+pub const X402_VERSION: u32 = 1;
+/// Request header carrying the payment payload
+pub const PAYMENT_HEADER: &str = "X-PAYMENT";
+/// Response header carrying the settlement receipt
+pub const PAYMENT_RESPONSE_HEADER: &str = "X-PAYMENT-RESPONSE";
+
+/// Largest request body [`peek_json`] reads, axum's default body limit
+const BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// USDC uses 6 decimals on every supported network
+const USDC_DECIMALS: u32 = 6;
+
+/// USDC contract for a network, if supported
+pub fn usdc_address(network: &str) -> Option<&'static str> {
+    match network {
+        "base" => Some("0x833589fCD6eDb6E08f4c3C32D4f71b54bdA02913"),
+        "base-sepolia" => Some("0x036CbD53842c5426634e7929541eC2318f3dCF7e"),
+        _ => None,
+    }
+}
+
+/// Convert a USD price to USDC base units, e.g. `0.05` -> `"50000"`
+pub fn usd_to_atomic(usd: f64) -> String {
+    let units = (usd * 10f64.powi(USDC_DECIMALS as i32)).round().max(0.0);
+    format!("{}", units as u64)
+}
+
+/// One way of paying for a resource (`accepts` entry of a 402 response)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequirements {
+    pub scheme: String,
+    pub network: String,
+    /// Price in asset base units
+    pub max_amount_required: String,
+    pub resource: String,
+    pub description: String,
+    pub mime_type: String,
+    pub pay_to: String,
+    pub max_timeout_seconds: u64,
+    /// Token contract address
+    pub asset: String,
+    /// EIP-712 domain of the token, needed by clients to sign
+    #[serde(default)]
+    pub extra: Option<serde_json::Value>,
+}
+
+/// Body of a `402 Payment Required` response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequiredResponse {
+    pub x402_version: u32,
+    pub error: String,
+    pub accepts: Vec<PaymentRequirements>,
+}
+
+/// Decoded `X-PAYMENT` header. The scheme-specific `payload` (signature and
+/// authorization) is passed through to the facilitator untouched.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct X402Payment {
-    pub amount_usd: f64,
+#[serde(rename_all = "camelCase")]
+pub struct PaymentPayload {
+    pub x402_version: u32,
+    pub scheme: String,
     pub network: String,
-    pub payment_address: String,
+    pub payload: serde_json::Value,
 }
 
-pub struct X402Client;
+impl PaymentPayload {
+    pub fn from_header(value: &str) -> Result<Self> {
+        let json = BASE64
+            .decode(value.trim())
+            .context("X-PAYMENT header is not valid base64")?;
+        serde_json::from_slice(&json).context("X-PAYMENT header is not a valid payment payload")
+    }
+
+    /// Check the payload targets the scheme/network we asked for
+    pub fn matches(&self, requirements: &PaymentRequirements) -> Result<()> {
+        if self.x402_version != X402_VERSION {
+            return Err(anyhow!("Unsupported x402 version {}", self.x402_version));
+        }
+        if self.scheme != requirements.scheme || self.network != requirements.network {
+            return Err(anyhow!(
+                "Payment uses {}/{}, expected {}/{}",
+                self.scheme,
+                self.network,
+                requirements.scheme,
+                requirements.network
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyResponse {
+    pub is_valid: bool,
+    #[serde(default)]
+    pub invalid_reason: Option<String>,
+    #[serde(default)]
+    pub payer: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettleResponse {
+    pub success: bool,
+    #[serde(default)]
+    pub error_reason: Option<String>,
+    #[serde(default)]
+    pub transaction: Option<String>,
+    #[serde(default)]
+    pub network: Option<String>,
+    #[serde(default)]
+    pub payer: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FacilitatorRequest<'a> {
+    x402_version: u32,
+    payment_payload: &'a PaymentPayload,
+    payment_requirements: &'a PaymentRequirements,
+}
+
+/// Client for an x402 facilitator (`/verify` and `/settle`)
+#[derive(Clone)]
+pub struct X402Client {
+    http: reqwest::Client,
+    facilitator_url: String,
+}
 
 impl X402Client {
-    pub async fn verify_payment(payment: &X402Payment) -> anyhow::Result<bool> {
-        // Placeholder implementation
-        Ok(true)
+    pub fn new(http: reqwest::Client, facilitator_url: impl Into<String>) -> Self {
+        Self {
+            http,
+            facilitator_url: facilitator_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub async fn verify(
+        &self,
+        payment: &PaymentPayload,
+        requirements: &PaymentRequirements,
+    ) -> Result<VerifyResponse> {
+        self.post("verify", payment, requirements).await
+    }
+
+    pub async fn settle(
+        &self,
+        payment: &PaymentPayload,
+        requirements: &PaymentRequirements,
+    ) -> Result<SettleResponse> {
+        self.post("settle", payment, requirements).await
+    }
+
+    async fn post<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        payment: &PaymentPayload,
+        requirements: &PaymentRequirements,
+    ) -> Result<T> {
+        let url = format!("{}/{}", self.facilitator_url, endpoint);
+        let response = self
+            .http
+            .post(&url)
+            .json(&FacilitatorRequest {
+                x402_version: X402_VERSION,
+                payment_payload: payment,
+                payment_requirements: requirements,
+            })
+            .send()
+            .await
+            .with_context(|| format!("Facilitator request to {} failed", url))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Facilitator {} returned {}: {}", endpoint, status, body));
+        }
+        response
+            .json()
+            .await
+            .with_context(|| format!("Invalid facilitator {} response", endpoint))
+    }
+}
+
+/// Per-router payment gate, used as axum middleware state
+#[derive(Clone)]
+pub struct X402Gate {
+    enabled: bool,
+    network: String,
+    pay_to: Option<String>,
+    price_usd: f64,
+    description: String,
+    /// Base of the resource URLs, without a trailing slash
+    public_url: Option<String>,
+    client: X402Client,
+}
+
+impl X402Gate {
+    pub fn new(config: &Config, price_usd: f64, description: &str) -> Self {
+        let payment = &config.payment;
+        Self {
+            enabled: payment.x402_enabled,
+            network: payment.x402_network.clone(),
+            pay_to: payment.x402_payment_address.clone().filter(|a| !a.is_empty()),
+            price_usd,
+            description: description.to_string(),
+            public_url: payment
+                .x402_public_url
+                .as_deref()
+                .map(|url| url.trim_end_matches('/').to_string())
+                .filter(|url| !url.is_empty()),
+            client: X402Client::new(
+                crate::utils::http::client(&config.network, "x402"),
+                payment.x402_facilitator_url.clone(),
+            ),
+        }
+    }
+
+    /// Requirements for the resource at `path` under the public URL
+    pub fn requirements(&self, path: &str) -> Result<PaymentRequirements> {
+        let public_url = self
+            .public_url
+            .as_deref()
+            .ok_or_else(|| anyhow!("X402_PUBLIC_URL is not set"))?;
+        let pay_to = self
+            .pay_to
+            .clone()
+            .ok_or_else(|| anyhow!("X402_PAYMENT_ADDRESS is not set"))?;
+        let asset = usdc_address(&self.network)
+            .ok_or_else(|| anyhow!("Unsupported x402 network: {}", self.network))?;
+        Ok(PaymentRequirements {
+            scheme: "exact".to_string(),
+            network: self.network.clone(),
+            max_amount_required: usd_to_atomic(self.price_usd),
+            resource: format!("{}{}", public_url, path),
+            description: self.description.clone(),
+            mime_type: "application/json".to_string(),
+            pay_to,
+            max_timeout_seconds: 300,
+            asset: asset.to_string(),
+            extra: Some(serde_json::json!({ "name": "USDC", "version": "2" })),
+        })
+    }
+}
+
+fn payment_required(requirements: PaymentRequirements, error: impl Into<String>) -> Response {
+    let body = PaymentRequiredResponse {
+        x402_version: X402_VERSION,
+        error: error.into(),
+        accepts: vec![requirements],
+    };
+    (StatusCode::PAYMENT_REQUIRED, Json(body)).into_response()
+}
+
+/// Parse the JSON body of `req` as the handler's `Json` extractor will and
+/// hand the request back intact, so a layer in front of [`require_payment`]
+/// can refuse it before it is charged. Bodies the extractor would reject
+/// are answered with its rejection.
+pub async fn peek_json<T: serde::de::DeserializeOwned>(req: Request) -> Result<(T, Request), Response> {
+    let (parts, body) = req.into_parts();
+    let bytes = axum::body::to_bytes(body, BODY_LIMIT)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE.into_response())?;
+    let mut probe = Request::new(Body::from(bytes.clone()));
+    *probe.headers_mut() = parts.headers.clone();
+    let Json(value) = Json::<T>::from_request(probe, &()).await.map_err(IntoResponse::into_response)?;
+    Ok((value, Request::from_parts(parts, Body::from(bytes))))
+}
+
+/// Settle `payment`, returning the receipt header or the `402` to answer with
+async fn settle(
+    gate: &X402Gate,
+    payment: &PaymentPayload,
+    requirements: &PaymentRequirements,
+) -> Result<Option<HeaderValue>, Response> {
+    match gate.client.settle(payment, requirements).await {
+        Ok(settled) if settled.success => {
+            let receipt = BASE64.encode(serde_json::to_vec(&settled).unwrap_or_default());
+            Ok(HeaderValue::from_str(&receipt).ok())
+        }
+        Ok(settled) => Err(payment_required(
+            requirements.clone(),
+            settled.error_reason.unwrap_or_else(|| "Settlement failed".to_string()),
+        )),
+        Err(e) => {
+            warn!(error = %e, "x402 settlement failed");
+            Err(payment_required(requirements.clone(), "Payment could not be settled"))
+        }
+    }
+}
+
+/// Middleware enforcing payment on the wrapped routes when x402 is enabled
+pub async fn require_payment(State(gate): State<X402Gate>, req: Request, next: Next) -> Response {
+    if !gate.enabled {
+        return next.run(req).await;
+    }
+
+    let requirements = match gate.requirements(req.uri().path()) {
+        Ok(requirements) => requirements,
+        Err(e) => {
+            warn!(error = %e, "x402 enabled but misconfigured");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": format!("Payment misconfigured: {}", e) })),
+            )
+                .into_response();
+        }
+    };
+
+    let Some(header) = req.headers().get(PAYMENT_HEADER).and_then(|h| h.to_str().ok()) else {
+        return payment_required(requirements, "X-PAYMENT header is required");
+    };
+    let payment = match PaymentPayload::from_header(header).and_then(|p| p.matches(&requirements).map(|_| p)) {
+        Ok(payment) => payment,
+        Err(e) => return payment_required(requirements, format!("{:#}", e)),
+    };
+
+    let payer = match gate.client.verify(&payment, &requirements).await {
+        Ok(verified) if verified.is_valid => {
+            info!(payer = ?verified.payer, resource = %requirements.resource, "x402 payment verified");
+            verified.payer
+        }
+        Ok(verified) => {
+            let reason = verified.invalid_reason.unwrap_or_else(|| "Payment invalid".to_string());
+            return payment_required(requirements, reason);
+        }
+        Err(e) => {
+            warn!(error = %e, "x402 verification failed");
+            return payment_required(requirements, "Payment could not be verified");
+        }
+    };
+
+    let settled_first = if req.method().is_idempotent() {
+        None
+    } else {
+        match settle(&gate, &payment, &requirements).await {
+            Ok(receipt) => Some(receipt),
+            Err(response) => return response,
+        }
+    };
+
+    // Bill usage to the payer rather than the (unauthenticated) X-User-Id
+    let mut response = match payer {
+        Some(payer) => metering::scope(payer, next.run(req)).await,
+        None => next.run(req).await,
+    };
+    let receipt = match settled_first {
+        Some(receipt) => {
+            if !response.status().is_success() {
+                let status = response.status();
+                warn!(resource = %requirements.resource, %status, "Request failed after its x402 payment was settled");
+            }
+            receipt
+        }
+        // Don't charge for failed requests
+        None if !response.status().is_success() => return response,
+        None => match settle(&gate, &payment, &requirements).await {
+            Ok(receipt) => receipt,
+            Err(payment_required) => {
+                error!(
+                    resource = %requirements.resource,
+                    "x402 settlement failed after the request ran; its response was withheld"
+                );
+                return payment_required;
+            }
+        },
+    };
+    if let Some(receipt) = receipt {
+        response.headers_mut().insert(PAYMENT_RESPONSE_HEADER, receipt);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn gate(facilitator_url: String) -> X402Gate {
+        X402Gate {
+            enabled: true,
+            network: "base-sepolia".to_string(),
+            pay_to: Some("0xpayee".to_string()),
+            price_usd: 0.05,
+            description: "Dataset analysis".to_string(),
+            public_url: Some("https://api.example.org".to_string()),
+            client: X402Client::new(reqwest::Client::new(), facilitator_url),
+        }
+    }
+
+    /// `/api/analysis` behind `gate`, counting the runs of its handler
+    fn paid_router(gate: X402Gate, runs: Arc<AtomicUsize>) -> axum::Router {
+        let handler = move || async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+            "done"
+        };
+        axum::Router::new()
+            .route("/api/analysis", axum::routing::get(handler.clone()).post(handler))
+            .route_layer(axum::middleware::from_fn_with_state(gate, require_payment))
+    }
+
+    fn paid_request(method: &str) -> Request {
+        let payment = serde_json::json!({
+            "x402Version": 1,
+            "scheme": "exact",
+            "network": "base-sepolia",
+            "payload": {}
+        });
+        Request::builder()
+            .method(method)
+            .uri("/api/analysis")
+            .header(axum::http::header::HOST, "attacker.example")
+            .header("x-forwarded-proto", "ftp")
+            .header(PAYMENT_HEADER, BASE64.encode(serde_json::to_vec(&payment).unwrap()))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn facilitator(settle_status: usize) -> mockito::ServerGuard {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/verify")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"isValid": true, "payer": "0xpayer"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/settle")
+            .with_status(settle_status)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"success": true, "transaction": "0xtx"}"#)
+            .create_async()
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_failed_settlement() {
+        let facilitator = facilitator(500).await;
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = paid_router(gate(facilitator.url()), runs.clone());
+
+        // Starting work is settled first, so nothing runs
+        let response = app.clone().oneshot(paid_request("POST")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let required: PaymentRequiredResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(required.error, "Payment could not be settled");
+        assert_eq!(required.accepts[0].resource, "https://api.example.org/api/analysis");
+
+        // Idempotent requests run first and have their response withheld
+        let response = app.oneshot(paid_request("GET")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_settled_request_gets_receipt() {
+        let facilitator = facilitator(200).await;
+        let runs = Arc::new(AtomicUsize::new(0));
+        let response = paid_router(gate(facilitator.url()), runs.clone())
+            .oneshot(paid_request("POST"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        let receipt = BASE64.decode(response.headers()[PAYMENT_RESPONSE_HEADER].as_bytes()).unwrap();
+        let receipt: SettleResponse = serde_json::from_slice(&receipt).unwrap();
+        assert_eq!(receipt.transaction.as_deref(), Some("0xtx"));
+
+        let unconfigured = X402Gate { public_url: None, ..gate(facilitator.url()) };
+        assert!(unconfigured.requirements("/api/analysis").unwrap_err().to_string().contains("X402_PUBLIC_URL"));
+    }
+
+    #[test]
+    fn test_usd_to_atomic() {
+        assert_eq!(usd_to_atomic(0.05), "50000");
+        assert_eq!(usd_to_atomic(1.0), "1000000");
+        assert_eq!(usd_to_atomic(0.0000004), "0");
+    }
+
+    #[test]
+    fn test_payment_header_roundtrip() {
+        let payload = serde_json::json!({
+            "x402Version": 1,
+            "scheme": "exact",
+            "network": "base-sepolia",
+            "payload": { "signature": "0xabc", "authorization": { "value": "50000" } }
+        });
+        let header = BASE64.encode(serde_json::to_vec(&payload).unwrap());
+        let decoded = PaymentPayload::from_header(&header).unwrap();
+        assert_eq!(decoded.network, "base-sepolia");
+        assert_eq!(decoded.payload["authorization"]["value"], "50000");
+
+        assert!(PaymentPayload::from_header("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_payment_must_match_requirements() {
+        let requirements = PaymentRequirements {
+            scheme: "exact".to_string(),
+            network: "base".to_string(),
+            max_amount_required: "50000".to_string(),
+            resource: "http://localhost/api/analysis".to_string(),
+            description: String::new(),
+            mime_type: "application/json".to_string(),
+            pay_to: "0x0".to_string(),
+            max_timeout_seconds: 300,
+            asset: usdc_address("base").unwrap().to_string(),
+            extra: None,
+        };
+        let payment = PaymentPayload {
+            x402_version: 1,
+            scheme: "exact".to_string(),
+            network: "base-sepolia".to_string(),
+            payload: serde_json::Value::Null,
+        };
+        assert!(payment.matches(&requirements).is_err());
+
+        let json = serde_json::to_value(&requirements).unwrap();
+        assert_eq!(json["maxAmountRequired"], "50000");
+        assert_eq!(json["payTo"], "0x0");
     }
 }
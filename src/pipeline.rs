@@ -0,0 +1,704 @@
+//! Pipeline-from-YAML batch mode
+//!
+//! `oxidized-bio run workflow.yaml` executes the guided workflow (planning,
+//! literature, analysis, drafting) headlessly for each declared dataset and
+//! writes the requested artifacts, so a study can be rerun from one file:
+//!
+//! ```yaml
+//! name: aging-biomarkers
+//! question: Which genes track chronological age in T cells?
+//! species: mouse   # detected from the Ensembl IDs when omitted
+//! parsing: { decimal: ",", na_values: [NA, "-"], quote: "'" }   # how cells are read
+//! datasets:
+//!   - data/expression.csv
+//!   - path: data/replication.tsv
+//!     description: Replication cohort
+//! analysis:
+//!   target: age
+//!   group: cell_type
+//!   covariates: [sex, batch]
+//!   formula: age ~ marker + sex + batch   # or a preset: sex, batch, demographics, full
+//!   platform: annotation/GPL570.soft   # probe-level data only
+//!   samples: data/samples.csv   # sample sheet joined on the first column, e.g. ages for a VCF
+//!   collapse: max
+//!   replicates: mean   # technical replicates: keep, mean or median
+//!   imputation: knn:5   # missing marker values: off (default), mean, median, knn or knn:<k>
+//!   normalization: [log2, quantile]   # applied in order: log2, zscore, quantile (none by default)
+//!   batch: series   # column naming each sample's batch, corrected for
+//!   batch_correction: center   # combat (default) or center
+//!   correlation: spearman   # pearson (default), spearman or kendall
+//!   tables: tsv   # result table format: csv (default) or tsv
+//!   numbers: de   # numbers in draft and report tables: plain (default), en, de, fr or ch
+//!   thresholds: { alpha: 0.05, fdr: 0.1, min_effect: 0.3 }   # significance cut-offs
+//!   kruskal_wallis: true   # with the ANOVA across 3+ groups
+//!   memory_limit: 8G   # refuse larger runs; auto (memory available, default) or off
+//!   threads: 8   # threads of the per-column statistics, at most one per core; 0 (default) uses one per core
+//!   regularization: { penalty: lasso, folds: 10 }   # ridge, lasso, elastic-net (default) or off; l1_ratio, lambda
+//!   roc: median   # target classes for the AUC: auto (binary targets, default), median, off or a cut-off
+//!   gene_sets: [annotation/hallmark.gmt]   # GMT files tested by GSEA along the candidate ranking
+//!   time: followup_months   # time-to-event column; with event, adds Kaplan-Meier curves and Cox models
+//!   event: died   # 1/yes/dead for an event, 0/no/alive for censored
+//!   subject: donor_id   # repeated samples per subject; ranks with mixed models
+//!   cross_reference: 10   # top genes searched as "<gene> aging"; 0 skips
+//! agents: [planning, literature, analysis, drafting]
+//! drafts: 3
+//! template: aging_cell
+//! citation_style: vancouver
+//! outputs:
+//!   dir: results
+//!   targets: [report, plan, literature, drafts, latex, markdown, docx]
+//! ```
+//!
+//! Relative paths are resolved against the workflow file's directory. Each
+//! dataset gets its own subdirectory of `outputs.dir`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::agents::{self, LiteratureResult, PlanningResult};
+use crate::annotation::{Collapse, Species};
+use crate::analysis::batch::BatchCorrection;
+use crate::analysis::formula::Formula;
+use crate::analysis::manifest::Stage;
+use crate::analysis::impute::Imputation;
+use crate::analysis::normalize::Normalization;
+use crate::analysis::memory::MemoryLimit;
+use crate::analysis::qc::Replicates;
+use crate::analysis::regularized::Regularization;
+use crate::analysis::report::TableFormat;
+use crate::analysis::roc::RocSplit;
+use crate::analysis::stats::{CorrelationMethod, Thresholds};
+use crate::analysis::{legends, literature, report, run_analysis, threads, AnalysisConfig};
+use crate::config::{Config, LlmTask};
+use crate::data_registry::{DatasetRecord, ParseOptions, UPLOAD_DIR};
+use crate::manuscript::{CitationStyle, JournalTemplate};
+use crate::models::PlanTask;
+use crate::utils::cancel::CancellationToken;
+use crate::utils::number::NumberFormat;
+
+const DEFAULT_QUESTION: &str = "Discover aging biomarkers from log2-normalized microarray data. \
+Ensure Ensembl IDs and age are primary variables.";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowSpec {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Research question given to the planning and literature agents
+    #[serde(default)]
+    pub question: Option<String>,
+    /// Species of every dataset; detected per dataset when unset
+    #[serde(default)]
+    pub species: Option<Species>,
+    /// Decimal separator, missing-value tokens and quote character of every
+    /// dataset
+    #[serde(default)]
+    pub parsing: ParseOptions,
+    pub datasets: Vec<DatasetSpec>,
+    #[serde(default)]
+    pub analysis: AnalysisSpec,
+    #[serde(default = "AgentKind::all")]
+    pub agents: Vec<AgentKind>,
+    /// Number of draft revisions (1-3)
+    #[serde(default = "default_drafts")]
+    pub drafts: usize,
+    /// Journal template; defaults to the configured one
+    #[serde(default)]
+    pub template: Option<JournalTemplate>,
+    /// CSL style for the markdown and docx targets; defaults to the configured one
+    #[serde(default)]
+    pub citation_style: Option<CitationStyle>,
+    #[serde(default)]
+    pub outputs: OutputSpec,
+}
+
+/// A dataset path, optionally with a description
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum DatasetSpec {
+    Path(PathBuf),
+    Detailed {
+        path: PathBuf,
+        #[serde(default)]
+        description: Option<String>,
+    },
+}
+
+impl DatasetSpec {
+    fn path(&self) -> &Path {
+        match self {
+            DatasetSpec::Path(path) | DatasetSpec::Detailed { path, .. } => path,
+        }
+    }
+
+    fn description(&self) -> Option<String> {
+        match self {
+            DatasetSpec::Path(_) => None,
+            DatasetSpec::Detailed { description, .. } => description.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalysisSpec {
+    pub target: String,
+    pub group: String,
+    pub covariates: Vec<String>,
+    /// Model formula or preset name; replaces `target` and `covariates`
+    pub formula: Option<String>,
+    pub boxplot: Option<String>,
+    pub max_columns: usize,
+    pub max_groups: usize,
+    /// Platform annotation for probe-level data
+    pub platform: Option<PathBuf>,
+    /// Sample sheet whose columns are added to every dataset's samples
+    pub samples: Option<PathBuf>,
+    pub collapse: Collapse,
+    pub replicates: Replicates,
+    /// How missing marker values are filled in
+    pub imputation: Imputation,
+    /// Normalization steps, in order
+    pub normalization: Vec<Normalization>,
+    /// Column naming each sample's batch, corrected for
+    pub batch: Option<String>,
+    pub batch_correction: BatchCorrection,
+    pub correlation: CorrelationMethod,
+    pub tables: TableFormat,
+    /// Decimal separator and grouping of the draft and report tables
+    pub numbers: NumberFormat,
+    /// p-value, q-value and effect-size cut-offs for significant results
+    pub thresholds: Thresholds,
+    /// Add the Kruskal-Wallis test to the ANOVA of multi-level groups
+    pub kruskal_wallis: bool,
+    /// Runs estimated to need more memory fail before reading the rows
+    pub memory_limit: MemoryLimit,
+    /// Threads of the per-column statistics; 0 uses one per core
+    pub threads: usize,
+    /// Penalty of the multi-marker model of the target
+    pub regularization: Regularization,
+    /// Split of the target into two classes for the candidates' AUC
+    pub roc: RocSplit,
+    /// GMT gene set files tested by GSEA against the ranked candidates
+    pub gene_sets: Vec<PathBuf>,
+    /// Time-to-event column of the survival analysis
+    pub time: Option<String>,
+    /// Event column of the survival analysis; other samples are censored
+    pub event: Option<String>,
+    /// Subject column; repeated samples of a subject get a random
+    /// intercept in the ranking models
+    pub subject: Option<String>,
+    /// Top candidates cross-referenced against the literature; defaults to
+    /// the configured `cross_reference.top_n`
+    pub cross_reference: Option<usize>,
+}
+
+impl Default for AnalysisSpec {
+    fn default() -> Self {
+        Self {
+            target: "age".to_string(),
+            group: "cell_type".to_string(),
+            covariates: Vec::new(),
+            formula: None,
+            boxplot: None,
+            max_columns: 50,
+            max_groups: 20,
+            platform: None,
+            samples: None,
+            collapse: Collapse::default(),
+            replicates: Replicates::default(),
+            imputation: Imputation::default(),
+            normalization: Vec::new(),
+            batch: None,
+            batch_correction: BatchCorrection::default(),
+            correlation: CorrelationMethod::default(),
+            tables: TableFormat::default(),
+            numbers: NumberFormat::default(),
+            thresholds: Thresholds::default(),
+            kruskal_wallis: false,
+            memory_limit: MemoryLimit::default(),
+            threads: 0,
+            regularization: Regularization::default(),
+            roc: RocSplit::default(),
+            gene_sets: Vec::new(),
+            time: None,
+            event: None,
+            subject: None,
+            cross_reference: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentKind {
+    Planning,
+    Literature,
+    Analysis,
+    Drafting,
+}
+
+impl AgentKind {
+    fn all() -> Vec<Self> {
+        vec![AgentKind::Planning, AgentKind::Literature, AgentKind::Analysis, AgentKind::Drafting]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputTarget {
+    /// Result tables, plots, manuscript.md and summary.json
+    Report,
+    /// plan.json
+    Plan,
+    /// literature.json
+    Literature,
+    /// draft_1.md .. draft_N.md
+    Drafts,
+    /// manuscript.tex from the final draft
+    Latex,
+    /// draft.md from the final draft, with CSL citations
+    Markdown,
+    /// draft.docx from the final draft, with CSL citations
+    Docx,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSpec {
+    pub dir: PathBuf,
+    pub targets: Vec<OutputTarget>,
+}
+
+impl Default for OutputSpec {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("results"),
+            targets: vec![
+                OutputTarget::Report,
+                OutputTarget::Plan,
+                OutputTarget::Literature,
+                OutputTarget::Drafts,
+                OutputTarget::Latex,
+            ],
+        }
+    }
+}
+
+fn default_drafts() -> usize {
+    3
+}
+
+impl WorkflowSpec {
+    /// Load and validate a workflow file, resolving relative paths against its directory
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read workflow {}", path.display()))?;
+        let mut spec: WorkflowSpec = serde_yaml::from_str(&text)
+            .with_context(|| format!("Invalid workflow {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        spec.resolve_paths(base);
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        for dataset in &mut self.datasets {
+            match dataset {
+                DatasetSpec::Path(path) | DatasetSpec::Detailed { path, .. } => {
+                    if path.is_relative() {
+                        *path = base.join(&*path);
+                    }
+                }
+            }
+        }
+        if self.outputs.dir.is_relative() {
+            self.outputs.dir = base.join(&self.outputs.dir);
+        }
+        let analysis = &mut self.analysis;
+        for path in [&mut analysis.platform, &mut analysis.samples].into_iter().flatten().chain(&mut analysis.gene_sets) {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.datasets.is_empty() {
+            bail!("datasets: at least one dataset is required");
+        }
+        if !(1..=3).contains(&self.drafts) {
+            bail!("drafts: must be between 1 and 3, got {}", self.drafts);
+        }
+        if self.runs(AgentKind::Drafting) && !self.runs(AgentKind::Analysis) {
+            bail!("agents: drafting needs the analysis agent");
+        }
+        if let Some(formula) = &self.analysis.formula {
+            Formula::resolve(formula, &self.analysis.target).map_err(|e| anyhow::anyhow!("analysis.formula: {}", e))?;
+        }
+        self.analysis
+            .thresholds
+            .validate()
+            .map_err(|e| anyhow::anyhow!("analysis.thresholds: {}", e))?;
+        self.analysis
+            .regularization
+            .validate()
+            .map_err(|e| anyhow::anyhow!("analysis.regularization: {}", e))?;
+        threads::validate(self.analysis.threads).map_err(|e| anyhow::anyhow!("analysis.threads: {}", e))?;
+        if self.analysis.time.is_some() != self.analysis.event.is_some() {
+            bail!("analysis: time and event must be given together");
+        }
+        self.parsing.validate().map_err(|e| anyhow::anyhow!("parsing: {}", e))?;
+        let needs = [
+            (OutputTarget::Report, AgentKind::Analysis),
+            (OutputTarget::Plan, AgentKind::Planning),
+            (OutputTarget::Literature, AgentKind::Literature),
+            (OutputTarget::Drafts, AgentKind::Drafting),
+            (OutputTarget::Latex, AgentKind::Drafting),
+            (OutputTarget::Markdown, AgentKind::Drafting),
+            (OutputTarget::Docx, AgentKind::Drafting),
+        ];
+        for (target, agent) in needs {
+            if self.outputs.targets.contains(&target) && !self.runs(agent) {
+                bail!("outputs.targets: {:?} needs the {:?} agent", target, agent);
+            }
+        }
+        Ok(())
+    }
+
+    fn runs(&self, agent: AgentKind) -> bool {
+        self.agents.contains(&agent)
+    }
+
+    fn writes(&self, target: OutputTarget) -> bool {
+        self.outputs.targets.contains(&target)
+    }
+}
+
+/// Run the workflow for every dataset; returns the files written
+pub async fn run(spec: &WorkflowSpec, config: &Config) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for dataset in &spec.datasets {
+        let path = dataset.path();
+        let mut record = DatasetRecord::from_path_with(path, dataset.description(), spec.parsing.clone())
+            .with_context(|| format!("Failed to load dataset {}", path.display()))?;
+        if let Some(samples) = &spec.analysis.samples {
+            record = record
+                .join_samples(samples, Path::new(UPLOAD_DIR))
+                .with_context(|| format!("Failed to join {} to {}", samples.display(), path.display()))?;
+        }
+        if let Some(species) = spec.species {
+            record
+                .set_species(species)
+                .with_context(|| format!("Invalid dataset {}", path.display()))?;
+        }
+        crate::annotation::ensembl::ensure_cached(&config.annotation, &config.network, record.species).await;
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("dataset");
+        let out = spec.outputs.dir.join(stem);
+        std::fs::create_dir_all(&out)
+            .with_context(|| format!("Failed to create output directory {}", out.display()))?;
+        info!(dataset = %path.display(), out = %out.display(), "Running workflow");
+
+        written.extend(
+            run_dataset(spec, config, &record, &out)
+                .await
+                .with_context(|| format!("Workflow failed for {}", path.display()))?,
+        );
+    }
+    Ok(written)
+}
+
+async fn run_dataset(spec: &WorkflowSpec, config: &Config, record: &DatasetRecord, out: &Path) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    let question = spec.question.as_deref().unwrap_or(DEFAULT_QUESTION);
+
+    // Agent stages before the analysis, added to its manifest once it exists
+    let mut stages = Vec::new();
+    let plan: Option<PlanningResult> = if spec.runs(AgentKind::Planning) {
+        let started = Utc::now();
+        let prompt = format!(
+            "Create a research plan: {} Dataset has {} rows and {} columns.",
+            question,
+            record.row_count,
+            record.columns.len()
+        );
+        let plan = agents::PlanningAgent::generate_plan(&prompt, None, config, &CancellationToken::new())
+            .await
+            .map_err(|e| anyhow::anyhow!("Planning failed: {}", e))?;
+        info!(objective = %plan.current_objective, tasks = plan.plan.len(), "Plan generated");
+        stages.push(Stage::finished("planning", config.llm.for_task(LlmTask::Planning).as_ref(), started));
+        if spec.writes(OutputTarget::Plan) {
+            written.push(write_json(out, "plan.json", &plan)?);
+        }
+        Some(plan)
+    } else {
+        None
+    };
+
+    let mut literature: Vec<LiteratureResult> = Vec::new();
+    if spec.runs(AgentKind::Literature) {
+        let started = Utc::now();
+        // Without a plan, the question itself is the only literature task
+        let tasks: Vec<PlanTask> = match &plan {
+            Some(plan) => plan.plan.iter().filter(|t| t.task_type == "LITERATURE").cloned().collect(),
+            None => vec![PlanTask {
+                id: None,
+                job_id: None,
+                objective: question.to_string(),
+                datasets: Vec::new(),
+                task_type: "LITERATURE".to_string(),
+                level: None,
+                start: None,
+                end: None,
+                output: None,
+                artifacts: None,
+            }],
+        };
+        for task in &tasks {
+            let result = agents::LiteratureAgent::execute_task(task, config, &CancellationToken::new())
+                .await
+                .map_err(|e| anyhow::anyhow!("Literature task failed: {}", e))?;
+            literature.push(result);
+        }
+        info!(tasks = literature.len(), "Literature review complete");
+        stages.push(Stage::finished("literature", config.llm.for_task(LlmTask::Literature).as_ref(), started));
+        if spec.writes(OutputTarget::Literature) {
+            written.push(write_json(out, "literature.json", &literature)?);
+        }
+    }
+
+    if !spec.runs(AgentKind::Analysis) {
+        return Ok(written);
+    }
+    let analysis_spec = &spec.analysis;
+    let formula = analysis_spec
+        .formula
+        .as_deref()
+        .map(|f| Formula::resolve(f, &analysis_spec.target))
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("analysis.formula: {}", e))?;
+    let target = formula.as_ref().map_or(&analysis_spec.target, |f| &f.response).clone();
+    let analysis_config = AnalysisConfig {
+        target_column: Some(target.clone()),
+        group_column: Some(analysis_spec.group.clone()),
+        covariates: analysis_spec.covariates.clone(),
+        boxplot_column: analysis_spec.boxplot.clone(),
+        max_columns: analysis_spec.max_columns,
+        max_groups: analysis_spec.max_groups,
+        platform: analysis_spec.platform.clone(),
+        collapse: analysis_spec.collapse,
+        formula,
+        replicates: analysis_spec.replicates,
+        imputation: analysis_spec.imputation,
+        normalization: analysis_spec.normalization.clone(),
+        batch_column: analysis_spec.batch.clone(),
+        batch_correction: analysis_spec.batch_correction,
+        correlation: analysis_spec.correlation,
+        tables: analysis_spec.tables,
+        numbers: analysis_spec.numbers,
+        thresholds: analysis_spec.thresholds,
+        kruskal_wallis: analysis_spec.kruskal_wallis,
+        memory_limit: analysis_spec.memory_limit,
+        threads: analysis_spec.threads,
+        regularization: analysis_spec.regularization,
+        roc: analysis_spec.roc,
+        gene_sets: analysis_spec.gene_sets.clone(),
+        time_column: analysis_spec.time.clone(),
+        event_column: analysis_spec.event.clone(),
+        subject_column: analysis_spec.subject.clone(),
+        ..Default::default()
+    };
+    let started = std::time::Instant::now();
+    let mut analysis = run_analysis(record, &analysis_config, out, &CancellationToken::new()).context("Analysis failed")?;
+    crate::metering::record_analysis(started, &record.dataset.id);
+    if let Some(manifest) = &mut analysis.manifest {
+        manifest.stages.splice(0..0, stages);
+    }
+    let top_n = analysis_spec.cross_reference.unwrap_or(config.cross_reference.top_n);
+    literature::cross_reference(config, &mut analysis, top_n).await;
+    legends::polish(config, &mut analysis).await;
+    info!(summary = %analysis.summary, "Analysis complete");
+    let template = spec.template.unwrap_or(config.manuscript.template);
+    if spec.writes(OutputTarget::Report) {
+        written.extend(report::write_report(out, record, &analysis_config, &analysis, template)?);
+    }
+
+    if !spec.runs(AgentKind::Drafting) {
+        return Ok(written);
+    }
+    let manuscript = crate::analysis::build_manuscript(
+        &record.dataset.id,
+        &target,
+        &analysis_spec.group,
+        record,
+        &analysis,
+        template,
+    );
+    let findings = analysis.findings();
+    let started = Utc::now();
+    let mut draft = String::new();
+    for version in 1..=spec.drafts {
+        let skeleton = crate::manuscript::automated_draft(version, &manuscript, plan.as_ref(), &literature);
+        draft = agents::DraftingAgent::revise(version, skeleton, &[], Some(&findings), config).await.text;
+        if spec.writes(OutputTarget::Drafts) {
+            let path = out.join(format!("draft_{}.md", version));
+            std::fs::write(&path, &draft)?;
+            written.push(path);
+        }
+    }
+    if let Some(manifest) = &mut analysis.manifest {
+        manifest.stage(Stage::finished("drafting", config.llm.for_task(LlmTask::Drafting).as_ref(), started));
+        manifest.write(out)?;
+    }
+    if spec.writes(OutputTarget::Latex) {
+        written.extend(crate::manuscript::export_latex(out, &draft, &literature, Some(&analysis), template)?);
+    }
+    let style = spec.citation_style.as_ref().unwrap_or(&config.manuscript.citation_style);
+    if spec.writes(OutputTarget::Markdown) {
+        written.push(crate::manuscript::export_markdown(out, &draft, &literature, style, template)?);
+    }
+    if spec.writes(OutputTarget::Docx) {
+        written.push(crate::manuscript::export_docx(out, &draft, &literature, Some(&analysis), style, template)?);
+    }
+
+    Ok(written)
+}
+
+fn write_json<T: serde::Serialize>(dir: &Path, name: &str, value: &T) -> Result<PathBuf> {
+    let path = dir.join(name);
+    std::fs::write(&path, serde_json::to_string_pretty(value)?)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_defaults_and_paths() {
+        let mut spec: WorkflowSpec = serde_yaml::from_str(
+            "datasets:\n  - data/a.csv\n  - path: /abs/b.tsv\n    description: Replication\nanalysis:\n  target: age\n",
+        )
+        .unwrap();
+        spec.resolve_paths(Path::new("/work"));
+        spec.validate().unwrap();
+        assert_eq!(spec.datasets[0].path(), Path::new("/work/data/a.csv"));
+        assert_eq!(spec.datasets[1].path(), Path::new("/abs/b.tsv"));
+        assert_eq!(spec.datasets[1].description().as_deref(), Some("Replication"));
+        assert_eq!(spec.outputs.dir, PathBuf::from("/work/results"));
+        assert_eq!(spec.agents, AgentKind::all());
+        assert_eq!(spec.analysis.group, "cell_type");
+    }
+
+    #[test]
+    fn test_validation_errors_name_the_field() {
+        let spec: WorkflowSpec =
+            serde_yaml::from_str("datasets: [a.csv]\nagents: [planning, drafting]\n").unwrap();
+        assert!(spec.validate().unwrap_err().to_string().starts_with("agents:"));
+
+        let spec: WorkflowSpec = serde_yaml::from_str(
+            "datasets: [a.csv]\nagents: [analysis]\noutputs:\n  targets: [report, latex]\n",
+        )
+        .unwrap();
+        assert!(spec.validate().unwrap_err().to_string().starts_with("outputs.targets:"));
+
+        let spec: WorkflowSpec =
+            serde_yaml::from_str("datasets: [a.csv]\nanalysis:\n  thresholds: { fdr: 1.5 }\n").unwrap();
+        assert!(spec.validate().unwrap_err().to_string().starts_with("analysis.thresholds:"));
+
+        let spec: WorkflowSpec = serde_yaml::from_str("datasets: [a.csv]\nparsing: { decimal: ';' }\n").unwrap();
+        assert!(spec.validate().unwrap_err().to_string().starts_with("parsing:"));
+
+        assert!(serde_yaml::from_str::<WorkflowSpec>("datasets: [a.csv]\nagent: [planning]\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_analysis_only_workflow() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut csv = String::from("age,cell_type,gene_a\n");
+        for i in 0..10 {
+            csv.push_str(&format!("{},{},{}\n", 20 + i * 4, if i % 2 == 0 { "a" } else { "b" }, i as f64 * 0.3));
+        }
+        std::fs::write(dir.path().join("cohort.csv"), csv).unwrap();
+        let workflow = dir.path().join("workflow.yaml");
+        std::fs::write(&workflow, "datasets: [cohort.csv]\nagents: [analysis]\noutputs:\n  dir: out\n  targets: [report]\n").unwrap();
+
+        let spec = WorkflowSpec::load(&workflow).unwrap();
+        let files = run(&spec, &Config::from_env().unwrap()).await.unwrap();
+        assert!(files.contains(&dir.path().join("out/cohort/manuscript.md")));
+    }
+
+    /// Upload -> plan -> literature -> findings -> drafts -> LaTeX against the
+    /// mock LLM and search engine, so it runs offline and deterministically
+    #[tokio::test]
+    async fn test_full_workflow_with_mocks() {
+        let mut config = Config::from_env().unwrap();
+        config.llm.default_provider = "mock".to_string();
+        config.llm.default_model = "mock".to_string();
+        config.llm.routes.clear();
+        config.search.mock_enabled = true;
+        config.annotation.download = false;
+        config.cross_reference.top_n = 3;
+
+        let dir = tempfile::tempdir().unwrap();
+        let demo = crate::demo::DemoOptions {
+            rows: 40,
+            markers: 20,
+            planted: 3,
+            ..Default::default()
+        };
+        let dataset = crate::demo::generate(&demo).unwrap();
+        let written = crate::demo::write(&dataset, &dir.path().join("demo.csv")).unwrap();
+        let bytes = std::fs::read(&written[0]).unwrap();
+        let record = DatasetRecord::from_upload(&dir.path().join("uploads"), "cohort.csv", &bytes, None, None, ParseOptions::default())
+            .await
+            .unwrap();
+
+        let workflow = dir.path().join("workflow.yaml");
+        std::fs::write(
+            &workflow,
+            format!(
+                "question: Which genes track chronological age?\ndatasets: [{}]\ndrafts: 2\noutputs:\n  dir: out\n",
+                record.local_path
+            ),
+        )
+        .unwrap();
+        let spec = WorkflowSpec::load(&workflow).unwrap();
+        let files = run(&spec, &config).await.unwrap();
+
+        let stem = Path::new(&record.local_path).file_stem().unwrap().to_str().unwrap().to_string();
+        let out = dir.path().join("out").join(stem);
+        for name in [
+            "plan.json",
+            "literature.json",
+            "biomarker_candidates.csv",
+            "draft_1.md",
+            "draft_2.md",
+            "manuscript.tex",
+            "references.bib",
+        ] {
+            assert!(files.contains(&out.join(name)), "{} not written", name);
+        }
+
+        let plan: PlanningResult = serde_json::from_slice(&std::fs::read(out.join("plan.json")).unwrap()).unwrap();
+        assert!(plan.current_objective.starts_with("Mock plan:"));
+        assert_eq!(plan.plan.len(), 2);
+
+        let literature: Vec<LiteratureResult> =
+            serde_json::from_slice(&std::fs::read(out.join("literature.json")).unwrap()).unwrap();
+        assert_eq!(literature.len(), 2);
+        assert!(literature
+            .iter()
+            .flat_map(|l| &l.sources)
+            .all(|s| s.doi.as_deref().is_some_and(|d| d.starts_with("10.5555/mock."))));
+
+        let summary: serde_json::Value = serde_json::from_slice(&std::fs::read(out.join("summary.json")).unwrap()).unwrap();
+        assert_eq!(summary["cross_reference"]["engine"], "mock");
+        let bib = std::fs::read_to_string(out.join("references.bib")).unwrap();
+        assert!(bib.contains("10.5555/mock."));
+    }
+}
@@ -0,0 +1,415 @@
+//! Projects
+//!
+//! A project groups the datasets, analysis runs and manuscript drafts of one
+//! study, so that a researcher juggling several studies does not share one
+//! dataset list and one draft history between them. Projects are saved to
+//! `projects.json` in the local data directory together with the active
+//! one. Each project keeps its drafts in `projects/<name>/drafts.json`; the
+//! default project keeps the `drafts.json` of earlier versions, so existing
+//! drafts stay where they were. `/project new|switch|list` manages them in
+//! the TUI.
+//!
+//! A project can carry a brief (aims, cohort, tissue) and a glossary of its
+//! own terms, such as gene panels and cohort names. Both are handed to the
+//! planning and reply prompts of every question asked in the project, so
+//! searches and answers start from the study's context without the user
+//! restating it (`/project brief`, `/project glossary`).
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::agents::ReplyMode;
+use crate::data_registry::{DatasetRecord, ParseOptions};
+use crate::manuscript::history::DRAFTS_FILE;
+
+/// Project file under `<data dir>/oxidized-bio/`
+pub const PROJECTS_FILE: &str = "projects.json";
+
+/// Project used until another one is created
+pub const DEFAULT_PROJECT: &str = "default";
+
+/// Longest project name
+const MAX_NAME: usize = 64;
+
+/// Longest brief, in characters, so that it leaves room for the findings
+const MAX_BRIEF: usize = 4000;
+
+/// A dataset of a project, with what is needed to load it again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectDataset {
+    pub id: String,
+    pub filename: String,
+    pub path: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub parsing: ParseOptions,
+    pub added_at: DateTime<Utc>,
+}
+
+/// An analysis run of one of the project's datasets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectAnalysis {
+    pub dataset_id: String,
+    /// Directory the tables, plots and supplement were written to
+    pub dir: PathBuf,
+    pub summary: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+    /// In the order they were added; a dataset loaded again replaces its
+    /// earlier entry
+    #[serde(default)]
+    pub datasets: Vec<ProjectDataset>,
+    /// Latest run of each dataset, oldest first
+    #[serde(default)]
+    pub analyses: Vec<ProjectAnalysis>,
+    /// Dataset the project was last working on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_dataset: Option<String>,
+    /// Aims, cohort and anything else the prompts should take as given
+    #[serde(default)]
+    pub brief: String,
+    /// Project terms (e.g. a gene panel's name) and what they stand for
+    #[serde(default)]
+    pub glossary: BTreeMap<String, String>,
+    /// Reply mode picked with `/mode`; guessed from each message when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_mode: Option<ReplyMode>,
+}
+
+impl Project {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            created_at: Utc::now(),
+            datasets: Vec::new(),
+            analyses: Vec::new(),
+            active_dataset: None,
+            brief: String::new(),
+            glossary: BTreeMap::new(),
+            reply_mode: None,
+        }
+    }
+
+    pub fn contains(&self, dataset_id: &str) -> bool {
+        self.datasets.iter().any(|d| d.id == dataset_id)
+    }
+
+    pub fn dataset(&self, dataset_id: &str) -> Option<&ProjectDataset> {
+        self.datasets.iter().find(|d| d.id == dataset_id)
+    }
+
+    /// Add `record` and make it the active dataset
+    pub fn add_dataset(&mut self, record: &DatasetRecord) {
+        self.datasets.retain(|d| d.id != record.dataset.id);
+        self.datasets.push(ProjectDataset {
+            id: record.dataset.id.clone(),
+            filename: record.dataset.filename.clone(),
+            path: record.local_path.clone(),
+            description: record.dataset.description.clone(),
+            parsing: record.parsing.clone(),
+            added_at: Utc::now(),
+        });
+        self.active_dataset = Some(record.dataset.id.clone());
+    }
+
+    /// Record a run of `dataset_id`, replacing its previous one
+    pub fn add_analysis(&mut self, dataset_id: &str, dir: &Path, summary: &str) {
+        self.analyses.retain(|a| a.dataset_id != dataset_id);
+        self.analyses.push(ProjectAnalysis {
+            dataset_id: dataset_id.to_string(),
+            dir: dir.to_path_buf(),
+            summary: summary.to_string(),
+            created_at: Utc::now(),
+        });
+    }
+
+    /// Drop `dataset_id` and its analysis runs, returning the runs'
+    /// directories
+    pub fn remove_dataset(&mut self, dataset_id: &str) -> Vec<PathBuf> {
+        self.datasets.retain(|d| d.id != dataset_id);
+        if self.active_dataset.as_deref() == Some(dataset_id) {
+            self.active_dataset = None;
+        }
+        let (removed, kept): (Vec<ProjectAnalysis>, Vec<ProjectAnalysis>) =
+            std::mem::take(&mut self.analyses).into_iter().partition(|a| a.dataset_id == dataset_id);
+        self.analyses = kept;
+        removed.into_iter().map(|a| a.dir).collect()
+    }
+
+    /// Replace the brief; an empty one removes it
+    pub fn set_brief(&mut self, brief: &str) -> Result<()> {
+        let brief = brief.trim();
+        if brief.chars().count() > MAX_BRIEF {
+            bail!("The brief has {} characters; keep it under {}", brief.chars().count(), MAX_BRIEF);
+        }
+        self.brief = brief.to_string();
+        Ok(())
+    }
+
+    /// Define `term`, or remove it when `definition` is empty; whether the
+    /// glossary changed
+    pub fn define(&mut self, term: &str, definition: &str) -> bool {
+        let (term, definition) = (term.trim(), definition.trim());
+        if definition.is_empty() {
+            return self.glossary.remove(term).is_some();
+        }
+        if term.is_empty() {
+            return false;
+        }
+        self.glossary.insert(term.to_string(), definition.to_string());
+        true
+    }
+
+    /// The brief and glossary as handed to the planning and reply prompts;
+    /// `None` when neither is set
+    pub fn prompt_context(&self) -> Option<String> {
+        if self.brief.is_empty() && self.glossary.is_empty() {
+            return None;
+        }
+        let mut context = match self.description.as_str() {
+            "" => format!("Project: {}\n", self.name),
+            description => format!("Project: {} ({})\n", self.name, description),
+        };
+        if !self.brief.is_empty() {
+            context.push_str(&format!("Brief: {}\n", self.brief));
+        }
+        if !self.glossary.is_empty() {
+            context.push_str("Glossary:\n");
+            for (term, definition) in &self.glossary {
+                context.push_str(&format!("- {}: {}\n", term, definition));
+            }
+        }
+        Some(context.trim_end().to_string())
+    }
+
+    /// "aging-tcells: T cell aging (3 datasets, 2 analyses)"
+    pub fn describe(&self) -> String {
+        let description = if self.description.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", self.description)
+        };
+        format!(
+            "{}{} ({} dataset(s), {} analysis run(s))",
+            self.name,
+            description,
+            self.datasets.len(),
+            self.analyses.len()
+        )
+    }
+}
+
+/// Whether `name` can name a project (and its directory)
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_NAME {
+        bail!("Project names have 1-{} characters", MAX_NAME);
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("Invalid project name '{}' (use letters, digits, - and _)", name);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectStore {
+    projects: Vec<Project>,
+    /// Name of the active project
+    active: String,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Default for ProjectStore {
+    fn default() -> Self {
+        Self {
+            projects: vec![Project::new(DEFAULT_PROJECT, "")],
+            active: DEFAULT_PROJECT.to_string(),
+            path: None,
+        }
+    }
+}
+
+impl ProjectStore {
+    /// Projects from the local data directory; only the default project
+    /// when there are none yet
+    pub fn load_default() -> Self {
+        match dirs::data_local_dir() {
+            Some(dir) => Self::load(&dir.join("oxidized-bio").join(PROJECTS_FILE)),
+            None => Self::default(),
+        }
+    }
+
+    /// Projects from `path`. A missing or unreadable file starts with the
+    /// default project, to be written to `path`.
+    pub fn load(path: &Path) -> Self {
+        let mut store: Self = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(error = %e, path = %path.display(), "Ignoring unreadable projects");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        if !store.projects.iter().any(|p| p.name == store.active) {
+            store.active = store.projects.first().map_or_else(|| DEFAULT_PROJECT.to_string(), |p| p.name.clone());
+        }
+        if store.projects.is_empty() {
+            store.projects.push(Project::new(DEFAULT_PROJECT, ""));
+        }
+        store.path = Some(path.to_path_buf());
+        store
+    }
+
+    pub fn projects(&self) -> &[Project] {
+        &self.projects
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Project> {
+        self.projects.iter().find(|p| p.name == name)
+    }
+
+    pub fn active(&self) -> &Project {
+        self.get(&self.active).expect("active project exists")
+    }
+
+    /// Change the active project and save
+    pub fn update(&mut self, change: impl FnOnce(&mut Project)) {
+        let active = &self.active;
+        if let Some(project) = self.projects.iter_mut().find(|p| &p.name == active) {
+            change(project);
+        }
+        self.persist();
+    }
+
+    /// Drop `dataset_id` from every project and save, returning the
+    /// directories of its analysis runs
+    pub fn remove_dataset(&mut self, dataset_id: &str) -> Vec<PathBuf> {
+        let dirs = self.projects.iter_mut().flat_map(|p| p.remove_dataset(dataset_id)).collect();
+        self.persist();
+        dirs
+    }
+
+    /// Create a project and make it active
+    pub fn create(&mut self, name: &str, description: &str) -> Result<&Project> {
+        validate_name(name)?;
+        if self.get(name).is_some() {
+            bail!("Project '{}' already exists; /project switch {} opens it", name, name);
+        }
+        self.projects.push(Project::new(name, description));
+        self.switch(name)
+    }
+
+    /// Make `name` the active project
+    pub fn switch(&mut self, name: &str) -> Result<&Project> {
+        if self.get(name).is_none() {
+            let names: Vec<&str> = self.projects.iter().map(|p| p.name.as_str()).collect();
+            bail!("No project '{}' (projects: {})", name, names.join(", "));
+        }
+        self.active = name.to_string();
+        self.persist();
+        Ok(self.active())
+    }
+
+    /// Draft history file of the active project; `None` when the store is
+    /// not saved
+    pub fn drafts_path(&self) -> Option<PathBuf> {
+        let dir = self.path.as_ref()?.parent()?;
+        Some(match self.active.as_str() {
+            DEFAULT_PROJECT => dir.join(DRAFTS_FILE),
+            name => dir.join("projects").join(name).join(DRAFTS_FILE),
+        })
+    }
+
+    /// Write the projects to their file
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self.save() {
+            warn!(error = %e, "Failed to save projects");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_projects_persist_with_their_own_drafts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECTS_FILE);
+
+        let mut store = ProjectStore::load(&path);
+        assert_eq!(store.active().name, DEFAULT_PROJECT);
+        assert_eq!(store.drafts_path(), Some(dir.path().join(DRAFTS_FILE)));
+        store.update(|p| p.add_analysis("ds1", Path::new("artifacts/analysis/ds1"), "first run"));
+
+        store.create("aging-tcells", "T cell aging").unwrap();
+        assert!(store.create("aging-tcells", "").unwrap_err().to_string().contains("already exists"));
+        assert!(store.create("../escape", "").is_err());
+        assert!(store.switch("missing").unwrap_err().to_string().contains("aging-tcells"));
+        store.update(|p| p.add_analysis("ds2", Path::new("artifacts/analysis/ds2"), "second run"));
+        store.update(|p| p.reply_mode = Some(ReplyMode::Report));
+        assert_eq!(
+            store.drafts_path(),
+            Some(dir.path().join("projects").join("aging-tcells").join(DRAFTS_FILE))
+        );
+
+        let mut reloaded = ProjectStore::load(&path);
+        assert_eq!(reloaded.active().name, "aging-tcells");
+        assert_eq!(reloaded.active().analyses[0].dataset_id, "ds2");
+        assert_eq!(reloaded.active().reply_mode, Some(ReplyMode::Report));
+        assert_eq!(reloaded.active().describe(), "aging-tcells: T cell aging (0 dataset(s), 1 analysis run(s))");
+        assert_eq!(reloaded.switch(DEFAULT_PROJECT).unwrap().analyses[0].dataset_id, "ds1");
+        assert_eq!(ProjectStore::load(&path).active().name, DEFAULT_PROJECT);
+
+        assert_eq!(reloaded.remove_dataset("ds1"), vec![PathBuf::from("artifacts/analysis/ds1")]);
+        let reloaded = ProjectStore::load(&path);
+        assert!(reloaded.active().analyses.is_empty());
+        assert_eq!(reloaded.get("aging-tcells").unwrap().analyses.len(), 1);
+    }
+
+    #[test]
+    fn test_prompt_context() {
+        let mut project = Project::new("aging-tcells", "T cell aging");
+        assert_eq!(project.prompt_context(), None);
+
+        project.set_brief("  Blood CD8+ T cells from 120 donors aged 20-90.  ").unwrap();
+        assert!(project.define("SenPanel", "CDKN2A, CDKN1A, TP53"));
+        assert!(project.define("HALC", "Healthy Aging Longitudinal Cohort"));
+        assert!(!project.define("missing", ""));
+        assert_eq!(
+            project.prompt_context().unwrap(),
+            "Project: aging-tcells (T cell aging)\n\
+Brief: Blood CD8+ T cells from 120 donors aged 20-90.\n\
+Glossary:\n\
+- HALC: Healthy Aging Longitudinal Cohort\n\
+- SenPanel: CDKN2A, CDKN1A, TP53"
+        );
+
+        assert!(project.define("HALC", " "));
+        project.set_brief("").unwrap();
+        assert_eq!(project.prompt_context().unwrap(), "Project: aging-tcells (T cell aging)\nGlossary:\n- SenPanel: CDKN2A, CDKN1A, TP53");
+        assert!(project.set_brief(&"x".repeat(MAX_BRIEF + 1)).is_err());
+    }
+}
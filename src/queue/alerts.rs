@@ -0,0 +1,145 @@
+//! Literature alert bookkeeping
+//!
+//! A literature alert is a scheduled [`JobType::LiteratureAlert`] job. Every
+//! paper it finds is recorded per schedule; papers seen for the first time are
+//! kept un-notified until the TUI shows them to the user.
+//!
+//! [`JobType::LiteratureAlert`]: super::jobs::JobType::LiteratureAlert
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::agents::literature::SourceReference;
+
+/// A paper recorded by a literature alert
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AlertPaper {
+    pub id: Uuid,
+    pub schedule_id: Option<Uuid>,
+    pub paper_key: String,
+    pub biomarker: String,
+    pub title: String,
+    pub year: Option<i32>,
+    pub doi: Option<String>,
+    pub url: Option<String>,
+    pub first_seen_at: chrono::DateTime<chrono::Utc>,
+    pub notified_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Stable identity for a paper: DOI if present, then URL, then title
+pub fn paper_key(source: &SourceReference) -> String {
+    if let Some(doi) = source.doi.as_deref().filter(|d| !d.trim().is_empty()) {
+        return format!("doi:{}", doi.trim().to_ascii_lowercase());
+    }
+    if let Some(url) = source.url.as_deref().filter(|u| !u.trim().is_empty()) {
+        return format!("url:{}", url.trim().trim_end_matches('/'));
+    }
+    let title = source
+        .title
+        .to_ascii_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("title:{}", title)
+}
+
+/// Record papers found for `biomarker`; returns how many had not been seen
+/// before for this schedule.
+pub async fn record_papers(
+    pool: &PgPool,
+    schedule_id: Option<Uuid>,
+    biomarker: &str,
+    sources: &[SourceReference],
+) -> Result<usize> {
+    let mut new_papers = 0;
+    for source in sources {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO literature_alert_papers (schedule_id, paper_key, biomarker, title, year, doi, url)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (schedule_id, paper_key) DO NOTHING
+            "#
+        )
+        .bind(schedule_id)
+        .bind(paper_key(source))
+        .bind(biomarker)
+        .bind(&source.title)
+        .bind(source.year)
+        .bind(&source.doi)
+        .bind(&source.url)
+        .execute(pool)
+        .await?;
+        new_papers += result.rows_affected() as usize;
+    }
+    Ok(new_papers)
+}
+
+/// Papers not yet shown to the user, oldest first
+pub async fn pending_papers(pool: &PgPool) -> Result<Vec<AlertPaper>> {
+    let papers = sqlx::query_as::<_, AlertPaper>(
+        "SELECT * FROM literature_alert_papers WHERE notified_at IS NULL ORDER BY first_seen_at ASC LIMIT 200"
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(papers)
+}
+
+pub async fn mark_notified(pool: &PgPool, ids: &[Uuid]) -> Result<()> {
+    sqlx::query("UPDATE literature_alert_papers SET notified_at = NOW() WHERE id = ANY($1)")
+        .bind(ids)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Group pending papers into a chat-ready notification
+pub fn format_notification(papers: &[AlertPaper]) -> String {
+    let mut biomarkers: Vec<&str> = papers.iter().map(|p| p.biomarker.as_str()).collect();
+    biomarkers.sort_unstable();
+    biomarkers.dedup();
+
+    let mut out = format!("Literature alert: {} new paper(s)\n", papers.len());
+    for biomarker in biomarkers {
+        out.push_str(&format!("\n{}:\n", biomarker));
+        for paper in papers.iter().filter(|p| p.biomarker == biomarker) {
+            let year = paper.year.map(|y| format!(" ({})", y)).unwrap_or_default();
+            let link = paper
+                .doi
+                .as_ref()
+                .map(|d| format!(" doi:{}", d))
+                .or_else(|| paper.url.as_ref().map(|u| format!(" {}", u)))
+                .unwrap_or_default();
+            out.push_str(&format!("- {}{}{}\n", paper.title, year, link));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(title: &str, doi: Option<&str>, url: Option<&str>) -> SourceReference {
+        SourceReference {
+            title: title.to_string(),
+            authors: None,
+            year: None,
+            doi: doi.map(str::to_string),
+            url: url.map(str::to_string),
+            summary: String::new(),
+            quality: None,
+        }
+    }
+
+    #[test]
+    fn test_paper_key_prefers_doi() {
+        let s = source("A Paper", Some("10.1000/ABC"), Some("https://example.org/a"));
+        assert_eq!(paper_key(&s), "doi:10.1000/abc");
+        let s = source("A Paper", None, Some("https://example.org/a/"));
+        assert_eq!(paper_key(&s), "url:https://example.org/a");
+        let s = source("  A   Paper ", None, None);
+        assert_eq!(paper_key(&s), "title:a paper");
+    }
+}
@@ -0,0 +1,77 @@
+//! Datasets of queued analyses
+//!
+//! Workers on other machines cannot read the uploader's `local_path`, so
+//! the dataset is stored once in `job_datasets`, keyed by its SHA-256, and
+//! the [`AnalysisJobPayload`] only names it. The same file queued twice is
+//! stored once.
+//!
+//! [`AnalysisJobPayload`]: super::jobs::AnalysisJobPayload
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Row};
+
+/// Hex SHA-256 of `content`, the key it is stored under
+pub fn key(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Store `content` unless it is stored already, and return its key
+pub async fn store(pool: &PgPool, filename: &str, content: &[u8]) -> Result<String> {
+    let key = key(content);
+    sqlx::query(
+        r#"
+        INSERT INTO job_datasets (sha256, filename, content)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (sha256) DO UPDATE SET stored_at = NOW()
+        "#,
+    )
+    .bind(&key)
+    .bind(filename)
+    .bind(content)
+    .execute(pool)
+    .await?;
+    Ok(key)
+}
+
+/// Bytes of the dataset stored under `key`
+pub async fn load(pool: &PgPool, key: &str) -> Result<Vec<u8>> {
+    let row = sqlx::query("SELECT content FROM job_datasets WHERE sha256 = $1")
+        .bind(key)
+        .fetch_optional(pool)
+        .await?
+        .with_context(|| format!("Dataset {} is no longer stored for the queue", key))?;
+    Ok(row.get("content"))
+}
+
+/// Remove datasets that no queued, running or dead analysis job refers to,
+/// once they are an hour old so a job about to be enqueued keeps its own
+pub async fn prune(pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM job_datasets d
+        WHERE d.stored_at < NOW() - INTERVAL '1 hour'
+          AND NOT EXISTS (
+            SELECT 1 FROM jobs j
+            WHERE j.job_type = 'analysis'
+              AND j.status <> 'completed'
+              AND j.payload->>'dataset_sha256' = d.sha256
+          )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_is_content_sha256() {
+        assert_eq!(key(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(key(b"sample,age\ns1,30\n"), key(b"sample,age\ns1,30\n"));
+        assert_ne!(key(b"sample,age\ns1,30\n"), key(b"sample,age\ns1,31\n"));
+    }
+}
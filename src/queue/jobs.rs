@@ -1,18 +1,550 @@
-// Job definitions stub
-// TODO: Define all job types for the queue
+//! Job definitions
+//!
+//! Every job stored in the `jobs` table carries a [`JobType`] and a JSON
+//! payload. The typed payload structs below are what producers enqueue and
+//! what workers deserialize before dispatching.
+
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::analysis::batch::BatchCorrection;
+use crate::analysis::impute::Imputation;
+use crate::analysis::memory::MemoryLimit;
+use crate::analysis::normalize::Normalization;
+use crate::analysis::qc::Replicates;
+use crate::analysis::regularized::Regularization;
+use crate::analysis::report::TableFormat;
+use crate::analysis::roc::RocSplit;
+use crate::analysis::stats::{CorrelationMethod, Thresholds};
+use crate::analysis::AnalysisConfig;
+use crate::annotation::Species;
+use crate::data_registry::{DatasetRecord, ParseOptions};
+use crate::utils::number::NumberFormat;
+
+/// Kinds of background work the queue knows how to run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum JobType {
+    /// Statistical analysis run over an uploaded dataset
+    Analysis,
+    /// A batch of literature search objectives
+    LiteratureBatch,
+    /// Parse and register a document/dataset from disk
+    DocumentIngestion,
+    /// Re-run a saved literature query and record papers not seen before
+    LiteratureAlert,
+}
+
+impl JobType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobType::Analysis => "analysis",
+            JobType::LiteratureBatch => "literature_batch",
+            JobType::DocumentIngestion => "document_ingestion",
+            JobType::LiteratureAlert => "literature_alert",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "analysis" => Some(JobType::Analysis),
+            "literature_batch" => Some(JobType::LiteratureBatch),
+            "document_ingestion" => Some(JobType::DocumentIngestion),
+            "literature_alert" => Some(JobType::LiteratureAlert),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for JobType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Lifecycle of a queued job
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    /// Exhausted all attempts; parked for manual inspection
+    Dead,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Dead => "dead",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "completed" => Some(JobStatus::Completed),
+            "failed" => Some(JobStatus::Failed),
+            "dead" => Some(JobStatus::Dead),
+            _ => None,
+        }
+    }
+}
+
+/// Priority lane. Interactive jobs (started from the TUI or an HTTP request
+/// someone is waiting on) are always claimed before batch jobs, and workers
+/// reserved for the interactive lane never pick up batch work.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum JobPriority {
+    Interactive,
+    #[default]
+    Batch,
+}
+
+impl JobPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobPriority::Interactive => "interactive",
+            JobPriority::Batch => "batch",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "interactive" => Some(JobPriority::Interactive),
+            "batch" => Some(JobPriority::Batch),
+            _ => None,
+        }
+    }
+}
+
+/// Retry behaviour for a single job
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one
+    pub max_attempts: i32,
+    /// Delay before the first retry; doubled for each subsequent retry
+    pub base_delay: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff delay after the given (1-based) failed attempt
+    pub fn delay_for_attempt(&self, attempt: i32) -> Duration {
+        let exponent = attempt.saturating_sub(1).clamp(0, 20) as u32;
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(exponent));
+        delay.min(self.max_delay)
+    }
+}
+
+/// Per-enqueue overrides; unset fields fall back to the queue defaults
+#[derive(Debug, Clone, Default)]
+pub struct EnqueueOptions {
+    pub priority: JobPriority,
+    pub retry: Option<RetryPolicy>,
+}
+
+impl EnqueueOptions {
+    pub fn interactive() -> Self {
+        Self {
+            priority: JobPriority::Interactive,
+            retry: None,
+        }
+    }
+
+    pub fn batch() -> Self {
+        Self::default()
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+/// A row from the `jobs` table
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Job {
+    pub id: Uuid,
     pub job_type: String,
     pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: chrono::DateTime<chrono::Utc>,
+    pub locked_by: Option<String>,
+    pub locked_until: Option<chrono::DateTime<chrono::Utc>>,
+    pub result: Option<serde_json::Value>,
+    pub last_error: Option<String>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub priority: String,
+    pub backoff_base_secs: i32,
+    pub backoff_max_secs: i32,
+    /// Fraction complete, 0.0 - 1.0
+    pub progress: f32,
+    pub progress_message: Option<String>,
 }
 
-pub enum JobType {
-    Chat,
-    DeepResearch,
-    FileUpload,
-    LiteratureSearch,
-    DataAnalysis,
+impl Job {
+    pub fn kind(&self) -> Option<JobType> {
+        JobType::from_id(&self.job_type)
+    }
+
+    pub fn state(&self) -> Option<JobStatus> {
+        JobStatus::from_id(&self.status)
+    }
+
+    pub fn lane(&self) -> JobPriority {
+        JobPriority::from_id(&self.priority).unwrap_or_default()
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts,
+            base_delay: Duration::from_secs(self.backoff_base_secs.max(0) as u64),
+            max_delay: Duration::from_secs(self.backoff_max_secs.max(0) as u64),
+        }
+    }
+
+    /// Deserialize the payload into the struct matching this job's type
+    pub fn payload_as<T: serde::de::DeserializeOwned>(&self) -> anyhow::Result<T> {
+        serde_json::from_value(self.payload.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid {} payload: {}", self.job_type, e))
+    }
+
+    /// Whether another failure would exhaust the retry budget
+    pub fn is_last_attempt(&self) -> bool {
+        self.attempts >= self.max_attempts
+    }
+}
+
+/// Live status of a job, as shown in the TUI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobProgress {
+    pub job_id: Uuid,
+    pub job_type: String,
+    pub status: String,
+    pub priority: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub progress: f32,
+    pub message: Option<String>,
+    pub last_error: Option<String>,
+}
+
+impl From<&Job> for JobProgress {
+    fn from(job: &Job) -> Self {
+        Self {
+            job_id: job.id,
+            job_type: job.job_type.clone(),
+            status: job.status.clone(),
+            priority: job.priority.clone(),
+            attempts: job.attempts,
+            max_attempts: job.max_attempts,
+            progress: job.progress,
+            message: job.progress_message.clone(),
+            last_error: job.last_error.clone(),
+        }
+    }
+}
+
+impl JobProgress {
+    /// Whether the job has reached a terminal state
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            JobStatus::from_id(&self.status),
+            Some(JobStatus::Completed) | Some(JobStatus::Dead)
+        )
+    }
+}
+
+/// Payload for [`JobType::Analysis`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisJobPayload {
+    pub dataset_id: String,
+    pub filename: String,
+    pub local_path: String,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: u8,
+    /// Decimal separator, missing-value tokens and quote character
+    #[serde(default)]
+    pub parsing: ParseOptions,
+    #[serde(default)]
+    pub target_column: Option<String>,
+    #[serde(default)]
+    pub group_column: Option<String>,
+    #[serde(default)]
+    pub covariates: Vec<String>,
+    #[serde(default)]
+    pub boxplot_column: Option<String>,
+    #[serde(default)]
+    pub max_columns: Option<usize>,
+    #[serde(default)]
+    pub max_groups: Option<usize>,
+    /// Detected from the gene IDs when unset
+    #[serde(default)]
+    pub species: Option<Species>,
+    /// Top candidates cross-referenced against the literature; defaults to
+    /// `cross_reference.top_n`
+    #[serde(default)]
+    pub cross_reference: Option<usize>,
+    /// Options of the run, as in [`AnalysisConfig`]
+    #[serde(default)]
+    pub replicates: Replicates,
+    #[serde(default)]
+    pub imputation: Imputation,
+    #[serde(default)]
+    pub normalization: Vec<Normalization>,
+    #[serde(default)]
+    pub batch_column: Option<String>,
+    #[serde(default)]
+    pub batch_correction: BatchCorrection,
+    #[serde(default)]
+    pub correlation: CorrelationMethod,
+    #[serde(default)]
+    pub tables: TableFormat,
+    #[serde(default)]
+    pub numbers: NumberFormat,
+    #[serde(default)]
+    pub thresholds: Thresholds,
+    #[serde(default)]
+    pub kruskal_wallis: bool,
+    #[serde(default)]
+    pub memory_limit: MemoryLimit,
+    /// Clamped to the worker's cores
+    #[serde(default)]
+    pub threads: usize,
+    #[serde(default)]
+    pub regularization: Regularization,
+    #[serde(default)]
+    pub roc: RocSplit,
+    /// Key of the dataset stored in `job_datasets` (see [`super::datasets`]),
+    /// for workers on other machines that cannot read `local_path`
+    #[serde(default)]
+    pub dataset_sha256: Option<String>,
+}
+
+/// Payload for [`JobType::LiteratureBatch`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiteratureBatchPayload {
+    /// One literature task is run per objective
+    pub objectives: Vec<String>,
+}
+
+/// Payload for [`JobType::DocumentIngestion`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentIngestionPayload {
+    pub path: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Payload for [`JobType::LiteratureAlert`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiteratureAlertPayload {
+    /// Schedule that owns this alert; papers are de-duplicated per schedule
+    #[serde(default)]
+    pub schedule_id: Option<Uuid>,
+    /// Saved query, searched once per biomarker
+    pub query: String,
+    #[serde(default)]
+    pub biomarkers: Vec<String>,
+}
+
+impl AnalysisJobPayload {
+    /// Payload running `config` over `record` on a worker. Options that need
+    /// files on this machine or that the payload does not carry are refused
+    /// rather than silently dropped.
+    pub fn new(record: &DatasetRecord, config: &AnalysisConfig) -> Result<Self, String> {
+        let local_only: Vec<&str> = [
+            ("platform annotation", config.platform.is_some()),
+            ("model formula", config.formula.is_some()),
+            ("gene sets", !config.gene_sets.is_empty()),
+            ("survival columns", config.time_column.is_some() || config.event_column.is_some()),
+            ("subject column", config.subject_column.is_some()),
+            ("marker selection", !config.markers.is_empty()),
+        ]
+        .into_iter()
+        .filter_map(|(option, set)| set.then_some(option))
+        .collect();
+        if !local_only.is_empty() {
+            return Err(format!("Remote analysis does not support {}; run it locally.", local_only.join(", ")));
+        }
+        Ok(Self {
+            dataset_id: record.dataset.id.clone(),
+            filename: record.dataset.filename.clone(),
+            local_path: record.local_path.clone(),
+            delimiter: record.delimiter,
+            parsing: record.parsing.clone(),
+            target_column: config.target_column.clone(),
+            group_column: config.group_column.clone(),
+            covariates: config.covariates.clone(),
+            boxplot_column: config.boxplot_column.clone(),
+            max_columns: Some(config.max_columns),
+            max_groups: Some(config.max_groups),
+            species: Some(record.species),
+            cross_reference: None,
+            replicates: config.replicates,
+            imputation: config.imputation,
+            normalization: config.normalization.clone(),
+            batch_column: config.batch_column.clone(),
+            batch_correction: config.batch_correction,
+            correlation: config.correlation,
+            tables: config.tables,
+            numbers: config.numbers,
+            thresholds: config.thresholds,
+            kruskal_wallis: config.kruskal_wallis,
+            memory_limit: config.memory_limit,
+            threads: config.threads,
+            regularization: config.regularization,
+            roc: config.roc,
+            dataset_sha256: None,
+        })
+    }
+
+    /// The analysis the worker runs
+    pub fn config(&self) -> AnalysisConfig {
+        let defaults = AnalysisConfig::default();
+        AnalysisConfig {
+            target_column: self.target_column.clone(),
+            group_column: self.group_column.clone(),
+            covariates: self.covariates.clone(),
+            boxplot_column: self.boxplot_column.clone(),
+            max_columns: self.max_columns.unwrap_or(defaults.max_columns),
+            max_groups: self.max_groups.unwrap_or(defaults.max_groups),
+            replicates: self.replicates,
+            imputation: self.imputation,
+            normalization: self.normalization.clone(),
+            batch_column: self.batch_column.clone(),
+            batch_correction: self.batch_correction,
+            correlation: self.correlation,
+            tables: self.tables,
+            numbers: self.numbers,
+            thresholds: self.thresholds,
+            kruskal_wallis: self.kruskal_wallis,
+            memory_limit: self.memory_limit,
+            threads: self.threads,
+            regularization: self.regularization,
+            roc: self.roc,
+            ..defaults
+        }
+    }
+}
+
+fn default_delimiter() -> u8 {
+    b','
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_type_roundtrip() {
+        for kind in [
+            JobType::Analysis,
+            JobType::LiteratureBatch,
+            JobType::DocumentIngestion,
+            JobType::LiteratureAlert,
+        ] {
+            assert_eq!(JobType::from_id(kind.as_str()), Some(kind));
+            let json = serde_json::to_string(&kind).unwrap();
+            assert_eq!(json, format!("\"{}\"", kind.as_str()));
+        }
+        assert_eq!(JobType::from_id("chat"), None);
+    }
+
+    #[test]
+    fn test_retry_policy_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(60),
+        };
+        assert_eq!(policy.delay_for_attempt(1), Duration::from_secs(10));
+        assert_eq!(policy.delay_for_attempt(2), Duration::from_secs(20));
+        assert_eq!(policy.delay_for_attempt(3), Duration::from_secs(40));
+        assert_eq!(policy.delay_for_attempt(4), Duration::from_secs(60));
+        assert_eq!(policy.delay_for_attempt(100), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_priority_default_is_batch() {
+        assert_eq!(JobPriority::default(), JobPriority::Batch);
+        assert_eq!(EnqueueOptions::interactive().priority, JobPriority::Interactive);
+    }
+
+    #[test]
+    fn test_analysis_payload_defaults() {
+        let payload: AnalysisJobPayload = serde_json::from_value(serde_json::json!({
+            "dataset_id": "abc",
+            "filename": "data.csv",
+            "local_path": "uploads/abc-data.csv"
+        }))
+        .unwrap();
+        assert_eq!(payload.delimiter, b',');
+        assert!(payload.covariates.is_empty());
+        assert!(payload.target_column.is_none());
+        assert_eq!(payload.config().max_columns, AnalysisConfig::default().max_columns);
+    }
+
+    #[test]
+    fn test_analysis_payload_carries_options() {
+        use crate::analysis::{manifest, regularized::Penalty};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        std::fs::write(&path, "sample,age,batch,gene_a\ns1,30,a,1.5\ns2,60,b,2.5\n").unwrap();
+        let record = DatasetRecord::from_path(&path, None).unwrap();
+        let config = AnalysisConfig {
+            target_column: Some("age".to_string()),
+            group_column: Some("batch".to_string()),
+            replicates: Replicates::Median,
+            imputation: "knn:3".parse().unwrap(),
+            normalization: vec![Normalization::Log2, Normalization::Quantile],
+            batch_column: Some("batch".to_string()),
+            batch_correction: BatchCorrection::Center,
+            correlation: CorrelationMethod::Spearman,
+            tables: TableFormat::Tsv,
+            numbers: NumberFormat::De,
+            thresholds: Thresholds { alpha: 0.01, fdr: 0.1, min_effect: 0.2 },
+            kruskal_wallis: true,
+            memory_limit: "4G".parse().unwrap(),
+            threads: 1,
+            regularization: Regularization { penalty: Penalty::Lasso, lambda: Some(0.5), ..Default::default() },
+            roc: "median".parse().unwrap(),
+            ..Default::default()
+        };
+
+        // Through the queue's JSON and back, the worker runs the same analysis
+        let payload = AnalysisJobPayload::new(&record, &config).unwrap();
+        let queued: AnalysisJobPayload = serde_json::from_value(serde_json::to_value(&payload).unwrap()).unwrap();
+        assert_eq!(manifest::parameters(&queued.config()), manifest::parameters(&config));
+
+        let local_only = AnalysisConfig { gene_sets: vec!["sets.gmt".into()], subject_column: Some("s".into()), ..config };
+        let refused = AnalysisJobPayload::new(&record, &local_only).unwrap_err();
+        assert!(refused.contains("gene sets, subject column"), "{}", refused);
+    }
 }
@@ -1,8 +1,31 @@
-// Job queue with Sidekiq
-// TODO: Implement full job queue system
+//! Persistent Job Queue
+//!
+//! Background work (analysis runs, literature batches, document ingestion) is
+//! stored in the Postgres `jobs` table and executed by [`WorkerPool`] workers.
+//!
+//! ```text
+//! enqueue ──► jobs (queued) ──claim (SKIP LOCKED)──► running ──► completed
+//!                 ▲                                     │
+//!                 └──────── retry (attempts left) ◄─────┤
+//!                                                       └──► dead (dead letter)
+//! ```
+//!
+//! Workers in any number of processes or containers can share the table.
+//! Idle workers are woken through Postgres `LISTEN/NOTIFY` (see [`notify`])
+//! and fall back to polling.
+//!
+//! Recurring work (e.g. weekly literature alerts) is described by rows in
+//! `job_schedules`; the [`Scheduler`] turns due schedules into queued jobs.
 
 pub mod workers;
 pub mod jobs;
+pub mod store;
+pub mod scheduler;
+pub mod alerts;
+pub mod notify;
+pub mod datasets;
 
 pub use workers::*;
 pub use jobs::*;
+pub use store::*;
+pub use scheduler::*;
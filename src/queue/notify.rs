@@ -0,0 +1,64 @@
+//! Cross-process worker wake-ups
+//!
+//! A trigger on `jobs` sends `NOTIFY jobs_queued` whenever a row becomes
+//! queued. Every worker process holds one `LISTEN` connection and wakes its
+//! idle workers when a notification arrives, so jobs enqueued by a TUI on
+//! another machine start within milliseconds instead of on the next poll.
+//! Polling stays in place as the fallback for missed notifications.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Channel used by the `notify_jobs_queued` trigger
+pub const JOBS_QUEUED_CHANNEL: &str = "jobs_queued";
+
+/// Listen for queue notifications and wake every waiter on `wake`.
+///
+/// Reconnects with a short delay if the listener connection is lost.
+pub fn spawn_listener(pool: PgPool, wake: Arc<Notify>, mut shutdown: watch::Receiver<bool>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if *shutdown.borrow() {
+                break;
+            }
+
+            let mut listener = match PgListener::connect_with(&pool).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    warn!(error = %e, "Queue listener failed to connect, retrying");
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs(5)) => continue,
+                        _ = shutdown.changed() => break,
+                    }
+                }
+            };
+            if let Err(e) = listener.listen(JOBS_QUEUED_CHANNEL).await {
+                warn!(error = %e, "Queue listener failed to LISTEN, retrying");
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(5)) => continue,
+                    _ = shutdown.changed() => break,
+                }
+            }
+            info!(channel = JOBS_QUEUED_CHANNEL, "Queue listener connected");
+
+            loop {
+                tokio::select! {
+                    notification = listener.recv() => match notification {
+                        Ok(_) => wake.notify_waiters(),
+                        Err(e) => {
+                            warn!(error = %e, "Queue listener lost connection");
+                            break;
+                        }
+                    },
+                    _ = shutdown.changed() => return,
+                }
+            }
+        }
+    })
+}
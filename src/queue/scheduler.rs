@@ -0,0 +1,293 @@
+//! Recurring job scheduler
+//!
+//! Schedules live in the `job_schedules` table. Each tick the scheduler locks
+//! the schedules that are due (`FOR UPDATE SKIP LOCKED`, so several worker
+//! processes can run a scheduler side by side), enqueues one job per schedule
+//! and pushes `next_run_at` forward by the schedule's interval. A schedule
+//! that was missed while no worker was running fires once, not once per
+//! missed interval.
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::jobs::{Job, JobPriority, JobType};
+use super::store::JobQueue;
+
+/// Longest interval `every <n><unit>` accepts: a year
+pub const MAX_INTERVAL: Duration = Duration::from_secs(365 * 86_400);
+
+/// A parsed schedule expression.
+///
+/// Supported forms are the cron-style shorthands `@hourly`, `@daily` and
+/// `@weekly`, plus `every <n><unit>` with unit `m`, `h`, `d` or `w`
+/// (e.g. `every 3d`), up to [`MAX_INTERVAL`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleSpec {
+    pub expression: String,
+    pub interval: Duration,
+}
+
+impl ScheduleSpec {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expr = expression.trim().to_ascii_lowercase();
+        let interval = match expr.as_str() {
+            "@hourly" => Duration::from_secs(3600),
+            "@daily" => Duration::from_secs(86_400),
+            "@weekly" => Duration::from_secs(7 * 86_400),
+            other => {
+                let Some(rest) = other.strip_prefix("every") else {
+                    bail!("Unsupported schedule '{}': use @hourly, @daily, @weekly or 'every <n><m|h|d|w>'", expression);
+                };
+                let rest = rest.trim();
+                let (amount, unit) = match rest.char_indices().last() {
+                    Some((idx, _)) if idx > 0 => rest.split_at(idx),
+                    _ => bail!("Invalid schedule '{}'", expression),
+                };
+                let amount: u64 = amount
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid schedule amount in '{}'", expression))?;
+                let unit_secs = match unit {
+                    "m" => 60,
+                    "h" => 3600,
+                    "d" => 86_400,
+                    "w" => 7 * 86_400,
+                    _ => bail!("Invalid schedule unit in '{}'", expression),
+                };
+                if amount == 0 {
+                    bail!("Schedule interval must be positive");
+                }
+                match amount.checked_mul(unit_secs).map(Duration::from_secs) {
+                    Some(interval) if interval <= MAX_INTERVAL => interval,
+                    _ => bail!("Schedule interval in '{}' is longer than a year", expression),
+                }
+            }
+        };
+
+        Ok(Self { expression: expr, interval })
+    }
+}
+
+/// A row from the `job_schedules` table
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct JobSchedule {
+    pub id: Uuid,
+    pub name: String,
+    pub job_type: String,
+    pub payload: serde_json::Value,
+    pub schedule: String,
+    pub interval_secs: i64,
+    pub priority: String,
+    pub enabled: bool,
+    pub next_run_at: chrono::DateTime<chrono::Utc>,
+    pub last_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_job_id: Option<Uuid>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Enqueues jobs for due schedules
+#[derive(Clone)]
+pub struct Scheduler {
+    queue: JobQueue,
+    tick: Duration,
+}
+
+impl Scheduler {
+    pub fn new(queue: JobQueue) -> Self {
+        Self {
+            queue,
+            tick: Duration::from_secs(60),
+        }
+    }
+
+    pub fn with_tick(mut self, tick: Duration) -> Self {
+        self.tick = tick.max(Duration::from_secs(1));
+        self
+    }
+
+    /// Create or replace the schedule called `name`. The first run happens
+    /// immediately so the user gets a baseline without waiting an interval.
+    pub async fn upsert<P: Serialize>(
+        &self,
+        name: &str,
+        spec: &ScheduleSpec,
+        job_type: JobType,
+        payload: &P,
+        priority: JobPriority,
+    ) -> Result<JobSchedule> {
+        let payload = serde_json::to_value(payload)?;
+        let schedule = sqlx::query_as::<_, JobSchedule>(
+            r#"
+            INSERT INTO job_schedules (name, job_type, payload, schedule, interval_secs, priority)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (name) DO UPDATE
+            SET job_type = EXCLUDED.job_type,
+                payload = EXCLUDED.payload,
+                schedule = EXCLUDED.schedule,
+                interval_secs = EXCLUDED.interval_secs,
+                priority = EXCLUDED.priority,
+                enabled = TRUE,
+                next_run_at = NOW()
+            RETURNING *
+            "#
+        )
+        .bind(name)
+        .bind(job_type.as_str())
+        .bind(payload)
+        .bind(&spec.expression)
+        .bind(spec.interval.as_secs() as i64)
+        .bind(priority.as_str())
+        .fetch_one(self.queue.pool())
+        .await?;
+
+        info!(schedule = %name, every = %spec.expression, job_type = %job_type, "Schedule saved");
+        Ok(schedule)
+    }
+
+    pub async fn list(&self) -> Result<Vec<JobSchedule>> {
+        let schedules = sqlx::query_as::<_, JobSchedule>(
+            "SELECT * FROM job_schedules ORDER BY name"
+        )
+        .fetch_all(self.queue.pool())
+        .await?;
+        Ok(schedules)
+    }
+
+    pub async fn set_enabled(&self, name: &str, enabled: bool) -> Result<bool> {
+        let result = sqlx::query("UPDATE job_schedules SET enabled = $2 WHERE name = $1")
+            .bind(name)
+            .bind(enabled)
+            .execute(self.queue.pool())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM job_schedules WHERE name = $1")
+            .bind(name)
+            .execute(self.queue.pool())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Enqueue one job for every due schedule. Returns the jobs created.
+    pub async fn enqueue_due(&self) -> Result<Vec<Job>> {
+        let retry = self.queue.default_retry();
+        let mut tx = self.queue.pool().begin().await?;
+
+        let due = sqlx::query_as::<_, JobSchedule>(
+            r#"
+            SELECT * FROM job_schedules
+            WHERE enabled AND next_run_at <= NOW()
+            ORDER BY next_run_at ASC
+            FOR UPDATE SKIP LOCKED
+            LIMIT 50
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut jobs = Vec::with_capacity(due.len());
+        for schedule in due {
+            let mut payload = schedule.payload.clone();
+            if schedule.job_type == JobType::LiteratureAlert.as_str() {
+                if let Some(obj) = payload.as_object_mut() {
+                    obj.insert("schedule_id".to_string(), serde_json::json!(schedule.id));
+                }
+            }
+
+            let job = sqlx::query_as::<_, Job>(
+                r#"
+                INSERT INTO jobs (job_type, payload, max_attempts, priority, backoff_base_secs, backoff_max_secs)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING *
+                "#
+            )
+            .bind(&schedule.job_type)
+            .bind(payload)
+            .bind(retry.max_attempts)
+            .bind(&schedule.priority)
+            .bind(retry.base_delay.as_secs() as i32)
+            .bind(retry.max_delay.as_secs() as i32)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                r#"
+                UPDATE job_schedules
+                SET last_run_at = NOW(), last_job_id = $2,
+                    next_run_at = NOW() + make_interval(secs => $3)
+                WHERE id = $1
+                "#
+            )
+            .bind(schedule.id)
+            .bind(job.id)
+            .bind(schedule.interval_secs as f64)
+            .execute(&mut *tx)
+            .await?;
+
+            info!(schedule = %schedule.name, job_id = %job.id, "Scheduled job enqueued");
+            jobs.push(job);
+        }
+
+        tx.commit().await?;
+        Ok(jobs)
+    }
+
+    /// Tick until `shutdown` flips to true
+    pub async fn run(self, mut shutdown: watch::Receiver<bool>) {
+        info!("Scheduler started");
+        loop {
+            if *shutdown.borrow() {
+                break;
+            }
+            if let Err(e) = self.enqueue_due().await {
+                error!(error = %e, "Failed to enqueue scheduled jobs");
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(self.tick) => {}
+                _ = shutdown.changed() => {}
+            }
+        }
+        info!("Scheduler stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shorthands() {
+        assert_eq!(ScheduleSpec::parse("@weekly").unwrap().interval, Duration::from_secs(604_800));
+        assert_eq!(ScheduleSpec::parse(" @Daily ").unwrap().interval, Duration::from_secs(86_400));
+        assert_eq!(ScheduleSpec::parse("@hourly").unwrap().expression, "@hourly");
+    }
+
+    #[test]
+    fn test_parse_every() {
+        assert_eq!(ScheduleSpec::parse("every 3d").unwrap().interval, Duration::from_secs(3 * 86_400));
+        assert_eq!(ScheduleSpec::parse("every 15m").unwrap().interval, Duration::from_secs(900));
+        assert_eq!(ScheduleSpec::parse("every 2w").unwrap().interval, Duration::from_secs(14 * 86_400));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        assert!(ScheduleSpec::parse("0 9 * * 1").is_err());
+        assert!(ScheduleSpec::parse("every 0d").is_err());
+        assert!(ScheduleSpec::parse("every d").is_err());
+        assert!(ScheduleSpec::parse("every 5y").is_err());
+        // Multi-byte units and overflowing amounts are errors, not panics
+        assert!(ScheduleSpec::parse("every 5é").is_err());
+        assert!(ScheduleSpec::parse("every é").is_err());
+        assert!(ScheduleSpec::parse("every 99999999999999999w").is_err());
+        assert!(ScheduleSpec::parse("every 53w").is_err());
+        assert_eq!(ScheduleSpec::parse("every 52w").unwrap().interval, Duration::from_secs(364 * 86_400));
+    }
+}
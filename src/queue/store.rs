@@ -0,0 +1,268 @@
+//! Postgres-backed job store
+//!
+//! Jobs are claimed with `FOR UPDATE SKIP LOCKED`, which lets any number of
+//! workers poll the same table concurrently. A claimed job is leased to one
+//! worker until `locked_until`; an expired lease makes the job claimable again
+//! (visibility timeout). Failed jobs are retried with exponential backoff
+//! according to their own [`RetryPolicy`]; jobs that fail on their final
+//! attempt are moved to the `dead` status instead of being retried.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::jobs::{EnqueueOptions, Job, JobPriority, JobStatus, JobType, RetryPolicy};
+
+/// Handle to the persistent job queue
+#[derive(Clone)]
+pub struct JobQueue {
+    pool: PgPool,
+    default_retry: RetryPolicy,
+}
+
+impl JobQueue {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            default_retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: i32) -> Self {
+        self.default_retry.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Retry policy applied to jobs enqueued without an explicit one
+    pub fn with_default_retry(mut self, retry: RetryPolicy) -> Self {
+        self.default_retry = RetryPolicy {
+            max_attempts: retry.max_attempts.max(1),
+            ..retry
+        };
+        self
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    pub fn default_retry(&self) -> RetryPolicy {
+        self.default_retry
+    }
+
+    /// Enqueue a batch-lane job with the queue's default retry policy
+    pub async fn enqueue<P: Serialize>(&self, job_type: JobType, payload: &P) -> Result<Job> {
+        self.enqueue_with(job_type, payload, EnqueueOptions::default()).await
+    }
+
+    /// Enqueue a job with an explicit priority lane and/or retry policy
+    pub async fn enqueue_with<P: Serialize>(
+        &self,
+        job_type: JobType,
+        payload: &P,
+        options: EnqueueOptions,
+    ) -> Result<Job> {
+        let payload = serde_json::to_value(payload)?;
+        let retry = options.retry.unwrap_or(self.default_retry);
+        let job = sqlx::query_as::<_, Job>(
+            r#"
+            INSERT INTO jobs (job_type, payload, max_attempts, priority, backoff_base_secs, backoff_max_secs)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#
+        )
+        .bind(job_type.as_str())
+        .bind(payload)
+        .bind(retry.max_attempts.max(1))
+        .bind(options.priority.as_str())
+        .bind(retry.base_delay.as_secs() as i32)
+        .bind(retry.max_delay.as_secs() as i32)
+        .fetch_one(&self.pool)
+        .await?;
+
+        info!(job_id = %job.id, job_type = %job_type, priority = options.priority.as_str(), "Job enqueued");
+        Ok(job)
+    }
+
+    /// Claim the next runnable job from the given lanes, leasing it to
+    /// `worker_id` for `visibility_timeout`. Interactive jobs win over batch.
+    ///
+    /// Running jobs whose lease has expired are treated as runnable, so work
+    /// abandoned by a crashed worker is picked up again.
+    pub async fn claim(
+        &self,
+        worker_id: &str,
+        lanes: &[JobPriority],
+        visibility_timeout: Duration,
+    ) -> Result<Option<Job>> {
+        let lanes: Vec<&str> = lanes.iter().map(|l| l.as_str()).collect();
+        let job = sqlx::query_as::<_, Job>(
+            r#"
+            UPDATE jobs
+            SET status = 'running',
+                attempts = attempts + 1,
+                locked_by = $1,
+                locked_until = NOW() + make_interval(secs => $2)
+            WHERE id = (
+                SELECT id FROM jobs
+                WHERE ((status = 'queued' AND run_at <= NOW())
+                    OR (status = 'running' AND locked_until < NOW()))
+                  AND priority = ANY($3)
+                ORDER BY (priority = 'interactive') DESC, run_at ASC
+                FOR UPDATE SKIP LOCKED
+                LIMIT 1
+            )
+            RETURNING *
+            "#
+        )
+        .bind(worker_id)
+        .bind(visibility_timeout.as_secs_f64())
+        .bind(&lanes)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(job)
+    }
+
+    /// Record progress (0.0 - 1.0) and a short status message for a running job
+    pub async fn report_progress(&self, job_id: Uuid, progress: f32, message: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE jobs SET progress = $2, progress_message = $3 WHERE id = $1"
+        )
+        .bind(job_id)
+        .bind(progress.clamp(0.0, 1.0))
+        .bind(message)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Jobs whose row changed after `since`, oldest change first
+    pub async fn updated_since(&self, since: chrono::DateTime<chrono::Utc>) -> Result<Vec<Job>> {
+        let jobs = sqlx::query_as::<_, Job>(
+            "SELECT * FROM jobs WHERE updated_at > $1 ORDER BY updated_at ASC LIMIT 200"
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(jobs)
+    }
+
+    /// Extend the lease on a job that is still being worked on
+    pub async fn heartbeat(&self, job_id: Uuid, worker_id: &str, visibility_timeout: Duration) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET locked_until = NOW() + make_interval(secs => $3)
+            WHERE id = $1 AND locked_by = $2 AND status = 'running'
+            "#
+        )
+        .bind(job_id)
+        .bind(worker_id)
+        .bind(visibility_timeout.as_secs_f64())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mark a job as completed and store its result
+    pub async fn complete(&self, job_id: Uuid, result: serde_json::Value) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = 'completed', result = $2, progress = 1, locked_by = NULL,
+                locked_until = NULL, last_error = NULL, completed_at = NOW()
+            WHERE id = $1
+            "#
+        )
+        .bind(job_id)
+        .bind(result)
+        .execute(&self.pool)
+        .await?;
+
+        info!(job_id = %job_id, "Job completed");
+        Ok(())
+    }
+
+    /// Record a failure. The job is re-queued after its backoff delay unless it
+    /// has used up its attempts, in which case it is dead-lettered.
+    pub async fn fail(&self, job: &Job, error: &str) -> Result<JobStatus> {
+        let status = if job.is_last_attempt() {
+            JobStatus::Dead
+        } else {
+            JobStatus::Queued
+        };
+        let retry_delay = job.retry_policy().delay_for_attempt(job.attempts);
+
+        sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = $2, last_error = $3, locked_by = NULL, locked_until = NULL,
+                run_at = NOW() + make_interval(secs => $4)
+            WHERE id = $1
+            "#
+        )
+        .bind(job.id)
+        .bind(status.as_str())
+        .bind(error)
+        .bind(retry_delay.as_secs_f64())
+        .execute(&self.pool)
+        .await?;
+
+        if status == JobStatus::Dead {
+            warn!(job_id = %job.id, attempts = job.attempts, error, "Job moved to dead letter");
+        } else {
+            warn!(job_id = %job.id, attempts = job.attempts, retry_in_secs = retry_delay.as_secs(), error, "Job failed, will retry");
+        }
+        Ok(status)
+    }
+
+    pub async fn get(&self, job_id: Uuid) -> Result<Option<Job>> {
+        let job = sqlx::query_as::<_, Job>("SELECT * FROM jobs WHERE id = $1")
+            .bind(job_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(job)
+    }
+
+    /// List jobs in the dead letter state, newest first
+    pub async fn dead_letters(&self, limit: i64) -> Result<Vec<Job>> {
+        let jobs = sqlx::query_as::<_, Job>(
+            "SELECT * FROM jobs WHERE status = 'dead' ORDER BY updated_at DESC LIMIT $1"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(jobs)
+    }
+
+    /// Move a dead-lettered job back onto the queue with a fresh attempt budget
+    pub async fn requeue_dead(&self, job_id: Uuid) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = 'queued', attempts = 0, run_at = NOW(), last_error = NULL
+            WHERE id = $1 AND status = 'dead'
+            "#
+        )
+        .bind(job_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Count jobs per status, for health and status reporting
+    pub async fn counts(&self) -> Result<Vec<(String, i64)>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT status, COUNT(*) FROM jobs GROUP BY status ORDER BY status"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+}
@@ -1,56 +1,516 @@
-// Worker implementations stub
-// TODO: Implement full workers for all job types
+//! Queue workers
+//!
+//! Each worker polls the [`JobQueue`], runs whatever it claims, and reports
+//! the outcome back. While a job runs, a heartbeat task keeps extending its
+//! lease so long analyses are not handed to another worker mid-flight, and
+//! handlers publish progress that the TUI picks up through [`JobQueue::updated_since`].
 
-use crate::queue::jobs::Job;
-use tracing::info;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
-pub struct Worker;
+use anyhow::{Context, Result};
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::agents::LiteratureAgent;
+use crate::analysis::{literature, run_analysis};
+use crate::annotation::Species;
+use crate::config::{Config, QueueConfig};
+use crate::data_registry::{ColumnSchema, DatasetRecord, ParseOptions, SchemaBuilder};
+use crate::models::{PlanTask, UploadedDataset};
+use crate::queue::alerts;
+use crate::queue::datasets;
+use crate::queue::jobs::{
+    AnalysisJobPayload, DocumentIngestionPayload, Job, JobPriority, JobType, LiteratureAlertPayload,
+    LiteratureBatchPayload,
+};
+use crate::queue::notify;
+use crate::queue::scheduler::Scheduler;
+use crate::queue::store::JobQueue;
+use crate::utils::cancel::CancellationToken;
+
+pub struct Worker {
+    id: String,
+    queue: JobQueue,
+    config: Config,
+    lanes: Vec<JobPriority>,
+    poll_interval: Duration,
+    visibility_timeout: Duration,
+    wake: Option<Arc<Notify>>,
+}
 
 impl Worker {
-    pub async fn process_job(&self, job: Job) -> anyhow::Result<()> {
-        info!("Processing job: {:?}", job.job_type);
-
-        match job.job_type.as_str() {
-            "chat" => self.process_chat_job(job).await?,
-            "deep_research" => self.process_deep_research_job(job).await?,
-            "file_upload" => self.process_file_upload_job(job).await?,
-            "literature_search" => self.process_literature_search_job(job).await?,
-            "data_analysis" => self.process_data_analysis_job(job).await?,
-            _ => {
-                tracing::warn!("Unknown job type: {}", job.job_type);
+    pub fn new(id: impl Into<String>, queue: JobQueue, config: Config) -> Self {
+        let queue_config = config.queue.clone();
+        Self {
+            id: id.into(),
+            queue,
+            config,
+            lanes: vec![JobPriority::Interactive, JobPriority::Batch],
+            poll_interval: Duration::from_millis(queue_config.poll_interval_ms),
+            visibility_timeout: Duration::from_secs(queue_config.visibility_timeout_secs),
+            wake: None,
+        }
+    }
+
+    /// Wake this worker early when `wake` is notified (new job queued)
+    pub fn with_wake(mut self, wake: Arc<Notify>) -> Self {
+        self.wake = Some(wake);
+        self
+    }
+
+    /// Restrict this worker to the given priority lanes
+    pub fn with_lanes(mut self, lanes: Vec<JobPriority>) -> Self {
+        self.lanes = lanes;
+        self
+    }
+
+    /// Poll the queue until `shutdown` flips to true
+    pub async fn run(self, mut shutdown: watch::Receiver<bool>) {
+        info!(worker_id = %self.id, "Worker started");
+
+        loop {
+            if *shutdown.borrow() {
+                break;
+            }
+
+            match self.queue.claim(&self.id, &self.lanes, self.visibility_timeout).await {
+                Ok(Some(job)) => {
+                    self.handle_job(job).await;
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    error!(worker_id = %self.id, error = %e, "Failed to claim job");
+                }
+            }
+
+            let woken = async {
+                match &self.wake {
+                    Some(wake) => wake.notified().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(self.poll_interval) => {}
+                _ = woken => {}
+                _ = shutdown.changed() => {}
+            }
+        }
+
+        info!(worker_id = %self.id, "Worker stopped");
+    }
+
+    async fn handle_job(&self, job: Job) {
+        info!(worker_id = %self.id, job_id = %job.id, job_type = %job.job_type, attempt = job.attempts, "Processing job");
+
+        let heartbeat = self.spawn_heartbeat(&job);
+        let outcome = self.process_job(&job).await;
+        heartbeat.abort();
+
+        let report = match outcome {
+            Ok(result) => self.queue.complete(job.id, result).await,
+            Err(e) => self
+                .queue
+                .fail(&job, &format!("{:#}", e))
+                .await
+                .map(|_| ()),
+        };
+
+        if let Err(e) = report {
+            error!(job_id = %job.id, error = %e, "Failed to record job outcome");
+        }
+    }
+
+    fn spawn_heartbeat(&self, job: &Job) -> JoinHandle<()> {
+        let queue = self.queue.clone();
+        let worker_id = self.id.clone();
+        let job_id = job.id;
+        let timeout = self.visibility_timeout;
+        let interval = (timeout / 3).max(Duration::from_secs(1));
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match queue.heartbeat(job_id, &worker_id, timeout).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        warn!(job_id = %job_id, "Lost lease on job");
+                        break;
+                    }
+                    Err(e) => warn!(job_id = %job_id, error = %e, "Heartbeat failed"),
+                }
+            }
+        })
+    }
+
+    pub async fn process_job(&self, job: &Job) -> Result<serde_json::Value> {
+        match job.kind() {
+            Some(JobType::Analysis) => self.process_analysis_job(job, job.payload_as()?).await,
+            Some(JobType::LiteratureBatch) => self.process_literature_batch_job(job, job.payload_as()?).await,
+            Some(JobType::DocumentIngestion) => self.process_document_ingestion_job(job, job.payload_as()?).await,
+            Some(JobType::LiteratureAlert) => self.process_literature_alert_job(job, job.payload_as()?).await,
+            None => Err(anyhow::anyhow!("Unknown job type: {}", job.job_type)),
+        }
+    }
+
+    /// Progress reporting is best-effort; a failed update never fails the job
+    async fn progress(&self, job: &Job, progress: f32, message: &str) {
+        if let Err(e) = self.queue.report_progress(job.id, progress, message).await {
+            warn!(job_id = %job.id, error = %e, "Failed to report job progress");
+        }
+    }
+
+    async fn process_analysis_job(&self, job: &Job, mut payload: AnalysisJobPayload) -> Result<serde_json::Value> {
+        self.progress(job, 0.05, "Loading dataset").await;
+        let output_dir = Path::new("artifacts")
+            .join("analysis")
+            .join(&payload.dataset_id);
+        tokio::fs::create_dir_all(&output_dir).await?;
+
+        let bytes = match &payload.dataset_sha256 {
+            Some(key) if !Path::new(&payload.local_path).exists() => {
+                // Remote worker: fetch the stored copy
+                let content = datasets::load(self.queue.pool(), key).await?;
+                let local = materialize(&output_dir, &payload.filename, &content).await?;
+                payload.local_path = local.to_string_lossy().to_string();
+                content
+            }
+            _ => tokio::fs::read(&payload.local_path)
+                .await
+                .with_context(|| format!("Failed to read dataset {}", payload.local_path))?,
+        };
+        let (columns, row_count, schema) = read_csv_metadata(&bytes, payload.delimiter, &payload.parsing)?;
+
+        let record = DatasetRecord {
+            dataset: UploadedDataset {
+                filename: payload.filename.clone(),
+                id: payload.dataset_id.clone(),
+                description: format!("Queued analysis of {}", payload.filename),
+                path: Some(payload.local_path.clone()),
+                content: None,
+                size: Some(bytes.len() as i64),
+            },
+            local_path: payload.local_path.clone(),
+            content_type: "text/plain".to_string(),
+            delimiter: payload.delimiter,
+            has_headers: true,
+            columns,
+            row_count,
+            species: Species::default(),
+            schema,
+            parsing: payload.parsing.clone(),
+            sequence: None,
+        };
+        let record = match payload.species {
+            Some(species) => {
+                let mut record = record;
+                record.set_species(species)?;
+                record
             }
+            None => record.with_detected_species(),
+        };
+        let config = payload.config();
+        config
+            .thresholds
+            .validate()
+            .and_then(|_| config.regularization.validate())
+            .map_err(anyhow::Error::msg)?;
+
+        self.progress(job, 0.2, "Running statistics").await;
+        let started = std::time::Instant::now();
+        let mut analysis = tokio::task::spawn_blocking(move || run_analysis(&record, &config, &output_dir, &CancellationToken::new()))
+            .await??;
+        crate::metering::record_analysis(started, &payload.dataset_id);
+        let top_n = payload.cross_reference.unwrap_or(self.config.cross_reference.top_n);
+        if top_n > 0 {
+            self.progress(job, 0.8, "Cross-referencing the literature").await;
+            literature::cross_reference(&self.config, &mut analysis, top_n).await;
+        }
+        self.progress(job, 0.95, "Writing results").await;
+        if payload.dataset_sha256.is_some() {
+            if let Err(e) = datasets::prune(self.queue.pool()).await {
+                warn!(job_id = %job.id, error = %e, "Failed to prune stored datasets");
+            }
+        }
+
+        Ok(serde_json::json!({
+            "dataset_id": payload.dataset_id,
+            "summary": analysis.summary,
+            "findings": analysis.findings(),
+            "descriptive_stats": analysis.descriptive_stats,
+            "regressions": analysis.regressions,
+            "novelty_scores": analysis.novelty_scores,
+            "biomarker_candidates": analysis.biomarker_candidates,
+            "heatmap_path": analysis.heatmap_path,
+            "expression_heatmap_path": analysis.expression_heatmap_path,
+            "boxplot_path": analysis.boxplot_path,
+            "volcano_path": analysis.volcano_path,
+            "roc": analysis.roc,
+            "roc_path": analysis.roc_path,
+            "go_enrichment": analysis.enrichment,
+            "go_enrichment_plot_path": analysis.enrichment_plot_path,
+            "pathway_enrichment": analysis.pathways,
+            "pca": analysis.pca,
+            "pca_scree_path": analysis.pca_scree_path,
+            "pca_scores_path": analysis.pca_scores_path,
+            "kmeans": analysis.kmeans,
+            "regularized": analysis.regularized,
+            "cross_reference": analysis.cross_reference,
+        }))
+    }
+
+    async fn process_literature_batch_job(&self, job: &Job, payload: LiteratureBatchPayload) -> Result<serde_json::Value> {
+        let total = payload.objectives.len().max(1);
+        let mut results = Vec::with_capacity(payload.objectives.len());
+        for (i, objective) in payload.objectives.into_iter().enumerate() {
+            self.progress(job, i as f32 / total as f32, &format!("Search {}/{}: {}", i + 1, total, objective))
+                .await;
+            let task = PlanTask {
+                id: Some(uuid::Uuid::new_v4().to_string()),
+                job_id: None,
+                objective,
+                datasets: vec![],
+                task_type: "LITERATURE".to_string(),
+                level: Some(1),
+                start: None,
+                end: None,
+                output: None,
+                artifacts: None,
+            };
+            let result = LiteratureAgent::execute_task(&task, &self.config, &CancellationToken::new())
+                .await
+                .map_err(|e| anyhow::anyhow!("Literature task failed: {}", e))?;
+            results.push(result);
+        }
+        Ok(serde_json::to_value(results)?)
+    }
+
+    /// Search the saved query once per biomarker and record unseen papers
+    async fn process_literature_alert_job(&self, job: &Job, payload: LiteratureAlertPayload) -> Result<serde_json::Value> {
+        let objectives: Vec<(String, String)> = if payload.biomarkers.is_empty() {
+            vec![(payload.query.clone(), payload.query.clone())]
+        } else {
+            payload
+                .biomarkers
+                .iter()
+                .map(|b| (b.clone(), format!("{} {}", payload.query, b)))
+                .collect()
+        };
+
+        let total = objectives.len();
+        let mut new_papers = 0usize;
+        for (i, (biomarker, objective)) in objectives.into_iter().enumerate() {
+            self.progress(job, i as f32 / total as f32, &format!("Searching {}", biomarker))
+                .await;
+            let task = PlanTask {
+                id: Some(uuid::Uuid::new_v4().to_string()),
+                job_id: None,
+                objective,
+                datasets: vec![],
+                task_type: "LITERATURE".to_string(),
+                level: Some(1),
+                start: None,
+                end: None,
+                output: None,
+                artifacts: None,
+            };
+            let result = LiteratureAgent::execute_task(&task, &self.config, &CancellationToken::new())
+                .await
+                .map_err(|e| anyhow::anyhow!("Literature alert search failed: {}", e))?;
+            new_papers +=
+                alerts::record_papers(self.queue.pool(), payload.schedule_id, &biomarker, &result.sources).await?;
+        }
+
+        info!(job_id = %job.id, new_papers, "Literature alert finished");
+        Ok(serde_json::json!({
+            "query": payload.query,
+            "biomarkers": payload.biomarkers,
+            "new_papers": new_papers,
+        }))
+    }
+
+    async fn process_document_ingestion_job(&self, job: &Job, payload: DocumentIngestionPayload) -> Result<serde_json::Value> {
+        self.progress(job, 0.1, "Reading document").await;
+        let path = Path::new(&payload.path);
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let delimiter = match extension.as_str() {
+            "csv" => b',',
+            "tsv" => b'\t',
+            other => return Err(anyhow::anyhow!("Unsupported document type: .{}", other)),
+        };
+
+        let bytes = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", payload.path))?;
+        let (columns, row_count, schema) = read_csv_metadata(&bytes, delimiter, &ParseOptions::default())?;
+
+        Ok(serde_json::json!({
+            "path": payload.path,
+            "description": payload.description,
+            "columns": columns,
+            "schema": schema,
+            "row_count": row_count,
+            "size": bytes.len(),
+        }))
+    }
+}
+
+/// Owns a set of running workers and the signal used to stop them
+pub struct WorkerPool {
+    shutdown: watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    /// Spawn `config.queue.workers` workers sharing one queue, plus a
+    /// [`Scheduler`] for recurring jobs and (unless disabled) a `LISTEN`
+    /// connection that wakes idle workers. Workers are given their lanes by
+    /// [`worker_lanes`].
+    pub fn spawn(queue: JobQueue, config: Config) -> Self {
+        let (shutdown, rx) = watch::channel(false);
+        let QueueConfig { workers, listen_notify, .. } = config.queue.clone();
+        let wake = listen_notify.then(|| Arc::new(Notify::new()));
+        let host = hostname::get()
+            .map(|h| h.to_string_lossy().to_string())
+            .unwrap_or_else(|_| "worker".to_string());
+
+        let mut handles: Vec<JoinHandle<()>> = (0..workers.max(1))
+            .map(|i| {
+                let worker_id = format!("{}-{}-{}", host, std::process::id(), i);
+                let mut worker = Worker::new(worker_id, queue.clone(), config.clone())
+                    .with_lanes(worker_lanes(i, &config.queue));
+                if let Some(wake) = &wake {
+                    worker = worker.with_wake(wake.clone());
+                }
+                tokio::spawn(worker.run(rx.clone()))
+            })
+            .collect();
+        if let Some(wake) = wake {
+            handles.push(notify::spawn_listener(queue.pool().clone(), wake, rx.clone()));
         }
+        handles.push(tokio::spawn(Scheduler::new(queue).run(rx)));
 
-        Ok(())
+        Self { shutdown, handles }
     }
 
-    async fn process_chat_job(&self, job: Job) -> anyhow::Result<()> {
-        info!("Processing chat job");
-        // Placeholder implementation
-        Ok(())
+    /// Ask all workers to stop after their current job and wait for them
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(true);
+        for handle in self.handles {
+            let _ = handle.await;
+        }
     }
+}
 
-    async fn process_deep_research_job(&self, job: Job) -> anyhow::Result<()> {
-        info!("Processing deep research job");
-        // Placeholder implementation
-        Ok(())
+/// Lanes of worker `index`: the first `interactive_workers` only serve the
+/// interactive lane, so a backlog of batch jobs cannot starve work a user is
+/// waiting on, but at least one worker always serves the batch lane too so
+/// batch and scheduled jobs still run
+fn worker_lanes(index: usize, queue: &QueueConfig) -> Vec<JobPriority> {
+    let reserved = queue.interactive_workers.min(queue.workers.max(1) - 1);
+    if index < reserved {
+        vec![JobPriority::Interactive]
+    } else {
+        vec![JobPriority::Interactive, JobPriority::Batch]
     }
+}
+
+/// Write a fetched dataset to `output_dir` under the last component of
+/// `filename`, so that a name such as `../../x` cannot leave it
+async fn materialize(output_dir: &Path, filename: &str, content: &[u8]) -> Result<PathBuf> {
+    let name = Path::new(filename)
+        .file_name()
+        .with_context(|| format!("Invalid dataset filename '{}'", filename))?;
+    let path = output_dir.join(name);
+    tokio::fs::write(&path, content)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
 
-    async fn process_file_upload_job(&self, job: Job) -> anyhow::Result<()> {
-        info!("Processing file upload job");
-        // Placeholder implementation
-        Ok(())
+fn read_csv_metadata(bytes: &[u8], delimiter: u8, parsing: &ParseOptions) -> Result<(Vec<String>, usize, Vec<ColumnSchema>)> {
+    parsing.validate().map_err(anyhow::Error::msg)?;
+    let mut rdr = parsing.reader_builder(delimiter).has_headers(true).from_reader(bytes);
+
+    let headers = rdr
+        .headers()?
+        .iter()
+        .map(|h| h.to_string())
+        .collect::<Vec<_>>();
+
+    let mut schema = SchemaBuilder::new(&headers, parsing);
+    let mut row_count = 0usize;
+    for record in rdr.records() {
+        schema.push(&record?);
+        row_count += 1;
     }
+    Ok((headers, row_count, schema.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_worker_serves_batch_lane() {
+        let mut queue = QueueConfig {
+            workers: 1,
+            interactive_workers: 1,
+            poll_interval_ms: 1000,
+            visibility_timeout_secs: 300,
+            max_attempts: 3,
+            retry_delay_secs: 30,
+            retry_max_delay_secs: 3600,
+            listen_notify: true,
+        };
+        // The only worker claims batch jobs (alerts, literature batches) too
+        assert_eq!(worker_lanes(0, &queue), [JobPriority::Interactive, JobPriority::Batch]);
+        assert!(queue.validate().is_err());
+
+        (queue.workers, queue.interactive_workers) = (3, 1);
+        assert_eq!(worker_lanes(0, &queue), [JobPriority::Interactive]);
+        assert_eq!(worker_lanes(1, &queue), [JobPriority::Interactive, JobPriority::Batch]);
+        assert!(queue.validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_remote_dataset_stays_in_output_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("artifacts").join("analysis").join("abc");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let content = b"sample,age,gene_a\ns1,30,1.5\ns2,60,2.5\n";
+
+        let path = materialize(&output_dir, "../../../escape.csv", content).await.unwrap();
+        assert_eq!(path, output_dir.join("escape.csv"));
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+        assert!(!dir.path().join("escape.csv").exists());
+        assert!(materialize(&output_dir, "..", content).await.is_err());
+        assert!(materialize(&output_dir, "/", content).await.is_err());
 
-    async fn process_literature_search_job(&self, job: Job) -> anyhow::Result<()> {
-        info!("Processing literature search job");
-        // Placeholder implementation
-        Ok(())
+        let (columns, rows, _) = read_csv_metadata(content, b',', &ParseOptions::default()).unwrap();
+        assert_eq!(columns, ["sample", "age", "gene_a"]);
+        assert_eq!(rows, 2);
     }
 
-    async fn process_data_analysis_job(&self, job: Job) -> anyhow::Result<()> {
-        info!("Processing data analysis job");
-        // Placeholder implementation
-        Ok(())
+    #[test]
+    fn test_remote_payload_names_the_stored_dataset() {
+        let content = "sample,age\n".repeat(1000);
+        let payload: AnalysisJobPayload = serde_json::from_value(serde_json::json!({
+            "dataset_id": "abc",
+            "filename": "data.csv",
+            "local_path": "/elsewhere/uploads/abc-data.csv",
+            "dataset_sha256": datasets::key(content.as_bytes()),
+        }))
+        .unwrap();
+        let stored = serde_json::to_string(&payload).unwrap();
+        assert!(stored.contains(&datasets::key(content.as_bytes())));
+        assert!(stored.len() < 1000);
     }
 }
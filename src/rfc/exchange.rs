@@ -283,7 +283,7 @@ pub mod client {
         };
 
         // Make HTTP request
-        let client = reqwest::Client::new();
+        let client = crate::utils::http::env_client();
         let response = client
             .post(format!("{}/api/rfc/exchange", base_url))
             .json(&request)
@@ -41,6 +41,13 @@
 //! - `POST /api/rfc/exchange` - Password exchange (no auth, uses RSA)
 //! - `GET /api/rfc/health` - Health check (no auth)
 //! - `POST /api/rfc/shell` - Execute shell command (requires HMAC auth)
+//!
+//! # Datasets
+//!
+//! With `oxidized-bio --serve`, `dataset.push` adds a CSV/TSV to the running
+//! TUI session (validated like `/upload`), either from a `path` on this
+//! machine or from `content` plus `filename`; `dataset.list` lists the
+//! session's datasets.
 
 pub mod crypto;
 pub mod exchange;
@@ -57,7 +64,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tracing::{error, info, warn};
 
+use crate::annotation::Species;
+use crate::data_registry::{DatasetRecord, ParseOptions, UPLOAD_DIR};
 use crate::models::AppState;
+use crate::session::SessionEvent;
 use shell::{CommandRequest, CommandResult};
 
 // =============================================================================
@@ -361,6 +371,78 @@ async fn execute_rfc_function(
             Ok(serde_json::json!({ "entries": entries }))
         }
 
+        // ======================
+        // Dataset Functions
+        // ======================
+        ("dataset", "push") => {
+            let kwarg = |name: &str| input.kwargs.get(name).and_then(|v| v.as_str());
+            let (filename, bytes) = match kwarg("content") {
+                Some(content) => {
+                    let filename = kwarg("filename").ok_or("Missing filename for content")?;
+                    (filename.to_string(), content.as_bytes().to_vec())
+                }
+                None => {
+                    let path = input.args.first()
+                        .and_then(|v| v.as_str())
+                        .or_else(|| kwarg("path"))
+                        .ok_or("Missing path argument (or content and filename)")?;
+                    (path.to_string(), tokio::fs::read(path).await?)
+                }
+            };
+            // Keep only the file name of paths and client-supplied names
+            let filename = std::path::Path::new(&filename)
+                .file_name()
+                .and_then(|s| s.to_str())
+                .ok_or("Invalid filename")?
+                .to_string();
+            let species = kwarg("species").map(str::parse::<Species>).transpose()?;
+            let description = kwarg("description").map(str::to_string);
+            let mut parsing = ParseOptions::default();
+            for name in ["decimal", "na", "quote"] {
+                if let Some(value) = kwarg(name) {
+                    parsing.set(name, value)?;
+                }
+            }
+
+            let record = DatasetRecord::from_upload(
+                std::path::Path::new(UPLOAD_DIR),
+                &filename,
+                &bytes,
+                description,
+                species,
+                parsing,
+            )
+            .await?;
+            state.dataset_registry.insert(record.clone()).await;
+            state.events.publish(SessionEvent::dataset_added(&record, "rfc"));
+            info!(dataset_id = %record.dataset.id, filename = %filename, "Dataset pushed over RFC");
+            Ok(serde_json::json!({
+                "id": record.dataset.id,
+                "filename": record.dataset.filename,
+                "columns": record.columns,
+                "row_count": record.row_count,
+                "species": record.species,
+            }))
+        }
+
+        ("dataset", "list") => {
+            let datasets: Vec<serde_json::Value> = state
+                .dataset_registry
+                .snapshot()
+                .await
+                .into_iter()
+                .map(|record| serde_json::json!({
+                    "id": record.dataset.id,
+                    "filename": record.dataset.filename,
+                    "description": record.dataset.description,
+                    "row_count": record.row_count,
+                    "species": record.species,
+                    "schema": record.schema,
+                }))
+                .collect();
+            Ok(serde_json::json!({ "datasets": datasets }))
+        }
+
         // ======================
         // Unknown Function
         // ======================
@@ -536,7 +618,7 @@ pub mod client {
 
         let call = RFCCall { rfc_input, hash };
 
-        let client = reqwest::Client::new();
+        let client = crate::utils::http::env_client();
         let response = client
             .post(format!("{}/api/rfc", base_url))
             .json(&call)
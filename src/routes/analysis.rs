@@ -1,74 +1,152 @@
 use std::path::Path;
 
-use axum::{extract::State, routing::post, Json, Router};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
 use tokio::fs;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::analysis::{AnalysisConfig, run_analysis, build_manuscript};
-use crate::models::{AnalysisRequest, AnalysisResponse, AppState, AnalysisArtifact, BiomarkerCandidate};
+use crate::analysis::{legends, literature, report, supplement, threads, AnalysisConfig, run_analysis, build_manuscript};
+use crate::metering;
+use crate::utils::cancel::CancellationToken;
+use crate::data_registry::DatasetRecord;
+use crate::payment::x402::{self, require_payment, X402Gate};
+use crate::models::{AnalysisRequest, AnalysisResponse, AppState, AnalysisArtifact};
+use crate::types::ErrorCategory;
 
 pub fn router(state: AppState) -> Router {
+    let config = state.config.current();
+    let gate = X402Gate::new(
+        &config,
+        config.payment.x402_analysis_price_usd,
+        "Dataset analysis",
+    );
+    // Layers run last-added first: requests are checked before they are paid for
     Router::new()
         .route("/api/analysis", post(run_analysis_handler))
+        .route_layer(middleware::from_fn_with_state(gate, require_payment))
+        .route_layer(middleware::from_fn_with_state(state.clone(), check_request))
         .with_state(state)
 }
 
-async fn run_analysis_handler(
-    State(state): State<AppState>,
-    Json(request): Json<AnalysisRequest>,
-) -> Result<Json<AnalysisResponse>, axum::http::StatusCode> {
-    info!(dataset_id = %request.dataset_id, "Analysis request received");
+/// Refuse requests the handler would reject before they reach the payment
+/// gate, so they are never charged
+async fn check_request(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let (request, req) = match x402::peek_json::<AnalysisRequest>(req).await {
+        Ok(peeked) => peeked,
+        Err(rejection) => return rejection,
+    };
+    match checked(&state, &request).await {
+        Ok(_) => next.run(req).await,
+        Err(status) => status.into_response(),
+    }
+}
 
+/// The dataset and analysis `request` asks for, or the status it is
+/// rejected with
+async fn checked(state: &AppState, request: &AnalysisRequest) -> Result<(DatasetRecord, AnalysisConfig), StatusCode> {
     let record = state
         .dataset_registry
         .get(&request.dataset_id)
         .await
-        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+        .ok_or(StatusCode::NOT_FOUND)?;
 
-    let output_dir = Path::new("artifacts")
-        .join("analysis")
-        .join(&request.dataset_id);
-    fs::create_dir_all(&output_dir)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    let thresholds = request.thresholds.unwrap_or_default();
+    thresholds.validate().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let regularization = request.regularization.unwrap_or_default();
+    regularization.validate().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let threads = request.threads.unwrap_or(0);
+    threads::validate(threads).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if request.time_column.is_some() != request.event_column.is_some() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
+    let defaults = AnalysisConfig::default();
     let config = AnalysisConfig {
         target_column: request.target_column.clone(),
         group_column: request.group_column.clone(),
         covariates: request.covariates.clone().unwrap_or_default(),
         boxplot_column: request.boxplot_column.clone(),
-        max_columns: request.max_columns.unwrap_or(50),
-        max_groups: request.max_groups.unwrap_or(20),
+        max_columns: request.max_columns.unwrap_or(defaults.max_columns),
+        max_groups: request.max_groups.unwrap_or(defaults.max_groups),
+        imputation: request.imputation.unwrap_or_default(),
+        normalization: request.normalization.clone().unwrap_or_default(),
+        batch_column: request.batch_column.clone(),
+        batch_correction: request.batch_correction.unwrap_or_default(),
+        correlation: request.correlation.unwrap_or_default(),
+        tables: request.tables.unwrap_or_default(),
+        numbers: request.numbers.unwrap_or_default(),
+        thresholds,
+        kruskal_wallis: request.kruskal_wallis.unwrap_or(false),
+        memory_limit: request.memory_limit.unwrap_or_default(),
+        threads,
+        regularization,
+        roc: request.roc.unwrap_or_default(),
+        time_column: request.time_column.clone(),
+        event_column: request.event_column.clone(),
+        subject_column: request.subject_column.clone(),
+        ..defaults
     };
+    Ok((record, config))
+}
 
-    let analysis = run_analysis(&record, &config, &output_dir)
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+async fn run_analysis_handler(
+    State(state): State<AppState>,
+    Json(request): Json<AnalysisRequest>,
+) -> Result<Json<AnalysisResponse>, axum::http::StatusCode> {
+    info!(dataset_id = %request.dataset_id, "Analysis request received");
 
-    let mut artifacts: Vec<AnalysisArtifact> = Vec::new();
-    let stats_path = output_dir.join("descriptive_stats.csv");
-    let regression_path = output_dir.join("regressions.csv");
-    let novelty_path = output_dir.join("novelty_scores.csv");
-    let biomarker_path = output_dir.join("biomarker_candidates.csv");
+    let (record, config) = checked(&state, &request).await?;
 
-    write_stats_csv(&stats_path, &analysis.descriptive_stats)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
-    write_regression_csv(&regression_path, &analysis.regressions)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
-    write_novelty_csv(&novelty_path, &analysis.novelty_scores)
+    let output_dir = Path::new("artifacts")
+        .join("analysis")
+        .join(&request.dataset_id);
+    fs::create_dir_all(&output_dir)
         .await
         .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
-    write_biomarker_csv(&biomarker_path, &analysis.biomarker_candidates)
+
+    // Dropping the handler (client disconnect) cancels the analysis
+    let cancel = CancellationToken::new();
+    let _cancel_on_drop = cancel.clone().drop_guard();
+    let started = std::time::Instant::now();
+    let (task_record, task_config, task_dir) = (record.clone(), config.clone(), output_dir.clone());
+    let mut analysis = tokio::task::spawn_blocking(move || run_analysis(&task_record, &task_config, &task_dir, &cancel))
         .await
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            warn!(dataset_id = %request.dataset_id, error = %e, "Analysis failed");
+            // The dataset or the options sent do not fit the analysis
+            match e.category() {
+                ErrorCategory::Data | ErrorCategory::Config => axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+                _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        })?;
+    metering::record_analysis(started, &request.dataset_id);
+    let app_config = state.config.current();
+    let top_n = request.cross_reference.unwrap_or(app_config.cross_reference.top_n);
+    literature::cross_reference(&app_config, &mut analysis, top_n).await;
+    legends::polish(&app_config, &mut analysis).await;
+
+    let tables = report::write_tables(&output_dir, &analysis, config.tables)
         .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    let file_name = |path: &std::path::Path| path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let stats_path = tables.descriptive_stats;
+    let regression_path = tables.regressions;
+    let novelty_path = tables.novelty_scores;
+    let biomarker_path = tables.biomarker_candidates;
 
+    let mut artifacts: Vec<AnalysisArtifact> = Vec::new();
     artifacts.push(AnalysisArtifact {
         id: "descriptive_stats".to_string(),
         description: "Descriptive statistics per numeric column".to_string(),
         artifact_type: "FILE".to_string(),
         content: None,
-        name: "descriptive_stats.csv".to_string(),
+        name: file_name(&stats_path),
         path: Some(stats_path.to_string_lossy().to_string()),
     });
     artifacts.push(AnalysisArtifact {
@@ -76,7 +154,7 @@ async fn run_analysis_handler(
         description: "Linear regression results".to_string(),
         artifact_type: "FILE".to_string(),
         content: None,
-        name: "regressions.csv".to_string(),
+        name: file_name(&regression_path),
         path: Some(regression_path.to_string_lossy().to_string()),
     });
     artifacts.push(AnalysisArtifact {
@@ -84,7 +162,7 @@ async fn run_analysis_handler(
         description: "Novelty scoring based on group mean deviation".to_string(),
         artifact_type: "FILE".to_string(),
         content: None,
-        name: "novelty_scores.csv".to_string(),
+        name: file_name(&novelty_path),
         path: Some(novelty_path.to_string_lossy().to_string()),
     });
     artifacts.push(AnalysisArtifact {
@@ -92,9 +170,39 @@ async fn run_analysis_handler(
         description: "Ranked biomarker candidates by correlation".to_string(),
         artifact_type: "FILE".to_string(),
         content: None,
-        name: "biomarker_candidates.csv".to_string(),
+        name: file_name(&biomarker_path),
         path: Some(biomarker_path.to_string_lossy().to_string()),
     });
+    if let Some(path) = tables.group_comparisons {
+        artifacts.push(AnalysisArtifact {
+            id: "group_comparisons".to_string(),
+            description: "Each group against the other groups per marker (Welch's t test)".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = tables.anova {
+        artifacts.push(AnalysisArtifact {
+            id: "anova".to_string(),
+            description: "One-way ANOVA per marker across the groups".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = tables.group_contrast {
+        artifacts.push(AnalysisArtifact {
+            id: "group_contrast".to_string(),
+            description: "Two-group tests per marker (Welch's t, Mann-Whitney U, Cohen's d)".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
 
     if let Some(path) = analysis.heatmap_path.clone() {
         artifacts.push(AnalysisArtifact {
@@ -106,6 +214,16 @@ async fn run_analysis_handler(
             path: Some(path),
         });
     }
+    if let Some(path) = analysis.expression_heatmap_path.clone() {
+        artifacts.push(AnalysisArtifact {
+            id: "expression_heatmap".to_string(),
+            description: "Expression heatmap of the top markers across samples".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: "expression_heatmap.png".to_string(),
+            path: Some(path),
+        });
+    }
     if let Some(path) = analysis.boxplot_path.clone() {
         artifacts.push(AnalysisArtifact {
             id: "boxplot".to_string(),
@@ -116,13 +234,176 @@ async fn run_analysis_handler(
             path: Some(path),
         });
     }
+    if let Some(path) = analysis.volcano_path.clone() {
+        artifacts.push(AnalysisArtifact {
+            id: "volcano".to_string(),
+            description: "Volcano plot of the two-group contrast".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: "volcano.png".to_string(),
+            path: Some(path),
+        });
+    }
+    if let Some(path) = analysis.roc_path.clone() {
+        artifacts.push(AnalysisArtifact {
+            id: "roc".to_string(),
+            description: "ROC curves of the best-discriminating biomarker candidates".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: crate::analysis::roc::ROC_FILE.to_string(),
+            path: Some(path),
+        });
+    }
+    if let Some(path) = analysis.survival_path.clone() {
+        artifacts.push(AnalysisArtifact {
+            id: "survival".to_string(),
+            description: "Kaplan-Meier survival curves".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: crate::analysis::survival::KM_FILE.to_string(),
+            path: Some(path),
+        });
+    }
+    if let Some(path) = tables.cox {
+        artifacts.push(AnalysisArtifact {
+            id: "cox_survival".to_string(),
+            description: "Univariate Cox proportional hazards model per marker".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = tables.go_enrichment {
+        artifacts.push(AnalysisArtifact {
+            id: "go_enrichment".to_string(),
+            description: "GO term enrichment among significant biomarkers".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = tables.pathway_enrichment {
+        artifacts.push(AnalysisArtifact {
+            id: "pathway_enrichment".to_string(),
+            description: "Reactome and KEGG pathway enrichment among significant biomarkers".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = tables.sex_interactions {
+        artifacts.push(AnalysisArtifact {
+            id: "sex_interactions".to_string(),
+            description: "Per-sex associations and marker-by-sex interaction tests".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = analysis.enrichment_plot_path.clone() {
+        artifacts.push(AnalysisArtifact {
+            id: "go_enrichment_plot".to_string(),
+            description: "Bar plot of enriched GO terms".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: "go_enrichment.png".to_string(),
+            path: Some(path),
+        });
+    }
+    if let Some(path) = tables.pca_loadings {
+        artifacts.push(AnalysisArtifact {
+            id: "pca_loadings".to_string(),
+            description: "Marker loadings on the principal components".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = tables.pca_variance {
+        artifacts.push(AnalysisArtifact {
+            id: "pca_variance".to_string(),
+            description: "Variance explained by each principal component".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = tables.kmeans {
+        artifacts.push(AnalysisArtifact {
+            id: "kmeans_clusters".to_string(),
+            description: "K-means cluster assignment of each sample".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = tables.regularized {
+        artifacts.push(AnalysisArtifact {
+            id: "regularized_model".to_string(),
+            description: "Intercept and non-zero coefficients of the regularized model of the target".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: file_name(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    if let Some(path) = analysis.pca_scree_path.clone() {
+        artifacts.push(AnalysisArtifact {
+            id: "pca_scree".to_string(),
+            description: "Scree plot of the principal components".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: "pca_scree.png".to_string(),
+            path: Some(path),
+        });
+    }
+    if let Some(path) = analysis.pca_scores_path.clone() {
+        artifacts.push(AnalysisArtifact {
+            id: "pca_scores".to_string(),
+            description: "PC1 against PC2 scores by group".to_string(),
+            artifact_type: "FILE".to_string(),
+            content: None,
+            name: "pca_scores.png".to_string(),
+            path: Some(path),
+        });
+    }
 
     let target = request.target_column.clone().unwrap_or_else(|| "age".to_string());
     let group = request.group_column.clone().unwrap_or_else(|| "cell_type".to_string());
-    let manuscript = build_manuscript(&request.dataset_id, &target, &group, &record, &analysis);
+    let template = request.template.unwrap_or(app_config.manuscript.template);
+    let manuscript = build_manuscript(&request.dataset_id, &target, &group, &record, &analysis, template);
+    // LaTeX version for POST /api/analysis/{dataset_id}/pdf
+    crate::manuscript::export_latex(&output_dir, &manuscript, &[], Some(&analysis), template)
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    artifacts.push(AnalysisArtifact {
+        id: "manuscript_tex".to_string(),
+        description: "LaTeX manuscript with biomarker table and figures".to_string(),
+        artifact_type: "FILE".to_string(),
+        content: None,
+        name: crate::manuscript::pdf::TEX_FILE.to_string(),
+        path: Some(output_dir.join(crate::manuscript::pdf::TEX_FILE).to_string_lossy().to_string()),
+    });
+    let bundle = supplement::write_supplement(&output_dir, &record, &config, &analysis, template)
+        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+    artifacts.push(AnalysisArtifact {
+        id: "supplementary".to_string(),
+        description: "Supplementary materials bundle (tables, figures, analysis JSON, rerun script)".to_string(),
+        artifact_type: "FILE".to_string(),
+        content: None,
+        name: supplement::SUPPLEMENT_FILE.to_string(),
+        path: Some(bundle.to_string_lossy().to_string()),
+    });
     let response = AnalysisResponse {
         status: "success".to_string(),
         dataset_id: request.dataset_id,
+        findings: analysis.findings(),
         summary: analysis.summary,
         descriptive_stats: analysis.descriptive_stats,
         regressions: analysis.regressions,
@@ -135,67 +416,71 @@ async fn run_analysis_handler(
     Ok(Json(response))
 }
 
-async fn write_stats_csv(path: &Path, stats: &[crate::models::DescriptiveStat]) -> anyhow::Result<()> {
-    let mut wtr = csv::Writer::from_writer(Vec::new());
-    wtr.write_record(["column", "count", "mean", "std_dev", "min", "median", "max"])?;
-    for stat in stats {
-        wtr.write_record([
-            &stat.column,
-            &stat.count.to_string(),
-            &stat.mean.to_string(),
-            &stat.std_dev.to_string(),
-            &stat.min.to_string(),
-            &stat.median.to_string(),
-            &stat.max.to_string(),
-        ])?;
-    }
-    let data = wtr.into_inner()?;
-    fs::write(path, data).await?;
-    Ok(())
-}
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use tower::ServiceExt;
 
-async fn write_regression_csv(path: &Path, regressions: &[crate::models::RegressionResult]) -> anyhow::Result<()> {
-    let mut wtr = csv::Writer::from_writer(Vec::new());
-    wtr.write_record(["target", "predictors", "intercept", "coefficients", "r2", "n"])?;
-    for reg in regressions {
-        wtr.write_record([
-            &reg.target,
-            &reg.predictors.join(";"),
-            &reg.intercept.to_string(),
-            &reg.coefficients.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(";"),
-            &reg.r2.to_string(),
-            &reg.n.to_string(),
-        ])?;
-    }
-    let data = wtr.into_inner()?;
-    fs::write(path, data).await?;
-    Ok(())
-}
+    use super::*;
+    use crate::config::Config;
+    use crate::session::{EventBus, LiveConfig};
 
-async fn write_novelty_csv(path: &Path, novelty: &[crate::models::NoveltyScore]) -> anyhow::Result<()> {
-    let mut wtr = csv::Writer::from_writer(Vec::new());
-    wtr.write_record(["column", "score", "rationale"])?;
-    for score in novelty {
-        wtr.write_record([&score.column, &score.score.to_string(), &score.rationale])?;
+    fn paid_post(body: serde_json::Value) -> Request {
+        let payment = serde_json::json!({
+            "x402Version": 1,
+            "scheme": "exact",
+            "network": "base-sepolia",
+            "payload": {}
+        });
+        Request::builder()
+            .method("POST")
+            .uri("/api/analysis")
+            .header("content-type", "application/json")
+            .header(x402::PAYMENT_HEADER, BASE64.encode(serde_json::to_vec(&payment).unwrap()))
+            .body(Body::from(body.to_string()))
+            .unwrap()
     }
-    let data = wtr.into_inner()?;
-    fs::write(path, data).await?;
-    Ok(())
-}
 
-async fn write_biomarker_csv(path: &Path, biomarkers: &[BiomarkerCandidate]) -> anyhow::Result<()> {
-    let mut wtr = csv::Writer::from_writer(Vec::new());
-    wtr.write_record(["column", "score", "correlation", "direction", "notes"])?;
-    for bm in biomarkers {
-        wtr.write_record([
-            &bm.column,
-            &bm.score.to_string(),
-            &bm.correlation.to_string(),
-            &bm.direction,
-            &bm.notes,
-        ])?;
-    }
-    let data = wtr.into_inner()?;
-    fs::write(path, data).await?;
-    Ok(())
+    #[tokio::test]
+    async fn test_rejected_requests_are_not_settled() {
+        let mut facilitator = mockito::Server::new_async().await;
+        let verify = facilitator.mock("POST", "/verify").expect(0).create_async().await;
+        let settle = facilitator.mock("POST", "/settle").expect(0).create_async().await;
+
+        let mut config = Config::from_env().unwrap();
+        config.payment.x402_enabled = true;
+        config.payment.x402_network = "base-sepolia".to_string();
+        config.payment.x402_payment_address = Some("0xpayee".to_string());
+        config.payment.x402_public_url = Some("https://api.example.org".to_string());
+        config.payment.x402_facilitator_url = facilitator.url();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cohort.csv");
+        std::fs::write(&path, "sample,age,gene_a\ns1,30,1.5\ns2,60,2.5\ns3,45,2.0\n").unwrap();
+        let record = DatasetRecord::from_path(&path, None).unwrap();
+        let dataset_id = record.dataset.id.clone();
+
+        let state = AppState {
+            pool: sqlx::postgres::PgPoolOptions::new().connect_lazy("postgres://localhost/oxidized_bio").unwrap(),
+            config: LiveConfig::new(config),
+            dataset_registry: Default::default(),
+            events: EventBus::default(),
+        };
+        state.dataset_registry.insert(record).await;
+        let app = router(state);
+
+        let unknown = app.clone().oneshot(paid_post(serde_json::json!({ "dataset_id": "missing" }))).await.unwrap();
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+
+        let invalid = serde_json::json!({ "dataset_id": dataset_id, "thresholds": { "alpha": 2.0 } });
+        let invalid = app.clone().oneshot(paid_post(invalid)).await.unwrap();
+        assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+
+        let malformed = app.oneshot(paid_post(serde_json::json!({ "target_column": "age" }))).await.unwrap();
+        assert_eq!(malformed.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        verify.assert_async().await;
+        settle.assert_async().await;
+    }
 }
@@ -3,6 +3,11 @@
 //! Handles the main chat endpoint that powers the research assistant.
 //! This endpoint orchestrates the full agent pipeline:
 //! Planning → Literature Search → Reply Generation
+//!
+//! `POST /api/chat` answers once the pipeline is done. `POST /api/chat/stream`
+//! takes the same request and streams the pipeline's
+//! [`PipelineEvent`](crate::agents::PipelineEvent)s as server-sent events,
+//! ending with `completed` or `failed`.
 
 use axum::{
     Router,
@@ -10,18 +15,24 @@ use axum::{
     Json,
     extract::State,
     response::Json as ResponseJson,
+    response::sse::{Event, KeepAlive, Sse},
     http::StatusCode,
 };
+use futures::stream::{self, Stream, StreamExt};
 use crate::models::{AppState, ChatRequest, ChatResponse};
-use crate::agents;
+use crate::agents::{self, PipelineEvent, ReplyMode};
+use crate::db::DatabaseOperations;
+use crate::utils::cancel::CancellationToken;
 use uuid::Uuid;
-use tracing::{info, error};
+use tracing::{info, error, warn};
+use std::convert::Infallible;
 use std::time::Instant;
 
 pub fn router(state: AppState) -> Router {
     Router::new()
         .route("/api/chat", get(get_chat))
         .route("/api/chat", post(post_chat))
+        .route("/api/chat/stream", post(stream_chat))
         .with_state(state)
 }
 
@@ -34,7 +45,8 @@ async fn get_chat() -> Result<ResponseJson<serde_json::Value>, StatusCode> {
         "request_format": {
             "message": "string (required)",
             "conversation_id": "uuid (optional)",
-            "files": "array (optional)"
+            "files": "array (optional)",
+            "mode": "answer | report | chat (optional; kept for later messages of the conversation, guessed from the message until set)"
         }
     })))
 }
@@ -62,10 +74,17 @@ pub async fn post_chat(
     });
 
     // Execute the research pipeline
+    // TODO: Load the rest of the conversation state from DB; for now only
+    // its reply mode and the artifacts of the registered datasets
+    let mode = conversation_mode(&state, Some(conversation_id), request.mode).await;
+    let conversation_state = agents::data_state(&request.message, None, mode, state.dataset_registry.artifacts().await);
     let response_text = match agents::execute_research_pipeline(
         &request.message,
-        None, // TODO: Load conversation state from DB
-        &state.config,
+        conversation_state.as_ref(),
+        &state.config.current(),
+        // A client abort drops this future, which stops the pipeline
+        &CancellationToken::new(),
+        |_| {},
     ).await {
         Ok(text) => text,
         Err(e) => {
@@ -73,7 +92,7 @@ pub async fn post_chat(
             format!(
                 "I apologize, but I encountered an error processing your request: {}\n\n\
                 Please try again or rephrase your question.",
-                e
+                e.user_message()
             )
         }
     };
@@ -97,6 +116,96 @@ pub async fn post_chat(
     Ok(Json(response))
 }
 
+/// POST /api/chat/stream - Run the pipeline, streaming its progress events
+async fn stream_chat(
+    State(state): State<AppState>,
+    Json(request): Json<ChatRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    if request.message.trim().is_empty() {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    info!(message_len = request.message.len(), "Received streaming chat request");
+
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<PipelineEvent>();
+    let config = state.config.current();
+    let mode = conversation_mode(&state, request.conversation_id, request.mode).await;
+    let conversation_state = agents::data_state(&request.message, None, mode, state.dataset_registry.artifacts().await);
+    let cancel = CancellationToken::new();
+    let task_cancel = cancel.clone();
+    tokio::spawn(async move {
+        // The pipeline reports failures as its last event
+        let _ = agents::execute_research_pipeline(&request.message, conversation_state.as_ref(), &config, &task_cancel, |event| {
+            // The client may have gone away
+            let _ = tx.send(event);
+        })
+        .await;
+    });
+
+    // The guard lives as long as the response stream: when the client
+    // disconnects, axum drops the stream and the pipeline is cancelled
+    let stream_state = (rx, cancel.drop_guard());
+    let events = stream::unfold(stream_state, |(mut rx, guard)| async move {
+        rx.recv().await.map(|event| (event, (rx, guard)))
+    })
+    .map(|event| Ok(to_sse(&event)));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Reply mode of the conversation: `requested`, saved in the conversation's
+/// state for its later messages, or else the one saved before. A
+/// conversation not stored in the database just uses `requested`
+async fn conversation_mode(
+    state: &AppState,
+    conversation_id: Option<Uuid>,
+    requested: Option<ReplyMode>,
+) -> Option<ReplyMode> {
+    let Some(conversation_id) = conversation_id else {
+        return requested;
+    };
+    let (state_id, mut values) = match stored_state(state, conversation_id).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return requested,
+        Err(e) => {
+            warn!(conversation_id = %conversation_id, error = %e, "Failed to load the conversation state");
+            return requested;
+        }
+    };
+    match requested {
+        None => values.reply_mode,
+        Some(mode) => {
+            if values.reply_mode != Some(mode) {
+                values.reply_mode = Some(mode);
+                if let Err(e) = DatabaseOperations::update_conversation_state(&state.pool, state_id, &values).await {
+                    warn!(conversation_id = %conversation_id, error = %e, "Failed to save the reply mode");
+                }
+            }
+            Some(mode)
+        }
+    }
+}
+
+async fn stored_state(
+    state: &AppState,
+    conversation_id: Uuid,
+) -> anyhow::Result<Option<(Uuid, crate::models::ConversationStateValues)>> {
+    let Some(state_id) = DatabaseOperations::get_conversation(&state.pool, conversation_id)
+        .await?
+        .and_then(|conversation| conversation.conversation_state_id)
+    else {
+        return Ok(None);
+    };
+    Ok(DatabaseOperations::get_conversation_state(&state.pool, state_id)
+        .await?
+        .map(|stored| (state_id, stored.values)))
+}
+
+fn to_sse(event: &PipelineEvent) -> Event {
+    Event::default()
+        .event(event.name())
+        .json_data(event)
+        .unwrap_or_else(|_| Event::default().event(event.name()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,50 +1,324 @@
+//! Deep Research Route
+//!
+//! `POST /api/deep-research/start` runs the iterative research loop
+//! ([`crate::agents::deep_research`]) in the background and returns its
+//! message id at once. Progress is polled with
+//! `GET /api/deep-research/status/{message_id}`, which includes the report
+//! once the run completes, or streamed as server-sent events from
+//! `GET /api/deep-research/stream/{message_id}`: one `progress` event per
+//! step (earlier steps are replayed first), then `completed` with the report
+//! or `failed` with the error. Runs are kept in memory for an hour after they
+//! finish.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
 use axum::{
     Router,
     routing::{get, post},
     Json,
-    extract::{State, Path},
+    extract::{Request, State, Path},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
     response::Json as ResponseJson,
+    response::sse::{Event, KeepAlive, Sse},
 };
+use axum::middleware;
+use futures::stream::{self, Stream, StreamExt};
+use tokio::sync::broadcast;
+use crate::utils::cancel::CancellationToken;
+use crate::agents::deep_research::{self, DeepResearchOptions, DeepResearchReport, ResearchEvent};
 use crate::models::{AppState, DeepResearchRequest, DeepResearchResponse};
+use crate::payment::x402::{self, require_payment, X402Gate};
 use uuid::Uuid;
-use tracing::info;
+use tracing::{info, error};
+
+/// Finished runs are dropped after this long
+const RETENTION: Duration = Duration::from_secs(3600);
+
+static RUNS: LazyLock<RwLock<HashMap<Uuid, Run>>> = LazyLock::new(Default::default);
 
 pub fn router(state: AppState) -> Router {
-    Router::new()
+    let config = state.config.current();
+    let gate = X402Gate::new(
+        &config,
+        config.payment.x402_deep_research_price_usd,
+        "Deep research request",
+    );
+    // Only starting research is metered; status polling stays free
+    let paid = Router::new()
         .route("/api/deep-research/start", post(start_deep_research))
+        .route_layer(middleware::from_fn_with_state(gate, require_payment))
+        .route_layer(middleware::from_fn(check_request));
+
+    Router::new()
+        .merge(paid)
         .route("/api/deep-research/status/{message_id}", get(get_status))
+        .route("/api/deep-research/stream/{message_id}", get(stream_progress))
         .with_state(state)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RunStatus {
+    Processing,
+    Completed,
+    Failed,
+}
+
+impl RunStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Processing => "processing",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Sent to stream subscribers
+#[derive(Debug, Clone)]
+enum RunUpdate {
+    Progress { event: ResearchEvent, progress: u8 },
+    Completed(Box<DeepResearchReport>),
+    Failed(String),
+}
+
+impl RunUpdate {
+    fn is_final(&self) -> bool {
+        !matches!(self, Self::Progress { .. })
+    }
+
+    fn to_sse(&self) -> Event {
+        match self {
+            Self::Progress { event, progress } => {
+                let mut data = serde_json::to_value(event).unwrap_or_default();
+                data["progress"] = (*progress).into();
+                Event::default().event("progress").data(data.to_string())
+            }
+            Self::Completed(report) => Event::default()
+                .event("completed")
+                .json_data(report)
+                .unwrap_or_else(|_| Event::default().event("completed")),
+            Self::Failed(error) => Event::default().event("failed").data(error.as_str()),
+        }
+    }
+}
+
+struct Run {
+    conversation_id: Uuid,
+    depth: u32,
+    status: RunStatus,
+    /// Percent done, from the last event
+    progress: u8,
+    events: Vec<ResearchEvent>,
+    report: Option<DeepResearchReport>,
+    error: Option<String>,
+    updates: broadcast::Sender<RunUpdate>,
+    finished_at: Option<Instant>,
+}
+
+impl Run {
+    /// Updates so far, for a subscriber joining late
+    fn backlog(&self) -> Vec<RunUpdate> {
+        let mut progress = 0;
+        let mut updates: Vec<RunUpdate> = self
+            .events
+            .iter()
+            .map(|event| {
+                progress = progress_after(event, self.depth, progress);
+                RunUpdate::Progress { event: event.clone(), progress }
+            })
+            .collect();
+        match (self.status, &self.report, &self.error) {
+            (RunStatus::Completed, Some(report), _) => updates.push(RunUpdate::Completed(Box::new(report.clone()))),
+            (RunStatus::Failed, _, Some(error)) => updates.push(RunUpdate::Failed(error.clone())),
+            _ => {}
+        }
+        updates
+    }
+}
+
+/// Percent done after `event`: iterations take up to 90%, writing the report the rest
+fn progress_after(event: &ResearchEvent, depth: u32, previous: u8) -> u8 {
+    let per_iteration = 90.0 / depth.max(1) as f64;
+    let progress = match event {
+        ResearchEvent::IterationStarted { iteration, .. } => (*iteration - 1) as f64 * per_iteration,
+        ResearchEvent::Planned { iteration, .. } => (*iteration as f64 - 0.8) * per_iteration,
+        ResearchEvent::Searched { .. } | ResearchEvent::SearchFailed { .. } => previous as f64 + 0.2 * per_iteration,
+        ResearchEvent::Synthesized { iteration, .. } => *iteration as f64 * per_iteration,
+        ResearchEvent::Stopped { .. } | ResearchEvent::Writing => 90.0,
+    };
+    (progress.round() as u8).clamp(previous, 95)
+}
+
+fn record(message_id: Uuid, update: impl FnOnce(&mut Run) -> RunUpdate) {
+    let mut runs = RUNS.write().unwrap_or_else(|e| e.into_inner());
+    if let Some(run) = runs.get_mut(&message_id) {
+        let update = update(run);
+        // No subscribers is fine
+        let _ = run.updates.send(update);
+    }
+}
+
+/// Refuse requests the handler would reject before they reach the payment
+/// gate, so they are never charged
+async fn check_request(req: Request, next: Next) -> Response {
+    let (request, req) = match x402::peek_json::<DeepResearchRequest>(req).await {
+        Ok(peeked) => peeked,
+        Err(rejection) => return rejection,
+    };
+    if request.message.trim().is_empty() {
+        return StatusCode::UNPROCESSABLE_ENTITY.into_response();
+    }
+    next.run(req).await
+}
+
 async fn start_deep_research(
     State(state): State<AppState>,
     Json(request): Json<DeepResearchRequest>,
-) -> Result<ResponseJson<DeepResearchResponse>, axum::http::StatusCode> {
+) -> Result<ResponseJson<DeepResearchResponse>, StatusCode> {
     info!("Received deep research request: {:?}", request.message);
+    if request.message.trim().is_empty() {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
 
     let message_id = Uuid::new_v4();
-    let conversation_id = request.conversation_id.unwrap_or_else(|| Uuid::new_v4());
+    let conversation_id = request.conversation_id.unwrap_or_else(Uuid::new_v4);
+    let depth = state.config.current().deep_research.depth_for(request.depth);
+    {
+        let mut runs = RUNS.write().unwrap_or_else(|e| e.into_inner());
+        runs.retain(|_, run| run.finished_at.is_none_or(|at| at.elapsed() < RETENTION));
+        runs.insert(
+            message_id,
+            Run {
+                conversation_id,
+                depth,
+                status: RunStatus::Processing,
+                progress: 0,
+                events: Vec::new(),
+                report: None,
+                error: None,
+                updates: broadcast::channel(64).0,
+                finished_at: None,
+            },
+        );
+    }
 
-    let response = DeepResearchResponse {
-        message_id,
-        status: "pending".to_string(), // Would be "processing" or "completed"
-        conversation_id,
+    let config = state.config.clone();
+    let options = DeepResearchOptions {
+        depth,
+        max_tasks: config.current().deep_research.max_tasks,
     };
+    tokio::spawn(async move {
+        // The run outlives the request that started it, so nothing cancels it
+        let cancel = CancellationToken::new();
+        let outcome = deep_research::run(&request.message, options, &config, &cancel, |event| {
+            record(message_id, |run| {
+                run.progress = progress_after(&event, run.depth, run.progress);
+                run.events.push(event.clone());
+                RunUpdate::Progress { event, progress: run.progress }
+            })
+        })
+        .await;
+        record(message_id, |run| {
+            run.finished_at = Some(Instant::now());
+            match outcome {
+                Ok(report) => {
+                    run.status = RunStatus::Completed;
+                    run.progress = 100;
+                    run.report = Some(report.clone());
+                    RunUpdate::Completed(Box::new(report))
+                }
+                Err(e) => {
+                    error!(message_id = %message_id, error = %e, "Deep research failed");
+                    run.status = RunStatus::Failed;
+                    run.error = Some(e.user_message());
+                    RunUpdate::Failed(e.user_message())
+                }
+            }
+        });
+    });
 
-    Ok(Json(response))
+    Ok(Json(DeepResearchResponse {
+        message_id,
+        status: RunStatus::Processing.as_str().to_string(),
+        conversation_id,
+        depth,
+    }))
 }
 
 async fn get_status(
     State(_state): State<AppState>,
     Path(message_id): Path<Uuid>,
-) -> Result<ResponseJson<serde_json::Value>, axum::http::StatusCode> {
+) -> Result<ResponseJson<serde_json::Value>, StatusCode> {
     info!("Checking status for message: {:?}", message_id);
 
-    let status = serde_json::json!({
+    let runs = RUNS.read().unwrap_or_else(|e| e.into_inner());
+    let run = runs.get(&message_id).ok_or(StatusCode::NOT_FOUND)?;
+    let iteration = run.events.iter().rev().find_map(ResearchEvent::iteration).unwrap_or(0);
+
+    Ok(Json(serde_json::json!({
         "message_id": message_id,
-        "status": "completed",
-        "progress": 100
+        "conversation_id": run.conversation_id,
+        "status": run.status.as_str(),
+        "progress": run.progress,
+        "depth": run.depth,
+        "iteration": iteration,
+        "events": run.events,
+        "report": run.report,
+        "error": run.error,
+    })))
+}
+
+async fn stream_progress(
+    Path(message_id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    // Subscribing under the lock means no update falls between backlog and live ones
+    let (backlog, receiver) = {
+        let runs = RUNS.read().unwrap_or_else(|e| e.into_inner());
+        let run = runs.get(&message_id).ok_or(StatusCode::NOT_FOUND)?;
+        let receiver = (run.status == RunStatus::Processing).then(|| run.updates.subscribe());
+        (run.backlog(), receiver)
+    };
+
+    let live = stream::unfold(receiver, |receiver| async move {
+        let mut receiver = receiver?;
+        loop {
+            match receiver.recv().await {
+                Ok(update) => {
+                    let next = (!update.is_final()).then_some(receiver);
+                    return Some((update, next));
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
     });
+    let updates = stream::iter(backlog)
+        .chain(live)
+        .map(|update| Ok(update.to_sse()));
+    Ok(Sse::new(updates).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_after_events() {
+        let planned = ResearchEvent::Planned { iteration: 1, objective: String::new(), tasks: vec![] };
+        let searched = ResearchEvent::Searched { iteration: 1, task: String::new(), sources: 3 };
+        let synthesized = ResearchEvent::Synthesized { iteration: 2, new_sources: 0, new_insights: 0, total_sources: 3 };
 
-    Ok(Json(status))
+        assert_eq!(progress_after(&ResearchEvent::IterationStarted { iteration: 1, depth: 3 }, 3, 0), 0);
+        assert_eq!(progress_after(&planned, 3, 0), 6);
+        assert_eq!(progress_after(&searched, 3, 6), 12);
+        assert_eq!(progress_after(&synthesized, 3, 12), 60);
+        assert_eq!(progress_after(&ResearchEvent::Writing, 3, 60), 90);
+        // Never goes backwards
+        assert_eq!(progress_after(&planned, 3, 40), 40);
+    }
 }
@@ -1,14 +1,26 @@
+//! File Upload Route
+//!
+//! `POST /api/files/{*path}` takes a multipart form with a `file` (CSV, TSV,
+//! Parquet, VCF, FASTA/FASTQ or a GEO series matrix/SOFT file) and optional `description` and `species` fields. Uploads are
+//! validated like the TUI's `/upload` ([`DatasetRecord::from_upload`]) and added to the
+//! shared dataset registry, so the returned ID works with `POST /api/analysis`
+//! and, when the TUI runs with `--serve`, shows up in its `/list`.
+
 use axum::{
     extract::{Multipart, State},
+    http::StatusCode,
     routing::post,
     Json, Router,
 };
-use crate::data_registry::{DatasetRecord};
-use crate::models::{AppState, UploadedDataset};
+use serde_json::json;
+use crate::annotation::Species;
+use crate::data_registry::{DatasetRecord, ParseOptions, UploadError, UPLOAD_DIR};
+use crate::metering::{self, UsageMetric};
+use crate::models::AppState;
+use crate::session::SessionEvent;
+use crate::utils::redact::redact;
 use bytes::Bytes;
-use tokio::fs;
 use tracing::{info, warn};
-use uuid::Uuid;
 
 pub fn router(state: AppState) -> Router {
     Router::new()
@@ -16,75 +28,90 @@ pub fn router(state: AppState) -> Router {
         .with_state(state)
 }
 
+type ErrorResponse = (StatusCode, Json<serde_json::Value>);
+
+fn error(status: StatusCode, message: impl Into<String>) -> ErrorResponse {
+    (status, Json(json!({ "error": redact(&message.into()) })))
+}
+
+fn status_for(e: &UploadError) -> StatusCode {
+    match e {
+        UploadError::UnsupportedType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        UploadError::Malformed(_) => StatusCode::BAD_REQUEST,
+        UploadError::MissingColumns | UploadError::MissingAge | UploadError::WrongSpecies(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        UploadError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 async fn upload_file(
     State(state): State<AppState>,
     mut multipart: Multipart,
-) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+) -> Result<Json<serde_json::Value>, ErrorResponse> {
     info!("File upload request received");
 
     let mut file_bytes: Option<Bytes> = None;
     let mut filename: Option<String> = None;
-    let mut content_type: Option<String> = None;
     let mut description: Option<String> = None;
+    let mut species: Option<Species> = None;
+    let mut parsing = ParseOptions::default();
 
     while let Ok(Some(field)) = multipart.next_field().await {
         let name = field.name().unwrap_or_default().to_string();
         if name == "file" {
             filename = field.file_name().map(|s| s.to_string());
-            content_type = field.content_type().map(|s| s.to_string());
-            file_bytes = Some(field.bytes().await.map_err(|_| axum::http::StatusCode::BAD_REQUEST)?);
+            file_bytes = Some(
+                field
+                    .bytes()
+                    .await
+                    .map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?,
+            );
         } else if name == "description" {
-            description = Some(field.text().await.unwrap_or_default());
+            description = Some(field.text().await.unwrap_or_default()).filter(|d| !d.trim().is_empty());
+        } else if name == "species" {
+            let text = field.text().await.unwrap_or_default();
+            species = Some(
+                text.parse()
+                    .map_err(|_| error(StatusCode::BAD_REQUEST, format!("Unknown species '{}'", text)))?,
+            );
+        } else if matches!(name.as_str(), "decimal" | "na" | "quote") {
+            let text = field.text().await.unwrap_or_default();
+            parsing.set(&name, &text).map_err(|e| error(StatusCode::BAD_REQUEST, e))?;
         }
     }
 
-    let filename = filename.ok_or(axum::http::StatusCode::BAD_REQUEST)?;
-    let file_bytes = file_bytes.ok_or(axum::http::StatusCode::BAD_REQUEST)?;
-
-    let extension = std::path::Path::new(&filename)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_ascii_lowercase();
-    if extension != "csv" && extension != "tsv" {
-        warn!(filename = %filename, "Rejected unsupported file type");
-        return Err(axum::http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
-    }
+    let filename = filename.ok_or_else(|| error(StatusCode::BAD_REQUEST, "Missing file field"))?;
+    let file_bytes = file_bytes.ok_or_else(|| error(StatusCode::BAD_REQUEST, "Missing file field"))?;
+    // Keep only the last path component of client-supplied names
+    let filename = std::path::Path::new(&filename)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("dataset.csv")
+        .to_string();
 
-    let delimiter = if extension == "tsv" { b'\t' } else { b',' };
-    let dataset_id = Uuid::new_v4().to_string();
-    let upload_dir = std::path::Path::new("uploads");
-    fs::create_dir_all(upload_dir)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
-    let stored_name = format!("{}-{}", dataset_id, filename);
-    let local_path = upload_dir.join(&stored_name);
-    fs::write(&local_path, &file_bytes)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let (columns, row_count) = infer_csv_metadata(&file_bytes, delimiter)?;
-
-    let dataset = UploadedDataset {
-        filename: filename.clone(),
-        id: dataset_id.clone(),
-        description: description.unwrap_or_else(|| format!("Uploaded dataset {}", filename)),
-        path: Some(local_path.to_string_lossy().to_string()),
-        content: None,
-        size: Some(file_bytes.len() as i64),
-    };
-
-    let record = DatasetRecord {
-        dataset: dataset.clone(),
-        local_path: local_path.to_string_lossy().to_string(),
-        content_type: content_type.unwrap_or_else(|| "text/plain".to_string()),
-        delimiter,
-        has_headers: true,
-        columns: columns.clone(),
-        row_count,
-    };
-    state.dataset_registry.insert(record).await;
+    let record = DatasetRecord::from_upload(
+        std::path::Path::new(UPLOAD_DIR),
+        &filename,
+        &file_bytes,
+        description,
+        species,
+        parsing,
+    )
+    .await
+    .map_err(|e| {
+        warn!(filename = %filename, error = %e, "Rejected dataset upload");
+        error(status_for(&e), e.to_string())
+    })?;
+    metering::record(
+        UsageMetric::StorageBytes,
+        file_bytes.len() as u64,
+        0.0,
+        serde_json::json!({ "dataset_id": record.dataset.id, "filename": filename }),
+    );
+    state.dataset_registry.insert(record.clone()).await;
+    state.events.publish(SessionEvent::dataset_added(&record, "http"));
+    info!(dataset_id = %record.dataset.id, rows = record.row_count, "Dataset uploaded");
 
+    let dataset = &record.dataset;
     let response = serde_json::json!({
         "status": "success",
         "message": "File uploaded successfully",
@@ -94,32 +121,12 @@ async fn upload_file(
             "description": dataset.description,
             "size": dataset.size,
             "path": dataset.path,
-            "columns": columns,
-            "row_count": row_count,
-            "delimiter": if delimiter == b'\t' { "tab" } else { "comma" },
+            "columns": record.columns,
+            "row_count": record.row_count,
+            "delimiter": if record.delimiter == b'\t' { "tab" } else { "comma" },
+            "species": record.species,
         }
     });
 
     Ok(Json(response))
 }
-
-fn infer_csv_metadata(bytes: &Bytes, delimiter: u8) -> Result<(Vec<String>, usize), axum::http::StatusCode> {
-    let mut rdr = csv::ReaderBuilder::new()
-        .delimiter(delimiter)
-        .has_headers(true)
-        .from_reader(bytes.as_ref());
-
-    let headers = rdr
-        .headers()
-        .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?
-        .iter()
-        .map(|h| h.to_string())
-        .collect::<Vec<_>>();
-
-    let mut row_count = 0usize;
-    for record in rdr.records() {
-        record.map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
-        row_count += 1;
-    }
-    Ok((headers, row_count))
-}
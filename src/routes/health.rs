@@ -1,12 +1,35 @@
-use axum::{Router, routing::get, Json, response::Json as ResponseJson};
-use crate::models::HealthResponse;
+//! Health Routes
+//!
+//! - `GET /healthz` - liveness: the process is up and serving requests
+//! - `GET /readyz` - readiness: the database, upload storage and job queue
+//!   answer and at least one LLM provider has a key. Responds 503 with each
+//!   component's status when any check fails, so orchestrators hold traffic
+//!   back instead of routing it to an instance that cannot serve it.
+//! - `GET /api/health` - liveness in the original response shape
 
-pub fn router() -> Router {
+use std::future::Future;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use serde_json::json;
+
+use crate::data_registry::UPLOAD_DIR;
+use crate::models::{AppState, ComponentHealth, HealthResponse, ReadinessChecks, ReadinessResponse};
+use crate::queue::JobQueue;
+
+/// Longest a single check may take before it counts as failed
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub fn router(state: AppState) -> Router {
     Router::new()
         .route("/api/health", get(health_check))
+        .route("/healthz", get(liveness))
+        .route("/readyz", get(readiness))
+        .with_state(state)
 }
 
-async fn health_check() -> ResponseJson<HealthResponse> {
+async fn health_check() -> Json<HealthResponse> {
     let response = HealthResponse {
         status: "ok".to_string(),
         timestamp: chrono::Utc::now().to_rfc3339(),
@@ -16,3 +39,102 @@ async fn health_check() -> ResponseJson<HealthResponse> {
 
     Json(response)
 }
+
+async fn liveness() -> Json<serde_json::Value> {
+    Json(json!({
+        "status": "ok",
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    }))
+}
+
+async fn readiness(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let config = state.config.current();
+    let database_configured = !config.database.url.is_empty();
+    let (database, storage, queue) = tokio::join!(
+        check(async {
+            if !database_configured {
+                anyhow::bail!("DATABASE_URL is not set");
+            }
+            crate::db::pool::health_check(&state.pool).await?;
+            Ok(None)
+        }),
+        check(check_storage(Path::new(UPLOAD_DIR))),
+        check(async {
+            if !database_configured {
+                anyhow::bail!("DATABASE_URL is not set");
+            }
+            let counts = JobQueue::new(state.pool.clone()).counts().await?;
+            let summary = counts
+                .iter()
+                .map(|(status, count)| format!("{} {}", count, status))
+                .collect::<Vec<_>>()
+                .join(", ");
+            Ok((!summary.is_empty()).then_some(summary))
+        }),
+    );
+    let llm = check(async {
+        let providers = config.llm.configured_providers();
+        if providers.is_empty() {
+            anyhow::bail!("no LLM provider has an API key");
+        }
+        Ok(Some(providers.join(", ")))
+    })
+    .await;
+
+    let checks = ReadinessChecks { database, storage, queue, llm };
+    let ready = [&checks.database, &checks.storage, &checks.queue, &checks.llm]
+        .iter()
+        .all(|c| c.status == "ok");
+    let response = ReadinessResponse {
+        status: if ready { "ready" } else { "unavailable" }.to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        checks,
+    };
+    let code = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (code, Json(response))
+}
+
+/// Run one readiness check within [`CHECK_TIMEOUT`]; it returns an optional
+/// detail to report on success
+async fn check(probe: impl Future<Output = anyhow::Result<Option<String>>>) -> ComponentHealth {
+    let started = Instant::now();
+    let outcome = tokio::time::timeout(CHECK_TIMEOUT, probe).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    let (status, detail) = match outcome {
+        Ok(Ok(detail)) => ("ok", detail),
+        Ok(Err(e)) => ("error", Some(e.to_string())),
+        Err(_) => ("error", Some(format!("timed out after {}s", CHECK_TIMEOUT.as_secs()))),
+    };
+    ComponentHealth {
+        status: status.to_string(),
+        latency_ms,
+        detail,
+    }
+}
+
+/// Uploads can be stored: `dir` exists (or can be created) and is writable
+async fn check_storage(dir: &Path) -> anyhow::Result<Option<String>> {
+    tokio::fs::create_dir_all(dir).await?;
+    let probe = dir.join(".readyz");
+    tokio::fs::write(&probe, b"ok").await?;
+    tokio::fs::remove_file(&probe).await?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_reports_status_and_detail() {
+        let dir = std::env::temp_dir().join(format!("readyz-{}", std::process::id()));
+        let ok = check(check_storage(&dir)).await;
+        assert_eq!((ok.status.as_str(), ok.detail), ("ok", None));
+        assert!(std::fs::read_dir(&dir).unwrap().next().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let failed = check(async { anyhow::bail!("no LLM provider has an API key") }).await;
+        assert_eq!(failed.status, "error");
+        assert_eq!(failed.detail.as_deref(), Some("no LLM provider has an API key"));
+    }
+}
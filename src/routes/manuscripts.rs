@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::manuscript::pdf;
+use crate::models::AppState;
+use crate::utils::redact::redact;
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/api/analysis/{dataset_id}/pdf", post(compile_pdf))
+        .with_state(state)
+}
+
+type ErrorResponse = (StatusCode, Json<serde_json::Value>);
+
+fn error(status: StatusCode, message: impl Into<String>) -> ErrorResponse {
+    (status, Json(json!({ "error": redact(&message.into()) })))
+}
+
+/// Compile the manuscript written by `POST /api/analysis` to PDF, save it
+/// next to the other artifacts and return it
+async fn compile_pdf(
+    State(state): State<AppState>,
+    UrlPath(dataset_id): UrlPath<String>,
+) -> Result<Response, ErrorResponse> {
+    if state.dataset_registry.get(&dataset_id).await.is_none() {
+        return Err(error(StatusCode::NOT_FOUND, format!("Dataset not found: {}", dataset_id)));
+    }
+    if !pdf::available() {
+        return Err(error(
+            StatusCode::NOT_IMPLEMENTED,
+            "PDF export is not available in this build (enable the `pdf` feature)",
+        ));
+    }
+    let dir = Path::new("artifacts").join("analysis").join(&dataset_id);
+    if !dir.join(pdf::TEX_FILE).is_file() {
+        return Err(error(
+            StatusCode::NOT_FOUND,
+            "No manuscript for this dataset; run POST /api/analysis first",
+        ));
+    }
+
+    info!(dataset_id = %dataset_id, "Compiling manuscript PDF");
+    let path = tokio::task::spawn_blocking(move || pdf::compile(&dir))
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| {
+            warn!(dataset_id = %dataset_id, error = %e, "PDF compilation failed");
+            error(StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+        })?;
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", pdf::PDF_FILE),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
@@ -4,8 +4,10 @@
 //! - `/api/chat` - Main chat endpoint
 //! - `/api/deep-research` - Deep research mode
 //! - `/api/files` - File upload handling
-//! - `/api/health` - Health checks
+//! - `/api/health`, `/healthz`, `/readyz` - Liveness and readiness checks
 //! - `/api/settings` - User settings and API key management
+//! - `/api/usage` - Metered usage per account
+//! - `/api/analysis/{dataset_id}/pdf` - Manuscript PDF compilation
 //! - `/api/rfc` - Remote Function Call endpoints
 //! - `/` - API root
 
@@ -14,9 +16,12 @@ pub mod deep_research;
 pub mod health;
 pub mod files;
 pub mod analysis;
+pub mod usage;
+pub mod manuscripts;
 pub mod ui;
 
-use axum::Router;
+use axum::{middleware, Router};
+use crate::metering;
 use crate::models::AppState;
 use crate::rfc;
 use crate::settings;
@@ -35,9 +40,12 @@ pub fn create_router(state: AppState) -> Router {
         .merge(deep_research::router(state.clone()))
         .merge(files::router(state.clone()))
         .merge(analysis::router(state.clone()))
+        .merge(usage::router(state.clone()))
+        .merge(manuscripts::router(state.clone()))
+        .merge(settings::router(state.clone()))
+        .merge(health::router(state.clone()))
         .merge(rfc::router(state))
-        .merge(health::router())
-        .merge(settings::router());  // Settings API (no state needed)
+        .layer(middleware::from_fn(metering::attribute_usage));
 
     Router::new()
         .merge(ui::router())
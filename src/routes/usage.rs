@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::metering;
+use crate::models::AppState;
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/api/usage", get(get_usage))
+        .with_state(state)
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageQuery {
+    /// Defaults to the account the request is attributed to
+    account: Option<String>,
+    /// Defaults to the start of the current month
+    since: Option<DateTime<Utc>>,
+}
+
+async fn get_usage(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+    let account = query.account.unwrap_or_else(metering::current_account);
+    let since = query.since.unwrap_or_else(metering::month_start);
+
+    let totals = metering::summary(&state.pool, &account, since)
+        .await
+        .map_err(|e| {
+            warn!(error = %e, "Failed to load usage summary");
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let total_cost_usd: f64 = totals.iter().map(|t| t.cost_usd).sum();
+
+    Ok(Json(serde_json::json!({
+        "account": account,
+        "since": since,
+        "totals": totals,
+        "total_cost_usd": total_cost_usd,
+    })))
+}
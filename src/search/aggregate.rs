@@ -0,0 +1,271 @@
+//! Multi-engine search aggregator
+//!
+//! Fans a query out to the selected engines and merges the results: Google
+//! Scholar and Google Light through SerpAPI (Light only when Scholar comes up
+//! short, see [`SerpApiClient::search_combined`]), plus PubMed and Semantic
+//! Scholar, whose papers are appended to the Scholar results with duplicates
+//! (same DOI or title) dropped. SerpAPI engines are skipped once the SerpAPI
+//! quota is spent; the other engines are free. With `search.mock_enabled` the
+//! offline [`MockSearchEngine`] replaces them all. Results scoring below
+//! `search.min_source_score` ([`quality`]) are dropped. [`merge_results`]
+//! combines the results of several queries for the same question.
+
+use std::collections::HashSet;
+
+use super::mock::MockSearchEngine;
+use super::pubmed::PubMedClient;
+use super::quality;
+use super::semantic_scholar::SemanticScholarClient;
+use super::serpapi::{CombinedSearchResults, LightResult, ScholarResult, SearchError, SerpApiClient};
+use crate::config::{Config, SearchConfig};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Engine {
+    Scholar,
+    Light,
+    PubMed,
+    SemanticScholar,
+    /// Offline fixtures; never part of [`Engine::ALL`]
+    Mock,
+}
+
+impl Engine {
+    pub const ALL: [Engine; 4] = [Engine::Scholar, Engine::Light, Engine::PubMed, Engine::SemanticScholar];
+
+    /// Name used in settings, env vars and on the command line
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Engine::Scholar => "scholar",
+            Engine::Light => "light",
+            Engine::PubMed => "pubmed",
+            Engine::SemanticScholar => "semantic_scholar",
+            Engine::Mock => "mock",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_lowercase().replace('-', "_");
+        Self::ALL.into_iter().chain([Engine::Mock]).find(|e| e.as_str() == name)
+    }
+
+    /// Parse a comma-separated engine list
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, String> {
+        let mut engines = Vec::new();
+        for name in list.split(',').filter(|s| !s.trim().is_empty()) {
+            let engine = Self::parse(name).ok_or_else(|| {
+                format!(
+                    "Unknown search engine '{}' (expected one of: {})",
+                    name.trim(),
+                    Self::ALL.map(|e| e.as_str()).join(", ")
+                )
+            })?;
+            if !engines.contains(&engine) {
+                engines.push(engine);
+            }
+        }
+        if engines.is_empty() {
+            return Err("No search engines given".to_string());
+        }
+        Ok(engines)
+    }
+
+    /// Engines that are switched on and usable with the configured
+    /// credentials; only the mock engine when it is enabled
+    pub fn enabled(config: &SearchConfig) -> Vec<Self> {
+        if config.mock_enabled {
+            return vec![Engine::Mock];
+        }
+        let has_serpapi = !config.serpapi_key.is_empty();
+        Self::ALL
+            .into_iter()
+            .filter(|engine| match engine {
+                Engine::Scholar => has_serpapi && config.scholar_enabled,
+                Engine::Light => has_serpapi && config.light_enabled,
+                Engine::PubMed => config.pubmed_enabled,
+                Engine::SemanticScholar => config.semantic_scholar_enabled,
+                Engine::Mock => false,
+            })
+            .collect()
+    }
+}
+
+pub struct SearchAggregator<'a> {
+    config: &'a Config,
+}
+
+impl<'a> SearchAggregator<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self { config }
+    }
+
+    /// Search `engines` for `query`. Engine failures are reported in
+    /// `errors` rather than failing the whole search.
+    pub async fn search(&self, query: &str, engines: &[Engine]) -> CombinedSearchResults {
+        let search = &self.config.search;
+        let network = &self.config.network;
+
+        let serpapi = async {
+            let scholar = engines.contains(&Engine::Scholar);
+            let light = engines.contains(&Engine::Light);
+            if !scholar && !light {
+                return None;
+            }
+            if let Some(reason) = crate::budget::search_exceeded(&self.config.budget) {
+                return Some(Err(SearchError::RequestFailed(format!("SerpAPI quota exceeded ({})", reason))));
+            }
+            let Some(client) = SerpApiClient::from_config(search) else {
+                return Some(Err(SearchError::NoApiKey));
+            };
+            let results = client
+                .with_http_client(crate::utils::http::client(network, "serpapi"))
+                .with_scholar(scholar)
+                .with_light(light)
+                .search_combined(query)
+                .await;
+            crate::budget::record_search(results.queries);
+            Some(Ok(results))
+        };
+        let pubmed = async {
+            if !engines.contains(&Engine::PubMed) {
+                return None;
+            }
+            let client = PubMedClient::from_config(search)
+                .with_http_client(crate::utils::http::client(network, "pubmed"));
+            Some(client.search(query).await)
+        };
+        let semantic_scholar = async {
+            if !engines.contains(&Engine::SemanticScholar) {
+                return None;
+            }
+            let client = SemanticScholarClient::from_config(search)
+                .with_http_client(crate::utils::http::client(network, "semantic_scholar"));
+            Some(client.search(query).await)
+        };
+        let (serpapi, pubmed, semantic_scholar) = tokio::join!(serpapi, pubmed, semantic_scholar);
+        let mock = engines
+            .contains(&Engine::Mock)
+            .then(|| Ok(MockSearchEngine::from_config(search).search(query)));
+
+        let mut combined = match serpapi {
+            Some(Ok(results)) => results,
+            Some(Err(e)) => {
+                warn!(error = %e, "SerpAPI search skipped");
+                CombinedSearchResults {
+                    errors: vec![format!("SerpAPI: {}", e)],
+                    ..Default::default()
+                }
+            }
+            None => CombinedSearchResults::default(),
+        };
+
+        for (name, outcome) in [("PubMed", pubmed), ("Semantic Scholar", semantic_scholar), ("Mock", mock)] {
+            match outcome {
+                Some(Ok(papers)) => merge_papers(&mut combined.scholar_results, papers),
+                Some(Err(e)) => {
+                    warn!(engine = name, error = %e, "Search failed");
+                    combined.errors.push(format!("{}: {}", name, e));
+                }
+                None => {}
+            }
+        }
+
+        quality::retain(&mut combined, search.min_source_score);
+        combined
+    }
+}
+
+/// Add the results of another query to `combined`, dropping papers already
+/// found (same DOI or title) and web results with a link already found
+pub fn merge_results(combined: &mut CombinedSearchResults, other: CombinedSearchResults) {
+    merge_papers(&mut combined.scholar_results, other.scholar_results);
+    merge_pages(&mut combined.light_results, other.light_results);
+    combined.scholar_success |= other.scholar_success;
+    combined.light_success |= other.light_success;
+    combined.errors.extend(other.errors);
+    combined.queries += other.queries;
+}
+
+/// Append `pages` to `results`, skipping links already present
+fn merge_pages(results: &mut Vec<LightResult>, pages: Vec<LightResult>) {
+    let mut seen: HashSet<String> = results.iter().map(|r| r.link.clone()).collect();
+    for page in pages {
+        if seen.insert(page.link.clone()) {
+            results.push(page);
+        }
+    }
+}
+
+/// Append `papers` to `results`, skipping any already present
+fn merge_papers(results: &mut Vec<ScholarResult>, papers: Vec<ScholarResult>) {
+    let mut seen: HashSet<String> = results.iter().flat_map(identity_keys).collect();
+    for paper in papers {
+        let keys = identity_keys(&paper);
+        if keys.iter().any(|k| seen.contains(k)) {
+            continue;
+        }
+        seen.extend(keys);
+        results.push(paper);
+    }
+}
+
+fn identity_keys(paper: &ScholarResult) -> Vec<String> {
+    let mut keys = Vec::with_capacity(2);
+    if let Some(doi) = &paper.doi {
+        keys.push(format!("doi:{}", doi.to_ascii_lowercase()));
+    }
+    let title: String = paper
+        .title
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    if !title.is_empty() {
+        keys.push(format!("title:{}", title));
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paper(title: &str, doi: Option<&str>) -> ScholarResult {
+        ScholarResult {
+            title: title.to_string(),
+            authors: None,
+            year: None,
+            snippet: String::new(),
+            link: None,
+            citations: None,
+            doi: doi.map(str::to_string),
+            pdf_link: None,
+            publication: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            Engine::parse_list("scholar, PubMed,semantic-scholar,scholar").unwrap(),
+            vec![Engine::Scholar, Engine::PubMed, Engine::SemanticScholar]
+        );
+        assert!(Engine::parse_list("scholar,arxiv").unwrap_err().contains("arxiv"));
+        assert!(Engine::parse_list(" ").is_err());
+    }
+
+    #[test]
+    fn test_merge_drops_duplicates() {
+        let mut results = vec![paper("Hallmarks of Aging", Some("10.1016/j.cell.2013.05.039"))];
+        merge_papers(
+            &mut results,
+            vec![
+                paper("Hallmarks of aging: an expanding universe", Some("10.1016/J.CELL.2013.05.039")),
+                paper("Hallmarks of aging.", None),
+                paper("Epigenetic clocks", None),
+            ],
+        );
+        let titles: Vec<_> = results.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, ["Hallmarks of Aging", "Epigenetic clocks"]);
+    }
+}
@@ -0,0 +1,203 @@
+//! BibTeX formatting of search results and literature sources
+//!
+//! Papers become `@article` entries, web results `@misc` entries with a URL.
+//! Cite keys follow the usual `surnameYEARword` pattern and are made unique
+//! within a bibliography.
+
+use std::collections::HashSet;
+
+use super::serpapi::{CombinedSearchResults, LightResult, ScholarResult};
+use crate::agents::SourceReference;
+
+/// Title words not used in cite keys
+const STOP_WORDS: &[&str] = &["a", "an", "the", "of", "on", "in", "for", "and", "to", "with", "from", "by"];
+
+/// Allocates unique cite keys
+#[derive(Debug, Default)]
+pub struct CiteKeys {
+    used: HashSet<String>,
+}
+
+impl CiteKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Unique key such as `horvath2018epigenetic`
+    pub fn key(&mut self, authors: Option<&str>, year: Option<i32>, title: &str) -> String {
+        let surname = authors.and_then(first_surname).unwrap_or_else(|| "anon".to_string());
+        let year = year.map(|y| y.to_string()).unwrap_or_default();
+        let word = title
+            .split(|c: char| !c.is_alphanumeric())
+            .map(|w| w.to_lowercase())
+            .find(|w| !w.is_empty() && !STOP_WORDS.contains(&w.as_str()))
+            .unwrap_or_default();
+        let base: String = format!("{}{}{}", surname, year, word)
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+
+        let mut key = base.clone();
+        let mut suffix = b'a';
+        while !self.used.insert(key.clone()) {
+            key = format!("{}{}", base, suffix as char);
+            suffix = suffix.saturating_add(1);
+        }
+        key
+    }
+}
+
+/// Surname of the first author in "S Horvath, K Raj", "Horvath S, Raj K" or
+/// "Steve Horvath and Ken Raj"; initials are skipped.
+pub fn first_surname(authors: &str) -> Option<String> {
+    let first = authors.split([',', ';']).next()?.split(" and ").next()?;
+    let is_initials = |t: &str| t.len() <= 3 && t.chars().all(|c| c.is_uppercase() || c == '.');
+    first
+        .split_whitespace()
+        .rev()
+        .find(|t| !is_initials(t))
+        .map(|t| t.to_lowercase())
+}
+
+/// Escape characters with special meaning in BibTeX/LaTeX field values
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '{' | '}' => {}
+            '~' => out.push_str("\\textasciitilde{}"),
+            '^' => out.push_str("\\textasciicircum{}"),
+            '\\' => out.push_str("\\textbackslash{}"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Author list in BibTeX form ("A and B"), dropping truncation markers
+fn bib_authors(authors: &str) -> String {
+    authors
+        .split([',', ';'])
+        .map(str::trim)
+        .filter(|a| !a.is_empty() && !a.contains('…') && *a != "...")
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+fn push_field(entry: &mut String, name: &str, value: Option<&str>) {
+    if let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) {
+        entry.push_str(&format!("  {} = {{{}}},\n", name, value));
+    }
+}
+
+pub fn paper_entry(key: &str, paper: &ScholarResult) -> String {
+    let mut entry = format!("@article{{{},\n", key);
+    push_field(&mut entry, "title", Some(&format!("{{{}}}", escape(&paper.title))));
+    push_field(&mut entry, "author", paper.authors.as_deref().map(|a| escape(&bib_authors(a))).as_deref());
+    push_field(&mut entry, "journal", paper.publication.as_deref().map(escape).as_deref());
+    push_field(&mut entry, "year", paper.year.map(|y| y.to_string()).as_deref());
+    push_field(&mut entry, "doi", paper.doi.as_deref());
+    push_field(&mut entry, "url", paper.link.as_deref());
+    entry.push_str("}\n");
+    entry
+}
+
+pub fn web_entry(key: &str, result: &LightResult) -> String {
+    let mut entry = format!("@misc{{{},\n", key);
+    push_field(&mut entry, "title", Some(&format!("{{{}}}", escape(&result.title))));
+    push_field(&mut entry, "howpublished", Some(&format!("\\url{{{}}}", result.link)));
+    push_field(&mut entry, "note", result.date.as_deref().map(escape).as_deref());
+    entry.push_str("}\n");
+    entry
+}
+
+/// Entry for a literature agent source: `@article` when it has bibliographic
+/// metadata, `@misc` for bare web links
+pub fn source_entry(key: &str, source: &SourceReference) -> String {
+    let is_paper = source.doi.is_some() || source.authors.is_some() || source.year.is_some();
+    let mut entry = format!("@{}{{{},\n", if is_paper { "article" } else { "misc" }, key);
+    push_field(&mut entry, "title", Some(&format!("{{{}}}", escape(&source.title))));
+    push_field(&mut entry, "author", source.authors.as_deref().map(|a| escape(&bib_authors(a))).as_deref());
+    push_field(&mut entry, "year", source.year.map(|y| y.to_string()).as_deref());
+    push_field(&mut entry, "doi", source.doi.as_deref());
+    if is_paper {
+        push_field(&mut entry, "url", source.url.as_deref());
+    } else {
+        push_field(&mut entry, "howpublished", source.url.as_deref().map(|u| format!("\\url{{{}}}", u)).as_deref());
+    }
+    entry.push_str("}\n");
+    entry
+}
+
+/// Bibliography for every paper and web result
+pub fn bibliography(results: &CombinedSearchResults) -> String {
+    let mut keys = CiteKeys::new();
+    let mut entries: Vec<String> = results
+        .scholar_results
+        .iter()
+        .map(|p| paper_entry(&keys.key(p.authors.as_deref(), p.year, &p.title), p))
+        .collect();
+    entries.extend(
+        results
+            .light_results
+            .iter()
+            .map(|r| web_entry(&keys.key(r.source.as_deref(), None, &r.title), r)),
+    );
+    entries.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cite_keys() {
+        let mut keys = CiteKeys::new();
+        assert_eq!(keys.key(Some("Horvath S, Raj K"), Some(2018), "Epigenetic clocks"), "horvath2018epigenetic");
+        assert_eq!(keys.key(Some("S Horvath, K Raj"), Some(2018), "The epigenetic clock"), "horvath2018epigenetica");
+        assert_eq!(keys.key(Some("Ming Xu"), None, "Senolytics"), "xusenolytics");
+        assert_eq!(keys.key(None, Some(2020), "A review"), "anon2020review");
+    }
+
+    #[test]
+    fn test_paper_entry() {
+        let paper = ScholarResult {
+            title: "DNA methylation & aging".to_string(),
+            authors: Some("S Horvath, K Raj, …".to_string()),
+            year: Some(2018),
+            snippet: String::new(),
+            link: None,
+            citations: None,
+            doi: Some("10.1038/s41576-018-0004-3".to_string()),
+            pdf_link: None,
+            publication: Some("Nat Rev Genet".to_string()),
+        };
+        let entry = paper_entry("horvath2018dna", &paper);
+        assert!(entry.starts_with("@article{horvath2018dna,\n"));
+        assert!(entry.contains("title = {{DNA methylation \\& aging}},"));
+        assert!(entry.contains("author = {S Horvath and K Raj},"));
+        assert!(entry.contains("doi = {10.1038/s41576-018-0004-3},"));
+        assert!(!entry.contains("url ="));
+    }
+
+    #[test]
+    fn test_source_entry_for_web_link() {
+        let source = SourceReference {
+            title: "Senolytics_overview".to_string(),
+            authors: None,
+            year: None,
+            doi: None,
+            url: Some("https://example.org/senolytics".to_string()),
+            summary: String::new(),
+            quality: None,
+        };
+        let entry = source_entry("anonsenolyticsoverview", &source);
+        assert!(entry.starts_with("@misc{anonsenolyticsoverview,\n"));
+        assert!(entry.contains("title = {{Senolytics\\_overview}},"));
+        assert!(entry.contains("howpublished = {\\url{https://example.org/senolytics}},"));
+    }
+}
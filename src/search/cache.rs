@@ -0,0 +1,60 @@
+//! On-disk cache of SerpAPI results
+//!
+//! Successful searches are stored by query so the literature agent can still
+//! answer from earlier results once the SerpAPI quota is used up.
+
+use super::serpapi::CombinedSearchResults;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// `<data dir>/oxidized-bio/search_cache`
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("oxidized-bio").join("search_cache"))
+}
+
+/// Cache file name for a query; case and whitespace differences share a file
+fn cache_key(query: &str) -> String {
+    let normalized = query
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let digest = Sha256::digest(normalized.as_bytes());
+    format!("{:x}.json", digest)
+}
+
+/// Previously cached results for `query`, if any
+pub fn load(query: &str) -> Option<CombinedSearchResults> {
+    let path = cache_dir()?.join(cache_key(query));
+    let json = std::fs::read_to_string(&path).ok()?;
+    let results = serde_json::from_str(&json).ok()?;
+    debug!(path = %path.display(), "Loaded cached search results");
+    Some(results)
+}
+
+/// Cache results that contain at least one hit
+pub fn store(query: &str, results: &CombinedSearchResults) {
+    if results.scholar_results.is_empty() && results.light_results.is_empty() {
+        return;
+    }
+    let Some(dir) = cache_dir() else { return };
+    let result = std::fs::create_dir_all(&dir).and_then(|_| {
+        let json = serde_json::to_string(results).unwrap_or_default();
+        std::fs::write(dir.join(cache_key(query)), json)
+    });
+    if let Err(e) = result {
+        warn!(error = %e, "Failed to cache search results");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_normalizes_query() {
+        assert_eq!(cache_key("IL-6  Sepsis"), cache_key("il-6 sepsis"));
+        assert_ne!(cache_key("il-6 sepsis"), cache_key("il-8 sepsis"));
+    }
+}
@@ -3,9 +3,23 @@
 //! Provides scientific literature search capabilities using multiple APIs:
 //! - Google Scholar (primary) - Academic papers and citations
 //! - Google Light (secondary) - General web search for supplementary info
+//! - PubMed (NCBI E-utilities) - Biomedical literature
+//! - Semantic Scholar - Papers with abstracts and citation counts
 //!
-//! Uses SerpAPI as the backend for both search engines.
+//! Google Scholar and Light use SerpAPI as the backend. [`aggregate`] queries
+//! any mix of engines and merges the results. SerpAPI results are cached on
+//! disk so searches keep working offline once the SerpAPI quota is spent.
+//! [`mock`] stands in for all of them in tests and offline demos, and
+//! [`quality`] scores every result.
 
+pub mod aggregate;
+pub mod bibtex;
+pub mod cache;
+pub mod mock;
+pub mod pubmed;
+pub mod quality;
+pub mod semantic_scholar;
 pub mod serpapi;
 
+pub use aggregate::{Engine, SearchAggregator};
 pub use serpapi::{SerpApiClient, ScholarResult, LightResult, SearchError};
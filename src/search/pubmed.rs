@@ -0,0 +1,247 @@
+//! PubMed client (NCBI E-utilities)
+//!
+//! `esearch` finds matching PMIDs, `esummary` fetches their citation data.
+//! No key is required; with `NCBI_API_KEY` the rate limit rises from 3 to 10
+//! requests per second. Results are returned as [`ScholarResult`]s so they
+//! merge with Google Scholar hits.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::info;
+
+use super::serpapi::{ScholarResult, SearchError};
+
+const EUTILS_BASE: &str = "https://eutils.ncbi.nlm.nih.gov/entrez/eutils";
+const TOOL_NAME: &str = "oxidized-bio";
+
+pub struct PubMedClient {
+    http: reqwest::Client,
+    api_key: Option<String>,
+    email: Option<String>,
+    max_results: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct ESearchResponse {
+    esearchresult: ESearchResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct ESearchResult {
+    #[serde(default)]
+    idlist: Vec<String>,
+    /// Total matches, as a string
+    #[serde(default)]
+    count: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ESummaryAuthor {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ESummaryArticleId {
+    idtype: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ESummaryDoc {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    authors: Vec<ESummaryAuthor>,
+    #[serde(default)]
+    pubdate: String,
+    #[serde(default)]
+    fulljournalname: String,
+    #[serde(default)]
+    source: String,
+    #[serde(default)]
+    articleids: Vec<ESummaryArticleId>,
+}
+
+impl PubMedClient {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_key: None,
+            email: None,
+            max_results: 10,
+        }
+    }
+
+    pub fn from_config(config: &crate::config::SearchConfig) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_key: Some(config.ncbi_api_key.clone()).filter(|k| !k.is_empty()),
+            email: Some(config.ncbi_email.clone()).filter(|e| !e.is_empty()),
+            max_results: config.max_results_for("pubmed"),
+        }
+    }
+
+    /// Use a preconfigured HTTP client (proxy, custom CA)
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn with_max_results(mut self, max: usize) -> Self {
+        self.max_results = max;
+        self
+    }
+
+    async fn fetch(&self, util: &str, mut params: HashMap<&str, String>) -> Result<serde_json::Value, SearchError> {
+        params.insert("db", "pubmed".to_string());
+        params.insert("retmode", "json".to_string());
+        params.insert("tool", TOOL_NAME.to_string());
+        if let Some(key) = &self.api_key {
+            params.insert("api_key", key.clone());
+        }
+        if let Some(email) = &self.email {
+            params.insert("email", email.clone());
+        }
+
+        let response = self
+            .http
+            .get(format!("{}/{}.fcgi", EUTILS_BASE, util))
+            .query(&params)
+            .send()
+            .await
+            .map_err(|e| SearchError::RequestFailed(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(SearchError::RequestFailed(format!("HTTP {}: {}", status, body)));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| SearchError::ParseError(e.to_string()))
+    }
+
+    /// Search PubMed, most relevant first
+    pub async fn search(&self, query: &str) -> Result<Vec<ScholarResult>, SearchError> {
+        info!(query = %query, "Searching PubMed");
+
+        let search = self
+            .fetch(
+                "esearch",
+                HashMap::from([
+                    ("term", query.to_string()),
+                    ("retmax", self.max_results.to_string()),
+                    ("sort", "relevance".to_string()),
+                ]),
+            )
+            .await?;
+        let ids = serde_json::from_value::<ESearchResponse>(search)
+            .map_err(|e| SearchError::ParseError(e.to_string()))?
+            .esearchresult
+            .idlist;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let summary = self
+            .fetch("esummary", HashMap::from([("id", ids.join(","))]))
+            .await?;
+        Ok(parse_summary(&summary, &ids))
+    }
+
+    /// Number of PubMed records matching `query`, without fetching them
+    pub async fn count(&self, query: &str) -> Result<usize, SearchError> {
+        let search = self
+            .fetch(
+                "esearch",
+                HashMap::from([("term", query.to_string()), ("retmax", "0".to_string())]),
+            )
+            .await?;
+        let count = serde_json::from_value::<ESearchResponse>(search)
+            .map_err(|e| SearchError::ParseError(e.to_string()))?
+            .esearchresult
+            .count
+            .ok_or_else(|| SearchError::ParseError("esearch response has no count".to_string()))?;
+        count
+            .parse()
+            .map_err(|_| SearchError::ParseError(format!("Invalid esearch count '{}'", count)))
+    }
+}
+
+impl Default for PubMedClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert an `esummary` response into results, keeping `ids` order
+fn parse_summary(summary: &serde_json::Value, ids: &[String]) -> Vec<ScholarResult> {
+    let Some(result) = summary.get("result") else {
+        return Vec::new();
+    };
+    ids.iter()
+        .filter_map(|pmid| {
+            let doc: ESummaryDoc = serde_json::from_value(result.get(pmid)?.clone()).ok()?;
+            if doc.title.is_empty() {
+                return None;
+            }
+            let authors = doc
+                .authors
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let doi = doc
+                .articleids
+                .iter()
+                .find(|id| id.idtype == "doi")
+                .map(|id| id.value.clone());
+            let journal = if doc.fulljournalname.is_empty() { doc.source } else { doc.fulljournalname };
+            Some(ScholarResult {
+                title: doc.title.trim_end_matches('.').to_string(),
+                authors: Some(authors).filter(|a| !a.is_empty()),
+                year: doc.pubdate.get(..4).and_then(|y| y.parse().ok()),
+                snippet: String::new(),
+                link: Some(format!("https://pubmed.ncbi.nlm.nih.gov/{}/", pmid)),
+                citations: None,
+                doi,
+                pdf_link: None,
+                publication: Some(journal).filter(|j| !j.is_empty()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary() {
+        let summary = serde_json::json!({
+            "result": {
+                "uids": ["111", "222"],
+                "111": {
+                    "title": "Epigenetic clocks of aging.",
+                    "authors": [{"name": "Horvath S"}, {"name": "Raj K"}],
+                    "pubdate": "2018 Jun",
+                    "fulljournalname": "Nature reviews. Genetics",
+                    "source": "Nat Rev Genet",
+                    "articleids": [{"idtype": "pubmed", "value": "111"}, {"idtype": "doi", "value": "10.1038/s41576-018-0004-3"}]
+                },
+                "222": {"title": "", "pubdate": "2020"}
+            }
+        });
+        let results = parse_summary(&summary, &["111".to_string(), "222".to_string(), "333".to_string()]);
+        assert_eq!(results.len(), 1);
+        let paper = &results[0];
+        assert_eq!(paper.title, "Epigenetic clocks of aging");
+        assert_eq!(paper.authors.as_deref(), Some("Horvath S, Raj K"));
+        assert_eq!(paper.year, Some(2018));
+        assert_eq!(paper.doi.as_deref(), Some("10.1038/s41576-018-0004-3"));
+        assert_eq!(paper.publication.as_deref(), Some("Nature reviews. Genetics"));
+        assert_eq!(paper.link.as_deref(), Some("https://pubmed.ncbi.nlm.nih.gov/111/"));
+    }
+}
@@ -0,0 +1,269 @@
+//! Source quality scores
+//!
+//! Every search result gets a score between 0 and 1 from four signals:
+//!
+//! - **Journal tier** (40%): leading journals, established publishers and
+//!   preprint servers, other reliable sites (government, universities,
+//!   databases, Wikipedia), any other named venue, and unlisted web pages
+//! - **Citations** (30%): log-scaled, full marks at 1000 citations
+//! - **Peer review** (15%): none for preprints, half when unknown
+//! - **Recency** (15%): full marks up to 5 years old, none past 30, half
+//!   when the year is unknown
+//!
+//! The aggregator drops results below `search.min_source_score`; the score
+//! is kept with the literature agent's sources and shown next to them.
+
+use chrono::Datelike;
+
+use super::serpapi::{CombinedSearchResults, LightResult, ScholarResult};
+
+/// Default of `search.min_source_score`: keeps any named venue, drops
+/// unlisted web pages
+pub const DEFAULT_MIN_SCORE: f64 = 0.25;
+
+const TIER_WEIGHT: f64 = 0.4;
+const CITATION_WEIGHT: f64 = 0.3;
+const PEER_REVIEW_WEIGHT: f64 = 0.15;
+const RECENCY_WEIGHT: f64 = 0.15;
+
+/// Citations earning full marks
+const FULL_CITATIONS: f64 = 1000.0;
+
+/// Age in years up to which a source counts as current, and past which it
+/// gets no recency marks
+const CURRENT_YEARS: i32 = 5;
+const STALE_YEARS: i32 = 30;
+
+/// Leading journals, matched at the start of the venue name
+const TOP_JOURNALS: [&str; 9] = [
+    "nature",
+    "science",
+    "cell",
+    "the lancet",
+    "lancet",
+    "new england journal of medicine",
+    "n engl j med",
+    "jama",
+    "bmj",
+];
+
+const TOP_DOMAINS: [&str; 7] = [
+    "nature.com",
+    "science.org",
+    "cell.com",
+    "thelancet.com",
+    "nejm.org",
+    "jamanetwork.com",
+    "bmj.com",
+];
+
+/// Academic publishers and bibliographic databases
+const ESTABLISHED_DOMAINS: [&str; 13] = [
+    "ncbi.nlm.nih.gov",
+    "doi.org",
+    "sciencedirect.com",
+    "springer.com",
+    "wiley.com",
+    "plos.org",
+    "frontiersin.org",
+    "mdpi.com",
+    "biomedcentral.com",
+    "academic.oup.com",
+    "pnas.org",
+    "elifesciences.org",
+    "semanticscholar.org",
+];
+
+const PREPRINT_DOMAINS: [&str; 5] = ["biorxiv.org", "medrxiv.org", "arxiv.org", "ssrn.com", "researchsquare.com"];
+
+const PREPRINT_VENUES: [&str; 6] = ["biorxiv", "medrxiv", "arxiv", "ssrn", "research square", "preprint"];
+
+/// Government, academic and database sites: reliable, not peer reviewed
+const RELIABLE_DOMAINS: [&str; 8] = [
+    ".gov",
+    ".edu",
+    ".ac.uk",
+    "who.int",
+    "uniprot.org",
+    "ensembl.org",
+    "genecards.org",
+    "wikipedia.org",
+];
+
+/// Standing of the journal or site a source comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    Top,
+    /// Established publishers, bibliographic databases and preprint servers
+    Established,
+    /// Government, academic and database sites
+    Reliable,
+    /// A named venue on none of the lists
+    Journal,
+    Unlisted,
+}
+
+impl Tier {
+    /// Tier of a source published in `venue` at `url`, the better of the two
+    pub fn of(venue: Option<&str>, url: Option<&str>) -> Self {
+        let venue = venue.map(venue_name).filter(|v| !v.is_empty());
+        let url = url.map(str::to_lowercase);
+        let host = url.as_deref().map(host).unwrap_or_default();
+        let on = |domains: &[&str]| domains.iter().any(|d| on_domain(&host, d));
+        if venue.as_deref().is_some_and(is_top_journal) || on(&TOP_DOMAINS) {
+            Tier::Top
+        } else if on(&ESTABLISHED_DOMAINS) || on(&PREPRINT_DOMAINS) {
+            Tier::Established
+        } else if on(&RELIABLE_DOMAINS) {
+            Tier::Reliable
+        } else if venue.is_some() {
+            Tier::Journal
+        } else {
+            Tier::Unlisted
+        }
+    }
+
+    fn weight(self) -> f64 {
+        match self {
+            Tier::Top => 1.0,
+            Tier::Established => 0.75,
+            Tier::Reliable => 0.5,
+            Tier::Journal => 0.25,
+            Tier::Unlisted => 0.0,
+        }
+    }
+}
+
+/// Score of a source from its venue, URL, citation count and publication
+/// year
+pub fn score(venue: Option<&str>, url: Option<&str>, citations: Option<i32>, year: Option<i32>) -> f64 {
+    score_in(venue, url, citations, year, chrono::Utc::now().year())
+}
+
+fn score_in(venue: Option<&str>, url: Option<&str>, citations: Option<i32>, year: Option<i32>, this_year: i32) -> f64 {
+    let tier = Tier::of(venue, url);
+    let citations = citations
+        .map(|c| ((1.0 + c.max(0) as f64).log10() / (1.0 + FULL_CITATIONS).log10()).min(1.0))
+        .unwrap_or(0.0);
+    let peer_review = if is_preprint(venue, url) {
+        0.0
+    } else if matches!(tier, Tier::Top | Tier::Established | Tier::Journal) {
+        1.0
+    } else {
+        0.5
+    };
+    let recency = match year {
+        Some(year) => {
+            let age = (this_year - year).max(0);
+            if age <= CURRENT_YEARS {
+                1.0
+            } else {
+                (STALE_YEARS - age).max(0) as f64 / (STALE_YEARS - CURRENT_YEARS) as f64
+            }
+        }
+        None => 0.5,
+    };
+    TIER_WEIGHT * tier.weight()
+        + CITATION_WEIGHT * citations
+        + PEER_REVIEW_WEIGHT * peer_review
+        + RECENCY_WEIGHT * recency
+}
+
+pub fn paper_score(paper: &ScholarResult) -> f64 {
+    score(paper.publication.as_deref(), paper.link.as_deref(), paper.citations, paper.year)
+}
+
+pub fn page_score(page: &LightResult) -> f64 {
+    score(None, Some(&page.link), None, page.date.as_deref().and_then(find_year))
+}
+
+/// Drop the results scoring below `min_score`
+pub fn retain(results: &mut CombinedSearchResults, min_score: f64) {
+    results.scholar_results.retain(|paper| paper_score(paper) >= min_score);
+    results.light_results.retain(|page| page_score(page) >= min_score);
+}
+
+/// Whether a source in `venue` at `url` is a preprint
+pub fn is_preprint(venue: Option<&str>, url: Option<&str>) -> bool {
+    let venue = venue.map(str::to_lowercase).unwrap_or_default();
+    let host = url.map(|u| host(&u.to_lowercase())).unwrap_or_default();
+    PREPRINT_VENUES.iter().any(|p| venue.contains(p)) || PREPRINT_DOMAINS.iter().any(|d| on_domain(&host, d))
+}
+
+/// Whether `host` is `domain` or one of its subdomains; `.gov` matches any
+/// host under the suffix
+fn on_domain(host: &str, domain: &str) -> bool {
+    let domain = domain.trim_start_matches('.');
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// Venue without its publisher or year ("Genome biology, 2013" or
+/// "S Horvath - Genome biology, 2013 - Springer"), lower-cased with
+/// punctuation removed
+fn venue_name(venue: &str) -> String {
+    let parts: Vec<&str> = venue.split(" - ").collect();
+    let venue = if parts.len() > 1 { parts[1] } else { parts[0] };
+    let venue = venue.split(',').next().unwrap_or(venue);
+    venue
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// "Nature aging" and "Cell reports" are top journals, "Cellular
+/// signalling" and "Journal of food science" are not
+fn is_top_journal(venue: &str) -> bool {
+    TOP_JOURNALS
+        .iter()
+        .any(|journal| venue == *journal || venue.starts_with(&format!("{} ", journal)))
+}
+
+fn host(url: &str) -> String {
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    rest.split(['/', '?', '#']).next().unwrap_or(rest).to_string()
+}
+
+fn find_year(text: &str) -> Option<i32> {
+    text.split(|c: char| !c.is_ascii_digit())
+        .filter(|part| part.len() == 4)
+        .filter_map(|part| part.parse().ok())
+        .find(|year| (1900..=2100).contains(year))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_scores() {
+        assert_eq!(Tier::of(Some("Nature aging"), None), Tier::Top);
+        assert_eq!(Tier::of(Some("S Horvath - Cell reports, 2019 - cell.com"), None), Tier::Top);
+        assert_eq!(Tier::of(Some("Cellular signalling"), None), Tier::Journal);
+        assert_eq!(Tier::of(Some("Journal of food science"), None), Tier::Journal);
+        assert_eq!(Tier::of(None, Some("https://www.nature.com/articles/example")), Tier::Top);
+        assert_eq!(Tier::of(None, Some("https://pubmed.ncbi.nlm.nih.gov/12345")), Tier::Established);
+        assert_eq!(Tier::of(None, Some("https://www.biorxiv.org/content/1")), Tier::Established);
+        assert_eq!(Tier::of(None, Some("https://www.harvard.edu/research")), Tier::Reliable);
+        assert_eq!(Tier::of(None, Some("https://random-blog.com/health?ref=nature.com")), Tier::Unlisted);
+
+        // Highly cited recent paper in a top journal
+        let top = score_in(Some("Nature"), None, Some(1500), Some(2024), 2026);
+        assert!((top - 1.0).abs() < 1e-12, "{}", top);
+        // The same work as an uncited preprint
+        let preprint = score_in(Some("bioRxiv"), Some("https://www.biorxiv.org/x"), Some(0), Some(2024), 2026);
+        assert!((preprint - (0.4 * 0.75 + 0.15)).abs() < 1e-12, "{}", preprint);
+        assert!(is_preprint(None, Some("https://arxiv.org/abs/1")));
+        // Older sources lose recency marks linearly
+        let old = score_in(Some("Aging cell"), None, None, Some(2006), 2026);
+        assert!((old - (0.4 * 0.25 + 0.15 + 0.15 * 0.4)).abs() < 1e-12, "{}", old);
+
+        // Reliable sites pass the default minimum, blogs do not
+        let gov = score_in(None, Some("https://www.nia.nih.gov/health"), None, None, 2026);
+        let blog = score_in(None, Some("https://random-blog.com/health"), None, None, 2026);
+        assert!(gov >= DEFAULT_MIN_SCORE && blog < DEFAULT_MIN_SCORE, "{} {}", gov, blog);
+        assert_eq!(find_year("Mar 3, 2021"), Some(2021));
+    }
+}
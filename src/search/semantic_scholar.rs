@@ -0,0 +1,181 @@
+//! Semantic Scholar client (Academic Graph API)
+//!
+//! Works without a key at a shared, low rate limit; `SEMANTIC_SCHOLAR_API_KEY`
+//! raises it. Results carry abstracts and citation counts and are returned as
+//! [`ScholarResult`]s so they merge with Google Scholar hits.
+
+use serde::Deserialize;
+use tracing::info;
+
+use super::serpapi::{ScholarResult, SearchError};
+
+const SEARCH_ENDPOINT: &str = "https://api.semanticscholar.org/graph/v1/paper/search";
+const FIELDS: &str = "title,authors,year,abstract,url,citationCount,externalIds,venue,openAccessPdf";
+
+pub struct SemanticScholarClient {
+    http: reqwest::Client,
+    api_key: Option<String>,
+    max_results: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    #[serde(default)]
+    data: Vec<Paper>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Paper {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    authors: Vec<Author>,
+    year: Option<i32>,
+    #[serde(rename = "abstract")]
+    abstract_text: Option<String>,
+    url: Option<String>,
+    citation_count: Option<i32>,
+    external_ids: Option<ExternalIds>,
+    venue: Option<String>,
+    open_access_pdf: Option<OpenAccessPdf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Author {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExternalIds {
+    #[serde(rename = "DOI")]
+    doi: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAccessPdf {
+    url: Option<String>,
+}
+
+impl From<Paper> for ScholarResult {
+    fn from(paper: Paper) -> Self {
+        let authors = paper
+            .authors
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        ScholarResult {
+            title: paper.title,
+            authors: Some(authors).filter(|a| !a.is_empty()),
+            year: paper.year,
+            snippet: paper.abstract_text.unwrap_or_default(),
+            link: paper.url,
+            citations: paper.citation_count,
+            doi: paper.external_ids.and_then(|ids| ids.doi),
+            pdf_link: paper.open_access_pdf.and_then(|pdf| pdf.url),
+            publication: paper.venue.filter(|v| !v.is_empty()),
+        }
+    }
+}
+
+impl SemanticScholarClient {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_key: None,
+            max_results: 10,
+        }
+    }
+
+    pub fn from_config(config: &crate::config::SearchConfig) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_key: Some(config.semantic_scholar_api_key.clone()).filter(|k| !k.is_empty()),
+            max_results: config.max_results_for("semantic_scholar"),
+        }
+    }
+
+    /// Use a preconfigured HTTP client (proxy, custom CA)
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn with_max_results(mut self, max: usize) -> Self {
+        self.max_results = max;
+        self
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<ScholarResult>, SearchError> {
+        info!(query = %query, "Searching Semantic Scholar");
+
+        // The API caps `limit` at 100
+        let limit = self.max_results.clamp(1, 100).to_string();
+        let mut request = self
+            .http
+            .get(SEARCH_ENDPOINT)
+            .query(&[("query", query), ("limit", limit.as_str()), ("fields", FIELDS)]);
+        if let Some(key) = &self.api_key {
+            request = request.header("x-api-key", key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| SearchError::RequestFailed(e.to_string()))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(SearchError::RequestFailed(format!("HTTP {}: {}", status, body)));
+        }
+
+        let parsed: SearchResponse = response
+            .json()
+            .await
+            .map_err(|e| SearchError::ParseError(e.to_string()))?;
+        Ok(parsed
+            .data
+            .into_iter()
+            .filter(|p| !p.title.is_empty())
+            .map(ScholarResult::from)
+            .collect())
+    }
+}
+
+impl Default for SemanticScholarClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paper_conversion() {
+        let response: SearchResponse = serde_json::from_value(serde_json::json!({
+            "total": 1,
+            "data": [{
+                "paperId": "abc",
+                "title": "Senolytics improve physical function",
+                "authors": [{"authorId": "1", "name": "Ming Xu"}, {"authorId": "2", "name": "Tamar Pirtskhalava"}],
+                "year": 2018,
+                "abstract": "Senescent cells accumulate with age.",
+                "url": "https://www.semanticscholar.org/paper/abc",
+                "citationCount": 1500,
+                "externalIds": {"DOI": "10.1038/s41591-018-0092-9", "PubMed": "29988130"},
+                "venue": "Nature Medicine",
+                "openAccessPdf": null
+            }]
+        }))
+        .unwrap();
+        let result = ScholarResult::from(response.data.into_iter().next().unwrap());
+        assert_eq!(result.authors.as_deref(), Some("Ming Xu, Tamar Pirtskhalava"));
+        assert_eq!(result.doi.as_deref(), Some("10.1038/s41591-018-0092-9"));
+        assert_eq!(result.citations, Some(1500));
+        assert_eq!(result.snippet, "Senescent cells accumulate with age.");
+        assert!(result.pdf_link.is_none());
+    }
+}
@@ -14,12 +14,13 @@
 //!    - Faster, lighter search for general information
 //!    - Useful when Scholar doesn't have enough results
 
-use serpapi_search_rust::serp_api_search::SerpApiSearch;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
 use tracing::{info, warn, debug};
 
+use crate::utils::retry::{CircuitOpen, RetryPolicy};
+
 /// Errors that can occur during search operations
 #[derive(Debug, Error)]
 pub enum SearchError {
@@ -28,6 +29,12 @@ pub enum SearchError {
     
     #[error("Search request failed: {0}")]
     RequestFailed(String),
+
+    #[error("Search request failed: HTTP {status}: {body}")]
+    Http { status: u16, body: String },
+
+    #[error(transparent)]
+    Unavailable(#[from] CircuitOpen),
     
     #[error("Failed to parse search results: {0}")]
     ParseError(String),
@@ -39,6 +46,18 @@ pub enum SearchError {
     EngineDisabled(String),
 }
 
+impl SearchError {
+    /// Whether retrying the request may succeed: network errors, rate
+    /// limiting and server errors
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::RequestFailed(_) => true,
+            Self::Http { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+}
+
 /// Result from a Google Scholar search
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScholarResult {
@@ -78,7 +97,7 @@ pub struct LightResult {
 }
 
 /// Combined search results from both engines
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CombinedSearchResults {
     /// Results from Google Scholar (primary)
     pub scholar_results: Vec<ScholarResult>,
@@ -90,24 +109,35 @@ pub struct CombinedSearchResults {
     pub light_success: bool,
     /// Any error messages
     pub errors: Vec<String>,
+    /// SerpAPI queries spent producing these results (counts against quotas)
+    #[serde(default)]
+    pub queries: u32,
 }
 
+const SERPAPI_ENDPOINT: &str = "https://serpapi.com/search.json";
+
 /// SerpAPI client for scientific search
 pub struct SerpApiClient {
+    http: reqwest::Client,
     api_key: String,
     scholar_enabled: bool,
     light_enabled: bool,
     max_results: usize,
+    light_max_results: usize,
+    retry: RetryPolicy,
 }
 
 impl SerpApiClient {
     /// Create a new SerpAPI client
     pub fn new(api_key: String) -> Self {
         Self {
+            http: reqwest::Client::new(),
             api_key,
             scholar_enabled: true,
             light_enabled: true,
             max_results: 10,
+            light_max_results: 10,
+            retry: RetryPolicy::for_target("serpapi"),
         }
     }
 
@@ -118,16 +148,65 @@ impl SerpApiClient {
         }
         
         Some(Self {
+            http: reqwest::Client::new(),
             api_key: config.serpapi_key.clone(),
             scholar_enabled: config.scholar_enabled,
             light_enabled: config.light_enabled,
-            max_results: config.max_results,
+            max_results: config.max_results_for("scholar"),
+            light_max_results: config.max_results_for("light"),
+            retry: RetryPolicy::for_target("serpapi"),
         })
     }
 
+    /// Use a preconfigured HTTP client (proxy, custom CA)
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Replace the retry policy for SerpAPI requests
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Run a SerpAPI search and return the raw JSON response, retrying
+    /// transient failures
+    async fn fetch(&self, mut params: HashMap<String, String>) -> Result<serde_json::Value, SearchError> {
+        params.insert("api_key".to_string(), self.api_key.clone());
+        params.insert("output".to_string(), "json".to_string());
+
+        self.retry
+            .run(|| self.fetch_once(&params), SearchError::is_transient)
+            .await
+            .map_err(|e| e.into_error())
+    }
+
+    async fn fetch_once(&self, params: &HashMap<String, String>) -> Result<serde_json::Value, SearchError> {
+        let response = self
+            .http
+            .get(SERPAPI_ENDPOINT)
+            .query(params)
+            .send()
+            .await
+            .map_err(|e| SearchError::RequestFailed(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(SearchError::Http { status: status.as_u16(), body });
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| SearchError::ParseError(e.to_string()))
+    }
+
     /// Set maximum results per search
     pub fn with_max_results(mut self, max: usize) -> Self {
         self.max_results = max;
+        self.light_max_results = max;
         self
     }
 
@@ -159,10 +238,7 @@ impl SerpApiClient {
         params.insert("hl".to_string(), "en".to_string());
         params.insert("num".to_string(), self.max_results.to_string());
 
-        let search = SerpApiSearch::google(params, self.api_key.clone());
-
-        let results = search.json().await
-            .map_err(|e| SearchError::RequestFailed(e.to_string()))?;
+        let results = self.fetch(params).await?;
 
         debug!("Raw Scholar response received");
 
@@ -280,12 +356,9 @@ impl SerpApiClient {
         params.insert("q".to_string(), query.to_string());
         params.insert("hl".to_string(), "en".to_string());
         params.insert("gl".to_string(), "us".to_string());
-        params.insert("num".to_string(), self.max_results.to_string());
+        params.insert("num".to_string(), self.light_max_results.to_string());
 
-        let search = SerpApiSearch::google(params, self.api_key.clone());
-
-        let results = search.json().await
-            .map_err(|e| SearchError::RequestFailed(e.to_string()))?;
+        let results = self.fetch(params).await?;
 
         debug!("Raw Light response received");
 
@@ -301,7 +374,7 @@ impl SerpApiClient {
         }
 
         let mut light_results = Vec::new();
-        for result in results_array.iter().take(self.max_results) {
+        for result in results_array.iter().take(self.light_max_results) {
             let title = result.get("title")
                 .and_then(|v| v.as_str())
                 .unwrap_or("Untitled")
@@ -355,10 +428,12 @@ impl SerpApiClient {
             scholar_success: false,
             light_success: false,
             errors: Vec::new(),
+            queries: 0,
         };
 
         // Try Google Scholar first (primary)
         if self.scholar_enabled {
+            combined.queries += 1;
             match self.search_scholar(query).await {
                 Ok(results) => {
                     combined.scholar_results = results;
@@ -381,14 +456,12 @@ impl SerpApiClient {
         if self.light_enabled && need_light {
             // Add scientific context to the query for better results
             let scientific_query = format!("{} research study scientific", query);
-            
+            combined.queries += 1;
+
             match self.search_light(&scientific_query).await {
                 Ok(results) => {
-                    // Filter to prefer reliable sources
-                    let filtered: Vec<_> = results.into_iter()
-                        .filter(|r| is_reliable_source(&r.link))
-                        .collect();
-                    combined.light_results = filtered;
+                    // Unreliable sites are scored out by the aggregator
+                    combined.light_results = results;
                     combined.light_success = true;
                     info!(count = combined.light_results.len(), "Light search successful");
                 }
@@ -450,47 +523,6 @@ fn extract_doi(text: &str) -> Option<String> {
     None
 }
 
-/// Check if a URL is from a reliable scientific source
-fn is_reliable_source(url: &str) -> bool {
-    let reliable_domains = [
-        // Academic publishers
-        "pubmed.ncbi.nlm.nih.gov",
-        "ncbi.nlm.nih.gov",
-        "nih.gov",
-        "nature.com",
-        "science.org",
-        "sciencedirect.com",
-        "springer.com",
-        "wiley.com",
-        "cell.com",
-        "plos.org",
-        "frontiersin.org",
-        "mdpi.com",
-        "biomedcentral.com",
-        "biorxiv.org",
-        "medrxiv.org",
-        "arxiv.org",
-        // Academic institutions
-        ".edu",
-        ".ac.uk",
-        // Government/research
-        ".gov",
-        "who.int",
-        "cdc.gov",
-        "fda.gov",
-        "clinicaltrials.gov",
-        // Databases
-        "uniprot.org",
-        "ensembl.org",
-        "genbank",
-        // Wikipedia (useful for overviews)
-        "wikipedia.org",
-    ];
-
-    let url_lower = url.to_lowercase();
-    reliable_domains.iter().any(|domain| url_lower.contains(domain))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -510,12 +542,4 @@ mod tests {
             None
         );
     }
-
-    #[test]
-    fn test_is_reliable_source() {
-        assert!(is_reliable_source("https://pubmed.ncbi.nlm.nih.gov/12345"));
-        assert!(is_reliable_source("https://www.nature.com/articles/example"));
-        assert!(is_reliable_source("https://www.harvard.edu/research"));
-        assert!(!is_reliable_source("https://random-blog.com/health"));
-    }
 }
@@ -0,0 +1,149 @@
+//! Session event bus
+//!
+//! In `--serve` mode the TUI and the embedded HTTP/RFC server share one
+//! [`AppState`](crate::models::AppState). Changes made through the server
+//! (a dataset pushed from an IDE or notebook, settings saved over HTTP) are
+//! published here so the running TUI session can pick them up. Without
+//! subscribers, events are dropped.
+//!
+//! The effective configuration (environment plus saved settings) is shared
+//! the same way through [`LiveConfig`], so a settings change from either side
+//! reaches the server's routes and runs already in progress without a
+//! restart.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::{broadcast, watch};
+
+use crate::annotation::Species;
+use crate::config::Config;
+use crate::data_registry::DatasetRecord;
+
+/// Events buffered per subscriber before the oldest are dropped
+const CAPACITY: usize = 64;
+
+/// Something changed in the shared session
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// A dataset was added to the registry from outside the TUI
+    DatasetAdded {
+        id: String,
+        filename: String,
+        rows: usize,
+        species: Species,
+        /// Where it came from, e.g. `http` or `rfc`
+        source: String,
+    },
+    /// Settings were saved from outside the TUI
+    SettingsChanged,
+}
+
+impl SessionEvent {
+    pub fn dataset_added(record: &DatasetRecord, source: &str) -> Self {
+        Self::DatasetAdded {
+            id: record.dataset.id.clone(),
+            filename: record.dataset.filename.clone(),
+            rows: record.row_count,
+            species: record.species,
+            source: source.to_string(),
+        }
+    }
+}
+
+/// Broadcasts [`SessionEvent`]s to every subscriber
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<SessionEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: SessionEvent) {
+        // No subscribers (e.g. server-only mode) is fine
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.tx.subscribe()
+    }
+}
+
+/// Current configuration, replaced whenever settings are saved. Readers take
+/// [`Self::current`] per request, or keep a [`Self::subscribe`] receiver to
+/// notice changes while they run: new API keys, models and routes, search
+/// engines and budgets apply from the next LLM call or search on.
+#[derive(Clone)]
+pub struct LiveConfig {
+    tx: Arc<watch::Sender<Arc<Config>>>,
+}
+
+impl LiveConfig {
+    pub fn new(config: Config) -> Self {
+        Self {
+            tx: Arc::new(watch::channel(Arc::new(config)).0),
+        }
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.tx.borrow().clone()
+    }
+
+    /// Replace the configuration and notify every receiver
+    pub fn publish(&self, config: Config) {
+        // Keys saved in the settings are new secrets
+        config.register_secrets();
+        self.tx.send_replace(Arc::new(config));
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Config>> {
+        self.tx.subscribe()
+    }
+}
+
+impl From<Config> for LiveConfig {
+    fn from(config: Config) -> Self {
+        Self::new(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_event_bus_delivers_to_subscribers() {
+        let bus = EventBus::default();
+        bus.publish(SessionEvent::SettingsChanged);
+
+        let mut rx = bus.subscribe();
+        bus.clone().publish(SessionEvent::SettingsChanged);
+        assert!(matches!(rx.recv().await, Ok(SessionEvent::SettingsChanged)));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_live_config_notifies_receivers() {
+        let live = LiveConfig::new(Config::from_env().unwrap());
+        let mut rx = live.subscribe();
+        assert!(!rx.has_changed().unwrap());
+
+        let mut settings = crate::settings::UserSettings::default();
+        settings.set_provider_key("groq", "gsk-test".to_string());
+        let mut config = Config::clone(&live.current());
+        settings.apply_to(&mut config);
+        live.clone().publish(config);
+
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(rx.borrow_and_update().llm.groq_api_key, "gsk-test");
+        assert_eq!(live.current().llm.default_provider, "groq");
+    }
+}
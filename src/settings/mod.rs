@@ -3,16 +3,21 @@
 //! Provides secure storage and retrieval of user settings including API keys.
 //! API keys are encrypted at rest using AES-256-GCM.
 
+pub mod connection;
 pub mod storage;
 pub mod routes;
 
 pub use storage::*;
 pub use routes::router;
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+pub use crate::config::LlmTask;
+
 /// Available LLM providers
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[serde(rename_all = "lowercase")]
 pub enum Provider {
     OpenAI,
@@ -41,6 +46,14 @@ impl std::fmt::Display for Provider {
 }
 
 impl Provider {
+    pub const ALL: [Provider; 5] = [
+        Provider::OpenAI,
+        Provider::Anthropic,
+        Provider::Google,
+        Provider::OpenRouter,
+        Provider::Groq,
+    ];
+
     pub fn from_id(id: &str) -> Option<Self> {
         match id {
             "openai" => Some(Provider::OpenAI),
@@ -71,8 +84,24 @@ fn default_true() -> bool {
     true
 }
 
+/// Where a task's LLM calls go. `model` falls back to the provider's default;
+/// unset `temperature`/`max_tokens` keep the agent's own defaults.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskRoute {
+    pub provider: Provider,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+}
+
+/// Search engine ids accepted for enable flags and result limits
+pub const SEARCH_ENGINES: [&str; 4] = ["scholar", "light", "pubmed", "semantic_scholar"];
+
 /// Search API configuration
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct SearchApiConfig {
     /// SerpAPI key for Google Scholar and Google Light searches
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -83,6 +112,311 @@ pub struct SearchApiConfig {
     /// Enable Google Light search (secondary)
     #[serde(default = "default_true")]
     pub light_enabled: bool,
+    /// NCBI E-utilities key for PubMed (raises the rate limit to 10 req/s)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ncbi_api_key: Option<String>,
+    /// Contact email NCBI asks E-utilities clients to send
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ncbi_email: Option<String>,
+    /// Enable PubMed search
+    #[serde(default = "default_true")]
+    pub pubmed_enabled: bool,
+    /// Semantic Scholar API key (optional; unauthenticated use is rate limited)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub semantic_scholar_key: Option<String>,
+    /// Enable Semantic Scholar search
+    #[serde(default = "default_true")]
+    pub semantic_scholar_enabled: bool,
+    /// Result limit per engine id (see [`SEARCH_ENGINES`])
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub max_results: BTreeMap<String, usize>,
+}
+
+impl SearchApiConfig {
+    /// Every stored search key with a display name, for encryption and masking
+    pub fn keys_mut(&mut self) -> [(&'static str, &mut Option<String>); 3] {
+        [
+            ("SerpAPI", &mut self.serpapi_key),
+            ("NCBI", &mut self.ncbi_api_key),
+            ("Semantic Scholar", &mut self.semantic_scholar_key),
+        ]
+    }
+
+    pub fn engine_enabled(&self, engine: &str) -> Option<bool> {
+        match engine {
+            "scholar" => Some(self.scholar_enabled),
+            "light" => Some(self.light_enabled),
+            "pubmed" => Some(self.pubmed_enabled),
+            "semantic_scholar" => Some(self.semantic_scholar_enabled),
+            _ => None,
+        }
+    }
+
+    pub fn set_engine_enabled(&mut self, engine: &str, enabled: bool) -> Result<(), String> {
+        match engine {
+            "scholar" => self.scholar_enabled = enabled,
+            "light" => self.light_enabled = enabled,
+            "pubmed" => self.pubmed_enabled = enabled,
+            "semantic_scholar" => self.semantic_scholar_enabled = enabled,
+            other => return Err(format!("Unknown search engine: {}", other)),
+        }
+        Ok(())
+    }
+
+    /// Copy with keys reduced to their last four characters, for display
+    pub fn masked(&self) -> Self {
+        let mut masked = self.clone();
+        for (_, slot) in masked.keys_mut() {
+            if let Some(key) = slot.as_mut() {
+                *key = format!("••••{}", &key[key.len().saturating_sub(4)..]);
+            }
+        }
+        masked
+    }
+
+    /// Undo masking for keys sent back unchanged from a masked response
+    pub fn unmask_from(mut self, current: &SearchApiConfig) -> Self {
+        let masked = current.masked();
+        let slots = [
+            (&mut self.serpapi_key, &masked.serpapi_key, &current.serpapi_key),
+            (&mut self.ncbi_api_key, &masked.ncbi_api_key, &current.ncbi_api_key),
+            (&mut self.semantic_scholar_key, &masked.semantic_scholar_key, &current.semantic_scholar_key),
+        ];
+        for (slot, masked, original) in slots {
+            if slot.is_some() && slot == masked {
+                *slot = original.clone();
+            }
+        }
+        self
+    }
+}
+
+/// Proxy and TLS settings for outbound requests
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct NetworkSettings {
+    /// Proxy for all requests (`http://`, `https://`, `socks5://` or `socks5h://`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_url: Option<String>,
+    /// Comma-separated hosts that bypass the proxy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    /// PEM bundle with additional root certificates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert_path: Option<String>,
+    /// Proxy overrides keyed by provider id (`openai`, `groq`, `serpapi`, ...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub provider_proxies: BTreeMap<String, String>,
+}
+
+impl NetworkSettings {
+    /// Apply these settings on top of the environment-derived config
+    pub fn apply_to(&self, network: &mut crate::config::NetworkConfig) {
+        if self.proxy_url.is_some() {
+            network.proxy_url = self.proxy_url.clone();
+        }
+        if self.no_proxy.is_some() {
+            network.no_proxy = self.no_proxy.clone();
+        }
+        if self.ca_cert_path.is_some() {
+            network.ca_cert_path = self.ca_cert_path.clone();
+        }
+        for (target, proxy) in &self.provider_proxies {
+            network.provider_proxies.insert(target.clone(), proxy.clone());
+        }
+    }
+
+    /// Undo masking for values sent back unchanged from a masked response
+    pub fn unmask_from(mut self, current: &NetworkSettings) -> Self {
+        let masked = current.masked();
+        if self.proxy_url.is_some() && self.proxy_url == masked.proxy_url {
+            self.proxy_url = current.proxy_url.clone();
+        }
+        for (target, proxy) in self.provider_proxies.iter_mut() {
+            if masked.provider_proxies.get(target) == Some(proxy) {
+                if let Some(original) = current.provider_proxies.get(target) {
+                    *proxy = original.clone();
+                }
+            }
+        }
+        self
+    }
+
+    /// Copy with any proxy passwords replaced, for display
+    pub fn masked(&self) -> Self {
+        Self {
+            proxy_url: self.proxy_url.as_deref().map(mask_proxy_password),
+            provider_proxies: self
+                .provider_proxies
+                .iter()
+                .map(|(k, v)| (k.clone(), mask_proxy_password(v)))
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+fn mask_proxy_password(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("****"));
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Spending limits; unset fields leave the environment value (or no limit)
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct BudgetSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_usd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_usd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_tokens: Option<u64>,
+    /// SerpAPI queries per calendar month
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_search_queries: Option<u32>,
+    /// SerpAPI queries per session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_search_queries: Option<u32>,
+}
+
+impl BudgetSettings {
+    /// Apply these limits on top of the environment-derived config
+    pub fn apply_to(&self, budget: &mut crate::config::BudgetConfig) {
+        budget.monthly_usd = self.monthly_usd.or(budget.monthly_usd);
+        budget.session_usd = self.session_usd.or(budget.session_usd);
+        budget.monthly_tokens = self.monthly_tokens.or(budget.monthly_tokens);
+        budget.session_tokens = self.session_tokens.or(budget.session_tokens);
+        budget.monthly_search_queries = self.monthly_search_queries.or(budget.monthly_search_queries);
+        budget.session_search_queries = self.session_search_queries.or(budget.session_search_queries);
+    }
+
+    /// Set a limit by name; `None` clears it
+    pub fn set(&mut self, field: &str, value: Option<&str>) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(value: Option<&str>) -> Result<Option<T>, String> {
+            value
+                .map(|v| v.trim_start_matches('$').parse().map_err(|_| format!("Invalid number: {}", v)))
+                .transpose()
+        }
+        match field {
+            "monthly_usd" => self.monthly_usd = parse(value)?,
+            "session_usd" => self.session_usd = parse(value)?,
+            "monthly_tokens" => self.monthly_tokens = parse(value)?,
+            "session_tokens" => self.session_tokens = parse(value)?,
+            "monthly_search_queries" => self.monthly_search_queries = parse(value)?,
+            "session_search_queries" => self.session_search_queries = parse(value)?,
+            other => return Err(format!("Unknown budget field: {}", other)),
+        }
+        Ok(())
+    }
+}
+
+/// OpenRouter vendor preferences, fallback models and attribution headers;
+/// unset fields leave the environment value
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct OpenRouterSettings {
+    /// Vendors tried first, in this order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+    /// Vendors requests are restricted to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub only: Vec<String>,
+    /// Vendors never used
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    /// Whether vendors outside `order` may serve a request once those fail
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_fallbacks: Option<bool>,
+    /// Models tried in turn when the requested one fails
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_models: Vec<String>,
+    /// `HTTP-Referer` attribution header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub referer: Option<String>,
+    /// `X-Title` attribution header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl OpenRouterSettings {
+    /// Apply these controls on top of the environment-derived config
+    pub fn apply_to(&self, openrouter: &mut crate::config::OpenRouterConfig) {
+        for (setting, config) in [
+            (&self.order, &mut openrouter.order),
+            (&self.only, &mut openrouter.only),
+            (&self.ignore, &mut openrouter.ignore),
+            (&self.fallback_models, &mut openrouter.fallback_models),
+        ] {
+            if !setting.is_empty() {
+                *config = setting.clone();
+            }
+        }
+        openrouter.allow_fallbacks = self.allow_fallbacks.or(openrouter.allow_fallbacks);
+        openrouter.referer = self.referer.clone().or(openrouter.referer.take());
+        openrouter.title = self.title.clone().or(openrouter.title.take());
+    }
+
+    /// Set a control by name (`order`, `only`, `ignore` and `models` take
+    /// comma-separated lists); `None` clears it
+    pub fn set(&mut self, field: &str, value: Option<&str>) -> Result<(), String> {
+        let list = |value: Option<&str>| -> Vec<String> {
+            value
+                .map(|v| v.split(',').map(str::trim).filter(|v| !v.is_empty()).map(String::from).collect())
+                .unwrap_or_default()
+        };
+        match field {
+            "order" => self.order = list(value),
+            "only" => self.only = list(value),
+            "ignore" => self.ignore = list(value),
+            "models" | "fallback_models" => self.fallback_models = list(value),
+            "fallbacks" | "allow_fallbacks" => {
+                self.allow_fallbacks = match value {
+                    None => None,
+                    Some("on" | "true" | "yes") => Some(true),
+                    Some("off" | "false" | "no") => Some(false),
+                    Some(other) => return Err(format!("Invalid fallbacks value: {} (expected on or off)", other)),
+                }
+            }
+            "referer" => self.referer = value.map(str::to_string),
+            "title" => self.title = value.map(str::to_string),
+            other => return Err(format!("Unknown OpenRouter setting: {}", other)),
+        }
+        Ok(())
+    }
+
+    /// Whether these controls, over the environment's, make a valid config
+    pub fn validate(&self) -> Result<(), String> {
+        let mut config = crate::config::OpenRouterConfig::from_env();
+        self.apply_to(&mut config);
+        config.validate()
+    }
+}
+
+/// Manuscript formatting; unset fields leave the environment value
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ManuscriptSettings {
+    /// Journal template (generic, aging_cell, plos, biorxiv)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<crate::manuscript::JournalTemplate>,
+    /// CSL style for Markdown/DOCX exports ("apa", "vancouver" or a .csl path)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citation_style: Option<crate::manuscript::CitationStyle>,
+}
+
+impl ManuscriptSettings {
+    /// Apply these choices on top of the environment-derived config
+    pub fn apply_to(&self, manuscript: &mut crate::config::ManuscriptConfig) {
+        if let Some(template) = self.template {
+            manuscript.template = template;
+        }
+        if let Some(style) = &self.citation_style {
+            manuscript.citation_style = style.clone();
+        }
+    }
 }
 
 /// User settings structure
@@ -111,10 +445,31 @@ pub struct UserSettings {
     /// Groq configuration
     #[serde(default)]
     pub groq: ProviderConfig,
+
+    /// OpenRouter vendor preferences, fallback models and attribution
+    #[serde(default)]
+    pub openrouter_routing: OpenRouterSettings,
     
     /// Search API configuration (SerpAPI)
     #[serde(default)]
     pub search: SearchApiConfig,
+
+    /// Per-task routing, e.g. a cheap model for literature search and a
+    /// strong one for replies. Unrouted tasks use `default_provider`.
+    #[serde(default)]
+    pub routing: BTreeMap<LlmTask, TaskRoute>,
+
+    /// Proxy and custom CA configuration
+    #[serde(default)]
+    pub network: NetworkSettings,
+
+    /// LLM spend and SerpAPI quota limits
+    #[serde(default)]
+    pub budget: BudgetSettings,
+
+    /// Journal template and citation style for manuscript exports
+    #[serde(default)]
+    pub manuscript: ManuscriptSettings,
     
     /// Theme preference
     #[serde(default)]
@@ -163,79 +518,182 @@ impl Default for UserSettings {
                 serpapi_key: None,
                 scholar_enabled: true,
                 light_enabled: true,
+                ncbi_api_key: None,
+                ncbi_email: None,
+                pubmed_enabled: true,
+                semantic_scholar_key: None,
+                semantic_scholar_enabled: true,
+                max_results: BTreeMap::new(),
             },
+            openrouter_routing: OpenRouterSettings::default(),
+            routing: BTreeMap::new(),
+            network: NetworkSettings::default(),
+            budget: BudgetSettings::default(),
+            manuscript: ManuscriptSettings::default(),
             theme: Theme::Dark,
         }
     }
 }
 
 impl UserSettings {
+    /// Overlay these settings on `config`: API keys, default provider and
+    /// model, per-task routes, search engines, and network, OpenRouter, budget
+    /// and manuscript settings on top of the environment
+    pub fn apply_to(&self, config: &mut crate::config::Config) {
+        // Update LLM API keys
+        config.llm.openai_api_key =
+            self.openai.api_key.clone().unwrap_or_default();
+        config.llm.anthropic_api_key =
+            self.anthropic.api_key.clone().unwrap_or_default();
+        config.llm.google_api_key =
+            self.google.api_key.clone().unwrap_or_default();
+        config.llm.openrouter_api_key =
+            self.openrouter.api_key.clone().unwrap_or_default();
+        config.llm.groq_api_key =
+            self.groq.api_key.clone().unwrap_or_default();
+        
+        // Update LLM provider
+        config.llm.default_provider = self.default_provider.to_string();
+        
+        // Update model based on selected provider's default model
+        // This is CRITICAL - without this, the model stays as "gpt-4" which other providers don't recognize
+        config.llm.default_model = match self.default_provider {
+            Provider::OpenAI => self.openai.default_model.clone()
+                .unwrap_or_else(|| "gpt-4o".to_string()),
+            Provider::Anthropic => self.anthropic.default_model.clone()
+                .unwrap_or_else(|| "claude-sonnet-4-20250514".to_string()),
+            Provider::Google => self.google.default_model.clone()
+                .unwrap_or_else(|| "gemini-2.0-flash".to_string()),
+            Provider::OpenRouter => self.openrouter.default_model.clone()
+                .unwrap_or_else(|| "anthropic/claude-sonnet-4".to_string()),
+            Provider::Groq => self.groq.default_model.clone()
+                .unwrap_or_else(|| "groq/compound".to_string()),
+        };
+
+        // Per-task routing; a route without a model uses that provider's default
+        config.llm.routes = self
+            .routing
+            .iter()
+            .map(|(task, route)| {
+                let model = route
+                    .model
+                    .clone()
+                    .or_else(|| self.provider_config(&route.provider).default_model.clone());
+                (
+                    *task,
+                    crate::config::LlmRoute {
+                        provider: route.provider.to_string(),
+                        model,
+                        temperature: route.temperature,
+                        max_tokens: route.max_tokens,
+                    },
+                )
+            })
+            .collect();
+
+        // Proxy / custom CA: settings override the environment
+        config.network = crate::config::NetworkConfig::from_env();
+        self.network.apply_to(&mut config.network);
+
+        // OpenRouter vendor preferences: settings override the environment
+        config.llm.openrouter = crate::config::OpenRouterConfig::from_env();
+        self.openrouter_routing.apply_to(&mut config.llm.openrouter);
+
+        // Budgets: settings override the environment
+        config.budget = crate::config::BudgetConfig::from_env();
+        self.budget.apply_to(&mut config.budget);
+
+        // Journal template: settings override the environment
+        config.manuscript = crate::config::ManuscriptConfig::from_env();
+        self.manuscript.apply_to(&mut config.manuscript);
+
+        // Update Search API config (SerpAPI)
+        config.search.serpapi_key =
+            self.search.serpapi_key.clone().unwrap_or_default();
+        config.search.scholar_enabled = self.search.scholar_enabled;
+        config.search.light_enabled = self.search.light_enabled;
+        config.search.ncbi_api_key =
+            self.search.ncbi_api_key.clone().unwrap_or_default();
+        config.search.ncbi_email =
+            self.search.ncbi_email.clone().unwrap_or_default();
+        config.search.pubmed_enabled = self.search.pubmed_enabled;
+        config.search.semantic_scholar_api_key =
+            self.search.semantic_scholar_key.clone().unwrap_or_default();
+        config.search.semantic_scholar_enabled = self.search.semantic_scholar_enabled;
+        config.search.engine_max_results = self
+            .search
+            .max_results
+            .iter()
+            .map(|(engine, n)| (engine.clone(), *n))
+            .collect();
+    }
+
     pub fn clear_api_keys(&mut self) {
-        self.openai.api_key = None;
-        self.anthropic.api_key = None;
-        self.google.api_key = None;
-        self.openrouter.api_key = None;
-        self.groq.api_key = None;
-    }
-
-    pub fn set_single_provider_key(&mut self, provider_id: &str, key: String) {
-        self.clear_api_keys();
-        match provider_id {
-            "openai" => self.openai.api_key = Some(key),
-            "anthropic" => self.anthropic.api_key = Some(key),
-            "google" => self.google.api_key = Some(key),
-            "openrouter" => self.openrouter.api_key = Some(key),
-            "groq" => self.groq.api_key = Some(key),
-            _ => {}
+        for provider in Provider::ALL {
+            self.provider_config_mut(&provider).api_key = None;
         }
+    }
 
-        if let Some(provider) = Provider::from_id(provider_id) {
-            self.default_provider = provider;
+    pub fn provider_config(&self, provider: &Provider) -> &ProviderConfig {
+        match provider {
+            Provider::OpenAI => &self.openai,
+            Provider::Anthropic => &self.anthropic,
+            Provider::Google => &self.google,
+            Provider::OpenRouter => &self.openrouter,
+            Provider::Groq => &self.groq,
         }
     }
 
-    pub fn enforce_single_provider_key(&mut self) {
-        let mut providers_with_keys = Vec::new();
-        if self.openai.api_key.is_some() {
-            providers_with_keys.push("openai");
-        }
-        if self.anthropic.api_key.is_some() {
-            providers_with_keys.push("anthropic");
-        }
-        if self.google.api_key.is_some() {
-            providers_with_keys.push("google");
-        }
-        if self.openrouter.api_key.is_some() {
-            providers_with_keys.push("openrouter");
-        }
-        if self.groq.api_key.is_some() {
-            providers_with_keys.push("groq");
+    pub fn provider_config_mut(&mut self, provider: &Provider) -> &mut ProviderConfig {
+        match provider {
+            Provider::OpenAI => &mut self.openai,
+            Provider::Anthropic => &mut self.anthropic,
+            Provider::Google => &mut self.google,
+            Provider::OpenRouter => &mut self.openrouter,
+            Provider::Groq => &mut self.groq,
         }
+    }
 
-        if providers_with_keys.len() <= 1 {
+    /// Store a key for one provider, leaving other providers' keys alone.
+    /// The provider becomes the default if the current default has no key.
+    pub fn set_provider_key(&mut self, provider_id: &str, key: String) {
+        let Some(provider) = Provider::from_id(provider_id) else {
             return;
+        };
+        self.provider_config_mut(&provider).api_key = Some(key);
+
+        if self.provider_config(&self.default_provider).api_key.is_none() {
+            self.default_provider = provider;
         }
+    }
 
-        let default_id = self.default_provider.to_string();
-        let keep_id = if providers_with_keys.contains(&default_id.as_str()) {
-            default_id
-        } else {
-            providers_with_keys[0].to_string()
-        };
+    /// Route `task` to `provider` (and optionally a specific model)
+    pub fn set_route(&mut self, task: LlmTask, provider: Provider, model: Option<String>) {
+        let (temperature, max_tokens) = self
+            .routing
+            .get(&task)
+            .map(|r| (r.temperature, r.max_tokens))
+            .unwrap_or_default();
+        self.routing.insert(task, TaskRoute { provider, model, temperature, max_tokens });
+    }
 
-        let keep_key = match keep_id.as_str() {
-            "openai" => self.openai.api_key.take(),
-            "anthropic" => self.anthropic.api_key.take(),
-            "google" => self.google.api_key.take(),
-            "openrouter" => self.openrouter.api_key.take(),
-            "groq" => self.groq.api_key.take(),
-            _ => None,
-        };
+    /// Override sampling parameters for `task`; a task without a route is
+    /// pinned to the current default provider first.
+    pub fn set_route_params(&mut self, task: LlmTask, temperature: Option<f32>, max_tokens: Option<u32>) {
+        let default_provider = self.default_provider.clone();
+        let route = self.routing.entry(task).or_insert_with(|| TaskRoute {
+            provider: default_provider,
+            model: None,
+            temperature: None,
+            max_tokens: None,
+        });
+        route.temperature = temperature;
+        route.max_tokens = max_tokens;
+    }
 
-        self.clear_api_keys();
-        if let Some(key) = keep_key {
-            self.set_single_provider_key(&keep_id, key);
-        }
+    /// Send `task` back to the default provider
+    pub fn clear_route(&mut self, task: LlmTask) -> bool {
+        self.routing.remove(&task).is_some()
     }
 }
 
@@ -243,11 +701,18 @@ impl UserSettings {
 #[derive(Debug, Clone, Serialize)]
 pub struct SettingsResponse {
     pub default_provider: Provider,
-    pub openai: ProviderStatus,
-    pub anthropic: ProviderStatus,
-    pub google: ProviderStatus,
-    pub openrouter: ProviderStatus,
-    pub groq: ProviderStatus,
+    /// Status per provider, serialized under the provider id (`"openai": {...}`)
+    #[serde(flatten)]
+    pub providers: BTreeMap<Provider, ProviderStatus>,
+    pub routing: BTreeMap<LlmTask, TaskRoute>,
+    pub openrouter_routing: OpenRouterSettings,
+    /// Search engines, with keys masked
+    pub search: SearchApiConfig,
+    pub network: NetworkSettings,
+    pub budget: BudgetSettings,
+    pub manuscript: ManuscriptSettings,
+    /// Spend so far this session and month
+    pub usage: crate::budget::UsageSnapshot,
     pub theme: Theme,
 }
 
@@ -288,47 +753,131 @@ impl From<&UserSettings> for SettingsResponse {
     fn from(settings: &UserSettings) -> Self {
         Self {
             default_provider: settings.default_provider.clone(),
-            openai: ProviderStatus::from(&settings.openai),
-            anthropic: ProviderStatus::from(&settings.anthropic),
-            google: ProviderStatus::from(&settings.google),
-            openrouter: ProviderStatus::from(&settings.openrouter),
-            groq: ProviderStatus::from(&settings.groq),
+            providers: Provider::ALL
+                .into_iter()
+                .map(|p| {
+                    let status = ProviderStatus::from(settings.provider_config(&p));
+                    (p, status)
+                })
+                .collect(),
+            routing: settings.routing.clone(),
+            openrouter_routing: settings.openrouter_routing.clone(),
+            search: settings.search.masked(),
+            network: settings.network.masked(),
+            budget: settings.budget.clone(),
+            manuscript: settings.manuscript.clone(),
+            usage: crate::budget::snapshot(),
             theme: settings.theme.clone(),
         }
     }
 }
 
+/// Changes to one provider's configuration. An empty `api_key` removes the
+/// stored key; an empty `default_model` resets it.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProviderUpdate {
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub default_model: Option<String>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
 /// Request to update settings
 #[derive(Debug, Clone, Deserialize)]
 pub struct UpdateSettingsRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_provider: Option<Provider>,
-    
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub openai_key: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub openai_model: Option<String>,
-    
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub anthropic_key: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub anthropic_model: Option<String>,
-    
+
+    /// Per-provider changes keyed by provider id
+    #[serde(default)]
+    pub providers: BTreeMap<Provider, ProviderUpdate>,
+
+    /// Full replacement for the task routing table
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub google_key: Option<String>,
+    pub routing: Option<BTreeMap<LlmTask, TaskRoute>>,
+
+    /// Full replacement for the OpenRouter vendor preferences
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub google_model: Option<String>,
-    
+    pub openrouter_routing: Option<OpenRouterSettings>,
+
+    /// Full replacement for the search engine settings; masked keys sent
+    /// back unchanged are kept
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub openrouter_key: Option<String>,
+    pub search: Option<SearchApiConfig>,
+
+    /// Full replacement for the proxy/CA settings
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub openrouter_model: Option<String>,
+    pub network: Option<NetworkSettings>,
 
+    /// Full replacement for the budget limits
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub groq_key: Option<String>,
+    pub budget: Option<BudgetSettings>,
+
+    /// Full replacement for the manuscript settings
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub groq_model: Option<String>,
+    pub manuscript: Option<ManuscriptSettings>,
     
     #[serde(skip_serializing_if = "Option::is_none")]
     pub theme: Option<Theme>,
+
+    /// Flat `<provider>_key` / `<provider>_model` fields from older clients
+    #[serde(flatten)]
+    pub legacy: std::collections::HashMap<String, serde_json::Value>,
+}
+
+impl UpdateSettingsRequest {
+    /// All provider changes, with legacy flat fields folded in. Entries in
+    /// `providers` win over flat fields for the same provider.
+    pub fn provider_updates(&mut self) -> BTreeMap<Provider, ProviderUpdate> {
+        let mut updates = std::mem::take(&mut self.providers);
+        for provider in Provider::ALL {
+            let legacy = |suffix: &str| {
+                self.legacy
+                    .get(&format!("{}_{}", provider, suffix))
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+            };
+            let (key, model) = (legacy("key"), legacy("model"));
+            if key.is_none() && model.is_none() {
+                continue;
+            }
+            let update = updates.entry(provider).or_default();
+            update.api_key = update.api_key.take().or(key);
+            update.default_model = update.default_model.take().or(model);
+        }
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_updates_accept_map_and_legacy_fields() {
+        let mut request: UpdateSettingsRequest = serde_json::from_value(serde_json::json!({
+            "providers": { "groq": { "api_key": "gsk-new", "enabled": false } },
+            "groq_model": "llama-3.3-70b-versatile",
+            "anthropic_key": "sk-ant-legacy"
+        }))
+        .unwrap();
+
+        let updates = request.provider_updates();
+        let groq = &updates[&Provider::Groq];
+        assert_eq!(groq.api_key.as_deref(), Some("gsk-new"));
+        assert_eq!(groq.default_model.as_deref(), Some("llama-3.3-70b-versatile"));
+        assert_eq!(groq.enabled, Some(false));
+        assert_eq!(updates[&Provider::Anthropic].api_key.as_deref(), Some("sk-ant-legacy"));
+        assert!(!updates.contains_key(&Provider::OpenAI));
+    }
+
+    #[test]
+    fn test_response_lists_every_provider() {
+        let json = serde_json::to_value(SettingsResponse::from(&UserSettings::default())).unwrap();
+        for provider in Provider::ALL {
+            assert!(json.get(provider.to_string()).is_some(), "missing {}", provider);
+        }
+    }
 }
@@ -4,11 +4,18 @@
 //! - GET /api/settings - Get current settings (with masked API keys)
 //! - POST /api/settings - Update settings
 //! - GET /api/settings/providers - List available providers
+//! - POST /api/settings/models/refresh/{provider} - Refresh model limits
+//!
+//! Settings are read from the profile the process runs with (`--profile`,
+//! else the active one), so with `--serve` they are the TUI's. Saving
+//! publishes the updated config for the routes and runs in progress, and
+//! [`SessionEvent::SettingsChanged`] for the TUI to reload.
 
 use axum::{
     Router,
     routing::{get, post},
     Json,
+    extract::State,
     response::IntoResponse,
     http::StatusCode,
 };
@@ -16,21 +23,37 @@ use super::{
     SettingsStorage, SettingsResponse, UpdateSettingsRequest,
     Provider, ProviderStatus,
 };
+use crate::config::Config;
+use crate::models::AppState;
+use crate::session::SessionEvent;
 use serde::Serialize;
-use tracing::{info, error};
+use tracing::{info, error, warn};
 
 /// Create the settings router
-pub fn router() -> Router {
+pub fn router(state: AppState) -> Router {
     Router::new()
         .route("/api/settings", get(get_settings))
         .route("/api/settings", post(update_settings))
         .route("/api/settings/providers", get(list_providers))
         .route("/api/settings/test/{provider}", post(test_provider))
+        .route("/api/settings/models/refresh/{provider}", post(refresh_models))
+        .with_state(state)
+}
+
+/// Storage for the profile this process runs with
+fn storage(state: &AppState) -> SettingsStorage {
+    match state.config.current().settings_profile.as_deref() {
+        Some(profile) => SettingsStorage::new().with_profile(profile).unwrap_or_else(|e| {
+            warn!("{}; using the active profile", e);
+            SettingsStorage::new()
+        }),
+        None => SettingsStorage::new(),
+    }
 }
 
 /// GET /api/settings - Get current settings
-async fn get_settings() -> impl IntoResponse {
-    let storage = SettingsStorage::new();
+async fn get_settings(State(state): State<AppState>) -> impl IntoResponse {
+    let storage = storage(&state);
     
     match storage.load().await {
         Ok(settings) => {
@@ -52,9 +75,10 @@ async fn get_settings() -> impl IntoResponse {
 
 /// POST /api/settings - Update settings
 async fn update_settings(
-    Json(request): Json<UpdateSettingsRequest>,
+    State(state): State<AppState>,
+    Json(mut request): Json<UpdateSettingsRequest>,
 ) -> impl IntoResponse {
-    let storage = SettingsStorage::new();
+    let storage = storage(&state);
     
     // Load existing settings
     let mut settings = match storage.load().await {
@@ -72,96 +96,76 @@ async fn update_settings(
     };
 
     // Apply updates
+    let provider_updates = request.provider_updates();
     if let Some(provider) = request.default_provider {
         settings.default_provider = provider;
     }
 
-    let mut provided_keys: Vec<(&str, String)> = Vec::new();
-
-    // Update OpenAI
-    if let Some(key) = request.openai_key {
-        if key.is_empty() {
-            settings.openai.api_key = None;
-        } else {
-            provided_keys.push(("openai", key));
+    // Provider changes are keyed by provider id, so new providers need no
+    // code here
+    let mut provided_keys: Vec<(Provider, String)> = Vec::new();
+    for (provider, update) in provider_updates {
+        let config = settings.provider_config_mut(&provider);
+        if let Some(model) = update.default_model {
+            config.default_model = Some(model).filter(|m| !m.is_empty());
         }
-    }
-    if let Some(model) = request.openai_model {
-        settings.openai.default_model = Some(model);
-    }
-    
-    // Update Anthropic
-    if let Some(key) = request.anthropic_key {
-        if key.is_empty() {
-            settings.anthropic.api_key = None;
-        } else {
-            provided_keys.push(("anthropic", key));
+        if let Some(enabled) = update.enabled {
+            config.enabled = enabled;
         }
-    }
-    if let Some(model) = request.anthropic_model {
-        settings.anthropic.default_model = Some(model);
-    }
-    
-    // Update Google
-    if let Some(key) = request.google_key {
-        if key.is_empty() {
-            settings.google.api_key = None;
-        } else {
-            provided_keys.push(("google", key));
+        match update.api_key {
+            Some(key) if key.is_empty() => config.api_key = None,
+            Some(key) => provided_keys.push((provider, key)),
+            None => {}
         }
     }
-    if let Some(model) = request.google_model {
-        settings.google.default_model = Some(model);
-    }
     
-    // Update OpenRouter
-    if let Some(key) = request.openrouter_key {
-        if key.is_empty() {
-            settings.openrouter.api_key = None;
-        } else {
-            provided_keys.push(("openrouter", key));
-        }
+    // Update theme
+    if let Some(theme) = request.theme {
+        settings.theme = theme;
     }
-    if let Some(model) = request.openrouter_model {
-        settings.openrouter.default_model = Some(model);
+
+    // Keys are kept per provider; several can be configured at once
+    for (provider, key) in provided_keys {
+        settings.set_provider_key(&provider.to_string(), key);
     }
 
-    // Update Groq
-    if let Some(key) = request.groq_key {
-        if key.is_empty() {
-            settings.groq.api_key = None;
-        } else {
-            provided_keys.push(("groq", key));
+    // Replace the routing table when one is supplied
+    if let Some(routing) = request.routing {
+        settings.routing = routing;
+    }
+    if let Some(openrouter) = request.openrouter_routing {
+        if let Err(e) = openrouter.validate() {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": e
+                }))
+            ).into_response();
         }
+        settings.openrouter_routing = openrouter;
     }
-    if let Some(model) = request.groq_model {
-        settings.groq.default_model = Some(model);
+    if let Some(search) = request.search {
+        settings.search = search.unmask_from(&settings.search);
     }
-    
-    // Update theme
-    if let Some(theme) = request.theme {
-        settings.theme = theme;
+    if let Some(network) = request.network {
+        settings.network = network.unmask_from(&settings.network);
     }
-
-    if provided_keys.len() > 1 {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({
-                "error": "Only one provider key can be configured at a time"
-            }))
-        ).into_response();
+    if let Some(budget) = request.budget {
+        settings.budget = budget;
     }
-
-    if let Some((provider_id, key)) = provided_keys.pop() {
-        settings.set_single_provider_key(provider_id, key);
-    } else {
-        settings.enforce_single_provider_key();
+    if let Some(manuscript) = request.manuscript {
+        settings.manuscript = manuscript;
     }
 
     // Save settings
     match storage.save(&settings).await {
         Ok(_) => {
             info!("Settings updated successfully");
+            let mut config = Config::clone(&state.config.current());
+            settings.apply_to(&mut config);
+            state.config.publish(config);
+            state.events.publish(SessionEvent::SettingsChanged);
             let response = SettingsResponse::from(&settings);
             (StatusCode::OK, Json(serde_json::json!({
                 "success": true,
@@ -196,10 +200,25 @@ struct ProviderInfo {
 struct ModelInfo {
     id: String,
     name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    context_length: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    supports_vision: Option<bool>,
+    context_length: u32,
+    max_output: u32,
+    supports_vision: bool,
+    supports_tools: bool,
+}
+
+impl ModelInfo {
+    /// Limits from the model capability table
+    fn new(provider: &str, id: &str, name: &str) -> Self {
+        let caps = crate::llm::capabilities::lookup(provider, id);
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            context_length: caps.context_length,
+            max_output: caps.max_output,
+            supports_vision: caps.vision,
+            supports_tools: caps.tools,
+        }
+    }
 }
 
 /// GET /api/settings/providers - List available providers
@@ -210,11 +229,11 @@ async fn list_providers() -> impl IntoResponse {
             name: "OpenAI".to_string(),
             description: "GPT-4o, GPT-4, and other OpenAI models".to_string(),
             models: vec![
-                ModelInfo { id: "gpt-4o".to_string(), name: "GPT-4o".to_string(), context_length: Some(128000), supports_vision: Some(true) },
-                ModelInfo { id: "gpt-4o-mini".to_string(), name: "GPT-4o Mini".to_string(), context_length: Some(128000), supports_vision: Some(true) },
-                ModelInfo { id: "gpt-4-turbo".to_string(), name: "GPT-4 Turbo".to_string(), context_length: Some(128000), supports_vision: Some(true) },
-                ModelInfo { id: "o1".to_string(), name: "o1".to_string(), context_length: Some(200000), supports_vision: Some(false) },
-                ModelInfo { id: "o1-mini".to_string(), name: "o1-mini".to_string(), context_length: Some(128000), supports_vision: Some(false) },
+                ModelInfo::new("openai", "gpt-4o", "GPT-4o"),
+                ModelInfo::new("openai", "gpt-4o-mini", "GPT-4o Mini"),
+                ModelInfo::new("openai", "gpt-4-turbo", "GPT-4 Turbo"),
+                ModelInfo::new("openai", "o1", "o1"),
+                ModelInfo::new("openai", "o1-mini", "o1-mini"),
             ],
             docs_url: Some("https://platform.openai.com/docs".to_string()),
         },
@@ -223,10 +242,10 @@ async fn list_providers() -> impl IntoResponse {
             name: "Anthropic".to_string(),
             description: "Claude 4, Claude 3.5, and Claude 3 models".to_string(),
             models: vec![
-                ModelInfo { id: "claude-sonnet-4-20250514".to_string(), name: "Claude Sonnet 4".to_string(), context_length: Some(200000), supports_vision: Some(true) },
-                ModelInfo { id: "claude-3-5-sonnet-20241022".to_string(), name: "Claude 3.5 Sonnet".to_string(), context_length: Some(200000), supports_vision: Some(true) },
-                ModelInfo { id: "claude-3-opus-20240229".to_string(), name: "Claude 3 Opus".to_string(), context_length: Some(200000), supports_vision: Some(true) },
-                ModelInfo { id: "claude-3-haiku-20240307".to_string(), name: "Claude 3 Haiku".to_string(), context_length: Some(200000), supports_vision: Some(true) },
+                ModelInfo::new("anthropic", "claude-sonnet-4-20250514", "Claude Sonnet 4"),
+                ModelInfo::new("anthropic", "claude-3-5-sonnet-20241022", "Claude 3.5 Sonnet"),
+                ModelInfo::new("anthropic", "claude-3-opus-20240229", "Claude 3 Opus"),
+                ModelInfo::new("anthropic", "claude-3-haiku-20240307", "Claude 3 Haiku"),
             ],
             docs_url: Some("https://docs.anthropic.com".to_string()),
         },
@@ -235,10 +254,10 @@ async fn list_providers() -> impl IntoResponse {
             name: "Google AI".to_string(),
             description: "Gemini 2.0, Gemini 1.5 models".to_string(),
             models: vec![
-                ModelInfo { id: "gemini-2.0-flash".to_string(), name: "Gemini 2.0 Flash".to_string(), context_length: Some(1000000), supports_vision: Some(true) },
-                ModelInfo { id: "gemini-2.0-flash-thinking".to_string(), name: "Gemini 2.0 Flash Thinking".to_string(), context_length: Some(1000000), supports_vision: Some(true) },
-                ModelInfo { id: "gemini-1.5-pro".to_string(), name: "Gemini 1.5 Pro".to_string(), context_length: Some(2000000), supports_vision: Some(true) },
-                ModelInfo { id: "gemini-1.5-flash".to_string(), name: "Gemini 1.5 Flash".to_string(), context_length: Some(1000000), supports_vision: Some(true) },
+                ModelInfo::new("google", "gemini-2.0-flash", "Gemini 2.0 Flash"),
+                ModelInfo::new("google", "gemini-2.0-flash-thinking", "Gemini 2.0 Flash Thinking"),
+                ModelInfo::new("google", "gemini-1.5-pro", "Gemini 1.5 Pro"),
+                ModelInfo::new("google", "gemini-1.5-flash", "Gemini 1.5 Flash"),
             ],
             docs_url: Some("https://ai.google.dev/docs".to_string()),
         },
@@ -247,9 +266,9 @@ async fn list_providers() -> impl IntoResponse {
             name: "OpenRouter".to_string(),
             description: "Access multiple providers through a single API".to_string(),
             models: vec![
-                ModelInfo { id: "anthropic/claude-sonnet-4".to_string(), name: "Claude Sonnet 4 (via OpenRouter)".to_string(), context_length: None, supports_vision: None },
-                ModelInfo { id: "openai/gpt-4o".to_string(), name: "GPT-4o (via OpenRouter)".to_string(), context_length: None, supports_vision: None },
-                ModelInfo { id: "google/gemini-2.0-flash".to_string(), name: "Gemini 2.0 Flash (via OpenRouter)".to_string(), context_length: None, supports_vision: None },
+                ModelInfo::new("openrouter", "anthropic/claude-sonnet-4", "Claude Sonnet 4 (via OpenRouter)"),
+                ModelInfo::new("openrouter", "openai/gpt-4o", "GPT-4o (via OpenRouter)"),
+                ModelInfo::new("openrouter", "google/gemini-2.0-flash", "Gemini 2.0 Flash (via OpenRouter)"),
             ],
             docs_url: Some("https://openrouter.ai/docs".to_string()),
         },
@@ -258,13 +277,13 @@ async fn list_providers() -> impl IntoResponse {
             name: "Groq Cloud".to_string(),
             description: "Ultra-fast inference with Compound AI and LLM models".to_string(),
             models: vec![
-                ModelInfo { id: "groq/compound".to_string(), name: "Compound (Agentic AI with tools)".to_string(), context_length: None, supports_vision: Some(false) },
-                ModelInfo { id: "groq/compound-mini".to_string(), name: "Compound Mini (Faster, single tool)".to_string(), context_length: None, supports_vision: Some(false) },
-                ModelInfo { id: "llama-3.3-70b-versatile".to_string(), name: "Llama 3.3 70B".to_string(), context_length: Some(128000), supports_vision: Some(false) },
-                ModelInfo { id: "llama-3.1-70b-versatile".to_string(), name: "Llama 3.1 70B".to_string(), context_length: Some(128000), supports_vision: Some(false) },
-                ModelInfo { id: "llama-3.1-8b-instant".to_string(), name: "Llama 3.1 8B (Fast)".to_string(), context_length: Some(128000), supports_vision: Some(false) },
-                ModelInfo { id: "mixtral-8x7b-32768".to_string(), name: "Mixtral 8x7B".to_string(), context_length: Some(32768), supports_vision: Some(false) },
-                ModelInfo { id: "gemma2-9b-it".to_string(), name: "Gemma 2 9B".to_string(), context_length: Some(8192), supports_vision: Some(false) },
+                ModelInfo::new("groq", "groq/compound", "Compound (Agentic AI with tools)"),
+                ModelInfo::new("groq", "groq/compound-mini", "Compound Mini (Faster, single tool)"),
+                ModelInfo::new("groq", "llama-3.3-70b-versatile", "Llama 3.3 70B"),
+                ModelInfo::new("groq", "llama-3.1-70b-versatile", "Llama 3.1 70B"),
+                ModelInfo::new("groq", "llama-3.1-8b-instant", "Llama 3.1 8B (Fast)"),
+                ModelInfo::new("groq", "mixtral-8x7b-32768", "Mixtral 8x7B"),
+                ModelInfo::new("groq", "gemma2-9b-it", "Gemma 2 9B"),
             ],
             docs_url: Some("https://console.groq.com/docs/compound".to_string()),
         },
@@ -273,11 +292,61 @@ async fn list_providers() -> impl IntoResponse {
     Json(providers)
 }
 
+/// POST /api/settings/models/refresh/{provider} - Update the model
+/// capability table from the provider's model list
+async fn refresh_models(
+    State(state): State<AppState>,
+    axum::extract::Path(provider): axum::extract::Path<String>,
+) -> impl IntoResponse {
+    let storage = storage(&state);
+    let provider = provider.to_lowercase();
+    let api_key = match storage.get_api_key(&provider).await {
+        Ok(Some(key)) => key,
+        Ok(None) => match state.config.current().llm.api_key_for(&provider) {
+            Some(key) => key,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({
+                        "success": false,
+                        "error": "No API key configured for this provider"
+                    }))
+                ).into_response();
+            }
+        },
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "success": false,
+                    "error": format!("Failed to load settings: {}", e)
+                }))
+            ).into_response();
+        }
+    };
+
+    let mut network = crate::config::NetworkConfig::from_env();
+    if let Ok(settings) = storage.load().await {
+        settings.network.apply_to(&mut network);
+    }
+    match crate::llm::capabilities::refresh(&provider, &api_key, &network).await {
+        Ok(models) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "success": true, "models": models }))
+        ).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "success": false, "error": e.to_string() }))
+        ).into_response(),
+    }
+}
+
 /// POST /api/settings/test/{provider} - Test provider connection
 async fn test_provider(
+    State(state): State<AppState>,
     axum::extract::Path(provider): axum::extract::Path<String>,
 ) -> impl IntoResponse {
-    let storage = SettingsStorage::new();
+    let storage = storage(&state);
     
     // Get the API key for the provider
     let api_key = match storage.get_api_key(&provider).await {
@@ -302,17 +371,12 @@ async fn test_provider(
         }
     };
 
-    // Test the connection based on provider
-    let test_result = match provider.to_lowercase().as_str() {
-        "openai" => test_openai(&api_key).await,
-        "anthropic" => test_anthropic(&api_key).await,
-        "google" => test_google(&api_key).await,
-        "openrouter" => test_openrouter(&api_key).await,
-        "groq" => test_groq(&api_key).await,
-        _ => Err(format!("Unknown provider: {}", provider)),
-    };
-
-    match test_result {
+    // Use the same proxy/CA settings the agents will use
+    let mut network = crate::config::NetworkConfig::from_env();
+    if let Ok(settings) = storage.load().await {
+        settings.network.apply_to(&mut network);
+    }
+    match super::connection::test(&provider.to_lowercase(), &api_key, &network).await {
         Ok(message) => {
             (
                 StatusCode::OK,
@@ -327,99 +391,9 @@ async fn test_provider(
                 StatusCode::BAD_REQUEST,
                 Json(serde_json::json!({
                     "success": false,
-                    "error": error
+                    "error": error.user_message()
                 }))
             ).into_response()
         }
     }
 }
-
-// Provider test functions
-async fn test_openai(api_key: &str) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let response = client
-        .get("https://api.openai.com/v1/models")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .send()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-
-    if response.status().is_success() {
-        Ok("OpenAI API key is valid".to_string())
-    } else {
-        Err(format!("API returned error: {}", response.status()))
-    }
-}
-
-async fn test_anthropic(api_key: &str) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let response = client
-        .post("https://api.anthropic.com/v1/messages")
-        .header("x-api-key", api_key)
-        .header("anthropic-version", "2023-06-01")
-        .header("content-type", "application/json")
-        .body(r#"{"model":"claude-3-haiku-20240307","max_tokens":1,"messages":[{"role":"user","content":"Hi"}]}"#)
-        .send()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-
-    if response.status().is_success() || response.status().as_u16() == 400 {
-        // 400 might be returned for invalid request, but key is valid
-        Ok("Anthropic API key is valid".to_string())
-    } else if response.status().as_u16() == 401 {
-        Err("Invalid API key".to_string())
-    } else {
-        Err(format!("API returned error: {}", response.status()))
-    }
-}
-
-async fn test_google(api_key: &str) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models?key={}",
-        api_key
-    );
-    let response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-
-    if response.status().is_success() {
-        Ok("Google AI API key is valid".to_string())
-    } else {
-        Err(format!("API returned error: {}", response.status()))
-    }
-}
-
-async fn test_openrouter(api_key: &str) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let response = client
-        .get("https://openrouter.ai/api/v1/models")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .send()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-
-    if response.status().is_success() {
-        Ok("OpenRouter API key is valid".to_string())
-    } else {
-        Err(format!("API returned error: {}", response.status()))
-    }
-}
-
-async fn test_groq(api_key: &str) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let response = client
-        .get("https://api.groq.com/openai/v1/models")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .send()
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
-
-    if response.status().is_success() {
-        Ok("Groq API key is valid".to_string())
-    } else {
-        Err(format!("API returned error: {}", response.status()))
-    }
-}
@@ -2,6 +2,11 @@
 //! 
 //! Provides encrypted file-based storage for user settings.
 //! Uses AES-256-GCM for encryption of sensitive data like API keys.
+//!
+//! Settings can be kept in named profiles (e.g. "work", "offline", "demo").
+//! The `default` profile lives in `settings.json`; other profiles are stored
+//! as `profiles/<name>.json` and share the same encryption key. The profile
+//! used when none is requested explicitly is recorded in `active_profile`.
 
 use super::{UserSettings, ProviderConfig, SearchApiConfig};
 use aes_gcm::{
@@ -15,16 +20,24 @@ use tracing::{info, warn, error};
 
 const SETTINGS_FILE: &str = "settings.json";
 const ENCRYPTION_KEY_FILE: &str = ".settings_key";
+const PROFILES_DIR: &str = "profiles";
+const ACTIVE_PROFILE_FILE: &str = "active_profile";
 const NONCE_SIZE: usize = 12;
 
+/// Name of the profile stored in `settings.json`
+pub const DEFAULT_PROFILE: &str = "default";
+
 /// Settings storage manager
+#[derive(Debug, Clone)]
 pub struct SettingsStorage {
+    base_dir: PathBuf,
+    profile: String,
     settings_path: PathBuf,
     key_path: PathBuf,
 }
 
 impl SettingsStorage {
-    /// Create a new settings storage manager
+    /// Create a new settings storage manager for the active profile
     pub fn new() -> Self {
         // Use XDG data directory or fallback to current directory
         let base_dir = std::env::var("XDG_DATA_HOME")
@@ -36,22 +49,105 @@ impl SettingsStorage {
             })
             .join("oxidized-bio");
         
-        Self {
-            settings_path: base_dir.join(SETTINGS_FILE),
-            key_path: base_dir.join(ENCRYPTION_KEY_FILE),
-        }
+        Self::with_path(base_dir)
     }
 
     /// Create storage with custom path (useful for Docker/testing)
     pub fn with_path(base_dir: PathBuf) -> Self {
+        let profile = std::fs::read_to_string(base_dir.join(ACTIVE_PROFILE_FILE))
+            .ok()
+            .map(|p| p.trim().to_string())
+            .filter(|p| validate_profile_name(p).is_ok())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+
         Self {
-            settings_path: base_dir.join(SETTINGS_FILE),
+            settings_path: Self::profile_path(&base_dir, &profile),
             key_path: base_dir.join(ENCRYPTION_KEY_FILE),
+            base_dir,
+            profile,
+        }
+    }
+
+    /// Point this storage at another profile (does not change the active one)
+    pub fn with_profile(mut self, profile: &str) -> anyhow::Result<Self> {
+        validate_profile_name(profile)?;
+        self.settings_path = Self::profile_path(&self.base_dir, profile);
+        self.profile = profile.to_string();
+        Ok(self)
+    }
+
+    fn profile_path(base_dir: &std::path::Path, profile: &str) -> PathBuf {
+        if profile == DEFAULT_PROFILE {
+            base_dir.join(SETTINGS_FILE)
+        } else {
+            base_dir.join(PROFILES_DIR).join(format!("{}.json", profile))
+        }
+    }
+
+    /// Profile this storage reads and writes
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Whether the profile has been saved before
+    pub fn exists(&self) -> bool {
+        self.settings_path.exists()
+    }
+
+    /// All saved profiles, `default` first
+    pub async fn list_profiles(&self) -> anyhow::Result<Vec<String>> {
+        let mut profiles = Vec::new();
+        let dir = self.base_dir.join(PROFILES_DIR);
+        if dir.exists() {
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                    if validate_profile_name(name).is_ok() && name != DEFAULT_PROFILE {
+                        profiles.push(name.to_string());
+                    }
+                }
+            }
+        }
+        profiles.sort();
+        profiles.insert(0, DEFAULT_PROFILE.to_string());
+        Ok(profiles)
+    }
+
+    /// Make `profile` the one used by `SettingsStorage::new()` from now on
+    pub async fn set_active_profile(&self, profile: &str) -> anyhow::Result<()> {
+        validate_profile_name(profile)?;
+        fs::create_dir_all(&self.base_dir).await?;
+        fs::write(self.base_dir.join(ACTIVE_PROFILE_FILE), profile).await?;
+        info!("Active settings profile set to {}", profile);
+        Ok(())
+    }
+
+    /// Remove a saved profile. The default profile cannot be deleted.
+    pub async fn delete_profile(&self, profile: &str) -> anyhow::Result<bool> {
+        validate_profile_name(profile)?;
+        if profile == DEFAULT_PROFILE {
+            return Err(anyhow::anyhow!("The default profile cannot be deleted"));
+        }
+        let path = Self::profile_path(&self.base_dir, profile);
+        if !path.exists() {
+            return Ok(false);
         }
+        fs::remove_file(&path).await?;
+
+        let active_path = self.base_dir.join(ACTIVE_PROFILE_FILE);
+        if fs::read_to_string(&active_path).await.map(|p| p.trim() == profile).unwrap_or(false) {
+            fs::remove_file(&active_path).await?;
+        }
+        Ok(true)
     }
 
     /// Ensure the storage directory exists
     async fn ensure_dir(&self) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.base_dir).await?;
         if let Some(parent) = self.settings_path.parent() {
             fs::create_dir_all(parent).await?;
         }
@@ -201,22 +297,23 @@ impl SettingsStorage {
     }
 
     fn encrypt_search_key(&self, config: &mut SearchApiConfig, key: &[u8; 32]) -> anyhow::Result<()> {
-        if let Some(api_key) = &config.serpapi_key {
-            if !api_key.is_empty() {
-                config.serpapi_key = Some(self.encrypt(api_key, key)?);
+        for (_, slot) in config.keys_mut() {
+            if let Some(api_key) = slot.as_deref().filter(|k| !k.is_empty()) {
+                let encrypted = self.encrypt(api_key, key)?;
+                *slot = Some(encrypted);
             }
         }
         Ok(())
     }
 
     fn decrypt_search_key(&self, config: &mut SearchApiConfig, key: &[u8; 32]) -> anyhow::Result<()> {
-        if let Some(encrypted_key) = &config.serpapi_key {
-            if !encrypted_key.is_empty() {
+        for (name, slot) in config.keys_mut() {
+            if let Some(encrypted_key) = slot.as_deref().filter(|k| !k.is_empty()) {
                 match self.decrypt(encrypted_key, key) {
-                    Ok(decrypted) => config.serpapi_key = Some(decrypted),
+                    Ok(decrypted) => *slot = Some(decrypted),
                     Err(e) => {
-                        warn!("Failed to decrypt SerpAPI key, it may be corrupted: {}", e);
-                        config.serpapi_key = None;
+                        warn!("Failed to decrypt {} key, it may be corrupted: {}", name, e);
+                        *slot = None;
                     }
                 }
             }
@@ -234,6 +331,8 @@ impl SettingsStorage {
             "openrouter" => settings.openrouter.api_key,
             "groq" => settings.groq.api_key,
             "serpapi" => settings.search.serpapi_key,
+            "ncbi" | "pubmed" => settings.search.ncbi_api_key,
+            "semantic_scholar" => settings.search.semantic_scholar_key,
             _ => None,
         };
         Ok(key)
@@ -252,6 +351,23 @@ impl Default for SettingsStorage {
     }
 }
 
+/// Profile names become file names, so keep them to a safe character set
+pub fn validate_profile_name(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Invalid profile name '{}': use letters, digits, '-' or '_'",
+            name
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -276,6 +392,51 @@ mod tests {
         assert_eq!(loaded.anthropic.api_key, Some("sk-ant-key-67890".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_search_keys_are_encrypted() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = SettingsStorage::with_path(temp_dir.path().to_path_buf());
+
+        let mut settings = UserSettings::default();
+        settings.search.ncbi_api_key = Some("ncbi-key-1234".to_string());
+        settings.search.semantic_scholar_key = Some("s2-key-5678".to_string());
+        settings.search.max_results.insert("pubmed".to_string(), 25);
+        storage.save(&settings).await.unwrap();
+
+        let raw = tokio::fs::read_to_string(temp_dir.path().join("settings.json")).await.unwrap();
+        assert!(!raw.contains("ncbi-key-1234"));
+        assert!(!raw.contains("s2-key-5678"));
+
+        let loaded = storage.load().await.unwrap();
+        assert_eq!(loaded.search, settings.search);
+    }
+
+    #[tokio::test]
+    async fn test_profiles_are_isolated() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = SettingsStorage::with_path(temp_dir.path().to_path_buf());
+        assert_eq!(storage.profile(), DEFAULT_PROFILE);
+
+        let mut work = UserSettings::default();
+        work.openai.api_key = Some("sk-work".to_string());
+        let work_storage = storage.clone().with_profile("work").unwrap();
+        work_storage.save(&work).await.unwrap();
+        storage.save(&UserSettings::default()).await.unwrap();
+
+        assert_eq!(storage.list_profiles().await.unwrap(), vec!["default", "work"]);
+        assert!(storage.load().await.unwrap().openai.api_key.is_none());
+
+        storage.set_active_profile("work").await.unwrap();
+        let reopened = SettingsStorage::with_path(temp_dir.path().to_path_buf());
+        assert_eq!(reopened.profile(), "work");
+        assert_eq!(reopened.load().await.unwrap().openai.api_key, Some("sk-work".to_string()));
+
+        assert!(storage.delete_profile("work").await.unwrap());
+        let reopened = SettingsStorage::with_path(temp_dir.path().to_path_buf());
+        assert_eq!(reopened.profile(), DEFAULT_PROFILE);
+        assert!(storage.clone().with_profile("../etc").is_err());
+    }
+
     #[tokio::test]
     async fn test_encryption() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,155 @@
+//! Local disk usage
+//!
+//! `/storage` adds up the space taken by uploaded datasets, analysis
+//! artifacts, caches and logs, and `/delete` removes the files of one
+//! dataset. Only copies in the upload directory are ever deleted; a dataset
+//! read in place from elsewhere keeps its file.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::analysis::memory::format_bytes;
+use crate::annotation::ensembl;
+use crate::data_registry::UPLOAD_DIR;
+use crate::llm::capabilities;
+use crate::search::cache;
+use crate::utils::logger;
+
+/// Directory analysis outputs are written to, relative to the working
+/// directory
+pub const ARTIFACT_DIR: &str = "artifacts";
+
+/// Space taken by one kind of stored data
+#[derive(Debug, Clone)]
+pub struct Area {
+    pub name: &'static str,
+    pub paths: Vec<PathBuf>,
+    pub bytes: u64,
+    pub files: usize,
+}
+
+/// Bytes and number of files under `path`; nothing when it does not exist
+pub fn size(path: &Path) -> (u64, usize) {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return (0, 0);
+    };
+    if !metadata.is_dir() {
+        return (metadata.len(), 1);
+    }
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| size(&entry.path()))
+        .fold((0, 0), |(bytes, files), (b, f)| (bytes + b, files + f))
+}
+
+/// `bytes` for display, e.g. `1.5GB` or `512B`
+pub fn format_size(bytes: u64) -> String {
+    format!("{}B", format_bytes(bytes))
+}
+
+/// Uploads, artifacts, caches (search results, gene annotation and model
+/// limits) and logs, in that order
+pub fn usage() -> Vec<Area> {
+    let caches = [cache::cache_dir(), ensembl::annotation_dir(), capabilities::cache_path()];
+    [
+        ("Uploads", vec![PathBuf::from(UPLOAD_DIR)]),
+        ("Artifacts", vec![PathBuf::from(ARTIFACT_DIR)]),
+        ("Caches", caches.into_iter().flatten().collect()),
+        ("Logs", vec![logger::log_dir()]),
+    ]
+    .into_iter()
+    .map(|(name, paths)| {
+        let (bytes, files) =
+            paths.iter().map(|p| size(p)).fold((0, 0), |(bytes, files), (b, f)| (bytes + b, files + f));
+        Area { name, paths, bytes, files }
+    })
+    .collect()
+}
+
+/// Files that deleting a dataset removes
+#[derive(Debug, Clone, Default)]
+pub struct DatasetFiles {
+    /// Its copy in the upload directory; `None` when it is read in place
+    pub upload: Option<PathBuf>,
+    /// Analysis output directories that exist
+    pub artifacts: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+impl DatasetFiles {
+    /// The dataset stored at `local_path`, if that is in `upload_dir`, and
+    /// whichever of `analysis_dirs` exist
+    pub fn find(local_path: &Path, upload_dir: &Path, mut analysis_dirs: Vec<PathBuf>) -> Self {
+        let uploaded = match (local_path.parent().map(fs::canonicalize), fs::canonicalize(upload_dir)) {
+            (Some(Ok(parent)), Ok(upload_dir)) => parent == upload_dir,
+            _ => false,
+        };
+        let upload = (uploaded && local_path.is_file()).then(|| local_path.to_path_buf());
+        analysis_dirs.sort();
+        analysis_dirs.dedup();
+        analysis_dirs.retain(|dir| dir.is_dir());
+        let bytes = upload.iter().chain(&analysis_dirs).map(|p| size(p).0).sum();
+        Self { upload, artifacts: analysis_dirs, bytes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.upload.is_none() && self.artifacts.is_empty()
+    }
+
+    /// Delete the files; ones already gone are skipped
+    pub fn remove(&self) -> Result<()> {
+        let skip_missing = |result: std::io::Result<()>| match result {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            other => other,
+        };
+        if let Some(upload) = &self.upload {
+            skip_missing(fs::remove_file(upload)).with_context(|| format!("Failed to delete {}", upload.display()))?;
+        }
+        for dir in &self.artifacts {
+            skip_missing(fs::remove_dir_all(dir)).with_context(|| format!("Failed to delete {}", dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dataset_files_only_removes_uploaded_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let uploads = dir.path().join("uploads");
+        let run = dir.path().join("artifacts").join("analysis").join("ds1");
+        fs::create_dir_all(&uploads).unwrap();
+        fs::create_dir_all(run.join("plots")).unwrap();
+        fs::write(uploads.join("ds1.csv"), "id,age\nA,1\n").unwrap();
+        fs::write(run.join("summary.json"), "{}").unwrap();
+        fs::write(run.join("plots").join("heatmap.png"), [0u8; 100]).unwrap();
+        assert_eq!(size(dir.path()), (11 + 2 + 100, 3));
+        assert_eq!(size(&dir.path().join("missing")), (0, 0));
+
+        let missing = dir.path().join("old_run");
+        let files = DatasetFiles::find(&uploads.join("ds1.csv"), &uploads, vec![run.clone(), missing, run.clone()]);
+        assert_eq!(files.upload, Some(uploads.join("ds1.csv")));
+        assert_eq!(files.artifacts, vec![run.clone()]);
+        assert_eq!(files.bytes, 113);
+        files.remove().unwrap();
+        assert!(!uploads.join("ds1.csv").exists() && !run.exists());
+        // Removing again finds nothing left to delete
+        files.remove().unwrap();
+
+        // A dataset read in place keeps its file
+        let original = dir.path().join("cohort.csv");
+        fs::write(&original, "id,age\n").unwrap();
+        let files = DatasetFiles::find(&original, &uploads, Vec::new());
+        assert!(files.is_empty());
+        files.remove().unwrap();
+        assert!(original.exists());
+    }
+}
@@ -3,6 +3,7 @@
 
 use anyhow::Result;
 
+pub mod disk;
 pub mod s3_client;
 
 pub use s3_client::*;
@@ -16,15 +17,14 @@ impl Storage {
         key: &str,
         data: Vec<u8>,
     ) -> Result<String> {
-        // Placeholder implementation
+        S3Client::new().put_object(key, &data).await?;
         Ok(format!("s3://bucket/{}", key))
     }
 
     pub async fn download_file(
         key: &str,
     ) -> Result<Vec<u8>> {
-        // Placeholder implementation
-        Ok(vec![])
+        S3Client::new().get_object(key).await
     }
 
     pub async fn generate_presigned_url(
@@ -3,11 +3,22 @@
 
 use anyhow::Result;
 
-pub struct S3Client;
+use crate::utils::retry::RetryPolicy;
+
+pub struct S3Client {
+    retry: RetryPolicy,
+}
 
 impl S3Client {
     pub fn new() -> Self {
-        Self
+        Self {
+            retry: RetryPolicy::for_target("s3"),
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
     }
 
     pub async fn put_object(
@@ -15,14 +26,28 @@ impl S3Client {
         key: &str,
         data: &[u8],
     ) -> Result<()> {
-        // Placeholder implementation
-        Ok(())
+        self.retry
+            .run(|| self.put_object_once(key, data), |_| true)
+            .await
+            .map_err(|e| e.into_error())
     }
 
     pub async fn get_object(
         &self,
         key: &str,
     ) -> Result<Vec<u8>> {
+        self.retry
+            .run(|| self.get_object_once(key), |_| true)
+            .await
+            .map_err(|e| e.into_error())
+    }
+
+    async fn put_object_once(&self, key: &str, data: &[u8]) -> Result<()> {
+        // Placeholder implementation
+        Ok(())
+    }
+
+    async fn get_object_once(&self, key: &str) -> Result<Vec<u8>> {
         // Placeholder implementation
         Ok(vec![])
     }
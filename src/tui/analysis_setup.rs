@@ -0,0 +1,229 @@
+//! Analysis Setup
+//!
+//! State behind the pre-analysis screen opened by `/analyze`: the dataset's
+//! inferred columns with checkboxes for the target, group, covariates and
+//! marker subset, turned into an [`AnalysisConfig`] on Enter.
+
+use crate::analysis::AnalysisConfig;
+use crate::data_registry::{ColumnKind, ColumnSchema, DatasetRecord};
+use crate::types::{AppError, AppResult};
+
+/// Part of the configuration being edited; Tab moves between them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetupSection {
+    Target,
+    Group,
+    Covariates,
+    Markers,
+}
+
+impl SetupSection {
+    pub const ALL: [SetupSection; 4] = [Self::Target, Self::Group, Self::Covariates, Self::Markers];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Target => "Target",
+            Self::Group => "Group",
+            Self::Covariates => "Covariates",
+            Self::Markers => "Markers",
+        }
+    }
+
+    /// Whether a column of `kind` can be picked in this section
+    fn accepts(&self, kind: ColumnKind) -> bool {
+        match self {
+            Self::Group => matches!(kind, ColumnKind::Categorical | ColumnKind::Identifier),
+            _ => kind == ColumnKind::Numeric,
+        }
+    }
+
+    /// Target and group take one column, the others any number
+    pub fn single(&self) -> bool {
+        matches!(self, Self::Target | Self::Group)
+    }
+}
+
+/// Column choices for one `/analyze` run
+#[derive(Debug, Clone)]
+pub struct AnalysisSetup {
+    pub dataset_id: String,
+    pub filename: String,
+    pub columns: Vec<ColumnSchema>,
+    pub section: SetupSection,
+    /// Highlighted row in the current section's column list
+    pub cursor: usize,
+    pub target: Option<String>,
+    pub group: Option<String>,
+    pub covariates: Vec<String>,
+    /// Empty analyses every numeric column
+    pub markers: Vec<String>,
+}
+
+impl AnalysisSetup {
+    /// Start from `age` as target and `cell_type` as group when present
+    pub fn new(record: &DatasetRecord) -> Self {
+        let mut setup = Self {
+            dataset_id: record.dataset.id.clone(),
+            filename: record.dataset.filename.clone(),
+            columns: record.schema.clone(),
+            section: SetupSection::Target,
+            cursor: 0,
+            target: None,
+            group: None,
+            covariates: Vec::new(),
+            markers: Vec::new(),
+        };
+        setup.target = setup.preset(SetupSection::Target, "age");
+        setup.group = setup.preset(SetupSection::Group, "cell_type");
+        setup
+    }
+
+    fn preset(&self, section: SetupSection, name: &str) -> Option<String> {
+        self.options(section)
+            .into_iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+            .map(|c| c.name.clone())
+    }
+
+    /// Columns that can be picked in `section`
+    pub fn options(&self, section: SetupSection) -> Vec<&ColumnSchema> {
+        self.columns.iter().filter(|c| section.accepts(c.kind)).collect()
+    }
+
+    pub fn is_checked(&self, section: SetupSection, column: &str) -> bool {
+        match section {
+            SetupSection::Target => self.target.as_deref() == Some(column),
+            SetupSection::Group => self.group.as_deref() == Some(column),
+            SetupSection::Covariates => self.covariates.iter().any(|c| c == column),
+            SetupSection::Markers => self.markers.iter().any(|c| c == column),
+        }
+    }
+
+    pub fn next_section(&mut self) {
+        let idx = SetupSection::ALL.iter().position(|s| *s == self.section).unwrap_or(0);
+        self.section = SetupSection::ALL[(idx + 1) % SetupSection::ALL.len()];
+        self.cursor = 0;
+    }
+
+    pub fn prev_section(&mut self) {
+        let idx = SetupSection::ALL.iter().position(|s| *s == self.section).unwrap_or(0);
+        self.section = SetupSection::ALL[(idx + SetupSection::ALL.len() - 1) % SetupSection::ALL.len()];
+        self.cursor = 0;
+    }
+
+    pub fn move_cursor(&mut self, down: bool) {
+        let len = self.options(self.section).len();
+        if len == 0 {
+            return;
+        }
+        self.cursor = if down { (self.cursor + 1) % len } else { (self.cursor + len - 1) % len };
+    }
+
+    /// Toggle the highlighted column in the current section
+    pub fn toggle(&mut self) {
+        let Some(column) = self.options(self.section).get(self.cursor).map(|c| c.name.clone()) else {
+            return;
+        };
+        let toggle_in = |list: &mut Vec<String>| match list.iter().position(|c| *c == column) {
+            Some(pos) => {
+                list.remove(pos);
+            }
+            None => list.push(column.clone()),
+        };
+        match self.section {
+            SetupSection::Target => {
+                self.target = if self.target.as_ref() == Some(&column) { None } else { Some(column.clone()) }
+            }
+            SetupSection::Group => {
+                self.group = if self.group.as_ref() == Some(&column) { None } else { Some(column.clone()) }
+            }
+            SetupSection::Covariates => toggle_in(&mut self.covariates),
+            SetupSection::Markers => toggle_in(&mut self.markers),
+        }
+    }
+
+    /// Configuration for `run_analysis`, or why it cannot run yet
+    pub fn config(&self) -> AppResult<AnalysisConfig> {
+        let missing = |what: &str| AppError::Data(format!("Pick a {} column ({} tab).", what.to_lowercase(), what));
+        let target = self.target.clone().ok_or_else(|| missing("Target"))?;
+        let group = self.group.clone().ok_or_else(|| missing("Group"))?;
+        let markers: Vec<String> = self.markers.iter().filter(|c| **c != target).cloned().collect();
+        Ok(AnalysisConfig {
+            covariates: self.covariates.iter().filter(|c| **c != target).cloned().collect(),
+            target_column: Some(target),
+            group_column: Some(group),
+            // The first marker picked gets the box plot
+            boxplot_column: markers.first().cloned(),
+            markers,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotation::Species;
+    use crate::models::UploadedDataset;
+
+    fn column(name: &str, kind: ColumnKind) -> ColumnSchema {
+        ColumnSchema {
+            name: name.to_string(),
+            kind,
+            missing_rate: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_setup_builds_config_from_toggles() {
+        let record = DatasetRecord {
+            dataset: UploadedDataset {
+                id: "d1".to_string(),
+                filename: "data.csv".to_string(),
+                description: String::new(),
+                path: None,
+                content: None,
+                size: None,
+            },
+            local_path: "data.csv".to_string(),
+            content_type: "text/csv".to_string(),
+            delimiter: b',',
+            has_headers: true,
+            columns: vec!["ensembl_id".into(), "Age".into(), "cell_type".into(), "batch".into(), "GENE1".into()],
+            row_count: 0,
+            species: Species::Human,
+            schema: vec![
+                column("ensembl_id", ColumnKind::Identifier),
+                column("Age", ColumnKind::Numeric),
+                column("cell_type", ColumnKind::Categorical),
+                column("batch", ColumnKind::Numeric),
+                column("GENE1", ColumnKind::Numeric),
+            ],
+            parsing: Default::default(),
+            sequence: None,
+        };
+        let mut setup = AnalysisSetup::new(&record);
+        assert_eq!(setup.target.as_deref(), Some("Age"));
+        assert_eq!(setup.group.as_deref(), Some("cell_type"));
+        assert_eq!(setup.options(SetupSection::Group).len(), 2);
+
+        setup.next_section();
+        setup.move_cursor(true);
+        setup.toggle();
+        assert!(setup.config().is_err());
+        setup.toggle();
+
+        setup.next_section();
+        setup.move_cursor(true);
+        setup.toggle();
+        setup.next_section();
+        setup.move_cursor(false);
+        setup.toggle();
+
+        let config = setup.config().unwrap();
+        assert_eq!(config.group_column.as_deref(), Some("cell_type"));
+        assert_eq!(config.covariates, ["batch"]);
+        assert_eq!(config.markers, ["GENE1"]);
+        assert_eq!(config.boxplot_column.as_deref(), Some("GENE1"));
+    }
+}
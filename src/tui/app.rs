@@ -2,21 +2,38 @@
 //!
 //! Contains the main application state and logic for the TUI.
 
-use crate::agents::{self, LiteratureResult, PlanningResult};
-use crate::analysis::{AnalysisConfig, build_manuscript, run_analysis};
-use crate::config::Config;
-use crate::data_registry::{DatasetRecord, DatasetRegistry};
-use crate::models::UploadedDataset;
+use crate::agents::{self, LiteratureResult, PlanStore, PlanningResult, ReplyMode, SavedPlan, TaskStatus};
+use crate::analysis::formula::Formula;
+use crate::analysis::{compare, legends, literature, meta, supplement, threads, AnalysisArtifacts, AnalysisConfig, build_manuscript, run_analysis};
+use crate::annotation::Species;
+use crate::config::{ApprovalGate, Config, LlmTask};
+use crate::manuscript::DraftHistory;
+use crate::project::ProjectStore;
+use crate::data_registry::{DatasetRecord, DatasetRegistry, ParseOptions, UPLOAD_DIR};
+use crate::queue::{alerts, datasets, AnalysisJobPayload, EnqueueOptions, JobPriority, JobProgress, JobQueue, JobType, LiteratureAlertPayload, ScheduleSpec, Scheduler};
+use crate::session::{EventBus, LiveConfig, SessionEvent};
+use crate::types::{AppError, AppResult};
+use crate::storage::disk::{self, DatasetFiles, ARTIFACT_DIR};
+use crate::tui::analysis_setup::AnalysisSetup;
+use crate::tui::onboarding::{Onboarding, OnboardingAction, DEMO_PATH};
+use crate::tui::paths;
+use crate::utils::cancel::{self, CancellationToken};
 use crate::settings::{SettingsStorage, UserSettings};
 use crate::tui::event::AppAction;
 use chrono::{DateTime, Utc};
 use std::time::Instant;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tracing::{error, info, warn};
 use tui_textarea::TextArea;
 use uuid::Uuid;
 
+/// First line of the chat message listing the planned tasks
+const PLAN_HEADING: &str = "Research plan:";
+
+/// Sources listed, with their quality scores, after the literature review
+const SOURCES_SHOWN: usize = 10;
+
 /// Research pipeline stage
 #[derive(Debug, Clone, PartialEq)]
 pub enum PipelineStage {
@@ -84,25 +101,77 @@ pub enum View {
     Chat,
     Settings,
     Help,
+    /// Column picker opened by `/analyze`
+    AnalysisSetup,
+    /// First-run setup wizard
+    Onboarding,
 }
 
 /// Events from async research pipeline
 #[derive(Debug)]
 pub enum AppEvent {
-    /// Pipeline stage changed
-    StageChanged(PipelineStage),
-    /// Current objective updated
-    ObjectiveUpdated(String),
-    /// Response chunk received (for streaming)
-    ResponseChunk(String),
-    /// Response complete
-    ResponseComplete(String),
-    /// Error occurred
-    Error(String),
+    /// Progress of the research pipeline
+    Pipeline(agents::PipelineEvent),
+    /// `action` (e.g. "Planning") failed or was cancelled
+    Error { action: String, error: AppError },
     /// Workflow stage updated
     WorkflowStageUpdated(WorkflowStage),
     /// Add a message to the chat
     WorkflowMessage(MessageRole, String),
+    /// Automated workflow produced draft `version`, working in the feedback
+    DraftRevised(usize, agents::Revision, Vec<String>),
+    /// Automated workflow is waiting at an approval gate for `label`
+    ApprovalRequested { label: String, reply: oneshot::Sender<GateDecision> },
+    /// Status update for a job in the background queue
+    JobProgress(JobProgress),
+    /// Change made through the embedded server (`--serve`)
+    Session(SessionEvent),
+    /// Bytes of the dataset being uploaded copied so far, of `total`
+    UploadProgress { copied: u64, total: u64 },
+    /// Upload finished copying and validating; `auto` starts the workflow
+    DatasetLoaded { record: Box<DatasetRecord>, auto: bool },
+    /// Next file of a multi-file upload started copying
+    UploadStarted(String),
+    /// Multi-file upload finished: the datasets loaded and why the others
+    /// were not; `auto` starts the workflow on the first
+    DatasetsLoaded { records: Vec<DatasetRecord>, failed: Vec<String>, auto: bool },
+    /// Upload failed or was cancelled, with the message to show
+    UploadFailed(String),
+    /// Connection test of the key entered in the first-run wizard finished
+    KeyTested(AppResult<String>),
+    /// Datasets of `project` loaded again from their files, and why the
+    /// others could not be
+    ProjectLoaded { project: String, loaded: usize, failed: Vec<String> },
+}
+
+/// Researcher's answer at an approval gate
+#[derive(Debug, Clone, PartialEq)]
+pub enum GateDecision {
+    Approve,
+    /// Revise the plan or draft with this feedback before asking again
+    Feedback(String),
+}
+
+/// Approval gate the automated workflow is waiting at
+#[derive(Debug)]
+pub struct PendingApproval {
+    pub label: String,
+    reply: oneshot::Sender<GateDecision>,
+}
+
+/// Dataset being copied into `uploads/` in the background
+#[derive(Debug, Clone)]
+pub struct UploadProgress {
+    pub filename: String,
+    pub copied: u64,
+    pub total: u64,
+    cancel: CancellationToken,
+}
+
+impl UploadProgress {
+    pub fn percent(&self) -> u64 {
+        self.copied * 100 / self.total.max(1)
+    }
 }
 
 /// Provider configuration for settings view
@@ -127,6 +196,8 @@ pub enum ApiStatus {
 pub struct App {
     // Configuration
     pub config: Config,
+    /// Where `config` is published for the embedded server and running jobs
+    pub live_config: LiveConfig,
 
     // UI State
     pub view: View,
@@ -167,9 +238,16 @@ pub struct App {
     pub literature_results: Vec<LiteratureResult>,
     pub findings_summary: Option<String>,
     pub manuscript_base: Option<String>,
-    pub draft_versions: Vec<String>,
+    /// Studies, each with its own datasets, analysis runs and drafts
+    pub projects: ProjectStore,
+    /// Saved drafts of the active project; survives restarts
+    pub drafts: DraftHistory,
     pub feedbacks: Vec<String>,
+    /// Gate of the automated workflow waiting for /approve or /feedback
+    pub pending_approval: Option<PendingApproval>,
     pub latex_output: Option<String>,
+    // Analysis behind the manuscript, for the LaTeX table and figures
+    pub last_analysis: Option<AnalysisArtifacts>,
     pub auto_mode: bool,
 
     // Async communication
@@ -179,18 +257,43 @@ pub struct App {
     // Local dataset state for TUI workflows
     pub dataset_registry: DatasetRegistry,
     pub last_dataset_id: Option<String>,
+
+    // Background queue jobs seen by the job watcher (most recent last)
+    pub jobs: Vec<JobProgress>,
+    // Top biomarkers from the latest /analyze run, used by /alert
+    pub last_biomarkers: Vec<String>,
+    // Column choices while the analysis setup screen is open
+    pub analysis_setup: Option<AnalysisSetup>,
+    // First-run wizard, while it is open
+    pub onboarding: Option<Onboarding>,
+    // Cancels the running pipeline or automated workflow (Esc in chat)
+    running: Option<CancellationToken>,
+    // Dataset copy in progress; Esc cancels it
+    pub upload: Option<UploadProgress>,
+    // Latest settings write, which the next one and exit wait for
+    settings_save: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl App {
-    /// Create a new application instance
-    pub fn new(config: Config) -> Self {
+    /// Create a new application instance on `dataset_registry`; changes
+    /// published on `events` (by the embedded server under `--serve`) show
+    /// up in the session, and the config with settings applied is published
+    /// on `live_config`
+    pub fn new(live_config: LiveConfig, dataset_registry: DatasetRegistry, events: &EventBus) -> Self {
+        let config = Config::clone(&live_config.current());
         // Initialize text input
         let mut input = TextArea::default();
         input.set_cursor_line_style(ratatui::style::Style::default());
-        input.set_placeholder_text("Paste dataset path (CSV/TSV with Ensembl ID + Age columns)...");
+        input.set_placeholder_text("Paste dataset path (CSV/TSV/Parquet with Ensembl ID + Age columns)...");
 
-        // Load settings
-        let settings_storage = SettingsStorage::new();
+        // Load settings (from the requested profile, else the active one)
+        let settings_storage = match config.settings_profile.as_deref() {
+            Some(profile) => SettingsStorage::new().with_profile(profile).unwrap_or_else(|e| {
+                warn!("{}; using the active profile", e);
+                SettingsStorage::new()
+            }),
+            None => SettingsStorage::new(),
+        };
         let settings = tokio::task::block_in_place(|| {
             tokio::runtime::Handle::current()
                 .block_on(settings_storage.load())
@@ -212,8 +315,13 @@ impl App {
             timestamp: Utc::now(),
         }];
 
+        let projects = ProjectStore::load_default();
+        let drafts = projects.drafts_path().map_or_else(DraftHistory::default, |path| DraftHistory::load(&path));
+        let last_dataset_id = projects.active().active_dataset.clone();
+
         let mut app = Self {
             config,
+            live_config,
             view: View::Chat,
             should_quit: false,
             messages,
@@ -240,16 +348,29 @@ impl App {
             literature_results: Vec::new(),
             findings_summary: None,
             manuscript_base: None,
-            draft_versions: Vec::new(),
+            projects,
+            drafts,
             feedbacks: Vec::new(),
+            pending_approval: None,
             latex_output: None,
+            last_analysis: None,
             auto_mode: true,
             event_rx: Some(rx),
             event_tx: Some(tx),
-            dataset_registry: DatasetRegistry::default(),
-            last_dataset_id: None,
+            dataset_registry,
+            last_dataset_id,
+            jobs: Vec::new(),
+            last_biomarkers: Vec::new(),
+            analysis_setup: None,
+            onboarding: None,
+            running: None,
+            upload: None,
+            settings_save: None,
         };
 
+        app.spawn_job_watcher();
+        app.spawn_session_listener(events);
+        app.load_project_datasets();
         app.update_config_from_settings();
         app.update_api_status();
         
@@ -270,18 +391,24 @@ impl App {
             "Welcome to Oxidized Bio Research Agent!\n\n\
              API Status: {} | {}\n\n\
              AUTOMATED WORKFLOW\n\
-             Paste a dataset path (.csv or .tsv) to begin automated analysis:\n\
+             Paste a dataset path (.csv, .tsv, .parquet, .vcf, FASTA/FASTQ or GEO series matrix) to begin automated analysis:\n\
              → Upload → Plan → Literature → Findings → Drafts 1-3 → LaTeX\n\n\
              Requirements: Dataset must include Ensembl ID and Age columns.\n\n\
              Examples:\n\
              • /home/user/data/microarray.csv\n\
              • ~/Documents/experiment_data.tsv\n\
              • ./data/samples.csv\n\n\
-             Tip: You can drag & drop a file into the terminal or use tab completion.\n\n\
+             Tip: You can drag & drop one or more files into the terminal or use tab completion.\n\n\
+             Project: {} (/project list shows the others)\n\
              Commands: Type /help for manual commands | Ctrl+S for Settings",
-            llm_status_str, search_status_str
+            llm_status_str, search_status_str, app.projects.active().describe()
         );
-        
+
+        if Onboarding::needed(&app.settings_storage, &app.config) {
+            app.onboarding = Some(Onboarding::new());
+            app.view = View::Onboarding;
+        }
+
         app
     }
 
@@ -378,9 +505,458 @@ impl App {
                     .as_ref()
                     .map(|k| format!("••••{}", &k[k.len().saturating_sub(4)..])),
             },
+            ProviderField {
+                id: "ncbi",
+                name: "NCBI (PubMed)",
+                has_key: settings.search.ncbi_api_key.is_some(),
+                key_hint: settings
+                    .search
+                    .ncbi_api_key
+                    .as_ref()
+                    .map(|k| format!("••••{}", &k[k.len().saturating_sub(4)..])),
+            },
+            ProviderField {
+                id: "semantic_scholar",
+                name: "Semantic Scholar",
+                has_key: settings.search.semantic_scholar_key.is_some(),
+                key_hint: settings
+                    .search
+                    .semantic_scholar_key
+                    .as_ref()
+                    .map(|k| format!("••••{}", &k[k.len().saturating_sub(4)..])),
+            },
         ]
     }
 
+    /// Watch the background job queue and forward status changes as events.
+    ///
+    /// Only runs when a database is configured; workers may live in another
+    /// process, so changes are picked up by polling `jobs.updated_at`. New
+    /// literature alert papers are announced at startup and whenever an alert
+    /// job completes. The same pool is used for usage metering.
+    fn spawn_job_watcher(&self) {
+        if self.config.database.url.is_empty() {
+            return;
+        }
+        let Some(tx) = self.event_tx.clone() else {
+            return;
+        };
+        let db_config = self.config.database.clone();
+
+        tokio::spawn(async move {
+            let pool = match crate::db::create_pool(&db_config).await {
+                Ok(pool) => pool,
+                Err(e) => {
+                    warn!("Job watcher disabled, database unavailable: {}", e);
+                    return;
+                }
+            };
+            crate::metering::install(pool.clone());
+            let queue = JobQueue::new(pool);
+            if !Self::announce_alert_papers(&queue, &tx).await {
+                return;
+            }
+            let mut since = Utc::now() - chrono::Duration::hours(1);
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(2));
+
+            loop {
+                interval.tick().await;
+                match queue.updated_since(since).await {
+                    Ok(jobs) => {
+                        let alert_finished = jobs.iter().any(|j| {
+                            j.job_type == JobType::LiteratureAlert.as_str() && j.status == "completed"
+                        });
+                        for job in jobs {
+                            if let Some(updated) = job.updated_at {
+                                since = since.max(updated);
+                            }
+                            if tx.send(AppEvent::JobProgress(JobProgress::from(&job))).await.is_err() {
+                                return;
+                            }
+                        }
+                        if alert_finished && !Self::announce_alert_papers(&queue, &tx).await {
+                            return;
+                        }
+                    }
+                    Err(e) => warn!("Job watcher poll failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Load a saved settings profile, make it active and apply it
+    async fn switch_profile(&mut self, name: &str) -> String {
+        let storage = match self.settings_storage.clone().with_profile(name) {
+            Ok(storage) => storage,
+            Err(e) => return format!("{}", e),
+        };
+        if !storage.exists() {
+            return format!("No profile named '{}'. Create it with /profile save {}", name, name);
+        }
+        let settings = match storage.load().await {
+            Ok(settings) => settings,
+            Err(e) => return format!("Failed to load profile '{}': {}", name, e),
+        };
+        if let Err(e) = storage.set_active_profile(name).await {
+            warn!("Failed to persist active profile: {}", e);
+        }
+
+        self.settings = settings;
+        self.settings_storage = storage;
+        self.refresh_providers();
+        self.update_config_from_settings();
+        self.update_api_status();
+        format!(
+            "Switched to profile '{}' (LLM: {}).",
+            name,
+            if self.config.llm.active_api_key().is_some() { "ready" } else { "not configured" }
+        )
+    }
+
+    /// Compare the biomarker tables of two analysis runs and describe the result
+    fn compare_runs(a: &str, b: &str) -> anyhow::Result<String> {
+        let (dir_a, dir_b) = (compare::run_dir(a), compare::run_dir(b));
+        let comparison = compare::compare(&compare::read_markers(&dir_a)?, &compare::read_markers(&dir_b)?);
+        let name = |dir: &std::path::Path| {
+            dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+        };
+        let (name_a, name_b) = (name(&dir_a), name(&dir_b));
+        let output_dir = std::path::Path::new("artifacts")
+            .join("compare")
+            .join(format!("{}_vs_{}", name_a, name_b));
+        let files = compare::write_comparison(&output_dir, &comparison, &name_a, &name_b)?;
+
+        let mut text = format!(
+            "Compared {} and {}: {} shared markers, {} only in {}, {} only in {}.\nConcordance (Spearman of r): {}",
+            name_a,
+            name_b,
+            comparison.shared,
+            comparison.only_a(),
+            name_a,
+            comparison.only_b(),
+            name_b,
+            comparison
+                .concordance
+                .map(|rho| format!("{:.3}", rho))
+                .unwrap_or_else(|| "n/a (fewer than 3 shared markers)".to_string())
+        );
+        let moves = comparison.biggest_moves(10);
+        if !moves.is_empty() {
+            text.push_str("\n\nBiggest rank changes:");
+            for m in moves {
+                text.push_str(&format!(
+                    "\n- {}: #{} -> #{} ({:+}, r {:.3} -> {:.3})",
+                    m.label,
+                    m.rank_a.unwrap_or_default(),
+                    m.rank_b.unwrap_or_default(),
+                    m.rank_change().unwrap_or_default(),
+                    m.r_a.unwrap_or_default(),
+                    m.r_b.unwrap_or_default()
+                ));
+            }
+        }
+        text.push_str("\n\nFiles:");
+        for file in files {
+            text.push_str(&format!("\n- {}", file.display()));
+        }
+        Ok(text)
+    }
+
+    /// Pool the biomarker tables of several analysis runs and describe the
+    /// markers with the strongest combined evidence
+    fn meta_analyze_runs(ids: &[String]) -> anyhow::Result<String> {
+        let dirs: Vec<std::path::PathBuf> = ids.iter().map(|id| compare::run_dir(id)).collect();
+        let runs = dirs
+            .iter()
+            .map(|dir| {
+                let name = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                Ok((name, compare::read_markers(dir)?))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let result = meta::meta_analyze(&runs)?;
+        let names: Vec<&str> = runs.iter().map(|(name, _)| name.as_str()).collect();
+        let output_dir = std::path::Path::new("artifacts").join("meta").join(names.join("_"));
+        let files = meta::write_meta(&output_dir, &result, meta::FOREST_PLOTS)?;
+
+        let fdr = crate::analysis::stats::Thresholds::default().fdr;
+        let mut text = format!(
+            "Meta-analysis of {}: {} markers in at least two runs, {} with random-effects q < {}, {} heterogeneous (I² ≥ 50%).",
+            names.join(", "),
+            result.markers.len(),
+            result.significant(fdr).count(),
+            fdr,
+            result.heterogeneous()
+        );
+        if !result.markers.is_empty() {
+            text.push_str("\n\nStrongest pooled markers:");
+            for m in result.markers.iter().take(10) {
+                text.push_str(&format!(
+                    "\n- {}: r = {:.3} [{:.3}, {:.3}] (random), {:.3} (fixed), {}, I² = {:.0}%, {} runs",
+                    m.label,
+                    m.random.r,
+                    m.random.ci[0],
+                    m.random.ci[1],
+                    m.fixed.r,
+                    crate::utils::number::p_quote("q", m.q_value),
+                    m.i_squared * 100.0,
+                    m.studies.len()
+                ));
+            }
+        }
+        text.push_str("\n\nFiles:");
+        for file in files {
+            text.push_str(&format!("\n- {}", file.display()));
+        }
+        Ok(text)
+    }
+
+    /// Run the analysis configured on the setup screen
+    async fn submit_analysis_setup(&mut self) {
+        let Some(setup) = &self.analysis_setup else {
+            return;
+        };
+        let config = match setup.config() {
+            Ok(config) => config,
+            Err(e) => {
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: e.to_string(),
+                    timestamp: Utc::now(),
+                });
+                return;
+            }
+        };
+        let dataset_id = setup.dataset_id.clone();
+        self.analysis_setup = None;
+        self.view = View::Chat;
+        match self.dataset_registry.get(&dataset_id).await {
+            Some(record) => self.run_local_analysis(record, config).await,
+            None => self.messages.push(ChatMessage {
+                role: MessageRole::System,
+                content: format!("Dataset not found: {}", dataset_id),
+                timestamp: Utc::now(),
+            }),
+        }
+        self.scroll_to_bottom();
+    }
+
+    /// What deleting `dataset_id` removes, or with `confirmed`, the outcome
+    /// of deleting its upload, analysis artifacts, registry entry and
+    /// project references
+    async fn delete_dataset(&mut self, dataset_id: &str, confirmed: bool) -> String {
+        let record = self.dataset_registry.get(dataset_id).await;
+        let projects = self.projects.projects();
+        let local_path = match &record {
+            Some(record) => std::path::PathBuf::from(&record.local_path),
+            None => match projects.iter().find_map(|p| p.dataset(dataset_id)) {
+                Some(dataset) => std::path::PathBuf::from(&dataset.path),
+                None => return format!("Dataset not found: {}", dataset_id),
+            },
+        };
+        let mut runs: Vec<std::path::PathBuf> = projects
+            .iter()
+            .flat_map(|p| &p.analyses)
+            .filter(|a| a.dataset_id == dataset_id)
+            .map(|a| a.dir.clone())
+            .collect();
+        runs.push(std::path::Path::new(ARTIFACT_DIR).join("analysis").join(dataset_id));
+        let files = DatasetFiles::find(&local_path, std::path::Path::new(UPLOAD_DIR), runs);
+
+        if !confirmed {
+            let mut lines = vec![format!("Deleting {} removes its registry entry and project references, plus:", dataset_id)];
+            match &files.upload {
+                Some(upload) => lines.push(format!("- uploaded copy {}", upload.display())),
+                None => lines.push(format!("- nothing of {}, which is read in place and kept", local_path.display())),
+            }
+            lines.extend(files.artifacts.iter().map(|dir| format!("- artifacts {}", dir.display())));
+            lines.push(format!(
+                "{} freed. Run /delete {} confirm to delete.",
+                disk::format_size(files.bytes),
+                dataset_id
+            ));
+            return lines.join("\n");
+        }
+        if let Err(e) = files.remove() {
+            return format!("Delete failed: {:#}", e);
+        }
+        self.dataset_registry.remove(dataset_id).await;
+        self.projects.remove_dataset(dataset_id);
+        if self.last_dataset_id.as_deref() == Some(dataset_id) {
+            self.last_dataset_id = None;
+            self.last_analysis = None;
+        }
+        info!(dataset_id, bytes = files.bytes, "Deleted dataset");
+        format!("Deleted {} ({} freed).", dataset_id, disk::format_size(files.bytes))
+    }
+
+    /// Run `config` on `record` in place and post the findings
+    async fn run_local_analysis(&mut self, record: DatasetRecord, config: AnalysisConfig) {
+        let dataset_id = record.dataset.id.clone();
+        let output_dir = std::path::Path::new("artifacts")
+            .join("analysis")
+            .join(&dataset_id);
+        if let Err(e) = tokio::fs::create_dir_all(&output_dir).await {
+            self.messages.push(ChatMessage {
+                role: MessageRole::System,
+                content: format!("Failed to create artifacts dir: {}", e),
+                timestamp: Utc::now(),
+            });
+            return;
+        }
+        let target = config.target_column.clone().unwrap_or_default();
+        let group = config.group_column.clone().unwrap_or_default();
+        let started = std::time::Instant::now();
+        let outcome = run_analysis(&record, &config, &output_dir, &CancellationToken::new());
+        crate::metering::record_analysis(started, &dataset_id);
+        match outcome {
+            Ok(mut result) => {
+                literature::cross_reference(
+                    &self.config,
+                    &mut result,
+                    self.config.cross_reference.top_n,
+                )
+                .await;
+                legends::polish(&self.config, &mut result).await;
+                let template = self.config.manuscript.template;
+                let manuscript = build_manuscript(
+                    &dataset_id,
+                    &target,
+                    &group,
+                    &record,
+                    &result,
+                    template,
+                );
+                let bundle = match supplement::write_supplement(
+                    &output_dir,
+                    &record,
+                    &config,
+                    &result,
+                    template,
+                ) {
+                    Ok(path) => format!("Supplementary bundle: {}", path.display()),
+                    Err(e) => format!("Failed to write supplementary bundle: {}", e),
+                };
+                self.projects.update(|p| p.add_analysis(&dataset_id, &output_dir, &result.summary));
+                self.last_analysis = Some(result.clone());
+                self.last_biomarkers = result
+                    .biomarker_candidates
+                    .iter()
+                    .take(5)
+                    .map(|b| b.human_label().to_string())
+                    .collect();
+                let findings = format!("{}{}", result.findings().to_markdown(), qc_section(&result));
+                self.messages.push(ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: format!("{}\n\n{}\n\n{}", manuscript, findings, bundle),
+                    timestamp: Utc::now(),
+                });
+            }
+            Err(e) => {
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: format!("Analysis failed: {}", e.user_message()),
+                    timestamp: Utc::now(),
+                });
+            }
+        }
+    }
+
+    /// Hand an analysis to the background queue so a worker on another machine
+    /// can run it. Workers do not share disk, so the dataset is stored in the
+    /// queue database once and the job names it by its SHA-256.
+    async fn enqueue_remote_analysis(&mut self, record: DatasetRecord, config: &AnalysisConfig) {
+        if self.config.database.url.is_empty() {
+            self.messages.push(ChatMessage {
+                role: MessageRole::System,
+                content: "Remote analysis needs the background queue (DATABASE_URL not set).".to_string(),
+                timestamp: Utc::now(),
+            });
+            return;
+        }
+        let mut payload = match AnalysisJobPayload::new(&record, config) {
+            Ok(payload) => payload,
+            Err(e) => {
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: e,
+                    timestamp: Utc::now(),
+                });
+                return;
+            }
+        };
+        let bytes = match tokio::fs::read(&record.local_path).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: format!("Failed to read dataset {}: {}", record.local_path, e),
+                    timestamp: Utc::now(),
+                });
+                return;
+            }
+        };
+
+        let db_config = self.config.database.clone();
+        let tx = self.event_tx.clone().unwrap();
+        tokio::spawn(async move {
+            let result = async {
+                let pool = crate::db::create_pool(&db_config).await?;
+                payload.dataset_sha256 = Some(datasets::store(&pool, &payload.filename, &bytes).await?);
+                JobQueue::new(pool)
+                    .enqueue_with(JobType::Analysis, &payload, EnqueueOptions::interactive())
+                    .await
+            }
+            .await;
+            let content = match result {
+                Ok(job) => format!("Queued analysis of {} as job {}. Track it with /jobs.", payload.filename, job.id),
+                Err(e) => format!("Failed to queue analysis: {}", e),
+            };
+            let _ = tx.send(AppEvent::WorkflowMessage(MessageRole::System, content)).await;
+        });
+    }
+
+    /// Post unseen literature alert papers to the chat and mark them notified.
+    /// Returns false once the UI has gone away.
+    async fn announce_alert_papers(queue: &JobQueue, tx: &mpsc::Sender<AppEvent>) -> bool {
+        let papers = match alerts::pending_papers(queue.pool()).await {
+            Ok(papers) => papers,
+            Err(e) => {
+                warn!("Failed to load literature alerts: {}", e);
+                return true;
+            }
+        };
+        if papers.is_empty() {
+            return true;
+        }
+
+        let content = alerts::format_notification(&papers);
+        if tx
+            .send(AppEvent::WorkflowMessage(MessageRole::System, content))
+            .await
+            .is_err()
+        {
+            return false;
+        }
+        let ids: Vec<Uuid> = papers.iter().map(|p| p.id).collect();
+        if let Err(e) = alerts::mark_notified(queue.pool(), &ids).await {
+            warn!("Failed to mark literature alerts as notified: {}", e);
+        }
+        true
+    }
+
+    /// Number of queued and running background jobs
+    pub fn active_job_counts(&self) -> (usize, usize) {
+        let queued = self.jobs.iter().filter(|j| j.status == "queued").count();
+        let running = self.jobs.iter().filter(|j| j.status == "running").count();
+        (queued, running)
+    }
+
+    /// Reply mode picked for the active project; `None` to guess it from
+    /// each message
+    pub fn reply_mode(&self) -> Option<ReplyMode> {
+        self.projects.active().reply_mode
+    }
+
     /// Refresh provider list from current settings
     pub fn refresh_providers(&mut self) {
         self.providers = Self::build_provider_list(&self.settings);
@@ -393,6 +969,29 @@ impl App {
         true
     }
 
+    /// Forward session events from the embedded server to the event loop
+    fn spawn_session_listener(&self, events: &EventBus) {
+        let Some(tx) = self.event_tx.clone() else {
+            return;
+        };
+        let mut rx = events.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if tx.send(AppEvent::Session(event)).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(missed, "Session listener fell behind");
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+
     /// Poll for async events
     pub fn poll_events(&mut self) {
         // Collect events first to avoid borrow checker issues
@@ -417,10 +1016,10 @@ impl App {
     /// Handle an async event
     fn handle_event(&mut self, event: AppEvent) {
         match event {
-            AppEvent::StageChanged(stage) => {
-                self.pipeline_stage = stage;
-            }
             AppEvent::WorkflowStageUpdated(stage) => {
+                if matches!(stage, WorkflowStage::LatexReady | WorkflowStage::Complete) {
+                    self.running = None;
+                }
                 self.workflow_stage = stage;
             }
             AppEvent::WorkflowMessage(role, content) => {
@@ -430,102 +1029,347 @@ impl App {
                     timestamp: Utc::now(),
                 });
             }
-            AppEvent::ObjectiveUpdated(objective) => {
-                self.current_objective = Some(objective);
+            AppEvent::DraftRevised(version, revision, feedback) => {
+                let dataset_id = self.last_dataset_id.clone();
+                self.drafts.push(version, revision.text, feedback, revision.model, dataset_id);
             }
-            AppEvent::ResponseChunk(chunk) => {
-                if self.stream_start.is_none() {
-                    self.stream_start = Some(Instant::now());
-                }
-                self.stream_tokens = self.stream_tokens.saturating_add(Self::estimate_tokens(&chunk));
-                if let Some(start) = self.stream_start {
-                    let elapsed = start.elapsed().as_secs_f32().max(0.001);
-                    self.stream_tps = self.stream_tokens as f32 / elapsed;
-                }
-
-                // Append to last assistant message if streaming
-                if let Some(last) = self.messages.last_mut() {
-                    if last.role == MessageRole::Assistant {
-                        last.content.push_str(&chunk);
-                        return;
-                    }
-                }
-
+            AppEvent::ApprovalRequested { label, reply } => {
+                let timeout = match self.config.workflow.gate_timeout_secs {
+                    0 => String::new(),
+                    secs => format!(" Continuing on its own in {}s.", secs),
+                };
                 self.messages.push(ChatMessage {
-                    role: MessageRole::Assistant,
-                    content: chunk,
+                    role: MessageRole::System,
+                    content: format!(
+                        "Review {} above: /approve to continue, or /feedback <text> to revise it.{}",
+                        label, timeout
+                    ),
                     timestamp: Utc::now(),
                 });
+                self.pending_approval = Some(PendingApproval { label, reply });
             }
-            AppEvent::ResponseComplete(response) => {
-                if self.stream_start.is_some() {
-                    self.last_stream_tps = self.stream_tps;
-                    self.reset_stream_stats();
-                }
-                if let Some(last) = self.messages.last_mut() {
-                    if last.role == MessageRole::Assistant {
-                        last.content = response;
-                    } else {
-                        self.messages.push(ChatMessage {
-                            role: MessageRole::Assistant,
-                            content: response,
-                            timestamp: Utc::now(),
-                        });
-                    }
-                } else {
+            AppEvent::Pipeline(event) => self.handle_pipeline_event(event),
+            AppEvent::JobProgress(progress) => {
+                let finished_now = progress.is_finished()
+                    && !self
+                        .jobs
+                        .iter()
+                        .any(|j| j.job_id == progress.job_id && j.is_finished());
+                if finished_now {
+                    let content = match progress.status.as_str() {
+                        "completed" => format!("Background {} job {} completed.", progress.job_type, progress.job_id),
+                        _ => format!(
+                            "Background {} job {} failed after {} attempt(s): {}",
+                            progress.job_type,
+                            progress.job_id,
+                            progress.attempts,
+                            progress.last_error.clone().unwrap_or_default()
+                        ),
+                    };
                     self.messages.push(ChatMessage {
-                        role: MessageRole::Assistant,
-                        content: response,
+                        role: MessageRole::System,
+                        content,
                         timestamp: Utc::now(),
                     });
                 }
-                self.pipeline_stage = PipelineStage::Complete;
-                self.scroll_to_bottom();
+                self.jobs.retain(|j| j.job_id != progress.job_id);
+                self.jobs.push(progress);
+                if self.jobs.len() > 50 {
+                    let excess = self.jobs.len() - 50;
+                    self.jobs.drain(..excess);
+                }
             }
-            AppEvent::Error(error) => {
-                self.reset_stream_stats();
-                self.pipeline_stage = PipelineStage::Error(error.clone());
+            AppEvent::Session(SessionEvent::DatasetAdded { id, filename, rows, species, source }) => {
+                if species != Species::Human {
+                    self.fetch_annotation(species);
+                }
+                self.last_dataset_id = Some(id.clone());
+                if self.workflow_stage == WorkflowStage::Upload {
+                    self.workflow_stage = WorkflowStage::Planning;
+                }
                 self.messages.push(ChatMessage {
                     role: MessageRole::System,
-                    content: format!("Error: {}", error),
+                    content: format!(
+                        "Dataset received over {}: {}\nRows: {} | Species: {}\nID: {}",
+                        source.to_uppercase(),
+                        filename,
+                        rows,
+                        species,
+                        id
+                    ),
                     timestamp: Utc::now(),
                 });
+                self.scroll_to_bottom();
             }
-        }
-    }
-
-    /// Handle a user action
-    pub async fn handle_action(&mut self, action: AppAction) {
-        match action {
-            AppAction::Quit | AppAction::ForceQuit => {
-                self.should_quit = true;
+            AppEvent::UploadProgress { copied, total } => {
+                if let Some(upload) = &mut self.upload {
+                    upload.copied = copied;
+                    upload.total = total;
+                }
             }
-            AppAction::Submit => {
-                if self.view == View::Settings {
-                    self.save_current_setting().await;
-                } else {
-                    self.submit_message().await;
+            AppEvent::DatasetLoaded { record, auto } => {
+                self.upload = None;
+                self.dataset_loaded(*record, auto);
+                self.scroll_to_bottom();
+            }
+            AppEvent::UploadStarted(filename) => {
+                if let Some(upload) = &mut self.upload {
+                    upload.filename = filename;
+                    upload.copied = 0;
+                    upload.total = 0;
                 }
             }
-            AppAction::ToggleSettings => {
-                self.view = if self.view == View::Settings {
-                    View::Chat
-                } else {
-                    View::Settings
-                };
+            AppEvent::DatasetsLoaded { records, failed, auto } => {
+                self.upload = None;
+                self.datasets_loaded(records, failed, auto);
+                self.scroll_to_bottom();
             }
-            AppAction::ToggleHelp => {
-                self.view = if self.view == View::Help {
-                    View::Chat
-                } else {
+            AppEvent::KeyTested(result) => {
+                if let Some(onboarding) = &mut self.onboarding {
+                    onboarding.tested(result);
+                }
+            }
+            AppEvent::ProjectLoaded { project, loaded, failed } => {
+                let mut content = format!("Project {}: loaded {} dataset(s) again.", project, loaded);
+                for failure in &failed {
+                    content.push_str(&format!("\n✗ {}", failure));
+                }
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                self.scroll_to_bottom();
+            }
+            AppEvent::UploadFailed(content) => {
+                self.upload = None;
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                self.scroll_to_bottom();
+            }
+            AppEvent::Session(SessionEvent::SettingsChanged) => {
+                let loaded = tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current().block_on(self.settings_storage.load())
+                });
+                let content = match loaded {
+                    Ok(settings) => {
+                        self.settings = settings;
+                        self.refresh_providers();
+                        self.update_config_from_settings();
+                        self.update_api_status();
+                        "Settings were updated over HTTP and reloaded.".to_string()
+                    }
+                    Err(e) => format!("Settings were updated over HTTP but could not be reloaded: {}", e),
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+            }
+            AppEvent::Error { action, error } => match error {
+                AppError::Cancelled => self.show_error(format!("{} cancelled", action)),
+                error => self.show_error(format!("{} failed: {}", action, error.user_message())),
+            },
+        }
+    }
+
+    /// End the running job with `error` in the status bar and chat
+    fn show_error(&mut self, error: String) {
+        self.running = None;
+        self.pending_approval = None;
+        self.reset_stream_stats();
+        self.pipeline_stage = PipelineStage::Error(error.clone());
+        self.messages.push(ChatMessage {
+            role: MessageRole::System,
+            content: format!("Error: {}", error),
+            timestamp: Utc::now(),
+        });
+    }
+
+    /// Reflect research pipeline progress in the status bar and chat
+    fn handle_pipeline_event(&mut self, event: agents::PipelineEvent) {
+        use agents::{PipelineEvent, PipelineStep};
+        match event {
+            PipelineEvent::StageStarted { stage: PipelineStep::Planning } => {
+                self.pipeline_stage = PipelineStage::Planning;
+            }
+            PipelineEvent::StageStarted { stage: PipelineStep::Generating } => {
+                self.pipeline_stage = PipelineStage::Generating;
+            }
+            // Shown per task
+            PipelineEvent::StageStarted { stage: PipelineStep::Literature } => {}
+            PipelineEvent::ObjectiveSet { objective } => {
+                self.current_objective = Some(objective);
+            }
+            // Listed in one message that grows as the plan streams in
+            PipelineEvent::TaskPlanned { index, task_type, objective } => {
+                let line = format!("{}. [{}] {}", index + 1, task_type, objective);
+                match self.messages.last_mut() {
+                    Some(last) if index > 0 && last.role == MessageRole::System && last.content.starts_with(PLAN_HEADING) => {
+                        last.content.push('\n');
+                        last.content.push_str(&line);
+                    }
+                    _ => self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: format!("{}\n{}", PLAN_HEADING, line),
+                        timestamp: Utc::now(),
+                    }),
+                }
+            }
+            PipelineEvent::TaskStarted { index, total, objective, .. } => {
+                self.pipeline_stage = PipelineStage::Literature {
+                    task_index: index,
+                    total,
+                    current_task: objective,
+                };
+            }
+            PipelineEvent::TaskFinished { objective, error: Some(error), .. } => {
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: format!("Literature search failed for \"{}\": {}", objective, error),
+                    timestamp: Utc::now(),
+                });
+            }
+            PipelineEvent::TaskFinished { .. } => {}
+            PipelineEvent::Token { text } => self.append_response_chunk(text),
+            PipelineEvent::Usage { stage, tokens, cost_usd } => {
+                info!(?stage, tokens, cost_usd, "Pipeline stage usage");
+            }
+            PipelineEvent::ArtifactCreated { name, path } => {
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: format!("Saved {}: {}", name, path),
+                    timestamp: Utc::now(),
+                });
+            }
+            PipelineEvent::Completed { response } => self.complete_response(response),
+            PipelineEvent::Failed { error, .. } => self.show_error(error),
+        }
+    }
+
+    /// Append streamed text to the assistant message being written
+    fn append_response_chunk(&mut self, chunk: String) {
+        if self.stream_start.is_none() {
+            self.stream_start = Some(Instant::now());
+        }
+        self.stream_tokens = self.stream_tokens.saturating_add(Self::estimate_tokens(&chunk));
+        if let Some(start) = self.stream_start {
+            let elapsed = start.elapsed().as_secs_f32().max(0.001);
+            self.stream_tps = self.stream_tokens as f32 / elapsed;
+        }
+
+        // Append to last assistant message if streaming
+        if let Some(last) = self.messages.last_mut() {
+            if last.role == MessageRole::Assistant {
+                last.content.push_str(&chunk);
+                return;
+            }
+        }
+
+        self.messages.push(ChatMessage {
+            role: MessageRole::Assistant,
+            content: chunk,
+            timestamp: Utc::now(),
+        });
+    }
+
+    /// Replace the streamed text with the final response
+    fn complete_response(&mut self, response: String) {
+        self.running = None;
+        if self.stream_start.is_some() {
+            self.last_stream_tps = self.stream_tps;
+            self.reset_stream_stats();
+        }
+        match self.messages.last_mut() {
+            Some(last) if last.role == MessageRole::Assistant => last.content = response,
+            _ => self.messages.push(ChatMessage {
+                role: MessageRole::Assistant,
+                content: response,
+                timestamp: Utc::now(),
+            }),
+        }
+        self.pipeline_stage = PipelineStage::Complete;
+        self.scroll_to_bottom();
+    }
+
+    /// Handle a user action
+    pub async fn handle_action(&mut self, action: AppAction) {
+        match action {
+            AppAction::Quit | AppAction::ForceQuit => {
+                self.should_quit = true;
+            }
+            AppAction::Submit => {
+                if self.view == View::Settings {
+                    self.save_current_setting().await;
+                } else if self.view == View::Onboarding {
+                    if let Some(action) = self.onboarding.as_mut().map(Onboarding::submit) {
+                        self.onboarding_action(action);
+                    }
+                } else if self.view == View::AnalysisSetup {
+                    self.submit_analysis_setup().await;
+                } else {
+                    self.submit_message().await;
+                }
+            }
+            // The wizard is closed with Esc only
+            AppAction::ToggleSettings | AppAction::ToggleHelp if self.view == View::Onboarding => {}
+            AppAction::ToggleSettings => {
+                self.view = if self.view == View::Settings {
+                    View::Chat
+                } else {
+                    View::Settings
+                };
+            }
+            AppAction::CycleReplyMode => {
+                if self.view == View::Chat {
+                    let mode = ReplyMode::cycle(self.reply_mode());
+                    self.projects.update(|p| p.reply_mode = mode);
+                }
+            }
+            AppAction::ToggleHelp => {
+                self.view = if self.view == View::Help {
+                    View::Chat
+                } else {
                     View::Help
                 };
             }
             AppAction::Escape => {
-                if self.view != View::Chat {
+                if self.view == View::Onboarding {
+                    self.finish_onboarding(false);
+                } else if self.view != View::Chat {
                     self.view = View::Chat;
                     self.settings_show_input = false;
                     self.settings_input.clear();
+                    self.analysis_setup = None;
+                } else if let Some(upload) = &self.upload {
+                    upload.cancel.cancel();
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: format!("Cancelling the copy of {}...", upload.filename),
+                        timestamp: Utc::now(),
+                    });
+                } else if let Some(cancel) = self.running.take() {
+                    cancel.cancel();
+                    self.pending_approval = None;
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "Cancelling the running task...".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                }
+            }
+            AppAction::ScrollUp | AppAction::ScrollDown if self.view == View::AnalysisSetup => {
+                if let Some(setup) = &mut self.analysis_setup {
+                    setup.move_cursor(matches!(action, AppAction::ScrollDown));
+                }
+            }
+            AppAction::ScrollUp | AppAction::ScrollDown if self.view == View::Onboarding => {
+                if let Some(onboarding) = &mut self.onboarding {
+                    onboarding.move_cursor(matches!(action, AppAction::ScrollDown));
                 }
             }
             AppAction::ScrollUp => {
@@ -542,6 +1386,25 @@ impl App {
             AppAction::ScrollPageDown => {
                 self.scroll_offset = (self.scroll_offset + 10).min(self.max_scroll);
             }
+            AppAction::NextField | AppAction::PrevField if self.view == View::AnalysisSetup => {
+                if let Some(setup) = &mut self.analysis_setup {
+                    if matches!(action, AppAction::NextField) {
+                        setup.next_section();
+                    } else {
+                        setup.prev_section();
+                    }
+                }
+            }
+            AppAction::NextField | AppAction::PrevField if self.view == View::Onboarding => {
+                if let Some(onboarding) = &mut self.onboarding {
+                    if matches!(action, AppAction::NextField) {
+                        let action = onboarding.skip();
+                        self.onboarding_action(action);
+                    } else {
+                        onboarding.back();
+                    }
+                }
+            }
             AppAction::NextField => {
                 if self.view == View::Settings {
                     self.settings_field_index =
@@ -596,6 +1459,20 @@ impl App {
                 // Enter edit mode for the selected provider
                 self.settings_show_input = true;
             }
+        } else if self.view == View::Onboarding {
+            if let Some(onboarding) = &mut self.onboarding {
+                match key.code {
+                    KeyCode::Char(c) => onboarding.push(c),
+                    KeyCode::Backspace => onboarding.pop(),
+                    _ => {}
+                }
+            }
+        } else if self.view == View::AnalysisSetup {
+            if key.code == KeyCode::Char(' ') {
+                if let Some(setup) = &mut self.analysis_setup {
+                    setup.toggle();
+                }
+            }
         } else if self.view == View::Chat {
             // Chat input mode - delegate to textarea
             self.input.input(key);
@@ -615,7 +1492,7 @@ impl App {
         self.input = TextArea::default();
         // Update placeholder based on workflow stage
         let placeholder = match self.workflow_stage {
-            WorkflowStage::Upload => "Paste dataset path (CSV/TSV with Ensembl ID + Age columns)...",
+            WorkflowStage::Upload => "Paste dataset path (CSV/TSV/Parquet with Ensembl ID + Age columns)...",
             _ => "Type a question or /help for commands...",
         };
         self.input.set_placeholder_text(placeholder);
@@ -627,45 +1504,22 @@ impl App {
             timestamp: Utc::now(),
         });
 
+        let upload_stage = self.auto_mode && self.workflow_stage == WorkflowStage::Upload;
+        // Files dropped on the terminal, checked before slash commands as
+        // absolute paths start with '/'
+        if let Some(dropped) = paths::dropped(&content) {
+            self.start_upload(dropped, None, ParseOptions::default(), upload_stage);
+            self.scroll_to_bottom();
+            return;
+        }
+
         if self.handle_slash_command(&content).await {
             self.scroll_to_bottom();
             return;
         }
 
-        if self.auto_mode && self.workflow_stage == WorkflowStage::Upload {
-            match self
-                .load_dataset_from_path(&content, None)
-                .await
-            {
-                Ok(record) => {
-                    self.last_dataset_id = Some(record.dataset.id.clone());
-                    self.dataset_registry.insert(record.clone()).await;
-                    self.messages.push(ChatMessage {
-                        role: MessageRole::System,
-                        content: format!(
-                            "Dataset loaded: {}\nRows: {} | Columns: {}\nID: {}\nAuto workflow starting...",
-                            record.dataset.filename,
-                            record.row_count,
-                            record.columns.len(),
-                            record.dataset.id
-                        ),
-                        timestamp: Utc::now(),
-                    });
-                    let tx = self.event_tx.clone().unwrap();
-                    let config = self.config.clone();
-                    tokio::spawn(async move {
-                        Self::run_automated_workflow(record, config, tx).await;
-                    });
-                    self.workflow_stage = WorkflowStage::Planning;
-                }
-                Err(e) => {
-                    self.messages.push(ChatMessage {
-                        role: MessageRole::System,
-                        content: format!("Upload failed: {}", e),
-                        timestamp: Utc::now(),
-                    });
-                }
-            }
+        if upload_stage {
+            self.start_upload(paths::split(&content), None, ParseOptions::default(), true);
             self.scroll_to_bottom();
             return;
         }
@@ -712,10 +1566,19 @@ impl App {
         // Get event sender
         let tx = self.event_tx.clone().unwrap();
         let config = self.config.clone();
+        let cancel = self.start_run();
+        // The project's brief and glossary and the summaries of loaded
+        // sequence files, for planning and the reply to draw on
+        let state = agents::data_state(
+            &content,
+            self.projects.active().prompt_context(),
+            self.reply_mode(),
+            self.dataset_registry.artifacts().await,
+        );
 
         // Spawn async research task
         tokio::spawn(async move {
-            Self::run_research_pipeline(content, config, tx).await;
+            Self::run_research_pipeline(content, state, config, cancel, tx).await;
         });
 
         self.scroll_to_bottom();
@@ -733,14 +1596,46 @@ impl App {
                 self.messages.push(ChatMessage {
                     role: MessageRole::System,
                     content: "Commands:\n\
-/upload <path> [description]\n\
-/list (list loaded datasets)\n\
+/upload <path> [more paths] [decimal=,] [na=NA,-] [quote='] [description]\n\
+/project [list | new <name> [description] | switch <name>] (studies, each with its own datasets, analyses and drafts)\n\
+/project brief [text | clear], /project glossary [term = definition] (context added to every question)\n\
+/mode [auto | answer | report | chat] (reply format; Ctrl+R cycles, auto guesses it from each message)\n\
+/list (datasets of the active project)\n\
+/jobs (show background queue jobs)\n\
+/route [task provider [model] | task temp=0.3 max_tokens=2048 | task default] (planning|literature|reply|drafting)\n\
+/profile [use|save|delete <name>] (settings profiles)\n\
+/proxy [set <url> | ca <pem> | <provider> <url> | off] (network settings)\n\
+/budget [set <limit> <value> | clear [limit]] (spend and SerpAPI quotas)\n\
+/openrouter [set <control> <value> | clear [control]] (OpenRouter vendors, fallback models, attribution)\n\
+/usage (metered usage this month)\n\
+/models [refresh] (context and output limits of each task's model)\n\
+/logs [path] (log files; path prints the current log)\n\
+/search [<engine> on|off | <engine> max <n> | email <addr>] (scholar|light|pubmed|semantic_scholar)\n\
+/alert <@weekly|@daily|every 3d> <query> (recurring search for top biomarkers)\n\
 /use <dataset_id>\n\
-/analyze [dataset_id] [target=age] [group=cell_type] [box=marker_1] [cov=batch,sex]\n\
+/delete <dataset_id> [confirm] (remove a dataset's upload, artifacts and registry entry)\n\
+/storage (space used by uploads, artifacts, caches and logs)\n\
+/compare <analysis_a> <analysis_b> (rank changes and concordance of two runs' biomarkers)\n\
+/meta [analysis ...] (pool runs' marker correlations; all analysed datasets by default)\n\
+/analyze [dataset_id] (pick target, group, covariates and markers on a setup screen)\n\
+/panel [max_size] [target=age] (minimal marker panel predicting the target, with CV performance)\n\
+/analyze [dataset_id] [target=age] [group=cell_type] [box=marker_1] [cov=batch,sex] [platform=<file>] [collapse=max|mean] [replicates=mean|median|keep] [impute=off|mean|median|knn|knn:5] [normalize=log2,zscore,quantile] [batch=<column>] [batch_correction=combat|center] [corr=pearson|spearman|kendall] [tables=csv|tsv] [numbers=plain|en|de|fr|ch] [alpha=0.05] [fdr=0.05] [min_effect=0] [kruskal] [mem=auto|off|4G] [threads=8] [penalty=ridge|lasso|elastic-net|off] [l1_ratio=0.5] [lambda=0.1] [roc=auto|median|off|<cut-off>] [gene_sets=a.gmt,b.gmt] [time=<column> event=<column>] [subject=<column>] [remote]\n\
+/analyze [dataset_id] [options] formula=age ~ marker + sex + batch (rank markers adjusted for covariates; or formula=sex|batch|demographics|full)\n\
+/genes [update | <ensembl_id>] (gene symbol annotation)\n\
+/species [human|mouse|rat] (species of the active dataset)\n\
  /status (show workflow stage)\n\
  /next (advance workflow stage)\n\
  /feedback <text>\n\
+ /approve (continue the automated workflow at a gate)\n\
+ /resume [dataset_id] (continue an interrupted automated workflow from its saved plan)\n\
+ /gates [plan,findings,drafts|all|none] [timeout] (approval gates)\n\
+ /drafts (list saved drafts)\n\
+ /rollback <n> (make an earlier draft current)\n\
+ /template [generic|aging_cell|plos|biorxiv] (journal template)\n\
  /latex (render LaTeX for latest draft)\n\
+ /export [md|docx] (Markdown/Word with CSL citations)\n\
+ /csl [apa|vancouver|<file.csl>] (citation style for /export)\n\
+ /pdf (compile the latest draft to PDF)\n\
 Tip: run /upload first, then /analyze."
                         .to_string(),
                     timestamp: Utc::now(),
@@ -748,67 +1643,72 @@ Tip: run /upload first, then /analyze."
                 return true;
             }
             "/upload" => {
-                let path = parts.next();
-                if path.is_none() {
+                let (files, options) = paths::leading(&content[cmd.len()..]);
+                if files.is_empty() {
                     self.messages.push(ChatMessage {
                         role: MessageRole::System,
-                        content: "Usage: /upload <path> [description]".to_string(),
+                        content: "Usage: /upload <path> [more paths] [decimal=,] [na=NA,-] [quote='] [description]".to_string(),
                         timestamp: Utc::now(),
                     });
                     return true;
                 }
-                let description = parts.collect::<Vec<_>>().join(" ");
-                match self
-                    .load_dataset_from_path(
-                        path.unwrap(),
-                        if description.is_empty() { None } else { Some(description) },
-                    )
-                    .await
-                {
-                    Ok(record) => {
-                        self.last_dataset_id = Some(record.dataset.id.clone());
-                        self.dataset_registry.insert(record.clone()).await;
-                        self.workflow_stage = WorkflowStage::Planning;
-                        self.messages.push(ChatMessage {
-                            role: MessageRole::System,
-                            content: format!(
-                                "Dataset loaded: {}\nRows: {} | Columns: {}\nID: {}",
-                                record.dataset.filename,
-                                record.row_count,
-                                record.columns.len(),
-                                record.dataset.id
-                            ),
-                            timestamp: Utc::now(),
-                        });
-                    }
-                    Err(e) => {
-                        self.messages.push(ChatMessage {
-                            role: MessageRole::System,
-                            content: format!("Upload failed: {}", e),
-                            timestamp: Utc::now(),
-                        });
+                let mut parsing = ParseOptions::default();
+                let mut words = Vec::new();
+                for part in options.split_whitespace() {
+                    match part.split_once('=') {
+                        Some((name @ ("decimal" | "na" | "quote"), value)) => {
+                            if let Err(e) = parsing.set(name, value) {
+                                self.messages.push(ChatMessage {
+                                    role: MessageRole::System,
+                                    content: format!("Upload failed: {}", e),
+                                    timestamp: Utc::now(),
+                                });
+                                return true;
+                            }
+                        }
+                        _ => words.push(part),
                     }
                 }
+                let description = words.join(" ");
+                self.start_upload(
+                    files,
+                    if description.is_empty() { None } else { Some(description) },
+                    parsing,
+                    false,
+                );
                 return true;
             }
             "/list" => {
-                let mut list = String::new();
+                let project = self.projects.active();
+                let mut list = format!("Project: {}\n", project.describe());
                 if let Some(last_id) = &self.last_dataset_id {
                     list.push_str(&format!("Active dataset: {}\n", last_id));
                 }
-                let datasets = self.dataset_registry.snapshot().await;
-                if datasets.is_empty() {
-                    list.push_str("No datasets loaded.");
-                } else {
-                    for record in datasets {
-                        list.push_str(&format!(
-                            "- {} ({}, rows: {})\n",
+                let (datasets, others): (Vec<DatasetRecord>, Vec<DatasetRecord>) = self
+                    .dataset_registry
+                    .snapshot()
+                    .await
+                    .into_iter()
+                    .partition(|record| project.contains(&record.dataset.id));
+                if project.datasets.is_empty() {
+                    list.push_str("No datasets in this project.");
+                }
+                for dataset in &project.datasets {
+                    match datasets.iter().find(|record| record.dataset.id == dataset.id) {
+                        Some(record) => list.push_str(&format!(
+                            "- {} ({}, rows: {}, {})\n",
                             record.dataset.id,
                             record.dataset.filename,
-                            record.row_count
-                        ));
+                            record.row_count,
+                            record.species
+                        )),
+                        None => list.push_str(&format!("- {} ({}, not loaded)\n", dataset.id, dataset.filename)),
                     }
                 }
+                if !others.is_empty() {
+                    let ids: Vec<&str> = others.iter().map(|record| record.dataset.id.as_str()).collect();
+                    list.push_str(&format!("\nLoaded outside this project (/use <id> adds one): {}", ids.join(", ")));
+                }
                 self.messages.push(ChatMessage {
                     role: MessageRole::System,
                     content: list,
@@ -816,9 +1716,204 @@ Tip: run /upload first, then /analyze."
                 });
                 return true;
             }
+            "/project" => {
+                let content = match parts.next() {
+                    None | Some("list") => {
+                        let active = self.projects.active().name.clone();
+                        let lines: Vec<String> = self
+                            .projects
+                            .projects()
+                            .iter()
+                            .map(|p| format!("{} {}", if p.name == active { "*" } else { " " }, p.describe()))
+                            .collect();
+                        format!("Projects (* = active):\n{}\nUse /project switch <name> to open another.", lines.join("\n"))
+                    }
+                    Some(action @ ("new" | "switch")) if self.running.is_some() => {
+                        format!("Stop the running workflow (Esc) before /project {}.", action)
+                    }
+                    Some("new") => match parts.next() {
+                        Some(name) => {
+                            let description = parts.collect::<Vec<_>>().join(" ");
+                            match self.projects.create(name, &description) {
+                                Ok(_) => self.open_project(),
+                                Err(e) => e.to_string(),
+                            }
+                        }
+                        None => "Usage: /project new <name> [description]".to_string(),
+                    },
+                    Some("switch") => match parts.next() {
+                        Some(name) => match self.projects.switch(name) {
+                            Ok(_) => self.open_project(),
+                            Err(e) => e.to_string(),
+                        },
+                        None => "Usage: /project switch <name>".to_string(),
+                    },
+                    Some("brief") => {
+                        let brief = parts.collect::<Vec<_>>().join(" ");
+                        let name = self.projects.active().name.clone();
+                        if brief.is_empty() {
+                            match self.projects.active().brief.as_str() {
+                                "" => format!("Project {} has no brief. /project brief <text> sets one.", name),
+                                brief => format!("Brief of {}:\n{}", name, brief),
+                            }
+                        } else {
+                            let brief = if brief == "clear" { String::new() } else { brief };
+                            let mut result = Ok(());
+                            self.projects.update(|p| result = p.set_brief(&brief));
+                            match result {
+                                Ok(()) if brief.is_empty() => format!("Removed the brief of {}.", name),
+                                Ok(()) => format!("Brief of {} saved; questions in this project now start from it.", name),
+                                Err(e) => e.to_string(),
+                            }
+                        }
+                    }
+                    Some("glossary") => {
+                        let entry = parts.collect::<Vec<_>>().join(" ");
+                        match entry.split_once('=') {
+                            None if entry.is_empty() => {
+                                let project = self.projects.active();
+                                if project.glossary.is_empty() {
+                                    format!(
+                                        "Project {} has no glossary. /project glossary <term> = <definition> adds a term.",
+                                        project.name
+                                    )
+                                } else {
+                                    let terms: Vec<String> =
+                                        project.glossary.iter().map(|(term, def)| format!("  {}: {}", term, def)).collect();
+                                    format!("Glossary of {}:\n{}", project.name, terms.join("\n"))
+                                }
+                            }
+                            None => "Usage: /project glossary <term> = <definition> (empty definition removes it)"
+                                .to_string(),
+                            Some((term, definition)) => {
+                                let term = term.trim().to_string();
+                                let mut changed = false;
+                                self.projects.update(|p| changed = p.define(&term, definition));
+                                match (changed, definition.trim().is_empty()) {
+                                    (true, true) => format!("Removed {} from the glossary.", term),
+                                    (true, false) => format!("Defined {} for this project's questions.", term),
+                                    (false, _) => format!("No glossary term '{}'.", term),
+                                }
+                            }
+                        }
+                    }
+                    Some(_) => {
+                        "Usage: /project [list | new <name> [description] | switch <name> | brief [text] | glossary [term = definition]]"
+                            .to_string()
+                    }
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/mode" => {
+                let content = match parts.next() {
+                    None => format!(
+                        "Reply mode: {}. Use /mode auto|answer|report|chat or Ctrl+R to change it.",
+                        self.reply_mode().map_or("auto", |m| m.id())
+                    ),
+                    Some(mode) => {
+                        let mode = match mode {
+                            "auto" => Ok(None),
+                            mode => mode.parse::<ReplyMode>().map(Some),
+                        };
+                        match mode {
+                            Ok(mode) => {
+                                self.projects.update(|p| p.reply_mode = mode);
+                                match mode {
+                                    Some(mode) => format!("Replies in this project now use {} mode.", mode),
+                                    None => "Reply mode is guessed from each message again.".to_string(),
+                                }
+                            }
+                            Err(e) => format!("{}; or auto to guess it from each message", e),
+                        }
+                    }
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/compare" => {
+                let content = match (parts.next(), parts.next()) {
+                    (Some(a), Some(b)) => Self::compare_runs(a, b)
+                        .unwrap_or_else(|e| format!("Comparison failed: {:#}", e)),
+                    _ => "Usage: /compare <analysis_a> <analysis_b> (dataset IDs or run directories)".to_string(),
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/meta" => {
+                let mut ids: Vec<String> = parts.map(str::to_string).collect();
+                if ids.is_empty() {
+                    // Every dataset of the project with an analysis run
+                    ids = self
+                        .projects
+                        .active()
+                        .datasets
+                        .iter()
+                        .map(|dataset| dataset.id.clone())
+                        .filter(|id| compare::read_markers(&compare::run_dir(id)).is_ok())
+                        .collect();
+                }
+                let content = Self::meta_analyze_runs(&ids).unwrap_or_else(|e| format!("Meta-analysis failed: {:#}", e));
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/panel" => {
+                let mut options = crate::analysis::panel::PanelOptions::default();
+                let mut target = "age".to_string();
+                for part in parts {
+                    if let Some(column) = part.strip_prefix("target=") {
+                        target = column.to_string();
+                    } else if let Ok(size) = part.parse() {
+                        options.max_size = size;
+                    }
+                }
+                let record = match &self.last_dataset_id {
+                    Some(id) => self.dataset_registry.get(id).await,
+                    None => None,
+                };
+                let content = match (record, &self.last_analysis) {
+                    (Some(record), Some(analysis)) => {
+                        let dir = compare::run_dir(&record.dataset.id);
+                        crate::analysis::panel::optimize(&record, &dir, analysis, &target, &options)
+                            .and_then(|panel| {
+                                crate::analysis::panel::write_panel(&dir, &panel)?;
+                                Ok(format!("{}\nSaved to {}", panel.summary(), dir.display()))
+                            })
+                            .unwrap_or_else(|e| format!("Panel selection failed: {:#}", e))
+                    }
+                    _ => "Run /analyze on a dataset first.".to_string(),
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
             "/use" => {
                 if let Some(id) = parts.next() {
                     self.last_dataset_id = Some(id.to_string());
+                    // Datasets from the embedded server or another project
+                    // join the active one
+                    if let Some(record) = self.dataset_registry.get(id).await {
+                        self.projects.update(|p| p.add_dataset(&record));
+                    }
                     self.messages.push(ChatMessage {
                         role: MessageRole::System,
                         content: format!("Active dataset set to {}", id),
@@ -833,29 +1928,274 @@ Tip: run /upload first, then /analyze."
                 }
                 return true;
             }
-            "/analyze" => {
-                let dataset_id = parts
-                    .next()
-                    .map(|s| s.to_string())
-                    .or_else(|| self.last_dataset_id.clone());
-                if dataset_id.is_none() {
-                    self.messages.push(ChatMessage {
+            "/delete" => {
+                let content = match parts.next() {
+                    Some(id) => self.delete_dataset(id, parts.next() == Some("confirm")).await,
+                    None => "Usage: /delete <dataset_id> [confirm]".to_string(),
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/storage" => {
+                let areas = disk::usage();
+                let total: u64 = areas.iter().map(|a| a.bytes).sum();
+                let mut content = format!("Storage used: {}", disk::format_size(total));
+                for area in &areas {
+                    let paths: Vec<String> = area.paths.iter().map(|p| p.display().to_string()).collect();
+                    content.push_str(&format!(
+                        "\n- {}: {} in {} file(s) ({})",
+                        area.name,
+                        disk::format_size(area.bytes),
+                        area.files,
+                        paths.join(", ")
+                    ));
+                }
+                content.push_str("\n/delete <dataset_id> frees a dataset's upload and artifacts; /logs shows log retention.");
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/analyze" => {
+                let dataset_id = parts
+                    .next()
+                    .map(|s| s.to_string())
+                    .or_else(|| self.last_dataset_id.clone());
+                if dataset_id.is_none() {
+                    self.messages.push(ChatMessage {
                         role: MessageRole::System,
-                        content: "Usage: /analyze <dataset_id> [target=age] [group=cell_type] [box=marker_1] [cov=batch,sex]".to_string(),
+                        content: "Usage: /analyze <dataset_id> [target=age] [group=cell_type] [box=marker_1] [cov=batch,sex] [platform=GPL570.soft] [collapse=max|mean] [replicates=mean|median|keep] [impute=off|mean|median|knn|knn:5] [normalize=log2,zscore,quantile] [batch=<column>] [batch_correction=combat|center] [corr=pearson|spearman|kendall] [tables=csv|tsv] [numbers=plain|en|de|fr|ch] [alpha=0.05] [fdr=0.05] [min_effect=0] [kruskal] [mem=auto|off|4G] [threads=8] [penalty=ridge|lasso|elastic-net|off] [l1_ratio=0.5] [lambda=0.1] [roc=auto|median|off|<cut-off>] [gene_sets=a.gmt,b.gmt] [time=<column> event=<column>] [subject=<column>] [remote] [formula=age ~ marker + sex]".to_string(),
                         timestamp: Utc::now(),
                     });
                     return true;
                 }
+                let mut options: Vec<&str> = parts.collect();
+                if options.is_empty() {
+                    // No options: pick the columns on the setup screen
+                    let dataset_id = dataset_id.unwrap();
+                    match self.dataset_registry.get(&dataset_id).await {
+                        Some(record) => {
+                            self.analysis_setup = Some(AnalysisSetup::new(&record));
+                            self.view = View::AnalysisSetup;
+                        }
+                        None => self.messages.push(ChatMessage {
+                            role: MessageRole::System,
+                            content: format!("Dataset not found: {}", dataset_id),
+                            timestamp: Utc::now(),
+                        }),
+                    }
+                    return true;
+                }
+                // formula= takes the rest of the line, since formulas contain spaces
+                let formula_spec = options.iter().position(|part| part.starts_with("formula=")).map(|pos| {
+                    let spec = options[pos..].join(" ");
+                    options.truncate(pos);
+                    spec["formula=".len()..].to_string()
+                });
                 let mut target = "age".to_string();
                 let mut group = "cell_type".to_string();
                 let mut boxplot = None;
                 let mut covariates: Vec<String> = Vec::new();
-                for part in parts {
-                    if let Some((k, v)) = part.split_once('=') {
+                let mut platform = None;
+                let mut gene_sets = Vec::new();
+                let mut time_column = None;
+                let mut event_column = None;
+                let mut subject_column = None;
+                let mut collapse = crate::annotation::Collapse::default();
+                let mut replicates = crate::analysis::qc::Replicates::default();
+                let mut imputation = crate::analysis::impute::Imputation::default();
+                let mut normalization = Vec::new();
+                let mut batch_column = None;
+                let mut batch_correction = crate::analysis::batch::BatchCorrection::default();
+                let mut correlation = crate::analysis::stats::CorrelationMethod::default();
+                let mut tables = crate::analysis::report::TableFormat::default();
+                let mut numbers = crate::utils::number::NumberFormat::default();
+                let mut thresholds = crate::analysis::stats::Thresholds::default();
+                let mut kruskal_wallis = false;
+                let mut memory_limit = crate::analysis::memory::MemoryLimit::default();
+                let mut threads = 0;
+                let mut regularization = crate::analysis::regularized::Regularization::default();
+                let mut roc = crate::analysis::roc::RocSplit::default();
+                let mut remote = false;
+                for part in options {
+                    if part == "remote" {
+                        remote = true;
+                    } else if part == "kruskal" {
+                        kruskal_wallis = true;
+                    } else if let Some((k, v)) = part.split_once('=') {
                         match k {
                             "target" => target = v.to_string(),
                             "group" => group = v.to_string(),
                             "box" => boxplot = Some(v.to_string()),
+                            "batch" => batch_column = Some(v.to_string()),
+                            "platform" => platform = Some(std::path::PathBuf::from(v)),
+                            "gene_sets" => gene_sets = v.split(',').map(std::path::PathBuf::from).collect(),
+                            "time" => time_column = Some(v.to_string()),
+                            "event" => event_column = Some(v.to_string()),
+                            "subject" => subject_column = Some(v.to_string()),
+                            "collapse" => match v.parse() {
+                                Ok(strategy) => collapse = strategy,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "corr" => match v.parse() {
+                                Ok(method) => correlation = method,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "replicates" => match v.parse() {
+                                Ok(strategy) => replicates = strategy,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "impute" => match v.parse() {
+                                Ok(method) => imputation = method,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "normalize" => match crate::analysis::normalize::parse_steps(v) {
+                                Ok(steps) => normalization = steps,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "batch_correction" => match v.parse() {
+                                Ok(method) => batch_correction = method,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "tables" => match v.parse() {
+                                Ok(format) => tables = format,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "numbers" => match v.parse() {
+                                Ok(format) => numbers = format,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "mem" => match v.parse() {
+                                Ok(limit) => memory_limit = limit,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "threads" => match v.parse::<usize>() {
+                                Ok(value) => threads = value,
+                                Err(_) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: format!("threads= expects a whole number, got '{}'", v),
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "roc" => match v.parse() {
+                                Ok(split) => roc = split,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "penalty" => match v.parse() {
+                                Ok(penalty) => regularization.penalty = penalty,
+                                Err(e) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: e,
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "l1_ratio" | "lambda" => match v.parse::<f64>() {
+                                Ok(value) if k == "l1_ratio" => regularization.l1_ratio = value,
+                                Ok(value) => regularization.lambda = Some(value),
+                                Err(_) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: format!("{}= expects a number, got '{}'", k, v),
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
+                            "alpha" | "fdr" | "min_effect" => match v.parse::<f64>() {
+                                Ok(value) if k == "alpha" => thresholds.alpha = value,
+                                Ok(value) if k == "fdr" => thresholds.fdr = value,
+                                Ok(value) => thresholds.min_effect = value,
+                                Err(_) => {
+                                    self.messages.push(ChatMessage {
+                                        role: MessageRole::System,
+                                        content: format!("{}= expects a number, got '{}'", k, v),
+                                        timestamp: Utc::now(),
+                                    });
+                                    return true;
+                                }
+                            },
                             "cov" => {
                                 covariates = v
                                     .split(',')
@@ -868,57 +2208,74 @@ Tip: run /upload first, then /analyze."
                     }
                 }
                 let dataset_id = dataset_id.unwrap();
+                if let Err(e) = thresholds
+                    .validate()
+                    .and_then(|_| regularization.validate())
+                    .and_then(|_| threads::validate(threads))
+                {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: e,
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                }
+                if time_column.is_some() != event_column.is_some() {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "time= and event= must be given together.".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                }
+                let formula = match formula_spec.map(|spec| Formula::resolve(&spec, &target)).transpose() {
+                    Ok(formula) => formula,
+                    Err(e) => {
+                        self.messages.push(ChatMessage {
+                            role: MessageRole::System,
+                            content: e,
+                            timestamp: Utc::now(),
+                        });
+                        return true;
+                    }
+                };
+                if let Some(formula) = &formula {
+                    target = formula.response.clone();
+                }
+                let config = AnalysisConfig {
+                    target_column: Some(target),
+                    group_column: Some(group),
+                    covariates,
+                    boxplot_column: boxplot,
+                    platform,
+                    collapse,
+                    formula,
+                    replicates,
+                    imputation,
+                    normalization,
+                    batch_column,
+                    batch_correction,
+                    correlation,
+                    tables,
+                    numbers,
+                    thresholds,
+                    kruskal_wallis,
+                    memory_limit,
+                    threads,
+                    regularization,
+                    roc,
+                    gene_sets,
+                    time_column,
+                    event_column,
+                    subject_column,
+                    ..Default::default()
+                };
                 match self.dataset_registry.get(&dataset_id).await {
+                    Some(record) if remote => {
+                        self.enqueue_remote_analysis(record, &config).await;
+                    }
                     Some(record) => {
-                        let output_dir = std::path::Path::new("artifacts")
-                            .join("analysis")
-                            .join(&dataset_id);
-                        if let Err(e) = tokio::fs::create_dir_all(&output_dir).await {
-                            self.messages.push(ChatMessage {
-                                role: MessageRole::System,
-                                content: format!("Failed to create artifacts dir: {}", e),
-                                timestamp: Utc::now(),
-                            });
-                            return true;
-                        }
-                        let config = AnalysisConfig {
-                            target_column: Some(target.clone()),
-                            group_column: Some(group.clone()),
-                            covariates,
-                            boxplot_column: boxplot,
-                            max_columns: 50,
-                            max_groups: 20,
-                        };
-                        match run_analysis(&record, &config, &output_dir) {
-                            Ok(result) => {
-                                let manuscript = build_manuscript(
-                                    &dataset_id,
-                                    &target,
-                                    &group,
-                                    &record,
-                                    &result,
-                                );
-                                let top = result
-                                    .biomarker_candidates
-                                    .iter()
-                                    .take(10)
-                                    .map(|b| format!("- {} (r={:.3})", b.column, b.correlation))
-                                    .collect::<Vec<_>>()
-                                    .join("\n");
-                                self.messages.push(ChatMessage {
-                                    role: MessageRole::Assistant,
-                                    content: format!("{}\n\nTop biomarkers:\n{}", manuscript, top),
-                                    timestamp: Utc::now(),
-                                });
-                            }
-                            Err(e) => {
-                                self.messages.push(ChatMessage {
-                                    role: MessageRole::System,
-                                    content: format!("Analysis failed: {}", e),
-                                    timestamp: Utc::now(),
-                                });
-                            }
-                        }
+                        self.run_local_analysis(record, config).await;
                     }
                     None => {
                         self.messages.push(ChatMessage {
@@ -930,169 +2287,1471 @@ Tip: run /upload first, then /analyze."
                 }
                 return true;
             }
-            "/status" => {
+            "/alert" => {
+                let rest = content.trim_start_matches("/alert").trim();
+                let (spec, query) = match rest.strip_prefix("every ") {
+                    Some(after) => {
+                        let (amount, query) = after.trim().split_once(' ').unwrap_or((after, ""));
+                        (format!("every {}", amount), query.trim())
+                    }
+                    None => {
+                        let (spec, query) = rest.split_once(' ').unwrap_or((rest, ""));
+                        (spec.to_string(), query.trim())
+                    }
+                };
+                let spec = match ScheduleSpec::parse(&spec) {
+                    Ok(spec) if !query.is_empty() => spec,
+                    Ok(_) => {
+                        self.messages.push(ChatMessage {
+                            role: MessageRole::System,
+                            content: "Usage: /alert <@weekly|@daily|every 3d> <query>".to_string(),
+                            timestamp: Utc::now(),
+                        });
+                        return true;
+                    }
+                    Err(e) => {
+                        self.messages.push(ChatMessage {
+                            role: MessageRole::System,
+                            content: format!("{}", e),
+                            timestamp: Utc::now(),
+                        });
+                        return true;
+                    }
+                };
+                if self.config.database.url.is_empty() {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "Literature alerts need the background queue (DATABASE_URL not set).".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                }
+
+                let payload = LiteratureAlertPayload {
+                    schedule_id: None,
+                    query: query.to_string(),
+                    biomarkers: self.last_biomarkers.clone(),
+                };
+                let name = format!("alert: {}", query);
+                let db_config = self.config.database.clone();
+                let tx = self.event_tx.clone().unwrap();
+                tokio::spawn(async move {
+                    let result = async {
+                        let pool = crate::db::create_pool(&db_config).await?;
+                        Scheduler::new(JobQueue::new(pool))
+                            .upsert(&name, &spec, JobType::LiteratureAlert, &payload, JobPriority::Batch)
+                            .await
+                    }
+                    .await;
+                    let content = match result {
+                        Ok(schedule) => format!(
+                            "Saved literature alert '{}' ({}){}. New papers will be shown here.",
+                            schedule.name,
+                            schedule.schedule,
+                            if payload.biomarkers.is_empty() {
+                                String::new()
+                            } else {
+                                format!(" for {}", payload.biomarkers.join(", "))
+                            }
+                        ),
+                        Err(e) => format!("Failed to save literature alert: {}", e),
+                    };
+                    let _ = tx.send(AppEvent::WorkflowMessage(MessageRole::System, content)).await;
+                });
+                return true;
+            }
+            "/profile" => {
+                let action = parts.next();
+                let name = parts.next();
+                let content = match (action, name) {
+                    (None, _) => match self.settings_storage.list_profiles().await {
+                        Ok(profiles) => format!(
+                            "Profile: {}\nSaved profiles: {}",
+                            self.settings_storage.profile(),
+                            profiles.join(", ")
+                        ),
+                        Err(e) => format!("Failed to list profiles: {}", e),
+                    },
+                    (Some("use"), Some(name)) => self.switch_profile(name).await,
+                    (Some("save"), Some(name)) => {
+                        match self.settings_storage.clone().with_profile(name) {
+                            Ok(storage) => match storage.save(&self.settings).await {
+                                Ok(_) => format!("Saved current settings as profile '{}'. Switch with /profile use {}", name, name),
+                                Err(e) => format!("Failed to save profile: {}", e),
+                            },
+                            Err(e) => format!("{}", e),
+                        }
+                    }
+                    (Some("delete"), Some(name)) if name == self.settings_storage.profile() => {
+                        "Cannot delete the profile in use; switch to another first.".to_string()
+                    }
+                    (Some("delete"), Some(name)) => match self.settings_storage.delete_profile(name).await {
+                        Ok(true) => format!("Deleted profile '{}'.", name),
+                        Ok(false) => format!("No profile named '{}'.", name),
+                        Err(e) => format!("{}", e),
+                    },
+                    _ => "Usage: /profile [use|save|delete <name>]".to_string(),
+                };
                 self.messages.push(ChatMessage {
                     role: MessageRole::System,
-                    content: format!("Workflow stage: {:?}", self.workflow_stage),
+                    content,
                     timestamp: Utc::now(),
                 });
                 return true;
             }
-            "/next" => {
-                self.advance_workflow().await;
+            "/proxy" => {
+                let args: Vec<&str> = parts.collect();
+                let network = &mut self.settings.network;
+                let mut content = match args.as_slice() {
+                    [] => {
+                        let shown = network.masked();
+                        let mut lines = vec![
+                            format!("Proxy: {}", shown.proxy_url.as_deref().unwrap_or("(environment)")),
+                            format!("No proxy: {}", shown.no_proxy.as_deref().unwrap_or("-")),
+                            format!("CA bundle: {}", shown.ca_cert_path.as_deref().unwrap_or("-")),
+                        ];
+                        for (target, proxy) in &shown.provider_proxies {
+                            lines.push(format!("{} proxy: {}", target, proxy));
+                        }
+                        lines.join("\n")
+                    }
+                    ["off"] => {
+                        *network = Default::default();
+                        "Proxy and CA settings cleared.".to_string()
+                    }
+                    ["set", url] => {
+                        network.proxy_url = Some(url.to_string());
+                        "Proxy saved.".to_string()
+                    }
+                    ["no_proxy", hosts] => {
+                        network.no_proxy = Some(hosts.to_string());
+                        "No-proxy list saved.".to_string()
+                    }
+                    ["ca", path] => {
+                        if std::path::Path::new(path).exists() {
+                            network.ca_cert_path = Some(path.to_string());
+                            "CA bundle saved.".to_string()
+                        } else {
+                            format!("CA bundle not found: {}", path)
+                        }
+                    }
+                    [target, "off"] => {
+                        network.provider_proxies.remove(*target);
+                        format!("{} uses the global proxy again.", target)
+                    }
+                    [target, url] => {
+                        network.provider_proxies.insert(target.to_string(), url.to_string());
+                        format!("Proxy override saved for {}.", target)
+                    }
+                    _ => "Usage: /proxy [set <url> | no_proxy <hosts> | ca <pem> | <provider> <url|off> | off]".to_string(),
+                };
+                if !args.is_empty() {
+                    self.save_settings();
+                    self.update_config_from_settings();
+                    if let Err(e) = crate::utils::http::build_client(&self.config.network, "default") {
+                        content.push_str(&format!("\nWarning: {:#}", e));
+                    }
+                }
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
                 return true;
             }
-            "/feedback" => {
-                let feedback = parts.collect::<Vec<_>>().join(" ");
-                if feedback.is_empty() {
-                    self.messages.push(ChatMessage {
-                        role: MessageRole::System,
-                        content: "Usage: /feedback <text>".to_string(),
-                        timestamp: Utc::now(),
-                    });
-                    return true;
+            "/search" => {
+                let args: Vec<&str> = parts.collect();
+                let search = &mut self.settings.search;
+                let content = match args.as_slice() {
+                    [] => {
+                        let key_status = |key: &Option<String>| if key.is_some() { "key set" } else { "no key" };
+                        let mut lines = Vec::new();
+                        for engine in crate::settings::SEARCH_ENGINES {
+                            let enabled = search.engine_enabled(engine).unwrap_or(false);
+                            let key = match engine {
+                                "pubmed" => key_status(&search.ncbi_api_key),
+                                "semantic_scholar" => key_status(&search.semantic_scholar_key),
+                                _ => key_status(&search.serpapi_key),
+                            };
+                            lines.push(format!(
+                                "{}: {}, {}, max {} results",
+                                engine,
+                                if enabled { "on" } else { "off" },
+                                key,
+                                self.config.search.max_results_for(engine)
+                            ));
+                        }
+                        if let Some(email) = &search.ncbi_email {
+                            lines.push(format!("NCBI contact email: {}", email));
+                        }
+                        lines.push("Keys are set in the settings view (Ctrl+S).".to_string());
+                        lines.join("\n")
+                    }
+                    ["email", email] if email.contains('@') => {
+                        search.ncbi_email = Some(email.to_string());
+                        "NCBI contact email saved.".to_string()
+                    }
+                    [engine, state @ ("on" | "off")] => match search.set_engine_enabled(engine, *state == "on") {
+                        Ok(()) => format!("{} search {}.", engine, if *state == "on" { "enabled" } else { "disabled" }),
+                        Err(e) => e,
+                    },
+                    [engine, "max", n] if crate::settings::SEARCH_ENGINES.contains(engine) => match n.parse::<usize>() {
+                        Ok(n) if (1..=100).contains(&n) => {
+                            search.max_results.insert(engine.to_string(), n);
+                            format!("{} returns up to {} results.", engine, n)
+                        }
+                        _ => format!("Invalid result limit '{}' (1-100)", n),
+                    },
+                    _ => "Usage: /search [<engine> on|off | <engine> max <n> | email <addr>]".to_string(),
+                };
+                if !args.is_empty() {
+                    self.save_settings();
+                    self.update_config_from_settings();
                 }
-                self.feedbacks.push(feedback.clone());
                 self.messages.push(ChatMessage {
                     role: MessageRole::System,
-                    content: "Feedback recorded. Use /next to apply it.".to_string(),
+                    content,
                     timestamp: Utc::now(),
                 });
                 return true;
             }
-            "/latex" => {
-                if let Some(draft) = self.draft_versions.last() {
-                    let latex = self.render_latex(draft);
-                    self.latex_output = Some(latex.clone());
-                    self.workflow_stage = WorkflowStage::LatexReady;
-                    self.messages.push(ChatMessage {
-                        role: MessageRole::Assistant,
-                        content: format!("LaTeX output:\n\n{}", latex),
-                        timestamp: Utc::now(),
-                    });
-                } else {
-                    self.messages.push(ChatMessage {
-                        role: MessageRole::System,
-                        content: "No draft available yet. Use /next to generate drafts.".to_string(),
-                        timestamp: Utc::now(),
-                    });
+            "/budget" => {
+                let args: Vec<&str> = parts.collect();
+                const LIMITS: &str = "monthly_usd, session_usd, monthly_tokens, session_tokens, monthly_search_queries, session_search_queries";
+                let result = match args.as_slice() {
+                    [] => Ok(None),
+                    ["set", field, value] => self.settings.budget.set(field, Some(*value)).map(|_| Some(format!("{} set to {}.", field, value))),
+                    ["clear"] => {
+                        self.settings.budget = Default::default();
+                        Ok(Some("Budget limits cleared.".to_string()))
+                    }
+                    ["clear", field] => self.settings.budget.set(field, None).map(|_| Some(format!("{} cleared.", field))),
+                    _ => Err(format!("Usage: /budget [set <limit> <value> | clear [limit]]\nLimits: {}", LIMITS)),
+                };
+                let content = match result {
+                    Ok(Some(message)) => {
+                        self.save_settings();
+                        self.update_config_from_settings();
+                        message
+                    }
+                    Ok(None) => {
+                        let usage = crate::budget::snapshot();
+                        let budget = &self.config.budget;
+                        let limit = |value: Option<String>| value.unwrap_or_else(|| "no limit".to_string());
+                        let mut lines = vec![
+                            format!(
+                                "Session: ${:.4} / {}, {} tokens / {}, {} SerpAPI queries / {}",
+                                usage.session.usd,
+                                limit(budget.session_usd.map(|v| format!("${:.2}", v))),
+                                usage.session.tokens,
+                                limit(budget.session_tokens.map(|v| v.to_string())),
+                                usage.session.search_queries,
+                                limit(budget.session_search_queries.map(|v| v.to_string())),
+                            ),
+                            format!(
+                                "This month: ${:.4} / {}, {} tokens / {}, {} SerpAPI queries / {}",
+                                usage.month.usd,
+                                limit(budget.monthly_usd.map(|v| format!("${:.2}", v))),
+                                usage.month.tokens,
+                                limit(budget.monthly_tokens.map(|v| v.to_string())),
+                                usage.month.search_queries,
+                                limit(budget.monthly_search_queries.map(|v| v.to_string())),
+                            ),
+                        ];
+                        if let Some(reason) = usage.llm_exceeded(budget) {
+                            lines.push(format!("LLM budget reached ({}): running offline.", reason));
+                        }
+                        if let Some(reason) = usage.search_exceeded(budget) {
+                            lines.push(format!("SerpAPI quota reached ({}): using cached results.", reason));
+                        }
+                        lines.join("\n")
+                    }
+                    Err(e) => e,
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/openrouter" => {
+                let args: Vec<&str> = parts.collect();
+                const CONTROLS: &str = "order, only, ignore (vendor lists), fallbacks (on|off), models (fallback models), referer, title";
+                let previous = self.settings.openrouter_routing.clone();
+                let routing = &mut self.settings.openrouter_routing;
+                let result = match args.as_slice() {
+                    [] => Ok(None),
+                    ["set", field, value @ ..] if !value.is_empty() => {
+                        let value = value.join(" ");
+                        routing.set(field, Some(&value)).map(|_| Some(format!("{} set to {}.", field, value)))
+                    }
+                    ["clear"] => {
+                        *routing = Default::default();
+                        Ok(Some("OpenRouter controls cleared.".to_string()))
+                    }
+                    ["clear", field] => routing.set(field, None).map(|_| Some(format!("{} cleared.", field))),
+                    _ => Err(format!(
+                        "Usage: /openrouter [set <control> <value> | clear [control]]\nControls: {}",
+                        CONTROLS
+                    )),
+                };
+                let result = result.and_then(|message| routing.validate().map(|_| message));
+                let content = match result {
+                    Ok(Some(message)) => {
+                        self.save_settings();
+                        self.update_config_from_settings();
+                        message
+                    }
+                    Ok(None) => {
+                        let routing = &self.config.llm.openrouter;
+                        let list = |values: &[String]| if values.is_empty() { "-".to_string() } else { values.join(", ") };
+                        [
+                            format!("Vendor order: {}", list(&routing.order)),
+                            format!("Only: {}", list(&routing.only)),
+                            format!("Ignore: {}", list(&routing.ignore)),
+                            format!(
+                                "Vendor fallbacks: {}",
+                                match routing.allow_fallbacks {
+                                    Some(false) => "off",
+                                    _ => "on",
+                                }
+                            ),
+                            format!("Fallback models: {}", list(&routing.fallback_models)),
+                            format!("HTTP-Referer: {}", routing.referer.as_deref().unwrap_or("-")),
+                            format!("X-Title: {}", routing.title.as_deref().unwrap_or("-")),
+                        ]
+                        .join("\n")
+                    }
+                    Err(e) => {
+                        self.settings.openrouter_routing = previous;
+                        e
+                    }
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/route" => {
+                let args: Vec<&str> = parts.collect();
+                let content = match args.as_slice() {
+                    [] => {
+                        let mut lines = vec![format!("Default: {} ({})", self.config.llm.default_provider, self.config.llm.default_model)];
+                        for task in LlmTask::ALL {
+                            let line = match self.config.llm.for_task(task) {
+                                Some(resolved) => {
+                                    let mut line = format!("{}: {} ({})", task, resolved.provider, resolved.model);
+                                    if let Some(t) = resolved.temperature {
+                                        line.push_str(&format!(" temp={}", t));
+                                    }
+                                    if let Some(m) = resolved.max_tokens {
+                                        line.push_str(&format!(" max_tokens={}", m));
+                                    }
+                                    line
+                                }
+                                None => format!("{}: no provider key configured", task),
+                            };
+                            lines.push(line);
+                        }
+                        lines.join("\n")
+                    }
+                    [task, rest @ ..] => match (LlmTask::from_id(task), rest) {
+                        (None, _) => format!("Unknown task '{}'. Tasks: planning, literature, reply, drafting", task),
+                        (Some(task), ["default"]) => {
+                            self.settings.clear_route(task);
+                            format!("{} now uses the default provider.", task)
+                        }
+                        (Some(task), params) if !params.is_empty() && params.iter().all(|p| p.contains('=')) => {
+                            let current = self.settings.routing.get(&task);
+                            let mut temperature = current.and_then(|r| r.temperature);
+                            let mut max_tokens = current.and_then(|r| r.max_tokens);
+                            let mut error = None;
+                            for param in params {
+                                match param.split_once('=') {
+                                    Some(("temp" | "temperature", "default")) => temperature = None,
+                                    Some(("max_tokens", "default")) => max_tokens = None,
+                                    Some(("temp" | "temperature", v)) => match v.parse::<f32>() {
+                                        Ok(t) if (0.0..=2.0).contains(&t) => temperature = Some(t),
+                                        _ => error = Some(format!("Invalid temperature '{}' (0.0-2.0)", v)),
+                                    },
+                                    Some(("max_tokens", v)) => match v.parse::<u32>() {
+                                        Ok(m) if m > 0 => max_tokens = Some(m),
+                                        _ => error = Some(format!("Invalid max_tokens '{}'", v)),
+                                    },
+                                    _ => error = Some(format!("Unknown parameter '{}'", param)),
+                                }
+                            }
+                            match error {
+                                Some(e) => e,
+                                None => {
+                                    self.settings.set_route_params(task, temperature, max_tokens);
+                                    format!("{} parameters updated.", task)
+                                }
+                            }
+                        }
+                        (Some(task), [provider, model @ ..]) if model.len() <= 1 => {
+                            match crate::settings::Provider::from_id(provider) {
+                                Some(provider) => {
+                                    let has_key = self.settings.provider_config(&provider).api_key.is_some();
+                                    let model = model.first().map(|m| m.to_string());
+                                    self.settings.set_route(task, provider.clone(), model);
+                                    if has_key {
+                                        format!("{} routed to {}.", task, provider)
+                                    } else {
+                                        format!("{} routed to {} (no key yet; using the default provider until one is set).", task, provider)
+                                    }
+                                }
+                                None => format!("Unknown provider '{}'", provider),
+                            }
+                        }
+                        _ => "Usage: /route [<task> <provider> [model] | <task> temp=<t> max_tokens=<n> | <task> default]".to_string(),
+                    },
+                };
+                if !args.is_empty() {
+                    self.save_settings();
+                    self.update_config_from_settings();
                 }
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
                 return true;
             }
-            _ => {
+            "/logs" => {
+                let current = crate::utils::logger::current_log_path();
+                let content = match parts.next() {
+                    Some("path") => match current {
+                        Some(path) => path.display().to_string(),
+                        None => "This session does not log to a file.".to_string(),
+                    },
+                    Some(other) => format!("Unknown /logs option: {} (usage: /logs [path])", other),
+                    None => {
+                        let dir = crate::utils::logger::log_dir();
+                        let files = crate::utils::logger::log_files(&dir);
+                        let bytes: u64 = files.iter().map(|f| f.size).sum();
+                        let retention = &self.config.logging;
+                        format!(
+                            "Log directory: {}
+{} file(s), {:.1} MB
+Current log: {}
+                             Rolled over at {} MB; kept for {} day(s), newest {} file(s) (0 = no limit)",
+                            dir.display(),
+                            files.len(),
+                            bytes as f64 / (1024.0 * 1024.0),
+                            current.map_or_else(|| "none".to_string(), |p| p.display().to_string()),
+                            retention.max_file_mb,
+                            retention.max_age_days,
+                            retention.max_files,
+                        )
+                    }
+                };
                 self.messages.push(ChatMessage {
                     role: MessageRole::System,
-                    content: format!("Unknown command: {}", cmd),
+                    content,
                     timestamp: Utc::now(),
                 });
                 return true;
             }
-        }
+            "/models" => {
+                let content = match parts.next() {
+                    Some("refresh") => {
+                        let mut lines = Vec::new();
+                        for provider in ["openrouter", "groq", "google"] {
+                            let Some(api_key) = self.config.llm.api_key_for(provider) else {
+                                continue;
+                            };
+                            let outcome = crate::llm::capabilities::refresh(provider, &api_key, &self.config.network).await;
+                            lines.push(match outcome {
+                                Ok(count) => format!("{}: {} model(s) updated", provider, count),
+                                Err(e) => format!("{}: {}", provider, e),
+                            });
+                        }
+                        if lines.is_empty() {
+                            "No OpenRouter, Groq or Google key configured; using the built-in model table.".to_string()
+                        } else {
+                            format!("Model limits refreshed:\n{}", lines.join("\n"))
+                        }
+                    }
+                    Some(other) => format!("Unknown /models option: {} (usage: /models [refresh])", other),
+                    None => {
+                        let lines: Vec<String> = LlmTask::ALL
+                            .iter()
+                            .map(|task| match self.config.llm.for_task(*task) {
+                                Some(route) => {
+                                    let caps = route.capabilities();
+                                    format!(
+                                        "{}: {}/{} - {} context, {} max output ({} requested), vision {}, tools {}",
+                                        task.as_str(),
+                                        route.provider,
+                                        route.model,
+                                        caps.context_length,
+                                        caps.max_output,
+                                        route.output_tokens(None),
+                                        if caps.vision { "yes" } else { "no" },
+                                        if caps.tools { "yes" } else { "no" },
+                                    )
+                                }
+                                None => format!("{}: no LLM configured", task.as_str()),
+                            })
+                            .collect();
+                        format!("Models by task:\n{}", lines.join("\n"))
+                    }
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/usage" => {
+                let content = match crate::metering::pool() {
+                    Some(pool) => {
+                        let since = crate::metering::month_start();
+                        match crate::metering::summary(pool, crate::metering::LOCAL_ACCOUNT, since).await {
+                            Ok(totals) => format!(
+                                "Metered usage since {}:\n{}",
+                                since.format("%Y-%m-%d"),
+                                crate::metering::format_summary(&totals)
+                            ),
+                            Err(e) => format!("Failed to load usage: {}", e),
+                        }
+                    }
+                    None => {
+                        let usage = crate::budget::snapshot();
+                        format!(
+                            "Usage metering needs DATABASE_URL; local totals only.\n\
+                             Session: {} tokens, ${:.4}, {} search queries\n\
+                             This month: {} tokens, ${:.4}, {} search queries",
+                            usage.session.tokens,
+                            usage.session.usd,
+                            usage.session.search_queries,
+                            usage.month.tokens,
+                            usage.month.usd,
+                            usage.month.search_queries,
+                        )
+                    }
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/jobs" => {
+                let content = if self.jobs.is_empty() {
+                    if self.config.database.url.is_empty() {
+                        "Background queue unavailable (DATABASE_URL not set).".to_string()
+                    } else {
+                        "No background jobs seen in the last hour.".to_string()
+                    }
+                } else {
+                    self.jobs
+                        .iter()
+                        .rev()
+                        .map(|j| {
+                            format!(
+                                "- {} [{}|{}] {} {:.0}%{}",
+                                j.job_id,
+                                j.job_type,
+                                j.priority,
+                                j.status,
+                                j.progress * 100.0,
+                                j.message
+                                    .as_ref()
+                                    .map(|m| format!(" - {}", m))
+                                    .unwrap_or_default()
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/status" => {
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: format!("Workflow stage: {:?}", self.workflow_stage),
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/next" => {
+                self.advance_workflow().await;
+                return true;
+            }
+            "/approve" => {
+                let content = match self.pending_approval.take() {
+                    Some(pending) => match pending.reply.send(GateDecision::Approve) {
+                        Ok(()) => format!("Approved {}; continuing.", pending.label),
+                        Err(_) => "The workflow is no longer waiting for approval.".to_string(),
+                    },
+                    None => "Nothing is waiting for approval.".to_string(),
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/gates" => {
+                let content = match parts.next() {
+                    Some(list) => match ApprovalGate::parse_list(list) {
+                        Ok(gates) => {
+                            self.config.workflow.gates = gates;
+                            if let Some(secs) = parts.next() {
+                                match secs.parse() {
+                                    Ok(secs) => self.config.workflow.gate_timeout_secs = secs,
+                                    Err(_) => {
+                                        self.messages.push(ChatMessage {
+                                            role: MessageRole::System,
+                                            content: format!("Invalid timeout '{}' (seconds, 0 to wait indefinitely)", secs),
+                                            timestamp: Utc::now(),
+                                        });
+                                        return true;
+                                    }
+                                }
+                            }
+                            format!("{} (applies to the next automated run)", self.describe_gates())
+                        }
+                        Err(e) => format!("{}\nUsage: /gates <plan,findings,drafts|all|none> [timeout secs]", e),
+                    },
+                    None => self.describe_gates(),
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/feedback" => {
+                let feedback = parts.collect::<Vec<_>>().join(" ");
+                if feedback.is_empty() {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "Usage: /feedback <text>".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                }
+                // At a gate the workflow revises with it right away
+                if let Some(pending) = self.pending_approval.take() {
+                    let label = pending.label;
+                    if pending.reply.send(GateDecision::Feedback(feedback.clone())).is_ok() {
+                        self.messages.push(ChatMessage {
+                            role: MessageRole::System,
+                            content: format!("Feedback sent; revising {}.", label),
+                            timestamp: Utc::now(),
+                        });
+                        return true;
+                    }
+                }
+                self.feedbacks.push(feedback.clone());
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: "Feedback recorded. Use /next to apply it.".to_string(),
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/drafts" => {
+                let content = if self.drafts.is_empty() {
+                    "No drafts saved yet. Use /next to generate drafts.".to_string()
+                } else {
+                    let current = self.drafts.current().map(|d| d.id);
+                    let lines: Vec<String> = self
+                        .drafts
+                        .drafts()
+                        .iter()
+                        .map(|d| {
+                            let feedback = match d.feedback.len() {
+                                0 => "no feedback".to_string(),
+                                1 => "1 feedback note".to_string(),
+                                n => format!("{} feedback notes", n),
+                            };
+                            format!(
+                                "{} #{}  Draft {}  {}  {}  {}  {} words",
+                                if current == Some(d.id) { "*" } else { " " },
+                                d.id,
+                                d.version,
+                                d.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                                d.model.as_deref().unwrap_or("template"),
+                                feedback,
+                                d.text.split_whitespace().count(),
+                            )
+                        })
+                        .collect();
+                    format!("Drafts (* = current):\n{}\nUse /rollback <n> to make an earlier draft current.", lines.join("\n"))
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/rollback" => {
+                let content = match parts.next().map(|n| n.trim_start_matches('#').parse::<usize>()) {
+                    Some(Ok(id)) => match self.drafts.rollback(id) {
+                        Ok(draft) => {
+                            let feedback = if draft.feedback.is_empty() {
+                                String::new()
+                            } else {
+                                format!("\nFeedback: {}", draft.feedback.join(" | "))
+                            };
+                            format!(
+                                "Draft #{} (Draft {}, {}) is now current; /latex renders it.{}",
+                                draft.id,
+                                draft.version,
+                                draft.model.as_deref().unwrap_or("template"),
+                                feedback
+                            )
+                        }
+                        Err(e) => e.to_string(),
+                    },
+                    _ => "Usage: /rollback <n> (see /drafts)".to_string(),
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/resume" => {
+                let plans = PlanStore::load_default();
+                let plan = match parts.next() {
+                    Some(id) => plans.get(id).cloned().ok_or_else(|| format!("No saved plan for dataset {}.", id)),
+                    None => plans
+                        .latest_unfinished()
+                        .cloned()
+                        .ok_or_else(|| "No interrupted workflow to resume.".to_string()),
+                };
+                match plan {
+                    Ok(plan) => self.resume_workflow(plan),
+                    Err(content) => self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content,
+                        timestamp: Utc::now(),
+                    }),
+                }
+                return true;
+            }
+            "/csl" => {
+                let content = match content.trim_start_matches("/csl").trim() {
+                    "" => format!(
+                        "Citation style: {}\nUse /csl <name> for a bundled style (apa, vancouver, nature, ...) or /csl <path.csl>.",
+                        self.config.manuscript.citation_style
+                    ),
+                    spec => match spec.parse::<crate::manuscript::CitationStyle>() {
+                        Ok(style) => match style.load() {
+                            Ok(_) => {
+                                self.settings.manuscript.citation_style = Some(style.clone());
+                                self.save_settings();
+                                self.update_config_from_settings();
+                                format!("Citation style set to {}. /export uses it for Markdown and DOCX.", style)
+                            }
+                            Err(e) => e.to_string(),
+                        },
+                        Err(e) => e,
+                    },
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/export" => {
+                let Some(draft) = self.drafts.current().map(|d| d.text.as_str()) else {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "No draft available yet. Use /next to generate drafts.".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                };
+                let dir = self.manuscript_dir();
+                let manuscript = &self.config.manuscript;
+                let (markdown, docx) = match parts.next() {
+                    None => (true, true),
+                    Some("md" | "markdown") => (true, false),
+                    Some("docx") => (false, true),
+                    Some(_) => (false, false),
+                };
+                let mut lines = Vec::new();
+                if !markdown && !docx {
+                    lines.push("Usage: /export [md|docx]".to_string());
+                }
+                if markdown {
+                    lines.push(
+                        match crate::manuscript::export_markdown(
+                            &dir,
+                            draft,
+                            &self.literature_results,
+                            &manuscript.citation_style,
+                            manuscript.template,
+                        ) {
+                            Ok(path) => format!("Saved: {}", path.display()),
+                            Err(e) => format!("Markdown export failed: {}", e),
+                        },
+                    );
+                }
+                if docx {
+                    lines.push(
+                        match crate::manuscript::export_docx(
+                            &dir,
+                            draft,
+                            &self.literature_results,
+                            self.last_analysis.as_ref(),
+                            &manuscript.citation_style,
+                            manuscript.template,
+                        ) {
+                            Ok(path) => format!("Saved: {}", path.display()),
+                            Err(e) => format!("DOCX export failed: {}", e),
+                        },
+                    );
+                }
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: lines.join("\n"),
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/template" => {
+                let content = match parts.next() {
+                    None => {
+                        let current = self.config.manuscript.template;
+                        let available = crate::manuscript::JournalTemplate::ALL
+                            .iter()
+                            .map(|t| format!("  {} ({})", t.id(), t.name()))
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        format!("Manuscript template: {} ({})\nAvailable:\n{}", current.id(), current.name(), available)
+                    }
+                    Some(id) => match id.parse::<crate::manuscript::JournalTemplate>() {
+                        Ok(template) => {
+                            self.settings.manuscript.template = Some(template);
+                            self.save_settings();
+                            self.update_config_from_settings();
+                            format!("Manuscript template set to {}. Run /latex to re-render.", template.name())
+                        }
+                        Err(e) => e,
+                    },
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/latex" => {
+                if let Some(draft) = self.drafts.current().map(|d| d.text.as_str()) {
+                    let bibliography = crate::manuscript::Bibliography::from_literature(&self.literature_results);
+                    let template = self.config.manuscript.template;
+                    let latex =
+                        crate::manuscript::render_latex(draft, &bibliography, self.last_analysis.as_ref(), template);
+                    let warnings = crate::manuscript::word_limit_warnings(draft, template);
+                    self.latex_output = Some(latex.clone());
+                    self.workflow_stage = WorkflowStage::LatexReady;
+                    let saved = match crate::manuscript::export_latex(
+                        &self.manuscript_dir(),
+                        draft,
+                        &self.literature_results,
+                        self.last_analysis.as_ref(),
+                        template,
+                    ) {
+                        Ok(files) => format!(
+                            "Saved ({} template): {}",
+                            template.name(),
+                            files.iter().map(|f| f.display().to_string()).collect::<Vec<_>>().join(", ")
+                        ),
+                        Err(e) => format!("Failed to save LaTeX: {}", e),
+                    };
+                    let saved = warnings.iter().fold(saved, |acc, w| format!("{}\nWarning: {}", acc, w));
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::Assistant,
+                        content: format!("LaTeX output:\n\n{}\n\n{}", latex, saved),
+                        timestamp: Utc::now(),
+                    });
+                } else {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "No draft available yet. Use /next to generate drafts.".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                }
+                return true;
+            }
+            "/pdf" => {
+                let Some(draft) = self.drafts.current().map(|d| d.text.as_str()) else {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "No draft available yet. Use /next to generate drafts.".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                };
+                if !crate::manuscript::pdf::available() {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "PDF export is not available in this build; rebuild with `cargo build --features pdf`."
+                            .to_string(),
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                }
+                let dir = self.manuscript_dir();
+                if let Err(e) = crate::manuscript::export_latex(
+                    &dir,
+                    draft,
+                    &self.literature_results,
+                    self.last_analysis.as_ref(),
+                    self.config.manuscript.template,
+                ) {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: format!("Failed to save LaTeX: {}", e),
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                }
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: "Compiling PDF (the first run downloads TeX packages)...".to_string(),
+                    timestamp: Utc::now(),
+                });
+                let tx = self.event_tx.clone().unwrap();
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || crate::manuscript::pdf::compile(&dir)).await;
+                    let event = match result {
+                        Ok(Ok(path)) => AppEvent::WorkflowMessage(
+                            MessageRole::Assistant,
+                            format!("PDF saved: {}", path.display()),
+                        ),
+                        Ok(Err(e)) => AppEvent::Error { action: "PDF compilation".to_string(), error: AppError::io(e) },
+                        Err(e) => AppEvent::Error {
+                            action: "PDF compilation".to_string(),
+                            error: AppError::Internal(format!("compiler task crashed: {}", e)),
+                        },
+                    };
+                    let _ = tx.send(event).await;
+                });
+                return true;
+            }
+            "/species" => {
+                let Some(id) = self.last_dataset_id.clone() else {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: "No dataset loaded. Use /upload first.".to_string(),
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                };
+                let Some(mut record) = self.dataset_registry.get(&id).await else {
+                    self.messages.push(ChatMessage {
+                        role: MessageRole::System,
+                        content: format!("Dataset {} not found.", id),
+                        timestamp: Utc::now(),
+                    });
+                    return true;
+                };
+                let content = match parts.next().map(str::parse::<Species>) {
+                    None => format!(
+                        "Species of {}: {} ({}). Usage: /species <human|mouse|rat>",
+                        record.dataset.filename,
+                        record.species,
+                        record.species.scientific_name()
+                    ),
+                    Some(Err(e)) => e,
+                    Some(Ok(species)) => match record.set_species(species) {
+                        Ok(()) => {
+                            self.dataset_registry.insert(record.clone()).await;
+                            self.fetch_annotation(species);
+                            match species {
+                                Species::Human => format!("Species of {} set to human.", record.dataset.filename),
+                                _ => format!(
+                                    "Species of {} set to {}. Markers will be mapped to human orthologs for literature comparison.",
+                                    record.dataset.filename, species
+                                ),
+                            }
+                        }
+                        Err(e) => format!("{:#}", e),
+                    },
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            "/genes" => {
+                let content = match parts.next() {
+                    None => {
+                        let genes = crate::annotation::ensembl::shared();
+                        match genes.sources() {
+                            [] => format!(
+                                "Gene annotation: {} genes from the bundled seed table. Use /genes update to download the full Ensembl table.",
+                                genes.len()
+                            ),
+                            paths => format!(
+                                "Gene annotation: {} genes from {}",
+                                genes.len(),
+                                paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ")
+                            ),
+                        }
+                    }
+                    Some("update") => {
+                        let species = match &self.last_dataset_id {
+                            Some(id) => self.dataset_registry.get(id).await.map(|r| r.species).unwrap_or_default(),
+                            None => Species::default(),
+                        };
+                        let tx = self.event_tx.clone().unwrap();
+                        let network = self.config.network.clone();
+                        tokio::spawn(async move {
+                            let result = async {
+                                let (path, count) = crate::annotation::ensembl::download(&network, species).await?;
+                                let mut content = format!("Cached {} {} Ensembl genes in {}", count, species, path.display());
+                                if species != Species::Human {
+                                    let (path, count) = crate::annotation::orthologs::download(&network, species).await?;
+                                    content.push_str(&format!("\nCached {} human orthologs in {}", count, path.display()));
+                                }
+                                anyhow::Ok(content)
+                            }
+                            .await;
+                            let event = match result {
+                                Ok(content) => AppEvent::WorkflowMessage(MessageRole::System, content),
+                                Err(e) => AppEvent::Error {
+                                    action: "Gene annotation download".to_string(),
+                                    error: AppError::network(e),
+                                },
+                            };
+                            let _ = tx.send(event).await;
+                        });
+                        format!("Downloading the {} Ensembl gene table...", species)
+                    }
+                    Some(id) => match crate::annotation::ensembl::shared().get(id) {
+                        Some(gene) => format!("{}: {} ({})", id, gene.symbol, gene.biotype),
+                        None => format!("{} is not in the gene annotation", id),
+                    },
+                };
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content,
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+            _ => {
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: format!("Unknown command: {}", cmd),
+                    timestamp: Utc::now(),
+                });
+                return true;
+            }
+        }
+    }
+
+    /// Copy the datasets at `paths` into `uploads/` in the background, one
+    /// after another, with progress in the status bar; Esc cancels the copy.
+    /// With `auto` the automated workflow starts once the (first) dataset is
+    /// loaded. Several files are summarized in one message.
+    fn start_upload(&mut self, paths: Vec<String>, description: Option<String>, parsing: ParseOptions, auto: bool) {
+        if let Some(upload) = &self.upload {
+            self.messages.push(ChatMessage {
+                role: MessageRole::System,
+                content: format!("{} is still being copied; press Esc to cancel it first.", upload.filename),
+                timestamp: Utc::now(),
+            });
+            return;
+        }
+        let single = paths.len() == 1;
+        let mut sources = Vec::new();
+        let mut failed = Vec::new();
+        for path in &paths {
+            match Self::resolve_upload_path(path) {
+                Ok(source) => sources.push(source),
+                Err(e) if single => failed.push(e.to_string()),
+                // Just the first line, without the checklist
+                Err(e) => failed.push(e.to_string().lines().next().unwrap_or_default().to_string()),
+            }
+        }
+        if sources.is_empty() {
+            self.messages.push(ChatMessage {
+                role: MessageRole::System,
+                content: format!("Upload failed: {}", failed.join("\n")),
+                timestamp: Utc::now(),
+            });
+            return;
+        }
+        let Some(tx) = self.event_tx.clone() else {
+            return;
+        };
+        let filename = |source: &std::path::Path| source.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let count = sources.len();
+        let numbered = move |i: usize, name: String| if count > 1 { format!("{} ({}/{})", name, i + 1, count) } else { name };
+        let cancel = CancellationToken::new();
+        self.upload = Some(UploadProgress {
+            filename: numbered(0, filename(&sources[0])),
+            copied: 0,
+            total: 0,
+            cancel: cancel.clone(),
+        });
+        let registry = self.dataset_registry.clone();
+        tokio::spawn(async move {
+            let mut records = Vec::new();
+            for (i, source) in sources.iter().enumerate() {
+                let name = filename(source);
+                if i > 0 {
+                    let _ = tx.send(AppEvent::UploadStarted(numbered(i, name.clone()))).await;
+                }
+                // One progress event per percent copied; dropped when the
+                // channel is full, as the next one supersedes it
+                let progress_tx = tx.clone();
+                let mut reported = None;
+                let progress = |copied: u64, total: u64| {
+                    let percent = copied * 100 / total.max(1);
+                    if reported != Some(percent) {
+                        reported = Some(percent);
+                        let _ = progress_tx.try_send(AppEvent::UploadProgress { copied, total });
+                    }
+                };
+                let imported = DatasetRecord::import(
+                    std::path::Path::new(UPLOAD_DIR),
+                    source,
+                    description.clone(),
+                    parsing.clone(),
+                    &cancel,
+                    progress,
+                )
+                .await;
+                match imported {
+                    Ok(record) => {
+                        registry.insert(record.clone()).await;
+                        records.push(record);
+                    }
+                    Err(e) if cancel::is_cancelled(&e) => {
+                        failed.push(match sources.len() - i {
+                            _ if single => "Upload cancelled.".to_string(),
+                            1 => format!("{}: cancelled", name),
+                            left => format!("{}: cancelled, with {} more not copied", name, left - 1),
+                        });
+                        break;
+                    }
+                    Err(e) if single => failed.push(format!("Upload failed: {:#}", e)),
+                    Err(e) => failed.push(format!("{}: {:#}", name, e)),
+                }
+            }
+            let event = match (single, records.pop()) {
+                (true, Some(record)) => AppEvent::DatasetLoaded { record: Box::new(record), auto },
+                (true, None) => AppEvent::UploadFailed(failed.pop().unwrap_or_default()),
+                (false, record) => {
+                    records.extend(record);
+                    AppEvent::DatasetsLoaded { records, failed, auto }
+                }
+            };
+            let _ = tx.send(event).await;
+        });
+    }
+
+    /// Announce a dataset loaded by [`Self::start_upload`] and, with `auto`,
+    /// start the automated workflow on it
+    fn dataset_loaded(&mut self, record: DatasetRecord, auto: bool) {
+        self.projects.update(|p| p.add_dataset(&record));
+        // Reads have no samples to run the workflow on
+        if let Some(summary) = &record.sequence {
+            self.messages.push(ChatMessage {
+                role: MessageRole::System,
+                content: format!(
+                    "Sequences loaded: {}\n{}\nID: {}\nAsk about them in the chat; replies draw on this summary.",
+                    record.dataset.filename,
+                    summary.describe(),
+                    record.dataset.id
+                ),
+                timestamp: Utc::now(),
+            });
+            return;
+        }
+        if record.species != Species::Human {
+            self.fetch_annotation(record.species);
+        }
+        self.last_dataset_id = Some(record.dataset.id.clone());
+        self.workflow_stage = WorkflowStage::Planning;
+        let next = if auto {
+            "Auto workflow starting..."
+        } else {
+            "Change the species with /species if it was detected wrongly."
+        };
+        self.messages.push(ChatMessage {
+            role: MessageRole::System,
+            content: format!(
+                "Dataset loaded: {}\nRows: {} | Columns: {} ({}) | Species: {}\nID: {}\n{}",
+                record.dataset.filename,
+                record.row_count,
+                record.columns.len(),
+                record.schema_summary(),
+                record.species,
+                record.dataset.id,
+                next
+            ),
+            timestamp: Utc::now(),
+        });
+        if auto {
+            let Some(tx) = self.event_tx.clone() else {
+                return;
+            };
+            let config = self.config.clone();
+            let cancel = self.start_run();
+            tokio::spawn(async move {
+                Self::run_automated_workflow(record, config, cancel, tx, None).await;
+            });
+        }
+    }
+
+    /// Announce the datasets of a multi-file upload in one message and, with
+    /// `auto`, start the automated workflow on the first
+    fn datasets_loaded(&mut self, records: Vec<DatasetRecord>, failed: Vec<String>, auto: bool) {
+        self.projects.update(|p| records.iter().for_each(|record| p.add_dataset(record)));
+        let mut fetched = Vec::new();
+        let mut content = format!("Loaded {} of {} datasets:", records.len(), records.len() + failed.len());
+        for record in &records {
+            if let Some(summary) = &record.sequence {
+                content.push_str(&format!(
+                    "\n✓ {}: {} | ID: {}",
+                    record.dataset.filename,
+                    summary.describe(),
+                    record.dataset.id
+                ));
+                continue;
+            }
+            if record.species != Species::Human && !fetched.contains(&record.species) {
+                fetched.push(record.species);
+                self.fetch_annotation(record.species);
+            }
+            content.push_str(&format!(
+                "\n✓ {}: {} rows, {} columns ({}), {} | ID: {}",
+                record.dataset.filename,
+                record.row_count,
+                record.columns.len(),
+                record.schema_summary(),
+                record.species,
+                record.dataset.id
+            ));
+        }
+        for failure in &failed {
+            content.push_str(&format!("\n✗ {}", failure));
+        }
+        // Sequence files have no samples to analyse
+        let first = records.into_iter().find(|record| record.sequence.is_none());
+        if let Some(first) = &first {
+            self.projects.update(|p| p.active_dataset = Some(first.dataset.id.clone()));
+            self.last_dataset_id = Some(first.dataset.id.clone());
+            self.workflow_stage = WorkflowStage::Planning;
+            content.push_str(&if auto {
+                format!("\nAuto workflow starting on {}; /analyze <ID> runs the others.", first.dataset.filename)
+            } else {
+                "\n/analyze <ID> runs any of them; change a species with /species if it was detected wrongly.".to_string()
+            });
+        }
+        self.messages.push(ChatMessage {
+            role: MessageRole::System,
+            content,
+            timestamp: Utc::now(),
+        });
+        if let (true, Some(record)) = (auto, first) {
+            let Some(tx) = self.event_tx.clone() else {
+                return;
+            };
+            let config = self.config.clone();
+            let cancel = self.start_run();
+            tokio::spawn(async move {
+                Self::run_automated_workflow(record, config, cancel, tx, None).await;
+            });
+        }
+    }
+
+    /// Make the active project current: its drafts and active dataset, with
+    /// the previous project's workflow state dropped. Returns the message
+    /// announcing it.
+    fn open_project(&mut self) -> String {
+        self.drafts = self
+            .projects
+            .drafts_path()
+            .map_or_else(DraftHistory::default, |path| DraftHistory::load(&path));
+        let project = self.projects.active();
+        self.last_dataset_id = project.active_dataset.clone();
+        self.workflow_stage = if project.datasets.is_empty() {
+            WorkflowStage::Upload
+        } else {
+            WorkflowStage::Planning
+        };
+        self.current_objective = None;
+        self.planning_result = None;
+        self.literature_results.clear();
+        self.findings_summary = None;
+        self.manuscript_base = None;
+        self.feedbacks.clear();
+        self.latex_output = None;
+        self.last_analysis = None;
+        self.last_biomarkers.clear();
+        let content = format!(
+            "Project: {}\nActive dataset: {} | Drafts: {}\n{}",
+            project.describe(),
+            project.active_dataset.as_deref().unwrap_or("none"),
+            self.drafts.drafts().len(),
+            if project.datasets.is_empty() {
+                "Upload a dataset to start."
+            } else {
+                "/list shows its datasets; /analyze runs the active one."
+            }
+        );
+        self.load_project_datasets();
+        content
+    }
+
+    /// Load the datasets of the active project that are not registered
+    /// (e.g. after a restart) again from their files, in the background
+    fn load_project_datasets(&self) {
+        let Some(tx) = self.event_tx.clone() else {
+            return;
+        };
+        let project = self.projects.active().clone();
+        if project.datasets.is_empty() {
+            return;
+        }
+        let registry = self.dataset_registry.clone();
+        tokio::spawn(async move {
+            let (mut loaded, mut failed) = (0, Vec::new());
+            for dataset in project.datasets {
+                if registry.get(&dataset.id).await.is_some() {
+                    continue;
+                }
+                let path = std::path::PathBuf::from(&dataset.path);
+                let (description, parsing) = (Some(dataset.description.clone()), dataset.parsing.clone());
+                let record = tokio::task::spawn_blocking(move || DatasetRecord::from_path_with(&path, description, parsing))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|r| r);
+                match record {
+                    Ok(mut record) => {
+                        record.dataset.id = dataset.id;
+                        registry.insert(record).await;
+                        loaded += 1;
+                    }
+                    Err(e) => failed.push(format!("{} ({}): {:#}", dataset.filename, dataset.path, e)),
+                }
+            }
+            if loaded > 0 || !failed.is_empty() {
+                let _ = tx.send(AppEvent::ProjectLoaded { project: project.name, loaded, failed }).await;
+            }
+        });
+    }
+
+    /// Continue the automated workflow of a saved plan, loading its dataset
+    /// again when it is no longer registered (e.g. after a restart)
+    fn resume_workflow(&mut self, plan: SavedPlan) {
+        let Some(tx) = self.event_tx.clone() else {
+            return;
+        };
+        self.messages.push(ChatMessage {
+            role: MessageRole::System,
+            content: format!(
+                "Resuming the workflow on dataset {} ({} of {} tasks left)...",
+                plan.conversation_id,
+                plan.incomplete(),
+                plan.tasks.len()
+            ),
+            timestamp: Utc::now(),
+        });
+        self.last_dataset_id = Some(plan.conversation_id.clone());
+        let registry = self.dataset_registry.clone();
+        let config = self.config.clone();
+        let cancel = self.start_run();
+        tokio::spawn(async move {
+            let record = match registry.get(&plan.conversation_id).await {
+                Some(record) => record,
+                None => {
+                    let path = std::path::PathBuf::from(&plan.dataset_path);
+                    let description = Some(plan.description.clone());
+                    let parsing = plan.parsing.clone();
+                    let loaded = tokio::task::spawn_blocking(move || {
+                        DatasetRecord::from_path_with(&path, description, parsing)
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|r| r);
+                    match loaded {
+                        Ok(mut record) => {
+                            record.dataset.id = plan.conversation_id.clone();
+                            registry.insert(record.clone()).await;
+                            record
+                        }
+                        Err(e) => {
+                            let _ = tx
+                                .send(AppEvent::Error {
+                                    action: format!("Loading {} to resume", plan.dataset_path),
+                                    error: AppError::data(e),
+                                })
+                                .await;
+                            return;
+                        }
+                    }
+                }
+            };
+            Self::run_automated_workflow(record, config, cancel, tx, Some(plan)).await;
+        });
     }
 
-    async fn load_dataset_from_path(
-        &self,
-        path: &str,
-        description: Option<String>,
-    ) -> Result<DatasetRecord, String> {
-        // Clean up the path: trim whitespace, expand home directory
-        let path = path.trim();
-        
-        // Expand ~ to home directory
-        let expanded_path = if path.starts_with("~/") {
-            if let Some(home) = dirs::home_dir() {
-                home.join(&path[2..])
-            } else {
-                std::path::PathBuf::from(path)
-            }
-        } else if path == "~" {
-            dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from(path))
-        } else {
-            std::path::PathBuf::from(path)
-        };
-        
-        // Convert to absolute path if relative
-        let absolute_path = if expanded_path.is_absolute() {
-            expanded_path
-        } else {
-            std::env::current_dir()
-                .map_err(|e| format!("Failed to get current directory: {}", e))?
-                .join(&expanded_path)
-        };
-        
+    /// Absolute path of the dataset file named by `path`, with `~` expanded
+    fn resolve_upload_path(path: &str) -> AppResult<std::path::PathBuf> {
+        let absolute_path = paths::resolve(path);
+
         // Check if file exists before trying to read
         if !absolute_path.exists() {
-            return Err(format!(
-                "File not found: {}\n\nPlease check:\n\
+            return Err(AppError::NotFound(format!(
+                "{}\n\nPlease check:\n\
                  1. The file path is correct\n\
                  2. The file exists at that location\n\
                  3. You have permission to read the file",
                 absolute_path.display()
-            ));
+            )));
         }
         
         if !absolute_path.is_file() {
-            return Err(format!(
-                "Path is not a file: {}\n\nPlease provide a path to a .csv or .tsv file.",
+            return Err(AppError::InvalidRequest(format!(
+                "Path is not a file: {}\n\nPlease provide a path to a .csv, .tsv, .parquet, .vcf, FASTA/FASTQ or GEO series matrix/SOFT file.",
                 absolute_path.display()
-            ));
+            )));
         }
         
-        let extension = absolute_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("")
-            .to_ascii_lowercase();
-        if extension != "csv" && extension != "tsv" {
-            return Err(format!(
-                "Only .csv or .tsv files are supported.\nYour file has extension: .{}",
-                extension
-            ));
-        }
-        let delimiter = if extension == "tsv" { b'\t' } else { b',' };
-        let bytes = tokio::fs::read(&absolute_path)
-            .await
-            .map_err(|e| format!("Failed to read file {}: {}", absolute_path.display(), e))?;
-        let dataset_id = Uuid::new_v4().to_string();
-        let upload_dir = std::path::Path::new("uploads");
-        tokio::fs::create_dir_all(upload_dir)
-            .await
-            .map_err(|e| format!("Failed to create uploads directory: {}", e))?;
-        let filename = absolute_path
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("dataset.csv")
-            .to_string();
-        let stored_name = format!("{}-{}", dataset_id, filename);
-        let local_path = upload_dir.join(&stored_name);
-        tokio::fs::write(&local_path, &bytes)
-            .await
-            .map_err(|e| e.to_string())?;
-
-        let (columns, row_count) = infer_csv_metadata(&bytes, delimiter)?;
-        validate_microarray_headers(&columns)?;
-
-        let dataset = UploadedDataset {
-            filename: filename.clone(),
-            id: dataset_id.clone(),
-            description: description.unwrap_or_else(|| format!("Uploaded dataset {}", filename)),
-            path: Some(local_path.to_string_lossy().to_string()),
-            content: None,
-            size: Some(bytes.len() as i64),
-        };
+        Ok(absolute_path)
+    }
 
-        Ok(DatasetRecord {
-            dataset,
-            local_path: local_path.to_string_lossy().to_string(),
-            content_type: "text/plain".to_string(),
-            delimiter,
-            has_headers: true,
-            columns,
-            row_count,
-        })
+    /// Fetch the gene and ortholog tables of `species` in the background
+    fn fetch_annotation(&self, species: Species) {
+        let (annotation, network) = (self.config.annotation.clone(), self.config.network.clone());
+        tokio::spawn(async move { crate::annotation::ensembl::ensure_cached(&annotation, &network, species).await });
     }
 
     async fn advance_workflow(&mut self) {
+        let findings = self.last_analysis.as_ref().map(|a| a.findings());
         match self.workflow_stage {
             WorkflowStage::Upload => {
                 self.messages.push(ChatMessage {
@@ -1105,7 +3764,7 @@ Tip: run /upload first, then /analyze."
                 if let Err(e) = self.run_planning_stage().await {
                     self.messages.push(ChatMessage {
                         role: MessageRole::System,
-                        content: format!("Planning failed: {}", e),
+                        content: format!("Planning failed: {}", e.user_message()),
                         timestamp: Utc::now(),
                     });
                 } else {
@@ -1116,7 +3775,7 @@ Tip: run /upload first, then /analyze."
                 if let Err(e) = self.run_literature_stage().await {
                     self.messages.push(ChatMessage {
                         role: MessageRole::System,
-                        content: format!("Literature review failed: {}", e),
+                        content: format!("Literature review failed: {}", e.user_message()),
                         timestamp: Utc::now(),
                     });
                 } else {
@@ -1127,7 +3786,7 @@ Tip: run /upload first, then /analyze."
                 if let Err(e) = self.run_findings_stage().await {
                     self.messages.push(ChatMessage {
                         role: MessageRole::System,
-                        content: format!("Findings generation failed: {}", e),
+                        content: format!("Findings generation failed: {}", e.user_message()),
                         timestamp: Utc::now(),
                     });
                 } else {
@@ -1135,33 +3794,24 @@ Tip: run /upload first, then /analyze."
                 }
             }
             WorkflowStage::ResearcherFeedback | WorkflowStage::Draft1 => {
-                let draft = self.build_draft(1);
-                self.draft_versions.push(draft.clone());
-                self.messages.push(ChatMessage {
-                    role: MessageRole::Assistant,
-                    content: format!("Draft 1:\n\n{}", draft),
-                    timestamp: Utc::now(),
-                });
+                let revision =
+                    agents::DraftingAgent::revise(1, self.build_draft(1), &self.feedbacks, findings.as_ref(), &self.config)
+                        .await;
+                self.save_draft(1, revision);
                 self.workflow_stage = WorkflowStage::UserFeedback1;
             }
             WorkflowStage::UserFeedback1 | WorkflowStage::Draft2 => {
-                let draft = self.build_draft(2);
-                self.draft_versions.push(draft.clone());
-                self.messages.push(ChatMessage {
-                    role: MessageRole::Assistant,
-                    content: format!("Draft 2:\n\n{}", draft),
-                    timestamp: Utc::now(),
-                });
+                let revision =
+                    agents::DraftingAgent::revise(2, self.build_draft(2), &self.feedbacks, findings.as_ref(), &self.config)
+                        .await;
+                self.save_draft(2, revision);
                 self.workflow_stage = WorkflowStage::UserFeedback2;
             }
             WorkflowStage::UserFeedback2 | WorkflowStage::Draft3 => {
-                let draft = self.build_draft(3);
-                self.draft_versions.push(draft.clone());
-                self.messages.push(ChatMessage {
-                    role: MessageRole::Assistant,
-                    content: format!("Draft 3:\n\n{}", draft),
-                    timestamp: Utc::now(),
-                });
+                let revision =
+                    agents::DraftingAgent::revise(3, self.build_draft(3), &self.feedbacks, findings.as_ref(), &self.config)
+                        .await;
+                self.save_draft(3, revision);
                 self.workflow_stage = WorkflowStage::UserFeedback3;
             }
             WorkflowStage::UserFeedback3 => {
@@ -1182,98 +3832,116 @@ Tip: run /upload first, then /analyze."
         }
     }
 
-    async fn run_planning_stage(&mut self) -> Result<(), String> {
+    async fn run_planning_stage(&mut self) -> AppResult<()> {
         let dataset_id = self
             .last_dataset_id
             .clone()
-            .ok_or_else(|| "No dataset loaded. Use /upload.".to_string())?;
+            .ok_or_else(|| AppError::InvalidRequest("No dataset loaded. Use /upload.".to_string()))?;
         let record = self
             .dataset_registry
             .get(&dataset_id)
             .await
-            .ok_or_else(|| "Dataset not found.".to_string())?;
+            .ok_or_else(|| AppError::NotFound(format!("dataset {}", dataset_id)))?;
         let prompt = format!(
             "Create a research plan to discover aging biomarkers from log2-normalized microarray data. \
 Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary variables.",
             record.row_count,
             record.columns.len()
         );
-        match agents::PlanningAgent::generate_plan(&prompt, None, &self.config).await {
-            Ok(plan) => {
-                self.planning_result = Some(plan.clone());
-                self.messages.push(ChatMessage {
-                    role: MessageRole::Assistant,
-                    content: format!("Research plan generated:\n{}", plan.current_objective),
-                    timestamp: Utc::now(),
-                });
-                Ok(())
-            }
-            Err(e) => Err(e.to_string()),
-        }
+        let state = agents::data_state(&prompt, self.projects.active().prompt_context(), None, Vec::new());
+        let plan =
+            agents::PlanningAgent::generate_plan(&prompt, state.as_ref(), &self.config, &CancellationToken::new()).await?;
+        self.messages.push(ChatMessage {
+            role: MessageRole::Assistant,
+            content: format!("Research plan generated:\n{}", plan.current_objective),
+            timestamp: Utc::now(),
+        });
+        self.planning_result = Some(plan);
+        Ok(())
     }
 
-    async fn run_literature_stage(&mut self) -> Result<(), String> {
+    async fn run_literature_stage(&mut self) -> AppResult<()> {
         let plan = self
             .planning_result
             .clone()
-            .ok_or_else(|| "No plan available. Run /next after planning.".to_string())?;
+            .ok_or_else(|| AppError::InvalidRequest("No plan available. Run /next after planning.".to_string()))?;
         let mut results = Vec::new();
         for task in plan.plan.iter().filter(|t| t.task_type == "LITERATURE") {
-            match agents::LiteratureAgent::execute_task(task, &self.config).await {
-                Ok(result) => results.push(result),
-                Err(e) => {
-                    return Err(format!("Literature task failed: {}", e));
-                }
-            }
+            results.push(agents::LiteratureAgent::execute_task(task, &self.config, &CancellationToken::new()).await?);
         }
         self.literature_results = results;
         self.messages.push(ChatMessage {
             role: MessageRole::Assistant,
-            content: format!(
-                "Literature review complete. Sources: {}",
-                self.literature_results.len()
-            ),
+            content: literature_summary(&self.literature_results),
             timestamp: Utc::now(),
         });
         Ok(())
     }
 
-    async fn run_findings_stage(&mut self) -> Result<(), String> {
+    async fn run_findings_stage(&mut self) -> AppResult<()> {
         let dataset_id = self
             .last_dataset_id
             .clone()
-            .ok_or_else(|| "No dataset loaded. Use /upload.".to_string())?;
+            .ok_or_else(|| AppError::InvalidRequest("No dataset loaded. Use /upload.".to_string()))?;
         let record = self
             .dataset_registry
             .get(&dataset_id)
             .await
-            .ok_or_else(|| "Dataset not found.".to_string())?;
+            .ok_or_else(|| AppError::NotFound(format!("dataset {}", dataset_id)))?;
         let output_dir = std::path::Path::new("artifacts")
             .join("analysis")
             .join(&dataset_id);
         tokio::fs::create_dir_all(&output_dir)
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", output_dir.display(), e)))?;
         let config = AnalysisConfig {
             target_column: Some("age".to_string()),
             group_column: Some("cell_type".to_string()),
-            covariates: Vec::new(),
-            boxplot_column: None,
-            max_columns: 50,
-            max_groups: 20,
+            ..Default::default()
         };
-        let analysis = run_analysis(&record, &config, &output_dir).map_err(|e| e.to_string())?;
-        let manuscript = build_manuscript(&dataset_id, "age", "cell_type", &record, &analysis);
-        self.findings_summary = Some(analysis.summary.clone());
+        let started = std::time::Instant::now();
+        let mut analysis = run_analysis(&record, &config, &output_dir, &CancellationToken::new())?;
+        crate::metering::record_analysis(started, &dataset_id);
+        literature::cross_reference(&self.config, &mut analysis, self.config.cross_reference.top_n).await;
+        legends::polish(&self.config, &mut analysis).await;
+        let template = self.config.manuscript.template;
+        let manuscript = build_manuscript(&dataset_id, "age", "cell_type", &record, &analysis, template);
+        supplement::write_supplement(&output_dir, &record, &config, &analysis, template)
+            .map_err(|e| AppError::io(e.context("Failed to write supplementary bundle")))?;
+        let findings = analysis.findings();
+        self.findings_summary = Some(findings.results_text());
         self.manuscript_base = Some(manuscript.clone());
+        self.projects.update(|p| p.add_analysis(&dataset_id, &output_dir, &analysis.summary));
+        self.last_analysis = Some(analysis.clone());
         self.messages.push(ChatMessage {
             role: MessageRole::Assistant,
-            content: format!("Findings generated.\n{}", analysis.summary),
+            content: format!("Findings generated.\n{}{}", findings.to_markdown(), qc_section(&analysis)),
             timestamp: Utc::now(),
         });
         Ok(())
     }
 
+    /// Add a workflow draft to the history and show it
+    fn save_draft(&mut self, version: usize, revision: agents::Revision) {
+        let dataset_id = self.last_dataset_id.clone();
+        let model = revision.model.clone();
+        let record = self.drafts.push(version, revision.text, self.feedbacks.clone(), model, dataset_id);
+        self.messages.push(ChatMessage {
+            role: MessageRole::Assistant,
+            content: format!("Draft {} (#{}):\n\n{}", version, record.id, record.text),
+            timestamp: Utc::now(),
+        });
+    }
+
+    /// Where `/latex` saves the manuscript: next to the current dataset's
+    /// analysis artifacts when there is one
+    fn manuscript_dir(&self) -> std::path::PathBuf {
+        match &self.last_dataset_id {
+            Some(id) => std::path::Path::new("artifacts").join("analysis").join(id),
+            None => std::path::Path::new("artifacts").join("manuscript"),
+        }
+    }
+
     fn build_draft(&self, version: usize) -> String {
         let base = self
             .manuscript_base
@@ -1284,18 +3952,7 @@ Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary varia
             .as_ref()
             .map(|p| p.current_objective.clone())
             .unwrap_or_else(|| "No plan available.".to_string());
-        let literature = if self.literature_results.is_empty() {
-            "No literature sources available.".to_string()
-        } else {
-            let items = self
-                .literature_results
-                .iter()
-                .take(5)
-                .map(|r| r.objective.clone())
-                .collect::<Vec<_>>()
-                .join(", ");
-            format!("Key literature tasks: {}", items)
-        };
+        let literature = crate::manuscript::literature_section(&self.literature_results);
         let feedback = if self.feedbacks.is_empty() {
             "No feedback provided.".to_string()
         } else {
@@ -1307,149 +3964,227 @@ Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary varia
         )
     }
 
-    fn render_latex(&self, draft: &str) -> String {
-        let mut latex = String::new();
-        latex.push_str("\\documentclass{article}\n");
-        latex.push_str("\\usepackage[margin=1in]{geometry}\n");
-        latex.push_str("\\usepackage{graphicx}\n");
-        latex.push_str("\\begin{document}\n");
-        for line in draft.lines() {
-            if line.trim().is_empty() {
-                latex.push_str("\n\n");
-            } else if line.starts_with("Draft") || line.starts_with("Project ID") || line.starts_with("Title") {
-                latex.push_str(&format!("\\section*{{{}}}\n", line.replace("_", "\\_")));
-            } else {
-                latex.push_str(&format!("{}\\\\\n", line.replace("_", "\\_")));
-            }
+    /// Token for a new background run, cancelling any previous one
+    fn start_run(&mut self) -> CancellationToken {
+        if let Some(previous) = self.running.take() {
+            previous.cancel();
         }
-        latex.push_str("\\end{document}\n");
-        latex
+        self.pending_approval = None;
+        let cancel = CancellationToken::new();
+        self.running = Some(cancel.clone());
+        cancel
     }
 
-    /// Run the research pipeline in background
-    async fn run_research_pipeline(message: String, config: Config, tx: mpsc::Sender<AppEvent>) {
-        // Planning stage
-        tx.send(AppEvent::StageChanged(PipelineStage::Planning))
-            .await
-            .ok();
-
-        let planning_result = agents::PlanningAgent::generate_plan(&message, None, &config).await;
-
-        match planning_result {
-            Ok(plan) => {
-                tx.send(AppEvent::ObjectiveUpdated(plan.current_objective.clone()))
-                    .await
-                    .ok();
-
-                // Literature search stage
-                let mut literature_results = Vec::new();
-                let total_tasks = plan.plan.len();
-
-                for (i, task) in plan.plan.iter().enumerate() {
-                    tx.send(AppEvent::StageChanged(PipelineStage::Literature {
-                        task_index: i,
-                        total: total_tasks,
-                        current_task: task.objective.clone(),
-                    }))
-                    .await
-                    .ok();
-
-                    match agents::LiteratureAgent::execute_task(task, &config).await {
-                        Ok(result) => literature_results.push(result),
-                        Err(e) => {
-                            warn!("Literature task failed: {}", e);
-                        }
-                    }
-                }
-
-                // Generating stage
-                tx.send(AppEvent::StageChanged(PipelineStage::Generating))
-                    .await
-                    .ok();
-
-                let reply_mode = agents::ReplyAgent::classify_mode(&message);
-                let tx_chunks = tx.clone();
-                let response = agents::ReplyAgent::generate_response_streaming(
-                    &message,
-                    Some(&plan),
-                    &literature_results,
-                    reply_mode,
-                    &config,
-                    move |chunk| {
-                        let _ = tx_chunks.try_send(AppEvent::ResponseChunk(chunk.to_string()));
-                    },
-                )
-                .await;
+    /// Gates of the automated workflow, for /gates
+    fn describe_gates(&self) -> String {
+        let workflow = &self.config.workflow;
+        if workflow.gates.is_empty() {
+            return "Approval gates: none (the automated workflow runs straight through)".to_string();
+        }
+        let gates: Vec<&str> = workflow.gates.iter().map(|g| g.as_str()).collect();
+        let timeout = match workflow.gate_timeout() {
+            Some(timeout) => format!("continue on their own after {}s", timeout.as_secs()),
+            None => "wait until /approve or /feedback".to_string(),
+        };
+        format!("Approval gates: {}; they {}", gates.join(", "), timeout)
+    }
 
-                match response {
-                    Ok(text) => {
-                        tx.send(AppEvent::ResponseComplete(text)).await.ok();
-                    }
-                    Err(e) => {
-                        tx.send(AppEvent::Error(e.to_string())).await.ok();
-                    }
-                }
+    /// Wait at `gate` of the automated workflow for /approve or /feedback,
+    /// or until the gate's timeout passes; approves straight away when the
+    /// gate is off. `None` when the run was cancelled, which is reported.
+    async fn await_approval(
+        config: &Config,
+        gate: ApprovalGate,
+        label: &str,
+        cancel: &CancellationToken,
+        tx: &mpsc::Sender<AppEvent>,
+    ) -> Option<GateDecision> {
+        if !config.workflow.gate_enabled(gate) {
+            return Some(GateDecision::Approve);
+        }
+        let (reply, decision) = oneshot::channel();
+        let _ = tx
+            .send(AppEvent::ApprovalRequested { label: label.to_string(), reply })
+            .await;
+        let wait = async {
+            match config.workflow.gate_timeout() {
+                Some(timeout) => tokio::time::timeout(timeout, decision).await.ok(),
+                None => Some(decision.await),
             }
-            Err(e) => {
-                tx.send(AppEvent::Error(e.to_string())).await.ok();
+        };
+        match cancel.run_until_cancelled(wait).await {
+            Some(Some(Ok(decision))) => Some(decision),
+            Some(None) => {
+                let _ = tx
+                    .send(AppEvent::WorkflowMessage(
+                        MessageRole::System,
+                        format!("No reply on {}; continuing.", label),
+                    ))
+                    .await;
+                Some(GateDecision::Approve)
+            }
+            // The TUI let go of the gate without answering
+            Some(Some(Err(_))) => Some(GateDecision::Approve),
+            None => {
+                let _ = tx.send(AppEvent::Error { action: "Workflow".to_string(), error: AppError::Cancelled }).await;
+                None
             }
         }
     }
 
+    /// Run the research pipeline in background, forwarding its events
+    async fn run_research_pipeline(
+        message: String,
+        state: Option<crate::models::ConversationState>,
+        config: Config,
+        cancel: CancellationToken,
+        tx: mpsc::Sender<AppEvent>,
+    ) {
+        // The pipeline reports synchronously; a relay keeps every event
+        // (tokens included) without blocking it on a full UI channel
+        let (relay_tx, mut relay_rx) = mpsc::unbounded_channel();
+        let relay = tokio::spawn(async move {
+            while let Some(event) = relay_rx.recv().await {
+                if tx.send(AppEvent::Pipeline(event)).await.is_err() {
+                    break;
+                }
+            }
+        });
+        // Failures arrive as the `Failed` event
+        let _ = agents::execute_research_pipeline(&message, state.as_ref(), &config, &cancel, |event| {
+            let _ = relay_tx.send(event);
+        })
+        .await;
+        drop(relay_tx);
+        let _ = relay.await;
+    }
+
+    /// Run the workflow on `record` from planning to LaTeX, saving the plan
+    /// and its task status as it goes. With a saved plan (`/resume`) an
+    /// approved plan is not generated again and literature tasks that are
+    /// done are not run again.
     async fn run_automated_workflow(
         record: DatasetRecord,
         config: Config,
+        cancel: CancellationToken,
         tx: mpsc::Sender<AppEvent>,
+        resume: Option<SavedPlan>,
     ) {
         let dataset_id = record.dataset.id.clone();
+        let mut plans = PlanStore::load_default();
+        // Brief and glossary of the project, for the planner
+        let project =
+            agents::data_state(&dataset_id, ProjectStore::load_default().active().prompt_context(), None, Vec::new());
 
-        let _ = tx
-            .send(AppEvent::WorkflowStageUpdated(WorkflowStage::Planning))
-            .await;
-        let plan_prompt = format!(
-            "Create a research plan to discover aging biomarkers from log2-normalized microarray data. \
-Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary variables.",
-            record.row_count,
-            record.columns.len()
-        );
-        let planning_result = agents::PlanningAgent::generate_plan(&plan_prompt, None, &config).await;
-        let plan = match planning_result {
-            Ok(plan) => {
+        let mut plan = match resume {
+            Some(plan) if plan.approved => {
                 let _ = tx
                     .send(AppEvent::WorkflowMessage(
                         MessageRole::Assistant,
-                        format!("Research plan generated:\n{}", plan.current_objective),
+                        format!(
+                            "Resuming the research plan ({} of {} tasks left):\n{}",
+                            plan.incomplete(),
+                            plan.tasks.len(),
+                            plan.summary()
+                        ),
                     ))
                     .await;
                 plan
             }
-            Err(e) => {
+            mut resume => {
                 let _ = tx
-                    .send(AppEvent::Error(format!("Planning failed: {}", e)))
+                    .send(AppEvent::WorkflowStageUpdated(WorkflowStage::Planning))
                     .await;
-                return;
+                let mut plan_prompt = format!(
+                    "Create a research plan to discover aging biomarkers from log2-normalized microarray data. \
+Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary variables.",
+                    record.row_count,
+                    record.columns.len()
+                );
+                // A saved plan that was waiting for approval is asked about
+                // again before a new one is generated
+                loop {
+                    let mut plan = match resume.take() {
+                        Some(plan) => plan,
+                        None => match agents::PlanningAgent::generate_plan(&plan_prompt, project.as_ref(), &config, &cancel)
+                            .await
+                        {
+                            Ok(result) => SavedPlan::new(&record, &result),
+                            Err(e) => {
+                                let _ = tx
+                                    .send(AppEvent::Error { action: "Planning".to_string(), error: e })
+                                    .await;
+                                return;
+                            }
+                        },
+                    };
+                    plans.put(&mut plan);
+                    let _ = tx
+                        .send(AppEvent::WorkflowMessage(
+                            MessageRole::Assistant,
+                            format!("Research plan generated:\n{}", plan.summary()),
+                        ))
+                        .await;
+                    match Self::await_approval(&config, ApprovalGate::Plan, "the research plan", &cancel, &tx).await {
+                        Some(GateDecision::Approve) => {
+                            plan.approved = true;
+                            plans.put(&mut plan);
+                            break plan;
+                        }
+                        Some(GateDecision::Feedback(feedback)) => {
+                            plan_prompt.push_str(&format!("\n\nResearcher feedback on the previous plan: {}", feedback));
+                        }
+                        None => return,
+                    }
+                }
             }
         };
 
         let _ = tx
             .send(AppEvent::WorkflowStageUpdated(WorkflowStage::Literature))
             .await;
-        let mut literature_results = Vec::new();
-        for task in plan.plan.iter().filter(|t| t.task_type == "LITERATURE") {
-            match agents::LiteratureAgent::execute_task(task, &config).await {
-                Ok(result) => literature_results.push(result),
+        for index in 0..plan.tasks.len() {
+            let saved = &plan.tasks[index];
+            if saved.task.task_type != "LITERATURE" || saved.status == TaskStatus::Done {
+                continue;
+            }
+            let task = saved.task.clone();
+            plan.tasks[index].status = TaskStatus::Running;
+            plans.put(&mut plan);
+            let outcome = agents::LiteratureAgent::execute_task(&task, &config, &cancel).await;
+            let saved = &mut plan.tasks[index];
+            match outcome {
+                Ok(result) => {
+                    saved.status = TaskStatus::Done;
+                    saved.result = Some(result);
+                    saved.error = None;
+                    plans.put(&mut plan);
+                }
                 Err(e) => {
+                    // A cancelled task has not failed; it runs again on /resume
+                    if matches!(e, AppError::Cancelled) {
+                        saved.status = TaskStatus::Pending;
+                    } else {
+                        saved.status = TaskStatus::Failed;
+                        saved.error = Some(e.to_string());
+                    }
+                    plans.put(&mut plan);
+                    let _ = tx.send(AppEvent::Error { action: "Literature task".to_string(), error: e }).await;
                     let _ = tx
-                        .send(AppEvent::Error(format!("Literature task failed: {}", e)))
+                        .send(AppEvent::WorkflowMessage(
+                            MessageRole::System,
+                            "/resume retries the tasks that are not done.".to_string(),
+                        ))
                         .await;
                     return;
                 }
             }
         }
+        let literature_results = plan.literature_results();
         let _ = tx
             .send(AppEvent::WorkflowMessage(
                 MessageRole::Assistant,
-                format!("Literature review complete. Sources: {}", literature_results.len()),
+                literature_summary(&literature_results),
             ))
             .await;
 
@@ -1461,72 +4196,123 @@ Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary varia
             .join(&dataset_id);
         if let Err(e) = tokio::fs::create_dir_all(&output_dir).await {
             let _ = tx
-                .send(AppEvent::Error(format!("Failed to create artifacts dir: {}", e)))
+                .send(AppEvent::Error {
+                    action: "Creating the artifacts directory".to_string(),
+                    error: AppError::Internal(e.to_string()),
+                })
                 .await;
             return;
         }
-        let analysis = match run_analysis(
-            &record,
-            &AnalysisConfig {
-                target_column: Some("age".to_string()),
-                group_column: Some("cell_type".to_string()),
-                covariates: Vec::new(),
-                boxplot_column: None,
-                max_columns: 50,
-                max_groups: 20,
-            },
-            &output_dir,
-        ) {
-            Ok(result) => result,
+        let started = std::time::Instant::now();
+        let analysis_config = AnalysisConfig {
+            target_column: Some("age".to_string()),
+            group_column: Some("cell_type".to_string()),
+            ..Default::default()
+        };
+        // The local analysis always runs again: its results are files in
+        // the output directory, not part of the saved plan
+        plan.mark("ANALYSIS", TaskStatus::Running, None);
+        plans.put(&mut plan);
+        let outcome = run_analysis(&record, &analysis_config, &output_dir, &cancel);
+        crate::metering::record_analysis(started, &dataset_id);
+        let mut analysis = match outcome {
+            Ok(result) => {
+                plan.mark("ANALYSIS", TaskStatus::Done, None);
+                plans.put(&mut plan);
+                result
+            }
             Err(e) => {
+                plan.mark("ANALYSIS", TaskStatus::Failed, Some(&e.to_string()));
+                plans.put(&mut plan);
                 let _ = tx
-                    .send(AppEvent::Error(format!("Analysis failed: {}", e)))
+                    .send(AppEvent::Error { action: "Analysis".to_string(), error: e })
                     .await;
                 return;
             }
         };
-        let manuscript = build_manuscript(&dataset_id, "age", "cell_type", &record, &analysis);
-        let _ = tx
-            .send(AppEvent::WorkflowMessage(
-                MessageRole::Assistant,
-                format!("Findings generated.\n{}", analysis.summary),
-            ))
-            .await;
-
-        let _ = tx
-            .send(AppEvent::WorkflowStageUpdated(WorkflowStage::Draft1))
-            .await;
-        let draft1 = Self::build_automated_draft(1, &manuscript, &plan, &literature_results);
-        let _ = tx
-            .send(AppEvent::WorkflowMessage(
-                MessageRole::Assistant,
-                format!("Draft 1:\n\n{}", draft1),
-            ))
-            .await;
-
-        let _ = tx
-            .send(AppEvent::WorkflowStageUpdated(WorkflowStage::Draft2))
-            .await;
-        let draft2 = Self::build_automated_draft(2, &manuscript, &plan, &literature_results);
+        literature::cross_reference(&config, &mut analysis, config.cross_reference.top_n).await;
+        legends::polish(&config, &mut analysis).await;
+        let template = config.manuscript.template;
+        let manuscript = build_manuscript(&dataset_id, "age", "cell_type", &record, &analysis, template);
+        let findings = analysis.findings();
+        match supplement::write_supplement(&output_dir, &record, &analysis_config, &analysis, template) {
+            Ok(path) => {
+                let _ = tx.send(AppEvent::Pipeline(agents::PipelineEvent::artifact(&path))).await;
+            }
+            Err(e) => {
+                let _ = tx
+                    .send(AppEvent::Error { action: "Writing the supplementary bundle".to_string(), error: AppError::io(e) })
+                    .await;
+            }
+        }
         let _ = tx
             .send(AppEvent::WorkflowMessage(
                 MessageRole::Assistant,
-                format!("Draft 2:\n\n{}", draft2),
+                format!("Findings generated.\n{}{}", findings.to_markdown(), qc_section(&analysis)),
             ))
             .await;
+        // Feedback on the findings goes into every draft
+        let mut feedback = Vec::new();
+        match Self::await_approval(&config, ApprovalGate::Findings, "the findings", &cancel, &tx).await {
+            Some(GateDecision::Approve) => {}
+            Some(GateDecision::Feedback(text)) => feedback.push(text),
+            None => return,
+        }
 
-        let _ = tx
-            .send(AppEvent::WorkflowStageUpdated(WorkflowStage::Draft3))
-            .await;
-        let draft3 = Self::build_automated_draft(3, &manuscript, &plan, &literature_results);
-        let _ = tx
-            .send(AppEvent::WorkflowMessage(
-                MessageRole::Assistant,
-                format!("Draft 3:\n\n{}", draft3),
-            ))
-            .await;
+        let planning_result = plan.planning_result();
+        let mut draft = String::new();
+        for (version, stage) in [(1, WorkflowStage::Draft1), (2, WorkflowStage::Draft2), (3, WorkflowStage::Draft3)] {
+            if cancel.is_cancelled() {
+                let _ = tx.send(AppEvent::Error { action: "Workflow".to_string(), error: AppError::Cancelled }).await;
+                return;
+            }
+            let _ = tx.send(AppEvent::WorkflowStageUpdated(stage)).await;
+            loop {
+                let revision = agents::DraftingAgent::revise(
+                    version,
+                    crate::manuscript::automated_draft(version, &manuscript, Some(&planning_result), &literature_results),
+                    &feedback,
+                    Some(&findings),
+                    &config,
+                )
+                .await;
+                let _ = tx
+                    .send(AppEvent::WorkflowMessage(
+                        MessageRole::Assistant,
+                        format!("Draft {}:\n\n{}", version, revision.text),
+                    ))
+                    .await;
+                draft = revision.text.clone();
+                let _ = tx.send(AppEvent::DraftRevised(version, revision, feedback.clone())).await;
+                let label = format!("Draft {}", version);
+                match Self::await_approval(&config, ApprovalGate::Drafts, &label, &cancel, &tx).await {
+                    Some(GateDecision::Approve) => break,
+                    Some(GateDecision::Feedback(text)) => feedback.push(text),
+                    None => return,
+                }
+            }
+        }
 
-        let latex = Self::render_latex_static(&draft3);
+        let latex = crate::manuscript::render_latex(
+            &draft,
+            &crate::manuscript::Bibliography::from_literature(&literature_results),
+            Some(&analysis),
+            template,
+        );
+        match crate::manuscript::export_latex(&output_dir, &draft, &literature_results, Some(&analysis), template) {
+            Ok(paths) => {
+                for path in paths {
+                    let _ = tx.send(AppEvent::Pipeline(agents::PipelineEvent::artifact(&path))).await;
+                }
+            }
+            Err(e) => {
+                let _ = tx
+                    .send(AppEvent::Error { action: "Saving LaTeX".to_string(), error: AppError::io(e) })
+                    .await;
+            }
+        }
+        plan.finished = true;
+        plans.put(&mut plan);
         let _ = tx
             .send(AppEvent::WorkflowStageUpdated(WorkflowStage::LatexReady))
             .await;
@@ -1538,51 +4324,6 @@ Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary varia
             .await;
     }
 
-    fn build_automated_draft(
-        version: usize,
-        manuscript: &str,
-        plan: &PlanningResult,
-        literature_results: &[LiteratureResult],
-    ) -> String {
-        let literature = if literature_results.is_empty() {
-            "No literature sources available.".to_string()
-        } else {
-            let items = literature_results
-                .iter()
-                .take(5)
-                .map(|r| r.objective.clone())
-                .collect::<Vec<_>>()
-                .join(", ");
-            format!("Key literature tasks: {}", items)
-        };
-        format!(
-            "Draft {version}\n\n{manuscript}\n\nResearch Plan:\n{}\n\nLiterature Review:\n{literature}\n",
-            plan.current_objective
-        )
-    }
-
-    fn render_latex_static(draft: &str) -> String {
-        let mut latex = String::new();
-        latex.push_str("\\documentclass{article}\n");
-        latex.push_str("\\usepackage[margin=1in]{geometry}\n");
-        latex.push_str("\\usepackage{graphicx}\n");
-        latex.push_str("\\begin{document}\n");
-        for line in draft.lines() {
-            if line.trim().is_empty() {
-                latex.push_str("\n\n");
-            } else if line.starts_with("Draft")
-                || line.starts_with("Project ID")
-                || line.starts_with("Title")
-            {
-                latex.push_str(&format!("\\section*{{{}}}\n", line.replace("_", "\\_")));
-            } else {
-                latex.push_str(&format!("{}\\\\\n", line.replace("_", "\\_")));
-            }
-        }
-        latex.push_str("\\end{document}\n");
-        latex
-    }
-
     /// Save the current setting
     async fn save_current_setting(&mut self) {
         if !self.settings_show_input || self.settings_input.is_empty() {
@@ -1598,25 +4339,19 @@ Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary varia
             // Ensure search engines are enabled when key is set
             self.settings.search.scholar_enabled = true;
             self.settings.search.light_enabled = true;
+        } else if provider_id == "ncbi" {
+            self.settings.search.ncbi_api_key = Some(key);
+        } else if provider_id == "semantic_scholar" {
+            self.settings.search.semantic_scholar_key = Some(key);
         } else {
-            // Update settings (only one LLM provider key at a time)
+            // Keys are kept per provider; routing decides which one each task uses
             if crate::settings::Provider::from_id(provider_id).is_none() {
                 return;
             }
-            self.settings.set_single_provider_key(provider_id, key);
+            self.settings.set_provider_key(provider_id, key);
         }
 
-        // Save to storage
-        if let Err(e) = self.settings_storage.save(&self.settings).await {
-            error!("Failed to save settings: {}", e);
-            self.messages.push(ChatMessage {
-                role: MessageRole::System,
-                content: format!("Failed to save API key: {}", e),
-                timestamp: Utc::now(),
-            });
-        } else {
-            info!("Settings saved for: {}", provider_id);
-        }
+        self.save_settings();
 
         // Refresh provider list
         self.refresh_providers();
@@ -1643,44 +4378,115 @@ Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary varia
         });
     }
 
-    /// Update config from settings
-    fn update_config_from_settings(&mut self) {
-        // Update LLM API keys
-        self.config.llm.openai_api_key =
-            self.settings.openai.api_key.clone().unwrap_or_default();
-        self.config.llm.anthropic_api_key =
-            self.settings.anthropic.api_key.clone().unwrap_or_default();
-        self.config.llm.google_api_key =
-            self.settings.google.api_key.clone().unwrap_or_default();
-        self.config.llm.openrouter_api_key =
-            self.settings.openrouter.api_key.clone().unwrap_or_default();
-        self.config.llm.groq_api_key =
-            self.settings.groq.api_key.clone().unwrap_or_default();
-        
-        // Update LLM provider
-        self.config.llm.default_provider = self.settings.default_provider.to_string();
-        
-        // Update model based on selected provider's default model
-        // This is CRITICAL - without this, the model stays as "gpt-4" which other providers don't recognize
-        use crate::settings::Provider;
-        self.config.llm.default_model = match self.settings.default_provider {
-            Provider::OpenAI => self.settings.openai.default_model.clone()
-                .unwrap_or_else(|| "gpt-4o".to_string()),
-            Provider::Anthropic => self.settings.anthropic.default_model.clone()
-                .unwrap_or_else(|| "claude-sonnet-4-20250514".to_string()),
-            Provider::Google => self.settings.google.default_model.clone()
-                .unwrap_or_else(|| "gemini-2.0-flash".to_string()),
-            Provider::OpenRouter => self.settings.openrouter.default_model.clone()
-                .unwrap_or_else(|| "anthropic/claude-sonnet-4".to_string()),
-            Provider::Groq => self.settings.groq.default_model.clone()
-                .unwrap_or_else(|| "groq/compound".to_string()),
-        };
+    /// Carry out a step of the first-run wizard
+    fn onboarding_action(&mut self, action: OnboardingAction) {
+        match action {
+            OnboardingAction::None => {}
+            OnboardingAction::Test { provider, key } => {
+                let Some(tx) = self.event_tx.clone() else {
+                    return;
+                };
+                let network = self.config.network.clone();
+                tokio::spawn(async move {
+                    let result = crate::settings::connection::test(provider, &key, &network).await;
+                    let _ = tx.send(AppEvent::KeyTested(result)).await;
+                });
+            }
+            OnboardingAction::SaveKey { provider, key } => {
+                self.settings.set_provider_key(&provider.to_string(), key);
+                self.settings.default_provider = provider;
+            }
+            OnboardingAction::SaveSerpApi(key) => {
+                if let Some(key) = key {
+                    self.settings.search.serpapi_key = Some(key);
+                    self.settings.search.scholar_enabled = true;
+                    self.settings.search.light_enabled = true;
+                }
+            }
+            OnboardingAction::Finish { demo } => self.finish_onboarding(demo),
+        }
+    }
+
+    /// Close the first-run wizard and save what it collected, so it is not
+    /// shown again; with `demo`, write the demo dataset and load it
+    fn finish_onboarding(&mut self, demo: bool) {
+        self.onboarding = None;
+        self.view = View::Chat;
+        self.save_settings();
+        self.refresh_providers();
+        self.update_config_from_settings();
+        self.update_api_status();
 
-        // Update Search API config (SerpAPI)
-        self.config.search.serpapi_key =
-            self.settings.search.serpapi_key.clone().unwrap_or_default();
-        self.config.search.scholar_enabled = self.settings.search.scholar_enabled;
-        self.config.search.light_enabled = self.settings.search.light_enabled;
+        let llm_ok = self.config.llm.active_api_key().is_some();
+        let search_ok = self.config.search.serpapi_available();
+        self.messages.push(ChatMessage {
+            role: MessageRole::System,
+            content: format!(
+                "Setup saved. LLM {} | SerpAPI {}\n\nKeys can be changed any time in Settings (Ctrl+S).",
+                if llm_ok { "✓" } else { "✗" },
+                if search_ok { "✓" } else { "✗" }
+            ),
+            timestamp: Utc::now(),
+        });
+        if !demo {
+            return;
+        }
+        let written = crate::demo::generate(&crate::demo::DemoOptions::default())
+            .and_then(|dataset| crate::demo::write(&dataset, std::path::Path::new(DEMO_PATH)));
+        match written {
+            Ok(_) => {
+                self.messages.push(ChatMessage {
+                    role: MessageRole::System,
+                    content: format!(
+                        "Wrote demo data to {} (200 samples, 10 markers planted to track age). \
+                         Once it is loaded, /analyze picks the columns to analyze.",
+                        DEMO_PATH
+                    ),
+                    timestamp: Utc::now(),
+                });
+                let description = Some("Demo dataset with planted age markers".to_string());
+                self.start_upload(vec![DEMO_PATH.to_string()], description, ParseOptions::default(), false);
+            }
+            Err(e) => self.messages.push(ChatMessage {
+                role: MessageRole::System,
+                content: format!("Could not write demo data: {:#}", e),
+                timestamp: Utc::now(),
+            }),
+        }
+    }
+
+    /// Write the settings in the background; each write waits for the
+    /// previous one, so the file always ends up with the latest settings
+    fn save_settings(&mut self) {
+        let (storage, settings) = (self.settings_storage.clone(), self.settings.clone());
+        let previous = self.settings_save.take();
+        let tx = self.event_tx.clone();
+        self.settings_save = Some(tokio::spawn(async move {
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+            if let Err(e) = storage.save(&settings).await {
+                error!("Failed to save settings: {}", e);
+                if let Some(tx) = tx {
+                    let content = format!("Failed to save settings: {}", e);
+                    let _ = tx.send(AppEvent::WorkflowMessage(MessageRole::System, content)).await;
+                }
+            }
+        }));
+    }
+
+    /// Wait for settings writes still in flight; called before exiting
+    pub async fn finish(&mut self) {
+        if let Some(save) = self.settings_save.take() {
+            let _ = save.await;
+        }
+    }
+
+    /// Update config from settings and share it with the embedded server and
+    /// runs in progress
+    fn update_config_from_settings(&mut self) {
+        self.settings.apply_to(&mut self.config);
+        self.live_config.publish(self.config.clone());
     }
 
     /// Scroll to bottom of messages
@@ -1723,33 +4529,22 @@ Dataset has {} rows and {} columns. Ensure Ensembl IDs and age are primary varia
     }
 }
 
-fn infer_csv_metadata(bytes: &[u8], delimiter: u8) -> Result<(Vec<String>, usize), String> {
-    let mut rdr = csv::ReaderBuilder::new()
-        .delimiter(delimiter)
-        .has_headers(true)
-        .from_reader(bytes);
-
-    let headers = rdr
-        .headers()
-        .map_err(|e| e.to_string())?
-        .iter()
-        .map(|h| h.to_string())
-        .collect::<Vec<_>>();
-
-    let mut row_count = 0usize;
-    for record in rdr.records() {
-        record.map_err(|e| e.to_string())?;
-        row_count += 1;
+/// Message closing the literature review: the number of results and the
+/// best sources with their quality scores
+fn literature_summary(results: &[LiteratureResult]) -> String {
+    let mut summary = format!("Literature review complete. Sources: {}", results.len());
+    let sources = agents::LiteratureAgent::format_sources(results, SOURCES_SHOWN);
+    if !sources.is_empty() {
+        summary.push_str(&format!("\nTop sources by quality score:\n{}", sources));
     }
-    Ok((headers, row_count))
+    summary
 }
 
-fn validate_microarray_headers(headers: &[String]) -> Result<(), String> {
-    let lowered: Vec<String> = headers.iter().map(|h| h.to_lowercase()).collect();
-    let has_ensembl = lowered.iter().any(|h| h.contains("ensembl"));
-    let has_age = lowered.iter().any(|h| h == "age" || h.contains("age"));
-    if !has_ensembl || !has_age {
-        return Err("Dataset must include Ensembl ID and Age columns.".to_string());
-    }
-    Ok(())
+/// Sample QC section shown after the findings, when QC ran
+fn qc_section(analysis: &AnalysisArtifacts) -> String {
+    analysis
+        .sample_qc
+        .as_ref()
+        .map(|qc| format!("\n{}", qc.to_markdown()))
+        .unwrap_or_default()
 }
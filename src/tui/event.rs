@@ -20,6 +20,8 @@ pub enum AppAction {
     ToggleSettings,
     /// Toggle help view
     ToggleHelp,
+    /// Switch to the next reply mode (auto, answer, report, chat)
+    CycleReplyMode,
     /// Escape - close modals, cancel
     Escape,
     /// Scroll up one line
@@ -112,6 +114,7 @@ impl EventHandler {
             // View toggles
             (KeyModifiers::CONTROL, KeyCode::Char('s')) => Some(AppAction::ToggleSettings),
             (KeyModifiers::CONTROL, KeyCode::Char('h')) => Some(AppAction::ToggleHelp),
+            (KeyModifiers::CONTROL, KeyCode::Char('r')) => Some(AppAction::CycleReplyMode),
 
             // Navigation with modifiers
             (KeyModifiers::SHIFT, KeyCode::BackTab) => Some(AppAction::PrevField),
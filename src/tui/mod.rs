@@ -22,8 +22,11 @@
 //! └─────────────────────────────────────────────────────────────────┘
 //! ```
 
+pub mod analysis_setup;
 pub mod app;
 pub mod event;
+pub mod onboarding;
+pub mod paths;
 pub mod theme;
 pub mod ui;
 pub mod widgets;
@@ -66,21 +69,29 @@ pub fn restore_terminal(terminal: &mut Tui) -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Run the TUI application
-pub async fn run(config: crate::config::Config) -> anyhow::Result<()> {
+/// Run the TUI application. Datasets are kept in `dataset_registry`,
+/// changes published on `events` are shown and the effective config is
+/// published on `config`; in `--serve` mode all three are shared with the
+/// embedded server.
+pub async fn run(
+    config: crate::session::LiveConfig,
+    dataset_registry: crate::data_registry::DatasetRegistry,
+    events: crate::session::EventBus,
+) -> anyhow::Result<()> {
     info!("Starting TUI mode");
 
     // Initialize terminal
     let mut terminal = init_terminal()?;
 
     // Create application state
-    let mut app = App::new(config);
+    let mut app = App::new(config, dataset_registry, &events);
 
     // Create event handler
     let mut events = EventHandler::new(std::time::Duration::from_millis(100));
 
     // Main loop
     let result = run_app(&mut terminal, &mut app, &mut events).await;
+    app.finish().await;
 
     // Restore terminal
     if let Err(e) = restore_terminal(&mut terminal) {
@@ -0,0 +1,264 @@
+//! First-Run Setup
+//!
+//! Wizard shown on first launch, when no settings have been saved and no LLM
+//! key comes from the environment: pick a provider, paste its key and test
+//! the connection, optionally add a SerpAPI key, then optionally load demo
+//! data. The chat opens ready to run something instead of failing on the
+//! first question.
+
+use crate::config::Config;
+use crate::settings::{Provider, SettingsStorage};
+use crate::types::AppResult;
+
+/// LLM providers offered, with where to get a key
+pub const PROVIDERS: [(Provider, &str, &str); 5] = [
+    (Provider::OpenAI, "OpenAI", "https://platform.openai.com/api-keys"),
+    (Provider::Anthropic, "Anthropic", "https://console.anthropic.com/settings/keys"),
+    (Provider::Google, "Google AI", "https://aistudio.google.com/apikey"),
+    (Provider::OpenRouter, "OpenRouter", "https://openrouter.ai/keys"),
+    (Provider::Groq, "Groq Cloud", "https://console.groq.com/keys"),
+];
+
+/// Where to get a SerpAPI key
+pub const SERPAPI_URL: &str = "https://serpapi.com/manage-api-key";
+
+/// File the demo dataset is written to, as by `oxidized-bio demo-data`
+pub const DEMO_PATH: &str = "demo_data.csv";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnboardingStep {
+    Provider,
+    Key,
+    SerpApi,
+    Demo,
+}
+
+impl OnboardingStep {
+    pub const ALL: [OnboardingStep; 4] = [Self::Provider, Self::Key, Self::SerpApi, Self::Demo];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Provider => "Provider",
+            Self::Key => "API key",
+            Self::SerpApi => "SerpAPI",
+            Self::Demo => "Demo data",
+        }
+    }
+}
+
+/// Result of testing the key being entered
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum KeyTest {
+    #[default]
+    Untested,
+    Testing,
+    Passed(String),
+    Failed(String),
+}
+
+/// What Enter does next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OnboardingAction {
+    /// Nothing to do yet (no key typed, or a test is running)
+    None,
+    /// Test `key` against `provider` (a provider ID or `serpapi`)
+    Test { provider: &'static str, key: String },
+    /// Keep the LLM key and move on to SerpAPI
+    SaveKey { provider: Provider, key: String },
+    /// Keep the SerpAPI key, if one was entered, and move on to demo data
+    SaveSerpApi(Option<String>),
+    /// Close the wizard, loading demo data or not
+    Finish { demo: bool },
+}
+
+#[derive(Debug, Clone)]
+pub struct Onboarding {
+    pub step: OnboardingStep,
+    /// Highlighted row of [`PROVIDERS`]
+    pub cursor: usize,
+    /// Key being typed in the key and SerpAPI steps
+    pub input: String,
+    pub test: KeyTest,
+}
+
+impl Onboarding {
+    /// Whether to show the wizard: nothing saved yet and no LLM key set in
+    /// the environment or config file
+    pub fn needed(storage: &SettingsStorage, config: &Config) -> bool {
+        !storage.exists() && config.llm.active_api_key().is_none()
+    }
+
+    pub fn new() -> Self {
+        Self {
+            step: OnboardingStep::Provider,
+            cursor: 0,
+            input: String::new(),
+            test: KeyTest::Untested,
+        }
+    }
+
+    pub fn provider(&self) -> (Provider, &'static str, &'static str) {
+        PROVIDERS[self.cursor].clone()
+    }
+
+    pub fn move_cursor(&mut self, down: bool) {
+        if self.step == OnboardingStep::Provider {
+            let len = PROVIDERS.len();
+            self.cursor = if down { (self.cursor + 1) % len } else { (self.cursor + len - 1) % len };
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        if matches!(self.step, OnboardingStep::Key | OnboardingStep::SerpApi) && !c.is_whitespace() {
+            self.input.push(c);
+            self.test = KeyTest::Untested;
+        }
+    }
+
+    pub fn pop(&mut self) {
+        self.input.pop();
+        self.test = KeyTest::Untested;
+    }
+
+    /// Enter: what to do at the current step
+    pub fn submit(&mut self) -> OnboardingAction {
+        let key = self.input.trim().to_string();
+        match self.step {
+            OnboardingStep::Provider => {
+                self.step = OnboardingStep::Key;
+                OnboardingAction::None
+            }
+            OnboardingStep::Key | OnboardingStep::SerpApi if self.test == KeyTest::Testing => OnboardingAction::None,
+            OnboardingStep::Key if key.is_empty() => OnboardingAction::None,
+            OnboardingStep::Key => match self.test {
+                KeyTest::Passed(_) => self.keep(),
+                _ => self.start_test("", key),
+            },
+            OnboardingStep::SerpApi if key.is_empty() => self.keep(),
+            OnboardingStep::SerpApi => match self.test {
+                KeyTest::Passed(_) => self.keep(),
+                _ => self.start_test("serpapi", key),
+            },
+            OnboardingStep::Demo => OnboardingAction::Finish { demo: true },
+        }
+    }
+
+    /// Tab: keep the key without a passing test (e.g. offline), skip
+    /// SerpAPI, or skip the demo data
+    pub fn skip(&mut self) -> OnboardingAction {
+        match self.step {
+            OnboardingStep::Provider => OnboardingAction::None,
+            OnboardingStep::Key if self.input.trim().is_empty() => OnboardingAction::None,
+            OnboardingStep::Key => self.keep(),
+            OnboardingStep::SerpApi => {
+                self.input.clear();
+                self.keep()
+            }
+            OnboardingStep::Demo => OnboardingAction::Finish { demo: false },
+        }
+    }
+
+    /// Go back a step (Shift+Tab)
+    pub fn back(&mut self) {
+        self.step = match self.step {
+            OnboardingStep::Provider | OnboardingStep::Key => OnboardingStep::Provider,
+            OnboardingStep::SerpApi => OnboardingStep::Key,
+            OnboardingStep::Demo => OnboardingStep::SerpApi,
+        };
+        self.input.clear();
+        self.test = KeyTest::Untested;
+    }
+
+    /// Record the outcome of a test started by [`Self::submit`]
+    pub fn tested(&mut self, result: AppResult<String>) {
+        if self.test == KeyTest::Testing {
+            self.test = match result {
+                Ok(message) => KeyTest::Passed(message),
+                Err(error) => KeyTest::Failed(error.user_message()),
+            };
+        }
+    }
+
+    fn start_test(&mut self, provider: &'static str, key: String) -> OnboardingAction {
+        self.test = KeyTest::Testing;
+        let provider = if provider.is_empty() { id(self.provider().0) } else { provider };
+        OnboardingAction::Test { provider, key }
+    }
+
+    /// Keep the typed key and go to the next step
+    fn keep(&mut self) -> OnboardingAction {
+        let key = std::mem::take(&mut self.input).trim().to_string();
+        self.test = KeyTest::Untested;
+        match self.step {
+            OnboardingStep::Key => {
+                self.step = OnboardingStep::SerpApi;
+                OnboardingAction::SaveKey { provider: self.provider().0, key }
+            }
+            _ => {
+                self.step = OnboardingStep::Demo;
+                OnboardingAction::SaveSerpApi((!key.is_empty()).then_some(key))
+            }
+        }
+    }
+}
+
+impl Default for Onboarding {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Provider ID as used by the settings and connection tests
+fn id(provider: Provider) -> &'static str {
+    match provider {
+        Provider::OpenAI => "openai",
+        Provider::Anthropic => "anthropic",
+        Provider::Google => "google",
+        Provider::OpenRouter => "openrouter",
+        Provider::Groq => "groq",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AppError;
+
+    #[test]
+    fn test_wizard_steps() {
+        let mut wizard = Onboarding::new();
+        wizard.move_cursor(true);
+        wizard.move_cursor(true);
+        assert_eq!(wizard.submit(), OnboardingAction::None);
+        assert_eq!(wizard.step, OnboardingStep::Key);
+
+        // A key has to be typed and tested first
+        assert_eq!(wizard.submit(), OnboardingAction::None);
+        "AIza-test".chars().for_each(|c| wizard.push(c));
+        let test = OnboardingAction::Test { provider: "google", key: "AIza-test".to_string() };
+        assert_eq!(wizard.submit(), test);
+        assert_eq!(wizard.submit(), OnboardingAction::None);
+        wizard.tested(Err(AppError::Auth("Invalid API key".to_string())));
+        assert_eq!(
+            wizard.test,
+            KeyTest::Failed("Authentication error: Invalid API key (check the settings and API keys)".to_string())
+        );
+        assert_eq!(wizard.submit(), test);
+        wizard.tested(Ok("Google AI API key is valid".to_string()));
+        assert_eq!(
+            wizard.submit(),
+            OnboardingAction::SaveKey { provider: Provider::Google, key: "AIza-test".to_string() }
+        );
+
+        // SerpAPI is optional
+        assert_eq!(wizard.step, OnboardingStep::SerpApi);
+        assert_eq!(wizard.submit(), OnboardingAction::SaveSerpApi(None));
+        assert_eq!(wizard.step, OnboardingStep::Demo);
+        wizard.back();
+        "serp".chars().for_each(|c| wizard.push(c));
+        assert_eq!(wizard.skip(), OnboardingAction::SaveSerpApi(None));
+
+        assert_eq!(wizard.skip(), OnboardingAction::Finish { demo: false });
+        assert_eq!(wizard.submit(), OnboardingAction::Finish { demo: true });
+    }
+}
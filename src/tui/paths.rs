@@ -0,0 +1,162 @@
+//! Dropped File Paths
+//!
+//! Dragging files onto a terminal pastes their paths the way a shell would
+//! take them: quoted (`'My Data/a.csv'`, `"b.tsv"`), with spaces and other
+//! special characters backslash-escaped (`My\ Data/a.csv`) or, from some
+//! file managers, as `file://` URIs. Several files dropped at once arrive as
+//! one line separated by spaces, or one path per line.
+
+use std::path::PathBuf;
+
+/// Characters a backslash escapes outside quotes; before anything else (e.g.
+/// in `C:\Users`) a backslash is kept as it is
+const ESCAPED: &str = " \t\\'\"()&;$!#*?[]{}<>|`~";
+
+/// Split `input` into paths, honouring quotes and backslash escapes and
+/// decoding `file://` URIs
+pub fn split(input: &str) -> Vec<String> {
+    tokens(input).into_iter().map(|(path, _)| path).collect()
+}
+
+/// The files at the start of `input` and the rest of it, such as `/upload`
+/// options. The first path is taken even when it is not a file, so that the
+/// upload can say why.
+pub fn leading(input: &str) -> (Vec<String>, &str) {
+    let mut paths = Vec::new();
+    let mut rest = input.len();
+    for (i, (path, end)) in tokens(input).into_iter().enumerate() {
+        if i > 0 && !resolve(&path).is_file() {
+            break;
+        }
+        paths.push(path);
+        rest = end;
+    }
+    (paths, &input[rest..])
+}
+
+/// Paths in `input`, each with the byte offset it ends at
+fn tokens(input: &str) -> Vec<(String, usize)> {
+    let mut tokens = Vec::new();
+    let mut path = String::new();
+    // Whether a path was started, so that `''` still counts as one
+    let mut started = false;
+    let mut quote: Option<char> = None;
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, next)| *next);
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') if matches!(next, Some('"' | '\\')) => path.extend(chars.next().map(|(_, c)| c)),
+            (Some(_), c) => path.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                started = true;
+            }
+            (None, '\\') if next.is_some_and(|next| ESCAPED.contains(next)) => {
+                path.extend(chars.next().map(|(_, c)| c));
+                started = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if started {
+                    tokens.push((from_uri(std::mem::take(&mut path)), i));
+                    started = false;
+                }
+            }
+            (None, c) => {
+                path.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        tokens.push((from_uri(path), input.len()));
+    }
+    tokens.retain(|(path, _)| !path.is_empty());
+    tokens
+}
+
+/// The paths of a submission that is nothing but existing files, as pasted
+/// by dropping them on the terminal; `None` for anything else
+pub fn dropped(input: &str) -> Option<Vec<String>> {
+    let paths = split(input);
+    let all_files = !paths.is_empty() && paths.iter().all(|p| resolve(p).is_file());
+    all_files.then_some(paths)
+}
+
+/// `path` with `~` expanded, relative to the current directory
+pub fn resolve(path: &str) -> PathBuf {
+    let path = path.trim();
+    let expanded = match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().map_or_else(|| PathBuf::from(path), |home| home.join(rest)),
+        None if path == "~" => dirs::home_dir().unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    };
+    if expanded.is_absolute() {
+        return expanded;
+    }
+    std::env::current_dir().map_or(expanded.clone(), |dir| dir.join(&expanded))
+}
+
+/// `file:///home/a%20b.csv` as `/home/a b.csv`; other paths as they are
+fn from_uri(path: String) -> String {
+    let Some(rest) = path.strip_prefix("file://") else {
+        return path;
+    };
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let bytes = rest.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| rest.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_dropped_paths() {
+        let cases: [(&str, &[&str]); 7] = [
+            ("/data/a.csv", &["/data/a.csv"]),
+            ("'/My Data/a.csv' '/My Data/b.tsv' ", &["/My Data/a.csv", "/My Data/b.tsv"]),
+            (r"/My\ Data/it\'s\ \(1\).csv /data/b.csv", &["/My Data/it's (1).csv", "/data/b.csv"]),
+            ("\"/data/say \\\"hi\\\".csv\"\n/data/c.parquet", &["/data/say \"hi\".csv", "/data/c.parquet"]),
+            ("file:///home/me/My%20Data/a.csv file://localhost/b.vcf", &["/home/me/My Data/a.csv", "/b.vcf"]),
+            (r"C:\Users\me\a.csv", &[r"C:\Users\me\a.csv"]),
+            ("  ''  ", &[]),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(split(input), expected, "{}", input);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("cohort one.csv");
+        let b = dir.path().join("b.tsv");
+        std::fs::write(&a, "").unwrap();
+        std::fs::write(&b, "").unwrap();
+        let input = format!("'{}' {}", a.display(), b.display());
+        assert_eq!(dropped(&input), Some(vec![a.display().to_string(), b.display().to_string()]));
+        assert_eq!(dropped(&format!("{} and more", b.display())), None);
+        assert_eq!(dropped("what changes with age?"), None);
+
+        let command = format!("{} '{}' na=NA quote=' Second cohort", b.display(), a.display());
+        let (files, rest) = leading(&command);
+        assert_eq!(files, [b.display().to_string(), a.display().to_string()]);
+        assert_eq!(rest, " na=NA quote=' Second cohort");
+    }
+}
@@ -5,6 +5,7 @@
 use crate::tui::app::{App, ApiStatus, MessageRole, PipelineStage, View};
 use crate::tui::theme::{Icons, Theme};
 use crate::tui::widgets;
+use crate::utils::redact::redact;
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style},
@@ -39,6 +40,8 @@ pub fn render(frame: &mut Frame, app: &App) {
     match app.view {
         View::Settings => widgets::render_settings(frame, app),
         View::Help => render_help(frame),
+        View::AnalysisSetup => widgets::render_analysis_setup(frame, app),
+        View::Onboarding => widgets::render_onboarding(frame, app),
         View::Chat => {}
     }
 }
@@ -209,7 +212,7 @@ fn render_messages(frame: &mut Frame, area: Rect, app: &App) {
 }
 
 fn format_message_content(content: &str, available_width: usize) -> String {
-    format_markdown_tables(content, available_width)
+    format_markdown_tables(&redact(content), available_width)
 }
 
 fn format_markdown_tables(content: &str, available_width: usize) -> String {
@@ -537,7 +540,7 @@ fn render_input(frame: &mut Frame, area: Rect, app: &App) {
 
 /// Render the status bar
 fn render_status_bar(frame: &mut Frame, area: Rect, app: &App) {
-    let status = match &app.pipeline_stage {
+    let stage = match &app.pipeline_stage {
         PipelineStage::Idle => Span::styled("Ready", Theme::text_secondary()),
         PipelineStage::Planning => Span::styled("Planning research...", Theme::active()),
         PipelineStage::Literature {
@@ -550,12 +553,21 @@ fn render_status_bar(frame: &mut Frame, area: Rect, app: &App) {
         ),
         PipelineStage::Generating => Span::styled("Generating response...", Theme::active()),
         PipelineStage::Complete => Span::styled("Complete", Theme::complete()),
-        PipelineStage::Error(e) => Span::styled(format!("Error: {}", e), Theme::error()),
+        PipelineStage::Error(e) => Span::styled(format!("Error: {}", redact(e)), Theme::error()),
+    };
+    let status = match &app.upload {
+        Some(upload) => Span::styled(
+            format!("Copying {} ({}%) - Esc to cancel", upload.filename, upload.percent()),
+            Theme::active(),
+        ),
+        None => stage,
     };
 
     let shortcuts = vec![
         Span::styled(" [Enter]", Theme::shortcut_key()),
         Span::styled(" Send ", Theme::shortcut_desc()),
+        Span::styled("[Ctrl+R]", Theme::shortcut_key()),
+        Span::styled(" Mode ", Theme::shortcut_desc()),
         Span::styled("[Ctrl+S]", Theme::shortcut_key()),
         Span::styled(" Settings ", Theme::shortcut_desc()),
         Span::styled("[Ctrl+Q]", Theme::shortcut_key()),
@@ -572,10 +584,23 @@ fn render_status_bar(frame: &mut Frame, area: Rect, app: &App) {
 
     let mut parts = Vec::new();
     parts.push(status);
+    parts.push(Span::raw(" │ "));
+    parts.push(Span::styled(
+        format!("Mode: {}", app.reply_mode().map_or("auto", |m| m.id())),
+        Theme::text_secondary(),
+    ));
     if tps_value > 0.0 {
         parts.push(Span::raw(" │ "));
         parts.push(Span::styled(format!("tok/s~ {:.1}", tps_value), Theme::text_dim()));
     }
+    let (queued_jobs, running_jobs) = app.active_job_counts();
+    if queued_jobs + running_jobs > 0 {
+        parts.push(Span::raw(" │ "));
+        parts.push(Span::styled(
+            format!("Jobs: {} running, {} queued", running_jobs, queued_jobs),
+            Theme::active(),
+        ));
+    }
     parts.push(Span::raw(" │ "));
     parts.extend(shortcuts);
 
@@ -599,6 +624,10 @@ fn render_help(frame: &mut Frame) {
             Span::styled("Enter        ", Theme::shortcut_key()),
             Span::styled("Send message / Confirm", Theme::text()),
         ]),
+        Line::from(vec![
+            Span::styled("Ctrl+R       ", Theme::shortcut_key()),
+            Span::styled("Cycle reply mode (auto/answer/report/chat)", Theme::text()),
+        ]),
         Line::from(vec![
             Span::styled("Ctrl+S       ", Theme::shortcut_key()),
             Span::styled("Open settings", Theme::text()),
@@ -625,7 +654,7 @@ fn render_help(frame: &mut Frame) {
         ]),
         Line::from(vec![
             Span::styled("Esc          ", Theme::shortcut_key()),
-            Span::styled("Close modal / Cancel", Theme::text()),
+            Span::styled("Close modal / Cancel running task", Theme::text()),
         ]),
         Line::from(vec![
             Span::styled("F1 / ?       ", Theme::shortcut_key()),
@@ -0,0 +1,135 @@
+//! Analysis Setup Widget
+//!
+//! Modal listing the dataset's columns with checkboxes for the `/analyze`
+//! target, group, covariates and markers.
+
+use crate::tui::analysis_setup::{AnalysisSetup, SetupSection};
+use crate::tui::app::App;
+use crate::tui::theme::{Icons, Theme};
+use crate::tui::ui::centered_rect;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// Render the analysis setup modal
+pub fn render_analysis_setup(frame: &mut Frame, app: &App) {
+    let Some(setup) = &app.analysis_setup else {
+        return;
+    };
+    let area = centered_rect(70, 70, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" Analyze {} ", setup.filename))
+        .borders(Borders::ALL)
+        .border_style(Theme::border_focused());
+
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3), // Instructions and tabs
+            Constraint::Min(5),    // Column list
+            Constraint::Length(2), // Selection summary
+        ])
+        .split(inner);
+
+    render_tabs(frame, chunks[0], setup);
+    render_columns(frame, chunks[1], setup);
+    render_summary(frame, chunks[2], setup);
+}
+
+fn render_tabs(frame: &mut Frame, area: Rect, setup: &AnalysisSetup) {
+    let mut tabs = Vec::new();
+    for section in SetupSection::ALL {
+        let style = if section == setup.section { Theme::selected() } else { Theme::text_dim() };
+        tabs.push(Span::styled(format!(" {} ", section.title()), style));
+    }
+    let lines = vec![
+        Line::from(vec![
+            Span::styled("[Tab]", Theme::shortcut_key()),
+            Span::styled(" Section ", Theme::shortcut_desc()),
+            Span::styled("[↑↓]", Theme::shortcut_key()),
+            Span::styled(" Move ", Theme::shortcut_desc()),
+            Span::styled("[Space]", Theme::shortcut_key()),
+            Span::styled(" Toggle ", Theme::shortcut_desc()),
+            Span::styled("[Enter]", Theme::shortcut_key()),
+            Span::styled(" Run ", Theme::shortcut_desc()),
+            Span::styled("[Esc]", Theme::shortcut_key()),
+            Span::styled(" Close", Theme::shortcut_desc()),
+        ]),
+        Line::from(""),
+        Line::from(tabs),
+    ];
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
+fn render_columns(frame: &mut Frame, area: Rect, setup: &AnalysisSetup) {
+    let options = setup.options(setup.section);
+    if options.is_empty() {
+        let line = Line::from(Span::styled("No eligible columns in this dataset.", Theme::text_dim()));
+        frame.render_widget(Paragraph::new(line), area);
+        return;
+    }
+
+    // Keep the cursor in view on long column lists
+    let height = area.height as usize;
+    let first = setup.cursor.saturating_sub(height.saturating_sub(1));
+    let lines: Vec<Line> = options
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(height)
+        .map(|(i, column)| {
+            let is_selected = i == setup.cursor;
+            let checked = setup.is_checked(setup.section, &column.name);
+            let checkbox = match (setup.section.single(), checked) {
+                (true, true) => "(•)",
+                (true, false) => "( )",
+                (false, true) => "[x]",
+                (false, false) => "[ ]",
+            };
+            Line::from(vec![
+                Span::styled(
+                    format!("{} ", if is_selected { Icons::SELECTED } else { " " }),
+                    if is_selected { Theme::selected() } else { Theme::text_dim() },
+                ),
+                Span::styled(format!("{} ", checkbox), if checked { Theme::success() } else { Theme::text() }),
+                Span::styled(
+                    format!("{:<24}", column.name),
+                    if is_selected { Theme::selected() } else { Theme::text() },
+                ),
+                Span::styled(
+                    format!("{} | {:.0}% missing", column.kind, column.missing_rate * 100.0),
+                    Theme::text_dim(),
+                ),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
+fn render_summary(frame: &mut Frame, area: Rect, setup: &AnalysisSetup) {
+    let markers = if setup.markers.is_empty() {
+        "all numeric".to_string()
+    } else {
+        setup.markers.len().to_string()
+    };
+    let line = Line::from(Span::styled(
+        format!(
+            "Target: {} | Group: {} | Covariates: {} | Markers: {}",
+            setup.target.as_deref().unwrap_or("-"),
+            setup.group.as_deref().unwrap_or("-"),
+            setup.covariates.len(),
+            markers
+        ),
+        Theme::text_secondary(),
+    ));
+    frame.render_widget(Paragraph::new(line), area);
+}
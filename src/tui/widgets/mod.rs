@@ -2,8 +2,12 @@
 //!
 //! Custom widgets for the Oxidized Bio TUI.
 
+mod analysis_setup;
+mod onboarding;
 mod progress;
 mod settings;
 
+pub use analysis_setup::render_analysis_setup;
+pub use onboarding::render_onboarding;
 pub use progress::render_progress;
 pub use settings::render_settings;
@@ -0,0 +1,170 @@
+//! Onboarding Widget
+//!
+//! Modal of the first-run wizard: the steps along the top, the current
+//! step's choices or input, and its key bindings.
+
+use crate::tui::app::App;
+use crate::tui::onboarding::{KeyTest, Onboarding, OnboardingStep, DEMO_PATH, PROVIDERS, SERPAPI_URL};
+use crate::tui::theme::{Icons, Theme};
+use crate::tui::ui::centered_rect;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+/// Render the first-run wizard
+pub fn render_onboarding(frame: &mut Frame, app: &App) {
+    let Some(wizard) = &app.onboarding else {
+        return;
+    };
+    let area = centered_rect(70, 60, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Welcome to Oxidized Bio ")
+        .borders(Borders::ALL)
+        .border_style(Theme::border_focused());
+
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(2), // Steps
+            Constraint::Min(6),    // Current step
+            Constraint::Length(1), // Key bindings
+        ])
+        .split(inner);
+
+    render_steps(frame, chunks[0], wizard);
+    render_step(frame, chunks[1], wizard);
+    render_keys(frame, chunks[2], wizard);
+}
+
+fn render_steps(frame: &mut Frame, area: Rect, wizard: &Onboarding) {
+    let current = OnboardingStep::ALL.iter().position(|s| *s == wizard.step).unwrap_or(0);
+    let mut spans = Vec::new();
+    for (i, step) in OnboardingStep::ALL.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::styled(format!(" {} ", Icons::ARROW), Theme::text_dim()));
+        }
+        let (icon, style) = match i.cmp(&current) {
+            std::cmp::Ordering::Less => (Icons::COMPLETE, Theme::success()),
+            std::cmp::Ordering::Equal => (Icons::ACTIVE, Theme::selected()),
+            std::cmp::Ordering::Greater => (Icons::PENDING, Theme::text_dim()),
+        };
+        spans.push(Span::styled(format!("{} {}", icon, step.title()), style));
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+fn render_step(frame: &mut Frame, area: Rect, wizard: &Onboarding) {
+    let (_, name, key_url) = wizard.provider();
+    let mut lines = Vec::new();
+    match wizard.step {
+        OnboardingStep::Provider => {
+            lines.push(Line::from(Span::styled(
+                "Let's get you set up. Which LLM provider do you have a key for?",
+                Theme::text(),
+            )));
+            lines.push(Line::from(""));
+            for (i, (_, name, _)) in PROVIDERS.iter().enumerate() {
+                let selected = i == wizard.cursor;
+                lines.push(Line::from(vec![
+                    Span::raw(if selected { Icons::SELECTED } else { " " }),
+                    Span::raw(" "),
+                    Span::styled(*name, if selected { Theme::selected() } else { Theme::text() }),
+                ]));
+            }
+        }
+        OnboardingStep::Key => {
+            lines.push(Line::from(Span::styled(format!("Paste your {} API key.", name), Theme::text())));
+            lines.push(Line::from(Span::styled(format!("Get one at {}", key_url), Theme::text_dim())));
+            lines.push(Line::from(""));
+            lines.push(input_line(&wizard.input));
+            lines.push(Line::from(""));
+            lines.extend(test_line(&wizard.test));
+        }
+        OnboardingStep::SerpApi => {
+            lines.push(Line::from(Span::styled(
+                "Optional: a SerpAPI key adds Google Scholar to the literature search.",
+                Theme::text(),
+            )));
+            lines.push(Line::from(Span::styled(
+                format!("Get one at {}; without it PubMed and Semantic Scholar are used.", SERPAPI_URL),
+                Theme::text_dim(),
+            )));
+            lines.push(Line::from(""));
+            lines.push(input_line(&wizard.input));
+            lines.push(Line::from(""));
+            lines.extend(test_line(&wizard.test));
+        }
+        OnboardingStep::Demo => {
+            lines.push(Line::from(Span::styled(
+                "Load a demo dataset to try the analysis straight away?",
+                Theme::text(),
+            )));
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "Writes {} (200 samples, 500 genes, 10 markers planted to track age) and loads it.",
+                    DEMO_PATH
+                ),
+                Theme::text_dim(),
+            )));
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                "Your own data can be loaded any time by pasting its path in the chat.",
+                Theme::text_dim(),
+            )));
+        }
+    }
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), area);
+}
+
+/// The key typed so far, masked but for its last 4 characters
+fn input_line(input: &str) -> Line<'static> {
+    let shown = if input.is_empty() {
+        Span::styled("(type or paste the key)", Theme::text_dim())
+    } else {
+        let chars = input.chars().count();
+        let tail: String = input.chars().skip(chars.saturating_sub(4)).collect();
+        Span::styled(format!("{}{}", "•".repeat(chars.saturating_sub(4).min(32)), tail), Theme::text())
+    };
+    Line::from(vec![Span::styled("> ", Theme::shortcut_key()), shown, Span::styled("█", Theme::text_dim())])
+}
+
+fn test_line(test: &KeyTest) -> Option<Line<'static>> {
+    let (text, style) = match test {
+        KeyTest::Untested => return None,
+        KeyTest::Testing => ("Testing the connection...".to_string(), Theme::warning()),
+        KeyTest::Passed(message) => (format!("{} {}", Icons::COMPLETE, message), Theme::success()),
+        KeyTest::Failed(error) => (format!("{} {}", Icons::ERROR, error), Theme::error()),
+    };
+    Some(Line::from(Span::styled(text, style)))
+}
+
+fn render_keys(frame: &mut Frame, area: Rect, wizard: &Onboarding) {
+    let bindings: &[(&str, &str)] = match (wizard.step, &wizard.test) {
+        (OnboardingStep::Provider, _) => &[("[↑↓]", " Choose "), ("[Enter]", " Next ")],
+        (OnboardingStep::Key, KeyTest::Passed(_)) => &[("[Enter]", " Continue "), ("[Shift+Tab]", " Back ")],
+        (OnboardingStep::Key, KeyTest::Failed(_)) => {
+            &[("[Enter]", " Test again "), ("[Tab]", " Keep anyway "), ("[Shift+Tab]", " Back ")]
+        }
+        (OnboardingStep::Key, _) => &[("[Enter]", " Test "), ("[Tab]", " Keep untested "), ("[Shift+Tab]", " Back ")],
+        (OnboardingStep::SerpApi, KeyTest::Passed(_)) => &[("[Enter]", " Continue "), ("[Shift+Tab]", " Back ")],
+        (OnboardingStep::SerpApi, _) => &[("[Enter]", " Test / skip if empty "), ("[Tab]", " Skip "), ("[Shift+Tab]", " Back ")],
+        (OnboardingStep::Demo, _) => &[("[Enter]", " Load demo "), ("[Tab]", " Skip "), ("[Shift+Tab]", " Back ")],
+    };
+    let mut spans = Vec::new();
+    for (key, desc) in bindings {
+        spans.push(Span::styled(*key, Theme::shortcut_key()));
+        spans.push(Span::styled(*desc, Theme::shortcut_desc()));
+    }
+    spans.push(Span::styled("[Esc]", Theme::shortcut_key()));
+    spans.push(Span::styled(" Skip setup", Theme::shortcut_desc()));
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
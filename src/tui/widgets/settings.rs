@@ -2,6 +2,7 @@
 //!
 //! Modal dialog for configuring API keys and preferences.
 
+use crate::config::LlmTask;
 use crate::tui::app::App;
 use crate::tui::theme::{Icons, Theme};
 use crate::tui::ui::centered_rect;
@@ -33,13 +34,50 @@ pub fn render_settings(frame: &mut Frame, app: &App) {
         .constraints([
             Constraint::Length(3),  // Instructions
             Constraint::Min(10),    // Provider list
+            Constraint::Length(LlmTask::ALL.len() as u16 + 1), // Agent routing
             Constraint::Length(2),  // Footer
         ])
         .split(inner);
 
     render_instructions(frame, chunks[0]);
     render_provider_list(frame, chunks[1], app);
-    render_footer(frame, chunks[2], app);
+    render_routing(frame, chunks[2], app);
+    render_footer(frame, chunks[3], app);
+}
+
+/// Render the per-agent model/parameter overrides (edited with /route)
+fn render_routing(frame: &mut Frame, area: Rect, app: &App) {
+    let mut lines = vec![Line::from(vec![
+        Span::styled("Agent models", Theme::text()),
+        Span::styled("  /route <agent> <provider> [model] | temp=<t> max_tokens=<n>", Theme::text_dim()),
+    ])];
+
+    for task in LlmTask::ALL {
+        let detail = match app.settings.routing.get(&task) {
+            Some(route) => {
+                let mut detail = format!(
+                    "{} ({})",
+                    route.provider,
+                    route.model.as_deref().unwrap_or("provider default")
+                );
+                if let Some(t) = route.temperature {
+                    detail.push_str(&format!(" temp={}", t));
+                }
+                if let Some(m) = route.max_tokens {
+                    detail.push_str(&format!(" max_tokens={}", m));
+                }
+                detail
+            }
+            None => "default".to_string(),
+        };
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled(format!("{:<13}", task.as_str()), Theme::text()),
+            Span::styled(detail, Theme::text_dim()),
+        ]));
+    }
+
+    frame.render_widget(Paragraph::new(lines), area);
 }
 
 /// Render instructions
@@ -29,6 +29,47 @@ pub struct LLMRequest {
     pub max_tokens: Option<u32>,
     pub temperature: Option<f32>,
     pub system_instruction: Option<String>,
+    /// Further system prompt after `system_instruction`, e.g. long context
+    /// shared by successive requests
+    #[serde(default)]
+    pub system_blocks: Vec<SystemBlock>,
+}
+
+impl LLMRequest {
+    /// `system_instruction` and `system_blocks` as one system prompt, for
+    /// providers without system blocks
+    pub fn system_text(&self) -> Option<String> {
+        let parts: Vec<&str> = self
+            .system_instruction
+            .as_deref()
+            .into_iter()
+            .chain(self.system_blocks.iter().map(|b| b.text.as_str()))
+            .filter(|text| !text.is_empty())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join("\n\n"))
+    }
+}
+
+/// Part of the system prompt. With prompt caching (Anthropic) the prompt up
+/// to and including a `cache` block is stored for a few minutes, so later
+/// requests starting with the same prefix are billed a fraction of the input
+/// price for it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SystemBlock {
+    pub text: String,
+    #[serde(default)]
+    pub cache: bool,
+}
+
+impl SystemBlock {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into(), cache: false }
+    }
+
+    /// A block worth caching: long, and repeated across requests
+    pub fn cached(text: impl Into<String>) -> Self {
+        Self { text: text.into(), cache: true }
+    }
 }
 
 /// Content part for multimodal messages (text, images, etc.)
@@ -183,20 +224,67 @@ pub struct LLMResponse {
     pub usage: TokenUsage,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct TokenUsage {
+    /// Input tokens billed at the full price, i.e. excluding cache reads and writes
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    /// Input tokens written to the provider's prompt cache
+    #[serde(default)]
+    pub cache_write_tokens: u32,
+    /// Input tokens read from the provider's prompt cache
+    #[serde(default)]
+    pub cache_read_tokens: u32,
+}
+
+/// Broad kind of an [`AppError`], deciding the hint shown to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Missing or invalid settings, API keys or options
+    Config,
+    /// The connection to a service failed
+    Network,
+    /// An LLM or search provider rejected the request or answered badly
+    Provider,
+    /// The dataset or the analysis of it
+    Data,
+    /// A bug, the database or the local file system
+    Internal,
 }
 
+/// Crate-wide error. Modules with errors of their own (search, uploads)
+/// convert into it; `anyhow` errors are given a category where they are
+/// produced, with [`AppError::data`], [`AppError::io`] or
+/// [`AppError::config`].
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
 
-    #[error("LLM API error: {0}")]
-    LLMApi(String),
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Network error: {0}")]
+    Network(String),
+
+    #[error("LLM API error: {message}")]
+    Provider { message: String, retryable: bool },
+
+    #[error(transparent)]
+    Search(#[from] crate::search::SearchError),
+
+    #[error(transparent)]
+    Upload(#[from] crate::data_registry::UploadError),
+
+    /// The dataset, or the analysis options given for it
+    #[error("{0}")]
+    Data(String),
+
+    /// Reading or writing local files
+    #[error("File error: {0}")]
+    Io(String),
 
     #[error("Authentication error: {0}")]
     Auth(String),
@@ -209,6 +297,161 @@ pub enum AppError {
 
     #[error("Internal error: {0}")]
     Internal(String),
+
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+impl AppError {
+    pub fn provider(message: impl Into<String>, retryable: bool) -> Self {
+        Self::Provider { message: message.into(), retryable }
+    }
+
+    /// A step failed on the dataset or the options given for it
+    pub fn data(error: impl Into<anyhow::Error>) -> Self {
+        Self::categorized(error.into(), Self::Data)
+    }
+
+    /// Reading or writing a local file failed
+    pub fn io(error: impl Into<anyhow::Error>) -> Self {
+        Self::categorized(error.into(), Self::Io)
+    }
+
+    /// A download or request to a service failed
+    pub fn network(error: impl Into<anyhow::Error>) -> Self {
+        Self::categorized(error.into(), Self::Network)
+    }
+
+    /// A setting, or a file or option it names, is missing or invalid
+    pub fn config(error: impl Into<anyhow::Error>) -> Self {
+        Self::categorized(error.into(), Self::Config)
+    }
+
+    /// `error` with its context as `variant`, unless it is a cancellation
+    fn categorized(error: anyhow::Error, variant: fn(String) -> Self) -> Self {
+        if error.is::<crate::utils::cancel::Cancelled>() {
+            return Self::Cancelled;
+        }
+        variant(format!("{:#}", error))
+    }
+
+    /// The category, which picks the hint of [`Self::user_message`]
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::Config(_) | Self::Auth(_) => ErrorCategory::Config,
+            Self::Network(_) => ErrorCategory::Network,
+            Self::Provider { .. } => ErrorCategory::Provider,
+            Self::Search(e) => search_category(e),
+            Self::Upload(e) => upload_category(e),
+            Self::Data(_) | Self::NotFound(_) | Self::InvalidRequest(_) => ErrorCategory::Data,
+            Self::Database(_) | Self::Io(_) | Self::Internal(_) | Self::Cancelled => ErrorCategory::Internal,
+        }
+    }
+
+    /// Whether the same request may succeed later: connection failures,
+    /// rate limiting, overloaded services and open circuit breakers
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Network(_) => true,
+            Self::Provider { retryable, .. } => *retryable,
+            Self::Search(e) => search_retryable(e),
+            Self::Database(e) => matches!(e, sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut),
+            _ => false,
+        }
+    }
+
+    /// The error with a hint of what to do about it, for the TUI and API
+    /// clients
+    pub fn user_message(&self) -> String {
+        let hint = match self.category() {
+            ErrorCategory::Config => "check the settings and API keys",
+            ErrorCategory::Network => "check the network connection and try again",
+            ErrorCategory::Provider if self.is_retryable() => "the service is busy or unavailable; try again shortly",
+            ErrorCategory::Data => "check the dataset and the options given",
+            ErrorCategory::Provider | ErrorCategory::Internal => return self.to_string(),
+        };
+        format!("{} ({})", self, hint)
+    }
+}
+
+impl From<crate::utils::cancel::Cancelled> for AppError {
+    fn from(_: crate::utils::cancel::Cancelled) -> Self {
+        Self::Cancelled
+    }
+}
+
+fn search_category(error: &crate::search::SearchError) -> ErrorCategory {
+    use crate::search::SearchError;
+    match error {
+        SearchError::NoApiKey | SearchError::EngineDisabled(_) => ErrorCategory::Config,
+        SearchError::RequestFailed(_) | SearchError::Unavailable(_) => ErrorCategory::Network,
+        SearchError::NoResults => ErrorCategory::Data,
+        SearchError::Http { .. } | SearchError::ParseError(_) => ErrorCategory::Provider,
+    }
+}
+
+fn search_retryable(error: &crate::search::SearchError) -> bool {
+    error.is_transient() || matches!(error, crate::search::SearchError::Unavailable(_))
+}
+
+fn upload_category(error: &crate::data_registry::UploadError) -> ErrorCategory {
+    match error {
+        crate::data_registry::UploadError::Io(_) => ErrorCategory::Internal,
+        _ => ErrorCategory::Data,
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(error: reqwest::Error) -> Self {
+        match error.status() {
+            Some(status) => Self::provider(error.to_string(), status.as_u16() == 429 || status.is_server_error()),
+            None => Self::Network(error.to_string()),
+        }
+    }
 }
 
 pub type AppResult<T> = std::result::Result<T, AppError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchError;
+
+    #[test]
+    fn test_error_categories() {
+        let busy = AppError::provider("HTTP 529: Overloaded", true);
+        assert_eq!(busy.category(), ErrorCategory::Provider);
+        assert!(busy.is_retryable());
+        assert!(busy.user_message().ends_with("(the service is busy or unavailable; try again shortly)"));
+
+        let no_key = AppError::from(SearchError::NoApiKey);
+        assert_eq!((no_key.category(), no_key.is_retryable()), (ErrorCategory::Config, false));
+        let rate_limited = AppError::from(SearchError::Http { status: 429, body: String::new() });
+        assert!(rate_limited.is_retryable());
+
+        let search = AppError::from(SearchError::Unavailable(crate::utils::retry::CircuitOpen {
+            target: "serpapi".to_string(),
+            retry_in: std::time::Duration::from_secs(30),
+        }));
+        assert_eq!((search.category(), search.is_retryable()), (ErrorCategory::Network, true));
+
+        // anyhow errors take the category they are given, keeping their
+        // context, unless they are a cancellation
+        let cancelled = AppError::data(anyhow::Error::new(crate::utils::cancel::Cancelled));
+        assert!(matches!(cancelled, AppError::Cancelled));
+        assert_eq!(cancelled.user_message(), "Cancelled");
+        let missing = AppError::data(anyhow::anyhow!("Formula column(s) not found: bmi"));
+        assert_eq!(missing.category(), ErrorCategory::Data);
+        assert_eq!(
+            missing.user_message(),
+            "Formula column(s) not found: bmi (check the dataset and the options given)"
+        );
+        let unwritable = AppError::io(
+            anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::PermissionDenied)).context("Failed to create out"),
+        );
+        assert_eq!(unwritable.category(), ErrorCategory::Internal);
+        assert_eq!(unwritable.to_string(), "File error: Failed to create out: permission denied");
+        let gene_sets = AppError::config(anyhow::anyhow!("Gene set file not found: sets.gmt"));
+        assert_eq!((gene_sets.category(), gene_sets.is_retryable()), (ErrorCategory::Config, false));
+    }
+}
@@ -0,0 +1,28 @@
+//! Cooperative cancellation
+//!
+//! Agents and `run_analysis` take a [`CancellationToken`]. The TUI cancels
+//! it on Esc and HTTP routes when the client goes away. Async work is dropped
+//! at its next await, so in-flight LLM and search requests are abandoned and
+//! nothing more is charged to the budget; synchronous loops call [`check`]
+//! between chunks.
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Work stopped because its token was cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Cancelled")]
+pub struct Cancelled;
+
+/// Fail with [`Cancelled`] once `cancel` has fired
+pub fn check(cancel: &CancellationToken) -> Result<(), Cancelled> {
+    if cancel.is_cancelled() {
+        Err(Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether `error` is (or wraps) [`Cancelled`]
+pub fn is_cancelled(error: &anyhow::Error) -> bool {
+    error.is::<Cancelled>()
+}
@@ -0,0 +1,85 @@
+//! Shared HTTP client construction
+//!
+//! Every outbound client is built here so proxy and custom CA settings from
+//! [`NetworkConfig`] apply uniformly.
+
+use std::sync::OnceLock;
+
+use anyhow::Context;
+use tracing::warn;
+
+use crate::config::NetworkConfig;
+
+/// Build a client for requests to `target` (a provider id such as `openai`,
+/// or `serpapi`, `download`), honouring per-target proxy overrides.
+pub fn build_client(network: &NetworkConfig, target: &str) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder();
+
+    if let Some(proxy_url) = network.proxy_for(target) {
+        let mut proxy = reqwest::Proxy::all(proxy_url)
+            .with_context(|| format!("Invalid proxy URL for {}", target))?;
+        if let Some(no_proxy) = &network.no_proxy {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(no_proxy));
+        }
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &network.ca_cert_path {
+        let pem = std::fs::read(path).with_context(|| format!("Failed to read CA bundle {}", path))?;
+        for cert in reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid PEM in CA bundle {}", path))?
+        {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Like [`build_client`], but falls back to a default client (after logging)
+/// so a bad proxy setting degrades to a network error instead of a panic.
+pub fn client(network: &NetworkConfig, target: &str) -> reqwest::Client {
+    build_client(network, target).unwrap_or_else(|e| {
+        warn!(target, error = %e, "Ignoring invalid network settings");
+        reqwest::Client::new()
+    })
+}
+
+/// Client for code paths without access to a `Config`; uses the environment
+/// (`PROXY_URL`, `NO_PROXY`, `CA_CERT_PATH`).
+pub fn env_client() -> reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| client(&NetworkConfig::from_env(), "download"))
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_proxy_overrides_global() {
+        let mut network = NetworkConfig {
+            proxy_url: Some("http://proxy.example:3128".to_string()),
+            ..Default::default()
+        };
+        network
+            .provider_proxies
+            .insert("openai".to_string(), "socks5h://localhost:1080".to_string());
+
+        assert_eq!(network.proxy_for("openai"), Some("socks5h://localhost:1080"));
+        assert_eq!(network.proxy_for("serpapi"), Some("http://proxy.example:3128"));
+        assert_eq!(NetworkConfig::default().proxy_for("openai"), None);
+    }
+
+    #[test]
+    fn test_build_client_rejects_missing_ca() {
+        let network = NetworkConfig {
+            ca_cert_path: Some("/nonexistent/ca.pem".to_string()),
+            ..Default::default()
+        };
+        assert!(build_client(&network, "openai").is_err());
+        assert!(build_client(&NetworkConfig::default(), "openai").is_ok());
+    }
+}
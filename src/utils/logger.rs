@@ -1,9 +1,289 @@
-// Logger initialization
-// Note: Logging is already initialized in main.rs with tracing
+//! Logging setup and log file retention
+//!
+//! Headless commands, workers and `--verbose` runs log to stderr. The TUI
+//! owns the terminal, so it logs to one file per session,
+//! `<data dir>/oxidized-bio/logs/oxidized-bio-<timestamp>.log`. Once that
+//! file reaches `LOG_MAX_FILE_MB` its content moves to
+//! `oxidized-bio-<timestamp>.1.log` (then `.2.log`, ...) and logging goes on
+//! in a fresh file under the original name, so the path `/logs path` prints
+//! stays valid. On start-up and at each rollover, log files older than
+//! `LOG_MAX_AGE_DAYS` are deleted, then all but the newest `LOG_MAX_FILES`.
+//! Either way, secrets are masked ([`super::redact`]) before a line is written.
 
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
 use tracing::info;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use crate::config::LoggingConfig;
+use crate::utils::redact::redact;
+
+const PREFIX: &str = "oxidized-bio-";
+
+static CURRENT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Directory of the TUI's log files
+pub fn log_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("oxidized-bio")
+        .join("logs")
+}
+
+/// File the current session logs to, if it logs to a file
+pub fn current_log_path() -> Option<&'static Path> {
+    CURRENT.get().map(PathBuf::as_path)
+}
+
+/// Log filter: `level` (`--log-level`) wins over `RUST_LOG`, which wins over
+/// `default`. A bare level (`debug`) applies to this crate; anything else is
+/// used as a filter directive (`oxidized_bio=debug,sqlx=warn`).
+pub fn filter(level: Option<&str>, default: &str) -> Result<EnvFilter> {
+    let Some(level) = level.map(str::trim) else {
+        return Ok(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default)));
+    };
+    let directive = match level.to_ascii_lowercase().as_str() {
+        bare @ ("trace" | "debug" | "info" | "warn" | "error") => format!("oxidized_bio={}", bare),
+        _ => level.to_string(),
+    };
+    EnvFilter::try_new(&directive).with_context(|| format!("Invalid log level '{}'", level))
+}
+
+/// Log to stderr, keeping stdout for command output
+pub fn init_stderr(filter: EnvFilter) {
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(Redacting(io::stderr)))
+        .init();
+}
+
+/// Log to a new session file in [`log_dir`] and prune old ones. Returns the
+/// file's path, or `None` when it cannot be created; logging is then off,
+/// since writing to the terminal would corrupt the TUI.
+pub fn init_file(config: &LoggingConfig, filter: EnvFilter) -> Option<PathBuf> {
+    let dir = log_dir();
+    let path = dir.join(format!("{}{}.log", PREFIX, chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    let opened = fs::create_dir_all(&dir)
+        .and_then(|_| RotatingFile::create(path.clone(), config.max_file_mb.saturating_mul(1024 * 1024), config));
+    let Ok(file) = opened else {
+        tracing_subscriber::registry().with(EnvFilter::new("off")).init();
+        return None;
+    };
+    let _ = CURRENT.set(path.clone());
+    let removed = prune(&dir, config, &path);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(Redacting(Mutex::new(file)))
+                .with_ansi(false),
+        )
+        .init();
+    if removed > 0 {
+        info!(removed, dir = %dir.display(), "Deleted old log files");
+    }
+    Some(path)
+}
+
+/// A log file in [`log_dir`]
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Log files in `dir`, newest first
+pub fn log_files(dir: &Path) -> Vec<LogFile> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<LogFile> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with(PREFIX) && name.ends_with(".log")
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(LogFile {
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified().ok()?,
+            })
+        })
+        .collect();
+    files.sort_by_key(|f| std::cmp::Reverse(f.modified));
+    files
+}
+
+/// Delete log files in `dir` older than `max_age_days`, then all but the
+/// newest `max_files`, never touching `current`. Returns how many were
+/// deleted.
+pub fn prune(dir: &Path, config: &LoggingConfig, current: &Path) -> usize {
+    let max_age = (config.max_age_days > 0).then(|| Duration::from_secs(config.max_age_days * 86_400));
+    let now = SystemTime::now();
+    let mut kept = 1; // the current file
+    let mut removed = 0;
+    for file in log_files(dir).into_iter().filter(|f| f.path != current) {
+        let too_old = max_age.is_some_and(|age| now.duration_since(file.modified).unwrap_or_default() > age);
+        let too_many = config.max_files > 0 && kept >= config.max_files;
+        if !(too_old || too_many) {
+            kept += 1;
+        } else if fs::remove_file(&file.path).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Writer of formatted log lines that masks secrets in each line first
+struct Redacting<M>(M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = Redacting<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        Redacting(self.0.make_writer())
+    }
+}
+
+impl<W: Write> Write for Redacting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Events are formatted in full and written at once, so a secret is
+        // never split across writes
+        let text = String::from_utf8_lossy(buf);
+        match redact(&text) {
+            std::borrow::Cow::Owned(redacted) => self.0.write_all(redacted.as_bytes())?,
+            std::borrow::Cow::Borrowed(_) => self.0.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Session log file that rolls over at `max_bytes`
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    rolled: u32,
+    retention: LoggingConfig,
+}
+
+impl RotatingFile {
+    fn create(path: PathBuf, max_bytes: u64, retention: &LoggingConfig) -> io::Result<Self> {
+        Ok(Self {
+            file: File::create(&path)?,
+            path,
+            written: 0,
+            max_bytes,
+            rolled: 0,
+            retention: retention.clone(),
+        })
+    }
+
+    /// Move the content to the next numbered file and start over
+    fn roll_over(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.rolled += 1;
+        fs::rename(&self.path, self.path.with_extension(format!("{}.log", self.rolled)))?;
+        self.file = File::create(&self.path)?;
+        self.written = 0;
+        if let Some(dir) = self.path.parent() {
+            prune(dir, &self.retention, &self.path);
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.roll_over()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retention(max_age_days: u64, max_files: usize) -> LoggingConfig {
+        LoggingConfig {
+            max_file_mb: 1,
+            max_age_days,
+            max_files,
+        }
+    }
+
+    #[test]
+    fn test_rotating_file_rolls_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("oxidized-bio-20260101-120000.log");
+        let mut file = RotatingFile::create(path.clone(), 16, &retention(0, 0)).unwrap();
+        file.write_all(b"first line\n").unwrap();
+        file.write_all(b"second line\n").unwrap();
+        file.write_all(b"third line\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third line\n");
+        assert_eq!(fs::read_to_string(dir.path().join("oxidized-bio-20260101-120000.1.log")).unwrap(), "first line\n");
+        assert_eq!(fs::read_to_string(dir.path().join("oxidized-bio-20260101-120000.2.log")).unwrap(), "second line\n");
+    }
+
+    #[test]
+    fn test_prune_by_age_and_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        let day = Duration::from_secs(86_400);
+        for (name, age_days) in [("a", 30), ("b", 3), ("c", 2), ("d", 1)] {
+            let path = dir.path().join(format!("oxidized-bio-{}.log", name));
+            let file = File::create(&path).unwrap();
+            file.set_modified(now - day * age_days).unwrap();
+        }
+        fs::write(dir.path().join("notes.txt"), "not a log").unwrap();
+        let current = dir.path().join("oxidized-bio-now.log");
+        File::create(&current).unwrap();
+
+        // "a" is too old; of the rest, only the current file and the 2 newest fit
+        assert_eq!(prune(dir.path(), &retention(14, 3), &current), 2);
+        let mut left: Vec<String> = log_files(dir.path())
+            .into_iter()
+            .map(|f| f.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["oxidized-bio-c.log", "oxidized-bio-d.log", "oxidized-bio-now.log"]);
+        assert!(dir.path().join("notes.txt").exists());
+        assert_eq!(prune(dir.path(), &retention(0, 0), &current), 0);
+    }
 
-pub fn init_logger() {
-    // Logging is handled in main.rs
-    info!("Logger already initialized via tracing");
+    #[test]
+    fn test_log_level_filter() {
+        assert_eq!(filter(Some("debug"), "off").unwrap().to_string(), "oxidized_bio=debug");
+        assert_eq!(
+            filter(Some("oxidized_bio=trace,sqlx=warn"), "off").unwrap().to_string(),
+            "oxidized_bio=trace,sqlx=warn"
+        );
+        assert!(filter(Some("oxidized_bio=loud"), "off").is_err());
+    }
 }
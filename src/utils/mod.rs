@@ -1,6 +1,11 @@
 // Utility functions
 
+pub mod cancel;
+pub mod http;
 pub mod logger;
+pub mod number;
+pub mod partial_json;
+pub mod redact;
 pub mod retry;
 
 pub use logger::*;
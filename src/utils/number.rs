@@ -0,0 +1,192 @@
+//! Numbers as shown to readers
+//!
+//! Every p- and q-value in tables, summaries and drafts follows one rule:
+//! three decimals, and `<0.001` below that. [`NumberFormat`] sets the
+//! decimal separator and thousands grouping of the tables in manuscripts
+//! and reports; result files (CSV/TSV, `summary.json`) always use plain
+//! numbers so they stay machine-readable.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Smallest p-value shown as a number; smaller ones read `<0.001`
+pub const P_FLOOR: f64 = 0.001;
+
+/// Narrow no-break space, the French thousands separator
+const NARROW_SPACE: char = '\u{202f}';
+
+/// Decimal separator and thousands grouping of displayed numbers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// `1234.5`, as computed
+    #[default]
+    Plain,
+    /// `1,234.5`
+    En,
+    /// `1.234,5`
+    De,
+    /// `1 234,5`
+    Fr,
+    /// `1'234.5`
+    Ch,
+}
+
+impl NumberFormat {
+    pub const ALL: [Self; 5] = [Self::Plain, Self::En, Self::De, Self::Fr, Self::Ch];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::En => "en",
+            Self::De => "de",
+            Self::Fr => "fr",
+            Self::Ch => "ch",
+        }
+    }
+
+    pub fn decimal(self) -> char {
+        match self {
+            Self::De | Self::Fr => ',',
+            Self::Plain | Self::En | Self::Ch => '.',
+        }
+    }
+
+    /// Separator between groups of three integer digits
+    pub fn grouping(self) -> Option<char> {
+        match self {
+            Self::Plain => None,
+            Self::En => Some(','),
+            Self::De => Some('.'),
+            Self::Fr => Some(NARROW_SPACE),
+            Self::Ch => Some('\''),
+        }
+    }
+
+    /// `value` with `decimals` digits after the separator
+    pub fn fixed(self, value: f64, decimals: usize) -> String {
+        self.localize(&format!("{:.*}", decimals, value))
+    }
+
+    pub fn integer(self, value: usize) -> String {
+        self.localize(&value.to_string())
+    }
+
+    /// [`p_value`] in this format
+    pub fn p_value(self, p: f64) -> String {
+        self.localize(&p_value(p))
+    }
+
+    /// [`p_quote`] in this format
+    pub fn p_quote(self, name: &str, p: f64) -> String {
+        self.localize(&p_quote(name, p))
+    }
+
+    /// The plain numbers in `text` (digits with an optional `.` fraction)
+    /// rewritten in this format; only for formatted values, since labels
+    /// such as GO IDs would be grouped too
+    fn localize(self, text: &str) -> String {
+        if self == Self::Plain {
+            return text.to_string();
+        }
+        let mut out = String::with_capacity(text.len() + 4);
+        let mut chars = text.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if !c.is_ascii_digit() {
+                out.push(c);
+                continue;
+            }
+            let mut end = start + 1;
+            while let Some(&(i, d)) = chars.peek() {
+                if !(d.is_ascii_digit() || (d == '.' && text[i + 1..].starts_with(|n: char| n.is_ascii_digit()))) {
+                    break;
+                }
+                end = i + 1;
+                chars.next();
+            }
+            let (integer, fraction) = text[start..end].split_once('.').map_or((&text[start..end], None), |(i, f)| (i, Some(f)));
+            for (i, digit) in integer.chars().enumerate() {
+                if i > 0 && (integer.len() - i) % 3 == 0 {
+                    out.extend(self.grouping());
+                }
+                out.push(digit);
+            }
+            if let Some(fraction) = fraction {
+                out.push(self.decimal());
+                out.push_str(fraction);
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for NumberFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.trim().to_ascii_lowercase();
+        Self::ALL.into_iter().find(|f| f.id() == id).ok_or_else(|| {
+            let ids: Vec<&str> = Self::ALL.iter().map(|f| f.id()).collect();
+            format!("unknown number format '{}' (expected one of {})", s.trim(), ids.join(", "))
+        })
+    }
+}
+
+impl Serialize for NumberFormat {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+impl<'de> Deserialize<'de> for NumberFormat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A p- or q-value for a table cell: `0.012`, or `<0.001`
+pub fn p_value(p: f64) -> String {
+    if p < P_FLOOR {
+        format!("<{}", P_FLOOR)
+    } else {
+        format!("{:.3}", p)
+    }
+}
+
+/// A p- or q-value named `name` in prose: `q = 0.012`, or `q < 0.001`
+pub fn p_quote(name: &str, p: f64) -> String {
+    if p < P_FLOOR {
+        format!("{} < {}", name, P_FLOOR)
+    } else {
+        format!("{} = {:.3}", name, p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_formats() {
+        assert_eq!(p_value(0.0123), "0.012");
+        assert_eq!(p_value(2e-9), "<0.001");
+        assert_eq!(p_quote("q", 0.0004), "q < 0.001");
+        assert_eq!(p_quote("p", 0.05), "p = 0.050");
+
+        assert_eq!(NumberFormat::Plain.fixed(12345.678, 2), "12345.68");
+        assert_eq!(NumberFormat::En.fixed(12345.678, 2), "12,345.68");
+        assert_eq!(NumberFormat::De.fixed(-1234567.5, 1), "-1.234.567,5");
+        assert_eq!(NumberFormat::Fr.integer(1234), "1\u{202f}234");
+        assert_eq!(NumberFormat::De.p_value(0.0004), "<0,001");
+        assert_eq!(NumberFormat::Ch.fixed(0.5, 3), "0.500");
+        assert_eq!("DE".parse::<NumberFormat>(), Ok(NumberFormat::De));
+        assert!("xx".parse::<NumberFormat>().is_err());
+    }
+}
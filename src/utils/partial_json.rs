@@ -0,0 +1,206 @@
+//! Incremental JSON parsing of streamed LLM output
+//!
+//! Agents that ask the model for JSON receive it a chunk at a time, and a
+//! response cut off by the token limit never gets its closing braces.
+//! [`PartialJson`] keeps the text so far and reads it as the value it would
+//! be if it ended here: an unfinished string value is closed, a dangling key,
+//! colon or comma dropped, and open arrays and objects closed. Markdown fences
+//! and prose around the JSON are skipped.
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, Default)]
+pub struct PartialJson {
+    text: String,
+}
+
+impl PartialJson {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, chunk: &str) {
+        self.text.push_str(chunk);
+    }
+
+    /// Everything received so far
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether the JSON value has been closed
+    pub fn is_complete(&self) -> bool {
+        scan(body(&self.text)).complete.is_some()
+    }
+
+    /// The value so far, unfinished parts closed; `None` before the opening
+    /// brace or bracket, or when the text is not JSON
+    pub fn value(&self) -> Option<Value> {
+        let body = body(&self.text);
+        let scan = scan(body);
+        if let Some(end) = scan.complete {
+            return serde_json::from_str(&body[..end]).ok();
+        }
+        // Up to the end, closing a string value left open ...
+        if scan.in_string.is_none_or(|key| !key) {
+            let mut text = body.trim_end().to_string();
+            if scan.in_string.is_some() {
+                if scan.escaped {
+                    text.pop();
+                }
+                text.push('"');
+            }
+            if let Ok(value) = serde_json::from_str(&closed(text, &scan.stack)) {
+                return Some(value);
+            }
+        }
+        // ... else up to the last complete value
+        let (end, stack) = &scan.safe;
+        serde_json::from_str(&closed(body[..*end].to_string(), stack)).ok()
+    }
+
+    /// Elements of the array under `key` (of the top-level object) that will
+    /// not change any more: all of them once the value is complete, else all
+    /// but the last
+    pub fn finished_items(&self, key: &str) -> Vec<Value> {
+        let Some(Value::Object(mut object)) = self.value() else {
+            return Vec::new();
+        };
+        let Some(Value::Array(mut items)) = object.remove(key) else {
+            return Vec::new();
+        };
+        if !self.is_complete() {
+            items.pop();
+        }
+        items
+    }
+}
+
+impl From<&str> for PartialJson {
+    fn from(text: &str) -> Self {
+        Self { text: text.to_string() }
+    }
+}
+
+/// The JSON part of a response: inside a markdown fence if there is one,
+/// from the first brace or bracket on
+fn body(text: &str) -> &str {
+    let text = match text.split_once("```") {
+        Some((_, fenced)) => {
+            let fenced = fenced.strip_prefix("json").unwrap_or(fenced);
+            fenced.split("```").next().unwrap_or(fenced)
+        }
+        None => text,
+    };
+    text.find(['{', '[']).map_or("", |start| &text[start..])
+}
+
+struct Scan {
+    /// End of the first value, when it is closed
+    complete: Option<usize>,
+    /// Containers open at the end
+    stack: Vec<u8>,
+    /// Whether the text ends inside a string, and if so whether it is a key
+    in_string: Option<bool>,
+    /// The text ends on a backslash inside a string
+    escaped: bool,
+    /// End of the longest prefix that ends on a complete value or an opening
+    /// bracket, with the containers open there
+    safe: (usize, Vec<u8>),
+}
+
+fn scan(body: &str) -> Scan {
+    let mut scan = Scan {
+        complete: None,
+        stack: Vec::new(),
+        in_string: None,
+        escaped: false,
+        safe: (0, Vec::new()),
+    };
+    // Last structural byte outside strings, to tell keys from values
+    let mut last = 0u8;
+    for (i, &b) in body.as_bytes().iter().enumerate() {
+        if let Some(key) = scan.in_string {
+            if scan.escaped {
+                scan.escaped = false;
+            } else if b == b'\\' {
+                scan.escaped = true;
+            } else if b == b'"' {
+                scan.in_string = None;
+                if !key {
+                    scan.safe = (i + 1, scan.stack.clone());
+                }
+                last = b;
+            }
+            continue;
+        }
+        match b {
+            b'"' => {
+                let key = scan.stack.last() == Some(&b'{') && matches!(last, b'{' | b',');
+                scan.in_string = Some(key);
+            }
+            b'{' | b'[' => {
+                scan.stack.push(b);
+                scan.safe = (i + 1, scan.stack.clone());
+            }
+            b'}' | b']' => {
+                scan.stack.pop();
+                if scan.stack.is_empty() {
+                    scan.complete = Some(i + 1);
+                    break;
+                }
+                scan.safe = (i + 1, scan.stack.clone());
+            }
+            b',' => scan.safe = (i, scan.stack.clone()),
+            _ if b.is_ascii_whitespace() => continue,
+            _ => {}
+        }
+        last = b;
+    }
+    scan
+}
+
+/// `text` with the containers in `stack` closed
+fn closed(mut text: String, stack: &[u8]) -> String {
+    text.extend(stack.iter().rev().map(|&b| if b == b'{' { '}' } else { ']' }));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_prefixes_read_as_closed_values() {
+        let full = r#"```json
+{"currentObjective": "Map aging \"clocks\"", "plan": [{"objective": "a", "type": "LITERATURE"}, {"objective": "b", "type": "ANALYSIS"}], "n": 12}
+```"#;
+        let cases = [
+            (r#"{"currentObjective": "Map ag"#, json!({"currentObjective": "Map ag"})),
+            (r#"{"currentObjective": "Map aging \"#, json!({"currentObjective": "Map aging "})),
+            (r#"{"currentObjective": "x", "pl"#, json!({"currentObjective": "x"})),
+            (r#"{"currentObjective": "x", "plan": [{"objective"#, json!({"currentObjective": "x", "plan": [{}]})),
+            (r#"{"currentObjective": "x", "plan": [{"objective": "a"},"#, json!({"currentObjective": "x", "plan": [{"objective": "a"}]})),
+            (r#"{"n": tr"#, json!({})),
+        ];
+        for (prefix, expected) in cases {
+            assert_eq!(PartialJson::from(prefix).value(), Some(expected), "{}", prefix);
+        }
+
+        let mut partial = PartialJson::new();
+        let mut seen = Vec::new();
+        for chunk in full.as_bytes().chunks(7) {
+            partial.push(std::str::from_utf8(chunk).unwrap());
+            seen.push(partial.finished_items("plan").len());
+        }
+        assert!(partial.is_complete());
+        assert_eq!(partial.value().unwrap()["n"], 12);
+        // Tasks are reported one at a time, and never taken back
+        assert!(seen.windows(2).all(|w| w[0] <= w[1]));
+        assert!(seen.contains(&1));
+        assert_eq!(partial.finished_items("plan").len(), 2);
+
+        assert_eq!(PartialJson::from("Sure, here it is:").value(), None);
+    }
+}
@@ -1,31 +1,458 @@
-// Retry utilities
-// TODO: Implement retry with exponential backoff
-
-use std::time::Duration;
-use tokio::time::sleep;
-
-pub async fn with_retry<F, T, E>(
-    mut operation: F,
-    max_retries: u32,
-) -> Result<T, E>
-where
-    F: FnMut() -> futures::future::BoxFuture<'static, Result<T, E>>,
-    E: std::fmt::Display,
-{
-    let mut attempt = 0;
-
-    loop {
-        match operation().await {
-            Ok(result) => return Ok(result),
-            Err(error) => {
-                attempt += 1;
-                if attempt >= max_retries {
-                    return Err(error);
+//! Retry with exponential backoff and circuit breaking
+//!
+//! A [`RetryPolicy`] retries transient failures of an async operation with
+//! exponentially growing, jittered delays, gives up after `max_attempts` or
+//! once the next delay would run past its deadline, and optionally reports to
+//! a [`CircuitBreaker`]. Breakers are shared per target (`openai`, `serpapi`,
+//! `s3`, the same ids [`crate::utils::http::client`] takes), so after repeated
+//! failures every caller of a service fails fast until its cooldown has
+//! passed instead of each one waiting out its own retries.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use tracing::warn;
+
+/// Consecutive failed calls that open a breaker
+const BREAKER_THRESHOLD: u32 = 5;
+/// How long an open breaker rejects calls before letting one through
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// How often and how long to retry an operation
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for each subsequent retry
+    pub base_delay: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_delay: Duration,
+    /// Fraction of each delay that is randomized (0 = none, 1 = full jitter)
+    pub jitter: f64,
+    /// No retry is started once this much time has passed since the first
+    /// attempt. An attempt in flight is not cut short.
+    pub deadline: Option<Duration>,
+    breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+            deadline: Some(Duration::from_secs(120)),
+            breaker: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Default policy reporting to the shared breaker of `target`
+    pub fn for_target(target: &str) -> Self {
+        Self::default().with_circuit_breaker(CircuitBreaker::shared(target))
+    }
+
+    /// Run the operation once, without retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.breaker.as_deref()
+    }
+
+    /// Backoff after the given (1-based) failed attempt, before jitter
+    pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(20);
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_delay)
+    }
+
+    /// `delay` shortened by up to `jitter` of its length; `sample` is uniform
+    /// in `[0, 1)`
+    fn jittered(&self, delay: Duration, sample: f64) -> Duration {
+        delay.mul_f64(1.0 - self.jitter * sample)
+    }
+
+    /// Run `operation` until it succeeds, fails with an error `is_transient`
+    /// rejects, or the attempts or deadline run out; the last error is
+    /// returned. With a circuit breaker, calls are rejected while it is open
+    /// and the outcome of each call (not each attempt) is reported to it.
+    pub async fn run<T, E, F, Fut>(
+        &self,
+        mut operation: F,
+        is_transient: impl Fn(&E) -> bool,
+    ) -> Result<T, RetryError<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: fmt::Display,
+    {
+        if let Some(breaker) = &self.breaker {
+            breaker.check().map_err(RetryError::Open)?;
+        }
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match operation().await {
+                Ok(value) => {
+                    if let Some(breaker) = &self.breaker {
+                        breaker.record_success();
+                    }
+                    return Ok(value);
+                }
+                Err(error) => error,
+            };
+            if !is_transient(&error) {
+                // The service answered; the request itself was at fault
+                if let Some(breaker) = &self.breaker {
+                    breaker.record_success();
                 }
+                return Err(RetryError::Failed(error));
+            }
 
-                let delay = Duration::from_secs(2u64.pow(attempt.min(5)));
-                sleep(delay).await;
+            let delay = self.jittered(self.delay_for_attempt(attempt), rand::thread_rng().gen());
+            let out_of_time = self
+                .deadline
+                .is_some_and(|deadline| started.elapsed() + delay > deadline);
+            if attempt >= self.max_attempts || out_of_time {
+                if let Some(breaker) = &self.breaker {
+                    breaker.record_failure();
+                }
+                return Err(RetryError::Failed(error));
             }
+            warn!(
+                target = self.breaker.as_ref().map_or("operation", |b| b.target()),
+                attempt,
+                max_attempts = self.max_attempts,
+                delay_ms = delay.as_millis() as u64,
+                error = %error,
+                "Transient failure, retrying"
+            );
+            tokio::time::sleep(delay).await;
         }
     }
 }
+
+/// Failure of [`RetryPolicy::run`]
+#[derive(Debug)]
+pub enum RetryError<E> {
+    /// The circuit breaker rejected the call without running it
+    Open(CircuitOpen),
+    /// The last error of the operation
+    Failed(E),
+}
+
+impl<E> RetryError<E> {
+    /// Whether a later call may succeed: the breaker closes again, and
+    /// transient errors only ran out of attempts
+    pub fn is_retryable(&self, is_transient: impl Fn(&E) -> bool) -> bool {
+        match self {
+            Self::Open(_) => true,
+            Self::Failed(error) => is_transient(error),
+        }
+    }
+
+    /// The operation's error type, converting a rejected call into it
+    pub fn into_error(self) -> E
+    where
+        E: From<CircuitOpen>,
+    {
+        match self {
+            Self::Open(open) => open.into(),
+            Self::Failed(error) => error,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Open(open) => open.fmt(f),
+            Self::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for RetryError<E> {}
+
+/// A call rejected by an open circuit breaker
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{target} is unavailable after repeated failures; retrying in {}s", retry_in.as_secs().max(1))]
+pub struct CircuitOpen {
+    pub target: String,
+    pub retry_in: Duration,
+}
+
+/// Stops calls to a failing service for a while
+///
+/// After `threshold` consecutive failed calls the breaker opens and rejects
+/// calls for `cooldown`. Then a single trial call is let through: success
+/// closes the breaker, failure opens it for another cooldown. A trial that
+/// has not finished after a further cooldown, typically because its future
+/// was dropped when a client disconnected or a run was cancelled, no longer
+/// holds the breaker open and another trial is let through.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    target: String,
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    opened_at: Option<Instant>,
+    /// When the trial call after the cooldown was let through
+    probing: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(target: impl Into<String>, threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            target: target.into(),
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Process-wide breaker of `target`, created on first use
+    pub fn shared(target: &str) -> Arc<Self> {
+        static BREAKERS: OnceLock<Mutex<HashMap<String, Arc<CircuitBreaker>>>> = OnceLock::new();
+        let mut breakers = BREAKERS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        breakers
+            .entry(target.to_string())
+            .or_insert_with(|| Arc::new(Self::new(target, BREAKER_THRESHOLD, BREAKER_COOLDOWN)))
+            .clone()
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether the breaker currently rejects calls
+    pub fn is_open(&self) -> bool {
+        let state = self.state();
+        state.opened_at.is_some_and(|at| self.probe_pending(&state) || at.elapsed() < self.cooldown)
+    }
+
+    /// Admit a call, or reject it while the breaker is open
+    pub fn check(&self) -> Result<(), CircuitOpen> {
+        let mut state = self.state();
+        let Some(opened_at) = state.opened_at else {
+            return Ok(());
+        };
+        let elapsed = opened_at.elapsed();
+        if elapsed >= self.cooldown && !self.probe_pending(&state) {
+            state.probing = Some(Instant::now());
+            return Ok(());
+        }
+        Err(CircuitOpen {
+            target: self.target.clone(),
+            retry_in: self.cooldown.saturating_sub(elapsed),
+        })
+    }
+
+    /// A trial call is in flight and has not yet been given up on
+    fn probe_pending(&self, state: &BreakerState) -> bool {
+        state.probing.is_some_and(|at| at.elapsed() < self.cooldown)
+    }
+
+    pub fn record_success(&self) {
+        *self.state() = BreakerState::default();
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state();
+        state.failures += 1;
+        if state.probing.is_some() || state.failures >= self.threshold {
+            if state.opened_at.is_none() || state.probing.is_some() {
+                warn!(target = %self.target, failures = state.failures, "Circuit breaker opened");
+            }
+            state.opened_at = Some(Instant::now());
+            state.probing = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy::default()
+            .with_backoff(Duration::from_millis(1), Duration::from_millis(4))
+            .with_jitter(0.0)
+    }
+
+    #[test]
+    fn test_backoff_and_jitter() {
+        let policy = RetryPolicy::default().with_backoff(Duration::from_millis(100), Duration::from_millis(500));
+        assert_eq!(policy.delay_for_attempt(1), Duration::from_millis(100));
+        assert_eq!(policy.delay_for_attempt(3), Duration::from_millis(400));
+        assert_eq!(policy.delay_for_attempt(10), Duration::from_millis(500));
+
+        let policy = policy.with_jitter(0.5);
+        let delay = Duration::from_millis(400);
+        assert_eq!(policy.jittered(delay, 0.0), delay);
+        assert_eq!(policy.jittered(delay, 0.5), Duration::from_millis(300));
+        assert!(policy.jittered(delay, 0.999) > Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_run_retries_transient_errors_only() {
+        let calls = AtomicU32::new(0);
+        let result = fast()
+            .run(
+                || async {
+                    match calls.fetch_add(1, Ordering::SeqCst) {
+                        0 | 1 => Err("timeout"),
+                        n => Ok(n),
+                    }
+                },
+                |_| true,
+            )
+            .await;
+        assert_eq!(result.unwrap(), 2);
+
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), _> = fast()
+            .run(
+                || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err("bad request")
+                },
+                |e| *e != "bad request",
+            )
+            .await;
+        assert!(matches!(result, Err(RetryError::Failed("bad request"))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), _> = fast()
+            .with_max_attempts(4)
+            .run(
+                || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err("timeout")
+                },
+                |_| true,
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_at_deadline() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = RetryPolicy::default()
+            .with_backoff(Duration::from_secs(5), Duration::from_secs(5))
+            .with_max_attempts(10)
+            .with_deadline(Some(Duration::from_secs(1)))
+            .run(
+                || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err("timeout")
+                },
+                |_| true,
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_and_recovers() {
+        let breaker = Arc::new(CircuitBreaker::new("test", 2, Duration::from_millis(20)));
+        let policy = fast().with_max_attempts(1).with_circuit_breaker(breaker.clone());
+        let calls = AtomicU32::new(0);
+        let failing = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>("unavailable")
+        };
+
+        assert!(policy.run(failing, |_| true).await.is_err());
+        assert!(!breaker.is_open());
+        assert!(policy.run(failing, |_| true).await.is_err());
+        assert!(breaker.is_open());
+
+        let rejected = policy.run(failing, |_| true).await;
+        assert!(matches!(rejected, Err(RetryError::Open(ref open)) if open.target == "test"));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // One trial call after the cooldown; its failure reopens the breaker
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        assert!(policy.run(failing, |_| true).await.is_err());
+        assert!(matches!(policy.run(failing, |_| true).await, Err(RetryError::Open(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        assert_eq!(policy.run(|| async { Ok::<_, &str>(7) }, |_| true).await.unwrap(), 7);
+        assert!(!breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_survives_dropped_trial() {
+        let breaker = Arc::new(CircuitBreaker::new("dropped", 1, Duration::from_millis(20)));
+        let policy = fast().with_max_attempts(1).with_circuit_breaker(breaker.clone());
+        assert!(policy.run(|| async { Err::<(), _>("unavailable") }, |_| true).await.is_err());
+        tokio::time::sleep(Duration::from_millis(25)).await;
+
+        // The trial call is dropped mid-flight, as on a client disconnect
+        let trial = policy.run(std::future::pending::<Result<(), &str>>, |_| true);
+        assert!(tokio::time::timeout(Duration::from_millis(1), trial).await.is_err());
+        assert!(matches!(policy.run(|| async { Ok::<_, &str>(1) }, |_| true).await, Err(RetryError::Open(_))));
+
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        assert_eq!(policy.run(|| async { Ok::<_, &str>(2) }, |_| true).await.unwrap(), 2);
+        assert!(!breaker.is_open());
+    }
+}